@@ -1130,6 +1130,7 @@ impl<B: Backend> ImageState<B> {
                         ..(i::Access::TRANSFER_WRITE, i::Layout::TransferDstOptimal),
                     target: &image,
                     range: COLOR_RANGE.clone(),
+                    families: None,
                 };
 
                 cmd_buffer.pipeline_barrier(
@@ -1165,6 +1166,7 @@ impl<B: Backend> ImageState<B> {
                         ..(i::Access::SHADER_READ, i::Layout::ShaderReadOnlyOptimal),
                     target: &image,
                     range: COLOR_RANGE.clone(),
+                    families: None,
                 };
                 cmd_buffer.pipeline_barrier(
                     PipelineStage::TRANSFER..PipelineStage::FRAGMENT_SHADER,
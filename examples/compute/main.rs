@@ -137,7 +137,8 @@ fn main() {
             memory::Dependencies::empty(),
             Some(memory::Barrier::Buffer {
                 states: buffer::Access::TRANSFER_WRITE .. buffer::Access::SHADER_READ | buffer::Access::SHADER_WRITE,
-                target: &device_buffer
+                target: &device_buffer,
+                families: None,
             }),
         );
         command_buffer.bind_compute_pipeline(&pipeline);
@@ -148,7 +149,8 @@ fn main() {
             memory::Dependencies::empty(),
             Some(memory::Barrier::Buffer {
                 states: buffer::Access::SHADER_READ | buffer::Access::SHADER_WRITE .. buffer::Access::TRANSFER_READ,
-                target: &device_buffer
+                target: &device_buffer,
+                families: None,
             }),
         );
         command_buffer.copy_buffer(&device_buffer, &staging_buffer, &[command::BufferCopy { src: 0, dst: 0, size: stride * numbers.len() as u64}]);
@@ -288,6 +288,7 @@ fn main() {
                     ..(i::Access::TRANSFER_WRITE, i::Layout::TransferDstOptimal),
                 target: &image_logo,
                 range: COLOR_RANGE.clone(),
+                families: None,
             };
 
             cmd_buffer.pipeline_barrier(
@@ -323,6 +324,7 @@ fn main() {
                     ..(i::Access::SHADER_READ, i::Layout::ShaderReadOnlyOptimal),
                 target: &image_logo,
                 range: COLOR_RANGE.clone(),
+                families: None,
             };
             cmd_buffer.pipeline_barrier(
                 PipelineStage::TRANSFER..PipelineStage::FRAGMENT_SHADER,
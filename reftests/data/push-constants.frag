@@ -0,0 +1,12 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform PushConstants {
+    uvec4 color;
+} pc;
+
+layout(location = 0) out vec4 o_Color;
+
+void main() {
+    o_Color = vec4(pc.color) / 255.0;
+}
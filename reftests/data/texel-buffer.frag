@@ -0,0 +1,10 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform samplerBuffer u_Texels;
+
+layout(location = 0) out vec4 o_Color;
+
+void main() {
+    o_Color = texelFetch(u_Texels, 1);
+}
@@ -0,0 +1,12 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform Block {
+    vec4 color;
+} u_Block;
+
+layout(location = 0) out vec4 o_Color;
+
+void main() {
+    o_Color = u_Block.color;
+}
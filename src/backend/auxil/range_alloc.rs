@@ -141,6 +141,17 @@ where
             .chain(last)
     }
 
+    /// Returns the length of the largest unused range, if any.
+    pub fn largest_free_range(&self) -> Option<T> {
+        self.free_ranges
+            .iter()
+            .map(|range| range.end - range.start)
+            .fold(None, |largest, length| match largest {
+                Some(largest) if largest >= length => Some(largest),
+                _ => Some(length),
+            })
+    }
+
     pub fn reset(&mut self) {
         self.free_ranges.clear();
         self.free_ranges.push(self.initial_range.clone());
@@ -264,4 +275,17 @@ mod tests {
         alloc.free_range(3..6);
         assert_eq!(alloc.free_ranges, vec![0..9]);
     }
+
+    #[test]
+    fn test_largest_free_range() {
+        let mut alloc = RangeAllocator::new(0..10);
+        assert_eq!(alloc.largest_free_range(), Some(10));
+        assert_eq!(alloc.allocate_range(2), Ok(0..2));
+        assert_eq!(alloc.allocate_range(3), Ok(2..5));
+        assert_eq!(alloc.allocate_range(5), Ok(5..10));
+        assert_eq!(alloc.largest_free_range(), None);
+        alloc.free_range(0..2);
+        alloc.free_range(5..10);
+        assert_eq!(alloc.largest_free_range(), Some(5));
+    }
 }
@@ -0,0 +1,734 @@
+use std::borrow::Borrow;
+use std::ops::Range;
+use std::sync::Arc;
+
+use hal::{self, buffer, command as com, image, memory, pass, pool, pso, query};
+use hal::{DrawCount, IndexCount, InstanceCount, VertexCount, VertexOffset, WorkGroupCount};
+use hal::range::RangeArg;
+
+use format as f;
+use format::{Call, Command, Id};
+use native as n;
+use record::Recorder;
+use Backend;
+
+
+fn collect<T: Clone, I>(items: I) -> Vec<T>
+where
+    I: IntoIterator,
+    I::Item: Borrow<T>,
+{
+    items
+        .into_iter()
+        .map(|item| item.borrow().clone())
+        .collect()
+}
+
+fn record_barrier<B: hal::Backend>(barrier: &memory::Barrier<Backend<B>>) -> f::Barrier {
+    match *barrier {
+        memory::Barrier::AllBuffers(ref access) => f::Barrier::AllBuffers(access.clone()),
+        memory::Barrier::AllImages(ref access) => f::Barrier::AllImages(access.clone()),
+        memory::Barrier::Buffer { ref states, target, ref families } => f::Barrier::Buffer {
+            states: states.clone(),
+            target: target.id,
+            families: families.as_ref().map(|f| f.start.0 .. f.end.0),
+        },
+        memory::Barrier::Image { ref states, target, ref range, ref families } => f::Barrier::Image {
+            states: states.clone(),
+            target: target.id,
+            range: range.clone(),
+            families: families.as_ref().map(|f| f.start.0 .. f.end.0),
+        },
+    }
+}
+
+fn unwrap_barrier<'a, B: hal::Backend>(barrier: &memory::Barrier<'a, Backend<B>>) -> memory::Barrier<'a, B> {
+    match *barrier {
+        memory::Barrier::AllBuffers(ref access) => memory::Barrier::AllBuffers(access.clone()),
+        memory::Barrier::AllImages(ref access) => memory::Barrier::AllImages(access.clone()),
+        memory::Barrier::Buffer { ref states, target, ref families } => memory::Barrier::Buffer {
+            states: states.clone(),
+            target: &target.raw,
+            families: families.clone(),
+        },
+        memory::Barrier::Image { ref states, target, ref range, ref families } => memory::Barrier::Image {
+            states: states.clone(),
+            target: &target.raw,
+            range: range.clone(),
+            families: families.clone(),
+        },
+    }
+}
+
+/// Command buffer of the wrapped backend.
+#[derive(Clone, Debug)]
+pub struct CommandBuffer<B: hal::Backend> {
+    raw: B::CommandBuffer,
+    pub(crate) id: Id,
+    recorder: Arc<Recorder>,
+}
+
+impl<B: hal::Backend> CommandBuffer<B> {
+    fn record(&self, command: Command) {
+        self.recorder.record(Call::Command(self.id, command));
+    }
+
+    pub(crate) fn raw(&self) -> &B::CommandBuffer {
+        &self.raw
+    }
+}
+
+impl<B: hal::Backend> com::RawCommandBuffer<Backend<B>> for CommandBuffer<B> {
+    fn begin(&mut self, flags: com::CommandBufferFlags, info: com::CommandBufferInheritanceInfo<Backend<B>>) {
+        self.record(Command::Begin {
+            flags,
+            subpass: info.subpass.as_ref().map(|subpass| (subpass.main_pass.id, subpass.index)),
+            framebuffer: info.framebuffer.map(|fb| fb.id),
+            occlusion_query_enable: info.occlusion_query_enable,
+            occlusion_query_flags: info.occlusion_query_flags,
+            pipeline_statistics: info.pipeline_statistics,
+        });
+        self.raw.begin(flags, com::CommandBufferInheritanceInfo {
+            subpass: info.subpass.map(|subpass| pass::Subpass {
+                index: subpass.index,
+                main_pass: &subpass.main_pass.raw,
+            }),
+            framebuffer: info.framebuffer.map(|fb| &fb.raw),
+            occlusion_query_enable: info.occlusion_query_enable,
+            occlusion_query_flags: info.occlusion_query_flags,
+            pipeline_statistics: info.pipeline_statistics,
+        });
+    }
+
+    fn finish(&mut self) {
+        self.record(Command::Finish);
+        self.raw.finish();
+    }
+
+    fn reset(&mut self, release_resources: bool) {
+        self.record(Command::Reset { release_resources });
+        self.raw.reset(release_resources);
+    }
+
+    fn pipeline_barrier<'a, T>(
+        &mut self,
+        stages: Range<pso::PipelineStage>,
+        dependencies: memory::Dependencies,
+        barriers: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<memory::Barrier<'a, Backend<B>>>,
+    {
+        let barriers = barriers.into_iter().collect::<Vec<_>>();
+        self.record(Command::PipelineBarrier {
+            stages: stages.clone(),
+            dependencies,
+            barriers: barriers.iter().map(|barrier| record_barrier(barrier.borrow())).collect(),
+        });
+        self.raw.pipeline_barrier(stages, dependencies, barriers.iter().map(|barrier| unwrap_barrier(barrier.borrow())));
+    }
+
+    fn set_event(&mut self, event: &n::Event<B>, stages: pso::PipelineStage) {
+        self.record(Command::SetEvent { event: event.id, stages });
+        self.raw.set_event(&event.raw, stages);
+    }
+
+    fn reset_event(&mut self, event: &n::Event<B>, stages: pso::PipelineStage) {
+        self.record(Command::ResetEvent { event: event.id, stages });
+        self.raw.reset_event(&event.raw, stages);
+    }
+
+    fn wait_events<'a, I, J>(
+        &mut self,
+        events: I,
+        stages: Range<pso::PipelineStage>,
+        barriers: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<n::Event<B>>,
+        J: IntoIterator,
+        J::Item: Borrow<memory::Barrier<'a, Backend<B>>>,
+    {
+        let events = events.into_iter().collect::<Vec<_>>();
+        let barriers = barriers.into_iter().collect::<Vec<_>>();
+        self.record(Command::WaitEvents {
+            events: events.iter().map(|event| event.borrow().id).collect(),
+            stages: stages.clone(),
+            barriers: barriers.iter().map(|barrier| record_barrier(barrier.borrow())).collect(),
+        });
+        self.raw.wait_events(
+            events.iter().map(|event| &event.borrow().raw),
+            stages,
+            barriers.iter().map(|barrier| unwrap_barrier(barrier.borrow())),
+        );
+    }
+
+    fn fill_buffer<R>(&mut self, buffer: &n::Buffer<B>, range: R, data: u32)
+    where
+        R: RangeArg<buffer::Offset>,
+    {
+        self.record(Command::FillBuffer {
+            buffer: buffer.id,
+            range: range.start().cloned() .. range.end().cloned(),
+            data,
+        });
+        self.raw.fill_buffer(&buffer.raw, range, data);
+    }
+
+    fn update_buffer(&mut self, buffer: &n::Buffer<B>, offset: buffer::Offset, data: &[u8]) {
+        self.record(Command::UpdateBuffer {
+            buffer: buffer.id,
+            offset,
+            data: data.to_vec(),
+        });
+        self.raw.update_buffer(&buffer.raw, offset, data);
+    }
+
+    fn clear_image<T>(
+        &mut self,
+        image: &n::Image<B>,
+        layout: image::Layout,
+        color: com::ClearColorRaw,
+        depth_stencil: com::ClearDepthStencilRaw,
+        subresource_ranges: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<image::SubresourceRange>,
+    {
+        let ranges = collect(subresource_ranges);
+        self.record(Command::ClearImage {
+            image: image.id,
+            layout,
+            color: unsafe { color.uint32 },
+            depth_stencil,
+            ranges: ranges.clone(),
+        });
+        self.raw.clear_image(&image.raw, layout, color, depth_stencil, ranges);
+    }
+
+    fn clear_attachments<T, U>(&mut self, clears: T, rects: U)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<com::AttachmentClear>,
+        U: IntoIterator,
+        U::Item: Borrow<pso::ClearRect>,
+    {
+        let clears = collect(clears);
+        let rects = collect(rects);
+        self.record(Command::ClearAttachments {
+            clears: clears.clone(),
+            rects: rects.clone(),
+        });
+        self.raw.clear_attachments(clears, rects);
+    }
+
+    fn resolve_image<T>(
+        &mut self,
+        src: &n::Image<B>,
+        src_layout: image::Layout,
+        dst: &n::Image<B>,
+        dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ImageResolve>,
+    {
+        let regions = collect(regions);
+        self.record(Command::ResolveImage {
+            src: src.id,
+            src_layout,
+            dst: dst.id,
+            dst_layout,
+            regions: regions.clone(),
+        });
+        self.raw.resolve_image(&src.raw, src_layout, &dst.raw, dst_layout, regions);
+    }
+
+    fn blit_image<T>(
+        &mut self,
+        src: &n::Image<B>,
+        src_layout: image::Layout,
+        dst: &n::Image<B>,
+        dst_layout: image::Layout,
+        filter: image::Filter,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ImageBlit>,
+    {
+        let regions = collect(regions);
+        self.record(Command::BlitImage {
+            src: src.id,
+            src_layout,
+            dst: dst.id,
+            dst_layout,
+            filter,
+            regions: regions.clone(),
+        });
+        self.raw.blit_image(&src.raw, src_layout, &dst.raw, dst_layout, filter, regions);
+    }
+
+    fn bind_index_buffer(&mut self, ibv: buffer::IndexBufferView<Backend<B>>) {
+        self.record(Command::BindIndexBuffer {
+            buffer: ibv.buffer.id,
+            offset: ibv.offset,
+            index_type: ibv.index_type,
+        });
+        self.raw.bind_index_buffer(buffer::IndexBufferView {
+            buffer: &ibv.buffer.raw,
+            offset: ibv.offset,
+            index_type: ibv.index_type,
+        });
+    }
+
+    fn bind_vertex_buffers<I, T>(&mut self, first_binding: u32, buffers: I)
+    where
+        I: IntoIterator<Item = (T, buffer::Offset)>,
+        T: Borrow<n::Buffer<B>>,
+    {
+        let buffers = buffers.into_iter().collect::<Vec<_>>();
+        self.record(Command::BindVertexBuffers {
+            first_binding,
+            buffers: buffers
+                .iter()
+                .map(|&(ref buffer, offset)| (buffer.borrow().id, offset))
+                .collect(),
+        });
+        self.raw.bind_vertex_buffers(
+            first_binding,
+            buffers.iter().map(|&(ref buffer, offset)| (&buffer.borrow().raw, offset)),
+        );
+    }
+
+    fn set_viewports<T>(&mut self, first_viewport: u32, viewports: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::Viewport>,
+    {
+        let viewports = collect(viewports);
+        self.record(Command::SetViewports {
+            first: first_viewport,
+            viewports: viewports.clone(),
+        });
+        self.raw.set_viewports(first_viewport, viewports);
+    }
+
+    fn set_scissors<T>(&mut self, first_scissor: u32, rects: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::Rect>,
+    {
+        let rects = collect(rects);
+        self.record(Command::SetScissors {
+            first: first_scissor,
+            rects: rects.clone(),
+        });
+        self.raw.set_scissors(first_scissor, rects);
+    }
+
+    fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.record(Command::SetStencilReference(faces, value));
+        self.raw.set_stencil_reference(faces, value);
+    }
+
+    fn set_stencil_read_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.record(Command::SetStencilReadMask(faces, value));
+        self.raw.set_stencil_read_mask(faces, value);
+    }
+
+    fn set_stencil_write_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.record(Command::SetStencilWriteMask(faces, value));
+        self.raw.set_stencil_write_mask(faces, value);
+    }
+
+    fn set_blend_constants(&mut self, color: pso::ColorValue) {
+        self.record(Command::SetBlendConstants(color));
+        self.raw.set_blend_constants(color);
+    }
+
+    fn set_depth_bounds(&mut self, bounds: Range<f32>) {
+        self.record(Command::SetDepthBounds(bounds.clone()));
+        self.raw.set_depth_bounds(bounds);
+    }
+
+    fn set_line_width(&mut self, width: f32) {
+        self.record(Command::SetLineWidth(width));
+        self.raw.set_line_width(width);
+    }
+
+    fn set_depth_bias(&mut self, depth_bias: pso::DepthBias) {
+        self.record(Command::SetDepthBias(depth_bias));
+        self.raw.set_depth_bias(depth_bias);
+    }
+
+    fn begin_render_pass<T>(
+        &mut self,
+        render_pass: &n::RenderPass<B>,
+        framebuffer: &n::Framebuffer<B>,
+        render_area: pso::Rect,
+        clear_values: T,
+        first_subpass: com::SubpassContents,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ClearValueRaw>,
+    {
+        let clear_values = collect::<com::ClearValueRaw, _>(clear_values);
+        self.record(Command::BeginRenderPass {
+            render_pass: render_pass.id,
+            framebuffer: framebuffer.id,
+            render_area,
+            clear_values: clear_values
+                .iter()
+                .map(|value| unsafe { value.color.uint32 })
+                .collect(),
+            first_subpass,
+        });
+        self.raw.begin_render_pass(&render_pass.raw, &framebuffer.raw, render_area, clear_values, first_subpass);
+    }
+
+    fn next_subpass(&mut self, contents: com::SubpassContents) {
+        self.record(Command::NextSubpass(contents));
+        self.raw.next_subpass(contents);
+    }
+
+    fn end_render_pass(&mut self) {
+        self.record(Command::EndRenderPass);
+        self.raw.end_render_pass();
+    }
+
+    fn bind_graphics_pipeline(&mut self, pipeline: &n::GraphicsPipeline<B>) {
+        self.record(Command::BindGraphicsPipeline(pipeline.id));
+        self.raw.bind_graphics_pipeline(&pipeline.raw);
+    }
+
+    fn bind_graphics_descriptor_sets<I, J>(
+        &mut self,
+        layout: &n::PipelineLayout<B>,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<n::DescriptorSet<B>>,
+        J: IntoIterator,
+        J::Item: Borrow<com::DescriptorSetOffset>,
+    {
+        let sets = sets.into_iter().collect::<Vec<_>>();
+        let offsets = collect(offsets);
+        self.record(Command::BindGraphicsDescriptorSets {
+            layout: layout.id,
+            first_set,
+            sets: sets.iter().map(|set| set.borrow().id).collect(),
+            offsets: offsets.clone(),
+        });
+        self.raw.bind_graphics_descriptor_sets(
+            &layout.raw,
+            first_set,
+            sets.iter().map(|set| &set.borrow().raw),
+            offsets,
+        );
+    }
+
+    fn bind_compute_pipeline(&mut self, pipeline: &n::ComputePipeline<B>) {
+        self.record(Command::BindComputePipeline(pipeline.id));
+        self.raw.bind_compute_pipeline(&pipeline.raw);
+    }
+
+    fn bind_compute_descriptor_sets<I, J>(
+        &mut self,
+        layout: &n::PipelineLayout<B>,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<n::DescriptorSet<B>>,
+        J: IntoIterator,
+        J::Item: Borrow<com::DescriptorSetOffset>,
+    {
+        let sets = sets.into_iter().collect::<Vec<_>>();
+        let offsets = collect(offsets);
+        self.record(Command::BindComputeDescriptorSets {
+            layout: layout.id,
+            first_set,
+            sets: sets.iter().map(|set| set.borrow().id).collect(),
+            offsets: offsets.clone(),
+        });
+        self.raw.bind_compute_descriptor_sets(
+            &layout.raw,
+            first_set,
+            sets.iter().map(|set| &set.borrow().raw),
+            offsets,
+        );
+    }
+
+    fn dispatch(&mut self, count: WorkGroupCount) {
+        self.record(Command::Dispatch(count));
+        self.raw.dispatch(count);
+    }
+
+    fn dispatch_indirect(&mut self, buffer: &n::Buffer<B>, offset: buffer::Offset) {
+        self.record(Command::DispatchIndirect { buffer: buffer.id, offset });
+        self.raw.dispatch_indirect(&buffer.raw, offset);
+    }
+
+    fn copy_buffer<T>(&mut self, src: &n::Buffer<B>, dst: &n::Buffer<B>, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<com::BufferCopy>,
+    {
+        let regions = collect(regions);
+        self.record(Command::CopyBuffer {
+            src: src.id,
+            dst: dst.id,
+            regions: regions.clone(),
+        });
+        self.raw.copy_buffer(&src.raw, &dst.raw, regions);
+    }
+
+    fn copy_image<T>(
+        &mut self,
+        src: &n::Image<B>,
+        src_layout: image::Layout,
+        dst: &n::Image<B>,
+        dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ImageCopy>,
+    {
+        let regions = collect(regions);
+        self.record(Command::CopyImage {
+            src: src.id,
+            src_layout,
+            dst: dst.id,
+            dst_layout,
+            regions: regions.clone(),
+        });
+        self.raw.copy_image(&src.raw, src_layout, &dst.raw, dst_layout, regions);
+    }
+
+    fn copy_buffer_to_image<T>(
+        &mut self,
+        src: &n::Buffer<B>,
+        dst: &n::Image<B>,
+        dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::BufferImageCopy>,
+    {
+        let regions = collect(regions);
+        self.record(Command::CopyBufferToImage {
+            src: src.id,
+            dst: dst.id,
+            dst_layout,
+            regions: regions.clone(),
+        });
+        self.raw.copy_buffer_to_image(&src.raw, &dst.raw, dst_layout, regions);
+    }
+
+    fn copy_image_to_buffer<T>(
+        &mut self,
+        src: &n::Image<B>,
+        src_layout: image::Layout,
+        dst: &n::Buffer<B>,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::BufferImageCopy>,
+    {
+        let regions = collect(regions);
+        self.record(Command::CopyImageToBuffer {
+            src: src.id,
+            src_layout,
+            dst: dst.id,
+            regions: regions.clone(),
+        });
+        self.raw.copy_image_to_buffer(&src.raw, src_layout, &dst.raw, regions);
+    }
+
+    fn draw(&mut self, vertices: Range<VertexCount>, instances: Range<InstanceCount>) {
+        self.record(Command::Draw {
+            vertices: vertices.clone(),
+            instances: instances.clone(),
+        });
+        self.raw.draw(vertices, instances);
+    }
+
+    fn draw_indexed(
+        &mut self,
+        indices: Range<IndexCount>,
+        base_vertex: VertexOffset,
+        instances: Range<InstanceCount>,
+    ) {
+        self.record(Command::DrawIndexed {
+            indices: indices.clone(),
+            base_vertex,
+            instances: instances.clone(),
+        });
+        self.raw.draw_indexed(indices, base_vertex, instances);
+    }
+
+    fn draw_indirect(
+        &mut self,
+        buffer: &n::Buffer<B>,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: u32,
+    ) {
+        self.record(Command::DrawIndirect {
+            buffer: buffer.id,
+            offset,
+            draw_count,
+            stride,
+        });
+        self.raw.draw_indirect(&buffer.raw, offset, draw_count, stride);
+    }
+
+    fn draw_indexed_indirect(
+        &mut self,
+        buffer: &n::Buffer<B>,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: u32,
+    ) {
+        self.record(Command::DrawIndexedIndirect {
+            buffer: buffer.id,
+            offset,
+            draw_count,
+            stride,
+        });
+        self.raw.draw_indexed_indirect(&buffer.raw, offset, draw_count, stride);
+    }
+
+    fn begin_query(&mut self, query: query::Query<Backend<B>>, flags: query::QueryControl) {
+        self.record(Command::BeginQuery(f::Query { pool: query.pool.id, id: query.id }, flags));
+        self.raw.begin_query(query::Query { pool: &query.pool.raw, id: query.id }, flags);
+    }
+
+    fn end_query(&mut self, query: query::Query<Backend<B>>) {
+        self.record(Command::EndQuery(f::Query { pool: query.pool.id, id: query.id }));
+        self.raw.end_query(query::Query { pool: &query.pool.raw, id: query.id });
+    }
+
+    fn reset_query_pool(&mut self, pool: &n::QueryPool<B>, queries: Range<query::QueryId>) {
+        self.record(Command::ResetQueryPool {
+            pool: pool.id,
+            queries: queries.clone(),
+        });
+        self.raw.reset_query_pool(&pool.raw, queries);
+    }
+
+    fn write_timestamp(&mut self, stage: pso::PipelineStage, query: query::Query<Backend<B>>) {
+        self.record(Command::WriteTimestamp(stage, f::Query { pool: query.pool.id, id: query.id }));
+        self.raw.write_timestamp(stage, query::Query { pool: &query.pool.raw, id: query.id });
+    }
+
+    fn copy_query_pool_results(
+        &mut self,
+        pool: &n::QueryPool<B>,
+        queries: Range<query::QueryId>,
+        buffer: &n::Buffer<B>,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) {
+        self.record(Command::CopyQueryPoolResults {
+            pool: pool.id,
+            queries: queries.clone(),
+            buffer: buffer.id,
+            offset,
+            stride,
+            flags,
+        });
+        self.raw.copy_query_pool_results(&pool.raw, queries, &buffer.raw, offset, stride, flags);
+    }
+
+    fn push_graphics_constants(
+        &mut self,
+        layout: &n::PipelineLayout<B>,
+        stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.record(Command::PushGraphicsConstants {
+            layout: layout.id,
+            stages,
+            offset,
+            constants: constants.to_vec(),
+        });
+        self.raw.push_graphics_constants(&layout.raw, stages, offset, constants);
+    }
+
+    fn push_compute_constants(
+        &mut self,
+        layout: &n::PipelineLayout<B>,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.record(Command::PushComputeConstants {
+            layout: layout.id,
+            offset,
+            constants: constants.to_vec(),
+        });
+        self.raw.push_compute_constants(&layout.raw, offset, constants);
+    }
+
+    fn execute_commands<I>(&mut self, buffers: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<CommandBuffer<B>>,
+    {
+        let buffers = buffers.into_iter().collect::<Vec<_>>();
+        self.record(Command::ExecuteCommands(buffers.iter().map(|buffer| buffer.borrow().id).collect()));
+        self.raw.execute_commands(buffers.iter().map(|buffer| &buffer.borrow().raw));
+    }
+}
+
+/// Command pool of the wrapped backend.
+#[derive(Debug)]
+pub struct CommandPool<B: hal::Backend> {
+    pub(crate) raw: B::CommandPool,
+    pub(crate) id: Id,
+    recorder: Arc<Recorder>,
+}
+
+impl<B: hal::Backend> CommandPool<B> {
+    pub(crate) fn new(raw: B::CommandPool, id: Id, recorder: Arc<Recorder>) -> Self {
+        CommandPool { raw, id, recorder }
+    }
+}
+
+impl<B: hal::Backend> pool::RawCommandPool<Backend<B>> for CommandPool<B> {
+    fn reset(&mut self) {
+        self.recorder.record(Call::ResetCommandPool(self.id));
+        self.raw.reset();
+    }
+
+    fn allocate(&mut self, num: usize, level: com::RawLevel) -> Vec<CommandBuffer<B>> {
+        let buffers = self.raw
+            .allocate(num, level)
+            .into_iter()
+            .map(|raw| CommandBuffer {
+                raw,
+                id: self.recorder.alloc_id(),
+                recorder: self.recorder.clone(),
+            })
+            .collect::<Vec<_>>();
+        self.recorder.record(Call::AllocateCommandBuffers {
+            pool: self.id,
+            buffers: buffers.iter().map(|buffer| buffer.id).collect(),
+            level,
+        });
+        buffers
+    }
+
+    unsafe fn free(&mut self, buffers: Vec<CommandBuffer<B>>) {
+        self.recorder.record(Call::FreeCommandBuffers {
+            pool: self.id,
+            buffers: buffers.iter().map(|buffer| buffer.id).collect(),
+        });
+        self.raw.free(buffers.into_iter().map(|buffer| buffer.raw).collect());
+    }
+}
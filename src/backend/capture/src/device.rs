@@ -0,0 +1,960 @@
+use std::borrow::Borrow;
+use std::ops::Range;
+use std::sync::Arc;
+
+use hal::{self, buffer, error, format, image, mapping, pass, pool, pso, query, window};
+use hal::device::{BindError, FramebufferError, OutOfMemory, ShaderError, WaitFor};
+use hal::memory::{Requirements, SparseFlags};
+use hal::queue::QueueFamilyId;
+use hal::range::RangeArg;
+
+use command::CommandPool;
+use format as f;
+use format::{Call, Id};
+use native as n;
+use record::Recorder;
+use {Backend, Surface, Swapchain};
+
+
+fn capture_range<R: RangeArg<u64>>(range: &R) -> Range<Option<u64>> {
+    range.start().cloned() .. range.end().cloned()
+}
+
+fn capture_entry<B: hal::Backend>(entry: &pso::EntryPoint<Backend<B>>) -> f::EntryPoint {
+    f::EntryPoint {
+        entry: entry.entry.to_string(),
+        module: entry.module.id,
+        specialization: entry.specialization.to_vec(),
+    }
+}
+
+fn capture_parent<P, F: Fn(&P) -> Id>(parent: &pso::BasePipeline<P>, id: F) -> f::BasePipeline {
+    match *parent {
+        pso::BasePipeline::Pipeline(pipeline) => f::BasePipeline::Pipeline(id(pipeline)),
+        pso::BasePipeline::Index(index) => f::BasePipeline::Index(index),
+        pso::BasePipeline::None => f::BasePipeline::None,
+    }
+}
+
+fn map_entry<'a, B: hal::Backend>(entry: &pso::EntryPoint<'a, Backend<B>>) -> pso::EntryPoint<'a, B> {
+    pso::EntryPoint {
+        entry: entry.entry,
+        module: &entry.module.raw,
+        specialization: entry.specialization,
+    }
+}
+
+/// Device of the wrapped backend.
+#[derive(Debug)]
+pub struct Device<B: hal::Backend> {
+    raw: B::Device,
+    recorder: Arc<Recorder>,
+    memory_properties: hal::MemoryProperties,
+}
+
+impl<B: hal::Backend> Device<B> {
+    pub(crate) fn new(raw: B::Device, recorder: Arc<Recorder>, memory_properties: hal::MemoryProperties) -> Self {
+        Device {
+            raw,
+            recorder,
+            memory_properties,
+        }
+    }
+
+    fn capture_descriptor(descriptor: &pso::Descriptor<Backend<B>>) -> f::Descriptor {
+        match *descriptor {
+            pso::Descriptor::Sampler(sampler) => f::Descriptor::Sampler(sampler.id),
+            pso::Descriptor::Image(view, layout) => f::Descriptor::Image(view.id, layout),
+            pso::Descriptor::CombinedImageSampler(view, layout, sampler) => {
+                f::Descriptor::CombinedImageSampler(view.id, layout, sampler.id)
+            }
+            pso::Descriptor::Buffer(buffer, ref range) => f::Descriptor::Buffer(buffer.id, range.clone()),
+            pso::Descriptor::UniformTexelBuffer(view) => f::Descriptor::UniformTexelBuffer(view.id),
+            pso::Descriptor::StorageTexelBuffer(view) => f::Descriptor::StorageTexelBuffer(view.id),
+        }
+    }
+}
+
+impl<B: hal::Backend> hal::Device<Backend<B>> for Device<B> {
+    fn allocate_memory(&self, memory_type: hal::MemoryTypeId, size: u64) -> Result<n::Memory<B>, OutOfMemory> {
+        let raw = self.raw.allocate_memory(memory_type, size)?;
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::AllocateMemory {
+            memory: id,
+            properties: self.memory_properties.memory_types[memory_type.0].properties,
+            size,
+        });
+        Ok(n::Memory { raw, id, size })
+    }
+
+    fn free_memory(&self, memory: n::Memory<B>) {
+        self.recorder.forget(memory.id);
+        self.recorder.record(Call::FreeMemory(memory.id));
+        self.raw.free_memory(memory.raw);
+    }
+
+    fn create_command_pool(
+        &self, family: QueueFamilyId, create_flags: pool::CommandPoolCreateFlags
+    ) -> CommandPool<B> {
+        let raw = self.raw.create_command_pool(family, create_flags);
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateCommandPool {
+            pool: id,
+            family: family.0,
+            flags: create_flags,
+        });
+        CommandPool::new(raw, id, self.recorder.clone())
+    }
+
+    fn destroy_command_pool(&self, pool: CommandPool<B>) {
+        self.recorder.record(Call::DestroyCommandPool(pool.id));
+        self.raw.destroy_command_pool(pool.raw);
+    }
+
+    fn create_render_pass<'a, IA, IS, ID>(
+        &self,
+        attachments: IA,
+        subpasses: IS,
+        dependencies: ID,
+    ) -> n::RenderPass<B>
+    where
+        IA: IntoIterator,
+        IA::Item: Borrow<pass::Attachment>,
+        IS: IntoIterator,
+        IS::Item: Borrow<pass::SubpassDesc<'a>>,
+        ID: IntoIterator,
+        ID::Item: Borrow<pass::SubpassDependency>,
+    {
+        let attachments = attachments
+            .into_iter()
+            .map(|at| at.borrow().clone())
+            .collect::<Vec<_>>();
+        let subpasses = subpasses.into_iter().collect::<Vec<_>>();
+        let dependencies = dependencies
+            .into_iter()
+            .map(|dep| dep.borrow().clone())
+            .collect::<Vec<_>>();
+
+        let captured_subpasses = subpasses
+            .iter()
+            .map(|sp| {
+                let sp = sp.borrow();
+                f::SubpassDesc {
+                    colors: sp.colors.to_vec(),
+                    depth_stencil: sp.depth_stencil.cloned(),
+                    inputs: sp.inputs.to_vec(),
+                    resolves: sp.resolves.to_vec(),
+                    preserves: sp.preserves.to_vec(),
+                }
+            })
+            .collect();
+        let raw = self.raw.create_render_pass(&attachments, subpasses, &dependencies);
+
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateRenderPass {
+            render_pass: id,
+            attachments,
+            subpasses: captured_subpasses,
+            dependencies,
+        });
+        n::RenderPass { raw, id }
+    }
+
+    fn destroy_render_pass(&self, rp: n::RenderPass<B>) {
+        self.recorder.record(Call::DestroyRenderPass(rp.id));
+        self.raw.destroy_render_pass(rp.raw);
+    }
+
+    fn create_pipeline_layout<IS, IR>(
+        &self,
+        set_layouts: IS,
+        push_constant: IR,
+    ) -> n::PipelineLayout<B>
+    where
+        IS: IntoIterator,
+        IS::Item: Borrow<n::DescriptorSetLayout<B>>,
+        IR: IntoIterator,
+        IR::Item: Borrow<(pso::ShaderStageFlags, Range<u32>)>,
+    {
+        let set_layouts = set_layouts.into_iter().collect::<Vec<_>>();
+        let push_constants = push_constant
+            .into_iter()
+            .map(|pc| pc.borrow().clone())
+            .collect::<Vec<_>>();
+        let raw = self.raw.create_pipeline_layout(
+            set_layouts.iter().map(|layout| &layout.borrow().raw),
+            &push_constants,
+        );
+
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreatePipelineLayout {
+            layout: id,
+            set_layouts: set_layouts.iter().map(|layout| layout.borrow().id).collect(),
+            push_constants,
+        });
+        n::PipelineLayout { raw, id }
+    }
+
+    fn destroy_pipeline_layout(&self, layout: n::PipelineLayout<B>) {
+        self.recorder.record(Call::DestroyPipelineLayout(layout.id));
+        self.raw.destroy_pipeline_layout(layout.raw);
+    }
+
+    fn create_pipeline_cache(&self, data: Option<&[u8]>) -> n::PipelineCache<B> {
+        let raw = self.raw.create_pipeline_cache(data);
+        let id = self.recorder.alloc_id();
+        // The initial data is specific to the driver and doesn't change the
+        // results, so it isn't captured.
+        self.recorder.record(Call::CreatePipelineCache(id));
+        n::PipelineCache { raw, id }
+    }
+
+    fn get_pipeline_cache_data(&self, cache: &n::PipelineCache<B>) -> Result<Vec<u8>, OutOfMemory> {
+        self.raw.get_pipeline_cache_data(&cache.raw)
+    }
+
+    fn merge_pipeline_caches<I>(&self, target: &n::PipelineCache<B>, sources: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::PipelineCache<B>>,
+    {
+        let sources = sources.into_iter().collect::<Vec<_>>();
+        self.recorder.record(Call::MergePipelineCaches {
+            target: target.id,
+            sources: sources.iter().map(|cache| cache.borrow().id).collect(),
+        });
+        self.raw.merge_pipeline_caches(&target.raw, sources.iter().map(|cache| &cache.borrow().raw));
+    }
+
+    fn destroy_pipeline_cache(&self, cache: n::PipelineCache<B>) {
+        self.recorder.record(Call::DestroyPipelineCache(cache.id));
+        self.raw.destroy_pipeline_cache(cache.raw);
+    }
+
+    fn create_graphics_pipelines<'a, I>(
+        &self, descs: I, cache: Option<&n::PipelineCache<B>>
+    ) -> Vec<Result<n::GraphicsPipeline<B>, pso::CreationError>>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::GraphicsPipelineDesc<'a, Backend<B>>>,
+    {
+        let descs = descs.into_iter().collect::<Vec<_>>();
+        let captured_descs = descs
+            .iter()
+            .map(|desc| {
+                let desc = desc.borrow();
+                f::GraphicsPipelineDesc {
+                    vertex: capture_entry(&desc.shaders.vertex),
+                    hull: desc.shaders.hull.as_ref().map(capture_entry),
+                    domain: desc.shaders.domain.as_ref().map(capture_entry),
+                    geometry: desc.shaders.geometry.as_ref().map(capture_entry),
+                    fragment: desc.shaders.fragment.as_ref().map(capture_entry),
+                    rasterizer: desc.rasterizer.clone(),
+                    vertex_buffers: desc.vertex_buffers.clone(),
+                    attributes: desc.attributes.clone(),
+                    input_assembler: desc.input_assembler.clone(),
+                    blender: desc.blender.clone(),
+                    depth_stencil: desc.depth_stencil,
+                    multisampling: desc.multisampling.clone(),
+                    baked_states: desc.baked_states.clone(),
+                    layout: desc.layout.id,
+                    render_pass: desc.subpass.main_pass.id,
+                    subpass: desc.subpass.index,
+                    flags: desc.flags,
+                    parent: capture_parent(&desc.parent, |pipeline: &n::GraphicsPipeline<B>| pipeline.id),
+                }
+            })
+            .collect();
+        let raw_descs = descs
+            .iter()
+            .map(|desc| {
+                let desc = desc.borrow();
+                pso::GraphicsPipelineDesc {
+                    shaders: pso::GraphicsShaderSet {
+                        vertex: map_entry(&desc.shaders.vertex),
+                        hull: desc.shaders.hull.as_ref().map(map_entry),
+                        domain: desc.shaders.domain.as_ref().map(map_entry),
+                        geometry: desc.shaders.geometry.as_ref().map(map_entry),
+                        fragment: desc.shaders.fragment.as_ref().map(map_entry),
+                    },
+                    rasterizer: desc.rasterizer.clone(),
+                    vertex_buffers: desc.vertex_buffers.clone(),
+                    attributes: desc.attributes.clone(),
+                    input_assembler: desc.input_assembler.clone(),
+                    blender: desc.blender.clone(),
+                    depth_stencil: desc.depth_stencil,
+                    multisampling: desc.multisampling.clone(),
+                    baked_states: desc.baked_states.clone(),
+                    layout: &desc.layout.raw,
+                    subpass: pass::Subpass {
+                        index: desc.subpass.index,
+                        main_pass: &desc.subpass.main_pass.raw,
+                    },
+                    flags: desc.flags,
+                    parent: match desc.parent {
+                        pso::BasePipeline::Pipeline(pipeline) => pso::BasePipeline::Pipeline(&pipeline.raw),
+                        pso::BasePipeline::Index(index) => pso::BasePipeline::Index(index),
+                        pso::BasePipeline::None => pso::BasePipeline::None,
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let pipelines = self.raw
+            .create_graphics_pipelines(raw_descs, cache.map(|cache| &cache.raw))
+            .into_iter()
+            .map(|result| result.map(|raw| n::GraphicsPipeline {
+                raw,
+                id: self.recorder.alloc_id(),
+            }))
+            .collect::<Vec<_>>();
+        self.recorder.record(Call::CreateGraphicsPipelines {
+            pipelines: pipelines
+                .iter()
+                .map(|result| result.as_ref().ok().map(|pipeline| pipeline.id))
+                .collect(),
+            descs: captured_descs,
+            cache: cache.map(|cache| cache.id),
+        });
+        pipelines
+    }
+
+    fn destroy_graphics_pipeline(&self, pipeline: n::GraphicsPipeline<B>) {
+        self.recorder.record(Call::DestroyGraphicsPipeline(pipeline.id));
+        self.raw.destroy_graphics_pipeline(pipeline.raw);
+    }
+
+    fn create_compute_pipelines<'a, I>(
+        &self, descs: I, cache: Option<&n::PipelineCache<B>>
+    ) -> Vec<Result<n::ComputePipeline<B>, pso::CreationError>>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::ComputePipelineDesc<'a, Backend<B>>>,
+    {
+        let descs = descs.into_iter().collect::<Vec<_>>();
+        let captured_descs = descs
+            .iter()
+            .map(|desc| {
+                let desc = desc.borrow();
+                f::ComputePipelineDesc {
+                    shader: capture_entry(&desc.shader),
+                    layout: desc.layout.id,
+                    flags: desc.flags,
+                    parent: capture_parent(&desc.parent, |pipeline: &n::ComputePipeline<B>| pipeline.id),
+                }
+            })
+            .collect();
+        let raw_descs = descs
+            .iter()
+            .map(|desc| {
+                let desc = desc.borrow();
+                pso::ComputePipelineDesc {
+                    shader: map_entry(&desc.shader),
+                    layout: &desc.layout.raw,
+                    flags: desc.flags,
+                    parent: match desc.parent {
+                        pso::BasePipeline::Pipeline(pipeline) => pso::BasePipeline::Pipeline(&pipeline.raw),
+                        pso::BasePipeline::Index(index) => pso::BasePipeline::Index(index),
+                        pso::BasePipeline::None => pso::BasePipeline::None,
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let pipelines = self.raw
+            .create_compute_pipelines(raw_descs, cache.map(|cache| &cache.raw))
+            .into_iter()
+            .map(|result| result.map(|raw| n::ComputePipeline {
+                raw,
+                id: self.recorder.alloc_id(),
+            }))
+            .collect::<Vec<_>>();
+        self.recorder.record(Call::CreateComputePipelines {
+            pipelines: pipelines
+                .iter()
+                .map(|result| result.as_ref().ok().map(|pipeline| pipeline.id))
+                .collect(),
+            descs: captured_descs,
+            cache: cache.map(|cache| cache.id),
+        });
+        pipelines
+    }
+
+    fn destroy_compute_pipeline(&self, pipeline: n::ComputePipeline<B>) {
+        self.recorder.record(Call::DestroyComputePipeline(pipeline.id));
+        self.raw.destroy_compute_pipeline(pipeline.raw);
+    }
+
+    fn create_framebuffer<I>(
+        &self,
+        pass: &n::RenderPass<B>,
+        attachments: I,
+        extent: image::Extent,
+    ) -> Result<n::Framebuffer<B>, FramebufferError>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::ImageView<B>>,
+    {
+        let attachments = attachments.into_iter().collect::<Vec<_>>();
+        let raw = self.raw.create_framebuffer(
+            &pass.raw,
+            attachments.iter().map(|view| &view.borrow().raw),
+            extent,
+        )?;
+
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateFramebuffer {
+            framebuffer: id,
+            render_pass: pass.id,
+            attachments: attachments.iter().map(|view| view.borrow().id).collect(),
+            extent,
+        });
+        Ok(n::Framebuffer { raw, id })
+    }
+
+    fn destroy_framebuffer(&self, buf: n::Framebuffer<B>) {
+        self.recorder.record(Call::DestroyFramebuffer(buf.id));
+        self.raw.destroy_framebuffer(buf.raw);
+    }
+
+    fn create_shader_module(&self, spirv_data: &[u8]) -> Result<n::ShaderModule<B>, ShaderError> {
+        let raw = self.raw.create_shader_module(spirv_data)?;
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateShaderModule {
+            module: id,
+            spirv: spirv_data.to_vec(),
+        });
+        Ok(n::ShaderModule { raw, id })
+    }
+
+    fn destroy_shader_module(&self, shader: n::ShaderModule<B>) {
+        self.recorder.record(Call::DestroyShaderModule(shader.id));
+        self.raw.destroy_shader_module(shader.raw);
+    }
+
+    fn create_buffer(
+        &self, size: u64, usage: buffer::Usage,
+    ) -> Result<n::UnboundBuffer<B>, buffer::CreationError> {
+        let raw = self.raw.create_buffer(size, usage)?;
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateBuffer { buffer: id, size, usage });
+        Ok(n::UnboundBuffer { raw, id })
+    }
+
+    fn get_buffer_requirements(&self, buf: &n::UnboundBuffer<B>) -> Requirements {
+        self.raw.get_buffer_requirements(&buf.raw)
+    }
+
+    fn bind_buffer_memory(
+        &self, memory: &n::Memory<B>, offset: u64, buf: n::UnboundBuffer<B>
+    ) -> Result<n::Buffer<B>, BindError> {
+        let raw = self.raw.bind_buffer_memory(&memory.raw, offset, buf.raw)?;
+        self.recorder.record(Call::BindBufferMemory {
+            buffer: buf.id,
+            memory: memory.id,
+            offset,
+        });
+        Ok(n::Buffer { raw, id: buf.id })
+    }
+
+    fn destroy_buffer(&self, buffer: n::Buffer<B>) {
+        self.recorder.record(Call::DestroyBuffer(buffer.id));
+        self.raw.destroy_buffer(buffer.raw);
+    }
+
+    fn create_buffer_view<R: RangeArg<u64>>(
+        &self, buf: &n::Buffer<B>, fmt: Option<format::Format>, range: R
+    ) -> Result<n::BufferView<B>, buffer::ViewCreationError> {
+        let captured_range = capture_range(&range);
+        let raw = self.raw.create_buffer_view(&buf.raw, fmt, range)?;
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateBufferView {
+            view: id,
+            buffer: buf.id,
+            format: fmt,
+            range: captured_range,
+        });
+        Ok(n::BufferView { raw, id })
+    }
+
+    fn destroy_buffer_view(&self, view: n::BufferView<B>) {
+        self.recorder.record(Call::DestroyBufferView(view.id));
+        self.raw.destroy_buffer_view(view.raw);
+    }
+
+    fn create_image(
+        &self, kind: image::Kind, mip_levels: image::Level, format: format::Format,
+        tiling: image::Tiling, usage: image::Usage, storage_flags: image::StorageFlags,
+    ) -> Result<n::UnboundImage<B>, image::CreationError> {
+        let raw = self.raw.create_image(kind, mip_levels, format, tiling, usage, storage_flags)?;
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateImage {
+            image: id,
+            kind,
+            mip_levels,
+            format,
+            tiling,
+            usage,
+            storage_flags,
+        });
+        Ok(n::UnboundImage { raw, id })
+    }
+
+    fn get_image_requirements(&self, image: &n::UnboundImage<B>) -> Requirements {
+        self.raw.get_image_requirements(&image.raw)
+    }
+
+    fn get_image_subresource_footprint(
+        &self, image: &n::Image<B>, subresource: image::Subresource
+    ) -> image::SubresourceFootprint {
+        self.raw.get_image_subresource_footprint(&image.raw, subresource)
+    }
+
+    fn bind_image_memory(
+        &self, memory: &n::Memory<B>, offset: u64, image: n::UnboundImage<B>
+    ) -> Result<n::Image<B>, BindError> {
+        let raw = self.raw.bind_image_memory(&memory.raw, offset, image.raw)?;
+        self.recorder.record(Call::BindImageMemory {
+            image: image.id,
+            memory: memory.id,
+            offset,
+        });
+        Ok(n::Image { raw, id: image.id })
+    }
+
+    fn create_sparse_buffer(
+        &self, size: u64, usage: buffer::Usage, sparse: SparseFlags,
+    ) -> Result<n::Buffer<B>, buffer::CreationError> {
+        let raw = self.raw.create_sparse_buffer(size, usage, sparse)?;
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateSparseBuffer { buffer: id, size, usage, sparse });
+        Ok(n::Buffer { raw, id })
+    }
+
+    fn get_sparse_buffer_requirements(&self, buffer: &n::Buffer<B>) -> Requirements {
+        self.raw.get_sparse_buffer_requirements(&buffer.raw)
+    }
+
+    fn create_sparse_image(
+        &self, kind: image::Kind, mip_levels: image::Level, format: format::Format,
+        usage: image::Usage, storage_flags: image::StorageFlags, sparse: SparseFlags,
+    ) -> Result<n::Image<B>, image::CreationError> {
+        let raw = self.raw.create_sparse_image(kind, mip_levels, format, usage, storage_flags, sparse)?;
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateSparseImage {
+            image: id,
+            kind,
+            mip_levels,
+            format,
+            usage,
+            storage_flags,
+            sparse,
+        });
+        Ok(n::Image { raw, id })
+    }
+
+    fn get_sparse_image_requirements(
+        &self, image: &n::Image<B>,
+    ) -> (Requirements, Vec<image::SparseRequirements>) {
+        self.raw.get_sparse_image_requirements(&image.raw)
+    }
+
+    fn destroy_image(&self, image: n::Image<B>) {
+        self.recorder.record(Call::DestroyImage(image.id));
+        self.raw.destroy_image(image.raw);
+    }
+
+    fn create_image_view(
+        &self,
+        image: &n::Image<B>,
+        view_kind: image::ViewKind,
+        format: format::Format,
+        swizzle: format::Swizzle,
+        range: image::SubresourceRange,
+    ) -> Result<n::ImageView<B>, image::ViewError> {
+        let raw = self.raw.create_image_view(&image.raw, view_kind, format, swizzle, range.clone())?;
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateImageView {
+            view: id,
+            image: image.id,
+            kind: view_kind,
+            format,
+            swizzle,
+            range,
+        });
+        Ok(n::ImageView { raw, id })
+    }
+
+    fn destroy_image_view(&self, view: n::ImageView<B>) {
+        self.recorder.record(Call::DestroyImageView(view.id));
+        self.raw.destroy_image_view(view.raw);
+    }
+
+    fn create_sampler(&self, info: image::SamplerInfo) -> n::Sampler<B> {
+        let raw = self.raw.create_sampler(info.clone());
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateSampler { sampler: id, info });
+        n::Sampler { raw, id }
+    }
+
+    fn destroy_sampler(&self, sampler: n::Sampler<B>) {
+        self.recorder.record(Call::DestroySampler(sampler.id));
+        self.raw.destroy_sampler(sampler.raw);
+    }
+
+    fn create_descriptor_pool<I>(&self, max_sets: usize, descriptor_ranges: I) -> n::DescriptorPool<B>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorRangeDesc>,
+    {
+        let ranges = descriptor_ranges
+            .into_iter()
+            .map(|range| *range.borrow())
+            .collect::<Vec<_>>();
+        let raw = self.raw.create_descriptor_pool(max_sets, &ranges);
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateDescriptorPool { pool: id, max_sets, ranges });
+        n::DescriptorPool {
+            raw,
+            id,
+            recorder: self.recorder.clone(),
+        }
+    }
+
+    fn destroy_descriptor_pool(&self, pool: n::DescriptorPool<B>) {
+        self.recorder.record(Call::DestroyDescriptorPool(pool.id));
+        self.raw.destroy_descriptor_pool(pool.raw);
+    }
+
+    fn create_descriptor_set_layout<I, J>(
+        &self, bindings: I, immutable_samplers: J
+    ) -> n::DescriptorSetLayout<B>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetLayoutBinding>,
+        J: IntoIterator,
+        J::Item: Borrow<n::Sampler<B>>,
+    {
+        let bindings = bindings
+            .into_iter()
+            .map(|binding| binding.borrow().clone())
+            .collect::<Vec<_>>();
+        let immutable_samplers = immutable_samplers.into_iter().collect::<Vec<_>>();
+        let raw = self.raw.create_descriptor_set_layout(
+            &bindings,
+            immutable_samplers.iter().map(|sampler| &sampler.borrow().raw),
+        );
+
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateDescriptorSetLayout {
+            layout: id,
+            bindings,
+            immutable_samplers: immutable_samplers.iter().map(|sampler| sampler.borrow().id).collect(),
+        });
+        n::DescriptorSetLayout { raw, id }
+    }
+
+    fn destroy_descriptor_set_layout(&self, layout: n::DescriptorSetLayout<B>) {
+        self.recorder.record(Call::DestroyDescriptorSetLayout(layout.id));
+        self.raw.destroy_descriptor_set_layout(layout.raw);
+    }
+
+    fn write_descriptor_sets<'a, I, J>(&self, write_iter: I)
+    where
+        I: IntoIterator<Item = pso::DescriptorSetWrite<'a, Backend<B>, J>>,
+        J: IntoIterator,
+        J::Item: Borrow<pso::Descriptor<'a, Backend<B>>>,
+    {
+        let mut captured_writes = Vec::new();
+        let mut raw_writes = Vec::new();
+        for write in write_iter {
+            let mut captured_descriptors = Vec::new();
+            let mut raw_descriptors = Vec::new();
+            for descriptor in write.descriptors {
+                let descriptor = descriptor.borrow();
+                captured_descriptors.push(Self::capture_descriptor(descriptor));
+                raw_descriptors.push(match *descriptor {
+                    pso::Descriptor::Sampler(sampler) => pso::Descriptor::Sampler(&sampler.raw),
+                    pso::Descriptor::Image(view, layout) => pso::Descriptor::Image(&view.raw, layout),
+                    pso::Descriptor::CombinedImageSampler(view, layout, sampler) => {
+                        pso::Descriptor::CombinedImageSampler(&view.raw, layout, &sampler.raw)
+                    }
+                    pso::Descriptor::Buffer(buffer, ref range) => pso::Descriptor::Buffer(&buffer.raw, range.clone()),
+                    pso::Descriptor::UniformTexelBuffer(view) => pso::Descriptor::UniformTexelBuffer(&view.raw),
+                    pso::Descriptor::StorageTexelBuffer(view) => pso::Descriptor::StorageTexelBuffer(&view.raw),
+                });
+            }
+
+            captured_writes.push(f::DescriptorSetWrite {
+                set: write.set.id,
+                binding: write.binding,
+                array_offset: write.array_offset,
+                descriptors: captured_descriptors,
+            });
+            raw_writes.push(pso::DescriptorSetWrite {
+                set: &write.set.raw,
+                binding: write.binding,
+                array_offset: write.array_offset,
+                descriptors: raw_descriptors,
+            });
+        }
+
+        self.recorder.record(Call::WriteDescriptorSets(captured_writes));
+        self.raw.write_descriptor_sets(raw_writes);
+    }
+
+    fn copy_descriptor_sets<'a, I>(&self, copy_iter: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetCopy<'a, Backend<B>>>,
+    {
+        let copies = copy_iter.into_iter().collect::<Vec<_>>();
+        self.recorder.record(Call::CopyDescriptorSets(
+            copies
+                .iter()
+                .map(|copy| {
+                    let copy = copy.borrow();
+                    f::DescriptorSetCopy {
+                        src_set: copy.src_set.id,
+                        src_binding: copy.src_binding,
+                        src_array_offset: copy.src_array_offset,
+                        dst_set: copy.dst_set.id,
+                        dst_binding: copy.dst_binding,
+                        dst_array_offset: copy.dst_array_offset,
+                        count: copy.count,
+                    }
+                })
+                .collect(),
+        ));
+        self.raw.copy_descriptor_sets(copies.iter().map(|copy| {
+            let copy = copy.borrow();
+            pso::DescriptorSetCopy {
+                src_set: &copy.src_set.raw,
+                src_binding: copy.src_binding,
+                src_array_offset: copy.src_array_offset,
+                dst_set: &copy.dst_set.raw,
+                dst_binding: copy.dst_binding,
+                dst_array_offset: copy.dst_array_offset,
+                count: copy.count,
+            }
+        }));
+    }
+
+    fn map_memory<R>(&self, memory: &n::Memory<B>, range: R) -> Result<*mut u8, mapping::Error>
+    where
+        R: RangeArg<u64>,
+    {
+        let captured_range = capture_range(&range);
+        let bounds = range.start().cloned().unwrap_or(0) .. range.end().cloned().unwrap_or(memory.size);
+        let ptr = self.raw.map_memory(&memory.raw, range)?;
+        self.recorder.record(Call::MapMemory {
+            memory: memory.id,
+            range: captured_range,
+        });
+        self.recorder.map(memory.id, ptr, bounds);
+        Ok(ptr)
+    }
+
+    fn flush_mapped_memory_ranges<'a, I, R>(&self, ranges: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a n::Memory<B>, R)>,
+        R: RangeArg<u64>,
+    {
+        let ranges = ranges.into_iter().collect::<Vec<_>>();
+        for range in &ranges {
+            self.recorder.snapshot(range.borrow().0.id);
+        }
+        self.raw.flush_mapped_memory_ranges(ranges.iter().map(|range| {
+            let (memory, ref range) = *range.borrow();
+            (&memory.raw, (range.start().cloned(), range.end().cloned()))
+        }));
+    }
+
+    fn invalidate_mapped_memory_ranges<'a, I, R>(&self, ranges: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a n::Memory<B>, R)>,
+        R: RangeArg<u64>,
+    {
+        let ranges = ranges.into_iter().collect::<Vec<_>>();
+        self.raw.invalidate_mapped_memory_ranges(ranges.iter().map(|range| {
+            let (memory, ref range) = *range.borrow();
+            (&memory.raw, (range.start().cloned(), range.end().cloned()))
+        }));
+        // The device wrote the new contents, and the replay is going to reproduce them.
+        for range in &ranges {
+            self.recorder.refresh(range.borrow().0.id);
+        }
+    }
+
+    fn unmap_memory(&self, memory: &n::Memory<B>) {
+        self.recorder.unmap(memory.id);
+        self.recorder.record(Call::UnmapMemory(memory.id));
+        self.raw.unmap_memory(&memory.raw);
+    }
+
+    fn create_semaphore(&self) -> n::Semaphore<B> {
+        let raw = self.raw.create_semaphore();
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateSemaphore(id));
+        n::Semaphore { raw, id }
+    }
+
+    fn destroy_semaphore(&self, semaphore: n::Semaphore<B>) {
+        self.recorder.record(Call::DestroySemaphore(semaphore.id));
+        self.raw.destroy_semaphore(semaphore.raw);
+    }
+
+    fn create_fence(&self, signaled: bool) -> n::Fence<B> {
+        let raw = self.raw.create_fence(signaled);
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateFence { fence: id, signaled });
+        n::Fence { raw, id }
+    }
+
+    fn reset_fences<I>(&self, fences: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::Fence<B>>,
+    {
+        let fences = fences.into_iter().collect::<Vec<_>>();
+        self.recorder.record(Call::ResetFences(fences.iter().map(|fence| fence.borrow().id).collect()));
+        self.raw.reset_fences(fences.iter().map(|fence| &fence.borrow().raw));
+    }
+
+    fn wait_for_fences<I>(&self, fences: I, wait: WaitFor, timeout_ns: u64) -> bool
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::Fence<B>>,
+    {
+        let fences = fences.into_iter().collect::<Vec<_>>();
+        let signaled = self.raw.wait_for_fences(fences.iter().map(|fence| &fence.borrow().raw), wait, timeout_ns);
+        if signaled {
+            // Only the fences observed signaled need to be waited for by the replay.
+            let ids = fences
+                .iter()
+                .map(|fence| fence.borrow())
+                .filter(|fence| match wait {
+                    WaitFor::All => true,
+                    WaitFor::Any => self.raw.get_fence_status(&fence.raw),
+                })
+                .map(|fence| fence.id)
+                .collect();
+            self.recorder.record(Call::WaitForFences(ids));
+        }
+        signaled
+    }
+
+    fn get_fence_status(&self, fence: &n::Fence<B>) -> bool {
+        let signaled = self.raw.get_fence_status(&fence.raw);
+        if signaled {
+            self.recorder.record(Call::WaitForFences(vec![fence.id]));
+        }
+        signaled
+    }
+
+    fn destroy_fence(&self, fence: n::Fence<B>) {
+        self.recorder.record(Call::DestroyFence(fence.id));
+        self.raw.destroy_fence(fence.raw);
+    }
+
+    fn create_event(&self) -> n::Event<B> {
+        let raw = self.raw.create_event();
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateEvent(id));
+        n::Event { raw, id }
+    }
+
+    fn get_event_status(&self, event: &n::Event<B>) -> bool {
+        self.raw.get_event_status(&event.raw)
+    }
+
+    fn set_event(&self, event: &n::Event<B>) {
+        self.recorder.record(Call::SetEvent(event.id));
+        self.raw.set_event(&event.raw)
+    }
+
+    fn reset_event(&self, event: &n::Event<B>) {
+        self.recorder.record(Call::ResetEvent(event.id));
+        self.raw.reset_event(&event.raw)
+    }
+
+    fn destroy_event(&self, event: n::Event<B>) {
+        self.recorder.record(Call::DestroyEvent(event.id));
+        self.raw.destroy_event(event.raw);
+    }
+
+    fn create_query_pool(
+        &self, ty: query::QueryType, count: query::QueryId
+    ) -> Result<n::QueryPool<B>, query::Error> {
+        let raw = self.raw.create_query_pool(ty, count)?;
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateQueryPool { pool: id, ty, count });
+        Ok(n::QueryPool { raw, id })
+    }
+
+    fn destroy_query_pool(&self, pool: n::QueryPool<B>) {
+        self.recorder.record(Call::DestroyQueryPool(pool.id));
+        self.raw.destroy_query_pool(pool.raw);
+    }
+
+    fn get_query_pool_results(
+        &self,
+        pool: &n::QueryPool<B>,
+        queries: Range<query::QueryId>,
+        data: &mut [u8],
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) -> Result<bool, error::HostExecutionError> {
+        self.raw.get_query_pool_results(&pool.raw, queries, data, stride, flags)
+    }
+
+    fn create_swapchain(
+        &self,
+        surface: &mut Surface<B>,
+        config: window::SwapchainConfig,
+        old_swapchain: Option<Swapchain<B>>,
+    ) -> (Swapchain<B>, window::Backbuffer<Backend<B>>) {
+        let old_id = old_swapchain.as_ref().map(|swapchain| swapchain.id);
+        let (raw, backbuffer) = self.raw.create_swapchain(
+            &mut surface.raw,
+            config.clone(),
+            old_swapchain.map(|swapchain| swapchain.raw),
+        );
+
+        let (backbuffer, captured_backbuffer) = match backbuffer {
+            window::Backbuffer::Images(images) => {
+                let images = images
+                    .into_iter()
+                    .map(|raw| n::Image { raw, id: self.recorder.alloc_id() })
+                    .collect::<Vec<_>>();
+                let ids = images.iter().map(|image| image.id).collect();
+                (window::Backbuffer::Images(images), f::Backbuffer::Images(ids))
+            }
+            window::Backbuffer::Framebuffer(raw) => {
+                let id = self.recorder.alloc_id();
+                (window::Backbuffer::Framebuffer(n::Framebuffer { raw, id }), f::Backbuffer::Framebuffer(id))
+            }
+        };
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::CreateSwapchain {
+            swapchain: id,
+            config,
+            old_swapchain: old_id,
+            backbuffer: captured_backbuffer,
+        });
+        (Swapchain { raw, id, recorder: self.recorder.clone() }, backbuffer)
+    }
+
+    fn destroy_swapchain(&self, swapchain: Swapchain<B>) {
+        self.recorder.record(Call::DestroySwapchain(swapchain.id));
+        self.raw.destroy_swapchain(swapchain.raw);
+    }
+
+    fn wait_idle(&self) -> Result<(), error::HostExecutionError> {
+        self.recorder.record(Call::WaitIdle);
+        self.recorder.flush();
+        self.raw.wait_idle()
+    }
+}
@@ -0,0 +1,653 @@
+//! Capture file format.
+//!
+//! A capture starts with a header holding `MAGIC` and `VERSION`, followed by
+//! a stream of `bincode`-encoded `Call`s, in the order they have been issued.
+//! Objects are referred to by the `Id` assigned at their creation.
+
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+use bincode;
+use hal::{self, buffer, command as com, format, image, memory, pass, pool, pso, query, window};
+use hal::{DrawCount, IndexCount, InstanceCount, VertexCount, VertexOffset, WorkGroupCount};
+
+
+/// Bytes at the start of every capture.
+pub const MAGIC: [u8; 4] = *b"GFXC";
+/// Version of the format, bumped on every incompatible change.
+pub const VERSION: u32 = 5;
+
+/// Identifier of a captured object.
+pub type Id = u32;
+
+/// Queue family the device has been opened with.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueueFamily {
+    pub id: usize,
+    pub queue_type: hal::QueueType,
+    pub priorities: Vec<hal::QueuePriority>,
+    /// Identifiers of the created queues.
+    pub queues: Vec<Id>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubpassDesc {
+    pub colors: Vec<pass::AttachmentRef>,
+    pub depth_stencil: Option<pass::AttachmentRef>,
+    pub inputs: Vec<pass::AttachmentRef>,
+    pub resolves: Vec<pass::AttachmentRef>,
+    pub preserves: Vec<pass::AttachmentId>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SparseBind {
+    pub resource_offset: u64,
+    pub size: u64,
+    pub memory: Option<(Id, u64)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SparseImageBind {
+    pub subresource: image::Subresource,
+    pub offset: image::Offset,
+    pub extent: image::Extent,
+    pub memory: Option<(Id, u64)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntryPoint {
+    pub entry: String,
+    pub module: Id,
+    pub specialization: Vec<pso::Specialization>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BasePipeline {
+    Pipeline(Id),
+    Index(usize),
+    None,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GraphicsPipelineDesc {
+    pub vertex: EntryPoint,
+    pub hull: Option<EntryPoint>,
+    pub domain: Option<EntryPoint>,
+    pub geometry: Option<EntryPoint>,
+    pub fragment: Option<EntryPoint>,
+    pub rasterizer: pso::Rasterizer,
+    pub vertex_buffers: Vec<pso::VertexBufferDesc>,
+    pub attributes: Vec<pso::AttributeDesc>,
+    pub input_assembler: pso::InputAssemblerDesc,
+    pub blender: pso::BlendDesc,
+    pub depth_stencil: pso::DepthStencilDesc,
+    pub multisampling: Option<pso::Multisampling>,
+    pub baked_states: pso::BakedStates,
+    pub layout: Id,
+    pub render_pass: Id,
+    pub subpass: pass::SubpassId,
+    pub flags: pso::PipelineCreationFlags,
+    pub parent: BasePipeline,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ComputePipelineDesc {
+    pub shader: EntryPoint,
+    pub layout: Id,
+    pub flags: pso::PipelineCreationFlags,
+    pub parent: BasePipeline,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Descriptor {
+    Sampler(Id),
+    Image(Id, image::Layout),
+    CombinedImageSampler(Id, image::Layout, Id),
+    Buffer(Id, Range<Option<buffer::Offset>>),
+    UniformTexelBuffer(Id),
+    StorageTexelBuffer(Id),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DescriptorSetWrite {
+    pub set: Id,
+    pub binding: pso::DescriptorBinding,
+    pub array_offset: pso::DescriptorArrayIndex,
+    pub descriptors: Vec<Descriptor>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DescriptorSetCopy {
+    pub src_set: Id,
+    pub src_binding: pso::DescriptorBinding,
+    pub src_array_offset: pso::DescriptorArrayIndex,
+    pub dst_set: Id,
+    pub dst_binding: pso::DescriptorBinding,
+    pub dst_array_offset: pso::DescriptorArrayIndex,
+    pub count: usize,
+}
+
+/// Images presented by a swapchain.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Backbuffer {
+    Images(Vec<Id>),
+    Framebuffer(Id),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Barrier {
+    AllBuffers(Range<buffer::Access>),
+    AllImages(Range<image::Access>),
+    Buffer {
+        states: Range<buffer::State>,
+        target: Id,
+        families: Option<Range<usize>>,
+    },
+    Image {
+        states: Range<image::State>,
+        target: Id,
+        range: image::SubresourceRange,
+        families: Option<Range<usize>>,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Query {
+    pub pool: Id,
+    pub id: query::QueryId,
+}
+
+/// Command recorded into a command buffer.
+///
+/// Clear values are stored as raw bits, their interpretation depending on the
+/// format of the cleared resource.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Command {
+    Begin {
+        flags: com::CommandBufferFlags,
+        subpass: Option<(Id, pass::SubpassId)>,
+        framebuffer: Option<Id>,
+        occlusion_query_enable: bool,
+        occlusion_query_flags: query::QueryControl,
+        pipeline_statistics: query::PipelineStatistic,
+    },
+    Finish,
+    Reset {
+        release_resources: bool,
+    },
+    PipelineBarrier {
+        stages: Range<pso::PipelineStage>,
+        dependencies: memory::Dependencies,
+        barriers: Vec<Barrier>,
+    },
+    SetEvent {
+        event: Id,
+        stages: pso::PipelineStage,
+    },
+    ResetEvent {
+        event: Id,
+        stages: pso::PipelineStage,
+    },
+    WaitEvents {
+        events: Vec<Id>,
+        stages: Range<pso::PipelineStage>,
+        barriers: Vec<Barrier>,
+    },
+    FillBuffer {
+        buffer: Id,
+        range: Range<Option<buffer::Offset>>,
+        data: u32,
+    },
+    UpdateBuffer {
+        buffer: Id,
+        offset: buffer::Offset,
+        data: Vec<u8>,
+    },
+    ClearImage {
+        image: Id,
+        layout: image::Layout,
+        color: [u32; 4],
+        depth_stencil: com::ClearDepthStencilRaw,
+        ranges: Vec<image::SubresourceRange>,
+    },
+    ClearAttachments {
+        clears: Vec<com::AttachmentClear>,
+        rects: Vec<pso::ClearRect>,
+    },
+    ResolveImage {
+        src: Id,
+        src_layout: image::Layout,
+        dst: Id,
+        dst_layout: image::Layout,
+        regions: Vec<com::ImageResolve>,
+    },
+    BlitImage {
+        src: Id,
+        src_layout: image::Layout,
+        dst: Id,
+        dst_layout: image::Layout,
+        filter: image::Filter,
+        regions: Vec<com::ImageBlit>,
+    },
+    BindIndexBuffer {
+        buffer: Id,
+        offset: buffer::Offset,
+        index_type: hal::IndexType,
+    },
+    BindVertexBuffers {
+        first_binding: u32,
+        buffers: Vec<(Id, buffer::Offset)>,
+    },
+    SetViewports {
+        first: u32,
+        viewports: Vec<pso::Viewport>,
+    },
+    SetScissors {
+        first: u32,
+        rects: Vec<pso::Rect>,
+    },
+    SetStencilReference(pso::Face, pso::StencilValue),
+    SetStencilReadMask(pso::Face, pso::StencilValue),
+    SetStencilWriteMask(pso::Face, pso::StencilValue),
+    SetBlendConstants(pso::ColorValue),
+    SetDepthBounds(Range<f32>),
+    SetLineWidth(f32),
+    SetDepthBias(pso::DepthBias),
+    BeginRenderPass {
+        render_pass: Id,
+        framebuffer: Id,
+        render_area: pso::Rect,
+        clear_values: Vec<[u32; 4]>,
+        first_subpass: com::SubpassContents,
+    },
+    NextSubpass(com::SubpassContents),
+    EndRenderPass,
+    BindGraphicsPipeline(Id),
+    BindGraphicsDescriptorSets {
+        layout: Id,
+        first_set: usize,
+        sets: Vec<Id>,
+        offsets: Vec<com::DescriptorSetOffset>,
+    },
+    BindComputePipeline(Id),
+    BindComputeDescriptorSets {
+        layout: Id,
+        first_set: usize,
+        sets: Vec<Id>,
+        offsets: Vec<com::DescriptorSetOffset>,
+    },
+    Dispatch(WorkGroupCount),
+    DispatchIndirect {
+        buffer: Id,
+        offset: buffer::Offset,
+    },
+    CopyBuffer {
+        src: Id,
+        dst: Id,
+        regions: Vec<com::BufferCopy>,
+    },
+    CopyImage {
+        src: Id,
+        src_layout: image::Layout,
+        dst: Id,
+        dst_layout: image::Layout,
+        regions: Vec<com::ImageCopy>,
+    },
+    CopyBufferToImage {
+        src: Id,
+        dst: Id,
+        dst_layout: image::Layout,
+        regions: Vec<com::BufferImageCopy>,
+    },
+    CopyImageToBuffer {
+        src: Id,
+        src_layout: image::Layout,
+        dst: Id,
+        regions: Vec<com::BufferImageCopy>,
+    },
+    Draw {
+        vertices: Range<VertexCount>,
+        instances: Range<InstanceCount>,
+    },
+    DrawIndexed {
+        indices: Range<IndexCount>,
+        base_vertex: VertexOffset,
+        instances: Range<InstanceCount>,
+    },
+    DrawIndirect {
+        buffer: Id,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: u32,
+    },
+    DrawIndexedIndirect {
+        buffer: Id,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: u32,
+    },
+    BeginQuery(Query, query::QueryControl),
+    EndQuery(Query),
+    ResetQueryPool {
+        pool: Id,
+        queries: Range<query::QueryId>,
+    },
+    WriteTimestamp(pso::PipelineStage, Query),
+    CopyQueryPoolResults {
+        pool: Id,
+        queries: Range<query::QueryId>,
+        buffer: Id,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    },
+    PushGraphicsConstants {
+        layout: Id,
+        stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: Vec<u32>,
+    },
+    PushComputeConstants {
+        layout: Id,
+        offset: u32,
+        constants: Vec<u32>,
+    },
+    ExecuteCommands(Vec<Id>),
+}
+
+/// API call, along with the identifiers of the objects it creates.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Call {
+    OpenDevice {
+        adapter: hal::AdapterInfo,
+        families: Vec<QueueFamily>,
+        features: hal::Features,
+    },
+
+    AllocateMemory {
+        memory: Id,
+        properties: memory::Properties,
+        size: u64,
+    },
+    FreeMemory(Id),
+    MapMemory {
+        memory: Id,
+        range: Range<Option<u64>>,
+    },
+    /// Contents written by the application to a mapped memory, at `offset`
+    /// from the start of the memory.
+    WriteMemory {
+        memory: Id,
+        offset: u64,
+        data: Vec<u8>,
+    },
+    UnmapMemory(Id),
+
+    CreateCommandPool {
+        pool: Id,
+        family: usize,
+        flags: pool::CommandPoolCreateFlags,
+    },
+    ResetCommandPool(Id),
+    AllocateCommandBuffers {
+        pool: Id,
+        buffers: Vec<Id>,
+        level: com::RawLevel,
+    },
+    FreeCommandBuffers {
+        pool: Id,
+        buffers: Vec<Id>,
+    },
+    DestroyCommandPool(Id),
+    Command(Id, Command),
+
+    CreateRenderPass {
+        render_pass: Id,
+        attachments: Vec<pass::Attachment>,
+        subpasses: Vec<SubpassDesc>,
+        dependencies: Vec<pass::SubpassDependency>,
+    },
+    DestroyRenderPass(Id),
+    CreatePipelineLayout {
+        layout: Id,
+        set_layouts: Vec<Id>,
+        push_constants: Vec<(pso::ShaderStageFlags, Range<u32>)>,
+    },
+    DestroyPipelineLayout(Id),
+    CreatePipelineCache(Id),
+    MergePipelineCaches {
+        target: Id,
+        sources: Vec<Id>,
+    },
+    DestroyPipelineCache(Id),
+    CreateGraphicsPipelines {
+        /// Identifiers of the pipelines, `None` for the ones failing to be created.
+        pipelines: Vec<Option<Id>>,
+        descs: Vec<GraphicsPipelineDesc>,
+        cache: Option<Id>,
+    },
+    DestroyGraphicsPipeline(Id),
+    CreateComputePipelines {
+        pipelines: Vec<Option<Id>>,
+        descs: Vec<ComputePipelineDesc>,
+        cache: Option<Id>,
+    },
+    DestroyComputePipeline(Id),
+    CreateFramebuffer {
+        framebuffer: Id,
+        render_pass: Id,
+        attachments: Vec<Id>,
+        extent: image::Extent,
+    },
+    DestroyFramebuffer(Id),
+    CreateShaderModule {
+        module: Id,
+        spirv: Vec<u8>,
+    },
+    DestroyShaderModule(Id),
+
+    CreateBuffer {
+        buffer: Id,
+        size: u64,
+        usage: buffer::Usage,
+    },
+    BindBufferMemory {
+        buffer: Id,
+        memory: Id,
+        offset: u64,
+    },
+    CreateSparseBuffer {
+        buffer: Id,
+        size: u64,
+        usage: buffer::Usage,
+        sparse: memory::SparseFlags,
+    },
+    DestroyBuffer(Id),
+    CreateBufferView {
+        view: Id,
+        buffer: Id,
+        format: Option<format::Format>,
+        range: Range<Option<u64>>,
+    },
+    DestroyBufferView(Id),
+    CreateImage {
+        image: Id,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        storage_flags: image::StorageFlags,
+    },
+    BindImageMemory {
+        image: Id,
+        memory: Id,
+        offset: u64,
+    },
+    CreateSparseImage {
+        image: Id,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        usage: image::Usage,
+        storage_flags: image::StorageFlags,
+        sparse: memory::SparseFlags,
+    },
+    DestroyImage(Id),
+    CreateImageView {
+        view: Id,
+        image: Id,
+        kind: image::ViewKind,
+        format: format::Format,
+        swizzle: format::Swizzle,
+        range: image::SubresourceRange,
+    },
+    DestroyImageView(Id),
+    CreateSampler {
+        sampler: Id,
+        info: image::SamplerInfo,
+    },
+    DestroySampler(Id),
+
+    CreateDescriptorPool {
+        pool: Id,
+        max_sets: usize,
+        ranges: Vec<pso::DescriptorRangeDesc>,
+    },
+    AllocateDescriptorSet {
+        pool: Id,
+        set: Id,
+        layout: Id,
+    },
+    FreeDescriptorSets {
+        pool: Id,
+        sets: Vec<Id>,
+    },
+    ResetDescriptorPool(Id),
+    DestroyDescriptorPool(Id),
+    CreateDescriptorSetLayout {
+        layout: Id,
+        bindings: Vec<pso::DescriptorSetLayoutBinding>,
+        immutable_samplers: Vec<Id>,
+    },
+    DestroyDescriptorSetLayout(Id),
+    WriteDescriptorSets(Vec<DescriptorSetWrite>),
+    CopyDescriptorSets(Vec<DescriptorSetCopy>),
+
+    CreateSemaphore(Id),
+    DestroySemaphore(Id),
+    CreateFence {
+        fence: Id,
+        signaled: bool,
+    },
+    ResetFences(Vec<Id>),
+    /// Wait for fences which have been observed signaled.
+    WaitForFences(Vec<Id>),
+    DestroyFence(Id),
+    CreateEvent(Id),
+    SetEvent(Id),
+    ResetEvent(Id),
+    DestroyEvent(Id),
+    CreateQueryPool {
+        pool: Id,
+        ty: query::QueryType,
+        count: query::QueryId,
+    },
+    DestroyQueryPool(Id),
+
+    CreateSwapchain {
+        swapchain: Id,
+        config: window::SwapchainConfig,
+        old_swapchain: Option<Id>,
+        backbuffer: Backbuffer,
+    },
+    DestroySwapchain(Id),
+    AcquireImage {
+        swapchain: Id,
+        /// Semaphore or fence signaled once the image is available.
+        semaphore: Option<Id>,
+        fence: Option<Id>,
+        index: Option<hal::SwapImageIndex>,
+    },
+    Present {
+        queue: Id,
+        swapchains: Vec<(Id, hal::SwapImageIndex)>,
+        wait_semaphores: Vec<Id>,
+    },
+
+    Submit {
+        queue: Id,
+        command_buffers: Vec<Id>,
+        wait_semaphores: Vec<(Id, pso::PipelineStage)>,
+        signal_semaphores: Vec<Id>,
+        fence: Option<Id>,
+    },
+    BindSparse {
+        queue: Id,
+        wait_semaphores: Vec<Id>,
+        signal_semaphores: Vec<Id>,
+        buffers: Vec<(Id, Vec<SparseBind>)>,
+        image_opaques: Vec<(Id, Vec<SparseBind>)>,
+        images: Vec<(Id, Vec<SparseImageBind>)>,
+        fence: Option<Id>,
+    },
+    QueueWaitIdle(Id),
+    WaitIdle,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    magic: [u8; 4],
+    version: u32,
+}
+
+/// Write the header of a capture.
+pub fn write_header<W: Write>(writer: &mut W) -> Result<(), bincode::Error> {
+    bincode::serialize_into(writer, &Header { magic: MAGIC, version: VERSION })
+}
+
+/// Append a call to a capture.
+pub fn write_call<W: Write>(writer: &mut W, call: &Call) -> Result<(), bincode::Error> {
+    bincode::serialize_into(writer, call)
+}
+
+/// Reader of the calls stored in a capture.
+pub struct Reader<R> {
+    inner: R,
+}
+
+impl<R: Read> Reader<R> {
+    /// Check the header of a capture and start reading its calls.
+    pub fn new(mut inner: R) -> Result<Self, bincode::Error> {
+        let header: Header = bincode::deserialize_from(&mut inner)?;
+        if header.magic != MAGIC {
+            let error = io::Error::new(io::ErrorKind::InvalidData, "not a gfx capture");
+            return Err(Box::new(bincode::ErrorKind::Io(error)));
+        }
+        if header.version != VERSION {
+            let message = format!("capture version {} is not supported, expected {}", header.version, VERSION);
+            let error = io::Error::new(io::ErrorKind::InvalidData, message);
+            return Err(Box::new(bincode::ErrorKind::Io(error)));
+        }
+        Ok(Reader { inner })
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Call, bincode::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match bincode::deserialize_from(&mut self.inner) {
+            Ok(call) => Some(Ok(call)),
+            Err(ref error) if is_eof(error) => None,
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+fn is_eof(error: &bincode::Error) -> bool {
+    match **error {
+        bincode::ErrorKind::Io(ref error) => error.kind() == io::ErrorKind::UnexpectedEof,
+        _ => false,
+    }
+}
@@ -0,0 +1,368 @@
+//! API call capture, wrapping another backend.
+//!
+//! `Backend<B>` implements the HAL on top of any backend `B`, serializing every
+//! call into a capture before forwarding it. The capture can then be replayed
+//! with `gfx-replay` on any other backend, reproducing a rendering issue without
+//! the application it comes from.
+//!
+//! The contents of mapped memory are recorded when the memory is flushed or
+//! unmapped, and before every submission, only including the bytes written by
+//! the application since the previous recording. Queries of the device state,
+//! like fence statuses or query results, are not recorded.
+//!
+//! ```ignore
+//! let file = BufWriter::new(File::create("app.gfxcapture")?);
+//! let instance = capture::Instance::new(back::Instance::create("app", 1), file)?;
+//! let surface = instance.wrap_surface(instance.raw().create_surface(&window));
+//! let adapters = instance.enumerate_adapters();
+//! ```
+
+extern crate bincode;
+#[macro_use]
+extern crate log;
+extern crate gfx_hal as hal;
+#[macro_use]
+extern crate serde;
+
+use std::io::Write;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use hal::{error, format as f, image};
+use hal::QueueFamily as _;
+use hal::backend::RawQueueGroup;
+use hal::queue::{QueueFamilyId, Queues};
+
+mod command;
+mod device;
+pub mod format;
+mod native;
+mod queue;
+mod record;
+mod window;
+
+pub use command::{CommandBuffer, CommandPool};
+pub use device::Device;
+pub use native::*;
+pub use queue::CommandQueue;
+pub use window::{Surface, Swapchain};
+
+use record::Recorder;
+
+
+/// Capture backend, wrapping the backend `B`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Backend<B>(PhantomData<B>);
+impl<B: hal::Backend> hal::Backend for Backend<B> {
+    type PhysicalDevice = PhysicalDevice<B>;
+    type Device = Device<B>;
+
+    type Surface = Surface<B>;
+    type Swapchain = Swapchain<B>;
+
+    type QueueFamily = QueueFamily<B>;
+    type CommandQueue = CommandQueue<B>;
+    type CommandBuffer = CommandBuffer<B>;
+
+    type Memory = Memory<B>;
+    type CommandPool = CommandPool<B>;
+
+    type ShaderModule = ShaderModule<B>;
+    type RenderPass = RenderPass<B>;
+    type Framebuffer = Framebuffer<B>;
+
+    type UnboundBuffer = UnboundBuffer<B>;
+    type Buffer = Buffer<B>;
+    type BufferView = BufferView<B>;
+    type UnboundImage = UnboundImage<B>;
+    type Image = Image<B>;
+    type ImageView = ImageView<B>;
+    type Sampler = Sampler<B>;
+
+    type ComputePipeline = ComputePipeline<B>;
+    type GraphicsPipeline = GraphicsPipeline<B>;
+    type PipelineCache = PipelineCache<B>;
+    type PipelineLayout = PipelineLayout<B>;
+    type DescriptorSetLayout = DescriptorSetLayout<B>;
+    type DescriptorPool = DescriptorPool<B>;
+    type DescriptorSet = DescriptorSet<B>;
+
+    type Fence = Fence<B>;
+    type Semaphore = Semaphore<B>;
+    type Event = Event<B>;
+    type QueryPool = QueryPool<B>;
+}
+
+/// Instance capturing the calls made to an instance of another backend.
+pub struct Instance<I> {
+    raw: I,
+    recorder: Arc<Recorder>,
+}
+
+impl<I: hal::Instance> Instance<I> {
+    /// Wrap an instance, writing the capture to `writer`.
+    pub fn new<W: Write + Send + 'static>(raw: I, writer: W) -> Result<Self, bincode::Error> {
+        Ok(Instance {
+            raw,
+            recorder: Arc::new(Recorder::new(writer)?),
+        })
+    }
+
+    /// Access the wrapped instance, e.g. to create surfaces.
+    pub fn raw(&self) -> &I {
+        &self.raw
+    }
+
+    /// Wrap a surface created by the wrapped instance.
+    pub fn wrap_surface(&self, raw: <I::Backend as hal::Backend>::Surface) -> Surface<I::Backend> {
+        Surface { raw }
+    }
+}
+
+impl<I: hal::Instance> hal::Instance for Instance<I> {
+    type Backend = Backend<I::Backend>;
+    fn enumerate_adapters(&self) -> Vec<hal::Adapter<Self::Backend>> {
+        self.raw
+            .enumerate_adapters()
+            .into_iter()
+            .map(|adapter| hal::Adapter {
+                physical_device: PhysicalDevice {
+                    raw: adapter.physical_device,
+                    info: adapter.info.clone(),
+                    recorder: self.recorder.clone(),
+                },
+                info: adapter.info,
+                queue_families: adapter.queue_families
+                    .into_iter()
+                    .map(|family| QueueFamily { raw: Arc::new(family) })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// Physical device of the wrapped backend.
+#[derive(Debug)]
+pub struct PhysicalDevice<B: hal::Backend> {
+    raw: B::PhysicalDevice,
+    info: hal::AdapterInfo,
+    recorder: Arc<Recorder>,
+}
+
+impl<B: hal::Backend> hal::PhysicalDevice<Backend<B>> for PhysicalDevice<B> {
+    fn open(
+        &self, families: &[(&QueueFamily<B>, &[hal::QueuePriority])], requested_features: hal::Features,
+    ) -> Result<hal::Gpu<Backend<B>>, error::DeviceCreationError> {
+        let raw_families = families
+            .iter()
+            .map(|&(family, priorities)| (&*family.raw, priorities))
+            .collect::<Vec<_>>();
+        let hal::Gpu { device, mut queues } = self.raw.open(&raw_families, requested_features)?;
+
+        let mut captured_families = Vec::new();
+        let queue_groups = families
+            .iter()
+            .map(|&(family, priorities)| {
+                let mut group = RawQueueGroup::new(family.clone());
+                let mut ids = Vec::new();
+                for raw in queues.take_raw(family.raw.id()).unwrap_or_default() {
+                    let queue = CommandQueue::new(raw, self.recorder.clone());
+                    ids.push(queue.id);
+                    group.add_queue(queue);
+                }
+                captured_families.push(format::QueueFamily {
+                    id: family.raw.id().0,
+                    queue_type: family.raw.queue_type(),
+                    priorities: priorities.to_vec(),
+                    queues: ids,
+                });
+                group
+            })
+            .collect();
+
+        self.recorder.record(format::Call::OpenDevice {
+            adapter: self.info.clone(),
+            families: captured_families,
+            features: requested_features,
+        });
+        Ok(hal::Gpu {
+            device: Device::new(device, self.recorder.clone(), self.raw.memory_properties()),
+            queues: Queues::new(queue_groups),
+        })
+    }
+
+    fn format_properties(&self, format: Option<f::Format>) -> f::Properties {
+        self.raw.format_properties(format)
+    }
+
+    fn image_format_properties(
+        &self, format: f::Format, dimensions: u8, tiling: image::Tiling,
+        usage: image::Usage, storage_flags: image::StorageFlags,
+    ) -> Option<image::FormatProperties> {
+        self.raw.image_format_properties(format, dimensions, tiling, usage, storage_flags)
+    }
+
+    fn memory_properties(&self) -> hal::MemoryProperties {
+        self.raw.memory_properties()
+    }
+
+    fn features(&self) -> hal::Features {
+        self.raw.features()
+    }
+
+    fn limits(&self) -> hal::Limits {
+        self.raw.limits()
+    }
+
+    fn timestamp_period(&self) -> f32 {
+        self.raw.timestamp_period()
+    }
+}
+
+/// Queue family of the wrapped backend.
+#[derive(Debug)]
+pub struct QueueFamily<B: hal::Backend> {
+    raw: Arc<B::QueueFamily>,
+}
+
+impl<B: hal::Backend> Clone for QueueFamily<B> {
+    fn clone(&self) -> Self {
+        QueueFamily { raw: self.raw.clone() }
+    }
+}
+
+impl<B: hal::Backend> hal::QueueFamily for QueueFamily<B> {
+    fn queue_type(&self) -> hal::QueueType {
+        self.raw.queue_type()
+    }
+    fn max_queues(&self) -> usize {
+        self.raw.max_queues()
+    }
+    fn id(&self) -> QueueFamilyId {
+        self.raw.id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate gfx_backend_empty as empty;
+
+    use std::io::{self, Cursor, Write};
+    use std::ptr;
+    use std::sync::{Arc, Mutex};
+
+    use hal::{buffer, command, Device, Instance as _, MemoryTypeId};
+    use hal::pool::CommandPoolCreateFlags;
+    use hal::queue::Submission;
+    use format::{Call, Command, Reader};
+    use Instance;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(data)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_copy() {
+        let output = Shared::default();
+        {
+            let instance = Instance::new(empty::Instance::create("capture", 1), output.clone()).unwrap();
+            let mut adapter = instance.enumerate_adapters().remove(0);
+            let (device, mut queue_group) = adapter.open_with::<_, hal::General>(1, |_| true).unwrap();
+
+            let memory = device.allocate_memory(MemoryTypeId(0), 8).unwrap();
+            let src = device.create_buffer(4, buffer::Usage::TRANSFER_SRC).unwrap();
+            let src = device.bind_buffer_memory(&memory, 0, src).unwrap();
+            let dst = device.create_buffer(4, buffer::Usage::TRANSFER_DST).unwrap();
+            let dst = device.bind_buffer_memory(&memory, 4, dst).unwrap();
+            let ptr = device.map_memory(&memory, 0 .. 4).unwrap();
+            unsafe {
+                ptr::copy_nonoverlapping([1u8, 2, 3, 4].as_ptr(), ptr, 4);
+            }
+
+            let mut pool = device.create_command_pool_typed(&queue_group, CommandPoolCreateFlags::empty(), 1);
+            let submit = {
+                let mut cmd = pool.acquire_command_buffer(false);
+                cmd.copy_buffer(&src, &dst, &[command::BufferCopy { src: 0, dst: 0, size: 4 }]);
+                cmd.finish()
+            };
+            queue_group.queues[0].submit(Submission::new().submit(Some(submit)), None);
+            device.unmap_memory(&memory);
+        }
+
+        let data = output.0.lock().unwrap().clone();
+        let calls = Reader::new(Cursor::new(data))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let position = |f: &dyn Fn(&Call) -> bool| calls.iter().position(f).unwrap();
+        let write = position(&|call| match *call {
+            Call::WriteMemory { offset: 0, ref data, .. } => data == &[1, 2, 3, 4],
+            _ => false,
+        });
+        let copy = position(&|call| match *call {
+            Call::Command(_, Command::CopyBuffer { .. }) => true,
+            _ => false,
+        });
+        let submit = position(&|call| match *call {
+            Call::Submit { .. } => true,
+            _ => false,
+        });
+        let map = position(&|call| match *call {
+            Call::WriteMemory { offset: 0, ref data, .. } => data == &[0, 0, 0, 0],
+            _ => false,
+        });
+        assert!(map < copy && copy < write && write < submit, "{:?}", calls);
+        // Nothing was written after the submission, so unmapping records no contents.
+        assert!(!calls[submit ..].iter().any(|call| match *call {
+            Call::WriteMemory { .. } => true,
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn test_map_filled() {
+        let output = Shared::default();
+        {
+            let instance = Instance::new(empty::Instance::create("capture", 1), output.clone()).unwrap();
+            let mut adapter = instance.enumerate_adapters().remove(0);
+            let (device, _queue_group) = adapter.open_with::<_, hal::General>(1, |_| true).unwrap();
+
+            let memory = device.allocate_memory(MemoryTypeId(0), 8).unwrap();
+            let ptr = device.map_memory(&memory, 0 .. 8).unwrap();
+            unsafe {
+                ptr::copy_nonoverlapping([1u8, 2, 3, 4, 5, 6, 7, 8].as_ptr(), ptr, 8);
+            }
+            device.unmap_memory(&memory);
+
+            let ptr = device.map_memory(&memory, 2 .. 6).unwrap();
+            unsafe {
+                *ptr.offset(1) = 9;
+            }
+            device.unmap_memory(&memory);
+        }
+
+        let data = output.0.lock().unwrap().clone();
+        let writes = Reader::new(Cursor::new(data))
+            .unwrap()
+            .filter_map(|call| match call.unwrap() {
+                Call::WriteMemory { offset, data, .. } => Some((offset, data)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(writes, vec![
+            (0, vec![0; 8]),
+            (0, vec![1, 2, 3, 4, 5, 6, 7, 8]),
+            // Mapping again records the filled contents, not only the change.
+            (2, vec![3, 4, 5, 6]),
+            (3, vec![9]),
+        ]);
+    }
+}
@@ -0,0 +1,178 @@
+//! Wrappers around the resources of the inner backend, carrying the
+//! identifiers they are referred to by in the capture.
+
+use std::sync::Arc;
+
+use hal::{self, pso};
+
+use format::{Call, Id};
+use record::Recorder;
+use Backend;
+
+
+#[derive(Debug)]
+pub struct ShaderModule<B: hal::Backend> {
+    pub(crate) raw: B::ShaderModule,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct RenderPass<B: hal::Backend> {
+    pub(crate) raw: B::RenderPass,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct Framebuffer<B: hal::Backend> {
+    pub(crate) raw: B::Framebuffer,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct Memory<B: hal::Backend> {
+    pub(crate) raw: B::Memory,
+    pub(crate) id: Id,
+    pub(crate) size: u64,
+}
+
+#[derive(Debug)]
+pub struct UnboundBuffer<B: hal::Backend> {
+    pub(crate) raw: B::UnboundBuffer,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct Buffer<B: hal::Backend> {
+    pub(crate) raw: B::Buffer,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct BufferView<B: hal::Backend> {
+    pub(crate) raw: B::BufferView,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct UnboundImage<B: hal::Backend> {
+    pub(crate) raw: B::UnboundImage,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct Image<B: hal::Backend> {
+    pub(crate) raw: B::Image,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct ImageView<B: hal::Backend> {
+    pub(crate) raw: B::ImageView,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct Sampler<B: hal::Backend> {
+    pub(crate) raw: B::Sampler,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct ComputePipeline<B: hal::Backend> {
+    pub(crate) raw: B::ComputePipeline,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct GraphicsPipeline<B: hal::Backend> {
+    pub(crate) raw: B::GraphicsPipeline,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct PipelineCache<B: hal::Backend> {
+    pub(crate) raw: B::PipelineCache,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct PipelineLayout<B: hal::Backend> {
+    pub(crate) raw: B::PipelineLayout,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct DescriptorSetLayout<B: hal::Backend> {
+    pub(crate) raw: B::DescriptorSetLayout,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct DescriptorSet<B: hal::Backend> {
+    pub(crate) raw: B::DescriptorSet,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct DescriptorPool<B: hal::Backend> {
+    pub(crate) raw: B::DescriptorPool,
+    pub(crate) id: Id,
+    pub(crate) recorder: Arc<Recorder>,
+}
+
+impl<B: hal::Backend> pso::DescriptorPool<Backend<B>> for DescriptorPool<B> {
+    fn allocate_set(&mut self, layout: &DescriptorSetLayout<B>) -> Result<DescriptorSet<B>, pso::AllocationError> {
+        let raw = self.raw.allocate_set(&layout.raw)?;
+        let id = self.recorder.alloc_id();
+        self.recorder.record(Call::AllocateDescriptorSet {
+            pool: self.id,
+            set: id,
+            layout: layout.id,
+        });
+        Ok(DescriptorSet { raw, id })
+    }
+
+    fn free_sets<I>(&mut self, descriptor_sets: I)
+    where
+        I: IntoIterator<Item = DescriptorSet<B>>,
+    {
+        let (raw_sets, ids): (Vec<_>, Vec<_>) = descriptor_sets
+            .into_iter()
+            .map(|set| (set.raw, set.id))
+            .unzip();
+        self.recorder.record(Call::FreeDescriptorSets {
+            pool: self.id,
+            sets: ids,
+        });
+        self.raw.free_sets(raw_sets);
+    }
+
+    fn reset(&mut self) {
+        self.recorder.record(Call::ResetDescriptorPool(self.id));
+        self.raw.reset();
+    }
+}
+
+#[derive(Debug)]
+pub struct Fence<B: hal::Backend> {
+    pub(crate) raw: B::Fence,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct Semaphore<B: hal::Backend> {
+    pub(crate) raw: B::Semaphore,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct Event<B: hal::Backend> {
+    pub(crate) raw: B::Event,
+    pub(crate) id: Id,
+}
+
+#[derive(Debug)]
+pub struct QueryPool<B: hal::Backend> {
+    pub(crate) raw: B::QueryPool,
+    pub(crate) id: Id,
+}
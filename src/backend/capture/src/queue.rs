@@ -0,0 +1,213 @@
+use std::borrow::Borrow;
+use std::sync::Arc;
+
+use hal::{self, error, memory, pso};
+use hal::queue::{RawCommandQueue, RawSparseBinding, RawSubmission};
+
+use command::CommandBuffer;
+use format as f;
+use format::{Call, Id};
+use native as n;
+use record::Recorder;
+use {Backend, Swapchain};
+
+
+fn unwrap_binds<'a, B: hal::Backend>(
+    binds: &[memory::SparseBind<'a, Backend<B>>],
+) -> Vec<memory::SparseBind<'a, B>> {
+    binds
+        .iter()
+        .map(|bind| memory::SparseBind {
+            resource_offset: bind.resource_offset,
+            size: bind.size,
+            memory: bind.memory.map(|(memory, offset)| (&memory.raw, offset)),
+        })
+        .collect()
+}
+
+fn unwrap_image_binds<'a, B: hal::Backend>(
+    binds: &[memory::SparseImageBind<'a, Backend<B>>],
+) -> Vec<memory::SparseImageBind<'a, B>> {
+    binds
+        .iter()
+        .map(|bind| memory::SparseImageBind {
+            subresource: bind.subresource,
+            offset: bind.offset,
+            extent: bind.extent,
+            memory: bind.memory.map(|(memory, offset)| (&memory.raw, offset)),
+        })
+        .collect()
+}
+
+/// Command queue of the wrapped backend.
+#[derive(Debug)]
+pub struct CommandQueue<B: hal::Backend> {
+    raw: B::CommandQueue,
+    pub(crate) id: Id,
+    recorder: Arc<Recorder>,
+}
+
+impl<B: hal::Backend> CommandQueue<B> {
+    pub(crate) fn new(raw: B::CommandQueue, recorder: Arc<Recorder>) -> Self {
+        CommandQueue {
+            raw,
+            id: recorder.alloc_id(),
+            recorder,
+        }
+    }
+}
+
+impl<B: hal::Backend> RawCommandQueue<Backend<B>> for CommandQueue<B> {
+    unsafe fn submit_raw<IC>(&mut self, submission: RawSubmission<Backend<B>, IC>, fence: Option<&n::Fence<B>>)
+    where
+        IC: IntoIterator,
+        IC::Item: Borrow<CommandBuffer<B>>,
+    {
+        let cmd_buffers = submission.cmd_buffers.into_iter().collect::<Vec<_>>();
+        // The submitted commands may read anything written to the mapped memories so far.
+        self.recorder.snapshot_all();
+        self.recorder.record(Call::Submit {
+            queue: self.id,
+            command_buffers: cmd_buffers.iter().map(|cmd_buffer| cmd_buffer.borrow().id).collect(),
+            wait_semaphores: submission.wait_semaphores
+                .iter()
+                .map(|&(semaphore, stage)| (semaphore.id, stage))
+                .collect(),
+            signal_semaphores: submission.signal_semaphores
+                .iter()
+                .map(|semaphore| semaphore.id)
+                .collect(),
+            fence: fence.map(|fence| fence.id),
+        });
+        self.recorder.flush();
+
+        let wait_semaphores = submission.wait_semaphores
+            .iter()
+            .map(|&(semaphore, stage)| (&semaphore.raw, stage))
+            .collect::<Vec<(_, pso::PipelineStage)>>();
+        let signal_semaphores = submission.signal_semaphores
+            .iter()
+            .map(|semaphore| &semaphore.raw)
+            .collect::<Vec<_>>();
+        let raw_submission = RawSubmission {
+            cmd_buffers: cmd_buffers.iter().map(|cmd_buffer| cmd_buffer.borrow().raw()),
+            wait_semaphores: &wait_semaphores,
+            signal_semaphores: &signal_semaphores,
+        };
+        self.raw.submit_raw(raw_submission, fence.map(|fence| &fence.raw));
+    }
+
+    unsafe fn bind_sparse(&mut self, binding: RawSparseBinding<Backend<B>>, fence: Option<&n::Fence<B>>) {
+        let record_binds = |binds: &[memory::SparseBind<Backend<B>>]| {
+            binds
+                .iter()
+                .map(|bind| f::SparseBind {
+                    resource_offset: bind.resource_offset,
+                    size: bind.size,
+                    memory: bind.memory.map(|(memory, offset)| (memory.id, offset)),
+                })
+                .collect()
+        };
+        self.recorder.record(Call::BindSparse {
+            queue: self.id,
+            wait_semaphores: binding.wait_semaphores.iter().map(|semaphore| semaphore.id).collect(),
+            signal_semaphores: binding.signal_semaphores.iter().map(|semaphore| semaphore.id).collect(),
+            buffers: binding.buffers
+                .iter()
+                .map(|&(buffer, binds)| (buffer.id, record_binds(binds)))
+                .collect(),
+            image_opaques: binding.image_opaques
+                .iter()
+                .map(|&(image, binds)| (image.id, record_binds(binds)))
+                .collect(),
+            images: binding.images
+                .iter()
+                .map(|&(image, binds)| {
+                    let binds = binds
+                        .iter()
+                        .map(|bind| f::SparseImageBind {
+                            subresource: bind.subresource,
+                            offset: bind.offset,
+                            extent: bind.extent,
+                            memory: bind.memory.map(|(memory, offset)| (memory.id, offset)),
+                        })
+                        .collect();
+                    (image.id, binds)
+                })
+                .collect(),
+            fence: fence.map(|fence| fence.id),
+        });
+        self.recorder.flush();
+
+        let buffer_binds = binding.buffers
+            .iter()
+            .map(|&(buffer, binds)| (&buffer.raw, unwrap_binds(binds)))
+            .collect::<Vec<_>>();
+        let image_opaque_binds = binding.image_opaques
+            .iter()
+            .map(|&(image, binds)| (&image.raw, unwrap_binds(binds)))
+            .collect::<Vec<_>>();
+        let image_binds = binding.images
+            .iter()
+            .map(|&(image, binds)| (&image.raw, unwrap_image_binds(binds)))
+            .collect::<Vec<_>>();
+
+        let buffers = buffer_binds
+            .iter()
+            .map(|&(buffer, ref binds)| (buffer, &binds[..]))
+            .collect::<Vec<_>>();
+        let image_opaques = image_opaque_binds
+            .iter()
+            .map(|&(image, ref binds)| (image, &binds[..]))
+            .collect::<Vec<_>>();
+        let images = image_binds
+            .iter()
+            .map(|&(image, ref binds)| (image, &binds[..]))
+            .collect::<Vec<_>>();
+        let wait_semaphores = binding.wait_semaphores
+            .iter()
+            .map(|semaphore| &semaphore.raw)
+            .collect::<Vec<_>>();
+        let signal_semaphores = binding.signal_semaphores
+            .iter()
+            .map(|semaphore| &semaphore.raw)
+            .collect::<Vec<_>>();
+        let raw_binding = RawSparseBinding {
+            wait_semaphores: &wait_semaphores,
+            signal_semaphores: &signal_semaphores,
+            buffers: &buffers,
+            image_opaques: &image_opaques,
+            images: &images,
+        };
+        self.raw.bind_sparse(raw_binding, fence.map(|fence| &fence.raw));
+    }
+
+    fn present<IS, S, IW>(&mut self, swapchains: IS, wait_semaphores: IW) -> Result<(), ()>
+    where
+        IS: IntoIterator<Item = (S, hal::SwapImageIndex)>,
+        S: Borrow<Swapchain<B>>,
+        IW: IntoIterator,
+        IW::Item: Borrow<n::Semaphore<B>>,
+    {
+        let swapchains = swapchains.into_iter().collect::<Vec<_>>();
+        let wait_semaphores = wait_semaphores.into_iter().collect::<Vec<_>>();
+        self.recorder.record(Call::Present {
+            queue: self.id,
+            swapchains: swapchains
+                .iter()
+                .map(|&(ref swapchain, index)| (swapchain.borrow().id, index))
+                .collect(),
+            wait_semaphores: wait_semaphores.iter().map(|semaphore| semaphore.borrow().id).collect(),
+        });
+        self.recorder.flush();
+        self.raw.present(
+            swapchains.iter().map(|&(ref swapchain, index)| (&swapchain.borrow().raw, index)),
+            wait_semaphores.iter().map(|semaphore| &semaphore.borrow().raw),
+        )
+    }
+
+    fn wait_idle(&self) -> Result<(), error::HostExecutionError> {
+        self.recorder.record(Call::QueueWaitIdle(self.id));
+        self.raw.wait_idle()
+    }
+}
@@ -0,0 +1,152 @@
+//! Serialization of the calls into a capture.
+
+use std::fmt;
+use std::io::Write;
+use std::ops::Range;
+use std::slice;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bincode;
+use hal::backend::FastHashMap;
+
+use format::{self, Call, Id};
+
+
+/// Mapped range of a memory, along with the contents last recorded for it.
+struct Mapping {
+    ptr: *mut u8,
+    offset: u64,
+    shadow: Vec<u8>,
+}
+
+// The pointer is only dereferenced while the memory is mapped, from behind the lock.
+unsafe impl Send for Mapping {}
+
+impl Mapping {
+    fn contents(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr, self.shadow.len()) }
+    }
+}
+
+/// Destination of the captured calls, shared by all the captured objects.
+pub struct Recorder {
+    writer: Mutex<Box<dyn Write + Send>>,
+    next_id: AtomicUsize,
+    mappings: Mutex<FastHashMap<Id, Mapping>>,
+}
+
+impl Recorder {
+    pub fn new<W: Write + Send + 'static>(mut writer: W) -> Result<Self, bincode::Error> {
+        format::write_header(&mut writer)?;
+        Ok(Recorder {
+            writer: Mutex::new(Box::new(writer)),
+            next_id: AtomicUsize::new(0),
+            mappings: Mutex::new(FastHashMap::default()),
+        })
+    }
+
+    /// Allocate the identifier of a new object.
+    pub fn alloc_id(&self) -> Id {
+        self.next_id.fetch_add(1, Ordering::Relaxed) as Id
+    }
+
+    pub fn record(&self, call: Call) {
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(e) = format::write_call(&mut *writer, &call) {
+            error!("Failed to record a call: {}", e);
+        }
+    }
+
+    /// Flush the recorded calls, so that they survive a crash of the application.
+    pub fn flush(&self) {
+        if let Err(e) = self.writer.lock().unwrap().flush() {
+            error!("Failed to flush the capture: {}", e);
+        }
+    }
+
+    /// Start tracking the contents of `range` of a memory, mapped at `ptr`.
+    ///
+    /// The whole range is recorded once, so that a replay starts from the
+    /// same contents; later snapshots only record what changed since.
+    pub fn map(&self, memory: Id, ptr: *mut u8, range: Range<u64>) {
+        let contents = unsafe { slice::from_raw_parts(ptr, (range.end - range.start) as usize) };
+        self.record(Call::WriteMemory {
+            memory,
+            offset: range.start,
+            data: contents.to_vec(),
+        });
+        let mapping = Mapping {
+            ptr,
+            offset: range.start,
+            shadow: contents.to_vec(),
+        };
+        self.mappings.lock().unwrap().insert(memory, mapping);
+    }
+
+    /// Record the last contents of a memory and stop tracking it.
+    pub fn unmap(&self, memory: Id) {
+        let mut mappings = self.mappings.lock().unwrap();
+        if let Some(mut mapping) = mappings.remove(&memory) {
+            self.record_contents(memory, &mut mapping);
+        }
+    }
+
+    /// Record the contents of a mapped memory written since the last snapshot.
+    pub fn snapshot(&self, memory: Id) {
+        let mut mappings = self.mappings.lock().unwrap();
+        if let Some(mapping) = mappings.get_mut(&memory) {
+            self.record_contents(memory, mapping);
+        }
+    }
+
+    /// Record the contents written to all the mapped memories since the last snapshot.
+    pub fn snapshot_all(&self) {
+        let mut mappings = self.mappings.lock().unwrap();
+        for (&memory, mapping) in mappings.iter_mut() {
+            self.record_contents(memory, mapping);
+        }
+    }
+
+    /// Accept the current contents of a mapped memory without recording them,
+    /// after they have been written by the device.
+    pub fn refresh(&self, memory: Id) {
+        let mut mappings = self.mappings.lock().unwrap();
+        if let Some(mapping) = mappings.get_mut(&memory) {
+            mapping.shadow = mapping.contents().to_vec();
+        }
+    }
+
+    /// Stop tracking a memory without recording its contents.
+    pub fn forget(&self, memory: Id) {
+        self.mappings.lock().unwrap().remove(&memory);
+    }
+
+    fn record_contents(&self, memory: Id, mapping: &mut Mapping) {
+        let (start, end) = {
+            let contents = mapping.contents();
+            let changed = |(i, (a, b)): (usize, (&u8, &u8))| if a != b { Some(i) } else { None };
+            let start = contents.iter().zip(&mapping.shadow).enumerate().filter_map(changed).next();
+            let end = contents.iter().zip(&mapping.shadow).enumerate().rev().filter_map(changed).next();
+            match (start, end) {
+                (Some(start), Some(end)) => (start, end + 1),
+                _ => return,
+            }
+        };
+        let data = mapping.contents()[start .. end].to_vec();
+        mapping.shadow[start .. end].copy_from_slice(&data);
+        self.record(Call::WriteMemory {
+            memory,
+            offset: mapping.offset + start as u64,
+            data,
+        });
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("next_id", &self.next_id)
+            .finish()
+    }
+}
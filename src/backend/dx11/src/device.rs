@@ -727,10 +727,15 @@ impl hal::Device<Backend> for Device {
         }
     }
 
-    fn create_pipeline_cache(&self) -> () {
+    fn create_pipeline_cache(&self, _data: Option<&[u8]>) -> () {
         ()
     }
 
+    fn get_pipeline_cache_data(&self, _cache: &()) -> Result<Vec<u8>, device::OutOfMemory> {
+        //empty
+        Ok(Vec::new())
+    }
+
     fn destroy_pipeline_cache(&self, _: ()) {
         //empty
     }
@@ -1916,7 +1921,7 @@ impl hal::Device<Backend> for Device {
         }
     }
 
-    fn create_query_pool(&self, _query_ty: query::QueryType, _count: query::QueryId) -> QueryPool {
+    fn create_query_pool(&self, _query_ty: query::QueryType, _count: query::QueryId) -> Result<QueryPool, query::Error> {
         unimplemented!()
     }
 
@@ -1924,6 +1929,17 @@ impl hal::Device<Backend> for Device {
         unimplemented!()
     }
 
+    fn get_query_pool_results(
+        &self,
+        _pool: &QueryPool,
+        _queries: Range<query::QueryId>,
+        _data: &mut [u8],
+        _stride: buffer::Offset,
+        _flags: query::ResultFlags,
+    ) -> Result<bool, error::HostExecutionError> {
+        unimplemented!()
+    }
+
     fn destroy_shader_module(&self, _shader_lib: ShaderModule) {
     }
 
@@ -1958,6 +1974,29 @@ impl hal::Device<Backend> for Device {
         // unimplemented!()
     }
 
+    fn create_sparse_buffer(
+        &self, _size: u64, _usage: buffer::Usage, _sparse: memory::SparseFlags,
+    ) -> Result<Buffer, buffer::CreationError> {
+        unimplemented!()
+    }
+
+    fn get_sparse_buffer_requirements(&self, _buffer: &Buffer) -> memory::Requirements {
+        unimplemented!()
+    }
+
+    fn create_sparse_image(
+        &self, _kind: image::Kind, _mip_levels: image::Level, _format: format::Format,
+        _usage: image::Usage, _storage_flags: image::StorageFlags, _sparse: memory::SparseFlags,
+    ) -> Result<Image, image::CreationError> {
+        unimplemented!()
+    }
+
+    fn get_sparse_image_requirements(
+        &self, _image: &Image,
+    ) -> (memory::Requirements, Vec<image::SparseRequirements>) {
+        unimplemented!()
+    }
+
     fn destroy_image_view(&self, _view: ImageView) {
         //unimplemented!()
     }
@@ -1973,6 +2012,26 @@ impl hal::Device<Backend> for Device {
         //unimplemented!()
     }
 
+    fn create_event(&self) -> () {
+        unimplemented!()
+    }
+
+    fn get_event_status(&self, _event: &()) -> bool {
+        unimplemented!()
+    }
+
+    fn set_event(&self, _event: &()) {
+        unimplemented!()
+    }
+
+    fn reset_event(&self, _event: &()) {
+        unimplemented!()
+    }
+
+    fn destroy_event(&self, _event: ()) {
+        unimplemented!()
+    }
+
     fn destroy_fence(&self, _fence: Fence) {
         // unimplemented!()
     }
@@ -442,9 +442,13 @@ fn get_feature_level(adapter: *mut IDXGIAdapter) -> d3dcommon::D3D_FEATURE_LEVEL
 
 // TODO: PhysicalDevice
 impl hal::PhysicalDevice<Backend> for PhysicalDevice {
-    fn open(&self, families: &[(&QueueFamily, &[hal::QueuePriority])])
+    fn open(&self, families: &[(&QueueFamily, &[hal::QueuePriority])], requested_features: hal::Features)
         -> Result<hal::Gpu<Backend>, error::DeviceCreationError>
     {
+        if !self.features().contains(requested_features) {
+            return Err(error::DeviceCreationError::MissingFeature);
+        }
+
         let (device, cxt) = {
             let feature_level = get_feature_level(self.adapter.as_raw());
             let mut returned_level = d3dcommon::D3D_FEATURE_LEVEL_9_1;
@@ -616,6 +620,10 @@ impl hal::PhysicalDevice<Backend> for PhysicalDevice {
         self.limits
     }
 
+    fn timestamp_period(&self) -> f32 {
+        1.0 // TODO
+    }
+
 }
 
 pub struct Surface {
@@ -754,6 +762,10 @@ impl hal::queue::RawCommandQueue<Backend> for CommandQueue {
         }
     }
 
+    unsafe fn bind_sparse(&mut self, _binding: hal::queue::RawSparseBinding<Backend>, _fence: Option<&Fence>) {
+        unimplemented!()
+    }
+
     fn present<IS, S, IW>(&mut self, swapchains: IS, _wait_semaphores: IW) -> Result<(), ()>
     where
         IS: IntoIterator<Item = (S, SwapImageIndex)>,
@@ -1133,6 +1145,28 @@ impl hal::command::RawCommandBuffer<Backend> for CommandBuffer {
         // unimplemented!()
     }
 
+    fn set_event(&mut self, _event: &(), _stages: pso::PipelineStage) {
+        unimplemented!()
+    }
+
+    fn reset_event(&mut self, _event: &(), _stages: pso::PipelineStage) {
+        unimplemented!()
+    }
+
+    fn wait_events<'a, I, J>(
+        &mut self,
+        _events: I,
+        _stages: Range<pso::PipelineStage>,
+        _barriers: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<()>,
+        J: IntoIterator,
+        J::Item: Borrow<memory::Barrier<'a, Backend>>,
+    {
+        unimplemented!()
+    }
+
     fn clear_image<T>(&mut self, image: &Image, _: image::Layout, color: command::ClearColorRaw, depth_stencil: command::ClearDepthStencilRaw, subresource_ranges: T)
     where
         T: IntoIterator,
@@ -1597,6 +1631,18 @@ impl hal::command::RawCommandBuffer<Backend> for CommandBuffer {
         unimplemented!()
     }
 
+    fn copy_query_pool_results(
+        &mut self,
+        _pool: &QueryPool,
+        _queries: Range<query::QueryId>,
+        _buffer: &Buffer,
+        _offset: buffer::Offset,
+        _stride: buffer::Offset,
+        _flags: query::ResultFlags,
+    ) {
+        unimplemented!()
+    }
+
     fn push_graphics_constants(&mut self, _layout: &PipelineLayout, _stages: pso::ShaderStageFlags, _offset: u32, _constants: &[u32]) {
         // unimplemented!()
     }
@@ -2434,6 +2480,7 @@ impl hal::Backend for Backend {
 
     type Fence = Fence;
     type Semaphore = Semaphore;
+    type Event = ();
     type QueryPool = QueryPool;
 }
 
@@ -1093,7 +1093,7 @@ impl com::RawCommandBuffer<Backend> for CommandBuffer {
                     };
                     raw_barriers.push(bar);
                 }
-                memory::Barrier::Buffer { ref states, target } => {
+                memory::Barrier::Buffer { ref states, target, .. } => {
                     let state_src = conv::map_buffer_resource_state(states.start);
                     let state_dst = conv::map_buffer_resource_state(states.end);
 
@@ -1112,7 +1112,7 @@ impl com::RawCommandBuffer<Backend> for CommandBuffer {
 
                     raw_barriers.push(bar);
                 }
-                memory::Barrier::Image { ref states, target, ref range } => {
+                memory::Barrier::Image { ref states, target, ref range, .. } => {
                     let _ = range; //TODO: use subresource range
                     let state_src = conv::map_image_resource_state(states.start.0, states.start.1);
                     let state_dst = conv::map_image_resource_state(states.end.0, states.end.1);
@@ -1863,6 +1863,28 @@ impl com::RawCommandBuffer<Backend> for CommandBuffer {
         }
     }
 
+    fn set_event(&mut self, _event: &(), _stages: pso::PipelineStage) {
+        unimplemented!()
+    }
+
+    fn reset_event(&mut self, _event: &(), _stages: pso::PipelineStage) {
+        unimplemented!()
+    }
+
+    fn wait_events<'a, I, J>(
+        &mut self,
+        _events: I,
+        _stages: Range<pso::PipelineStage>,
+        _barriers: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<()>,
+        J: IntoIterator,
+        J::Item: Borrow<memory::Barrier<'a, Backend>>,
+    {
+        unimplemented!()
+    }
+
     fn fill_buffer<R>(
         &mut self,
         buffer: &n::Buffer,
@@ -2388,6 +2410,18 @@ impl com::RawCommandBuffer<Backend> for CommandBuffer {
         }
     }
 
+    fn copy_query_pool_results(
+        &mut self,
+        _pool: &n::QueryPool,
+        _queries: Range<query::QueryId>,
+        _buffer: &n::Buffer,
+        _offset: buffer::Offset,
+        _stride: buffer::Offset,
+        _flags: query::ResultFlags,
+    ) {
+        unimplemented!()
+    }
+
     fn push_graphics_constants(
         &mut self,
         layout: &n::PipelineLayout,
@@ -1470,10 +1470,15 @@ impl d::Device<B> for Device {
         }
     }
 
-    fn create_pipeline_cache(&self) -> () {
+    fn create_pipeline_cache(&self, _data: Option<&[u8]>) -> () {
         ()
     }
 
+    fn get_pipeline_cache_data(&self, _cache: &()) -> Result<Vec<u8>, d::OutOfMemory> {
+        //empty
+        Ok(Vec::new())
+    }
+
     fn destroy_pipeline_cache(&self, _: ()) {
         //empty
     }
@@ -2856,7 +2861,9 @@ impl d::Device<B> for Device {
         }
     }
 
-    fn create_query_pool(&self, query_ty: query::QueryType, count: query::QueryId) -> n::QueryPool {
+    fn create_query_pool(
+        &self, query_ty: query::QueryType, count: query::QueryId
+    ) -> Result<n::QueryPool, query::Error> {
         let heap_ty = match query_ty {
             query::QueryType::Occlusion =>
                 d3d12::D3D12_QUERY_HEAP_TYPE_OCCLUSION,
@@ -2881,16 +2888,27 @@ impl d::Device<B> for Device {
             )
         });
 
-        n::QueryPool {
+        Ok(n::QueryPool {
             raw: unsafe { ComPtr::from_raw(handle as *mut _) },
             ty: heap_ty,
-        }
+        })
     }
 
     fn destroy_query_pool(&self, _pool: n::QueryPool) {
         // Just drop
     }
 
+    fn get_query_pool_results(
+        &self,
+        _pool: &n::QueryPool,
+        _queries: Range<query::QueryId>,
+        _data: &mut [u8],
+        _stride: buffer::Offset,
+        _flags: query::ResultFlags,
+    ) -> Result<bool, error::HostExecutionError> {
+        unimplemented!()
+    }
+
     fn destroy_shader_module(&self, shader_lib: n::ShaderModule) {
         if let n::ShaderModule::Compiled(shaders) = shader_lib {
             for (_, _blob) in shaders {
@@ -2931,6 +2949,29 @@ impl d::Device<B> for Device {
         unsafe { (*image.resource).Release(); }
     }
 
+    fn create_sparse_buffer(
+        &self, _size: u64, _usage: buffer::Usage, _sparse: memory::SparseFlags,
+    ) -> Result<n::Buffer, buffer::CreationError> {
+        unimplemented!()
+    }
+
+    fn get_sparse_buffer_requirements(&self, _buffer: &n::Buffer) -> memory::Requirements {
+        unimplemented!()
+    }
+
+    fn create_sparse_image(
+        &self, _kind: image::Kind, _mip_levels: image::Level, _format: format::Format,
+        _usage: image::Usage, _storage_flags: image::StorageFlags, _sparse: memory::SparseFlags,
+    ) -> Result<n::Image, image::CreationError> {
+        unimplemented!()
+    }
+
+    fn get_sparse_image_requirements(
+        &self, _image: &n::Image,
+    ) -> (memory::Requirements, Vec<image::SparseRequirements>) {
+        unimplemented!()
+    }
+
     fn destroy_image_view(&self, _view: n::ImageView) {
         // Just drop
     }
@@ -2948,6 +2989,26 @@ impl d::Device<B> for Device {
         // Just drop
     }
 
+    fn create_event(&self) -> () {
+        unimplemented!()
+    }
+
+    fn get_event_status(&self, _event: &()) -> bool {
+        unimplemented!()
+    }
+
+    fn set_event(&self, _event: &()) {
+        unimplemented!()
+    }
+
+    fn reset_event(&self, _event: &()) {
+        unimplemented!()
+    }
+
+    fn destroy_event(&self, _event: ()) {
+        unimplemented!()
+    }
+
     fn destroy_fence(&self, _fence: n::Fence) {
         // Just drop, ComPtr backed
     }
@@ -193,8 +193,12 @@ unsafe impl Sync for PhysicalDevice { }
 
 impl hal::PhysicalDevice<Backend> for PhysicalDevice {
     fn open(
-        &self, families: &[(&QueueFamily, &[hal::QueuePriority])]
+        &self, families: &[(&QueueFamily, &[hal::QueuePriority])], requested_features: Features,
     ) -> Result<hal::Gpu<Backend>, error::DeviceCreationError> {
+        if !self.features().contains(requested_features) {
+            return Err(error::DeviceCreationError::MissingFeature);
+        }
+
         let lock = self.is_open.try_lock();
         let mut open_guard = match lock {
             Ok(inner) => inner,
@@ -425,6 +429,7 @@ impl hal::PhysicalDevice<Backend> for PhysicalDevice {
 
     fn features(&self) -> Features { self.features }
     fn limits(&self) -> Limits { self.limits }
+    fn timestamp_period(&self) -> f32 { 1.0 } // TODO: query the command queue timestamp frequency
 }
 
 #[derive(Clone)]
@@ -466,6 +471,10 @@ impl hal::queue::RawCommandQueue<Backend> for CommandQueue {
         }
     }
 
+    unsafe fn bind_sparse(&mut self, _binding: hal::queue::RawSparseBinding<Backend>, _fence: Option<&native::Fence>) {
+        unimplemented!()
+    }
+
     fn present<IS, S, IW>(&mut self, swapchains: IS, _wait_semaphores: IW) -> Result<(), ()>
     where
         IS: IntoIterator<Item = (S, SwapImageIndex)>,
@@ -1106,6 +1115,7 @@ impl hal::Backend for Backend {
 
     type Fence = native::Fence;
     type Semaphore = native::Semaphore;
+    type Event = ();
     type QueryPool = native::QueryPool;
 }
 
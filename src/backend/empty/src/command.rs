@@ -0,0 +1,580 @@
+use hal::{buffer, command, image, memory, pso, query};
+use hal::range::RangeArg;
+
+use std::borrow::Borrow;
+use std::ops::Range;
+use std::sync::Arc;
+
+use interpreter::Program;
+use native as n;
+use Backend;
+
+
+/// A recorded command, executed by the queue on submission.
+#[derive(Clone)]
+pub enum Command {
+    CopyBuffer {
+        src: n::Buffer,
+        dst: n::Buffer,
+        regions: Vec<command::BufferCopy>,
+    },
+    CopyImage {
+        src: n::Image,
+        dst: n::Image,
+        regions: Vec<command::ImageCopy>,
+    },
+    CopyBufferToImage {
+        src: n::Buffer,
+        dst: n::Image,
+        regions: Vec<command::BufferImageCopy>,
+    },
+    CopyImageToBuffer {
+        src: n::Image,
+        dst: n::Buffer,
+        regions: Vec<command::BufferImageCopy>,
+    },
+    BlitImage {
+        src: n::Image,
+        dst: n::Image,
+        filter: image::Filter,
+        regions: Vec<command::ImageBlit>,
+    },
+    FillBuffer {
+        dst: n::Buffer,
+        range: Range<buffer::Offset>,
+        data: u32,
+    },
+    UpdateBuffer {
+        dst: n::Buffer,
+        offset: buffer::Offset,
+        data: Vec<u8>,
+    },
+    ClearImage {
+        image: n::Image,
+        color: command::ClearColorRaw,
+        depth_stencil: command::ClearDepthStencilRaw,
+        ranges: Vec<image::SubresourceRange>,
+    },
+    BindComputePipeline(Arc<Program>),
+    BindComputeDescriptorSets {
+        first_set: usize,
+        /// Contents of the sets at the time of recording, with the dynamic
+        /// offsets already applied.
+        sets: Vec<Vec<n::DescriptorBinding>>,
+    },
+    PushComputeConstants {
+        offset: u32,
+        constants: Vec<u32>,
+    },
+    SetEvent {
+        event: n::Event,
+        signaled: bool,
+    },
+    Dispatch(::hal::WorkGroupCount),
+    DispatchIndirect {
+        buffer: n::Buffer,
+        offset: buffer::Offset,
+    },
+}
+
+/// Command pool, allocating command buffers which record into host memory.
+#[derive(Debug, Default)]
+pub struct RawCommandPool;
+
+impl ::hal::pool::RawCommandPool<Backend> for RawCommandPool {
+    fn reset(&mut self) {
+        // Command buffers own their storage, which is cleared on `begin`.
+    }
+
+    fn allocate(&mut self, num: usize, _level: command::RawLevel) -> Vec<RawCommandBuffer> {
+        (0 .. num)
+            .map(|_| RawCommandBuffer::default())
+            .collect()
+    }
+
+    unsafe fn free(&mut self, _buffers: Vec<RawCommandBuffer>) {
+    }
+}
+
+/// Command buffer recording a list of commands for the CPU to execute.
+#[derive(Clone, Default)]
+pub struct RawCommandBuffer {
+    pub(crate) commands: Vec<Command>,
+}
+
+impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
+    fn begin(&mut self, _: command::CommandBufferFlags, _: command::CommandBufferInheritanceInfo<Backend>) {
+        self.commands.clear();
+    }
+
+    fn finish(&mut self) {
+    }
+
+    fn reset(&mut self, _: bool) {
+        self.commands.clear();
+    }
+
+    fn pipeline_barrier<'a, T>(
+        &mut self,
+        _: Range<pso::PipelineStage>,
+        _: memory::Dependencies,
+        _: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<memory::Barrier<'a, Backend>>,
+    {
+        // Commands are executed in order on a single thread.
+    }
+
+    fn set_event(&mut self, event: &n::Event, _: pso::PipelineStage) {
+        self.commands.push(Command::SetEvent {
+            event: event.clone(),
+            signaled: true,
+        });
+    }
+
+    fn reset_event(&mut self, event: &n::Event, _: pso::PipelineStage) {
+        self.commands.push(Command::SetEvent {
+            event: event.clone(),
+            signaled: false,
+        });
+    }
+
+    fn wait_events<'a, I, J>(
+        &mut self,
+        _: I,
+        _: Range<pso::PipelineStage>,
+        _: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<n::Event>,
+        J: IntoIterator,
+        J::Item: Borrow<memory::Barrier<'a, Backend>>,
+    {
+        // Commands are executed in order on a single thread, the events
+        // can't be set by anything but the previous commands.
+    }
+
+    fn fill_buffer<R>(&mut self, buffer: &n::Buffer, range: R, data: u32)
+    where
+        R: RangeArg<buffer::Offset>,
+    {
+        let start = *range.start().unwrap_or(&0);
+        // Filling up to the end of the buffer rounds down to a multiple of 4 bytes.
+        let end = *range.end().unwrap_or(&(buffer.size & !3));
+        self.commands.push(Command::FillBuffer {
+            dst: buffer.clone(),
+            range: start .. end,
+            data,
+        });
+    }
+
+    fn update_buffer(&mut self, buffer: &n::Buffer, offset: buffer::Offset, data: &[u8]) {
+        self.commands.push(Command::UpdateBuffer {
+            dst: buffer.clone(),
+            offset,
+            data: data.to_vec(),
+        });
+    }
+
+    fn clear_image<T>(
+        &mut self,
+        image: &n::Image,
+        _: image::Layout,
+        color: command::ClearColorRaw,
+        depth_stencil: command::ClearDepthStencilRaw,
+        subresource_ranges: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<image::SubresourceRange>,
+    {
+        self.commands.push(Command::ClearImage {
+            image: image.clone(),
+            color,
+            depth_stencil,
+            ranges: subresource_ranges
+                .into_iter()
+                .map(|range| range.borrow().clone())
+                .collect(),
+        });
+    }
+
+    fn clear_attachments<T, U>(&mut self, _: T, _: U)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<command::AttachmentClear>,
+        U: IntoIterator,
+        U::Item: Borrow<pso::ClearRect>,
+    {
+        unimplemented!()
+    }
+
+    fn resolve_image<T>(
+        &mut self,
+        _: &n::Image,
+        _: image::Layout,
+        _: &n::Image,
+        _: image::Layout,
+        _: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<command::ImageResolve>,
+    {
+        unimplemented!()
+    }
+
+    fn blit_image<T>(
+        &mut self,
+        src: &n::Image,
+        _: image::Layout,
+        dst: &n::Image,
+        _: image::Layout,
+        filter: image::Filter,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<command::ImageBlit>,
+    {
+        self.commands.push(Command::BlitImage {
+            src: src.clone(),
+            dst: dst.clone(),
+            filter,
+            regions: regions
+                .into_iter()
+                .map(|region| region.borrow().clone())
+                .collect(),
+        });
+    }
+
+    fn bind_index_buffer(&mut self, _: buffer::IndexBufferView<Backend>) {
+        unimplemented!()
+    }
+
+    fn bind_vertex_buffers<I, T>(&mut self, _: u32, _: I)
+    where
+        I: IntoIterator<Item = (T, buffer::Offset)>,
+        T: Borrow<n::Buffer>,
+    {
+        unimplemented!()
+    }
+
+    fn set_viewports<T>(&mut self, _: u32, _: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::Viewport>,
+    {
+        unimplemented!()
+    }
+
+    fn set_scissors<T>(&mut self, _: u32, _: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::Rect>,
+    {
+        unimplemented!()
+    }
+
+    fn set_stencil_reference(&mut self, _: pso::Face, _: pso::StencilValue) {
+        unimplemented!()
+    }
+
+    fn set_stencil_read_mask(&mut self, _: pso::Face, _: pso::StencilValue) {
+        unimplemented!()
+    }
+
+    fn set_stencil_write_mask(&mut self, _: pso::Face, _: pso::StencilValue) {
+        unimplemented!()
+    }
+
+    fn set_blend_constants(&mut self, _: pso::ColorValue) {
+        unimplemented!()
+    }
+
+    fn set_depth_bounds(&mut self, _: Range<f32>) {
+        unimplemented!()
+    }
+
+    fn set_line_width(&mut self, _: f32) {
+        unimplemented!()
+    }
+
+    fn set_depth_bias(&mut self, _: pso::DepthBias) {
+        unimplemented!()
+    }
+
+    fn begin_render_pass<T>(
+        &mut self,
+        _: &(),
+        _: &(),
+        _: pso::Rect,
+        _: T,
+        _: command::SubpassContents,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<command::ClearValueRaw>,
+    {
+        unimplemented!()
+    }
+
+    fn next_subpass(&mut self, _: command::SubpassContents) {
+        unimplemented!()
+    }
+
+    fn end_render_pass(&mut self) {
+        unimplemented!()
+    }
+
+    fn bind_graphics_pipeline(&mut self, _: &()) {
+        unimplemented!()
+    }
+
+    fn bind_graphics_descriptor_sets<I, J>(&mut self, _: &(), _: usize, _: I, _: J)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::DescriptorSet>,
+        J: IntoIterator,
+        J::Item: Borrow<command::DescriptorSetOffset>,
+    {
+        unimplemented!()
+    }
+
+    fn bind_compute_pipeline(&mut self, pipeline: &n::ComputePipeline) {
+        self.commands.push(Command::BindComputePipeline(pipeline.program.clone()));
+    }
+
+    fn bind_compute_descriptor_sets<I, J>(&mut self, _: &(), first_set: usize, sets: I, offsets: J)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::DescriptorSet>,
+        J: IntoIterator,
+        J::Item: Borrow<command::DescriptorSetOffset>,
+    {
+        // Dynamic offsets are consumed in the order of the sets and bindings.
+        let mut offsets = offsets.into_iter().map(|offset| *offset.borrow() as buffer::Offset);
+        let sets = sets
+            .into_iter()
+            .map(|set| {
+                let mut bindings = set.borrow().bindings.lock().unwrap().clone();
+                for binding in &mut bindings {
+                    match binding.ty {
+                        pso::DescriptorType::UniformBufferDynamic |
+                        pso::DescriptorType::StorageBufferDynamic => {}
+                        _ => continue,
+                    }
+                    for descriptor in &mut binding.descriptors {
+                        let offset = offsets.next().expect("Missing dynamic offset");
+                        if let Some(ref mut descriptor) = *descriptor {
+                            descriptor.range.start += offset;
+                            descriptor.range.end += offset;
+                        }
+                    }
+                }
+                bindings
+            })
+            .collect();
+        self.commands.push(Command::BindComputeDescriptorSets {
+            first_set,
+            sets,
+        });
+    }
+
+    fn dispatch(&mut self, count: ::hal::WorkGroupCount) {
+        self.commands.push(Command::Dispatch(count));
+    }
+
+    fn dispatch_indirect(&mut self, buffer: &n::Buffer, offset: buffer::Offset) {
+        self.commands.push(Command::DispatchIndirect {
+            buffer: buffer.clone(),
+            offset,
+        });
+    }
+
+    fn copy_buffer<T>(&mut self, src: &n::Buffer, dst: &n::Buffer, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<command::BufferCopy>,
+    {
+        self.commands.push(Command::CopyBuffer {
+            src: src.clone(),
+            dst: dst.clone(),
+            regions: regions
+                .into_iter()
+                .map(|region| *region.borrow())
+                .collect(),
+        });
+    }
+
+    fn copy_image<T>(
+        &mut self,
+        src: &n::Image,
+        _: image::Layout,
+        dst: &n::Image,
+        _: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<command::ImageCopy>,
+    {
+        self.commands.push(Command::CopyImage {
+            src: src.clone(),
+            dst: dst.clone(),
+            regions: regions
+                .into_iter()
+                .map(|region| region.borrow().clone())
+                .collect(),
+        });
+    }
+
+    fn copy_buffer_to_image<T>(
+        &mut self,
+        src: &n::Buffer,
+        dst: &n::Image,
+        _: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<command::BufferImageCopy>,
+    {
+        self.commands.push(Command::CopyBufferToImage {
+            src: src.clone(),
+            dst: dst.clone(),
+            regions: regions
+                .into_iter()
+                .map(|region| region.borrow().clone())
+                .collect(),
+        });
+    }
+
+    fn copy_image_to_buffer<T>(
+        &mut self,
+        src: &n::Image,
+        _: image::Layout,
+        dst: &n::Buffer,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<command::BufferImageCopy>,
+    {
+        self.commands.push(Command::CopyImageToBuffer {
+            src: src.clone(),
+            dst: dst.clone(),
+            regions: regions
+                .into_iter()
+                .map(|region| region.borrow().clone())
+                .collect(),
+        });
+    }
+
+    fn draw(&mut self,
+        _: Range<::hal::VertexCount>,
+        _: Range<::hal::InstanceCount>,
+    ) {
+        unimplemented!()
+    }
+
+    fn draw_indexed(
+        &mut self,
+        _: Range<::hal::IndexCount>,
+        _: ::hal::VertexOffset,
+        _: Range<::hal::InstanceCount>,
+    ) {
+        unimplemented!()
+    }
+
+    fn draw_indirect(
+        &mut self,
+        _: &n::Buffer,
+        _: buffer::Offset,
+        _: ::hal::DrawCount,
+        _: u32,
+    ) {
+        unimplemented!()
+    }
+
+    fn draw_indexed_indirect(
+        &mut self,
+        _: &n::Buffer,
+        _: buffer::Offset,
+        _: ::hal::DrawCount,
+        _: u32,
+    ) {
+        unimplemented!()
+    }
+
+    fn begin_query(
+        &mut self,
+        _: query::Query<Backend>,
+        _: query::QueryControl,
+    ) {
+        unimplemented!()
+    }
+
+    fn end_query(
+        &mut self,
+        _: query::Query<Backend>,
+    ) {
+        unimplemented!()
+    }
+
+    fn reset_query_pool(
+        &mut self,
+        _: &(),
+        _: Range<query::QueryId>,
+    ) {
+        unimplemented!()
+    }
+
+    fn write_timestamp(
+        &mut self,
+        _: pso::PipelineStage,
+        _: query::Query<Backend>,
+    ) {
+        unimplemented!()
+    }
+
+    fn copy_query_pool_results(
+        &mut self,
+        _: &(),
+        _: Range<query::QueryId>,
+        _: &n::Buffer,
+        _: buffer::Offset,
+        _: buffer::Offset,
+        _: query::ResultFlags,
+    ) {
+        unimplemented!()
+    }
+
+    fn push_graphics_constants(
+        &mut self,
+        _: &(),
+        _: pso::ShaderStageFlags,
+        _: u32,
+        _: &[u32],
+    ) {
+        unimplemented!()
+    }
+
+    fn push_compute_constants(
+        &mut self,
+        _: &(),
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.commands.push(Command::PushComputeConstants {
+            offset,
+            constants: constants.to_vec(),
+        });
+    }
+
+    fn execute_commands<I>(
+        &mut self,
+        buffers: I,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<RawCommandBuffer>
+    {
+        for buffer in buffers {
+            self.commands.extend(buffer.borrow().commands.iter().cloned());
+        }
+    }
+}
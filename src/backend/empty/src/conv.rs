@@ -0,0 +1,354 @@
+//! Conversions between texel memory and channel values.
+//!
+//! Only uncompressed formats, either with uniformly sized channels or packed
+//! into a single integer, and the depth/stencil formats, can be interpreted.
+//! Every other format can still be copied around, since that doesn't require
+//! looking at the texels.
+
+use hal::format::{Aspects, ChannelType, Format, SurfaceType};
+use hal::command::{ClearColorRaw, ClearDepthStencilRaw};
+
+
+/// In-memory arrangement of the channels of a color texel.
+#[derive(Clone, Copy, Debug)]
+pub struct ChannelLayout {
+    /// Size of a single channel in bytes.
+    pub size: usize,
+    /// Logical channel (RGBA index) stored at each memory position.
+    pub order: &'static [usize],
+}
+
+pub fn channel_layout(surface: SurfaceType) -> Option<ChannelLayout> {
+    use self::SurfaceType::*;
+    let (size, order): (usize, &'static [usize]) = match surface {
+        R8 => (1, &[0]),
+        R8_G8 => (1, &[0, 1]),
+        R8_G8_B8 => (1, &[0, 1, 2]),
+        B8_G8_R8 => (1, &[2, 1, 0]),
+        R8_G8_B8_A8 => (1, &[0, 1, 2, 3]),
+        B8_G8_R8_A8 => (1, &[2, 1, 0, 3]),
+        R16 => (2, &[0]),
+        R16_G16 => (2, &[0, 1]),
+        R16_G16_B16 => (2, &[0, 1, 2]),
+        R16_G16_B16_A16 => (2, &[0, 1, 2, 3]),
+        R32 => (4, &[0]),
+        R32_G32 => (4, &[0, 1]),
+        R32_G32_B32 => (4, &[0, 1, 2]),
+        R32_G32_B32_A32 => (4, &[0, 1, 2, 3]),
+        R64 => (8, &[0]),
+        R64_G64 => (8, &[0, 1]),
+        R64_G64_B64 => (8, &[0, 1, 2]),
+        R64_G64_B64_A64 => (8, &[0, 1, 2, 3]),
+        _ => return None,
+    };
+    Some(ChannelLayout { size, order })
+}
+
+/// Logical channel (RGBA index) and bit width of the fields of a packed
+/// texel, starting from the least significant bit.
+pub fn packed_layout(surface: SurfaceType) -> Option<&'static [(usize, u32)]> {
+    use self::SurfaceType::*;
+    Some(match surface {
+        R4_G4 => &[(1, 4), (0, 4)],
+        R4_G4_B4_A4 => &[(3, 4), (2, 4), (1, 4), (0, 4)],
+        B4_G4_R4_A4 => &[(3, 4), (0, 4), (1, 4), (2, 4)],
+        R5_G6_B5 => &[(2, 5), (1, 6), (0, 5)],
+        B5_G6_R5 => &[(0, 5), (1, 6), (2, 5)],
+        R5_G5_B5_A1 => &[(3, 1), (2, 5), (1, 5), (0, 5)],
+        B5_G5_R5_A1 => &[(3, 1), (0, 5), (1, 5), (2, 5)],
+        A1_R5_G5_B5 => &[(2, 5), (1, 5), (0, 5), (3, 1)],
+        A8_B8_G8_R8 => &[(0, 8), (1, 8), (2, 8), (3, 8)],
+        A2_R10_G10_B10 => &[(2, 10), (1, 10), (0, 10), (3, 2)],
+        A2_B10_G10_R10 => &[(0, 10), (1, 10), (2, 10), (3, 2)],
+        B10_G11_R11 => &[(0, 11), (1, 11), (2, 10)],
+        _ => return None,
+    })
+}
+
+/// Check if texels of the format can be decoded and encoded.
+pub fn is_interpretable(format: Format) -> bool {
+    let base = format.base_format();
+    let desc = base.0.desc();
+    if desc.aspects.contains(Aspects::COLOR) {
+        match (channel_layout(base.0), base.1) {
+            (Some(layout), ChannelType::Float) |
+            (Some(layout), ChannelType::Ufloat) => layout.size != 1,
+            (Some(_), _) => true,
+            (None, _) => base.0 == SurfaceType::E5_B9_G9_R9 || packed_layout(base.0).is_some(),
+        }
+    } else {
+        true
+    }
+}
+
+fn read_uint(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u64)
+}
+
+fn write_uint(bytes: &mut [u8], mut value: u64) {
+    for b in bytes {
+        *b = value as u8;
+        value >>= 8;
+    }
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half >> 15) as u32) << 31;
+    let exp = ((half >> 10) & 0x1F) as u32;
+    let mantissa = (half & 0x3FF) as u32;
+    let bits = match exp {
+        0 if mantissa == 0 => sign,
+        0 => {
+            // subnormal, renormalize
+            let shift = mantissa.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | (((mantissa << shift) & 0x3FF) << 13)
+        }
+        0x1F => sign | 0x7F80_0000 | (mantissa << 13),
+        _ => sign | ((exp + 112) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+    if exp == 0xFF {
+        return sign | 0x7C00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let exp = exp - 127 + 15;
+    if exp >= 0x1F {
+        sign | 0x7C00
+    } else if exp <= 0 {
+        if exp < -10 {
+            sign
+        } else {
+            let mantissa = mantissa | 0x80_0000;
+            let shift = (14 - exp) as u32;
+            let round = (mantissa >> (shift - 1)) & 1;
+            sign | ((mantissa >> shift) + round) as u16
+        }
+    } else {
+        let round = (mantissa >> 12) & 1;
+        (sign | ((exp as u16) << 10) | (mantissa >> 13) as u16) + round as u16
+    }
+}
+
+fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decode the `bits` wide value of a single channel.
+fn decode_channel(raw: u64, bits: u32, ty: ChannelType) -> f64 {
+    let max_unsigned = (!0u64 >> (64 - bits)) as f64;
+    let max_signed = (!0u64 >> (65 - bits)) as f64;
+    match ty {
+        ChannelType::Unorm => raw as f64 / max_unsigned,
+        ChannelType::Srgb => srgb_to_linear(raw as f64 / max_unsigned),
+        ChannelType::Inorm => (sign_extend(raw, bits) as f64 / max_signed).max(-1.0),
+        ChannelType::Uint | ChannelType::Uscaled => raw as f64,
+        ChannelType::Int | ChannelType::Iscaled => sign_extend(raw, bits) as f64,
+        ChannelType::Float | ChannelType::Ufloat => match bits {
+            // unsigned small floats share the exponent of the half floats
+            10 | 11 => f16_to_f32((raw << (15 - bits)) as u16) as f64,
+            16 => f16_to_f32(raw as u16) as f64,
+            32 => f32::from_bits(raw as u32) as f64,
+            64 => f64::from_bits(raw),
+            _ => unreachable!(),
+        },
+    }
+}
+
+/// Encode a single channel into a `bits` wide value.
+fn encode_channel(value: f64, bits: u32, ty: ChannelType) -> u64 {
+    let max_unsigned = (!0u64 >> (64 - bits)) as f64;
+    let max_signed = (!0u64 >> (65 - bits)) as f64;
+    let raw = match ty {
+        ChannelType::Unorm => (value.max(0.0).min(1.0) * max_unsigned).round() as u64,
+        ChannelType::Srgb => (linear_to_srgb(value.max(0.0).min(1.0)) * max_unsigned).round() as u64,
+        ChannelType::Inorm => (value.max(-1.0).min(1.0) * max_signed).round() as i64 as u64,
+        ChannelType::Uint | ChannelType::Uscaled => value.max(0.0).min(max_unsigned) as u64,
+        ChannelType::Int | ChannelType::Iscaled => value.max(-max_signed - 1.0).min(max_signed) as i64 as u64,
+        ChannelType::Float | ChannelType::Ufloat => match bits {
+            10 | 11 => (f32_to_f16(value.max(0.0) as f32) as u64) >> (15 - bits),
+            16 => f32_to_f16(value as f32) as u64,
+            32 => (value as f32).to_bits() as u64,
+            64 => value.to_bits(),
+            _ => unreachable!(),
+        },
+    };
+    raw & (!0u64 >> (64 - bits))
+}
+
+/// Decode a texel with 9 bit mantissas sharing a 5 bit exponent.
+fn decode_shared_exponent(raw: u64) -> [f64; 4] {
+    let scale = 2f64.powi((raw >> 27) as i32 - 15 - 9);
+    let channel = |i: u64| ((raw >> (9 * i)) & 0x1FF) as f64 * scale;
+    [channel(0), channel(1), channel(2), 1.0]
+}
+
+fn encode_shared_exponent(value: [f64; 4]) -> u64 {
+    let max_value = 511.0 / 512.0 * 2f64.powi(16);
+    let channels = [
+        value[0].max(0.0).min(max_value),
+        value[1].max(0.0).min(max_value),
+        value[2].max(0.0).min(max_value),
+    ];
+    let max_channel = channels[0].max(channels[1]).max(channels[2]);
+    let mut exp = max_channel.log2().floor().max(-16.0) as i32 + 1 + 15;
+    if (max_channel / 2f64.powi(exp - 15 - 9) + 0.5).floor() == 512.0 {
+        exp += 1;
+    }
+    let scale = 2f64.powi(exp - 15 - 9);
+    channels
+        .iter()
+        .enumerate()
+        .fold((exp as u64) << 27, |raw, (i, &c)| raw | ((c / scale + 0.5).floor() as u64) << (9 * i))
+}
+
+/// Decode a color texel into RGBA values.
+///
+/// Missing channels are filled with `(0, 0, 0, 1)`.
+pub fn decode_color(format: Format, texel: &[u8]) -> [f64; 4] {
+    let base = format.base_format();
+    let mut value = [0.0, 0.0, 0.0, 1.0];
+    if let Some(layout) = channel_layout(base.0) {
+        for (i, &channel) in layout.order.iter().enumerate() {
+            let bytes = &texel[i * layout.size .. (i + 1) * layout.size];
+            value[channel] = decode_channel(read_uint(bytes), 8 * layout.size as u32, base.1);
+        }
+    } else if base.0 == SurfaceType::E5_B9_G9_R9 {
+        value = decode_shared_exponent(read_uint(texel));
+    } else {
+        let fields = packed_layout(base.0)
+            .expect("Format is not supported for decoding");
+        let raw = read_uint(texel);
+        let mut shift = 0;
+        for &(channel, bits) in fields {
+            value[channel] = decode_channel((raw >> shift) & (!0u64 >> (64 - bits)), bits, base.1);
+            shift += bits;
+        }
+    }
+    value
+}
+
+/// Encode RGBA values into a color texel.
+pub fn encode_color(format: Format, value: [f64; 4], texel: &mut [u8]) {
+    let base = format.base_format();
+    if let Some(layout) = channel_layout(base.0) {
+        for (i, &channel) in layout.order.iter().enumerate() {
+            let raw = encode_channel(value[channel], 8 * layout.size as u32, base.1);
+            write_uint(&mut texel[i * layout.size .. (i + 1) * layout.size], raw);
+        }
+    } else if base.0 == SurfaceType::E5_B9_G9_R9 {
+        write_uint(texel, encode_shared_exponent(value));
+    } else {
+        let fields = packed_layout(base.0)
+            .expect("Format is not supported for encoding");
+        let mut raw = 0;
+        let mut shift = 0;
+        for &(channel, bits) in fields {
+            raw |= encode_channel(value[channel], bits, base.1) << shift;
+            shift += bits;
+        }
+        write_uint(texel, raw);
+    }
+}
+
+/// Interpret the clear color union according to the format channel type.
+pub fn clear_color_value(format: Format, color: ClearColorRaw) -> [f64; 4] {
+    unsafe {
+        match format.base_format().1 {
+            ChannelType::Uint => {
+                let c = color.uint32;
+                [c[0] as f64, c[1] as f64, c[2] as f64, c[3] as f64]
+            }
+            ChannelType::Int => {
+                let c = color.int32;
+                [c[0] as f64, c[1] as f64, c[2] as f64, c[3] as f64]
+            }
+            _ => {
+                let c = color.float32;
+                [c[0] as f64, c[1] as f64, c[2] as f64, c[3] as f64]
+            }
+        }
+    }
+}
+
+/// Write the selected aspects of a depth/stencil value into a texel.
+///
+/// Depth is stored in the low bytes, followed by stencil.
+pub fn encode_depth_stencil(
+    format: Format, value: ClearDepthStencilRaw, aspects: Aspects, texel: &mut [u8],
+) {
+    use self::SurfaceType::*;
+    let depth = value.depth.max(0.0).min(1.0) as f64;
+    let unorm = |bits: u32| (depth * ((1u64 << bits) - 1) as f64).round() as u64;
+    let (depth_bytes, depth_raw) = match format.base_format().0 {
+        D16 | D16_S8 => (2, unorm(16)),
+        X8D24 | D24_S8 => (3, unorm(24)),
+        D32 | D32_S8 => (4, value.depth.to_bits() as u64),
+        S8 => (0, 0),
+        _ => panic!("{:?} is not a depth/stencil format", format),
+    };
+    if aspects.contains(Aspects::DEPTH) && depth_bytes != 0 {
+        write_uint(&mut texel[.. depth_bytes], depth_raw);
+    }
+    if aspects.contains(Aspects::STENCIL) && format.is_stencil() {
+        texel[texel.len() - 1] = value.stencil as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(format: Format, value: [f64; 4], texel: &[u8]) {
+        let mut encoded = vec![0; texel.len()];
+        encode_color(format, value, &mut encoded);
+        assert_eq!(encoded, texel, "{:?}", format);
+        assert_eq!(decode_color(format, texel), value, "{:?}", format);
+    }
+
+    #[test]
+    fn packed() {
+        round_trip(Format::R5g6b5Unorm, [1.0, 0.0, 1.0, 1.0], &[0x1F, 0xF8]);
+        round_trip(Format::Rgba4Unorm, [0.0, 1.0, 0.0, 1.0], &[0x0F, 0x0F]);
+        round_trip(Format::A2b10g10r10Uint, [1.0, 2.0, 3.0, 1.0], &[0x01, 0x08, 0x30, 0x40]);
+        round_trip(Format::A2b10g10r10Inorm, [-1.0, 0.0, 1.0, 0.0], &[0x01, 0x02, 0xF0, 0x1F]);
+    }
+
+    #[test]
+    fn packed_float() {
+        // 1.0, 2.0 and 0.5 in 11, 11 and 10 bit unsigned floats
+        round_trip(Format::B10g11r11Ufloat, [1.0, 2.0, 0.5, 1.0], &[0xC0, 0x03, 0x20, 0x70]);
+        // 9 bit mantissas of 256, 128 and 64 with an exponent of 16
+        round_trip(Format::E5b9g9r9Ufloat, [1.0, 0.5, 0.25, 1.0], &[0x00, 0x01, 0x01, 0x81]);
+    }
+
+    #[test]
+    fn interpretable() {
+        assert!(is_interpretable(Format::R5g6b5Unorm));
+        assert!(is_interpretable(Format::E5b9g9r9Ufloat));
+        assert!(is_interpretable(Format::D24UnormS8Uint));
+        assert!(!is_interpretable(Format::Bc1RgbaUnorm));
+    }
+}
@@ -0,0 +1,497 @@
+use hal::{buffer, device, error, format, image, mapping, memory, pass, pool, pso, query, queue};
+use hal::range::RangeArg;
+
+use std::borrow::Borrow;
+use std::ops::Range;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+use command::RawCommandPool;
+use interpreter::Program;
+use native::{self as n, Block, ImageLayout};
+use hal::pso::spirv;
+use {Backend, DescriptorPool, Surface, Swapchain, HEAP_SIZE, MEMORY_TYPE_MASK};
+
+
+/// Alignment of every resource within a memory allocation.
+const RESOURCE_ALIGNMENT: u64 = 16;
+
+/// Device allocating resources in host memory.
+#[derive(Debug)]
+pub struct Device;
+
+impl ::hal::Device<Backend> for Device {
+    fn create_command_pool(&self, _: queue::QueueFamilyId, _: pool::CommandPoolCreateFlags) -> RawCommandPool {
+        RawCommandPool
+    }
+
+    fn destroy_command_pool(&self, _: RawCommandPool) {
+    }
+
+    fn allocate_memory(&self, _: ::hal::MemoryTypeId, size: u64) -> Result<n::Memory, device::OutOfMemory> {
+        if size > HEAP_SIZE {
+            return Err(device::OutOfMemory);
+        }
+        Ok(n::Memory {
+            block: Arc::new(Block::new(size)),
+        })
+    }
+
+    fn create_render_pass<'a ,IA, IS, ID>(&self, _: IA, _: IS, _: ID) -> ()
+    where
+        IA: IntoIterator,
+        IA::Item: Borrow<pass::Attachment>,
+        IS: IntoIterator,
+        IS::Item: Borrow<pass::SubpassDesc<'a>>,
+        ID: IntoIterator,
+        ID::Item: Borrow<pass::SubpassDependency>,
+    {
+        unimplemented!()
+    }
+
+    fn create_pipeline_layout<IS, IR>(&self, _: IS, _: IR) -> ()
+    where
+        IS: IntoIterator,
+        IS::Item: Borrow<n::DescriptorSetLayout>,
+        IR: IntoIterator,
+        IR::Item: Borrow<(pso::ShaderStageFlags, Range<u32>)>,
+    {
+        // Descriptor sets and push constants are bound by index,
+        // the layout doesn't need to remember anything.
+    }
+
+    fn create_pipeline_cache(&self, data: Option<&[u8]>) -> n::PipelineCache {
+        n::PipelineCache {
+            data: Mutex::new(data.map_or(Vec::new(), |data| data.to_vec())),
+        }
+    }
+
+    fn get_pipeline_cache_data(&self, cache: &n::PipelineCache) -> Result<Vec<u8>, device::OutOfMemory> {
+        Ok(cache.data.lock().unwrap().clone())
+    }
+
+    fn destroy_pipeline_cache(&self, _: n::PipelineCache) {
+    }
+
+    fn merge_pipeline_caches<I>(&self, target: &n::PipelineCache, sources: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::PipelineCache>,
+    {
+        // The blobs can't be combined, an empty cache takes the first
+        // non-empty one instead.
+        let mut data = target.data.lock().unwrap();
+        for source in sources {
+            if !data.is_empty() {
+                break;
+            }
+            let source = source.borrow();
+            // Merging a cache into itself would deadlock.
+            if !ptr::eq(source, target) {
+                data.clone_from(&source.data.lock().unwrap());
+            }
+        }
+    }
+
+    fn create_framebuffer<I>(
+        &self, _: &(), _: I, _: image::Extent
+    ) -> Result<(), device::FramebufferError>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<()>,
+    {
+        unimplemented!()
+    }
+
+    fn create_shader_module(&self, spirv_data: &[u8]) -> Result<n::ShaderModule, device::ShaderError> {
+        let module = spirv::parse(spirv_data)
+            .map_err(device::ShaderError::CompilationFailed)?;
+        Ok(n::ShaderModule {
+            module: Arc::new(module),
+        })
+    }
+
+    fn create_compute_pipeline<'a>(
+        &self,
+        desc: &pso::ComputePipelineDesc<'a, Backend>,
+        _: Option<&n::PipelineCache>,
+    ) -> Result<n::ComputePipeline, pso::CreationError> {
+        let shader = &desc.shader;
+        let program = Program::new(shader.module.module.clone(), shader.entry, shader.specialization)
+            .map_err(pso::CreationError::Shader)?;
+        Ok(n::ComputePipeline {
+            program: Arc::new(program),
+        })
+    }
+
+    fn create_sampler(&self, _: image::SamplerInfo) -> () {
+        unimplemented!()
+    }
+
+    fn create_buffer(&self, size: u64, _: buffer::Usage) -> Result<n::UnboundBuffer, buffer::CreationError> {
+        Ok(n::UnboundBuffer {
+            size,
+        })
+    }
+
+    fn get_buffer_requirements(&self, buffer: &n::UnboundBuffer) -> memory::Requirements {
+        memory::Requirements {
+            size: buffer.size,
+            alignment: RESOURCE_ALIGNMENT,
+            type_mask: MEMORY_TYPE_MASK,
+        }
+    }
+
+    fn bind_buffer_memory(
+        &self, memory: &n::Memory, offset: u64, buffer: n::UnboundBuffer
+    ) -> Result<n::Buffer, device::BindError> {
+        if offset.checked_add(buffer.size).map_or(true, |end| end > memory.block.size()) {
+            return Err(device::BindError::OutOfBounds);
+        }
+        Ok(n::Buffer {
+            block: memory.block.clone(),
+            offset,
+            size: buffer.size,
+        })
+    }
+
+    fn create_buffer_view<R: RangeArg<u64>>(&self, _: &n::Buffer, _: Option<format::Format>, _: R) -> Result<(), buffer::ViewCreationError> {
+        unimplemented!()
+    }
+
+    fn create_image(
+        &self,
+        kind: image::Kind,
+        levels: image::Level,
+        format: format::Format,
+        _: image::Tiling,
+        _: image::Usage,
+        _: image::StorageFlags,
+    ) -> Result<n::UnboundImage, image::CreationError> {
+        if kind.num_samples() != 1 {
+            return Err(image::CreationError::Samples(kind.num_samples()));
+        }
+        Ok(n::UnboundImage {
+            kind,
+            levels,
+            format,
+        })
+    }
+
+    fn get_image_requirements(&self, image: &n::UnboundImage) -> memory::Requirements {
+        memory::Requirements {
+            size: image.size(),
+            alignment: RESOURCE_ALIGNMENT,
+            type_mask: MEMORY_TYPE_MASK,
+        }
+    }
+
+    fn get_image_subresource_footprint(
+        &self, image: &n::Image, sub: image::Subresource
+    ) -> image::SubresourceFootprint {
+        image.layout.footprint(&sub)
+    }
+
+    fn bind_image_memory(
+        &self, memory: &n::Memory, offset: u64, image: n::UnboundImage
+    ) -> Result<n::Image, device::BindError> {
+        if offset.checked_add(image.size()).map_or(true, |end| end > memory.block.size()) {
+            return Err(device::BindError::OutOfBounds);
+        }
+        Ok(n::Image {
+            block: memory.block.clone(),
+            offset,
+            layout: ImageLayout::new(image.kind, image.levels, image.format),
+        })
+    }
+
+    fn create_image_view(
+        &self,
+        _: &n::Image,
+        _: image::ViewKind,
+        _: format::Format,
+        _: format::Swizzle,
+        _: image::SubresourceRange,
+    ) -> Result<(), image::ViewError> {
+        unimplemented!()
+    }
+
+    fn create_descriptor_pool<I>(&self, _: usize, _: I) -> DescriptorPool
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorRangeDesc>,
+    {
+        DescriptorPool
+    }
+
+    fn create_descriptor_set_layout<I, J>(&self, bindings: I, _: J) -> n::DescriptorSetLayout
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetLayoutBinding>,
+        J: IntoIterator,
+        J::Item: Borrow<()>
+    {
+        let mut bindings = bindings
+            .into_iter()
+            .map(|binding| binding.borrow().clone())
+            .collect::<Vec<_>>();
+        bindings.sort_by_key(|binding| binding.binding);
+        n::DescriptorSetLayout {
+            bindings,
+        }
+    }
+
+    fn write_descriptor_sets<'a, I, J>(&self, writes: I)
+    where
+        I: IntoIterator<Item = pso::DescriptorSetWrite<'a, Backend, J>>,
+        J: IntoIterator,
+        J::Item: Borrow<pso::Descriptor<'a, Backend>>,
+    {
+        for write in writes {
+            let mut bindings = write.set.bindings.lock().unwrap();
+            let slots = n::descriptor_slots(&mut bindings, write.binding, write.array_offset);
+            for (slot, descriptor) in slots.zip(write.descriptors) {
+                *slot = match *descriptor.borrow() {
+                    pso::Descriptor::Buffer(buffer, ref range) => Some(n::BufferDescriptor {
+                        block: buffer.block.clone(),
+                        range: buffer.range(range.start, range.end),
+                    }),
+                    // Images and samplers are not supported by the interpreter.
+                    _ => None,
+                };
+            }
+        }
+    }
+
+    fn copy_descriptor_sets<'a, I>(&self, copies: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetCopy<'a, Backend>>
+    {
+        for copy in copies {
+            let copy = copy.borrow();
+            // Copying within a set must not lock it twice.
+            let descriptors = {
+                let mut bindings = copy.src_set.bindings.lock().unwrap();
+                n::descriptor_slots(&mut bindings, copy.src_binding, copy.src_array_offset)
+                    .take(copy.count)
+                    .map(|slot| slot.clone())
+                    .collect::<Vec<_>>()
+            };
+            let mut bindings = copy.dst_set.bindings.lock().unwrap();
+            let slots = n::descriptor_slots(&mut bindings, copy.dst_binding, copy.dst_array_offset);
+            for (slot, descriptor) in slots.zip(descriptors) {
+                *slot = descriptor;
+            }
+        }
+    }
+
+    fn create_semaphore(&self) -> () {
+        // Submissions are executed in order, there is nothing to wait for.
+    }
+
+    fn create_fence(&self, signaled: bool) -> n::Fence {
+        n::Fence::new(signaled)
+    }
+
+    fn reset_fence(&self, fence: &n::Fence) {
+        fence.set(false);
+    }
+
+    fn wait_for_fence(&self, fence: &n::Fence, _: u64) -> bool {
+        // Fences are signaled at the end of `submit_raw`, so waiting
+        // would never change the outcome.
+        fence.is_signaled()
+    }
+
+    fn get_fence_status(&self, fence: &n::Fence) -> bool {
+        fence.is_signaled()
+    }
+
+    fn create_event(&self) -> n::Event {
+        n::Event::default()
+    }
+
+    fn get_event_status(&self, event: &n::Event) -> bool {
+        event.is_signaled()
+    }
+
+    fn set_event(&self, event: &n::Event) {
+        event.set(true);
+    }
+
+    fn reset_event(&self, event: &n::Event) {
+        event.set(false);
+    }
+
+    fn create_query_pool(&self, _: query::QueryType, _: query::QueryId) -> Result<(), query::Error> {
+        unimplemented!()
+    }
+
+    fn destroy_query_pool(&self, _: ()) {
+        unimplemented!()
+    }
+
+    fn get_query_pool_results(
+        &self, _: &(), _: Range<query::QueryId>, _: &mut [u8], _: buffer::Offset, _: query::ResultFlags,
+    ) -> Result<bool, error::HostExecutionError> {
+        unimplemented!()
+    }
+
+    fn map_memory<R: RangeArg<u64>>(&self, memory: &n::Memory, range: R) -> Result<*mut u8, mapping::Error> {
+        let start = *range.start().unwrap_or(&0);
+        let end = *range.end().unwrap_or(&memory.block.size());
+        if start > end || end > memory.block.size() {
+            return Err(mapping::Error::OutOfBounds);
+        }
+        Ok(memory.block.ptr(start))
+    }
+
+    fn unmap_memory(&self, _: &n::Memory) {
+    }
+
+    fn flush_mapped_memory_ranges<'a, I, R>(&self, _: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a n::Memory, R)>,
+        R: RangeArg<u64>,
+    {
+        // Host memory is always coherent.
+    }
+
+    fn invalidate_mapped_memory_ranges<'a, I, R>(&self, _: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a n::Memory, R)>,
+        R: RangeArg<u64>,
+    {
+        // Host memory is always coherent.
+    }
+
+    fn free_memory(&self, _: n::Memory) {
+        // The block is released once the last resource bound to it is dropped.
+    }
+
+    fn destroy_shader_module(&self, _: n::ShaderModule) {
+    }
+
+    fn destroy_render_pass(&self, _: ()) {
+        unimplemented!()
+    }
+
+    fn destroy_pipeline_layout(&self, _: ()) {
+    }
+    fn destroy_graphics_pipeline(&self, _: ()) {
+        unimplemented!()
+    }
+    fn destroy_compute_pipeline(&self, _: n::ComputePipeline) {
+    }
+    fn destroy_framebuffer(&self, _: ()) {
+        unimplemented!()
+    }
+
+    fn destroy_buffer(&self, _: n::Buffer) {
+    }
+    fn destroy_buffer_view(&self, _: ()) {
+        unimplemented!()
+    }
+    fn destroy_image(&self, _: n::Image) {
+    }
+    fn create_sparse_buffer(
+        &self, _size: u64, _usage: buffer::Usage, _sparse: memory::SparseFlags,
+    ) -> Result<n::Buffer, buffer::CreationError> {
+        unimplemented!()
+    }
+
+    fn get_sparse_buffer_requirements(&self, _buffer: &n::Buffer) -> memory::Requirements {
+        unimplemented!()
+    }
+
+    fn create_sparse_image(
+        &self, _kind: image::Kind, _mip_levels: image::Level, _format: format::Format,
+        _usage: image::Usage, _storage_flags: image::StorageFlags, _sparse: memory::SparseFlags,
+    ) -> Result<n::Image, image::CreationError> {
+        unimplemented!()
+    }
+
+    fn get_sparse_image_requirements(
+        &self, _image: &n::Image,
+    ) -> (memory::Requirements, Vec<image::SparseRequirements>) {
+        unimplemented!()
+    }
+
+    fn destroy_image_view(&self, _: ()) {
+        unimplemented!()
+    }
+    fn destroy_sampler(&self, _: ()) {
+        unimplemented!()
+    }
+
+    fn destroy_descriptor_pool(&self, _: DescriptorPool) {
+    }
+
+    fn destroy_descriptor_set_layout(&self, _: n::DescriptorSetLayout) {
+    }
+
+    fn destroy_fence(&self, _: n::Fence) {
+    }
+
+    fn destroy_event(&self, _: n::Event) {
+    }
+
+    fn destroy_semaphore(&self, _: ()) {
+    }
+
+    fn create_swapchain(
+        &self,
+        _: &mut Surface,
+        _: ::hal::SwapchainConfig,
+        _: Option<Swapchain>,
+    ) -> (Swapchain, ::hal::Backbuffer<Backend>) {
+        unimplemented!()
+    }
+
+    fn destroy_swapchain(&self, _: Swapchain) {
+        unimplemented!()
+    }
+
+    fn wait_idle(&self) -> Result<(), error::HostExecutionError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hal::{buffer, device, Device as _, MemoryTypeId};
+    use super::{Device, HEAP_SIZE};
+
+    #[test]
+    fn allocate_above_heap_size() {
+        assert!(Device.allocate_memory(MemoryTypeId(0), HEAP_SIZE + 1).is_err());
+    }
+
+    #[test]
+    fn bind_past_end() {
+        let memory = Device.allocate_memory(MemoryTypeId(0), 16).unwrap();
+        let buffer = Device.create_buffer(8, buffer::Usage::TRANSFER_SRC).unwrap();
+        match Device.bind_buffer_memory(&memory, !0 - 4, buffer) {
+            Err(device::BindError::OutOfBounds) => (),
+            other => panic!("Unexpected bind result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pipeline_cache_data() {
+        let empty = Device.create_pipeline_cache(None);
+        assert_eq!(Device.get_pipeline_cache_data(&empty).unwrap(), Vec::<u8>::new());
+
+        let first = Device.create_pipeline_cache(Some(&[1, 2, 3]));
+        let second = Device.create_pipeline_cache(Some(&[4]));
+        assert_eq!(Device.get_pipeline_cache_data(&first).unwrap(), vec![1, 2, 3]);
+
+        Device.merge_pipeline_caches(&empty, vec![&empty, &first, &second]);
+        assert_eq!(Device.get_pipeline_cache_data(&empty).unwrap(), vec![1, 2, 3]);
+        Device.merge_pipeline_caches(&second, Some(&first));
+        assert_eq!(Device.get_pipeline_cache_data(&second).unwrap(), vec![4]);
+    }
+}
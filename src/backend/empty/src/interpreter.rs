@@ -0,0 +1,1931 @@
+//! Interpreter running compute shaders on the CPU.
+//!
+//! The invocations of a workgroup are executed one after another, each of
+//! them running until it either returns from the entry point or reaches a
+//! control barrier, at which point the next one is resumed. Memory with an
+//! explicit layout (storage and uniform buffers, push constants) is accessed
+//! in place, while all the other variables are kept as logical values.
+//!
+//! Floating-point operations are evaluated in double precision and rounded
+//! to the width of the result, which gives correctly rounded results for the
+//! basic arithmetic and makes the execution deterministic.
+
+use hal::{device, pso};
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use native::{Block, DescriptorBinding};
+use hal::pso::spirv::{self, builtin, glsl, op, storage, Module, Type};
+
+
+/// Value of a SPIR-V object.
+#[derive(Clone, Debug)]
+pub enum Value {
+    /// Boolean, integer or floating-point value, stored as raw bits.
+    Scalar(u64),
+    /// Vector, matrix, array or structure.
+    Composite(Vec<Value>),
+    Pointer(Pointer),
+}
+
+impl Value {
+    fn bool(value: bool) -> Self {
+        Value::Scalar(value as u64)
+    }
+
+    fn scalar(&self) -> u64 {
+        match *self {
+            Value::Scalar(bits) => bits,
+            ref other => panic!("Expected a scalar, found {:?}", other),
+        }
+    }
+
+    fn components(&self) -> &[Value] {
+        match *self {
+            Value::Composite(ref components) => components,
+            ref other => panic!("Expected a composite, found {:?}", other),
+        }
+    }
+
+    /// Component `i` of a composite, or the value itself for a scalar.
+    fn component(&self, i: usize) -> &Value {
+        match *self {
+            Value::Composite(ref components) => &components[i],
+            _ => self,
+        }
+    }
+
+    fn len(&self) -> Option<usize> {
+        match *self {
+            Value::Composite(ref components) => Some(components.len()),
+            _ => None,
+        }
+    }
+
+    fn element(&self, path: &[u32]) -> &Value {
+        path.iter().fold(self, |value, &i| &value.components()[i as usize])
+    }
+
+    fn element_mut(&mut self, path: &[u32]) -> &mut Value {
+        let mut value = self;
+        for &i in path {
+            value = match *value {
+                Value::Composite(ref mut components) => &mut components[i as usize],
+                ref other => panic!("Unable to index into {:?}", other),
+            };
+        }
+        value
+    }
+}
+
+/// Variable without an explicit memory layout.
+#[derive(Clone, Copy, Debug)]
+pub enum Variable {
+    /// Variable of the function at the given depth of the call stack.
+    Function(usize, u32),
+    Private(u32),
+    Workgroup(u32),
+    Input(u32),
+}
+
+#[derive(Clone, Debug)]
+pub enum Pointer {
+    /// Location in a memory block with an explicit layout.
+    Memory {
+        block: Arc<Block>,
+        offset: u64,
+        /// End of the range accessible through the pointer.
+        end: u64,
+        ty: u32,
+        /// Stride of the enclosing matrix, if any.
+        matrix_stride: u64,
+    },
+    /// Element of a logical variable.
+    Variable {
+        variable: Variable,
+        path: Vec<u32>,
+    },
+    /// Buffer descriptor, or an array of them, resolved on access.
+    Descriptor {
+        set: usize,
+        binding: u32,
+        ty: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    Bool,
+    Int(u32),
+    Float(u32),
+}
+
+impl Scalar {
+    fn width(&self) -> u32 {
+        match *self {
+            Scalar::Bool => 1,
+            Scalar::Int(width) | Scalar::Float(width) => width,
+        }
+    }
+}
+
+fn mask(width: u32) -> u64 {
+    if width >= 64 { !0 } else { (1 << width) - 1 }
+}
+
+fn sign_extend(bits: u64, width: u32) -> i64 {
+    let shift = 64 - width;
+    ((bits << shift) as i64) >> shift
+}
+
+fn saturate_signed(value: i64, width: u32) -> u64 {
+    let max = (mask(width) >> 1) as i64;
+    value.max(-max - 1).min(max) as u64 & mask(width)
+}
+
+fn to_float(bits: u64, width: u32) -> f64 {
+    match width {
+        32 => f32::from_bits(bits as u32) as f64,
+        64 => f64::from_bits(bits),
+        _ => panic!("Unsupported floating-point width {}", width),
+    }
+}
+
+fn from_float(value: f64, width: u32) -> u64 {
+    match width {
+        32 => (value as f32).to_bits() as u64,
+        64 => value.to_bits(),
+        _ => panic!("Unsupported floating-point width {}", width),
+    }
+}
+
+fn map1<F: FnMut(u64) -> u64>(a: &Value, f: &mut F) -> Value {
+    match a.len() {
+        Some(len) => Value::Composite((0 .. len).map(|i| map1(a.component(i), &mut *f)).collect()),
+        None => Value::Scalar(f(a.scalar())),
+    }
+}
+
+/// Apply `f` component-wise, broadcasting scalar operands.
+fn map2<F: FnMut(u64, u64) -> u64>(a: &Value, b: &Value, f: &mut F) -> Value {
+    match a.len().or(b.len()) {
+        Some(len) => Value::Composite((0 .. len)
+            .map(|i| map2(a.component(i), b.component(i), &mut *f))
+            .collect()),
+        None => Value::Scalar(f(a.scalar(), b.scalar())),
+    }
+}
+
+fn map3<F: FnMut(u64, u64, u64) -> u64>(a: &Value, b: &Value, c: &Value, f: &mut F) -> Value {
+    match a.len().or(b.len()).or(c.len()) {
+        Some(len) => Value::Composite((0 .. len)
+            .map(|i| map3(a.component(i), b.component(i), c.component(i), &mut *f))
+            .collect()),
+        None => Value::Scalar(f(a.scalar(), b.scalar(), c.scalar())),
+    }
+}
+
+fn float1<F: Fn(f64) -> f64>(a: &Value, width: u32, f: F) -> Value {
+    map1(a, &mut |x| from_float(f(to_float(x, width)), width))
+}
+
+fn float2<F: Fn(f64, f64) -> f64>(a: &Value, b: &Value, width: u32, f: F) -> Value {
+    map2(a, b, &mut |x, y| from_float(f(to_float(x, width), to_float(y, width)), width))
+}
+
+fn float3<F: Fn(f64, f64, f64) -> f64>(a: &Value, b: &Value, c: &Value, width: u32, f: F) -> Value {
+    map3(a, b, c, &mut |x, y, z| {
+        from_float(f(to_float(x, width), to_float(y, width), to_float(z, width)), width)
+    })
+}
+
+fn int2<F: Fn(u64, u64) -> u64>(a: &Value, b: &Value, width: u32, f: F) -> Value {
+    map2(a, b, &mut |x, y| f(x, y) & mask(width))
+}
+
+fn signed2<F: Fn(i64, i64) -> i64>(a: &Value, b: &Value, width: u32, f: F) -> Value {
+    map2(a, b, &mut |x, y| f(sign_extend(x, width), sign_extend(y, width)) as u64 & mask(width))
+}
+
+fn compare_float<F: Fn(f64, f64) -> bool>(a: &Value, b: &Value, width: u32, f: F) -> Value {
+    map2(a, b, &mut |x, y| f(to_float(x, width), to_float(y, width)) as u64)
+}
+
+fn compare_signed<F: Fn(i64, i64) -> bool>(a: &Value, b: &Value, width: u32, f: F) -> Value {
+    map2(a, b, &mut |x, y| f(sign_extend(x, width), sign_extend(y, width)) as u64)
+}
+
+fn compare_unsigned<F: Fn(u64, u64) -> bool>(a: &Value, b: &Value, f: F) -> Value {
+    map2(a, b, &mut |x, y| f(x, y) as u64)
+}
+
+fn fadd(x: u64, y: u64, width: u32) -> u64 {
+    from_float(to_float(x, width) + to_float(y, width), width)
+}
+
+fn fmul(x: u64, y: u64, width: u32) -> u64 {
+    from_float(to_float(x, width) * to_float(y, width), width)
+}
+
+/// Dot product, rounding after every operation.
+fn dot(a: &Value, b: &Value, width: u32) -> u64 {
+    a.components()
+        .iter()
+        .zip(b.components())
+        .map(|(x, y)| fmul(x.scalar(), y.scalar(), width))
+        .fold(None, |sum, product| Some(sum.map_or(product, |sum| fadd(sum, product, width))))
+        .unwrap_or(0)
+}
+
+fn matrix_times_vector(matrix: &Value, vector: &Value, width: u32) -> Value {
+    let rows = matrix.components()[0].components().len();
+    Value::Composite((0 .. rows)
+        .map(|row| {
+            let row = Value::Composite(matrix
+                .components()
+                .iter()
+                .map(|column| column.components()[row].clone())
+                .collect());
+            Value::Scalar(dot(&row, vector, width))
+        })
+        .collect())
+}
+
+fn length(a: &Value, width: u32) -> f64 {
+    match a.len() {
+        Some(_) => a.components()
+            .iter()
+            .map(|x| to_float(x.scalar(), width).powi(2))
+            .sum::<f64>()
+            .sqrt(),
+        None => to_float(a.scalar(), width).abs(),
+    }
+}
+
+/// Opcodes of the instructions executed by `Dispatch::run` itself.
+const CONTROL_OPS: &[u16] = &[
+    op::NOP, op::LINE, op::NO_LINE, op::SELECTION_MERGE, op::LOOP_MERGE, op::MEMORY_BARRIER,
+    op::CONTROL_BARRIER, op::VARIABLE, op::LOAD, op::STORE, op::COPY_MEMORY, op::ACCESS_CHAIN,
+    op::IN_BOUNDS_ACCESS_CHAIN, op::ARRAY_LENGTH, op::ATOMIC_LOAD, op::ATOMIC_STORE,
+    op::ATOMIC_EXCHANGE, op::ATOMIC_COMPARE_EXCHANGE, op::ATOMIC_I_INCREMENT,
+    op::ATOMIC_I_DECREMENT, op::ATOMIC_I_ADD, op::ATOMIC_I_SUB, op::ATOMIC_S_MIN, op::ATOMIC_U_MIN,
+    op::ATOMIC_S_MAX, op::ATOMIC_U_MAX, op::ATOMIC_AND, op::ATOMIC_OR, op::ATOMIC_XOR,
+    op::FUNCTION_CALL, op::RETURN, op::RETURN_VALUE, op::BRANCH, op::BRANCH_CONDITIONAL,
+    op::SWITCH, op::UNREACHABLE, op::PHI,
+];
+
+/// Opcodes of the instructions computed by `Program::evaluate`, besides
+/// the extended instructions.
+const EVALUATED_OPS: &[u16] = &[
+    op::UNDEF, op::COPY_OBJECT, op::CONVERT_F_TO_U, op::CONVERT_F_TO_S, op::CONVERT_S_TO_F,
+    op::CONVERT_U_TO_F, op::U_CONVERT, op::S_CONVERT, op::F_CONVERT, op::BITCAST, op::S_NEGATE,
+    op::F_NEGATE, op::I_ADD, op::I_SUB, op::I_MUL, op::U_DIV, op::U_MOD, op::S_DIV, op::S_REM,
+    op::S_MOD, op::F_ADD, op::F_SUB, op::F_MUL, op::F_DIV, op::F_REM, op::F_MOD,
+    op::VECTOR_TIMES_SCALAR, op::MATRIX_TIMES_SCALAR, op::DOT, op::MATRIX_TIMES_VECTOR,
+    op::VECTOR_TIMES_MATRIX, op::MATRIX_TIMES_MATRIX, op::OUTER_PRODUCT, op::TRANSPOSE,
+    op::I_ADD_CARRY, op::I_SUB_BORROW, op::U_MUL_EXTENDED, op::S_MUL_EXTENDED, op::ANY, op::ALL,
+    op::IS_NAN, op::IS_INF, op::LOGICAL_EQUAL, op::LOGICAL_NOT_EQUAL, op::LOGICAL_OR,
+    op::LOGICAL_AND, op::LOGICAL_NOT, op::SELECT, op::I_EQUAL, op::I_NOT_EQUAL, op::U_GREATER_THAN,
+    op::U_GREATER_THAN_EQUAL, op::U_LESS_THAN, op::U_LESS_THAN_EQUAL, op::S_GREATER_THAN,
+    op::S_GREATER_THAN_EQUAL, op::S_LESS_THAN, op::S_LESS_THAN_EQUAL, op::F_ORD_EQUAL,
+    op::F_ORD_NOT_EQUAL, op::F_ORD_LESS_THAN, op::F_ORD_GREATER_THAN, op::F_ORD_LESS_THAN_EQUAL,
+    op::F_ORD_GREATER_THAN_EQUAL, op::F_UNORD_EQUAL, op::F_UNORD_NOT_EQUAL, op::F_UNORD_LESS_THAN,
+    op::F_UNORD_GREATER_THAN, op::F_UNORD_LESS_THAN_EQUAL, op::F_UNORD_GREATER_THAN_EQUAL,
+    op::SHIFT_RIGHT_LOGICAL, op::SHIFT_RIGHT_ARITHMETIC, op::SHIFT_LEFT_LOGICAL, op::BITWISE_OR,
+    op::BITWISE_XOR, op::BITWISE_AND, op::NOT, op::BIT_FIELD_INSERT, op::BIT_FIELD_S_EXTRACT,
+    op::BIT_FIELD_U_EXTRACT, op::BIT_REVERSE, op::BIT_COUNT, op::COMPOSITE_CONSTRUCT,
+    op::COMPOSITE_EXTRACT, op::COMPOSITE_INSERT, op::VECTOR_EXTRACT_DYNAMIC,
+    op::VECTOR_INSERT_DYNAMIC, op::VECTOR_SHUFFLE,
+];
+
+/// Instructions of the `GLSL.std.450` set computed by `Program::extended`.
+const GLSL_INSTRUCTIONS: &[u32] = &[
+    glsl::ROUND, glsl::ROUND_EVEN, glsl::TRUNC, glsl::F_ABS, glsl::S_ABS, glsl::F_SIGN,
+    glsl::S_SIGN, glsl::FLOOR, glsl::CEIL, glsl::FRACT, glsl::RADIANS, glsl::DEGREES, glsl::SIN,
+    glsl::COS, glsl::TAN, glsl::ASIN, glsl::ACOS, glsl::ATAN, glsl::SINH, glsl::COSH, glsl::TANH,
+    glsl::ASINH, glsl::ACOSH, glsl::ATANH, glsl::ATAN2, glsl::POW, glsl::EXP, glsl::LOG,
+    glsl::EXP2, glsl::LOG2, glsl::SQRT, glsl::INVERSE_SQRT, glsl::F_MIN, glsl::N_MIN, glsl::F_MAX,
+    glsl::N_MAX, glsl::U_MIN, glsl::U_MAX, glsl::S_MIN, glsl::S_MAX, glsl::F_CLAMP, glsl::N_CLAMP,
+    glsl::U_CLAMP, glsl::S_CLAMP, glsl::F_MIX, glsl::STEP, glsl::SMOOTH_STEP, glsl::FMA,
+    glsl::LENGTH, glsl::DISTANCE, glsl::CROSS, glsl::NORMALIZE, glsl::FIND_I_LSB, glsl::FIND_S_MSB,
+    glsl::FIND_U_MSB,
+];
+
+/// Compute entry point of a module, along with its specialized constants.
+#[derive(Debug)]
+pub struct Program {
+    module: Arc<Module>,
+    function: u32,
+    constants: HashMap<u32, Value>,
+    local_size: [u32; 3],
+}
+
+impl Program {
+    pub fn new(
+        module: Arc<Module>, entry: &str, specialization: &[pso::Specialization]
+    ) -> Result<Self, device::ShaderError> {
+        let function = module.entry_points
+            .iter()
+            .find(|ep| ep.execution_model == spirv::EXECUTION_MODEL_GL_COMPUTE && ep.name == entry)
+            .map(|ep| ep.function)
+            .ok_or_else(|| device::ShaderError::MissingEntryPoint(entry.to_owned()))?;
+
+        let mut program = Program {
+            module: module.clone(),
+            function,
+            constants: HashMap::new(),
+            local_size: [1; 3],
+        };
+        program.validate()?;
+
+        for instruction in &module.constants {
+            let (ty, id) = (instruction.operands[0], instruction.operands[1]);
+            let arguments = &instruction.operands[2 ..];
+            let mut value = match instruction.op {
+                op::CONSTANT_TRUE | op::SPEC_CONSTANT_TRUE => Value::bool(true),
+                op::CONSTANT_FALSE | op::SPEC_CONSTANT_FALSE => Value::bool(false),
+                op::CONSTANT | op::SPEC_CONSTANT => {
+                    let low = arguments.get(0).map_or(0, |&word| word as u64);
+                    let high = arguments.get(1).map_or(0, |&word| word as u64);
+                    Value::Scalar((low | high << 32) & mask(program.scalar(ty).width()))
+                }
+                op::CONSTANT_COMPOSITE | op::SPEC_CONSTANT_COMPOSITE => {
+                    Value::Composite(arguments.iter().map(|id| program.constants[id].clone()).collect())
+                }
+                op::SPEC_CONSTANT_OP => {
+                    let opcode = arguments[0] as u16;
+                    let constants = &program.constants;
+                    program
+                        .evaluate(opcode, ty, &arguments[1 ..], |id| constants[&id].clone())
+                        .ok_or_else(|| device::ShaderError::CompilationFailed(
+                            format!("Unsupported specialization constant operation {}", opcode)
+                        ))?
+                }
+                _ => program.zero(ty),
+            };
+
+            if let op::SPEC_CONSTANT_TRUE | op::SPEC_CONSTANT_FALSE | op::SPEC_CONSTANT = instruction.op {
+                let spec_id = module.decorations(id).spec_id;
+                if let Some(spec) = specialization.iter().find(|spec| Some(spec.id) == spec_id) {
+                    let bits = match spec.value {
+                        pso::Constant::Bool(value) => value as u64,
+                        pso::Constant::U32(value) => value as u64,
+                        pso::Constant::U64(value) => value,
+                        pso::Constant::I32(value) => value as u64,
+                        pso::Constant::I64(value) => value as u64,
+                        pso::Constant::F32(value) => value.to_bits() as u64,
+                        pso::Constant::F64(value) => value.to_bits(),
+                    };
+                    value = Value::Scalar(bits & mask(program.scalar(ty).width()));
+                }
+            }
+            program.constants.insert(id, value);
+        }
+
+        // A constant decorated as the workgroup size overrides the execution mode.
+        let workgroup_size = module.constants
+            .iter()
+            .map(|instruction| instruction.operands[1])
+            .find(|&id| module.decorations(id).builtin == Some(builtin::WORKGROUP_SIZE));
+        program.local_size = match workgroup_size {
+            Some(id) => {
+                let size = program.constants[&id].components();
+                [size[0].scalar() as u32, size[1].scalar() as u32, size[2].scalar() as u32]
+            }
+            None => *module.local_sizes
+                .get(&function)
+                .ok_or_else(|| device::ShaderError::CompilationFailed(
+                    format!("Entry point {} has no local size", entry)
+                ))?,
+        };
+
+        Ok(program)
+    }
+
+    /// Check that the entry point only relies on what the interpreter supports,
+    /// so that running it can't fail.
+    fn validate(&self) -> Result<(), device::ShaderError> {
+        let unsupported = |what: String| Err(device::ShaderError::CompilationFailed(what));
+
+        for (&id, ty) in &self.module.types {
+            if let Type::Float { width } = *ty {
+                if width != 32 && width != 64 {
+                    return unsupported(format!("Unsupported floating-point width {} of type {}", width, id));
+                }
+            }
+        }
+
+        // Functions reachable from the entry point.
+        let mut functions = vec![self.function];
+        let mut visited = HashSet::new();
+        while let Some(id) = functions.pop() {
+            if !visited.insert(id) {
+                continue
+            }
+            let function = match self.module.functions.get(&id) {
+                Some(function) => function,
+                None => return unsupported(format!("Unknown function {}", id)),
+            };
+            for instruction in function.blocks.iter().flat_map(|block| &block.instructions) {
+                let args = &instruction.operands;
+                match instruction.op {
+                    op::FUNCTION_CALL => functions.push(args[2]),
+                    op::EXT_INST => {
+                        match self.module.ext_inst_imports.get(&args[2]) {
+                            Some(set) if set == spirv::GLSL_STD_450 => {}
+                            set => return unsupported(format!("Unsupported extended instruction set {:?}", set)),
+                        }
+                        if !GLSL_INSTRUCTIONS.contains(&args[3]) {
+                            return unsupported(format!("Unsupported GLSL.std.450 instruction {}", args[3]));
+                        }
+                    }
+                    opcode if CONTROL_OPS.contains(&opcode) || EVALUATED_OPS.contains(&opcode) => {}
+                    opcode => return unsupported(format!("Unsupported instruction {}", opcode)),
+                }
+                // Global variables used by the instruction.
+                for id in args {
+                    if let Some(var) = self.module.variables.get(id) {
+                        self.validate_variable(*id, var)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_variable(&self, id: u32, var: &spirv::Variable) -> Result<(), device::ShaderError> {
+        match var.storage {
+            storage::UNIFORM | storage::STORAGE_BUFFER | storage::PUSH_CONSTANT |
+            storage::PRIVATE | storage::WORKGROUP => Ok(()),
+            storage::INPUT => match self.module.decorations(id).builtin {
+                Some(builtin::NUM_WORKGROUPS) | Some(builtin::WORKGROUP_SIZE) |
+                Some(builtin::WORKGROUP_ID) | Some(builtin::LOCAL_INVOCATION_ID) |
+                Some(builtin::GLOBAL_INVOCATION_ID) | Some(builtin::LOCAL_INVOCATION_INDEX) => Ok(()),
+                other => Err(device::ShaderError::CompilationFailed(
+                    format!("Unsupported input variable {} (built-in {:?})", id, other)
+                )),
+            },
+            other => Err(device::ShaderError::CompilationFailed(
+                format!("Unsupported storage class {} of variable {}", other, id)
+            )),
+        }
+    }
+
+    fn ty(&self, id: u32) -> &Type {
+        self.module.types
+            .get(&id)
+            .unwrap_or_else(|| panic!("Unknown type {}", id))
+    }
+
+    fn type_of(&self, id: u32) -> u32 {
+        *self.module.result_types
+            .get(&id)
+            .unwrap_or_else(|| panic!("Unknown type of {}", id))
+    }
+
+    /// Scalar type of a scalar, vector or matrix type.
+    fn scalar(&self, ty: u32) -> Scalar {
+        match *self.ty(ty) {
+            Type::Bool => Scalar::Bool,
+            Type::Int { width, .. } => Scalar::Int(width),
+            Type::Float { width } => Scalar::Float(width),
+            Type::Vector { component, .. } => self.scalar(component),
+            Type::Matrix { column, .. } => self.scalar(column),
+            ref other => panic!("{:?} is not a numerical type", other),
+        }
+    }
+
+    fn pointee(&self, ty: u32) -> u32 {
+        match *self.ty(ty) {
+            Type::Pointer { pointee, .. } => pointee,
+            ref other => panic!("{:?} is not a pointer type", other),
+        }
+    }
+
+    fn array_length(&self, length: u32) -> usize {
+        self.constants[&length].scalar() as usize
+    }
+
+    fn array_stride(&self, ty: u32) -> u64 {
+        self.module.decorations(ty).array_stride
+            .unwrap_or_else(|| panic!("Array type {} has no stride", ty)) as u64
+    }
+
+    /// Size in memory of a scalar type.
+    fn scalar_size(&self, ty: u32) -> u64 {
+        match self.scalar(ty) {
+            Scalar::Bool => 4,
+            scalar => scalar.width() as u64 / 8,
+        }
+    }
+
+    /// Offset and matrix stride of a structure member.
+    fn member_layout(&self, ty: u32, member: u32) -> (u64, u64) {
+        let decorations = self.module.member_decorations(ty, member);
+        assert!(!decorations.row_major, "Row-major matrices are not supported");
+        let offset = decorations.offset
+            .unwrap_or_else(|| panic!("Member {} of structure {} has no offset", member, ty));
+        (offset as u64, decorations.matrix_stride.unwrap_or(0) as u64)
+    }
+
+    fn zero(&self, ty: u32) -> Value {
+        match *self.ty(ty) {
+            Type::Bool | Type::Int { .. } | Type::Float { .. } => Value::Scalar(0),
+            Type::Vector { component, count } => Value::Composite(vec![self.zero(component); count as usize]),
+            Type::Matrix { column, count } => Value::Composite(vec![self.zero(column); count as usize]),
+            Type::Array { element, length } => {
+                Value::Composite(vec![self.zero(element); self.array_length(length)])
+            }
+            Type::Struct { ref members } => {
+                Value::Composite(members.iter().map(|&member| self.zero(member)).collect())
+            }
+            ref other => panic!("Unable to create a value of {:?}", other),
+        }
+    }
+
+    fn bitcast(&self, value: &Value, from: u32, ty: u32) -> Value {
+        let to = self.scalar(ty).width();
+        let mut bytes = Vec::new();
+        for i in 0 .. value.len().unwrap_or(1) {
+            let bits = value.component(i).scalar();
+            bytes.extend((0 .. from / 8).map(|byte| (bits >> (8 * byte)) as u8));
+        }
+        let mut components = bytes
+            .chunks(to as usize / 8)
+            .map(|chunk| Value::Scalar(chunk.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u64)))
+            .collect::<Vec<_>>();
+        match *self.ty(ty) {
+            Type::Vector { .. } => Value::Composite(components),
+            _ => components.remove(0),
+        }
+    }
+
+    /// Evaluate an instruction without side effects.
+    ///
+    /// The arguments follow the result type and id of the instruction.
+    /// Returns `None` if the opcode isn't one of those instructions.
+    fn evaluate<F>(&self, opcode: u16, ty: u32, args: &[u32], get: F) -> Option<Value>
+    where
+        F: Fn(u32) -> Value,
+    {
+        let arg = |i: usize| get(args[i]);
+        let width = || self.scalar(ty).width();
+        let operand_width = |i: usize| self.scalar(self.type_of(args[i])).width();
+
+        Some(match opcode {
+            op::UNDEF => self.zero(ty),
+            op::COPY_OBJECT => arg(0),
+            op::CONVERT_F_TO_U => {
+                let (from, to) = (operand_width(0), width());
+                map1(&arg(0), &mut |x| (to_float(x, from).max(0.0) as u64).min(mask(to)))
+            }
+            op::CONVERT_F_TO_S => {
+                let (from, to) = (operand_width(0), width());
+                map1(&arg(0), &mut |x| saturate_signed(to_float(x, from) as i64, to))
+            }
+            op::CONVERT_S_TO_F => {
+                let (from, to) = (operand_width(0), width());
+                map1(&arg(0), &mut |x| from_float(sign_extend(x, from) as f64, to))
+            }
+            op::CONVERT_U_TO_F => {
+                let to = width();
+                map1(&arg(0), &mut |x| from_float(x as f64, to))
+            }
+            op::U_CONVERT => {
+                let to = width();
+                map1(&arg(0), &mut |x| x & mask(to))
+            }
+            op::S_CONVERT => {
+                let (from, to) = (operand_width(0), width());
+                map1(&arg(0), &mut |x| sign_extend(x, from) as u64 & mask(to))
+            }
+            op::F_CONVERT => {
+                let (from, to) = (operand_width(0), width());
+                map1(&arg(0), &mut |x| from_float(to_float(x, from), to))
+            }
+            op::BITCAST => self.bitcast(&arg(0), operand_width(0), ty),
+
+            op::S_NEGATE => {
+                let w = width();
+                map1(&arg(0), &mut |x| 0u64.wrapping_sub(x) & mask(w))
+            }
+            op::F_NEGATE => {
+                let w = width();
+                map1(&arg(0), &mut |x| x ^ (1 << (w - 1)))
+            }
+            op::I_ADD => int2(&arg(0), &arg(1), width(), |x, y| x.wrapping_add(y)),
+            op::I_SUB => int2(&arg(0), &arg(1), width(), |x, y| x.wrapping_sub(y)),
+            op::I_MUL => int2(&arg(0), &arg(1), width(), |x, y| x.wrapping_mul(y)),
+            op::U_DIV => int2(&arg(0), &arg(1), width(), |x, y| if y == 0 { 0 } else { x / y }),
+            op::U_MOD => int2(&arg(0), &arg(1), width(), |x, y| if y == 0 { 0 } else { x % y }),
+            op::S_DIV => signed2(&arg(0), &arg(1), width(), |x, y| if y == 0 { 0 } else { x.wrapping_div(y) }),
+            op::S_REM => signed2(&arg(0), &arg(1), width(), |x, y| if y == 0 { 0 } else { x.wrapping_rem(y) }),
+            op::S_MOD => signed2(&arg(0), &arg(1), width(), |x, y| {
+                let r = if y == 0 { 0 } else { x.wrapping_rem(y) };
+                if r != 0 && (r < 0) != (y < 0) { r + y } else { r }
+            }),
+            op::F_ADD => float2(&arg(0), &arg(1), width(), |x, y| x + y),
+            op::F_SUB => float2(&arg(0), &arg(1), width(), |x, y| x - y),
+            op::F_MUL => float2(&arg(0), &arg(1), width(), |x, y| x * y),
+            op::F_DIV => float2(&arg(0), &arg(1), width(), |x, y| x / y),
+            op::F_REM => float2(&arg(0), &arg(1), width(), |x, y| x % y),
+            op::F_MOD => {
+                // GLSL `mod`, with every step rounded like a GPU would.
+                let w = width();
+                let round = |x: f64| to_float(from_float(x, w), w);
+                float2(&arg(0), &arg(1), w, |x, y| x - round(y * round(round(x / y).floor())))
+            }
+            op::VECTOR_TIMES_SCALAR | op::MATRIX_TIMES_SCALAR => {
+                let (w, scalar) = (width(), arg(1).scalar());
+                map1(&arg(0), &mut |x| fmul(x, scalar, w))
+            }
+            op::DOT => Value::Scalar(dot(&arg(0), &arg(1), width())),
+            op::MATRIX_TIMES_VECTOR => matrix_times_vector(&arg(0), &arg(1), width()),
+            op::VECTOR_TIMES_MATRIX => {
+                let (vector, w) = (arg(0), width());
+                Value::Composite(arg(1)
+                    .components()
+                    .iter()
+                    .map(|column| Value::Scalar(dot(&vector, column, w)))
+                    .collect())
+            }
+            op::MATRIX_TIMES_MATRIX => {
+                let (matrix, w) = (arg(0), width());
+                Value::Composite(arg(1)
+                    .components()
+                    .iter()
+                    .map(|column| matrix_times_vector(&matrix, column, w))
+                    .collect())
+            }
+            op::OUTER_PRODUCT => {
+                let (a, w) = (arg(0), width());
+                Value::Composite(arg(1)
+                    .components()
+                    .iter()
+                    .map(|y| map1(&a, &mut |x| fmul(x, y.scalar(), w)))
+                    .collect())
+            }
+            op::TRANSPOSE => {
+                let matrix = arg(0);
+                let columns = matrix.components();
+                Value::Composite((0 .. columns[0].components().len())
+                    .map(|row| Value::Composite(columns
+                        .iter()
+                        .map(|column| column.components()[row].clone())
+                        .collect()))
+                    .collect())
+            }
+            op::I_ADD_CARRY => {
+                let (a, b, w) = (arg(0), arg(1), self.scalar(self.type_of(args[0])).width());
+                Value::Composite(vec![
+                    int2(&a, &b, w, |x, y| x.wrapping_add(y)),
+                    map2(&a, &b, &mut |x, y| ((x as u128 + y as u128) >> w) as u64 & 1),
+                ])
+            }
+            op::I_SUB_BORROW => {
+                let (a, b, w) = (arg(0), arg(1), operand_width(0));
+                Value::Composite(vec![
+                    int2(&a, &b, w, |x, y| x.wrapping_sub(y)),
+                    map2(&a, &b, &mut |x, y| (x < y) as u64),
+                ])
+            }
+            op::U_MUL_EXTENDED => {
+                let (a, b, w) = (arg(0), arg(1), operand_width(0));
+                Value::Composite(vec![
+                    map2(&a, &b, &mut |x, y| (x as u128 * y as u128) as u64 & mask(w)),
+                    map2(&a, &b, &mut |x, y| ((x as u128 * y as u128) >> w) as u64 & mask(w)),
+                ])
+            }
+            op::S_MUL_EXTENDED => {
+                let (a, b, w) = (arg(0), arg(1), operand_width(0));
+                let product = |x, y| sign_extend(x, w) as i128 * sign_extend(y, w) as i128;
+                Value::Composite(vec![
+                    map2(&a, &b, &mut |x, y| product(x, y) as u64 & mask(w)),
+                    map2(&a, &b, &mut |x, y| (product(x, y) >> w) as u64 & mask(w)),
+                ])
+            }
+
+            op::ANY => Value::bool(arg(0).components().iter().any(|x| x.scalar() != 0)),
+            op::ALL => Value::bool(arg(0).components().iter().all(|x| x.scalar() != 0)),
+            op::IS_NAN => {
+                let w = operand_width(0);
+                map1(&arg(0), &mut |x| to_float(x, w).is_nan() as u64)
+            }
+            op::IS_INF => {
+                let w = operand_width(0);
+                map1(&arg(0), &mut |x| to_float(x, w).is_infinite() as u64)
+            }
+            op::LOGICAL_EQUAL => compare_unsigned(&arg(0), &arg(1), |x, y| x == y),
+            op::LOGICAL_NOT_EQUAL => compare_unsigned(&arg(0), &arg(1), |x, y| x != y),
+            op::LOGICAL_OR => map2(&arg(0), &arg(1), &mut |x, y| x | y),
+            op::LOGICAL_AND => map2(&arg(0), &arg(1), &mut |x, y| x & y),
+            op::LOGICAL_NOT => map1(&arg(0), &mut |x| x ^ 1),
+            op::SELECT => {
+                let (condition, a, b) = (arg(0), arg(1), arg(2));
+                match condition.len() {
+                    Some(len) => Value::Composite((0 .. len)
+                        .map(|i| if condition.component(i).scalar() != 0 { a.component(i) } else { b.component(i) })
+                        .cloned()
+                        .collect()),
+                    None => if condition.scalar() != 0 { a } else { b },
+                }
+            }
+            op::I_EQUAL => compare_unsigned(&arg(0), &arg(1), |x, y| x == y),
+            op::I_NOT_EQUAL => compare_unsigned(&arg(0), &arg(1), |x, y| x != y),
+            op::U_GREATER_THAN => compare_unsigned(&arg(0), &arg(1), |x, y| x > y),
+            op::U_GREATER_THAN_EQUAL => compare_unsigned(&arg(0), &arg(1), |x, y| x >= y),
+            op::U_LESS_THAN => compare_unsigned(&arg(0), &arg(1), |x, y| x < y),
+            op::U_LESS_THAN_EQUAL => compare_unsigned(&arg(0), &arg(1), |x, y| x <= y),
+            op::S_GREATER_THAN => compare_signed(&arg(0), &arg(1), operand_width(0), |x, y| x > y),
+            op::S_GREATER_THAN_EQUAL => compare_signed(&arg(0), &arg(1), operand_width(0), |x, y| x >= y),
+            op::S_LESS_THAN => compare_signed(&arg(0), &arg(1), operand_width(0), |x, y| x < y),
+            op::S_LESS_THAN_EQUAL => compare_signed(&arg(0), &arg(1), operand_width(0), |x, y| x <= y),
+            op::F_ORD_EQUAL => compare_float(&arg(0), &arg(1), operand_width(0), |x, y| x == y),
+            op::F_ORD_NOT_EQUAL => {
+                compare_float(&arg(0), &arg(1), operand_width(0), |x, y| x < y || x > y)
+            }
+            op::F_ORD_LESS_THAN => compare_float(&arg(0), &arg(1), operand_width(0), |x, y| x < y),
+            op::F_ORD_GREATER_THAN => compare_float(&arg(0), &arg(1), operand_width(0), |x, y| x > y),
+            op::F_ORD_LESS_THAN_EQUAL => compare_float(&arg(0), &arg(1), operand_width(0), |x, y| x <= y),
+            op::F_ORD_GREATER_THAN_EQUAL => compare_float(&arg(0), &arg(1), operand_width(0), |x, y| x >= y),
+            op::F_UNORD_EQUAL => compare_float(&arg(0), &arg(1), operand_width(0), |x, y| !(x < y || x > y)),
+            op::F_UNORD_NOT_EQUAL => compare_float(&arg(0), &arg(1), operand_width(0), |x, y| x != y),
+            op::F_UNORD_LESS_THAN => compare_float(&arg(0), &arg(1), operand_width(0), |x, y| !(x >= y)),
+            op::F_UNORD_GREATER_THAN => compare_float(&arg(0), &arg(1), operand_width(0), |x, y| !(x <= y)),
+            op::F_UNORD_LESS_THAN_EQUAL => compare_float(&arg(0), &arg(1), operand_width(0), |x, y| !(x > y)),
+            op::F_UNORD_GREATER_THAN_EQUAL => compare_float(&arg(0), &arg(1), operand_width(0), |x, y| !(x < y)),
+
+            op::SHIFT_RIGHT_LOGICAL => {
+                int2(&arg(0), &arg(1), width(), |x, s| if s >= 64 { 0 } else { x >> s })
+            }
+            op::SHIFT_RIGHT_ARITHMETIC => {
+                let w = width();
+                int2(&arg(0), &arg(1), w, |x, s| (sign_extend(x, w) >> s.min(63)) as u64)
+            }
+            op::SHIFT_LEFT_LOGICAL => {
+                int2(&arg(0), &arg(1), width(), |x, s| if s >= 64 { 0 } else { x << s })
+            }
+            op::BITWISE_OR => map2(&arg(0), &arg(1), &mut |x, y| x | y),
+            op::BITWISE_XOR => map2(&arg(0), &arg(1), &mut |x, y| x ^ y),
+            op::BITWISE_AND => map2(&arg(0), &arg(1), &mut |x, y| x & y),
+            op::NOT => {
+                let w = width();
+                map1(&arg(0), &mut |x| !x & mask(w))
+            }
+            op::BIT_FIELD_INSERT => {
+                let (offset, count) = (arg(2).scalar(), arg(3).scalar() as u32);
+                let field = mask(count) << offset;
+                map2(&arg(0), &arg(1), &mut |base, insert| (base & !field) | ((insert << offset) & field))
+            }
+            op::BIT_FIELD_S_EXTRACT => {
+                let (w, offset, count) = (width(), arg(1).scalar(), arg(2).scalar() as u32);
+                map1(&arg(0), &mut |x| if count == 0 {
+                    0
+                } else {
+                    sign_extend((x >> offset) & mask(count), count) as u64 & mask(w)
+                })
+            }
+            op::BIT_FIELD_U_EXTRACT => {
+                let (offset, count) = (arg(1).scalar(), arg(2).scalar() as u32);
+                map1(&arg(0), &mut |x| (x >> offset) & mask(count))
+            }
+            op::BIT_REVERSE => {
+                let w = width();
+                map1(&arg(0), &mut |x| x.reverse_bits() >> (64 - w))
+            }
+            op::BIT_COUNT => map1(&arg(0), &mut |x| x.count_ones() as u64),
+
+            op::COMPOSITE_CONSTRUCT => match *self.ty(ty) {
+                // Vectors can be constructed from smaller vectors.
+                Type::Vector { .. } => Value::Composite(args
+                    .iter()
+                    .flat_map(|&id| match get(id) {
+                        Value::Composite(components) => components,
+                        scalar => vec![scalar],
+                    })
+                    .collect()),
+                _ => Value::Composite(args.iter().map(|&id| get(id)).collect()),
+            },
+            op::COMPOSITE_EXTRACT => arg(0).element(&args[1 ..]).clone(),
+            op::COMPOSITE_INSERT => {
+                let mut composite = arg(1);
+                *composite.element_mut(&args[2 ..]) = arg(0);
+                composite
+            }
+            op::VECTOR_EXTRACT_DYNAMIC => {
+                let index = arg(1).scalar() as usize;
+                arg(0).components().get(index).cloned().unwrap_or(Value::Scalar(0))
+            }
+            op::VECTOR_INSERT_DYNAMIC => {
+                let (mut vector, index) = (arg(0), arg(2).scalar() as u32);
+                if (index as usize) < vector.components().len() {
+                    *vector.element_mut(&[index]) = arg(1);
+                }
+                vector
+            }
+            op::VECTOR_SHUFFLE => {
+                let (a, b) = (arg(0), arg(1));
+                let components = a.components().iter().chain(b.components()).collect::<Vec<_>>();
+                Value::Composite(args[2 ..]
+                    .iter()
+                    .map(|&i| components.get(i as usize).map_or(Value::Scalar(0), |&c| c.clone()))
+                    .collect())
+            }
+            op::EXT_INST => self.extended(ty, args, get),
+            _ => return None,
+        })
+    }
+
+    /// Evaluate an instruction of the `GLSL.std.450` set.
+    fn extended<F>(&self, ty: u32, args: &[u32], get: F) -> Value
+    where
+        F: Fn(u32) -> Value,
+    {
+        let set = &self.module.ext_inst_imports[&args[0]];
+        assert_eq!(set, spirv::GLSL_STD_450, "Unsupported extended instruction set");
+        let instruction = args[1];
+        let arg = |i: usize| get(args[2 + i]);
+        let w = self.scalar(ty).width();
+
+        match instruction {
+            glsl::ROUND => float1(&arg(0), w, f64::round),
+            glsl::ROUND_EVEN => float1(&arg(0), w, |x| {
+                if (x - x.trunc()).abs() == 0.5 { 2.0 * (x / 2.0).round() } else { x.round() }
+            }),
+            glsl::TRUNC => float1(&arg(0), w, f64::trunc),
+            glsl::F_ABS => map1(&arg(0), &mut |x| x & !(1 << (w - 1))),
+            glsl::S_ABS => map1(&arg(0), &mut |x| sign_extend(x, w).wrapping_abs() as u64 & mask(w)),
+            glsl::F_SIGN => float1(&arg(0), w, |x| if x > 0.0 { 1.0 } else if x < 0.0 { -1.0 } else { x }),
+            glsl::S_SIGN => map1(&arg(0), &mut |x| sign_extend(x, w).signum() as u64 & mask(w)),
+            glsl::FLOOR => float1(&arg(0), w, f64::floor),
+            glsl::CEIL => float1(&arg(0), w, f64::ceil),
+            glsl::FRACT => float1(&arg(0), w, |x| x - x.floor()),
+            glsl::RADIANS => float1(&arg(0), w, f64::to_radians),
+            glsl::DEGREES => float1(&arg(0), w, f64::to_degrees),
+            glsl::SIN => float1(&arg(0), w, f64::sin),
+            glsl::COS => float1(&arg(0), w, f64::cos),
+            glsl::TAN => float1(&arg(0), w, f64::tan),
+            glsl::ASIN => float1(&arg(0), w, f64::asin),
+            glsl::ACOS => float1(&arg(0), w, f64::acos),
+            glsl::ATAN => float1(&arg(0), w, f64::atan),
+            glsl::SINH => float1(&arg(0), w, f64::sinh),
+            glsl::COSH => float1(&arg(0), w, f64::cosh),
+            glsl::TANH => float1(&arg(0), w, f64::tanh),
+            glsl::ASINH => float1(&arg(0), w, f64::asinh),
+            glsl::ACOSH => float1(&arg(0), w, f64::acosh),
+            glsl::ATANH => float1(&arg(0), w, f64::atanh),
+            glsl::ATAN2 => float2(&arg(0), &arg(1), w, f64::atan2),
+            glsl::POW => float2(&arg(0), &arg(1), w, f64::powf),
+            glsl::EXP => float1(&arg(0), w, f64::exp),
+            glsl::LOG => float1(&arg(0), w, f64::ln),
+            glsl::EXP2 => float1(&arg(0), w, f64::exp2),
+            glsl::LOG2 => float1(&arg(0), w, f64::log2),
+            glsl::SQRT => float1(&arg(0), w, f64::sqrt),
+            glsl::INVERSE_SQRT => float1(&arg(0), w, |x| 1.0 / x.sqrt()),
+            glsl::F_MIN | glsl::N_MIN => float2(&arg(0), &arg(1), w, f64::min),
+            glsl::F_MAX | glsl::N_MAX => float2(&arg(0), &arg(1), w, f64::max),
+            glsl::U_MIN => int2(&arg(0), &arg(1), w, u64::min),
+            glsl::U_MAX => int2(&arg(0), &arg(1), w, u64::max),
+            glsl::S_MIN => signed2(&arg(0), &arg(1), w, i64::min),
+            glsl::S_MAX => signed2(&arg(0), &arg(1), w, i64::max),
+            glsl::F_CLAMP | glsl::N_CLAMP => {
+                float3(&arg(0), &arg(1), &arg(2), w, |x, low, high| x.max(low).min(high))
+            }
+            glsl::U_CLAMP => map3(&arg(0), &arg(1), &arg(2), &mut |x, low, high| x.max(low).min(high)),
+            glsl::S_CLAMP => map3(&arg(0), &arg(1), &arg(2), &mut |x, low, high| {
+                let (x, low, high) = (sign_extend(x, w), sign_extend(low, w), sign_extend(high, w));
+                x.max(low).min(high) as u64 & mask(w)
+            }),
+            glsl::F_MIX => float3(&arg(0), &arg(1), &arg(2), w, |x, y, a| x * (1.0 - a) + y * a),
+            glsl::STEP => float2(&arg(0), &arg(1), w, |edge, x| if x < edge { 0.0 } else { 1.0 }),
+            glsl::SMOOTH_STEP => float3(&arg(0), &arg(1), &arg(2), w, |edge0, edge1, x| {
+                let t = ((x - edge0) / (edge1 - edge0)).max(0.0).min(1.0);
+                t * t * (3.0 - 2.0 * t)
+            }),
+            glsl::FMA => float3(&arg(0), &arg(1), &arg(2), w, f64::mul_add),
+            glsl::LENGTH => Value::Scalar(from_float(length(&arg(0), w), w)),
+            glsl::DISTANCE => {
+                let difference = float2(&arg(0), &arg(1), w, |x, y| x - y);
+                Value::Scalar(from_float(length(&difference, w), w))
+            }
+            glsl::CROSS => {
+                let (a, b) = (arg(0), arg(1));
+                let c = |v: &Value, i: usize| to_float(v.components()[i].scalar(), w);
+                Value::Composite(vec![
+                    Value::Scalar(from_float(c(&a, 1) * c(&b, 2) - c(&b, 1) * c(&a, 2), w)),
+                    Value::Scalar(from_float(c(&a, 2) * c(&b, 0) - c(&b, 2) * c(&a, 0), w)),
+                    Value::Scalar(from_float(c(&a, 0) * c(&b, 1) - c(&b, 0) * c(&a, 1), w)),
+                ])
+            }
+            glsl::NORMALIZE => {
+                let a = arg(0);
+                let length = length(&a, w);
+                float1(&a, w, |x| x / length)
+            }
+            glsl::FIND_I_LSB => map1(&arg(0), &mut |x| {
+                if x == 0 { mask(w) } else { x.trailing_zeros() as u64 }
+            }),
+            glsl::FIND_S_MSB => map1(&arg(0), &mut |x| {
+                let x = sign_extend(x, w);
+                let x = if x < 0 { !x } else { x };
+                if x == 0 { mask(w) } else { 63 - x.leading_zeros() as u64 }
+            }),
+            glsl::FIND_U_MSB => map1(&arg(0), &mut |x| {
+                if x == 0 { mask(w) } else { 63 - x.leading_zeros() as u64 }
+            }),
+            _ => panic!("Unsupported GLSL.std.450 instruction {}", instruction),
+        }
+    }
+}
+
+/// Function activation record.
+struct Frame {
+    function: u32,
+    block: usize,
+    /// Index of the next instruction within the block.
+    pc: usize,
+    values: HashMap<u32, Value>,
+    /// Contents of the function variables.
+    locals: HashMap<u32, Value>,
+    /// Id receiving the return value in the calling frame.
+    result: Option<u32>,
+}
+
+struct Invocation {
+    /// Call stack, empty once the entry point returned.
+    frames: Vec<Frame>,
+    private: HashMap<u32, Value>,
+    inputs: HashMap<u32, Value>,
+}
+
+enum Status {
+    Barrier,
+    Finished,
+}
+
+/// State shared by all the invocations of a dispatch.
+struct Dispatch<'a> {
+    program: &'a Program,
+    descriptor_sets: &'a [Vec<DescriptorBinding>],
+    push_constants: Arc<Block>,
+    group_count: [u32; 3],
+}
+
+/// Run a compute program over a grid of workgroups.
+pub fn dispatch(
+    program: &Program,
+    descriptor_sets: &[Vec<DescriptorBinding>],
+    push_constants: &[u8],
+    group_count: [u32; 3],
+) {
+    let block = Block::new(push_constants.len() as u64);
+    unsafe {
+        block.write(0, push_constants);
+    }
+    let dispatch = Dispatch {
+        program,
+        descriptor_sets,
+        push_constants: Arc::new(block),
+        group_count,
+    };
+    for z in 0 .. group_count[2] {
+        for y in 0 .. group_count[1] {
+            for x in 0 .. group_count[0] {
+                dispatch.run_workgroup([x, y, z]);
+            }
+        }
+    }
+}
+
+impl<'a> Dispatch<'a> {
+    fn run_workgroup(&self, group: [u32; 3]) {
+        let module = &self.program.module;
+        let size = self.program.local_size;
+        let mut shared = module.variables
+            .iter()
+            .filter(|&(_, var)| var.storage == storage::WORKGROUP)
+            .map(|(&id, var)| (id, self.initial_value(var)))
+            .collect();
+
+        let mut invocations = Vec::new();
+        for z in 0 .. size[2] {
+            for y in 0 .. size[1] {
+                for x in 0 .. size[0] {
+                    invocations.push(self.invocation(group, [x, y, z]));
+                }
+            }
+        }
+
+        // Resume the invocations in turn until all of them have returned.
+        loop {
+            let mut waiting = false;
+            for invocation in &mut invocations {
+                if invocation.frames.is_empty() {
+                    continue
+                }
+                match self.run(invocation, &mut shared) {
+                    Status::Barrier => waiting = true,
+                    Status::Finished => {}
+                }
+            }
+            if !waiting {
+                break
+            }
+        }
+    }
+
+    fn invocation(&self, group: [u32; 3], local: [u32; 3]) -> Invocation {
+        let module = &self.program.module;
+        let size = self.program.local_size;
+        let vector = |v: [u32; 3]| Value::Composite(v.iter().map(|&c| Value::Scalar(c as u64)).collect());
+
+        let inputs = module.variables
+            .iter()
+            .filter(|&(_, var)| var.storage == storage::INPUT)
+            .filter_map(|(&id, _)| {
+                let value = match module.decorations(id).builtin {
+                    Some(builtin::NUM_WORKGROUPS) => vector(self.group_count),
+                    Some(builtin::WORKGROUP_SIZE) => vector(size),
+                    Some(builtin::WORKGROUP_ID) => vector(group),
+                    Some(builtin::LOCAL_INVOCATION_ID) => vector(local),
+                    Some(builtin::GLOBAL_INVOCATION_ID) => vector([
+                        group[0] * size[0] + local[0],
+                        group[1] * size[1] + local[1],
+                        group[2] * size[2] + local[2],
+                    ]),
+                    Some(builtin::LOCAL_INVOCATION_INDEX) => Value::Scalar(
+                        (local[0] + size[0] * (local[1] + size[1] * local[2])) as u64
+                    ),
+                    // Not used by the entry point, as checked by `Program::new`.
+                    _ => return None,
+                };
+                Some((id, value))
+            })
+            .collect();
+
+        let private = module.variables
+            .iter()
+            .filter(|&(_, var)| var.storage == storage::PRIVATE)
+            .map(|(&id, var)| (id, self.initial_value(var)))
+            .collect();
+
+        Invocation {
+            frames: vec![Frame {
+                function: self.program.function,
+                block: 0,
+                pc: 0,
+                values: HashMap::new(),
+                locals: HashMap::new(),
+                result: None,
+            }],
+            private,
+            inputs,
+        }
+    }
+
+    fn initial_value(&self, var: &spirv::Variable) -> Value {
+        match var.initializer {
+            Some(id) => self.program.constants[&id].clone(),
+            None => self.program.zero(self.program.pointee(var.ty)),
+        }
+    }
+
+    /// Pointer to a global variable.
+    fn global(&self, id: u32, var: &spirv::Variable) -> Value {
+        let ty = self.program.pointee(var.ty);
+        let variable = |variable| Pointer::Variable { variable, path: Vec::new() };
+        Value::Pointer(match var.storage {
+            storage::UNIFORM | storage::STORAGE_BUFFER => {
+                let decorations = self.program.module.decorations(id);
+                Pointer::Descriptor {
+                    set: decorations.descriptor_set.unwrap_or(0) as usize,
+                    binding: decorations.binding.unwrap_or(0),
+                    ty,
+                }
+            }
+            storage::PUSH_CONSTANT => Pointer::Memory {
+                block: self.push_constants.clone(),
+                offset: 0,
+                end: self.push_constants.size(),
+                ty,
+                matrix_stride: 0,
+            },
+            storage::PRIVATE => variable(Variable::Private(id)),
+            storage::WORKGROUP => variable(Variable::Workgroup(id)),
+            storage::INPUT => variable(Variable::Input(id)),
+            other => panic!("Unsupported storage class {} of variable {}", other, id),
+        })
+    }
+
+    fn value(&self, frame: &Frame, id: u32) -> Value {
+        if let Some(value) = frame.values.get(&id) {
+            return value.clone();
+        }
+        if let Some(value) = self.program.constants.get(&id) {
+            return value.clone();
+        }
+        match self.program.module.variables.get(&id) {
+            Some(var) => self.global(id, var),
+            None => panic!("Unknown id {}", id),
+        }
+    }
+
+    fn pointer(&self, frame: &Frame, id: u32) -> Pointer {
+        match self.value(frame, id) {
+            Value::Pointer(pointer) => pointer,
+            other => panic!("Expected a pointer, found {:?}", other),
+        }
+    }
+
+    /// Pointer to the start of the buffer bound to a descriptor.
+    fn descriptor(&self, set: usize, binding: u32, index: usize, ty: u32) -> Pointer {
+        let descriptor = self.descriptor_sets
+            .get(set)
+            .and_then(|bindings| bindings.iter().find(|b| b.binding == binding))
+            .and_then(|b| b.descriptors.get(index))
+            .and_then(|descriptor| descriptor.as_ref())
+            .unwrap_or_else(|| panic!("No buffer bound to set {}, binding {}, element {}", set, binding, index));
+        Pointer::Memory {
+            block: descriptor.block.clone(),
+            offset: descriptor.range.start,
+            end: descriptor.range.end,
+            ty,
+            matrix_stride: 0,
+        }
+    }
+
+    /// Resolve a pointer to a non-arrayed descriptor.
+    fn resolve(&self, pointer: Pointer) -> Pointer {
+        match pointer {
+            Pointer::Descriptor { set, binding, ty } => self.descriptor(set, binding, 0, ty),
+            pointer => pointer,
+        }
+    }
+
+    fn access_chain(&self, base: Pointer, indices: &[u64]) -> Pointer {
+        match base {
+            Pointer::Descriptor { set, binding, ty } => match *self.program.ty(ty) {
+                Type::Array { element, .. } | Type::RuntimeArray { element } => {
+                    let base = self.descriptor(set, binding, indices[0] as usize, element);
+                    self.access_chain(base, &indices[1 ..])
+                }
+                _ => self.access_chain(self.descriptor(set, binding, 0, ty), indices),
+            },
+            Pointer::Memory { block, mut offset, end, mut ty, mut matrix_stride } => {
+                for &index in indices {
+                    ty = match *self.program.ty(ty) {
+                        Type::Struct { ref members } => {
+                            let (member_offset, member_matrix_stride) = self.program.member_layout(ty, index as u32);
+                            offset += member_offset;
+                            matrix_stride = member_matrix_stride;
+                            members[index as usize]
+                        }
+                        Type::Array { element, .. } | Type::RuntimeArray { element } => {
+                            offset += index * self.program.array_stride(ty);
+                            element
+                        }
+                        Type::Matrix { column, .. } => {
+                            offset += index * matrix_stride;
+                            column
+                        }
+                        Type::Vector { component, .. } => {
+                            offset += index * self.program.scalar_size(component);
+                            component
+                        }
+                        ref other => panic!("Unable to index into {:?}", other),
+                    };
+                }
+                Pointer::Memory { block, offset, end, ty, matrix_stride }
+            }
+            Pointer::Variable { variable, mut path } => {
+                path.extend(indices.iter().map(|&index| index as u32));
+                Pointer::Variable { variable, path }
+            }
+        }
+    }
+
+    fn read_bits(&self, block: &Block, offset: u64, end: u64, size: u64) -> u64 {
+        assert!(offset + size <= end, "Reading {} bytes at {} past the end of the buffer ({})", size, offset, end);
+        let mut bytes = [0; 8];
+        unsafe { block.read(offset, &mut bytes[.. size as usize]) };
+        bytes[.. size as usize].iter().rev().fold(0, |acc, &b| (acc << 8) | b as u64)
+    }
+
+    fn write_bits(&self, block: &Block, offset: u64, end: u64, size: u64, mut bits: u64) {
+        assert!(offset + size <= end, "Writing {} bytes at {} past the end of the buffer ({})", size, offset, end);
+        let mut bytes = [0; 8];
+        for byte in &mut bytes[.. size as usize] {
+            *byte = bits as u8;
+            bits >>= 8;
+        }
+        unsafe { block.write(offset, &bytes[.. size as usize]) };
+    }
+
+    /// Call `fun(offset, ty, matrix_stride)` for every element of a composite type in memory.
+    fn for_each_element<F>(&self, offset: u64, ty: u32, matrix_stride: u64, mut fun: F)
+    where
+        F: FnMut(usize, u64, u32, u64),
+    {
+        match *self.program.ty(ty) {
+            Type::Vector { component, count } => {
+                let size = self.program.scalar_size(component);
+                for i in 0 .. count as usize {
+                    fun(i, offset + i as u64 * size, component, 0);
+                }
+            }
+            Type::Matrix { column, count } => {
+                for i in 0 .. count as usize {
+                    fun(i, offset + i as u64 * matrix_stride, column, 0);
+                }
+            }
+            Type::Array { element, length } => {
+                let stride = self.program.array_stride(ty);
+                for i in 0 .. self.program.array_length(length) {
+                    fun(i, offset + i as u64 * stride, element, matrix_stride);
+                }
+            }
+            Type::Struct { ref members } => {
+                for (i, &member) in members.iter().enumerate() {
+                    let (member_offset, member_matrix_stride) = self.program.member_layout(ty, i as u32);
+                    fun(i, offset + member_offset, member, member_matrix_stride);
+                }
+            }
+            ref other => panic!("Unable to access {:?} in memory", other),
+        }
+    }
+
+    fn read(&self, block: &Block, offset: u64, end: u64, ty: u32, matrix_stride: u64) -> Value {
+        match *self.program.ty(ty) {
+            Type::Bool => Value::bool(self.read_bits(block, offset, end, 4) != 0),
+            Type::Int { width, .. } | Type::Float { width } => {
+                Value::Scalar(self.read_bits(block, offset, end, width as u64 / 8))
+            }
+            _ => {
+                let mut components = Vec::new();
+                self.for_each_element(offset, ty, matrix_stride, |_, offset, ty, matrix_stride| {
+                    components.push(self.read(block, offset, end, ty, matrix_stride));
+                });
+                Value::Composite(components)
+            }
+        }
+    }
+
+    fn write(&self, block: &Block, offset: u64, end: u64, ty: u32, matrix_stride: u64, value: &Value) {
+        match *self.program.ty(ty) {
+            Type::Bool => self.write_bits(block, offset, end, 4, value.scalar()),
+            Type::Int { width, .. } | Type::Float { width } => {
+                self.write_bits(block, offset, end, width as u64 / 8, value.scalar())
+            }
+            _ => {
+                self.for_each_element(offset, ty, matrix_stride, |i, offset, ty, matrix_stride| {
+                    self.write(block, offset, end, ty, matrix_stride, &value.components()[i]);
+                });
+            }
+        }
+    }
+
+    fn load(&self, invocation: &Invocation, shared: &HashMap<u32, Value>, pointer: Pointer) -> Value {
+        match self.resolve(pointer) {
+            Pointer::Memory { ref block, offset, end, ty, matrix_stride } => {
+                self.read(block, offset, end, ty, matrix_stride)
+            }
+            Pointer::Variable { variable, ref path } => {
+                let root = match variable {
+                    Variable::Function(depth, id) => &invocation.frames[depth].locals[&id],
+                    Variable::Private(id) => &invocation.private[&id],
+                    Variable::Workgroup(id) => &shared[&id],
+                    Variable::Input(id) => &invocation.inputs[&id],
+                };
+                root.element(path).clone()
+            }
+            Pointer::Descriptor { .. } => unreachable!(),
+        }
+    }
+
+    fn store(&self, invocation: &mut Invocation, shared: &mut HashMap<u32, Value>, pointer: Pointer, value: Value) {
+        match self.resolve(pointer) {
+            Pointer::Memory { ref block, offset, end, ty, matrix_stride } => {
+                self.write(block, offset, end, ty, matrix_stride, &value)
+            }
+            Pointer::Variable { variable, ref path } => {
+                let root = match variable {
+                    Variable::Function(depth, id) => invocation.frames[depth].locals.get_mut(&id).unwrap(),
+                    Variable::Private(id) => invocation.private.get_mut(&id).unwrap(),
+                    Variable::Workgroup(id) => shared.get_mut(&id).unwrap(),
+                    Variable::Input(id) => panic!("Unable to write to input variable {}", id),
+                };
+                *root.element_mut(path) = value;
+            }
+            Pointer::Descriptor { .. } => unreachable!(),
+        }
+    }
+
+    /// Branch to a block of the current function, evaluating its phi instructions.
+    fn jump(&self, invocation: &mut Invocation, label: u32) {
+        let frame = invocation.frames.last_mut().unwrap();
+        let function = &self.program.module.functions[&frame.function];
+        let previous = function.blocks[frame.block].label;
+        frame.block = function.labels[&label];
+
+        let values = function.blocks[frame.block].instructions
+            .iter()
+            .take_while(|instruction| instruction.op == op::PHI)
+            .map(|phi| {
+                let source = phi.operands[2 ..]
+                    .chunks(2)
+                    .find(|pair| pair[1] == previous)
+                    .unwrap_or_else(|| panic!("Phi {} has no value for block {}", phi.operands[1], previous));
+                (phi.operands[1], self.value(frame, source[0]))
+            })
+            .collect::<Vec<_>>();
+        frame.pc = values.len();
+        frame.values.extend(values);
+    }
+
+    fn atomic(&self, invocation: &mut Invocation, shared: &mut HashMap<u32, Value>, opcode: u16, args: &[u32]) {
+        let frame_value = |invocation: &Invocation, id| self.value(invocation.frames.last().unwrap(), id);
+        if opcode == op::ATOMIC_STORE {
+            let pointer = self.pointer(invocation.frames.last().unwrap(), args[0]);
+            let value = frame_value(invocation, args[3]);
+            return self.store(invocation, shared, pointer, value);
+        }
+
+        let pointer = self.pointer(invocation.frames.last().unwrap(), args[2]);
+        let w = self.program.scalar(args[0]).width();
+        let old = self.load(invocation, shared, pointer.clone()).scalar();
+        let operand = || frame_value(invocation, args[5]).scalar();
+        let new = match opcode {
+            op::ATOMIC_LOAD => None,
+            op::ATOMIC_EXCHANGE => Some(operand()),
+            op::ATOMIC_COMPARE_EXCHANGE => {
+                let (value, comparator) = (frame_value(invocation, args[6]), frame_value(invocation, args[7]));
+                if old == comparator.scalar() { Some(value.scalar()) } else { None }
+            }
+            op::ATOMIC_I_INCREMENT => Some(old.wrapping_add(1)),
+            op::ATOMIC_I_DECREMENT => Some(old.wrapping_sub(1)),
+            op::ATOMIC_I_ADD => Some(old.wrapping_add(operand())),
+            op::ATOMIC_I_SUB => Some(old.wrapping_sub(operand())),
+            op::ATOMIC_S_MIN => Some(sign_extend(old, w).min(sign_extend(operand(), w)) as u64),
+            op::ATOMIC_U_MIN => Some(old.min(operand())),
+            op::ATOMIC_S_MAX => Some(sign_extend(old, w).max(sign_extend(operand(), w)) as u64),
+            op::ATOMIC_U_MAX => Some(old.max(operand())),
+            op::ATOMIC_AND => Some(old & operand()),
+            op::ATOMIC_OR => Some(old | operand()),
+            op::ATOMIC_XOR => Some(old ^ operand()),
+            _ => unreachable!(),
+        };
+        if let Some(new) = new {
+            self.store(invocation, shared, pointer, Value::Scalar(new & mask(w)));
+        }
+        invocation.frames.last_mut().unwrap().values.insert(args[1], Value::Scalar(old));
+    }
+
+    /// Execute an invocation until it returns or reaches a barrier.
+    fn run(&self, invocation: &mut Invocation, shared: &mut HashMap<u32, Value>) -> Status {
+        let module = &*self.program.module;
+        loop {
+            let depth = invocation.frames.len() - 1;
+            let instruction = {
+                let frame = &mut invocation.frames[depth];
+                let block = &module.functions[&frame.function].blocks[frame.block];
+                frame.pc += 1;
+                &block.instructions[frame.pc - 1]
+            };
+            let args = &instruction.operands[..];
+
+            match instruction.op {
+                op::NOP | op::LINE | op::NO_LINE | op::SELECTION_MERGE |
+                op::LOOP_MERGE | op::MEMORY_BARRIER => {}
+                op::CONTROL_BARRIER => return Status::Barrier,
+                op::VARIABLE => {
+                    let value = match args.get(3) {
+                        Some(&initializer) => self.value(&invocation.frames[depth], initializer),
+                        None => self.program.zero(self.program.pointee(args[0])),
+                    };
+                    let frame = &mut invocation.frames[depth];
+                    frame.locals.insert(args[1], value);
+                    frame.values.insert(args[1], Value::Pointer(Pointer::Variable {
+                        variable: Variable::Function(depth, args[1]),
+                        path: Vec::new(),
+                    }));
+                }
+                op::LOAD => {
+                    let pointer = self.pointer(&invocation.frames[depth], args[2]);
+                    let value = self.load(invocation, shared, pointer);
+                    invocation.frames[depth].values.insert(args[1], value);
+                }
+                op::STORE => {
+                    let pointer = self.pointer(&invocation.frames[depth], args[0]);
+                    let value = self.value(&invocation.frames[depth], args[1]);
+                    self.store(invocation, shared, pointer, value);
+                }
+                op::COPY_MEMORY => {
+                    let target = self.pointer(&invocation.frames[depth], args[0]);
+                    let source = self.pointer(&invocation.frames[depth], args[1]);
+                    let value = self.load(invocation, shared, source);
+                    self.store(invocation, shared, target, value);
+                }
+                op::ACCESS_CHAIN | op::IN_BOUNDS_ACCESS_CHAIN => {
+                    let pointer = {
+                        let frame = &invocation.frames[depth];
+                        let indices = args[3 ..]
+                            .iter()
+                            .map(|&id| self.value(frame, id).scalar())
+                            .collect::<Vec<_>>();
+                        self.access_chain(self.pointer(frame, args[2]), &indices)
+                    };
+                    invocation.frames[depth].values.insert(args[1], Value::Pointer(pointer));
+                }
+                op::ARRAY_LENGTH => {
+                    let pointer = self.resolve(self.pointer(&invocation.frames[depth], args[2]));
+                    let length = match pointer {
+                        Pointer::Memory { offset, end, ty, .. } => {
+                            let array = match *self.program.ty(ty) {
+                                Type::Struct { ref members } => members[args[3] as usize],
+                                ref other => panic!("Unable to get the array length of {:?}", other),
+                            };
+                            let (member_offset, _) = self.program.member_layout(ty, args[3]);
+                            end.saturating_sub(offset + member_offset) / self.program.array_stride(array)
+                        }
+                        other => panic!("Unable to get the array length of {:?}", other),
+                    };
+                    invocation.frames[depth].values.insert(args[1], Value::Scalar(length));
+                }
+                op::ATOMIC_LOAD | op::ATOMIC_STORE | op::ATOMIC_EXCHANGE | op::ATOMIC_COMPARE_EXCHANGE |
+                op::ATOMIC_I_INCREMENT | op::ATOMIC_I_DECREMENT | op::ATOMIC_I_ADD | op::ATOMIC_I_SUB |
+                op::ATOMIC_S_MIN | op::ATOMIC_U_MIN | op::ATOMIC_S_MAX | op::ATOMIC_U_MAX |
+                op::ATOMIC_AND | op::ATOMIC_OR | op::ATOMIC_XOR => {
+                    self.atomic(invocation, shared, instruction.op, args);
+                }
+                op::FUNCTION_CALL => {
+                    let values = {
+                        let frame = &invocation.frames[depth];
+                        module.functions[&args[2]].parameters
+                            .iter()
+                            .zip(&args[3 ..])
+                            .map(|(&parameter, &argument)| (parameter, self.value(frame, argument)))
+                            .collect()
+                    };
+                    invocation.frames.push(Frame {
+                        function: args[2],
+                        block: 0,
+                        pc: 0,
+                        values,
+                        locals: HashMap::new(),
+                        result: Some(args[1]),
+                    });
+                }
+                op::RETURN | op::RETURN_VALUE => {
+                    let value = match instruction.op {
+                        op::RETURN_VALUE => Some(self.value(&invocation.frames[depth], args[0])),
+                        _ => None,
+                    };
+                    let frame = invocation.frames.pop().unwrap();
+                    match invocation.frames.last_mut() {
+                        Some(caller) => if let (Some(id), Some(value)) = (frame.result, value) {
+                            caller.values.insert(id, value);
+                        },
+                        None => return Status::Finished,
+                    }
+                }
+                op::BRANCH => self.jump(invocation, args[0]),
+                op::BRANCH_CONDITIONAL => {
+                    let condition = self.value(&invocation.frames[depth], args[0]).scalar() != 0;
+                    self.jump(invocation, if condition { args[1] } else { args[2] });
+                }
+                op::SWITCH => {
+                    let selector = self.value(&invocation.frames[depth], args[0]).scalar();
+                    let words = if self.program.scalar(self.program.type_of(args[0])).width() > 32 { 2 } else { 1 };
+                    let target = args[2 ..]
+                        .chunks(words + 1)
+                        .find(|case| {
+                            let literal = case[.. words]
+                                .iter()
+                                .rev()
+                                .fold(0, |acc, &word| (acc << 32) | word as u64);
+                            literal == selector
+                        })
+                        .map_or(args[1], |case| case[words]);
+                    self.jump(invocation, target);
+                }
+                op::UNREACHABLE => panic!("Reached an unreachable instruction"),
+                opcode => {
+                    let value = {
+                        let frame = &invocation.frames[depth];
+                        self.program.evaluate(opcode, args[0], &args[2 ..], |id| self.value(frame, id))
+                    };
+                    let value = value.unwrap_or_else(|| panic!("Unsupported instruction {}", opcode));
+                    invocation.frames[depth].values.insert(args[1], value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use hal::pso::DescriptorType;
+    use native::BufferDescriptor;
+    use hal::pso::spirv::assembler::{string, Assembler};
+    use hal::pso::spirv::decoration;
+
+    // Ids declared by `program`.
+    pub const UINT: u32 = 3;
+    pub const INT: u32 = 4;
+    pub const FLOAT: u32 = 5;
+    pub const BOOL: u32 = 6;
+    pub const INPUT_UINT: u32 = 10;
+    pub const GLOBAL_ID: u32 = 9;
+    pub const OUTPUT: u32 = 14;
+    pub const OUTPUT_UINT: u32 = 15;
+    pub const PUSH: u32 = 20;
+    pub const PUSH_UINT: u32 = 21;
+    pub const ENTRY: u32 = 31;
+    /// Unsigned constants from 0 to 15.
+    pub const C0: u32 = 60;
+
+    pub type Instructions = Vec<(u16, Vec<u32>)>;
+
+    /// Build the program of a module with the given constants and entry point body.
+    ///
+    /// The entry point has access to the `x` component of the global invocation
+    /// id, push constants `struct { uint a; uint b; }` and a storage buffer at
+    /// set 0, binding 0 laid out as `struct { uint count; uint data[]; }`,
+    /// with the array at offset 16 and a stride of 8 bytes.
+    pub fn program(local_size: u32, constants: &[(u16, Vec<u32>)], body: &[(u16, Vec<u32>)]) -> Program {
+        Program::new(Arc::new(module(local_size, constants, body)), "main", &[]).unwrap()
+    }
+
+    /// Build the module behind `program`.
+    fn module(local_size: u32, constants: &[(u16, Vec<u32>)], body: &[(u16, Vec<u32>)]) -> Module {
+        let mut asm = Assembler::new();
+        asm
+            .inst(op::CAPABILITY, &[1])
+            .inst(op::MEMORY_MODEL, &[0, 1])
+            .inst(op::ENTRY_POINT, &[&[spirv::EXECUTION_MODEL_GL_COMPUTE, 30][..], &string("main")[..], &[GLOBAL_ID][..]].concat())
+            .inst(op::EXECUTION_MODE, &[30, 17, local_size, 1, 1])
+            .inst(op::DECORATE, &[GLOBAL_ID, decoration::BUILT_IN, builtin::GLOBAL_INVOCATION_ID])
+            .inst(op::DECORATE, &[11, decoration::ARRAY_STRIDE, 8])
+            .inst(op::MEMBER_DECORATE, &[12, 0, decoration::OFFSET, 0])
+            .inst(op::MEMBER_DECORATE, &[12, 1, decoration::OFFSET, 16])
+            .inst(op::DECORATE, &[OUTPUT, decoration::DESCRIPTOR_SET, 0])
+            .inst(op::DECORATE, &[OUTPUT, decoration::BINDING, 0])
+            .inst(op::MEMBER_DECORATE, &[18, 0, decoration::OFFSET, 0])
+            .inst(op::MEMBER_DECORATE, &[18, 1, decoration::OFFSET, 4])
+            .inst(op::TYPE_VOID, &[1])
+            .inst(op::TYPE_FUNCTION, &[2, 1])
+            .inst(op::TYPE_INT, &[UINT, 32, 0])
+            .inst(op::TYPE_INT, &[INT, 32, 1])
+            .inst(op::TYPE_FLOAT, &[FLOAT, 32])
+            .inst(op::TYPE_BOOL, &[BOOL])
+            .inst(op::TYPE_VECTOR, &[7, UINT, 3])
+            .inst(op::TYPE_POINTER, &[8, storage::INPUT, 7])
+            .inst(op::TYPE_POINTER, &[INPUT_UINT, storage::INPUT, UINT])
+            .inst(op::TYPE_RUNTIME_ARRAY, &[11, UINT])
+            .inst(op::TYPE_STRUCT, &[12, UINT, 11])
+            .inst(op::TYPE_POINTER, &[13, storage::STORAGE_BUFFER, 12])
+            .inst(op::TYPE_POINTER, &[OUTPUT_UINT, storage::STORAGE_BUFFER, UINT])
+            .inst(op::TYPE_STRUCT, &[18, UINT, UINT])
+            .inst(op::TYPE_POINTER, &[19, storage::PUSH_CONSTANT, 18])
+            .inst(op::TYPE_POINTER, &[PUSH_UINT, storage::PUSH_CONSTANT, UINT]);
+        for i in 0 .. 16 {
+            asm.inst(op::CONSTANT, &[UINT, C0 + i, i]);
+        }
+        for &(opcode, ref operands) in constants {
+            asm.inst(opcode, operands);
+        }
+        asm
+            .inst(op::VARIABLE, &[8, GLOBAL_ID, storage::INPUT])
+            .inst(op::VARIABLE, &[13, OUTPUT, storage::STORAGE_BUFFER])
+            .inst(op::VARIABLE, &[19, PUSH, storage::PUSH_CONSTANT])
+            .inst(op::FUNCTION, &[1, 30, 0, 2])
+            .inst(op::LABEL, &[ENTRY]);
+        for &(opcode, ref operands) in body {
+            asm.inst(opcode, operands);
+        }
+        asm.inst(op::FUNCTION_END, &[]);
+
+        spirv::parse(&asm.bytes()).unwrap()
+    }
+
+    /// Load the `x` component of the global invocation id into `id`.
+    pub fn load_global_id(id: u32) -> Instructions {
+        vec![
+            (op::ACCESS_CHAIN, vec![INPUT_UINT, id + 1000, GLOBAL_ID, C0]),
+            (op::LOAD, vec![UINT, id, id + 1000]),
+        ]
+    }
+
+    /// Store `value` into `data[index]`.
+    pub fn store_data(index: u32, value: u32) -> Instructions {
+        // pointer ids are derived from the value, which is only stored once
+        vec![
+            (op::ACCESS_CHAIN, vec![OUTPUT_UINT, value + 2000, OUTPUT, C0 + 1, index]),
+            (op::STORE, vec![value + 2000, value]),
+        ]
+    }
+
+    /// Store the values into consecutive elements of `data`, then return.
+    fn store_all(values: &[u32]) -> Instructions {
+        let mut body = Instructions::new();
+        for (i, &value) in values.iter().enumerate() {
+            body.extend(store_data(C0 + i as u32, value));
+        }
+        body.push((op::RETURN, vec![]));
+        body
+    }
+
+    /// Binding of a storage buffer with `count` elements in `data`.
+    pub fn storage_binding(count: usize) -> (Arc<Block>, DescriptorBinding) {
+        let block = Arc::new(Block::new(16 + 8 * count as u64));
+        let binding = DescriptorBinding {
+            binding: 0,
+            ty: DescriptorType::StorageBuffer,
+            descriptors: vec![Some(BufferDescriptor {
+                block: block.clone(),
+                range: 0 .. block.size(),
+            })],
+        };
+        (block, binding)
+    }
+
+    /// Read the `count` field and the elements of `data`.
+    pub fn read_storage(block: &Block) -> (u32, Vec<u32>) {
+        let mut bytes = vec![0; block.size() as usize];
+        unsafe { block.read(0, &mut bytes) };
+        let word = |offset: usize| {
+            bytes[offset .. offset + 4].iter().rev().fold(0, |acc, &b| (acc << 8) | b as u32)
+        };
+        let data = (16 .. bytes.len()).step_by(8).map(word).collect();
+        (word(0), data)
+    }
+
+    fn run(program: &Program, push_constants: &[u8], groups: [u32; 3], count: usize) -> Vec<u32> {
+        let (block, binding) = storage_binding(count);
+        dispatch(program, &[vec![binding]], push_constants, groups);
+        read_storage(&block).1
+    }
+
+    fn constant(ty: u32, id: u32, bits: u32) -> (u16, Vec<u32>) {
+        (op::CONSTANT, vec![ty, id, bits])
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        let constants = vec![
+            constant(UINT, 50, 7),
+            constant(UINT, 51, 5),
+            constant(INT, 52, -7i32 as u32),
+            constant(INT, 53, 2),
+            constant(INT, 54, -8i32 as u32),
+        ];
+        let mut body = vec![
+            (op::I_ADD, vec![UINT, 100, 50, 51]),
+            (op::I_SUB, vec![UINT, 101, 51, 50]),
+            (op::I_MUL, vec![UINT, 102, 50, 51]),
+            (op::U_DIV, vec![UINT, 103, 50, C0 + 2]),
+            (op::S_DIV, vec![INT, 104, 52, 53]),
+            (op::S_REM, vec![INT, 105, 52, 53]),
+            (op::S_MOD, vec![INT, 106, 52, 53]),
+            (op::SHIFT_RIGHT_ARITHMETIC, vec![INT, 107, 54, C0 + 1]),
+            (op::SHIFT_RIGHT_LOGICAL, vec![UINT, 108, 54, C0 + 1]),
+            (op::S_NEGATE, vec![INT, 109, 52]),
+        ];
+        body.extend(store_all(&[100, 101, 102, 103, 104, 105, 106, 107, 108, 109]));
+        let program = program(1, &constants, &body);
+
+        assert_eq!(run(&program, &[], [1, 1, 1], 10), vec![
+            12,
+            -2i32 as u32,
+            35,
+            3,
+            -3i32 as u32,
+            -1i32 as u32,
+            1,
+            -4i32 as u32,
+            0x7FFF_FFFC,
+            7,
+        ]);
+    }
+
+    #[test]
+    fn float_arithmetic_and_conversions() {
+        let constants = vec![
+            constant(FLOAT, 50, 1.5f32.to_bits()),
+            constant(FLOAT, 51, 2.25f32.to_bits()),
+            constant(FLOAT, 52, 1.0f32.to_bits()),
+            constant(FLOAT, 53, 3.0f32.to_bits()),
+            constant(FLOAT, 54, (-2.75f32).to_bits()),
+            constant(FLOAT, 55, 3.9f32.to_bits()),
+            constant(INT, 56, -3i32 as u32),
+            constant(UINT, 57, 16_777_217),
+        ];
+        let mut body = vec![
+            (op::F_ADD, vec![FLOAT, 100, 50, 51]),
+            (op::F_DIV, vec![FLOAT, 101, 52, 53]),
+            (op::F_MUL, vec![FLOAT, 102, 50, 51]),
+            (op::F_SUB, vec![FLOAT, 103, 50, 51]),
+            (op::CONVERT_F_TO_S, vec![INT, 104, 54]),
+            (op::CONVERT_F_TO_U, vec![UINT, 105, 55]),
+            (op::CONVERT_S_TO_F, vec![FLOAT, 106, 56]),
+            (op::CONVERT_U_TO_F, vec![FLOAT, 107, 57]),
+        ];
+        for i in 0 .. 8 {
+            body.push((op::BITCAST, vec![UINT, 110 + i, 100 + i]));
+        }
+        body.extend(store_all(&[110, 111, 112, 113, 114, 115, 116, 117]));
+        let program = program(1, &constants, &body);
+
+        assert_eq!(run(&program, &[], [1, 1, 1], 8), vec![
+            3.75f32.to_bits(),
+            (1.0f32 / 3.0).to_bits(),
+            3.375f32.to_bits(),
+            (-0.75f32).to_bits(),
+            -2i32 as u32,
+            3,
+            (-3.0f32).to_bits(),
+            16_777_216f32.to_bits(),
+        ]);
+    }
+
+    #[test]
+    fn comparisons() {
+        let constants = vec![
+            constant(INT, 50, -1i32 as u32),
+            constant(INT, 51, 1),
+            constant(FLOAT, 52, 0.5f32.to_bits()),
+            constant(FLOAT, 53, 0x7FC0_0000), // NaN
+        ];
+        let mut body = vec![
+            (op::S_LESS_THAN, vec![BOOL, 100, 50, 51]),
+            (op::U_LESS_THAN, vec![BOOL, 101, 50, 51]),
+            (op::F_ORD_LESS_THAN, vec![BOOL, 102, 52, 53]),
+            (op::F_UNORD_LESS_THAN, vec![BOOL, 103, 52, 53]),
+            (op::I_EQUAL, vec![BOOL, 104, 50, 50]),
+            (op::S_GREATER_THAN, vec![BOOL, 105, 50, 51]),
+            (op::F_ORD_GREATER_THAN_EQUAL, vec![BOOL, 106, 52, 52]),
+            (op::U_GREATER_THAN, vec![BOOL, 107, 50, 51]),
+        ];
+        for i in 0 .. 8 {
+            body.push((op::SELECT, vec![UINT, 110 + i, 100 + i, C0 + 1, C0]));
+        }
+        body.extend(store_all(&[110, 111, 112, 113, 114, 115, 116, 117]));
+        let program = program(1, &constants, &body);
+
+        assert_eq!(run(&program, &[], [1, 1, 1], 8), vec![1, 0, 0, 1, 1, 0, 1, 1]);
+    }
+
+    #[test]
+    fn access_chain_runtime_array() {
+        let constants = vec![constant(UINT, 50, 42)];
+        let mut body = load_global_id(100);
+        body.push((op::I_MUL, vec![UINT, 101, 100, C0 + 2]));
+        body.extend(store_data(100, 101));
+        body.push((op::ACCESS_CHAIN, vec![OUTPUT_UINT, 102, OUTPUT, C0]));
+        body.push((op::STORE, vec![102, 50]));
+        body.push((op::RETURN, vec![]));
+        let program = program(4, &constants, &body);
+
+        let (block, binding) = storage_binding(9);
+        dispatch(&program, &[vec![binding]], &[], [2, 1, 1]);
+        assert_eq!(read_storage(&block), (42, vec![0, 2, 4, 6, 8, 10, 12, 14, 0]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn access_chain_past_end() {
+        let mut body = load_global_id(100);
+        body.extend(store_data(100, 100));
+        body.push((op::RETURN, vec![]));
+        let program = program(4, &[], &body);
+        run(&program, &[], [1, 1, 1], 3);
+    }
+
+    #[test]
+    fn loop_with_phi() {
+        // sum of the integers up to 10
+        let constants = vec![constant(UINT, 50, 10)];
+        let mut body = vec![
+            (op::BRANCH, vec![40]),
+            (op::LABEL, vec![40]),
+            (op::PHI, vec![UINT, 101, C0, ENTRY, 104, 42]),
+            (op::PHI, vec![UINT, 102, C0, ENTRY, 103, 42]),
+            (op::LOOP_MERGE, vec![43, 42, 0]),
+            (op::U_LESS_THAN_EQUAL, vec![BOOL, 105, 101, 50]),
+            (op::BRANCH_CONDITIONAL, vec![105, 41, 43]),
+            (op::LABEL, vec![41]),
+            (op::I_ADD, vec![UINT, 103, 102, 101]),
+            (op::BRANCH, vec![42]),
+            (op::LABEL, vec![42]),
+            (op::I_ADD, vec![UINT, 104, 101, C0 + 1]),
+            (op::BRANCH, vec![40]),
+            (op::LABEL, vec![43]),
+        ];
+        body.extend(store_all(&[102, 101]));
+        let program = program(1, &constants, &body);
+
+        assert_eq!(run(&program, &[], [1, 1, 1], 2), vec![55, 11]);
+    }
+
+    #[test]
+    fn selection_with_phi() {
+        let constants = vec![constant(UINT, 50, 100), constant(UINT, 51, 200)];
+        let mut body = load_global_id(100);
+        body.extend(vec![
+            (op::U_MOD, vec![UINT, 101, 100, C0 + 2]),
+            (op::I_EQUAL, vec![BOOL, 102, 101, C0]),
+            (op::SELECTION_MERGE, vec![46, 0]),
+            (op::BRANCH_CONDITIONAL, vec![102, 44, 45]),
+            (op::LABEL, vec![44]),
+            (op::BRANCH, vec![46]),
+            (op::LABEL, vec![45]),
+            (op::BRANCH, vec![46]),
+            (op::LABEL, vec![46]),
+            (op::PHI, vec![UINT, 103, 50, 44, 51, 45]),
+        ]);
+        body.extend(store_data(100, 103));
+        body.push((op::RETURN, vec![]));
+        let program = program(4, &constants, &body);
+
+        assert_eq!(run(&program, &[], [1, 1, 1], 4), vec![100, 200, 100, 200]);
+    }
+
+    #[test]
+    fn push_constants() {
+        let mut body = vec![
+            (op::ACCESS_CHAIN, vec![PUSH_UINT, 100, PUSH, C0]),
+            (op::LOAD, vec![UINT, 101, 100]),
+            (op::ACCESS_CHAIN, vec![PUSH_UINT, 102, PUSH, C0 + 1]),
+            (op::LOAD, vec![UINT, 103, 102]),
+            (op::I_SUB, vec![UINT, 104, 101, 103]),
+        ];
+        body.extend(store_all(&[104]));
+        let program = program(1, &[], &body);
+
+        assert_eq!(run(&program, &[10, 0, 0, 0, 3, 0, 0, 0], [1, 1, 1], 1), vec![7]);
+    }
+
+    fn compile_error(constants: &[(u16, Vec<u32>)], body: &[(u16, Vec<u32>)]) -> String {
+        let mut body = body.to_vec();
+        body.push((op::RETURN, vec![]));
+        match Program::new(Arc::new(module(1, constants, &body)), "main", &[]) {
+            Err(device::ShaderError::CompilationFailed(message)) => message,
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unsupported_instruction() {
+        // OpImageRead
+        let message = compile_error(&[], &[(98, vec![UINT, 100, C0, C0])]);
+        assert_eq!(message, "Unsupported instruction 98");
+    }
+
+    #[test]
+    fn unsupported_glsl_instruction() {
+        let constants = vec![(op::EXT_INST_IMPORT, [&[40][..], &string(spirv::GLSL_STD_450)[..]].concat())];
+        // PackHalf2x16
+        let message = compile_error(&constants, &[(op::EXT_INST, vec![UINT, 100, 40, 58, C0])]);
+        assert_eq!(message, "Unsupported GLSL.std.450 instruction 58");
+
+        let constants = vec![(op::EXT_INST_IMPORT, [&[40][..], &string("OpenCL.std")[..]].concat())];
+        let message = compile_error(&constants, &[(op::EXT_INST, vec![UINT, 100, 40, 1, C0])]);
+        assert_eq!(message, "Unsupported extended instruction set Some(\"OpenCL.std\")");
+    }
+
+    #[test]
+    fn unsupported_float_width() {
+        let message = compile_error(&[(op::TYPE_FLOAT, vec![40, 16])], &[]);
+        assert_eq!(message, "Unsupported floating-point width 16 of type 40");
+    }
+
+    #[test]
+    fn unsupported_variables() {
+        // SubgroupId
+        let constants = vec![
+            (op::DECORATE, vec![41, decoration::BUILT_IN, 40]),
+            (op::VARIABLE, vec![INPUT_UINT, 41, storage::INPUT]),
+        ];
+        let message = compile_error(&constants, &[(op::LOAD, vec![UINT, 100, 41])]);
+        assert_eq!(message, "Unsupported input variable 41 (built-in Some(40))");
+
+        // Output
+        let constants = vec![
+            (op::TYPE_POINTER, vec![40, 3, UINT]),
+            (op::VARIABLE, vec![40, 41, 3]),
+        ];
+        let message = compile_error(&constants, &[(op::STORE, vec![41, C0])]);
+        assert_eq!(message, "Unsupported storage class 3 of variable 41");
+    }
+
+    #[test]
+    fn unused_unsupported_variable() {
+        let constants = vec![
+            (op::TYPE_POINTER, vec![40, 3, UINT]),
+            (op::VARIABLE, vec![40, 41, 3]),
+        ];
+        assert!(Program::new(Arc::new(module(1, &constants, &[(op::RETURN, vec![])])), "main", &[]).is_ok());
+    }
+}
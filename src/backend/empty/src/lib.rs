@@ -1,15 +1,33 @@
-//! Dummy backend implementation to test the code for compile errors
-//! outside of the graphics development environment.
+//! Empty backend, executing work on the CPU.
+//!
+//! Resources live in host memory and command buffers are executed by the
+//! queue at submission time. Transfer operations are fully supported, and
+//! compute dispatches are run by interpreting the SPIR-V of the pipeline,
+//! which makes the backend usable for testing upload code and compute
+//! kernels on machines without a GPU. Everything else is left
+//! unimplemented, so the backend still serves to check the code for compile
+//! errors outside of the graphics development environment.
 
 extern crate gfx_hal as hal;
 
-use std::borrow::Borrow;
-use std::ops::Range;
-use hal::{
-    buffer, command, device, error, format, image, mapping,
-    memory, pass, pool, pso, query, queue
-};
-use hal::range::RangeArg;
+use hal::{error, format, image, memory, pso};
+
+mod command;
+mod conv;
+mod device;
+mod interpreter;
+mod native;
+mod queue;
+
+pub use command::{RawCommandBuffer, RawCommandPool};
+pub use queue::RawCommandQueue;
+pub use device::Device;
+
+/// Memory types supported by all the resources.
+const MEMORY_TYPE_MASK: u64 = 0x1;
+
+/// Size of the single memory heap.
+const HEAP_SIZE: u64 = 1 << 32;
 
 /// Dummy backend.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -25,765 +43,176 @@ impl hal::Backend for Backend {
     type CommandQueue = RawCommandQueue;
     type CommandBuffer = RawCommandBuffer;
 
-    type Memory = ();
+    type Memory = native::Memory;
     type CommandPool = RawCommandPool;
 
-    type ShaderModule = ();
+    type ShaderModule = native::ShaderModule;
     type RenderPass = ();
     type Framebuffer = ();
 
-    type UnboundBuffer = ();
-    type Buffer = ();
+    type UnboundBuffer = native::UnboundBuffer;
+    type Buffer = native::Buffer;
     type BufferView = ();
-    type UnboundImage = ();
-    type Image = ();
+    type UnboundImage = native::UnboundImage;
+    type Image = native::Image;
     type ImageView = ();
     type Sampler = ();
 
-    type ComputePipeline = ();
+    type ComputePipeline = native::ComputePipeline;
     type GraphicsPipeline = ();
-    type PipelineCache = ();
+    type PipelineCache = native::PipelineCache;
     type PipelineLayout = ();
-    type DescriptorSetLayout = ();
+    type DescriptorSetLayout = native::DescriptorSetLayout;
     type DescriptorPool = DescriptorPool;
-    type DescriptorSet = ();
+    type DescriptorSet = native::DescriptorSet;
 
-    type Fence = ();
+    type Fence = native::Fence;
     type Semaphore = ();
+    type Event = native::Event;
     type QueryPool = ();
 }
 
-/// Dummy physical device.
+/// Physical device representing the host CPU.
 pub struct PhysicalDevice;
 impl hal::PhysicalDevice<Backend> for PhysicalDevice {
     fn open(
-        &self, _: &[(&QueueFamily, &[hal::QueuePriority])]
+        &self, families: &[(&QueueFamily, &[hal::QueuePriority])], requested_features: hal::Features,
     ) -> Result<hal::Gpu<Backend>, error::DeviceCreationError> {
-        unimplemented!()
-    }
-
-    fn format_properties(&self, _: Option<format::Format>) -> format::Properties {
-        unimplemented!()
+        if !self.features().contains(requested_features) {
+            return Err(error::DeviceCreationError::MissingFeature);
+        }
+
+        let queue_groups = families
+            .iter()
+            .map(|&(family, priorities)| {
+                let mut group = hal::backend::RawQueueGroup::new(family.clone());
+                for _ in priorities {
+                    group.add_queue(RawCommandQueue);
+                }
+                group
+            })
+            .collect();
+
+        Ok(hal::Gpu {
+            device: Device,
+            queues: hal::queue::Queues::new(queue_groups),
+        })
+    }
+
+    fn format_properties(&self, format: Option<format::Format>) -> format::Properties {
+        use hal::format::ImageFeature;
+
+        let image_features = match format {
+            Some(format) if conv::is_interpretable(format) => {
+                ImageFeature::BLIT_SRC | ImageFeature::BLIT_DST | ImageFeature::SAMPLED_LINEAR
+            }
+            _ => ImageFeature::empty(),
+        };
+        format::Properties {
+            linear_tiling: image_features,
+            optimal_tiling: image_features,
+            buffer_features: format::BufferFeature::empty(),
+        }
     }
 
     fn image_format_properties(
-        &self, _: format::Format, _dim: u8, _: image:: Tiling,
+        &self, _: format::Format, dimensions: u8, _: image::Tiling,
         _: image::Usage, _: image::StorageFlags,
     ) -> Option<image::FormatProperties> {
-        unimplemented!()
+        let max_dimension = self.limits().max_texture_size as image::Size;
+        Some(image::FormatProperties {
+            max_extent: image::Extent {
+                width: max_dimension,
+                height: if dimensions >= 2 { max_dimension } else { 1 },
+                depth: if dimensions >= 3 { max_dimension } else { 1 },
+            },
+            max_levels: 15,
+            max_layers: 2048,
+            sample_count_mask: 0x1,
+            max_resource_size: !0,
+        })
     }
 
     fn memory_properties(&self) -> hal::MemoryProperties {
-        unimplemented!()
+        // All the memory is host memory, there is no distinction to make.
+        hal::MemoryProperties {
+            memory_types: vec![
+                hal::MemoryType {
+                    properties: memory::Properties::DEVICE_LOCAL | memory::Properties::CPU_VISIBLE |
+                        memory::Properties::COHERENT | memory::Properties::CPU_CACHED,
+                    heap_index: 0,
+                },
+            ],
+            memory_heaps: vec![HEAP_SIZE],
+        }
     }
 
     fn features(&self) -> hal::Features {
-        unimplemented!()
+        hal::Features::empty()
     }
 
     fn limits(&self) -> hal::Limits {
-        unimplemented!()
-    }
-}
-
-/// Dummy command queue doing nothing.
-pub struct RawCommandQueue;
-impl queue::RawCommandQueue<Backend> for RawCommandQueue {
-    unsafe fn submit_raw<IC>(&mut self, _: queue::RawSubmission<Backend, IC>, _: Option<&()>)
-    where
-        IC: IntoIterator,
-        IC::Item: Borrow<RawCommandBuffer>,
-    {
-        unimplemented!()
-    }
-
-    fn present<IS, S, IW>(&mut self, _: IS, _: IW) -> Result<(), ()>
-    where
-        IS: IntoIterator<Item = (S, hal::SwapImageIndex)>,
-        S: Borrow<Swapchain>,
-        IW: IntoIterator,
-        IW::Item: Borrow<()>,
-    {
-        unimplemented!()
-    }
-
-    fn wait_idle(&self) -> Result<(), error::HostExecutionError> {
-        unimplemented!()
+        hal::Limits {
+            max_texture_size: 1 << 14,
+            max_patch_size: 0,
+            max_viewports: 1,
+            max_compute_group_count: [!0; 3],
+            max_compute_group_size: [1024, 1024, 64],
+            max_vertex_input_attributes: 16,
+            max_vertex_input_bindings: 16,
+            max_vertex_input_attribute_offset: 2047,
+            max_vertex_input_binding_stride: 2048,
+            max_vertex_output_components: 64,
+            min_buffer_copy_offset_alignment: 1,
+            min_buffer_copy_pitch_alignment: 1,
+            min_texel_buffer_offset_alignment: 1,
+            min_uniform_buffer_offset_alignment: 1,
+            min_storage_buffer_offset_alignment: 1,
+            framebuffer_color_samples_count: 1,
+            framebuffer_depth_samples_count: 1,
+            framebuffer_stencil_samples_count: 1,
+            max_color_attachments: 8,
+            non_coherent_atom_size: 1,
+        }
+    }
+
+    fn timestamp_period(&self) -> f32 {
+        1.0
     }
 }
 
-/// Dummy device doing nothing.
-pub struct Device;
-impl hal::Device<Backend> for Device {
-    fn create_command_pool(&self, _: queue::QueueFamilyId, _: pool::CommandPoolCreateFlags) -> RawCommandPool {
-        unimplemented!()
-    }
-
-    fn destroy_command_pool(&self, _: RawCommandPool) {
-        unimplemented!()
-    }
-
-    fn allocate_memory(&self, _: hal::MemoryTypeId, _: u64) -> Result<(), device::OutOfMemory> {
-        unimplemented!()
-    }
-
-    fn create_render_pass<'a ,IA, IS, ID>(&self, _: IA, _: IS, _: ID) -> ()
-    where
-        IA: IntoIterator,
-        IA::Item: Borrow<pass::Attachment>,
-        IS: IntoIterator,
-        IS::Item: Borrow<pass::SubpassDesc<'a>>,
-        ID: IntoIterator,
-        ID::Item: Borrow<pass::SubpassDependency>,
-    {
-        unimplemented!()
-    }
-
-    fn create_pipeline_layout<IS, IR>(&self, _: IS, _: IR) -> ()
-    where
-        IS: IntoIterator,
-        IS::Item: Borrow<()>,
-        IR: IntoIterator,
-        IR::Item: Borrow<(pso::ShaderStageFlags, Range<u32>)>,
-    {
-        unimplemented!()
-    }
-
-    fn create_pipeline_cache(&self) -> () {
-        unimplemented!()
-    }
-
-    fn destroy_pipeline_cache(&self, _: ()) {
-        unimplemented!()
-    }
-
-    fn merge_pipeline_caches<I>(&self, _: &(), _: I)
-    where
-        I: IntoIterator,
-        I::Item: Borrow<()>,
-    {
-        unimplemented!()
-    }
-
-    fn create_framebuffer<I>(
-        &self, _: &(), _: I, _: image::Extent
-    ) -> Result<(), device::FramebufferError>
-    where
-        I: IntoIterator,
-        I::Item: Borrow<()>,
-    {
-        unimplemented!()
-    }
-
-    fn create_shader_module(&self, _: &[u8]) -> Result<(), device::ShaderError> {
-        unimplemented!()
-    }
-
-    fn create_sampler(&self, _: image::SamplerInfo) -> () {
-        unimplemented!()
-    }
-    fn create_buffer(&self, _: u64, _: buffer::Usage) -> Result<(), buffer::CreationError> {
-        unimplemented!()
-    }
-
-    fn get_buffer_requirements(&self, _: &()) -> memory::Requirements {
-        unimplemented!()
-    }
-
-    fn bind_buffer_memory(&self, _: &(), _: u64, _: ()) -> Result<(), device::BindError> {
-        unimplemented!()
-    }
-
-    fn create_buffer_view<R: RangeArg<u64>>(&self, _: &(), _: Option<format::Format>, _: R) -> Result<(), buffer::ViewCreationError> {
-        unimplemented!()
-    }
-
-    fn create_image(
-        &self,
-        _: image::Kind,
-        _: image::Level,
-        _: format::Format,
-        _: image::Tiling,
-        _: image::Usage,
-        _: image::StorageFlags,
-    ) -> Result<(), image::CreationError> {
-        unimplemented!()
-    }
-
-    fn get_image_requirements(&self, _: &()) -> memory::Requirements {
-        unimplemented!()
-    }
-
-    fn get_image_subresource_footprint(&self, _: &(), _: image::Subresource) -> image::SubresourceFootprint {
-        unimplemented!()
-    }
-
-    fn bind_image_memory(&self, _: &(), _: u64, _: ()) -> Result<(), device::BindError> {
-        unimplemented!()
-    }
-
-    fn create_image_view(
-        &self,
-        _: &(),
-        _: image::ViewKind,
-        _: format::Format,
-        _: format::Swizzle,
-        _: image::SubresourceRange,
-    ) -> Result<(), image::ViewError> {
-        unimplemented!()
-    }
-
-    fn create_descriptor_pool<I>(&self, _: usize, _: I) -> DescriptorPool
-    where
-        I: IntoIterator,
-        I::Item: Borrow<pso::DescriptorRangeDesc>,
-    {
-        unimplemented!()
-    }
-
-    fn create_descriptor_set_layout<I, J>(&self, _: I, _: J) -> ()
-    where
-        I: IntoIterator,
-        I::Item: Borrow<pso::DescriptorSetLayoutBinding>,
-        J: IntoIterator,
-        J::Item: Borrow<()>
-    {
-        unimplemented!()
-    }
-
-    fn write_descriptor_sets<'a, I, J>(&self, _: I)
-    where
-        I: IntoIterator<Item = pso::DescriptorSetWrite<'a, Backend, J>>,
-        J: IntoIterator,
-        J::Item: Borrow<pso::Descriptor<'a, Backend>>,
-    {
-        unimplemented!()
-    }
-
-    fn copy_descriptor_sets<'a, I>(&self, _: I)
-    where
-        I: IntoIterator,
-        I::Item: Borrow<pso::DescriptorSetCopy<'a, Backend>>
-    {
-        unimplemented!()
-    }
-
-    fn create_semaphore(&self) -> () {
-        unimplemented!()
-    }
-
-    fn create_fence(&self, _: bool) -> () {
-        unimplemented!()
-    }
-
-    fn get_fence_status(&self, _: &()) -> bool {
-        unimplemented!()
-    }
-
-    fn create_query_pool(&self, _: query::QueryType, _: query::QueryId) -> () {
-        unimplemented!()
-    }
-
-    fn destroy_query_pool(&self, _: ()) {
-        unimplemented!()
-    }
-
-    fn map_memory<R: RangeArg<u64>>(&self, _: &(), _: R) -> Result<*mut u8, mapping::Error> {
-        unimplemented!()
-    }
-
-    fn unmap_memory(&self, _: &()) {
-        unimplemented!()
-    }
-
-    fn flush_mapped_memory_ranges<'a, I, R>(&self, _: I)
-    where
-        I: IntoIterator,
-        I::Item: Borrow<(&'a (), R)>,
-        R: RangeArg<u64>,
-    {
-        unimplemented!()
-    }
-
-    fn invalidate_mapped_memory_ranges<'a, I, R>(&self, _: I)
-    where
-        I: IntoIterator,
-        I::Item: Borrow<(&'a (), R)>,
-        R: RangeArg<u64>,
-    {
-        unimplemented!()
-    }
-
-    fn free_memory(&self, _: ()) {
-        unimplemented!()
-    }
-
-    fn destroy_shader_module(&self, _: ()) {
-        unimplemented!()
-    }
-
-    fn destroy_render_pass(&self, _: ()) {
-        unimplemented!()
-    }
-
-    fn destroy_pipeline_layout(&self, _: ()) {
-        unimplemented!()
-    }
-    fn destroy_graphics_pipeline(&self, _: ()) {
-        unimplemented!()
-    }
-    fn destroy_compute_pipeline(&self, _: ()) {
-        unimplemented!()
-    }
-    fn destroy_framebuffer(&self, _: ()) {
-        unimplemented!()
-    }
-
-    fn destroy_buffer(&self, _: ()) {
-        unimplemented!()
-    }
-    fn destroy_buffer_view(&self, _: ()) {
-        unimplemented!()
-    }
-    fn destroy_image(&self, _: ()) {
-        unimplemented!()
-    }
-    fn destroy_image_view(&self, _: ()) {
-        unimplemented!()
-    }
-    fn destroy_sampler(&self, _: ()) {
-        unimplemented!()
-    }
-
-    fn destroy_descriptor_pool(&self, _: DescriptorPool) {
-        unimplemented!()
-    }
-
-    fn destroy_descriptor_set_layout(&self, _: ()) {
-        unimplemented!()
-    }
-
-    fn destroy_fence(&self, _: ()) {
-        unimplemented!()
-    }
-
-    fn destroy_semaphore(&self, _: ()) {
-        unimplemented!()
-    }
-
-    fn create_swapchain(
-        &self,
-        _: &mut Surface,
-        _: hal::SwapchainConfig,
-        _: Option<Swapchain>,
-    ) -> (Swapchain, hal::Backbuffer<Backend>) {
-        unimplemented!()
-    }
-
-    fn destroy_swapchain(&self, _: Swapchain) {
-        unimplemented!()
-    }
-
-    fn wait_idle(&self) -> Result<(), error::HostExecutionError> {
-        unimplemented!()
-    }
-}
-
-#[derive(Debug)]
+/// The only queue family, supporting all operations.
+#[derive(Clone, Debug)]
 pub struct QueueFamily;
-impl queue::QueueFamily for QueueFamily {
+impl hal::queue::QueueFamily for QueueFamily {
     fn queue_type(&self) -> hal::QueueType {
-        unimplemented!()
+        hal::QueueType::General
     }
     fn max_queues(&self) -> usize {
-        unimplemented!()
-    }
-    fn id(&self) -> queue::QueueFamilyId {
-        unimplemented!()
-    }
-}
-
-/// Dummy raw command pool.
-pub struct RawCommandPool;
-impl pool::RawCommandPool<Backend> for RawCommandPool {
-    fn reset(&mut self) {
-        unimplemented!()
-    }
-
-    fn allocate(&mut self, _: usize, _: command::RawLevel) -> Vec<RawCommandBuffer> {
-        unimplemented!()
-    }
-
-    unsafe fn free(&mut self, _: Vec<RawCommandBuffer>) {
-        unimplemented!()
-    }
-}
-
-/// Dummy command buffer, which ignores all the calls.
-#[derive(Clone)]
-pub struct RawCommandBuffer;
-impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
-    fn begin(&mut self, _: command::CommandBufferFlags, _: command::CommandBufferInheritanceInfo<Backend>) {
-        unimplemented!()
-    }
-
-    fn finish(&mut self) {
-        unimplemented!()
-    }
-
-    fn reset(&mut self, _: bool) {
-        unimplemented!()
-    }
-
-    fn pipeline_barrier<'a, T>(
-        &mut self,
-        _: Range<pso::PipelineStage>,
-        _: memory::Dependencies,
-        _: T,
-    ) where
-        T: IntoIterator,
-        T::Item: Borrow<memory::Barrier<'a, Backend>>,
-    {
-        unimplemented!()
-    }
-
-    fn fill_buffer<R>(&mut self, _: &(), _: R, _: u32)
-    where
-        R: RangeArg<buffer::Offset>,
-    {
-        unimplemented!()
-    }
-
-    fn update_buffer(&mut self, _: &(), _: buffer::Offset, _: &[u8]) {
-        unimplemented!()
-    }
-
-    fn clear_image<T>(
-        &mut self,
-        _: &(),
-        _: image::Layout,
-        _: command::ClearColorRaw,
-        _: command::ClearDepthStencilRaw,
-        _: T,
-    ) where
-        T: IntoIterator,
-        T::Item: Borrow<image::SubresourceRange>,
-    {
-        unimplemented!()
-    }
-
-    fn clear_attachments<T, U>(&mut self, _: T, _: U)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<command::AttachmentClear>,
-        U: IntoIterator,
-        U::Item: Borrow<pso::ClearRect>,
-    {
-        unimplemented!()
-    }
-
-    fn resolve_image<T>(
-        &mut self,
-        _: &(),
-        _: image::Layout,
-        _: &(),
-        _: image::Layout,
-        _: T,
-    ) where
-        T: IntoIterator,
-        T::Item: Borrow<command::ImageResolve>,
-    {
-        unimplemented!()
-    }
-
-    fn blit_image<T>(
-        &mut self,
-        _: &(),
-        _: image::Layout,
-        _: &(),
-        _: image::Layout,
-        _: image::Filter,
-        _: T,
-    ) where
-        T: IntoIterator,
-        T::Item: Borrow<command::ImageBlit>,
-    {
-        unimplemented!()
-    }
-
-    fn bind_index_buffer(&mut self, _: buffer::IndexBufferView<Backend>) {
-        unimplemented!()
-    }
-
-    fn bind_vertex_buffers<I, T>(&mut self, _: u32, _: I)
-    where
-        I: IntoIterator<Item = (T, buffer::Offset)>,
-        T: Borrow<()>,
-    {
-        unimplemented!()
-    }
-
-    fn set_viewports<T>(&mut self, _: u32, _: T)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<pso::Viewport>,
-    {
-        unimplemented!()
-    }
-
-    fn set_scissors<T>(&mut self, _: u32, _: T)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<pso::Rect>,
-    {
-        unimplemented!()
-    }
-
-    fn set_stencil_reference(&mut self, _: pso::Face, _: pso::StencilValue) {
-        unimplemented!()
-    }
-
-    fn set_stencil_read_mask(&mut self, _: pso::Face, _: pso::StencilValue) {
-        unimplemented!()
-    }
-
-    fn set_stencil_write_mask(&mut self, _: pso::Face, _: pso::StencilValue) {
-        unimplemented!()
-    }
-
-    fn set_blend_constants(&mut self, _: pso::ColorValue) {
-        unimplemented!()
-    }
-
-    fn set_depth_bounds(&mut self, _: Range<f32>) {
-        unimplemented!()
-    }
-
-    fn set_line_width(&mut self, _: f32) {
-        unimplemented!()
+        1
     }
-
-    fn set_depth_bias(&mut self, _: pso::DepthBias) {
-        unimplemented!()
-    }
-
-    fn begin_render_pass<T>(
-        &mut self,
-        _: &(),
-        _: &(),
-        _: pso::Rect,
-        _: T,
-        _: command::SubpassContents,
-    ) where
-        T: IntoIterator,
-        T::Item: Borrow<command::ClearValueRaw>,
-    {
-        unimplemented!()
-    }
-
-    fn next_subpass(&mut self, _: command::SubpassContents) {
-        unimplemented!()
-    }
-
-    fn end_render_pass(&mut self) {
-        unimplemented!()
-    }
-
-    fn bind_graphics_pipeline(&mut self, _: &()) {
-        unimplemented!()
-    }
-
-    fn bind_graphics_descriptor_sets<I, J>(&mut self, _: &(), _: usize, _: I, _: J)
-    where
-        I: IntoIterator,
-        I::Item: Borrow<()>,
-        J: IntoIterator,
-        J::Item: Borrow<command::DescriptorSetOffset>,
-    {
-        unimplemented!()
-    }
-
-    fn bind_compute_pipeline(&mut self, _: &()) {
-        unimplemented!()
-    }
-
-    fn bind_compute_descriptor_sets<I, J>(&mut self, _: &(), _: usize, _: I, _: J)
-    where
-        I: IntoIterator,
-        I::Item: Borrow<()>,
-        J: IntoIterator,
-        J::Item: Borrow<command::DescriptorSetOffset>,
-    {
-        unimplemented!()
-    }
-
-    fn dispatch(&mut self, _: hal::WorkGroupCount) {
-        unimplemented!()
-    }
-
-    fn dispatch_indirect(&mut self, _: &(), _: buffer::Offset) {
-        unimplemented!()
-    }
-
-    fn copy_buffer<T>(&mut self, _: &(), _: &(), _: T)
-    where
-        T: IntoIterator,
-        T::Item: Borrow<command::BufferCopy>,
-    {
-        unimplemented!()
-    }
-
-    fn copy_image<T>(
-        &mut self,
-        _: &(),
-        _: image::Layout,
-        _: &(),
-        _: image::Layout,
-        _: T,
-    ) where
-        T: IntoIterator,
-        T::Item: Borrow<command::ImageCopy>,
-    {
-        unimplemented!()
-    }
-
-    fn copy_buffer_to_image<T>(
-        &mut self,
-        _: &(),
-        _: &(),
-        _: image::Layout,
-        _: T,
-    ) where
-        T: IntoIterator,
-        T::Item: Borrow<command::BufferImageCopy>,
-    {
-        unimplemented!()
+    fn id(&self) -> hal::queue::QueueFamilyId {
+        hal::queue::QueueFamilyId(0)
     }
-
-    fn copy_image_to_buffer<T>(
-        &mut self,
-        _: &(),
-        _: image::Layout,
-        _: &(),
-        _: T,
-    ) where
-        T: IntoIterator,
-        T::Item: Borrow<command::BufferImageCopy>,
-    {
-        unimplemented!()
-    }
-
-    fn draw(&mut self,
-        _: Range<hal::VertexCount>,
-        _: Range<hal::InstanceCount>,
-    ) {
-        unimplemented!()
-    }
-
-    fn draw_indexed(
-        &mut self,
-        _: Range<hal::IndexCount>,
-        _: hal::VertexOffset,
-        _: Range<hal::InstanceCount>,
-    ) {
-        unimplemented!()
-    }
-
-    fn draw_indirect(
-        &mut self,
-        _: &(),
-        _: buffer::Offset,
-        _: hal::DrawCount,
-        _: u32,
-    ) {
-        unimplemented!()
-    }
-
-    fn draw_indexed_indirect(
-        &mut self,
-        _: &(),
-        _: buffer::Offset,
-        _: hal::DrawCount,
-        _: u32,
-    ) {
-        unimplemented!()
-    }
-
-    fn begin_query(
-        &mut self,
-        _: query::Query<Backend>,
-        _: query::QueryControl,
-    ) {
-        unimplemented!()
-    }
-
-    fn end_query(
-        &mut self,
-        _: query::Query<Backend>,
-    ) {
-        unimplemented!()
-    }
-
-    fn reset_query_pool(
-        &mut self,
-        _: &(),
-        _: Range<query::QueryId>,
-    ) {
-        unimplemented!()
-    }
-
-    fn write_timestamp(
-        &mut self,
-        _: pso::PipelineStage,
-        _: query::Query<Backend>,
-    ) {
-        unimplemented!()
-    }
-
-    fn push_graphics_constants(
-        &mut self,
-        _: &(),
-        _: pso::ShaderStageFlags,
-        _: u32,
-        _: &[u32],
-    ) {
-        unimplemented!()
-    }
-
-    fn push_compute_constants(
-        &mut self,
-        _: &(),
-        _: u32,
-        _: &[u32],
-    ) {
-        unimplemented!()
-    }
-
-    fn execute_commands<I>(
-        &mut self,
-        _: I,
-    ) where
-        I: IntoIterator,
-        I::Item: Borrow<RawCommandBuffer>
-    {
-        unimplemented!()
-    }
-
 }
 
-// Dummy descriptor pool.
+/// Descriptor pool, allocating sets which own their descriptors.
 #[derive(Debug)]
 pub struct DescriptorPool;
 impl pso::DescriptorPool<Backend> for DescriptorPool {
+    fn allocate_set(
+        &mut self, layout: &native::DescriptorSetLayout
+    ) -> Result<native::DescriptorSet, pso::AllocationError> {
+        Ok(native::DescriptorSet::new(layout))
+    }
+
     fn free_sets<I>(&mut self, _descriptor_sets: I)
     where
-        I: IntoIterator<Item = ()>
+        I: IntoIterator<Item = native::DescriptorSet>
     {
-        unimplemented!()
     }
 
     fn reset(&mut self) {
-        unimplemented!()
     }
 }
 
@@ -813,10 +242,28 @@ impl hal::Swapchain<Backend> for Swapchain {
     }
 }
 
+/// Instance exposing the host CPU as the only adapter.
 pub struct Instance;
+
+impl Instance {
+    /// Create a new instance.
+    pub fn create(_: &str, _: u32) -> Self {
+        Instance
+    }
+}
+
 impl hal::Instance for Instance {
     type Backend = Backend;
     fn enumerate_adapters(&self) -> Vec<hal::Adapter<Backend>> {
-        unimplemented!()
+        vec![hal::Adapter {
+            info: hal::AdapterInfo {
+                name: "CPU".to_owned(),
+                vendor: 0,
+                device: 0,
+                software_rendering: true,
+            },
+            physical_device: PhysicalDevice,
+            queue_families: vec![QueueFamily],
+        }]
     }
 }
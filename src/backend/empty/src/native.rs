@@ -0,0 +1,416 @@
+use hal::{format, image, pso};
+
+use std::cell::UnsafeCell;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{fmt, ptr};
+
+use interpreter::Program;
+use hal::pso::spirv;
+
+
+/// Host allocation backing a `Memory` object.
+///
+/// The contents are accessed through raw pointers, either by the queue while
+/// executing command buffers or by the user through a mapping. Synchronizing
+/// those accesses is the responsibility of the user, just like on a GPU.
+pub struct Block {
+    // `u64` storage keeps every allocation 8-byte aligned.
+    data: UnsafeCell<Box<[u64]>>,
+    size: u64,
+}
+
+unsafe impl Send for Block {}
+unsafe impl Sync for Block {}
+
+impl fmt::Debug for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Block")
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+impl Block {
+    pub fn new(size: u64) -> Self {
+        let words = (size as usize + 7) / 8;
+        Block {
+            data: UnsafeCell::new(vec![0u64; words].into_boxed_slice()),
+            size,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Raw pointer to the byte at `offset`.
+    pub fn ptr(&self, offset: u64) -> *mut u8 {
+        assert!(offset <= self.size, "offset {} is outside of the block of size {}", offset, self.size);
+        unsafe {
+            ((*self.data.get()).as_mut_ptr() as *mut u8).offset(offset as isize)
+        }
+    }
+
+    /// Copy the bytes at `offset` into `data`.
+    pub unsafe fn read(&self, offset: u64, data: &mut [u8]) {
+        assert!(offset + data.len() as u64 <= self.size,
+            "range {:?} is outside of the block of size {}", offset .. offset + data.len() as u64, self.size);
+        ptr::copy(self.ptr(offset), data.as_mut_ptr(), data.len());
+    }
+
+    /// Copy `data` to the bytes at `offset`.
+    pub unsafe fn write(&self, offset: u64, data: &[u8]) {
+        assert!(offset + data.len() as u64 <= self.size,
+            "range {:?} is outside of the block of size {}", offset .. offset + data.len() as u64, self.size);
+        ptr::copy(data.as_ptr(), self.ptr(offset), data.len());
+    }
+
+    /// Copy `size` bytes between two blocks, which may alias.
+    pub unsafe fn copy(src: &Block, src_offset: u64, dst: &Block, dst_offset: u64, size: u64) {
+        assert!(src_offset + size <= src.size && dst_offset + size <= dst.size);
+        ptr::copy(src.ptr(src_offset), dst.ptr(dst_offset), size as usize);
+    }
+}
+
+#[derive(Debug)]
+pub struct Memory {
+    pub(crate) block: Arc<Block>,
+}
+
+#[derive(Debug)]
+pub struct UnboundBuffer {
+    pub(crate) size: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Buffer {
+    pub(crate) block: Arc<Block>,
+    pub(crate) offset: u64,
+    pub(crate) size: u64,
+}
+
+impl Buffer {
+    /// Resolve an optional range into absolute offsets within the memory block.
+    pub(crate) fn range(&self, start: Option<u64>, end: Option<u64>) -> Range<u64> {
+        let start = start.unwrap_or(0);
+        let end = end.unwrap_or(self.size);
+        assert!(start <= end && end <= self.size,
+            "range {:?} is outside of the buffer of size {}", start .. end, self.size);
+        self.offset + start .. self.offset + end
+    }
+}
+
+#[derive(Debug)]
+pub struct UnboundImage {
+    pub(crate) kind: image::Kind,
+    pub(crate) levels: image::Level,
+    pub(crate) format: format::Format,
+}
+
+impl UnboundImage {
+    /// Total number of bytes occupied by all the subresources.
+    pub(crate) fn size(&self) -> u64 {
+        let layout = ImageLayout::new(self.kind, self.levels, self.format);
+        layout.level_offsets[self.levels as usize]
+    }
+}
+
+/// Linear memory layout of an image.
+///
+/// Mip levels are stored one after another, each level containing all
+/// the array layers, each layer containing all the depth slices.
+#[derive(Clone, Debug)]
+pub struct ImageLayout {
+    pub(crate) kind: image::Kind,
+    pub(crate) format: format::Format,
+    /// Offsets of each level, with an extra entry for the total size.
+    pub(crate) level_offsets: Vec<u64>,
+}
+
+impl ImageLayout {
+    pub fn new(kind: image::Kind, levels: image::Level, format: format::Format) -> Self {
+        let mut level_offsets = Vec::with_capacity(levels as usize + 1);
+        let mut offset = 0;
+        for level in 0 .. levels {
+            level_offsets.push(offset);
+            let extent = kind.level_extent(level);
+            let (_, row_count) = Self::block_counts(format, extent);
+            let row_pitch = Self::row_pitch(format, extent.width);
+            offset += row_pitch * row_count as u64 * extent.depth as u64 * kind.num_layers() as u64;
+        }
+        level_offsets.push(offset);
+        ImageLayout {
+            kind,
+            format,
+            level_offsets,
+        }
+    }
+
+    /// Number of texel blocks per row and number of block rows for an extent.
+    pub fn block_counts(format: format::Format, extent: image::Extent) -> (u32, u32) {
+        let (block_width, block_height) = format.surface_desc().dim;
+        // rounding up in `u64` can't overflow for any `u32` extent
+        let count = |size: u32, block: u8| {
+            ((size as u64 + block as u64 - 1) / block as u64) as u32
+        };
+        (count(extent.width, block_width), count(extent.height, block_height))
+    }
+
+    /// Bytes per texel block.
+    pub fn block_size(format: format::Format) -> u64 {
+        format.surface_desc().bits as u64 / 8
+    }
+
+    /// Byte size of a row of texel blocks of the given width.
+    pub fn row_pitch(format: format::Format, width: image::Size) -> u64 {
+        let (blocks, _) = Self::block_counts(format, image::Extent { width, height: 1, depth: 1 });
+        blocks as u64 * Self::block_size(format)
+    }
+
+    pub fn footprint(&self, sub: &image::Subresource) -> image::SubresourceFootprint {
+        let extent = self.kind.level_extent(sub.level);
+        let (_, row_count) = Self::block_counts(self.format, extent);
+        let row_pitch = Self::row_pitch(self.format, extent.width);
+        let depth_pitch = row_pitch * row_count as u64;
+        let array_pitch = depth_pitch * extent.depth as u64;
+        let start = self.level_offsets[sub.level as usize] + array_pitch * sub.layer as u64;
+        image::SubresourceFootprint {
+            slice: start .. start + array_pitch,
+            row_pitch,
+            array_pitch,
+            depth_pitch,
+        }
+    }
+
+    /// Byte offset of the texel block containing the given texel.
+    pub fn texel_offset(&self, level: image::Level, layer: image::Layer, x: u32, y: u32, z: u32) -> u64 {
+        let footprint = self.footprint(&image::Subresource {
+            aspects: format::Aspects::empty(),
+            level,
+            layer,
+        });
+        let (block_width, block_height) = self.format.surface_desc().dim;
+        footprint.slice.start +
+            z as u64 * footprint.depth_pitch +
+            (y / block_height as u32) as u64 * footprint.row_pitch +
+            (x / block_width as u32) as u64 * Self::block_size(self.format)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub(crate) block: Arc<Block>,
+    pub(crate) offset: u64,
+    pub(crate) layout: ImageLayout,
+}
+
+#[derive(Debug)]
+pub struct ShaderModule {
+    pub(crate) module: Arc<spirv::Module>,
+}
+
+#[derive(Debug)]
+pub struct ComputePipeline {
+    pub(crate) program: Arc<Program>,
+}
+
+/// Pipeline cache data, kept as an opaque blob since the programs are cheap
+/// to build from the shader modules.
+#[derive(Debug)]
+pub struct PipelineCache {
+    pub(crate) data: Mutex<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub struct DescriptorSetLayout {
+    /// Bindings sorted by their index.
+    pub(crate) bindings: Vec<pso::DescriptorSetLayoutBinding>,
+}
+
+/// Buffer range bound to a descriptor, in absolute offsets within the memory block.
+#[derive(Clone, Debug)]
+pub struct BufferDescriptor {
+    pub(crate) block: Arc<Block>,
+    pub(crate) range: Range<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DescriptorBinding {
+    pub(crate) binding: pso::DescriptorBinding,
+    pub(crate) ty: pso::DescriptorType,
+    /// Buffers bound to each array element. Other kinds of descriptors can't
+    /// be accessed by the interpreter and leave the element empty.
+    pub(crate) descriptors: Vec<Option<BufferDescriptor>>,
+}
+
+/// Iterate over the descriptors starting at the given binding and array
+/// element, spilling over into the following bindings.
+pub fn descriptor_slots<'a>(
+    bindings: &'a mut [DescriptorBinding], binding: pso::DescriptorBinding, array_offset: usize,
+) -> impl Iterator<Item = &'a mut Option<BufferDescriptor>> {
+    bindings
+        .iter_mut()
+        .skip_while(move |b| b.binding != binding)
+        .flat_map(|b| b.descriptors.iter_mut())
+        .skip(array_offset)
+}
+
+#[derive(Debug)]
+pub struct DescriptorSet {
+    pub(crate) bindings: Mutex<Vec<DescriptorBinding>>,
+}
+
+impl DescriptorSet {
+    pub fn new(layout: &DescriptorSetLayout) -> Self {
+        let bindings = layout.bindings
+            .iter()
+            .map(|binding| DescriptorBinding {
+                binding: binding.binding,
+                ty: binding.ty,
+                descriptors: vec![None; binding.count],
+            })
+            .collect();
+        DescriptorSet {
+            bindings: Mutex::new(bindings),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Fence(pub(crate) AtomicBool);
+
+impl Fence {
+    pub fn new(signaled: bool) -> Self {
+        Fence(AtomicBool::new(signaled))
+    }
+
+    pub fn is_signaled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    pub fn set(&self, signaled: bool) {
+        self.0.store(signaled, Ordering::Release);
+    }
+}
+
+/// Event, shared with the commands setting it.
+#[derive(Clone, Debug, Default)]
+pub struct Event(pub(crate) Arc<AtomicBool>);
+
+impl Event {
+    pub fn is_signaled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    pub fn set(&self, signaled: bool) {
+        self.0.store(signaled, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::format::Format;
+    use hal::image::{Extent, Kind, Subresource};
+
+    fn subresource(level: image::Level, layer: image::Layer) -> Subresource {
+        Subresource {
+            aspects: format::Aspects::COLOR,
+            level,
+            layer,
+        }
+    }
+
+    #[test]
+    fn layout_levels_and_layers() {
+        let layout = ImageLayout::new(Kind::D2(4, 4, 2, 1), 3, Format::Rgba8Unorm);
+        // each level holds both layers: 2 * 64, 2 * 16, 2 * 4 bytes
+        assert_eq!(layout.level_offsets, vec![0, 128, 160, 168]);
+
+        let footprint = layout.footprint(&subresource(1, 1));
+        assert_eq!(footprint.slice, 144 .. 160);
+        assert_eq!(footprint.row_pitch, 8);
+        assert_eq!(footprint.depth_pitch, 16);
+        assert_eq!(footprint.array_pitch, 16);
+
+        assert_eq!(layout.texel_offset(0, 0, 0, 0, 0), 0);
+        assert_eq!(layout.texel_offset(0, 1, 3, 2, 0), 64 + 2 * 16 + 3 * 4);
+        assert_eq!(layout.texel_offset(1, 1, 1, 1, 0), 144 + 8 + 4);
+        assert_eq!(layout.texel_offset(2, 1, 0, 0, 0), 164);
+    }
+
+    #[test]
+    fn layout_depth_slices() {
+        let layout = ImageLayout::new(Kind::D3(2, 2, 3), 2, Format::Rgba8Unorm);
+        assert_eq!(layout.level_offsets, vec![0, 48, 52]);
+
+        let footprint = layout.footprint(&subresource(0, 0));
+        assert_eq!(footprint.slice, 0 .. 48);
+        assert_eq!(footprint.depth_pitch, 16);
+        assert_eq!(layout.texel_offset(0, 0, 1, 0, 2), 2 * 16 + 4);
+    }
+
+    #[test]
+    fn layout_compressed() {
+        // 8-byte blocks of 4x4 texels, partial blocks are rounded up
+        let layout = ImageLayout::new(Kind::D2(10, 6, 1, 1), 2, Format::Bc1RgbaUnorm);
+        assert_eq!(layout.level_offsets, vec![0, 48, 64]);
+
+        let footprint = layout.footprint(&subresource(0, 0));
+        assert_eq!(footprint.row_pitch, 24);
+        assert_eq!(footprint.depth_pitch, 48);
+        assert_eq!(layout.texel_offset(0, 0, 3, 3, 0), 0);
+        assert_eq!(layout.texel_offset(0, 0, 5, 5, 0), 24 + 8);
+        assert_eq!(layout.texel_offset(1, 0, 4, 2, 0), 48 + 8);
+    }
+
+    #[test]
+    fn block_counts_large_extent() {
+        let max = Extent { width: !0, height: !0, depth: 1 };
+        assert_eq!(ImageLayout::block_counts(Format::Bc1RgbaUnorm, max), (1 << 30, 1 << 30));
+        assert_eq!(ImageLayout::block_counts(Format::Rgba8Unorm, max), (!0, !0));
+    }
+
+    #[test]
+    fn buffer_range() {
+        let buffer = Buffer {
+            block: Arc::new(Block::new(16)),
+            offset: 4,
+            size: 8,
+        };
+        assert_eq!(buffer.range(None, None), 4 .. 12);
+        assert_eq!(buffer.range(Some(2), Some(6)), 6 .. 10);
+        assert_eq!(buffer.range(Some(8), None), 12 .. 12);
+    }
+
+    #[test]
+    #[should_panic]
+    fn buffer_range_past_end() {
+        let buffer = Buffer {
+            block: Arc::new(Block::new(16)),
+            offset: 4,
+            size: 8,
+        };
+        buffer.range(Some(4), Some(9));
+    }
+
+    #[test]
+    #[should_panic]
+    fn buffer_range_reversed() {
+        let buffer = Buffer {
+            block: Arc::new(Block::new(16)),
+            offset: 4,
+            size: 8,
+        };
+        buffer.range(Some(6), Some(2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn block_write_past_end() {
+        let block = Block::new(4);
+        unsafe { block.write(2, &[0; 3]) };
+    }
+}
@@ -0,0 +1,553 @@
+use hal::{command as com, error, image, queue};
+
+use std::borrow::Borrow;
+use std::sync::Arc;
+
+use command::{Command, RawCommandBuffer};
+use interpreter::{self, Program};
+use native::{self as n, Block, ImageLayout};
+use {conv, Backend, Swapchain};
+
+
+/// Compute state of a command buffer during its execution.
+#[derive(Default)]
+struct ComputeState {
+    program: Option<Arc<Program>>,
+    descriptor_sets: Vec<Vec<n::DescriptorBinding>>,
+    push_constants: Vec<u8>,
+}
+
+impl ComputeState {
+    fn dispatch(&self, count: ::hal::WorkGroupCount) {
+        let program = self.program
+            .as_ref()
+            .expect("No compute pipeline is bound");
+        interpreter::dispatch(program, &self.descriptor_sets, &self.push_constants, count);
+    }
+}
+
+
+/// Command queue executing the submitted command buffers on the calling thread.
+#[derive(Debug)]
+pub struct RawCommandQueue;
+
+impl queue::RawCommandQueue<Backend> for RawCommandQueue {
+    unsafe fn submit_raw<IC>(&mut self, submission: queue::RawSubmission<Backend, IC>, fence: Option<&n::Fence>)
+    where
+        IC: IntoIterator,
+        IC::Item: Borrow<RawCommandBuffer>,
+    {
+        for cmd_buffer in submission.cmd_buffers {
+            let mut state = ComputeState::default();
+            for command in &cmd_buffer.borrow().commands {
+                execute(command, &mut state);
+            }
+        }
+        if let Some(fence) = fence {
+            fence.set(true);
+        }
+    }
+
+    unsafe fn bind_sparse(&mut self, _binding: queue::RawSparseBinding<Backend>, _fence: Option<&n::Fence>) {
+        unimplemented!()
+    }
+
+    fn present<IS, S, IW>(&mut self, _: IS, _: IW) -> Result<(), ()>
+    where
+        IS: IntoIterator<Item = (S, ::hal::SwapImageIndex)>,
+        S: Borrow<Swapchain>,
+        IW: IntoIterator,
+        IW::Item: Borrow<()>,
+    {
+        unimplemented!()
+    }
+
+    fn wait_idle(&self) -> Result<(), error::HostExecutionError> {
+        // Submissions complete before `submit_raw` returns.
+        Ok(())
+    }
+}
+
+unsafe fn execute(command: &Command, state: &mut ComputeState) {
+    match *command {
+        Command::CopyBuffer { ref src, ref dst, ref regions } => {
+            for region in regions {
+                let src_range = src.range(Some(region.src), Some(region.src + region.size));
+                let dst_range = dst.range(Some(region.dst), Some(region.dst + region.size));
+                Block::copy(&src.block, src_range.start, &dst.block, dst_range.start, region.size);
+            }
+        }
+        Command::CopyImage { ref src, ref dst, ref regions } => {
+            for region in regions {
+                copy_image(src, dst, region);
+            }
+        }
+        Command::CopyBufferToImage { ref src, ref dst, ref regions } => {
+            for region in regions {
+                for_each_image_row(dst, region, |buffer_offset, image_offset, size| {
+                    let start = src.range(Some(buffer_offset), Some(buffer_offset + size)).start;
+                    Block::copy(&src.block, start, &dst.block, image_offset, size);
+                });
+            }
+        }
+        Command::CopyImageToBuffer { ref src, ref dst, ref regions } => {
+            for region in regions {
+                for_each_image_row(src, region, |buffer_offset, image_offset, size| {
+                    let start = dst.range(Some(buffer_offset), Some(buffer_offset + size)).start;
+                    Block::copy(&src.block, image_offset, &dst.block, start, size);
+                });
+            }
+        }
+        Command::BlitImage { ref src, ref dst, filter, ref regions } => {
+            for region in regions {
+                blit_image(src, dst, filter, region);
+            }
+        }
+        Command::FillBuffer { ref dst, ref range, data } => {
+            let range = dst.range(Some(range.start), Some(range.end));
+            let pattern = [data as u8, (data >> 8) as u8, (data >> 16) as u8, (data >> 24) as u8];
+            let data = pattern
+                .iter()
+                .cloned()
+                .cycle()
+                .take((range.end - range.start) as usize)
+                .collect::<Vec<_>>();
+            dst.block.write(range.start, &data);
+        }
+        Command::UpdateBuffer { ref dst, offset, ref data } => {
+            let range = dst.range(Some(offset), Some(offset + data.len() as u64));
+            dst.block.write(range.start, data);
+        }
+        Command::ClearImage { ref image, color, depth_stencil, ref ranges } => {
+            let format = image.layout.format;
+            let texel_size = ImageLayout::block_size(format) as usize;
+            let color_texel = if format.is_color() {
+                let mut texel = vec![0; texel_size];
+                conv::encode_color(format, conv::clear_color_value(format, color), &mut texel);
+                Some(texel)
+            } else {
+                None
+            };
+            for range in ranges {
+                for level in range.levels.clone() {
+                    let extent = image.layout.kind.level_extent(level);
+                    for layer in range.layers.clone() {
+                        for z in 0 .. extent.depth {
+                            for y in 0 .. extent.height {
+                                for x in 0 .. extent.width {
+                                    let offset = image.offset + image.layout.texel_offset(level, layer, x, y, z);
+                                    match color_texel {
+                                        Some(ref value) => image.block.write(offset, value),
+                                        None => {
+                                            // only the cleared aspects are overwritten
+                                            let mut texel = vec![0; texel_size];
+                                            image.block.read(offset, &mut texel);
+                                            conv::encode_depth_stencil(format, depth_stencil, range.aspects, &mut texel);
+                                            image.block.write(offset, &texel);
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Command::BindComputePipeline(ref program) => {
+            state.program = Some(program.clone());
+        }
+        Command::BindComputeDescriptorSets { first_set, ref sets } => {
+            if state.descriptor_sets.len() < first_set + sets.len() {
+                state.descriptor_sets.resize(first_set + sets.len(), Vec::new());
+            }
+            for (slot, set) in state.descriptor_sets[first_set ..].iter_mut().zip(sets) {
+                *slot = set.clone();
+            }
+        }
+        Command::PushComputeConstants { offset, ref constants } => {
+            // the offset is counted in 32-bit words
+            let start = offset as usize * 4;
+            let end = start + constants.len() * 4;
+            if state.push_constants.len() < end {
+                state.push_constants.resize(end, 0);
+            }
+            for (chunk, constant) in state.push_constants[start .. end].chunks_mut(4).zip(constants) {
+                for (i, byte) in chunk.iter_mut().enumerate() {
+                    *byte = (constant >> (8 * i)) as u8;
+                }
+            }
+        }
+        Command::SetEvent { ref event, signaled } => {
+            event.set(signaled);
+        }
+        Command::Dispatch(count) => {
+            state.dispatch(count);
+        }
+        Command::DispatchIndirect { ref buffer, offset } => {
+            let range = buffer.range(Some(offset), Some(offset + 12));
+            let mut data = [0; 12];
+            buffer.block.read(range.start, &mut data);
+            let mut count = [0; 3];
+            for (value, chunk) in count.iter_mut().zip(data.chunks(4)) {
+                *value = chunk.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u32);
+            }
+            state.dispatch(count);
+        }
+    }
+}
+
+/// Call `fun(buffer_offset, image_offset, size)` for every row of texel blocks
+/// covered by a buffer-image copy region. The image offset is absolute within
+/// the image memory block.
+fn for_each_image_row<F>(image: &n::Image, region: &com::BufferImageCopy, mut fun: F)
+where
+    F: FnMut(u64, u64, u64),
+{
+    let format = image.layout.format;
+    let (block_width, block_height) = format.surface_desc().dim;
+    let extent = region.image_extent;
+    let buffer_width = if region.buffer_width == 0 { extent.width } else { region.buffer_width };
+    let buffer_height = if region.buffer_height == 0 { extent.height } else { region.buffer_height };
+    let buffer_row_pitch = ImageLayout::row_pitch(format, buffer_width);
+    let (_, buffer_rows) = ImageLayout::block_counts(format, image::Extent {
+        width: buffer_width,
+        height: buffer_height,
+        depth: 1,
+    });
+    let buffer_slice_pitch = buffer_row_pitch * buffer_rows as u64;
+    let (_, rows) = ImageLayout::block_counts(format, extent);
+    let row_size = ImageLayout::row_pitch(format, extent.width);
+    let layers = &region.image_layers.layers;
+
+    for (i, layer) in (layers.start .. layers.end).enumerate() {
+        for z in 0 .. extent.depth {
+            for row in 0 .. rows {
+                let buffer_offset = region.buffer_offset +
+                    (i as u64 * extent.depth as u64 + z as u64) * buffer_slice_pitch +
+                    row as u64 * buffer_row_pitch;
+                let image_offset = image.offset + image.layout.texel_offset(
+                    region.image_layers.level,
+                    layer,
+                    region.image_offset.x as u32,
+                    region.image_offset.y as u32 + row * block_height as u32,
+                    region.image_offset.z as u32 + z,
+                );
+                debug_assert_eq!(region.image_offset.x as u32 % block_width as u32, 0);
+                fun(buffer_offset, image_offset, row_size);
+            }
+        }
+    }
+}
+
+unsafe fn copy_image(src: &n::Image, dst: &n::Image, region: &com::ImageCopy) {
+    let format = src.layout.format;
+    let (_, block_height) = format.surface_desc().dim;
+    let (_, rows) = ImageLayout::block_counts(format, region.extent);
+    let row_size = ImageLayout::row_pitch(format, region.extent.width);
+    let src_layers = region.src_subresource.layers.clone();
+    let dst_layers = region.dst_subresource.layers.clone();
+
+    for (src_layer, dst_layer) in src_layers.zip(dst_layers) {
+        for z in 0 .. region.extent.depth {
+            for row in 0 .. rows {
+                let y = row * block_height as u32;
+                let src_offset = src.offset + src.layout.texel_offset(
+                    region.src_subresource.level,
+                    src_layer,
+                    region.src_offset.x as u32,
+                    region.src_offset.y as u32 + y,
+                    region.src_offset.z as u32 + z,
+                );
+                let dst_offset = dst.offset + dst.layout.texel_offset(
+                    region.dst_subresource.level,
+                    dst_layer,
+                    region.dst_offset.x as u32,
+                    region.dst_offset.y as u32 + y,
+                    region.dst_offset.z as u32 + z,
+                );
+                Block::copy(&src.block, src_offset, &dst.block, dst_offset, row_size);
+            }
+        }
+    }
+}
+
+unsafe fn blit_image(src: &n::Image, dst: &n::Image, filter: image::Filter, region: &com::ImageBlit) {
+    let src_format = src.layout.format;
+    let dst_format = dst.layout.format;
+    let src_texel_size = ImageLayout::block_size(src_format);
+    let dst_texel_size = ImageLayout::block_size(dst_format);
+    let raw_copy = filter == image::Filter::Nearest && src_format == dst_format;
+    assert!(raw_copy || (conv::is_interpretable(src_format) && conv::is_interpretable(dst_format)),
+        "Unable to blit from {:?} to {:?}", src_format, dst_format);
+    assert!(raw_copy || (src_format.is_color() && dst_format.is_color()));
+
+    let src_start = &region.src_bounds.start;
+    let src_end = &region.src_bounds.end;
+    let dst_start = &region.dst_bounds.start;
+    let dst_end = &region.dst_bounds.end;
+    // Maps a destination texel center to the source coordinate along one axis.
+    let scale = |x: i32, d0: i32, d1: i32, s0: i32, s1: i32| {
+        s0 as f64 + (x as f64 + 0.5 - d0 as f64) * (s1 - s0) as f64 / (d1 - d0) as f64
+    };
+    let clamp = |v: i32, a: i32, b: i32| v.max(a.min(b)).min(a.max(b) - 1);
+
+    let src_layers = region.src_subresource.layers.clone();
+    let dst_layers = region.dst_subresource.layers.clone();
+    let src_level = region.src_subresource.level;
+    let dst_level = region.dst_subresource.level;
+
+    let read = |layer, x, y, z| {
+        let offset = src.offset + src.layout.texel_offset(src_level, layer, x as u32, y as u32, z as u32);
+        let mut texel = vec![0; src_texel_size as usize];
+        src.block.read(offset, &mut texel);
+        texel
+    };
+
+    for (src_layer, dst_layer) in src_layers.zip(dst_layers) {
+        for z in dst_start.z.min(dst_end.z) .. dst_start.z.max(dst_end.z) {
+            for y in dst_start.y.min(dst_end.y) .. dst_start.y.max(dst_end.y) {
+                for x in dst_start.x.min(dst_end.x) .. dst_start.x.max(dst_end.x) {
+                    // Source coordinates of the destination texel center.
+                    let u = scale(x, dst_start.x, dst_end.x, src_start.x, src_end.x);
+                    let v = scale(y, dst_start.y, dst_end.y, src_start.y, src_end.y);
+                    let w = scale(z, dst_start.z, dst_end.z, src_start.z, src_end.z);
+
+                    let offset = dst.offset + dst.layout.texel_offset(dst_level, dst_layer, x as u32, y as u32, z as u32);
+                    let mut texel = vec![0; dst_texel_size as usize];
+
+                    match filter {
+                        image::Filter::Nearest => {
+                            let sx = clamp(u.floor() as i32, src_start.x, src_end.x);
+                            let sy = clamp(v.floor() as i32, src_start.y, src_end.y);
+                            let sz = clamp(w.floor() as i32, src_start.z, src_end.z);
+                            if raw_copy {
+                                texel = read(src_layer, sx, sy, sz);
+                            } else {
+                                let value = conv::decode_color(src_format, &read(src_layer, sx, sy, sz));
+                                conv::encode_color(dst_format, value, &mut texel);
+                            }
+                        }
+                        image::Filter::Linear => {
+                            let (u, v, w) = (u - 0.5, v - 0.5, w - 0.5);
+                            let (x0, y0, z0) = (u.floor() as i32, v.floor() as i32, w.floor() as i32);
+                            let (tx, ty, tz) = (u - x0 as f64, v - y0 as f64, w - z0 as f64);
+                            let mut value = [0.0; 4];
+                            for &(dz, fz) in &[(0, 1.0 - tz), (1, tz)] {
+                                for &(dy, fy) in &[(0, 1.0 - ty), (1, ty)] {
+                                    for &(dx, fx) in &[(0, 1.0 - tx), (1, tx)] {
+                                        let factor = fx * fy * fz;
+                                        if factor == 0.0 {
+                                            continue
+                                        }
+                                        let sample = conv::decode_color(src_format, &read(
+                                            src_layer,
+                                            clamp(x0 + dx, src_start.x, src_end.x),
+                                            clamp(y0 + dy, src_start.y, src_end.y),
+                                            clamp(z0 + dz, src_start.z, src_end.z),
+                                        ));
+                                        for (v, s) in value.iter_mut().zip(sample.iter()) {
+                                            *v += factor * s;
+                                        }
+                                    }
+                                }
+                            }
+                            conv::encode_color(dst_format, value, &mut texel);
+                        }
+                    }
+                    dst.block.write(offset, &texel);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::format::Format;
+    use hal::image::{Extent, Kind, Offset, SubresourceLayers};
+    use interpreter::tests::{load_global_id, program, read_storage, storage_binding, store_data};
+    use interpreter::tests::{C0, PUSH, PUSH_UINT, UINT};
+    use hal::pso::spirv::op;
+
+    fn buffer(data: &[u8]) -> n::Buffer {
+        let block = Block::new(data.len() as u64);
+        unsafe { block.write(0, data) };
+        n::Buffer {
+            block: Arc::new(block),
+            offset: 0,
+            size: data.len() as u64,
+        }
+    }
+
+    fn image(kind: Kind, format: Format, data: Option<&[u8]>) -> n::Image {
+        let layout = ImageLayout::new(kind, 1, format);
+        let block = Block::new(layout.level_offsets[1]);
+        if let Some(data) = data {
+            unsafe { block.write(0, data) };
+        }
+        n::Image {
+            block: Arc::new(block),
+            offset: 0,
+            layout,
+        }
+    }
+
+    fn contents(block: &Block) -> Vec<u8> {
+        let mut data = vec![0; block.size() as usize];
+        unsafe { block.read(0, &mut data) };
+        data
+    }
+
+    fn run(command: Command) {
+        unsafe { execute(&command, &mut ComputeState::default()) };
+    }
+
+    /// Two 2x2 rows in each of the two layers, read from a buffer with
+    /// rows of 5 texels and layers of 3 rows.
+    fn padded_region() -> com::BufferImageCopy {
+        com::BufferImageCopy {
+            buffer_offset: 2,
+            buffer_width: 5,
+            buffer_height: 3,
+            image_layers: SubresourceLayers {
+                aspects: ::hal::format::Aspects::COLOR,
+                level: 0,
+                layers: 0 .. 2,
+            },
+            image_offset: Offset { x: 1, y: 2, z: 0 },
+            image_extent: Extent { width: 2, height: 2, depth: 1 },
+        }
+    }
+
+    #[test]
+    fn copy_buffer_to_image_padded() {
+        let data = (0 .. 40).collect::<Vec<u8>>();
+        let src = buffer(&data);
+        let dst = image(Kind::D2(4, 4, 2, 1), Format::R8Unorm, None);
+        run(Command::CopyBufferToImage {
+            src,
+            dst: dst.clone(),
+            regions: vec![padded_region()],
+        });
+
+        let mut expected = vec![0; 32];
+        for &(texel, value) in &[(9, 2), (10, 3), (13, 7), (14, 8), (25, 17), (26, 18), (29, 22), (30, 23)] {
+            expected[texel] = value;
+        }
+        assert_eq!(contents(&dst.block), expected);
+    }
+
+    #[test]
+    fn copy_image_to_buffer_padded() {
+        let data = (0 .. 32).collect::<Vec<u8>>();
+        let src = image(Kind::D2(4, 4, 2, 1), Format::R8Unorm, Some(&data));
+        let dst = buffer(&[0; 40]);
+        run(Command::CopyImageToBuffer {
+            src,
+            dst: dst.clone(),
+            regions: vec![padded_region()],
+        });
+
+        let mut expected = vec![0; 40];
+        for &(offset, value) in &[(2, 9), (3, 10), (7, 13), (8, 14), (17, 25), (18, 26), (22, 29), (23, 30)] {
+            expected[offset] = value;
+        }
+        assert_eq!(contents(&dst.block), expected);
+    }
+
+    #[test]
+    fn copy_buffer_to_compressed_image() {
+        // rows of 3 blocks in the buffer, 2 blocks in the image
+        let data = (0 .. 48).collect::<Vec<u8>>();
+        let src = buffer(&data);
+        let dst = image(Kind::D2(8, 8, 1, 1), Format::Bc1RgbaUnorm, None);
+        run(Command::CopyBufferToImage {
+            src,
+            dst: dst.clone(),
+            regions: vec![com::BufferImageCopy {
+                buffer_offset: 0,
+                buffer_width: 12,
+                buffer_height: 0,
+                image_layers: SubresourceLayers {
+                    aspects: ::hal::format::Aspects::COLOR,
+                    level: 0,
+                    layers: 0 .. 1,
+                },
+                image_offset: Offset { x: 4, y: 0, z: 0 },
+                image_extent: Extent { width: 4, height: 8, depth: 1 },
+            }],
+        });
+
+        let mut expected = vec![0; 32];
+        expected[8 .. 16].copy_from_slice(&data[0 .. 8]);
+        expected[24 .. 32].copy_from_slice(&data[24 .. 32]);
+        assert_eq!(contents(&dst.block), expected);
+    }
+
+    #[test]
+    fn clear_packed_image() {
+        let image = image(Kind::D2(2, 1, 1, 1), Format::R5g6b5Unorm, None);
+        run(Command::ClearImage {
+            image: image.clone(),
+            color: com::ClearColorRaw { float32: [1.0, 0.0, 1.0, 1.0] },
+            depth_stencil: com::ClearDepthStencilRaw { depth: 0.0, stencil: 0 },
+            ranges: vec![::hal::image::SubresourceRange {
+                aspects: ::hal::format::Aspects::COLOR,
+                levels: 0 .. 1,
+                layers: 0 .. 1,
+            }],
+        });
+        assert_eq!(contents(&image.block), vec![0x1F, 0xF8, 0x1F, 0xF8]);
+    }
+
+    #[test]
+    fn push_constants_offset() {
+        let mut state = ComputeState::default();
+        for &(offset, ref constants) in &[(0, vec![1, 2]), (1, vec![3, 4])] {
+            let command = Command::PushComputeConstants {
+                offset,
+                constants: constants.clone(),
+            };
+            unsafe { execute(&command, &mut state) };
+        }
+        assert_eq!(state.push_constants, vec![1, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0]);
+    }
+
+    #[test]
+    fn dispatch_indirect() {
+        // data[id] = push constant + id
+        let mut body = load_global_id(100);
+        body.extend(vec![
+            (op::ACCESS_CHAIN, vec![PUSH_UINT, 101, PUSH, C0]),
+            (op::LOAD, vec![UINT, 102, 101]),
+            (op::I_ADD, vec![UINT, 103, 102, 100]),
+        ]);
+        body.extend(store_data(100, 103));
+        body.push((op::RETURN, vec![]));
+        let program = program(1, &[], &body);
+
+        let (block, binding) = storage_binding(4);
+        let indirect = buffer(&[0xFF, 0xFF, 0xFF, 0xFF, 3, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        let mut state = ComputeState::default();
+        let commands = vec![
+            Command::BindComputePipeline(Arc::new(program)),
+            Command::BindComputeDescriptorSets {
+                first_set: 0,
+                sets: vec![vec![binding]],
+            },
+            Command::PushComputeConstants {
+                offset: 0,
+                constants: vec![5, 0],
+            },
+            Command::DispatchIndirect {
+                buffer: indirect,
+                offset: 4,
+            },
+        ];
+        for command in &commands {
+            unsafe { execute(command, &mut state) };
+        }
+        assert_eq!(read_storage(&block).1, vec![5, 6, 7, 0]);
+    }
+}
@@ -3,14 +3,14 @@
 use gl;
 
 use hal::{self, buffer, command, image, memory, pass, pso, query, ColorSlot};
-use hal::format::ChannelType;
+use hal::format::{ChannelType, Format};
 use hal::range::RangeArg;
 
 use {native as n, Backend};
 use pool::{self, BufferMemory};
 
 use std::borrow::Borrow;
-use std::{mem, slice};
+use std::{fmt, mem, slice};
 use std::ops::Range;
 use std::sync::{Arc, Mutex};
 
@@ -52,6 +52,27 @@ impl BufferSlice {
     }
 }
 
+/// The commands recorded by a secondary command buffer.
+///
+/// They are resolved from the memory of the owning pool on submission,
+/// so the secondary command buffer must not be reset or re-recorded
+/// while a primary command buffer executing it is pending.
+#[derive(Clone)]
+pub struct SecondaryCommands {
+    pub(crate) memory: Arc<Mutex<BufferMemory>>,
+    pub(crate) id: u64,
+    pub(crate) buf: BufferSlice,
+}
+
+impl fmt::Debug for SecondaryCommands {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SecondaryCommands")
+            .field("id", &self.id)
+            .field("buf", &self.buf)
+            .finish()
+    }
+}
+
 ///
 #[derive(Clone, Debug)]
 pub enum Command {
@@ -70,6 +91,21 @@ pub enum Command {
         base_vertex: hal::VertexOffset,
         instances: Range<hal::InstanceCount>,
     },
+    DrawIndirect {
+        primitive: gl::types::GLenum,
+        buffer: gl::types::GLuint,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
+    },
+    DrawIndexedIndirect {
+        primitive: gl::types::GLenum,
+        index_type: gl::types::GLenum,
+        buffer: gl::types::GLuint,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
+    },
     BindIndexBuffer(gl::types::GLuint),
     //BindVertexBuffers(BufferSlice),
     SetViewports {
@@ -78,6 +114,8 @@ pub enum Command {
         depth_range_ptr: BufferSlice,
     },
     SetScissors(u32, BufferSlice),
+    /// Enable or disable the scissor test.
+    SetScissorTest(bool),
     SetBlendColor(pso::ColorValue),
 
     /// Clear floating-point color drawbuffer of bound framebuffer.
@@ -93,18 +131,27 @@ pub enum Command {
     /// The buffer slice contains a list of `GLenum`.
     DrawBuffers(BufferSlice),
 
-    BindFrameBuffer(FrameBufferTarget, n::FrameBuffer),
+    BindFrameBuffer(FrameBufferTarget, n::RawFrameBuffer),
+    /// Discard the contents of attachments of the bound draw framebuffer.
+    /// The buffer slice contains a list of attachment points (`GLenum`).
+    InvalidateFramebuffer(BufferSlice),
     BindTargetView(FrameBufferTarget, AttachmentPoint, n::ImageView),
     SetDrawColorBuffers(usize),
     SetPatchSize(gl::types::GLint),
     BindProgram(gl::types::GLuint),
+    /// Update the uniforms emulating push constants for the bound program.
+    /// The buffer slices contain a list of `PushConstantUniform` and the push constant data.
+    SetPushConstants {
+        uniforms: BufferSlice,
+        data: BufferSlice,
+    },
     BindBlendSlot(ColorSlot, pso::ColorBlendDesc),
     BindAttribute(n::AttributeDesc, gl::types::GLuint, gl::types::GLsizei, n::VertexAttribFunction),
     //UnbindAttribute(n::AttributeDesc),
     CopyBufferToBuffer(n::RawBuffer, n::RawBuffer, command::BufferCopy),
-    CopyBufferToTexture(n::RawBuffer, n::Texture, command::BufferImageCopy),
+    CopyBufferToTexture(n::RawBuffer, n::Texture, Format, command::BufferImageCopy),
     CopyBufferToSurface(n::RawBuffer, n::Surface, command::BufferImageCopy),
-    CopyTextureToBuffer(n::Texture, n::RawBuffer, command::BufferImageCopy),
+    CopyTextureToBuffer(n::Texture, Format, n::RawBuffer, command::BufferImageCopy),
     CopySurfaceToBuffer(n::Surface, n::RawBuffer, command::BufferImageCopy),
     CopyImageToTexture(n::ImageKind, n::Texture, command::ImageCopy),
     CopyImageToSurface(n::ImageKind, n::Surface, command::ImageCopy),
@@ -112,6 +159,22 @@ pub enum Command {
     BindBufferRange(gl::types::GLenum, gl::types::GLuint, n::RawBuffer, gl::types::GLintptr, gl::types::GLsizeiptr),
     BindTexture(gl::types::GLenum, n::Texture),
     BindSampler(gl::types::GLuint, n::Texture),
+
+    BeginQuery(gl::types::GLenum, gl::types::GLuint),
+    EndQuery(gl::types::GLenum),
+    WriteTimestamp(gl::types::GLuint),
+    SetEvent(n::Event),
+    ResetEvent(n::Event),
+    WaitEvent(n::Event),
+    /// Write the result of a single query into a buffer.
+    CopyQueryResult {
+        query: gl::types::GLuint,
+        buffer: n::RawBuffer,
+        offset: buffer::Offset,
+        flags: query::ResultFlags,
+    },
+    /// Execute the commands of a secondary command buffer.
+    ExecuteCommands(SecondaryCommands),
 }
 
 pub type FrameBufferTarget = gl::types::GLenum;
@@ -128,7 +191,12 @@ struct AttachmentClear {
 #[derive(Clone)]
 pub struct RenderPassCache {
     render_pass: n::RenderPass,
-    framebuffer: n::FrameBuffer,
+    // Unknown for secondary command buffers continuing a render pass
+    // without the framebuffer being specified.
+    framebuffer: Option<n::FrameBuffer>,
+    // Area the attachment clears are restricted to, unknown for
+    // secondary command buffers.
+    render_area: Option<pso::Rect>,
     attachment_clears: Vec<AttachmentClear>,
 }
 
@@ -144,7 +212,7 @@ struct Cache {
     // Blend color.
     blend_color: Option<pso::ColorValue>,
     ///
-    framebuffer: Option<(FrameBufferTarget, n::FrameBuffer)>,
+    framebuffer: Option<(FrameBufferTarget, n::RawFrameBuffer)>,
     ///
     // Indicates that invalid commands have been recorded.
     error_state: bool,
@@ -160,6 +228,12 @@ struct Cache {
     vertex_buffer_descs: Vec<Option<pso::VertexBufferDesc>>,
     // Active attributes.
     attributes: Vec<n::AttributeDesc>,
+    // Uniforms emulating the push constants of the active program.
+    push_constant_uniforms: Vec<n::PushConstantUniform>,
+    // Push constant data, in 32-bit words.
+    push_constants: Vec<u32>,
+    // Last scissors set, with the index of the first one.
+    scissors: Option<(u32, BufferSlice)>,
 }
 
 impl Cache {
@@ -177,6 +251,9 @@ impl Cache {
             vertex_buffers: Vec::new(),
             vertex_buffer_descs: Vec::new(),
             attributes: Vec::new(),
+            push_constant_uniforms: Vec::new(),
+            push_constants: Vec::new(),
+            scissors: None,
         }
     }
 }
@@ -209,7 +286,7 @@ pub struct RawCommandBuffer {
     pub(crate) id: u64,
     individual_reset: bool,
 
-    fbo: n::FrameBuffer,
+    fbo: n::RawFrameBuffer,
     /// The framebuffer to use for rendering to the main targets (0 by default).
     ///
     /// Use this to set the framebuffer that will be used for the screen display targets created
@@ -219,7 +296,7 @@ pub struct RawCommandBuffer {
     ///
     /// This framebuffer must exist and be configured correctly (with renderbuffer attachments,
     /// etc.) so that rendering to it can occur immediately.
-    pub display_fb: n::FrameBuffer,
+    pub display_fb: n::RawFrameBuffer,
     cache: Cache,
 
     pass_cache: Option<RenderPassCache>,
@@ -231,7 +308,7 @@ pub struct RawCommandBuffer {
 
 impl RawCommandBuffer {
     pub(crate) fn new(
-        fbo: n::FrameBuffer,
+        fbo: n::RawFrameBuffer,
         limits: Limits,
         memory: Arc<Mutex<BufferMemory>>,
     ) -> Self {
@@ -258,7 +335,7 @@ impl RawCommandBuffer {
             id,
             individual_reset,
             fbo,
-            display_fb: 0 as n::FrameBuffer,
+            display_fb: 0 as n::RawFrameBuffer,
             cache: Cache::new(),
             pass_cache: None,
             cur_subpass: !0,
@@ -386,36 +463,88 @@ impl RawCommandBuffer {
         }
     }
 
+    fn bind_program(&mut self, program: n::Program, push_constants: &[n::PushConstantUniform]) {
+        if self.cache.program != Some(program) {
+            self.cache.program = Some(program);
+            self.push_cmd(Command::BindProgram(program));
+
+            // Uniforms are program state, the push constants need to be set again.
+            self.cache.push_constant_uniforms = push_constants.to_vec();
+            self.update_push_constants();
+        }
+    }
+
+    fn push_constants(&mut self, offset: u32, constants: &[u32]) {
+        // The offset is counted in 32-bit words, like the constants.
+        let start = offset as usize;
+        let end = start + constants.len();
+        if self.cache.push_constants.len() < end {
+            self.cache.push_constants.resize(end, 0);
+        }
+        self.cache.push_constants[start .. end].copy_from_slice(constants);
+
+        self.update_push_constants();
+    }
+
+    fn update_push_constants(&mut self) {
+        if self.cache.push_constant_uniforms.is_empty() || self.cache.push_constants.is_empty() {
+            return;
+        }
+
+        // Split processing and command recording due to borrowchk.
+        let uniforms = self.cache.push_constant_uniforms.clone();
+        let data = self.cache.push_constants.clone();
+        let uniforms = self.add(&uniforms);
+        let data = self.add(&data);
+        self.push_cmd(Command::SetPushConstants { uniforms, data });
+    }
+
     fn begin_subpass(&mut self) {
         // Split processing and command recording due to borrowchk.
-        let (draw_buffers, clear_cmds) = {
+        let (framebuffer, draw_buffers, invalidate_points, clear_cmds, render_area) = {
             let state = self.pass_cache.as_ref().unwrap();
             let subpass = &state.render_pass.subpasses[self.cur_subpass];
+            let framebuffer = state.framebuffer.as_ref().unwrap().subpass(self.cur_subpass);
+            let is_default = framebuffer == n::DEFAULT_FRAMEBUFFER;
 
-            // See `begin_renderpass_cache` for clearing strategy
+            // See `begin_render_pass` for the load and store ops strategy
 
             // Bind draw buffers for mapping color output locations with
             // framebuffer attachments.
-            let draw_buffers = if state.framebuffer == n::DEFAULT_FRAMEBUFFER {
+            let draw_buffers = if is_default {
                 // The default framebuffer is created by the driver
                 // We don't have influence on its layout and we treat it as single image.
                 //
                 // TODO: handle case where we don't du double-buffering?
                 vec![gl::BACK_LEFT]
             } else {
-                subpass
-                    .color_attachments
-                    .iter()
-                    .map(|id| gl::COLOR_ATTACHMENT0 + *id as gl::types::GLenum)
+                (0 .. subpass.color_attachments.len())
+                    .map(|i| gl::COLOR_ATTACHMENT0 + i as gl::types::GLenum)
                     .collect::<Vec<_>>()
             };
 
+            // Attachments first used in this subpass without loading their contents.
+            let invalidate_points = state
+                .render_pass
+                .attachments
+                .iter()
+                .zip(state.attachment_clears.iter())
+                .enumerate()
+                .filter(|&(_, (_, clear))| clear.subpass_id == Some(self.cur_subpass))
+                .flat_map(|(id, (attachment, _))| {
+                    attachment_points(subpass, id, attachment, is_default, |ops| {
+                        ops.load == pass::AttachmentLoadOp::DontCare
+                    })
+                })
+                .collect::<Vec<_>>();
+
             let clear_cmds = state
                 .render_pass
                 .attachments
                 .iter()
                 .zip(state.attachment_clears.iter())
-                .filter_map(|(attachment, clear)| {
+                .enumerate()
+                .filter_map(|(id, (attachment, clear))| {
                     // Check if the attachment is first used in this subpass
                     if clear.subpass_id != Some(self.cur_subpass) {
                         return None;
@@ -428,20 +557,22 @@ impl RawCommandBuffer {
 
                     // Clear color target
                     if view_format.is_color() {
+                        // Only attachments rendered to by the subpass can be cleared.
+                        let draw_buffer = subpass.color_attachments.iter().position(|&c| c == id)? as DrawBuffer;
                         if let Some(cv) = clear.value {
                             let channel = view_format.base_format().1;
 
                             let cmd = match channel {
                                 ChannelType::Unorm | ChannelType::Inorm | ChannelType::Ufloat |
                                 ChannelType::Float | ChannelType::Srgb | ChannelType::Uscaled |
-                                ChannelType::Iscaled => Command::ClearBufferColorF(0, unsafe { cv.color.float32 }),
-                                ChannelType::Uint => Command::ClearBufferColorU(0, unsafe { cv.color.uint32 }),
-                                ChannelType::Int => Command::ClearBufferColorI(0, unsafe { cv.color.int32 }),
+                                ChannelType::Iscaled => Command::ClearBufferColorF(draw_buffer, unsafe { cv.color.float32 }),
+                                ChannelType::Uint => Command::ClearBufferColorU(draw_buffer, unsafe { cv.color.uint32 }),
+                                ChannelType::Int => Command::ClearBufferColorI(draw_buffer, unsafe { cv.color.int32 }),
                             };
 
                             return Some(cmd);
                         }
-                    } else {
+                    } else if subpass.depth_stencil == Some(id) {
                         // Clear depth-stencil target
                         let depth = if view_format.is_depth() {
                             clear.value.map(|cv| unsafe { cv.depth_stencil.depth })
@@ -464,31 +595,123 @@ impl RawCommandBuffer {
                 })
                 .collect::<Vec<_>>();
 
-            (draw_buffers, clear_cmds)
+            (framebuffer, draw_buffers, invalidate_points, clear_cmds, state.render_area)
         };
 
         // Record commands
+        self.push_cmd(Command::BindFrameBuffer(gl::DRAW_FRAMEBUFFER, framebuffer));
+
         let draw_buffers = self.add(&draw_buffers);
         self.push_cmd(Command::DrawBuffers(draw_buffers));
 
-        for cmd in clear_cmds {
-            self.push_cmd(cmd);
+        if !invalidate_points.is_empty() {
+            let invalidate_points = self.add(&invalidate_points);
+            self.push_cmd(Command::InvalidateFramebuffer(invalidate_points));
+        }
+
+        if !clear_cmds.is_empty() {
+            // Clears only affect the render area, which the scissor test
+            // applies to them.
+            let area = render_area.unwrap();
+            let scissor = self.add::<i32>(&[area.x as i32, area.y as i32, area.w as i32, area.h as i32]);
+            self.push_cmd(Command::SetScissors(0, scissor));
+            self.push_cmd(Command::SetScissorTest(true));
+            for cmd in clear_cmds {
+                self.push_cmd(cmd);
+            }
+            self.push_cmd(Command::SetScissorTest(false));
+            if let Some((first_scissor, scissors)) = self.cache.scissors {
+                self.push_cmd(Command::SetScissors(first_scissor, scissors));
+            }
+        }
+    }
+
+    fn end_subpass(&mut self) {
+        // Split processing and command recording due to borrowchk.
+        let invalidate_points = {
+            let state = self.pass_cache.as_ref().unwrap();
+            let subpasses = &state.render_pass.subpasses;
+            let subpass = &subpasses[self.cur_subpass];
+            let framebuffer = state.framebuffer.as_ref().unwrap().subpass(self.cur_subpass);
+
+            // Attachments last used in this subpass without storing their contents.
+            state
+                .render_pass
+                .attachments
+                .iter()
+                .enumerate()
+                .filter(|&(id, _)| subpasses.iter().rposition(|sp| sp.is_using(id)) == Some(self.cur_subpass))
+                .flat_map(|(id, attachment)| {
+                    attachment_points(subpass, id, attachment, framebuffer == n::DEFAULT_FRAMEBUFFER, |ops| {
+                        ops.store == pass::AttachmentStoreOp::DontCare
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        if !invalidate_points.is_empty() {
+            let invalidate_points = self.add(&invalidate_points);
+            self.push_cmd(Command::InvalidateFramebuffer(invalidate_points));
+        }
+    }
+}
+
+/// Attachment points of an attachment in the framebuffer object of a subpass,
+/// for its color or depth and stencil aspects whose operations match `filter`.
+fn attachment_points<F>(
+    subpass: &n::SubpassDesc,
+    id: pass::AttachmentId,
+    attachment: &pass::Attachment,
+    is_default: bool,
+    filter: F,
+) -> Vec<gl::types::GLenum>
+where
+    F: Fn(&pass::AttachmentOps) -> bool,
+{
+    let mut points = Vec::new();
+    if let Some(i) = subpass.color_attachments.iter().position(|&c| c == id) {
+        if filter(&attachment.ops) {
+            points.push(if is_default { gl::COLOR } else { gl::COLOR_ATTACHMENT0 + i as gl::types::GLenum });
+        }
+    } else if subpass.depth_stencil == Some(id) {
+        let format = attachment.format.unwrap();
+        if format.is_depth() && filter(&attachment.ops) {
+            points.push(if is_default { gl::DEPTH } else { gl::DEPTH_ATTACHMENT });
+        }
+        if format.is_stencil() && filter(&attachment.stencil_ops) {
+            points.push(if is_default { gl::STENCIL } else { gl::STENCIL_ATTACHMENT });
         }
     }
+    points
 }
 
 impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
     fn begin(
         &mut self,
-        _flags: hal::command::CommandBufferFlags,
-        _inheritance_info: hal::command::CommandBufferInheritanceInfo<Backend>
-    ) { // TODO: Implement flags!
+        flags: hal::command::CommandBufferFlags,
+        inheritance_info: hal::command::CommandBufferInheritanceInfo<Backend>
+    ) { // TODO: Implement remaining flags!
         if self.individual_reset {
             // Implicit buffer reset when individual reset is set.
             self.reset(false);
         } else {
             self.soft_reset();
         }
+
+        // Secondary command buffers executed inside a render pass inherit
+        // the subpass state. Binding the framebuffer, draw buffers and
+        // clearing the attachments is left to the primary command buffer.
+        if flags.contains(hal::command::CommandBufferFlags::RENDER_PASS_CONTINUE) {
+            if let Some(subpass) = inheritance_info.subpass {
+                self.pass_cache = Some(RenderPassCache {
+                    render_pass: subpass.main_pass.clone(),
+                    framebuffer: inheritance_info.framebuffer.cloned(),
+                    render_area: None,
+                    attachment_clears: Vec::new(),
+                });
+                self.cur_subpass = subpass.index;
+            }
+        }
     }
 
     fn finish(&mut self) {
@@ -535,6 +758,32 @@ impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
         // TODO
     }
 
+    fn set_event(&mut self, event: &n::Event, _stages: pso::PipelineStage) {
+        // The sync is signaled once all the previous commands are completed.
+        self.push_cmd(Command::SetEvent(event.clone()));
+    }
+
+    fn reset_event(&mut self, event: &n::Event, _stages: pso::PipelineStage) {
+        self.push_cmd(Command::ResetEvent(event.clone()));
+    }
+
+    fn wait_events<'a, I, J>(
+        &mut self,
+        events: I,
+        _stages: Range<pso::PipelineStage>,
+        _barriers: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<n::Event>,
+        J: IntoIterator,
+        J::Item: Borrow<memory::Barrier<'a, Backend>>,
+    {
+        for event in events {
+            self.push_cmd(Command::WaitEvent(event.borrow().clone()));
+        }
+        // TODO: barriers, see `pipeline_barrier`
+    }
+
     fn fill_buffer<R>(&mut self, _buffer: &n::Buffer, _range: R, _data: u32)
     where
         R: RangeArg<buffer::Offset>,
@@ -550,44 +799,43 @@ impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
         &mut self,
         render_pass: &n::RenderPass,
         framebuffer: &n::FrameBuffer,
-        _render_area: pso::Rect,
+        render_area: pso::Rect,
         clear_values: T,
         _first_subpass: command::SubpassContents,
     ) where
         T: IntoIterator,
         T::Item: Borrow<command::ClearValueRaw>,
     {
-        // TODO: load ops: clearing strategy
-        //  1.  < GL 3.0 / GL ES 2.0: glClear, only single color attachment?
-        //  2.  = GL ES 2.0: glBindFramebuffer + glClear (no single draw buffer supported)
-        //  3. >= GL 3.0 / GL ES 3.0: glBindFramerbuffer + glClearBuffer
+        // Each subpass binds its own framebuffer object.
         //
-        // Clearing when entering a subpass:
-        //    * Acquire channel information from renderpass description to
-        //      select correct ClearBuffer variant.
-        //    * Check for attachment loading clearing strategy
-
-        // TODO: store ops:
-        //   < GL 4.5: Ignore
-        //  >= GL 4.5: Invalidate framebuffer attachment when store op is `DONT_CARE`.
+        // Load ops are applied when entering the first subpass using an attachment:
+        //  * `Clear`: glClearBuffer (>= GL 3.0 / GL ES 3.0), acquiring channel
+        //    information from the renderpass description to select the variant.
+        //  * `DontCare`: glInvalidateFramebuffer, if supported.
+        //
+        // Store ops are applied when leaving the last subpass using an attachment:
+        //  * `DontCare`: glInvalidateFramebuffer, if supported.
 
-        // 2./3.
-        self.push_cmd(Command::BindFrameBuffer(gl::DRAW_FRAMEBUFFER, *framebuffer));
+        // Clear values are only required for attachments being cleared.
+        let clear_values = clear_values
+            .into_iter()
+            .map(|cv| *cv.borrow())
+            .collect::<Vec<_>>();
 
         let attachment_clears = render_pass.attachments
             .iter()
-            .zip(clear_values.into_iter())
             .enumerate()
-            .map(|(i, (attachment, clear_value))| {
+            .map(|(i, attachment)| {
+                let clear_value = clear_values.get(i);
                 AttachmentClear {
                     subpass_id: render_pass.subpasses.iter().position(|sp| sp.is_using(i)),
                     value: if attachment.ops.load == pass::AttachmentLoadOp::Clear {
-                        Some(*clear_value.borrow())
+                        clear_value.cloned()
                     } else {
                         None
                     },
                     stencil_value: if attachment.stencil_ops.load == pass::AttachmentLoadOp::Clear {
-                        Some(unsafe { clear_value.borrow().depth_stencil.stencil })
+                        clear_value.map(|cv| unsafe { cv.depth_stencil.stencil })
                     } else {
                         None
                     },
@@ -596,7 +844,8 @@ impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
 
         self.pass_cache = Some(RenderPassCache {
             render_pass: render_pass.clone(),
-            framebuffer: *framebuffer,
+            framebuffer: Some(framebuffer.clone()),
+            render_area: Some(render_area),
             attachment_clears,
         });
 
@@ -606,11 +855,15 @@ impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
     }
 
     fn next_subpass(&mut self, _contents: command::SubpassContents) {
-        unimplemented!()
+        self.end_subpass();
+        self.cur_subpass += 1;
+        self.begin_subpass();
     }
 
     fn end_render_pass(&mut self) {
-        // TODO
+        self.end_subpass();
+        self.pass_cache = None;
+        self.cur_subpass = !0;
     }
 
     fn clear_image<T>(
@@ -773,6 +1026,7 @@ impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
                 self.cache.error_state = true;
             }
             n if n + first_scissor as usize <= self.limits.max_viewports => {
+                self.cache.scissors = Some((first_scissor, scissors_ptr));
                 self.push_cmd(Command::SetScissors(first_scissor, scissors_ptr));
             }
             _ => {
@@ -842,6 +1096,7 @@ impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
             ref blend_targets,
             ref attributes,
             ref vertex_buffers,
+            ref push_constants,
         } = *pipeline;
 
         if self.cache.primitive != Some(primitive) {
@@ -855,10 +1110,7 @@ impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
             }
         }
 
-        if self.cache.program != Some(program) {
-            self.cache.program = Some(program);
-            self.push_cmd(Command::BindProgram(program));
-        }
+        self.bind_program(program, push_constants);
 
         self.cache.attributes = attributes.clone();
 
@@ -928,12 +1180,10 @@ impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
     fn bind_compute_pipeline(&mut self, pipeline: &n::ComputePipeline) {
         let n::ComputePipeline {
             program,
+            ref push_constants,
         } = *pipeline;
 
-        if self.cache.program != Some(program) {
-            self.cache.program = Some(program);
-            self.push_cmd(Command::BindProgram(program));
-        }
+        self.bind_program(program, push_constants);
     }
 
     fn bind_compute_descriptor_sets<I, J>(
@@ -1020,7 +1270,7 @@ impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
             let r = region.borrow().clone();
             let cmd = match dst.kind {
                 n::ImageKind::Surface(s) => Command::CopyBufferToSurface(src.raw, s, r),
-                n::ImageKind::Texture(t) => Command::CopyBufferToTexture(src.raw, t, r),
+                n::ImageKind::Texture(t) => Command::CopyBufferToTexture(src.raw, t, dst.format, r),
             };
             self.push_cmd(cmd);
         }
@@ -1046,7 +1296,7 @@ impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
             let r = region.borrow().clone();
             let cmd = match src.kind {
                 n::ImageKind::Surface(s) => Command::CopySurfaceToBuffer(s, dst.raw, r),
-                n::ImageKind::Texture(t) => Command::CopyTextureToBuffer(t, dst.raw, r),
+                n::ImageKind::Texture(t) => Command::CopyTextureToBuffer(t, src.format, dst.raw, r),
             };
             self.push_cmd(cmd);
         }
@@ -1119,82 +1369,164 @@ impl command::RawCommandBuffer<Backend> for RawCommandBuffer {
 
     fn draw_indirect(
         &mut self,
-        _buffer: &n::Buffer,
-        _offset: buffer::Offset,
-        _draw_count: hal::DrawCount,
-        _stride: u32,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
     ) {
-        unimplemented!()
+        self.bind_attributes();
+
+        match self.cache.primitive {
+            Some(primitive) => {
+                self.push_cmd(
+                    Command::DrawIndirect {
+                        primitive,
+                        buffer: buffer.raw,
+                        offset,
+                        draw_count,
+                        stride,
+                    }
+                );
+            }
+            None => {
+                warn!("No primitive bound. An active pipeline needs to be bound before calling `draw_indirect`.");
+                self.cache.error_state = true;
+            }
+        }
     }
 
     fn draw_indexed_indirect(
         &mut self,
-        _buffer: &n::Buffer,
-        _offset: buffer::Offset,
-        _draw_count: hal::DrawCount,
-        _stride: u32,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
     ) {
-        unimplemented!()
+        self.bind_attributes();
+
+        let index_type = match self.cache.index_type {
+            Some(hal::IndexType::U16) => gl::UNSIGNED_SHORT,
+            Some(hal::IndexType::U32) => gl::UNSIGNED_INT,
+            None => {
+                warn!("No index type bound. An index buffer needs to be bound before calling `draw_indexed_indirect`.");
+                self.cache.error_state = true;
+                return;
+            }
+        };
+        match self.cache.primitive {
+            Some(primitive) => {
+                self.push_cmd(
+                    Command::DrawIndexedIndirect {
+                        primitive,
+                        index_type,
+                        buffer: buffer.raw,
+                        offset,
+                        draw_count,
+                        stride,
+                    }
+                );
+            }
+            None => {
+                warn!("No primitive bound. An active pipeline needs to be bound before calling `draw_indexed_indirect`.");
+                self.cache.error_state = true;
+            }
+        }
     }
 
     fn begin_query(
         &mut self,
-        _query: query::Query<Backend>,
+        query: query::Query<Backend>,
         _flags: query::QueryControl,
     ) {
-        unimplemented!()
+        // Occlusion queries always count the exact number of samples.
+        let id = query.pool.queries[query.id as usize];
+        self.push_cmd(Command::BeginQuery(query.pool.target, id));
     }
 
     fn push_graphics_constants(
         &mut self,
         _layout: &n::PipelineLayout,
         _stages: pso::ShaderStageFlags,
-        _offset: u32,
-        _constants: &[u32],
+        offset: u32,
+        constants: &[u32],
     ) {
-        unimplemented!()
+        self.push_constants(offset, constants);
     }
 
     fn end_query(
         &mut self,
-        _query: query::Query<Backend>,
+        query: query::Query<Backend>,
     ) {
-        unimplemented!()
+        self.push_cmd(Command::EndQuery(query.pool.target));
     }
 
     fn reset_query_pool(
         &mut self,
-        _pool: &(),
+        _pool: &n::QueryPool,
         _queries: Range<query::QueryId>,
     ) {
-        unimplemented!()
+        // Nothing to do, the results of GL queries are replaced each time they are issued.
     }
 
     fn write_timestamp(
         &mut self,
         _: pso::PipelineStage,
-        _: query::Query<Backend>,
+        query: query::Query<Backend>,
     ) {
-        unimplemented!()
+        // The timestamp is always written once all previous commands are completed.
+        let id = query.pool.queries[query.id as usize];
+        self.push_cmd(Command::WriteTimestamp(id));
+    }
+
+    fn copy_query_pool_results(
+        &mut self,
+        pool: &n::QueryPool,
+        queries: Range<query::QueryId>,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) {
+        for (i, id) in queries.enumerate() {
+            self.push_cmd(Command::CopyQueryResult {
+                query: pool.queries[id as usize],
+                buffer: buffer.raw,
+                offset: offset + i as buffer::Offset * stride,
+                flags,
+            });
+        }
     }
 
     fn push_compute_constants(
         &mut self,
         _layout: &n::PipelineLayout,
-        _offset: u32,
-        _constants: &[u32],
+        offset: u32,
+        constants: &[u32],
     ) {
-        unimplemented!()
+        self.push_constants(offset, constants);
     }
 
     fn execute_commands<I>(
         &mut self,
-        _buffers: I,
+        buffers: I,
     ) where
         I: IntoIterator,
         I::Item: Borrow<RawCommandBuffer>
     {
-        unimplemented!()
+        for buffer in buffers {
+            let buffer = buffer.borrow();
+            self.push_cmd(Command::ExecuteCommands(SecondaryCommands {
+                memory: buffer.memory.clone(),
+                id: buffer.id,
+                buf: buffer.buf,
+            }));
+        }
+
+        // The secondary command buffers may have changed any state,
+        // which needs to be bound again afterwards.
+        let error_state = self.cache.error_state;
+        self.cache = Cache::new();
+        self.cache.error_state = error_state;
     }
 }
 
@@ -51,7 +51,7 @@ pub fn buffer_usage_to_gl_target(usage: buffer::Usage) -> Option<t::GLenum> {
         Usage::UNIFORM => Some(gl::UNIFORM_BUFFER),
         Usage::INDEX => Some(gl::ELEMENT_ARRAY_BUFFER),
         Usage::VERTEX => Some(gl::ARRAY_BUFFER),
-        Usage::INDIRECT => unimplemented!(),
+        Usage::INDIRECT => Some(gl::DRAW_INDIRECT_BUFFER),
         _ => None
     }
 }
@@ -111,3 +111,240 @@ pub fn format_to_gl_format(format: Format) -> Option<(gl::types::GLint, gl::type
 
     Some(format)
 }
+
+/// Compressed formats exposed by extensions not part of the core bindings.
+mod ext {
+    use gl::types::GLenum;
+
+    // GL_EXT_texture_compression_s3tc
+    pub const COMPRESSED_RGB_S3TC_DXT1: GLenum = 0x83F0;
+    pub const COMPRESSED_RGBA_S3TC_DXT1: GLenum = 0x83F1;
+    pub const COMPRESSED_RGBA_S3TC_DXT3: GLenum = 0x83F2;
+    pub const COMPRESSED_RGBA_S3TC_DXT5: GLenum = 0x83F3;
+    // GL_EXT_texture_sRGB
+    pub const COMPRESSED_SRGB_S3TC_DXT1: GLenum = 0x8C4C;
+    pub const COMPRESSED_SRGB_ALPHA_S3TC_DXT1: GLenum = 0x8C4D;
+    pub const COMPRESSED_SRGB_ALPHA_S3TC_DXT3: GLenum = 0x8C4E;
+    pub const COMPRESSED_SRGB_ALPHA_S3TC_DXT5: GLenum = 0x8C4F;
+    // GL_KHR_texture_compression_astc_ldr, the sRGB variants are offset by
+    // `COMPRESSED_SRGB8_ALPHA8_ASTC - COMPRESSED_RGBA_ASTC`.
+    pub const COMPRESSED_RGBA_ASTC_4x4: GLenum = 0x93B0;
+    pub const COMPRESSED_SRGB8_ALPHA8_ASTC_4x4: GLenum = 0x93D0;
+}
+
+/// Family of a format, deciding which version or extension exposes it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatFamily {
+    /// Plain color format, with channels in the native order.
+    Color,
+    /// Color format with a BGR(A) client-side layout, desktop GL only.
+    Bgra,
+    /// Signed normalized color format.
+    Snorm,
+    /// 16 bit normalized color format.
+    Norm16,
+    /// Depth and/or stencil format.
+    DepthStencil,
+    /// Stencil only format.
+    Stencil,
+    /// S3TC/DXT compressed format.
+    S3tc,
+    /// S3TC/DXT compressed format with sRGB encoding.
+    S3tcSrgb,
+    /// RGTC compressed format.
+    Rgtc,
+    /// BPTC compressed format.
+    Bptc,
+    /// ETC2/EAC compressed format.
+    Etc2,
+    /// ASTC compressed format.
+    Astc,
+}
+
+/// GL description of a `Format` used for images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FormatDescription {
+    /// Sized internal format for texture and renderbuffer storage.
+    pub tex_internal: t::GLenum,
+    /// Client-side format of pixel transfers, `0` for compressed formats.
+    pub tex_external: t::GLenum,
+    /// Client-side data type of pixel transfers, `0` for compressed formats.
+    pub data_type: t::GLenum,
+}
+
+impl FormatDescription {
+    fn new(tex_internal: t::GLenum, tex_external: t::GLenum, data_type: t::GLenum) -> Self {
+        FormatDescription { tex_internal, tex_external, data_type }
+    }
+
+    fn compressed(tex_internal: t::GLenum) -> Self {
+        FormatDescription { tex_internal, tex_external: 0, data_type: 0 }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.tex_external == 0
+    }
+}
+
+/// Map an image format to its GL description, if GL or GLES can express it.
+///
+/// Formats without a sized internal format (scaled integers, 64 bit channels,
+/// 8 bit sRGB with less than 3 channels, ..) are not supported.
+pub fn describe_format(format: Format) -> Option<(FormatDescription, FormatFamily)> {
+    use hal::format::Format::*;
+    use gl::*;
+    use self::FormatFamily as F;
+    let new = FormatDescription::new;
+    let compressed = FormatDescription::compressed;
+
+    let desc = match format {
+        Rgba4Unorm => (new(RGBA4, RGBA, UNSIGNED_SHORT_4_4_4_4), F::Color),
+        Bgra4Unorm => (new(RGBA4, BGRA, UNSIGNED_SHORT_4_4_4_4), F::Bgra),
+        R5g6b5Unorm => (new(RGB565, RGB, UNSIGNED_SHORT_5_6_5), F::Color),
+        B5g6r5Unorm => (new(RGB565, RGB, UNSIGNED_SHORT_5_6_5_REV), F::Bgra),
+        R5g5b5a1Unorm => (new(RGB5_A1, RGBA, UNSIGNED_SHORT_5_5_5_1), F::Color),
+        B5g5r5a1Unorm => (new(RGB5_A1, BGRA, UNSIGNED_SHORT_5_5_5_1), F::Bgra),
+        A1r5g5b5Unorm => (new(RGB5_A1, BGRA, UNSIGNED_SHORT_1_5_5_5_REV), F::Bgra),
+
+        R8Unorm => (new(R8, RED, UNSIGNED_BYTE), F::Color),
+        R8Inorm => (new(R8_SNORM, RED, BYTE), F::Snorm),
+        R8Uint => (new(R8UI, RED_INTEGER, UNSIGNED_BYTE), F::Color),
+        R8Int => (new(R8I, RED_INTEGER, BYTE), F::Color),
+        Rg8Unorm => (new(RG8, RG, UNSIGNED_BYTE), F::Color),
+        Rg8Inorm => (new(RG8_SNORM, RG, BYTE), F::Snorm),
+        Rg8Uint => (new(RG8UI, RG_INTEGER, UNSIGNED_BYTE), F::Color),
+        Rg8Int => (new(RG8I, RG_INTEGER, BYTE), F::Color),
+        Rgb8Unorm => (new(RGB8, RGB, UNSIGNED_BYTE), F::Color),
+        Rgb8Inorm => (new(RGB8_SNORM, RGB, BYTE), F::Snorm),
+        Rgb8Uint => (new(RGB8UI, RGB_INTEGER, UNSIGNED_BYTE), F::Color),
+        Rgb8Int => (new(RGB8I, RGB_INTEGER, BYTE), F::Color),
+        Rgb8Srgb => (new(SRGB8, RGB, UNSIGNED_BYTE), F::Color),
+        Bgr8Unorm => (new(RGB8, BGR, UNSIGNED_BYTE), F::Bgra),
+        Bgr8Uint => (new(RGB8UI, BGR_INTEGER, UNSIGNED_BYTE), F::Bgra),
+        Bgr8Int => (new(RGB8I, BGR_INTEGER, BYTE), F::Bgra),
+        Bgr8Srgb => (new(SRGB8, BGR, UNSIGNED_BYTE), F::Bgra),
+        // Packed `ABGR` formats match the byte order of `RGBA` ones on
+        // little-endian systems.
+        Rgba8Unorm | Abgr8Unorm => (new(RGBA8, RGBA, UNSIGNED_BYTE), F::Color),
+        Rgba8Inorm | Abgr8Inorm => (new(RGBA8_SNORM, RGBA, BYTE), F::Snorm),
+        Rgba8Uint | Abgr8Uint => (new(RGBA8UI, RGBA_INTEGER, UNSIGNED_BYTE), F::Color),
+        Rgba8Int | Abgr8Int => (new(RGBA8I, RGBA_INTEGER, BYTE), F::Color),
+        Rgba8Srgb | Abgr8Srgb => (new(SRGB8_ALPHA8, RGBA, UNSIGNED_BYTE), F::Color),
+        Bgra8Unorm => (new(RGBA8, BGRA, UNSIGNED_BYTE), F::Bgra),
+        Bgra8Uint => (new(RGBA8UI, BGRA_INTEGER, UNSIGNED_BYTE), F::Bgra),
+        Bgra8Int => (new(RGBA8I, BGRA_INTEGER, BYTE), F::Bgra),
+        Bgra8Srgb => (new(SRGB8_ALPHA8, BGRA, UNSIGNED_BYTE), F::Bgra),
+        A2r10g10b10Unorm => (new(RGB10_A2, BGRA, UNSIGNED_INT_2_10_10_10_REV), F::Bgra),
+        A2r10g10b10Uint => (new(RGB10_A2UI, BGRA_INTEGER, UNSIGNED_INT_2_10_10_10_REV), F::Bgra),
+        A2b10g10r10Unorm => (new(RGB10_A2, RGBA, UNSIGNED_INT_2_10_10_10_REV), F::Color),
+        A2b10g10r10Uint => (new(RGB10_A2UI, RGBA_INTEGER, UNSIGNED_INT_2_10_10_10_REV), F::Color),
+
+        R16Unorm => (new(R16, RED, UNSIGNED_SHORT), F::Norm16),
+        R16Inorm => (new(R16_SNORM, RED, SHORT), F::Norm16),
+        R16Uint => (new(R16UI, RED_INTEGER, UNSIGNED_SHORT), F::Color),
+        R16Int => (new(R16I, RED_INTEGER, SHORT), F::Color),
+        R16Float => (new(R16F, RED, HALF_FLOAT), F::Color),
+        Rg16Unorm => (new(RG16, RG, UNSIGNED_SHORT), F::Norm16),
+        Rg16Inorm => (new(RG16_SNORM, RG, SHORT), F::Norm16),
+        Rg16Uint => (new(RG16UI, RG_INTEGER, UNSIGNED_SHORT), F::Color),
+        Rg16Int => (new(RG16I, RG_INTEGER, SHORT), F::Color),
+        Rg16Float => (new(RG16F, RG, HALF_FLOAT), F::Color),
+        Rgb16Unorm => (new(RGB16, RGB, UNSIGNED_SHORT), F::Norm16),
+        Rgb16Inorm => (new(RGB16_SNORM, RGB, SHORT), F::Norm16),
+        Rgb16Uint => (new(RGB16UI, RGB_INTEGER, UNSIGNED_SHORT), F::Color),
+        Rgb16Int => (new(RGB16I, RGB_INTEGER, SHORT), F::Color),
+        Rgb16Float => (new(RGB16F, RGB, HALF_FLOAT), F::Color),
+        Rgba16Unorm => (new(RGBA16, RGBA, UNSIGNED_SHORT), F::Norm16),
+        Rgba16Inorm => (new(RGBA16_SNORM, RGBA, SHORT), F::Norm16),
+        Rgba16Uint => (new(RGBA16UI, RGBA_INTEGER, UNSIGNED_SHORT), F::Color),
+        Rgba16Int => (new(RGBA16I, RGBA_INTEGER, SHORT), F::Color),
+        Rgba16Float => (new(RGBA16F, RGBA, HALF_FLOAT), F::Color),
+
+        R32Uint => (new(R32UI, RED_INTEGER, UNSIGNED_INT), F::Color),
+        R32Int => (new(R32I, RED_INTEGER, INT), F::Color),
+        R32Float => (new(R32F, RED, FLOAT), F::Color),
+        Rg32Uint => (new(RG32UI, RG_INTEGER, UNSIGNED_INT), F::Color),
+        Rg32Int => (new(RG32I, RG_INTEGER, INT), F::Color),
+        Rg32Float => (new(RG32F, RG, FLOAT), F::Color),
+        Rgb32Uint => (new(RGB32UI, RGB_INTEGER, UNSIGNED_INT), F::Color),
+        Rgb32Int => (new(RGB32I, RGB_INTEGER, INT), F::Color),
+        Rgb32Float => (new(RGB32F, RGB, FLOAT), F::Color),
+        Rgba32Uint => (new(RGBA32UI, RGBA_INTEGER, UNSIGNED_INT), F::Color),
+        Rgba32Int => (new(RGBA32I, RGBA_INTEGER, INT), F::Color),
+        Rgba32Float => (new(RGBA32F, RGBA, FLOAT), F::Color),
+        B10g11r11Ufloat => (new(R11F_G11F_B10F, RGB, UNSIGNED_INT_10F_11F_11F_REV), F::Color),
+        E5b9g9r9Ufloat => (new(RGB9_E5, RGB, UNSIGNED_INT_5_9_9_9_REV), F::Color),
+
+        D16Unorm => (new(DEPTH_COMPONENT16, DEPTH_COMPONENT, UNSIGNED_SHORT), F::DepthStencil),
+        X8D24Unorm => (new(DEPTH_COMPONENT24, DEPTH_COMPONENT, UNSIGNED_INT), F::DepthStencil),
+        D32Float => (new(DEPTH_COMPONENT32F, DEPTH_COMPONENT, FLOAT), F::DepthStencil),
+        S8Uint => (new(STENCIL_INDEX8, STENCIL_INDEX, UNSIGNED_BYTE), F::Stencil),
+        D24UnormS8Uint => (new(DEPTH24_STENCIL8, DEPTH_STENCIL, UNSIGNED_INT_24_8), F::DepthStencil),
+        D32FloatS8Uint => (new(DEPTH32F_STENCIL8, DEPTH_STENCIL, FLOAT_32_UNSIGNED_INT_24_8_REV), F::DepthStencil),
+
+        Bc1RgbUnorm => (compressed(ext::COMPRESSED_RGB_S3TC_DXT1), F::S3tc),
+        Bc1RgbSrgb => (compressed(ext::COMPRESSED_SRGB_S3TC_DXT1), F::S3tcSrgb),
+        Bc1RgbaUnorm => (compressed(ext::COMPRESSED_RGBA_S3TC_DXT1), F::S3tc),
+        Bc1RgbaSrgb => (compressed(ext::COMPRESSED_SRGB_ALPHA_S3TC_DXT1), F::S3tcSrgb),
+        Bc2Unorm => (compressed(ext::COMPRESSED_RGBA_S3TC_DXT3), F::S3tc),
+        Bc2Srgb => (compressed(ext::COMPRESSED_SRGB_ALPHA_S3TC_DXT3), F::S3tcSrgb),
+        Bc3Unorm => (compressed(ext::COMPRESSED_RGBA_S3TC_DXT5), F::S3tc),
+        Bc3Srgb => (compressed(ext::COMPRESSED_SRGB_ALPHA_S3TC_DXT5), F::S3tcSrgb),
+        Bc4Unorm => (compressed(COMPRESSED_RED_RGTC1), F::Rgtc),
+        Bc4Inorm => (compressed(COMPRESSED_SIGNED_RED_RGTC1), F::Rgtc),
+        Bc5Unorm => (compressed(COMPRESSED_RG_RGTC2), F::Rgtc),
+        Bc5Inorm => (compressed(COMPRESSED_SIGNED_RG_RGTC2), F::Rgtc),
+        Bc6hUfloat => (compressed(COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT), F::Bptc),
+        Bc6hFloat => (compressed(COMPRESSED_RGB_BPTC_SIGNED_FLOAT), F::Bptc),
+        Bc7Unorm => (compressed(COMPRESSED_RGBA_BPTC_UNORM), F::Bptc),
+        Bc7Srgb => (compressed(COMPRESSED_SRGB_ALPHA_BPTC_UNORM), F::Bptc),
+        Etc2R8g8b8Unorm => (compressed(COMPRESSED_RGB8_ETC2), F::Etc2),
+        Etc2R8g8b8Srgb => (compressed(COMPRESSED_SRGB8_ETC2), F::Etc2),
+        Etc2R8g8b8a1Unorm => (compressed(COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2), F::Etc2),
+        Etc2R8g8b8a1Srgb => (compressed(COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2), F::Etc2),
+        Etc2R8g8b8a8Unorm => (compressed(COMPRESSED_RGBA8_ETC2_EAC), F::Etc2),
+        Etc2R8g8b8a8Srgb => (compressed(COMPRESSED_SRGB8_ALPHA8_ETC2_EAC), F::Etc2),
+        EacR11Unorm => (compressed(COMPRESSED_R11_EAC), F::Etc2),
+        EacR11Inorm => (compressed(COMPRESSED_SIGNED_R11_EAC), F::Etc2),
+        EacR11g11Unorm => (compressed(COMPRESSED_RG11_EAC), F::Etc2),
+        EacR11g11Inorm => (compressed(COMPRESSED_SIGNED_RG11_EAC), F::Etc2),
+        Astc4x4Unorm | Astc5x4Unorm | Astc5x5Unorm | Astc6x5Unorm |
+        Astc6x6Unorm | Astc8x5Unorm | Astc8x6Unorm | Astc8x8Unorm |
+        Astc10x5Unorm | Astc10x6Unorm | Astc10x8Unorm | Astc10x10Unorm |
+        Astc12x10Unorm | Astc12x12Unorm => {
+            let index = (format as t::GLenum - Astc4x4Unorm as t::GLenum) / 2;
+            (compressed(ext::COMPRESSED_RGBA_ASTC_4x4 + index), F::Astc)
+        }
+        Astc4x4Srgb | Astc5x4Srgb | Astc5x5Srgb | Astc6x5Srgb |
+        Astc6x6Srgb | Astc8x5Srgb | Astc8x6Srgb | Astc8x8Srgb |
+        Astc10x5Srgb | Astc10x6Srgb | Astc10x8Srgb | Astc10x10Srgb |
+        Astc12x10Srgb | Astc12x12Srgb => {
+            let index = (format as t::GLenum - Astc4x4Srgb as t::GLenum) / 2;
+            (compressed(ext::COMPRESSED_SRGB8_ALPHA8_ASTC_4x4 + index), F::Astc)
+        }
+
+        _ => return None,
+    };
+
+    Some(desc)
+}
+
+/// Number of columns and rows of a uniform type that can hold push constants.
+pub fn uniform_shape(ty: t::GLenum) -> Option<(usize, usize)> {
+    Some(match ty {
+        gl::FLOAT | gl::INT | gl::UNSIGNED_INT => (1, 1),
+        gl::FLOAT_VEC2 | gl::INT_VEC2 | gl::UNSIGNED_INT_VEC2 => (1, 2),
+        gl::FLOAT_VEC3 | gl::INT_VEC3 | gl::UNSIGNED_INT_VEC3 => (1, 3),
+        gl::FLOAT_VEC4 | gl::INT_VEC4 | gl::UNSIGNED_INT_VEC4 => (1, 4),
+        gl::FLOAT_MAT2 => (2, 2),
+        gl::FLOAT_MAT2x3 => (2, 3),
+        gl::FLOAT_MAT2x4 => (2, 4),
+        gl::FLOAT_MAT3x2 => (3, 2),
+        gl::FLOAT_MAT3 => (3, 3),
+        gl::FLOAT_MAT3x4 => (3, 4),
+        gl::FLOAT_MAT4x2 => (4, 2),
+        gl::FLOAT_MAT4x3 => (4, 3),
+        gl::FLOAT_MAT4 => (4, 4),
+        _ => return None,
+    })
+}
@@ -17,11 +17,14 @@ use hal::range::RangeArg;
 
 use spirv_cross::{glsl, spirv, ErrorCode as SpirvErrorCode};
 
-use {Backend as B, Share, Surface, Swapchain, Starc};
+use {Backend as B, Error, Share, Surface, Swapchain, Starc};
 use {conv, native as n, state};
 use info::LegacyFeatures;
 use pool::{BufferMemory, OwnedBuffer, RawCommandPool};
 
+/// Name given to the push constant blocks, which are emulated with plain uniforms.
+const PUSH_CONSTANTS_NAME: &str = "GFX_HAL_PUSH_CONSTANTS";
+
 /// Emit error during shader module creation. Used if we don't expect an error
 /// but might panic due to an exception in SPIRV-Cross.
 fn gen_unexpected_error(err: SpirvErrorCode) -> d::ShaderError {
@@ -76,8 +79,135 @@ pub fn get_program_log(gl: &gl::Gl, name: n::Program) -> String {
     }
 }
 
+/// Write a query value at the start of `data`, truncating it to 32 bits
+/// unless 64-bit results are requested. Returns the number of written bytes.
+fn write_query_value(data: &mut [u8], value: u64, flags: query::ResultFlags) -> usize {
+    if flags.contains(query::ResultFlags::BITS_64) {
+        let bytes: [u8; 8] = unsafe { mem::transmute(value) };
+        data[.. 8].copy_from_slice(&bytes);
+        8
+    } else {
+        let bytes: [u8; 4] = unsafe { mem::transmute(value as u32) };
+        data[.. 4].copy_from_slice(&bytes);
+        4
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// 64-bit FNV-1a hash, stable across runs to key the cached program binaries.
+fn fnv_hash(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+/// Bytes at the start of the pipeline cache data.
+const PIPELINE_CACHE_MAGIC: [u8; 4] = *b"GLPC";
+
+fn read_u32(data: &[u8], offset: &mut usize) -> Option<u32> {
+    let bytes = data.get(*offset .. *offset + 4)?;
+    *offset += 4;
+    Some(bytes.iter().rev().fold(0, |value, &byte| value << 8 | byte as u32))
+}
+
+fn read_u64(data: &[u8], offset: &mut usize) -> Option<u64> {
+    let low = read_u32(data, offset)? as u64;
+    let high = read_u32(data, offset)? as u64;
+    Some(high << 32 | low)
+}
+
+fn write_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend((0 .. 4).map(|i| (value >> (8 * i)) as u8));
+}
+
+fn write_u64(data: &mut Vec<u8>, value: u64) {
+    write_u32(data, value as u32);
+    write_u32(data, (value >> 32) as u32);
+}
+
+/// Store the program binaries of a pipeline cache, along with the hash of
+/// the driver which produced them.
+fn write_pipeline_cache(binaries: &FastHashMap<u64, n::ProgramBinary>, driver: u64) -> Vec<u8> {
+    let mut data = PIPELINE_CACHE_MAGIC.to_vec();
+    write_u64(&mut data, driver);
+    for (&key, binary) in binaries {
+        write_u64(&mut data, key);
+        write_u32(&mut data, binary.format);
+        write_u32(&mut data, binary.data.len() as u32);
+        data.extend_from_slice(&binary.data);
+    }
+    data
+}
+
+/// Parse the program binaries stored by `write_pipeline_cache`.
+fn parse_pipeline_cache(data: &[u8], driver: u64) -> Option<FastHashMap<u64, n::ProgramBinary>> {
+    if data.get(.. 4) != Some(&PIPELINE_CACHE_MAGIC[..]) {
+        return None;
+    }
+    let mut offset = 4;
+    if read_u64(data, &mut offset)? != driver {
+        return None;
+    }
+    let mut binaries = FastHashMap::default();
+    while offset < data.len() {
+        let key = read_u64(data, &mut offset)?;
+        let format = read_u32(data, &mut offset)?;
+        let length = read_u32(data, &mut offset)? as usize;
+        let binary = data.get(offset .. offset + length)?;
+        offset += length;
+        binaries.insert(key, n::ProgramBinary { format, data: binary.to_vec() });
+    }
+    Some(binaries)
+}
+
+/// Shader of a pipeline, before compilation.
+enum ShaderSource {
+    Raw(n::Shader),
+    Glsl(String),
+}
+
+/// Record the offset of each uniform emulating the members of a push
+/// constant struct, named after `prefix`. Nested structs become one uniform
+/// per member as well.
+fn add_push_constant_members(
+    ast: &spirv::Ast<glsl::Target>,
+    type_id: u32,
+    prefix: &str,
+    base_offset: u32,
+    push_constant_offsets: &mut FastHashMap<String, u32>,
+) -> Result<(), d::ShaderError> {
+    let member_types = match ast.get_type(type_id).unwrap() {
+        spirv::Type::Struct { member_types, .. } => member_types,
+        other => panic!("Unexpected push constant block type: {:?}", other),
+    };
+    for (index, &member_type) in member_types.iter().enumerate() {
+        let index = index as u32;
+        let mut member = ast.get_member_name(type_id, index).unwrap();
+        if member.is_empty() {
+            // Name generated by SPIRV-Cross for stripped modules.
+            member = format!("_m{}", index);
+        }
+        let name = format!("{}.{}", prefix, member);
+        let offset = base_offset + ast
+            .get_member_decoration(type_id, index, spirv::Decoration::Offset)
+            .unwrap();
+        match ast.get_type(member_type).unwrap() {
+            spirv::Type::Struct { ref array, .. } if array.is_empty() => {
+                add_push_constant_members(ast, member_type, &name, offset, push_constant_offsets)?;
+            }
+            spirv::Type::Struct { .. } => {
+                let msg = format!("Push constant {} is an array of structs, which is not supported", name);
+                return Err(d::ShaderError::CompilationFailed(msg));
+            }
+            _ => {
+                push_constant_offsets.insert(name, offset);
+            }
+        }
+    }
+    Ok(())
+}
+
 fn create_fbo_internal(gl: &gl::Gl) -> gl::types::GLuint {
-    let mut name = 0 as n::FrameBuffer;
+    let mut name = 0 as n::RawFrameBuffer;
     unsafe {
         gl.GenFramebuffers(1, &mut name);
     }
@@ -95,6 +225,7 @@ pub struct UnboundBuffer {
 #[derive(Debug)]
 pub struct UnboundImage {
     image: n::ImageKind,
+    format: Format,
     channel: ChannelType,
     requirements: memory::Requirements,
 }
@@ -293,6 +424,8 @@ impl Device {
         let res = ast.get_shader_resources().unwrap();
         self.remap_binding(ast, desc_remap_data, nb_map, &res.sampled_images, n::BindingTypes::Images);
         self.remap_binding(ast, desc_remap_data, nb_map, &res.uniform_buffers, n::BindingTypes::UniformBuffers);
+        // SPIRV-Cross turns subpass inputs into textures fetched at the fragment coordinates.
+        self.remap_binding(ast, desc_remap_data, nb_map, &res.subpass_inputs, n::BindingTypes::Images);
     }
 
     fn remap_binding(
@@ -363,6 +496,21 @@ impl Device {
         }
     }
 
+    fn remap_push_constants(
+        &self,
+        ast: &mut spirv::Ast<glsl::Target>,
+        push_constant_offsets: &mut FastHashMap<String, u32>,
+    ) -> Result<(), d::ShaderError> {
+        // SPIRV-Cross declares push constant blocks as uniform structs
+        // when targeting GL, so each member becomes a separate uniform.
+        let res = ast.get_shader_resources().unwrap();
+        for pc in &res.push_constant_buffers {
+            ast.set_name(pc.id, PUSH_CONSTANTS_NAME).unwrap();
+            add_push_constant_members(ast, pc.base_type_id, PUSH_CONSTANTS_NAME, 0, push_constant_offsets)?;
+        }
+        Ok(())
+    }
+
     fn populate_id_map(
         &self,
         ast: &mut spirv::Ast<glsl::Target>,
@@ -376,18 +524,20 @@ impl Device {
         }
     }
 
-    fn compile_shader(
+    /// Translate an entry point to GLSL, remapping its bindings, unless the
+    /// module is already a compiled shader.
+    fn translate_shader(
         &self,
         point: &pso::EntryPoint<B>,
-        stage: pso::Stage,
         desc_remap_data: &mut n::DescRemapData,
         name_binding_map: &mut FastHashMap<String, pso::DescriptorBinding>,
-    ) -> n::Shader {
+        push_constant_offsets: &mut FastHashMap<String, u32>,
+    ) -> Result<ShaderSource, d::ShaderError> {
         assert_eq!(point.entry, "main");
         match *point.module {
             n::ShaderModule::Raw(raw) => {
                 debug!("Can't remap bindings for raw shaders. Assuming they are already rebound.");
-                raw
+                Ok(ShaderSource::Raw(raw))
             }
             n::ShaderModule::Spirv(ref spirv) => {
                 let mut ast = self.parse_spirv(spirv).unwrap();
@@ -395,17 +545,224 @@ impl Device {
                 self.specialize_ast(&mut ast, point.specialization).unwrap();
                 self.remap_bindings(&mut ast, desc_remap_data, name_binding_map);
                 self.combine_separate_images_and_samplers(&mut ast, desc_remap_data, name_binding_map);
+                self.remap_push_constants(&mut ast, push_constant_offsets)?;
 
                 let glsl = self.translate_spirv(&mut ast).unwrap();
                 info!("Generated:\n{:?}", glsl);
-                let shader = match self.create_shader_module_from_source(glsl.as_bytes(), stage).unwrap() {
+                Ok(ShaderSource::Glsl(glsl))
+            }
+        }
+    }
+
+    fn compile_shader(&self, source: &ShaderSource, stage: pso::Stage) -> n::Shader {
+        match *source {
+            ShaderSource::Raw(raw) => raw,
+            ShaderSource::Glsl(ref glsl) => {
+                match self.create_shader_module_from_source(glsl.as_bytes(), stage).unwrap() {
                     n::ShaderModule::Raw(raw) => raw,
                     _ => panic!("Unhandled")
+                }
+            }
+        }
+    }
+
+    /// Identify the driver, since program binaries can only be loaded by the
+    /// one that produced them.
+    fn driver_hash(&self) -> u64 {
+        let info = &self.share.info;
+        let version = [
+            info.version.is_embedded as u8,
+            info.version.major as u8,
+            info.version.minor as u8,
+        ];
+        [
+            info.platform_name.vendor.as_bytes(),
+            info.platform_name.renderer.as_bytes(),
+            info.version.vendor_info.as_bytes(),
+            &version[..],
+        ]
+            .iter()
+            .fold(FNV_OFFSET_BASIS, |hash, bytes| fnv_hash(hash, bytes))
+    }
+
+    /// Create a program from the shaders of a pipeline, loading it from the
+    /// binary in the cache when possible.
+    ///
+    /// Returns the program along with the uniforms emulating its push constants.
+    fn create_program(
+        &self,
+        shaders: &[(pso::Stage, &pso::EntryPoint<B>)],
+        layout: &n::PipelineLayout,
+        color_targets: usize,
+        cache: Option<&n::PipelineCache>,
+    ) -> Result<(n::Program, Vec<n::PushConstantUniform>), pso::CreationError> {
+        let gl = &self.share.context;
+        let share = &self.share;
+
+        let mut name_binding_map = FastHashMap::<String, pso::DescriptorBinding>::default();
+        let mut push_constant_offsets = FastHashMap::<String, u32>::default();
+        let sources = {
+            let mut desc_remap_data = layout.desc_remap_data.write().unwrap();
+            shaders
+                .iter()
+                .map(|&(stage, point)| {
+                    let source = self.translate_shader(
+                        point,
+                        &mut desc_remap_data,
+                        &mut name_binding_map,
+                        &mut push_constant_offsets,
+                    )?;
+                    Ok((stage, source))
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(pso::CreationError::Shader)?
+        };
+
+        // The generated sources depend on the shaders, the specialization
+        // and the layout. Raw shaders can't be identified across runs.
+        let key = match cache {
+            Some(_) if share.private_caps.program_binary => {
+                sources.iter().fold(Some(FNV_OFFSET_BASIS), |hash, &(stage, ref source)| {
+                    match (hash, source) {
+                        (Some(hash), &ShaderSource::Glsl(ref glsl)) => {
+                            Some(fnv_hash(fnv_hash(hash, &[stage as u8]), glsl.as_bytes()))
+                        }
+                        _ => None,
+                    }
+                })
+                .map(|hash| fnv_hash(hash, &[color_targets as u8]))
+            }
+            _ => None,
+        };
+
+        let name = unsafe { gl.CreateProgram() };
+        let cached = match (cache, key) {
+            (Some(cache), Some(key)) => cache.binaries.lock().unwrap().get(&key).cloned(),
+            _ => None,
+        };
+        let loaded = match cached {
+            Some(binary) => {
+                unsafe {
+                    gl.ProgramBinary(name, binary.format, binary.data.as_ptr() as *const _, binary.data.len() as _);
+                }
+                // Loading fails if the driver has been updated in between.
+                let status = get_program_iv(gl, name, gl::LINK_STATUS);
+                if status == 0 {
+                    info!("\tProgram binary of {} rejected, linking from sources", name);
+                }
+                status != 0
+            }
+            None => false,
+        };
+
+        if !loaded {
+            let shader_names = sources
+                .iter()
+                .map(|&(stage, ref source)| {
+                    let shader_name = self.compile_shader(source, stage);
+                    unsafe { gl.AttachShader(name, shader_name); }
+                    shader_name
+                })
+                .collect::<Vec<_>>();
+
+            if !share.private_caps.program_interface && share.private_caps.frag_data_location {
+                for i in 0..color_targets {
+                    let color_name = format!("Target{}\0", i);
+                    unsafe {
+                        gl.BindFragDataLocation(name, i as u32, (&color_name[..]).as_ptr() as *mut gl::types::GLchar);
+                    }
+                }
+            }
+            if key.is_some() {
+                unsafe { gl.ProgramParameteri(name, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as _) };
+            }
+
+            unsafe { gl.LinkProgram(name) };
+            info!("\tLinked program {}", name);
+            if let Err(err) = share.check() {
+                panic!("Error linking program: {:?}", err);
+            }
+
+            for shader_name in shader_names {
+                unsafe {
+                    gl.DetachShader(name, shader_name);
+                    gl.DeleteShader(shader_name);
+                }
+            }
+        }
+
+        if !self.share.legacy_features.contains(LegacyFeatures::EXPLICIT_LAYOUTS_IN_SHADER) {
+            unsafe {
+                gl.UseProgram(name);
+                for (bname, binding) in name_binding_map.iter() {
+                    let loc = gl.GetUniformLocation(name, bname.as_ptr() as _);
+                    gl.Uniform1i(loc, *binding as _);
+                }
+            }
+        }
+
+        let status = get_program_iv(gl, name, gl::LINK_STATUS);
+        let log = get_program_log(gl, name);
+        if status != 0 {
+            if !log.is_empty() {
+                warn!("\tLog: {}", log);
+            }
+        } else {
+            return Err(pso::CreationError::Shader(d::ShaderError::CompilationFailed(log)));
+        }
+
+        let mut push_constants = Vec::new();
+        if !push_constant_offsets.is_empty() {
+            let num_uniforms = get_program_iv(gl, name, gl::ACTIVE_UNIFORMS);
+            let max_length = get_program_iv(gl, name, gl::ACTIVE_UNIFORM_MAX_LENGTH);
+            let mut uniform_name = vec![0u8; max_length as usize];
+            for index in 0 .. num_uniforms as gl::types::GLuint {
+                let (mut length, mut count, mut ty) = (0, 0, 0);
+                unsafe {
+                    gl.GetActiveUniform(
+                        name,
+                        index,
+                        max_length,
+                        &mut length,
+                        &mut count,
+                        &mut ty,
+                        uniform_name.as_mut_ptr() as *mut _,
+                    );
+                }
+                let full_name = ::std::str::from_utf8(&uniform_name[.. length as usize]).unwrap();
+                // Arrays are reported by their first element.
+                let member_name = if full_name.ends_with("[0]") {
+                    &full_name[.. full_name.len() - 3]
+                } else {
+                    full_name
                 };
+                if let Some(&offset) = push_constant_offsets.get(member_name) {
+                    if conv::uniform_shape(ty).is_none() {
+                        let msg = format!("Push constant {} has the unsupported type {:#x}", member_name, ty);
+                        return Err(pso::CreationError::Shader(d::ShaderError::CompilationFailed(msg)));
+                    }
+                    // The name is still nul-terminated from `GetActiveUniform`.
+                    let location = unsafe { gl.GetUniformLocation(name, uniform_name.as_ptr() as _) };
+                    push_constants.push(n::PushConstantUniform { location, offset, ty, count });
+                }
+            }
+        }
 
-                shader
+        if let (false, Some(cache), Some(key)) = (loaded, cache, key) {
+            let length = get_program_iv(gl, name, gl::PROGRAM_BINARY_LENGTH);
+            let mut data = vec![0u8; length as usize];
+            let mut format = 0;
+            let mut written = 0;
+            unsafe {
+                gl.GetProgramBinary(name, length, &mut written, &mut format, data.as_mut_ptr() as *mut _);
+            }
+            data.truncate(written as usize);
+            if !data.is_empty() {
+                cache.binaries.lock().unwrap().insert(key, n::ProgramBinary { format, data });
             }
         }
+
+        Ok((name, push_constants))
     }
 }
 
@@ -468,16 +825,24 @@ impl d::Device<B> for Device {
             subpasses
                 .into_iter()
                 .map(|subpass| {
+                    let subpass = subpass.borrow();
                     let color_attachments =
                         subpass
-                            .borrow()
                             .colors
                             .iter()
                             .map(|&(index, _)| index)
                             .collect();
+                    let input_attachments =
+                        subpass
+                            .inputs
+                            .iter()
+                            .map(|&(index, _)| index)
+                            .collect();
 
                     n::SubpassDesc {
                         color_attachments,
+                        depth_stencil: subpass.depth_stencil.map(|&(index, _)| index),
+                        input_attachments,
                     }
                 })
                 .collect();
@@ -524,14 +889,16 @@ impl d::Device<B> for Device {
                         UniformBuffer => {
                             drd.insert_missing_binding_into_spare(n::BindingTypes::UniformBuffers, set as _, binding.binding);
                         }
+                        InputAttachment => {
+                            // Sampled from the texture of the attachment.
+                            drd.insert_missing_binding_into_spare(n::BindingTypes::Images, set as _, binding.binding);
+                        }
                         StorageImage
                         | UniformTexelBuffer
                         | UniformBufferDynamic
                         | StorageTexelBuffer
                         | StorageBufferDynamic
-                        | StorageBuffer
-
-                        | InputAttachment => unimplemented!(), // 6
+                        | StorageBuffer => unimplemented!(), // 6
                     }
                 })
             });
@@ -541,27 +908,48 @@ impl d::Device<B> for Device {
         }
     }
 
-    fn create_pipeline_cache(&self) -> () {
-        ()
+    fn create_pipeline_cache(&self, data: Option<&[u8]>) -> n::PipelineCache {
+        let binaries = match data {
+            Some(data) => parse_pipeline_cache(data, self.driver_hash()).unwrap_or_else(|| {
+                warn!("Ignoring pipeline cache data from another driver");
+                FastHashMap::default()
+            }),
+            None => FastHashMap::default(),
+        };
+        n::PipelineCache {
+            binaries: Mutex::new(binaries),
+        }
+    }
+
+    fn get_pipeline_cache_data(&self, cache: &n::PipelineCache) -> Result<Vec<u8>, d::OutOfMemory> {
+        Ok(write_pipeline_cache(&cache.binaries.lock().unwrap(), self.driver_hash()))
     }
 
-    fn destroy_pipeline_cache(&self, _: ()) {
-        //empty
+    fn destroy_pipeline_cache(&self, _: n::PipelineCache) {
+        //drop
     }
 
-    fn merge_pipeline_caches<I>(&self, _: &(), _: I)
+    fn merge_pipeline_caches<I>(&self, target: &n::PipelineCache, sources: I)
     where
         I: IntoIterator,
-        I::Item: Borrow<()>,
+        I::Item: Borrow<n::PipelineCache>,
     {
-        //empty
+        let mut binaries = target.binaries.lock().unwrap();
+        for source in sources {
+            let source = source.borrow();
+            // Merging a cache into itself would deadlock.
+            if ptr::eq(source, target) {
+                continue;
+            }
+            for (&key, binary) in source.binaries.lock().unwrap().iter() {
+                binaries.entry(key).or_insert_with(|| binary.clone());
+            }
+        }
     }
 
     fn create_graphics_pipeline<'a>(
-        &self, desc: &pso::GraphicsPipelineDesc<'a, B>, _cache: Option<&()>,
+        &self, desc: &pso::GraphicsPipelineDesc<'a, B>, cache: Option<&n::PipelineCache>,
     ) -> Result<n::GraphicsPipeline, pso::CreationError> {
-        let gl = &self.share.context;
-        let share = &self.share;
         let desc = desc.borrow();
         let subpass = {
             let subpass = desc.subpass;
@@ -571,80 +959,19 @@ impl d::Device<B> for Device {
             }
         };
 
-        let program = {
-            let name = unsafe { gl.CreateProgram() };
-
-            // Attach shaders to program
-            let shaders = [
-                (pso::Stage::Vertex, Some(&desc.shaders.vertex)),
-                (pso::Stage::Hull, desc.shaders.hull.as_ref()),
-                (pso::Stage::Domain, desc.shaders.domain.as_ref()),
-                (pso::Stage::Geometry, desc.shaders.geometry.as_ref()),
-                (pso::Stage::Fragment, desc.shaders.fragment.as_ref()),
-            ];
-
-            let mut name_binding_map = FastHashMap::<String, pso::DescriptorBinding>::default();
-            let shader_names = &shaders
-                .iter()
-                .filter_map(|&(stage, point_maybe)| {
-                    point_maybe.map(|point| {
-                        let shader_name = self.compile_shader(
-                            point,
-                            stage,
-                            &mut desc.layout.desc_remap_data.write().unwrap(),
-                            &mut name_binding_map,
-                        );
-                        unsafe { gl.AttachShader(name, shader_name); }
-                        shader_name
-                    })
-                })
-                .collect::<Vec<_>>();
-
-            if !share.private_caps.program_interface && share.private_caps.frag_data_location {
-                for i in 0..subpass.color_attachments.len() {
-                    let color_name = format!("Target{}\0", i);
-                    unsafe {
-                        gl.BindFragDataLocation(name, i as u32, (&color_name[..]).as_ptr() as *mut gl::types::GLchar);
-                    }
-                }
-            }
-
-            unsafe { gl.LinkProgram(name) };
-            info!("\tLinked program {}", name);
-            if let Err(err) = share.check() {
-                panic!("Error linking program: {:?}", err);
-            }
-
-            for shader_name in shader_names {
-                unsafe {
-                    gl.DetachShader(name, *shader_name);
-                    gl.DeleteShader(*shader_name);
-                }
-            }
-
-            if !self.share.legacy_features.contains(LegacyFeatures::EXPLICIT_LAYOUTS_IN_SHADER) {
-                let gl = &self.share.context;
-                unsafe {
-                    gl.UseProgram(name);
-                    for (bname, binding) in name_binding_map.iter() {
-                        let loc = gl.GetUniformLocation(name, bname.as_ptr() as _);
-                        gl.Uniform1i(loc, *binding as _);
-                    }
-                }
-            }
-
-            let status = get_program_iv(gl, name, gl::LINK_STATUS);
-            let log = get_program_log(gl, name);
-            if status != 0 {
-                if !log.is_empty() {
-                    warn!("\tLog: {}", log);
-                }
-            } else {
-                return Err(pso::CreationError::Shader(d::ShaderError::CompilationFailed(log)));
-            }
-
-            name
-        };
+        let shaders = [
+            (pso::Stage::Vertex, Some(&desc.shaders.vertex)),
+            (pso::Stage::Hull, desc.shaders.hull.as_ref()),
+            (pso::Stage::Domain, desc.shaders.domain.as_ref()),
+            (pso::Stage::Geometry, desc.shaders.geometry.as_ref()),
+            (pso::Stage::Fragment, desc.shaders.fragment.as_ref()),
+        ];
+        let shaders = shaders
+            .iter()
+            .filter_map(|&(stage, point)| point.map(|point| (stage, point)))
+            .collect::<Vec<_>>();
+        let (program, push_constants) =
+            self.create_program(&shaders, desc.layout, subpass.color_attachments.len(), cache)?;
 
         let patch_size = match desc.input_assembler.primitive {
             c::Primitive::PatchList(size) => Some(size as _),
@@ -679,64 +1006,19 @@ impl d::Device<B> for Device {
                     }
                 })
                 .collect(),
+            push_constants,
         })
     }
 
     fn create_compute_pipeline<'a>(
-        &self, desc: &pso::ComputePipelineDesc<'a, B>, _cache: Option<&()>
+        &self, desc: &pso::ComputePipelineDesc<'a, B>, cache: Option<&n::PipelineCache>
     ) -> Result<n::ComputePipeline, pso::CreationError> {
-        let gl = &self.share.context;
-        let share = &self.share;
-
-        let program = {
-            let name = unsafe { gl.CreateProgram() };
-
-            let mut name_binding_map = FastHashMap::<String, pso::DescriptorBinding>::default();
-            let shader = self.compile_shader(
-                &desc.shader,
-                pso::Stage::Compute,
-                &mut desc.layout.desc_remap_data.write().unwrap(),
-                &mut name_binding_map,
-            );
-            unsafe { gl.AttachShader(name, shader) };
-
-            unsafe { gl.LinkProgram(name) };
-            info!("\tLinked program {}", name);
-            if let Err(err) = share.check() {
-                panic!("Error linking program: {:?}", err);
-            }
-
-            unsafe {
-                gl.DetachShader(name, shader);
-                gl.DeleteShader(shader);
-            }
-
-            if !self.share.legacy_features.contains(LegacyFeatures::EXPLICIT_LAYOUTS_IN_SHADER) {
-                let gl = &self.share.context;
-                unsafe {
-                    gl.UseProgram(name);
-                    for (bname, binding) in name_binding_map.iter() {
-                        let loc = gl.GetUniformLocation(name, bname.as_ptr() as _);
-                        gl.Uniform1i(loc, *binding as _);
-                    }
-                }
-            }
-
-            let status = get_program_iv(gl, name, gl::LINK_STATUS);
-            let log = get_program_log(gl, name);
-            if status != 0 {
-                if !log.is_empty() {
-                    warn!("\tLog: {}", log);
-                }
-            } else {
-                return Err(pso::CreationError::Other);
-            }
-
-            name
-        };
+        let (program, push_constants) =
+            self.create_program(&[(pso::Stage::Compute, &desc.shader)], desc.layout, 0, cache)?;
 
         Ok(n::ComputePipeline {
             program,
+            push_constants,
         })
     }
 
@@ -756,45 +1038,63 @@ impl d::Device<B> for Device {
 
         let gl = &self.share.context;
         let target = gl::DRAW_FRAMEBUFFER;
-        let mut name = 0;
-        unsafe {
-            gl.GenFramebuffers(1, &mut name);
-            gl.BindFramebuffer(target, name);
-        }
-
-        let att_points = [
-            gl::COLOR_ATTACHMENT0,
-            gl::COLOR_ATTACHMENT1,
-            gl::COLOR_ATTACHMENT2,
-            gl::COLOR_ATTACHMENT3,
-        ];
+        let attachments = attachments
+            .into_iter()
+            .map(|view| *view.borrow())
+            .collect::<Vec<_>>();
+        assert_eq!(attachments.len(), pass.attachments.len());
 
-        let mut attachments_len = 0;
-        //TODO: exclude depth/stencil attachments from here
-        for (&att_point, view) in att_points.iter().zip(attachments.into_iter()) {
-            attachments_len += 1;
+        let bind_target = |point, view: &n::ImageView| {
             if self.share.private_caps.framebuffer_texture {
-                Self::bind_target(gl, target, att_point, view.borrow());
+                Self::bind_target(gl, target, point, view);
             } else {
-                Self::bind_target_compat(gl, target, att_point, view.borrow());
+                Self::bind_target_compat(gl, target, point, view);
             }
-        }
-        assert_eq!(attachments_len, pass.attachments.len());
-        // attachments_len actually equals min(attachments.len(), att_points.len()) until the next assert
+        };
 
-        unsafe {
-            assert!(pass.attachments.len() <= att_points.len());
-            gl.DrawBuffers(attachments_len as _, att_points.as_ptr());
-            let _status = gl.CheckFramebufferStatus(target); //TODO: check status
-            gl.BindFramebuffer(target, 0);
-        }
+        // Create a framebuffer object per subpass, with the color attachments
+        // of the subpass bound to the draw buffers in order.
+        let subpasses = pass.subpasses
+            .iter()
+            .map(|subpass| {
+                let name = create_fbo_internal(gl);
+                unsafe { gl.BindFramebuffer(target, name) };
+
+                let draw_buffers = subpass.color_attachments
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &id)| {
+                        let point = gl::COLOR_ATTACHMENT0 + i as GLenum;
+                        bind_target(point, &attachments[id]);
+                        point
+                    })
+                    .collect::<Vec<_>>();
+
+                if let Some(id) = subpass.depth_stencil {
+                    let format = pass.attachments[id].format.unwrap();
+                    let point = match (format.is_depth(), format.is_stencil()) {
+                        (true, true) => gl::DEPTH_STENCIL_ATTACHMENT,
+                        (true, false) => gl::DEPTH_ATTACHMENT,
+                        (false, _) => gl::STENCIL_ATTACHMENT,
+                    };
+                    bind_target(point, &attachments[id]);
+                }
+
+                unsafe {
+                    gl.DrawBuffers(draw_buffers.len() as _, draw_buffers.as_ptr());
+                    let _status = gl.CheckFramebufferStatus(target); //TODO: check status
+                }
+                name
+            })
+            .collect();
+
+        unsafe { gl.BindFramebuffer(target, 0) };
         if let Err(err) = self.share.check() {
-            //TODO: attachments have been consumed
-            panic!("Error creating FBO: {:?} for {:?}"/* with attachments {:?}"*/,
-               err, pass/*, attachments*/);
+            panic!("Error creating FBO: {:?} for {:?} with attachments {:?}",
+               err, pass, attachments);
         }
 
-        Ok(name)
+        Ok(n::FrameBuffer { subpasses })
     }
 
     fn create_shader_module(
@@ -871,6 +1171,10 @@ impl d::Device<B> for Device {
             usage.contains(buffer::Usage::UNIFORM) {
             return Err(buffer::CreationError::UnsupportedUsage { usage });
         }
+        if !self.share.legacy_features.contains(LegacyFeatures::INDIRECT_EXECUTION) &&
+            usage.contains(buffer::Usage::INDIRECT) {
+            return Err(buffer::CreationError::UnsupportedUsage { usage });
+        }
 
         let target = if self.share.private_caps.buffer_role_change {
             gl::ARRAY_BUFFER
@@ -1050,17 +1354,25 @@ impl d::Device<B> for Device {
     ) -> Result<UnboundImage, i::CreationError> {
         let gl = &self.share.context;
 
-        let int_format = match format {
-            Format::Rgba8Unorm => gl::RGBA8,
-            Format::Rgba8Srgb => gl::SRGB8_ALPHA8,
-            _ => unimplemented!()
+        let supported = !self.share.format_properties[format as usize].optimal_tiling.is_empty();
+        let int_format = match conv::describe_format(format) {
+            Some((desc, _)) if supported => desc.tex_internal,
+            _ => return Err(i::CreationError::Format(format)),
         };
 
+        // Only single-sampled 2D images without layers are supported for now.
+        match kind {
+            i::Kind::D2(_, _, 1, 1) => {}
+            i::Kind::D2(_, _, 1, samples) => return Err(i::CreationError::Samples(samples)),
+            _ => return Err(i::CreationError::Kind),
+        }
+
         let channel = format.base_format().1;
 
         let image = if num_levels > 1 ||
             usage.contains(i::Usage::STORAGE) ||
-            usage.contains(i::Usage::SAMPLED)
+            usage.contains(i::Usage::SAMPLED) ||
+            usage.contains(i::Usage::INPUT_ATTACHMENT)
         {
             let mut name = 0;
             unsafe { gl.GenTextures(1, &mut name) };
@@ -1069,7 +1381,7 @@ impl d::Device<B> for Device {
                     gl.BindTexture(gl::TEXTURE_2D, name);
                     gl.TexStorage2D(gl::TEXTURE_2D, num_levels as _, int_format, w as _, h as _);
                 }
-                _ => unimplemented!(),
+                _ => unreachable!(),
             };
             n::ImageKind::Texture(name)
         } else {
@@ -1080,15 +1392,18 @@ impl d::Device<B> for Device {
                     gl.BindRenderbuffer(gl::RENDERBUFFER, name);
                     gl.RenderbufferStorage(gl::RENDERBUFFER, int_format, w as _, h as _);
                 }
-                _ => unimplemented!(),
+                _ => unreachable!(),
             };
             n::ImageKind::Surface(name)
         };
 
         let surface_desc = format.base_format().0.desc();
-        let bytes_per_texel  = surface_desc.bits / 8;
+        let bytes_per_block = surface_desc.bits / 8;
+        let (block_width, block_height) = surface_desc.dim;
         let ext = kind.extent();
-        let size = (ext.width * ext.height * ext.depth) as u64 * bytes_per_texel as u64;
+        let blocks_x = (ext.width + block_width as u32 - 1) / block_width as u32;
+        let blocks_y = (ext.height + block_height as u32 - 1) / block_height as u32;
+        let size = (blocks_x * blocks_y * ext.depth) as u64 * bytes_per_block as u64;
 
         if let Err(err) = self.share.check() {
             panic!("Error creating image: {:?} for kind {:?} of {:?}",
@@ -1097,6 +1412,7 @@ impl d::Device<B> for Device {
 
         Ok(UnboundImage {
             image,
+            format,
             channel,
             requirements: memory::Requirements {
                 size,
@@ -1121,6 +1437,7 @@ impl d::Device<B> for Device {
     ) -> Result<n::Image, d::BindError> {
         Ok(n::Image {
             kind: unbound.image,
+            format: unbound.format,
             channel: unbound.channel,
         })
     }
@@ -1316,16 +1633,134 @@ impl d::Device<B> for Device {
         unimplemented!()
     }
 
+    fn create_event(&self) -> n::Event {
+        n::Event::new()
+    }
+
+    fn get_event_status(&self, event: &n::Event) -> bool {
+        match *event.0.lock().unwrap() {
+            n::EventState::Unsignaled => false,
+            n::EventState::Host => true,
+            n::EventState::Device(sync) => {
+                let gl = &self.share.context;
+                match unsafe { gl.ClientWaitSync(sync, gl::SYNC_FLUSH_COMMANDS_BIT, 0) } {
+                    gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => true,
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    fn set_event(&self, event: &n::Event) {
+        event.set_state(&self.share.context, n::EventState::Host);
+    }
+
+    fn reset_event(&self, event: &n::Event) {
+        event.set_state(&self.share.context, n::EventState::Unsignaled);
+    }
+
     fn free_memory(&self, _memory: n::Memory) {
         // Nothing to do
     }
 
-    fn create_query_pool(&self, _ty: query::QueryType, _count: query::QueryId) -> () {
-        unimplemented!()
+    fn create_query_pool(
+        &self, ty: query::QueryType, count: query::QueryId
+    ) -> Result<n::QueryPool, query::Error> {
+        let gl = &self.share.context;
+        let target = match ty {
+            // GLES only supports boolean occlusion queries.
+            query::QueryType::Occlusion if self.share.info.version.is_embedded => gl::ANY_SAMPLES_PASSED,
+            query::QueryType::Occlusion => gl::SAMPLES_PASSED,
+            query::QueryType::Timestamp if self.share.private_caps.timer_query => gl::TIMESTAMP,
+            query::QueryType::Timestamp |
+            query::QueryType::PipelineStatistics(_) => return Err(query::Error::Unsupported(ty)),
+        };
+
+        let mut queries = vec![0; count as usize];
+        unsafe {
+            gl.GenQueries(count as _, queries.as_mut_ptr());
+        }
+        match self.share.check() {
+            Ok(()) => {}
+            Err(Error::OutOfMemory) => return Err(query::Error::OutOfDeviceMemory),
+            Err(err) => panic!("Error creating query pool: {:?}", err),
+        }
+
+        Ok(n::QueryPool {
+            target,
+            queries,
+        })
     }
 
-    fn destroy_query_pool(&self, _: ()) {
-        unimplemented!()
+    fn destroy_query_pool(&self, pool: n::QueryPool) {
+        unsafe {
+            self.share.context.DeleteQueries(pool.queries.len() as _, pool.queries.as_ptr());
+        }
+    }
+
+    fn get_query_pool_results(
+        &self,
+        pool: &n::QueryPool,
+        queries: Range<query::QueryId>,
+        data: &mut [u8],
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) -> Result<bool, error::HostExecutionError> {
+        let gl = &self.share.context;
+        let mut ready = true;
+
+        for (i, id) in queries.enumerate() {
+            let query = pool.queries[id as usize];
+            // The query object only exists once it has been issued by a submission,
+            // until then its result is unavailable.
+            let issued = unsafe { gl.IsQuery(query) == gl::TRUE };
+            if !issued && flags.contains(query::ResultFlags::WAIT) {
+                // Submissions are executed synchronously, the query would never become available.
+                return Err(error::HostExecutionError::DeviceLost);
+            }
+            let available = issued &&
+                (flags.contains(query::ResultFlags::WAIT) || {
+                    let mut available = 0;
+                    unsafe { gl.GetQueryObjectuiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available) };
+                    available != 0
+                });
+            ready &= available;
+
+            let value = if available {
+                // Blocks until the result is available.
+                if self.share.private_caps.timer_query {
+                    let mut value = 0;
+                    unsafe { gl.GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut value) };
+                    Some(value)
+                } else {
+                    let mut value = 0;
+                    unsafe { gl.GetQueryObjectuiv(query, gl::QUERY_RESULT, &mut value) };
+                    Some(value as u64)
+                }
+            } else if flags.contains(query::ResultFlags::PARTIAL) {
+                // GL doesn't expose intermediate results, zero is a valid partial result.
+                Some(0)
+            } else {
+                None
+            };
+
+            let data = &mut data[i * stride as usize ..];
+            let size = if let Some(value) = value {
+                write_query_value(data, value, flags)
+            } else if flags.contains(query::ResultFlags::BITS_64) {
+                8
+            } else {
+                4
+            };
+            if flags.contains(query::ResultFlags::WITH_AVAILABILITY) {
+                write_query_value(&mut data[size ..], available as u64, flags);
+            }
+        }
+
+        if let Err(err) = self.share.check() {
+            panic!("Error getting query pool results: {:?}", err);
+        }
+        Ok(ready)
     }
 
     fn destroy_shader_module(&self, _: n::ShaderModule) {
@@ -1354,7 +1789,11 @@ impl d::Device<B> for Device {
 
     fn destroy_framebuffer(&self, frame_buffer: n::FrameBuffer) {
         let gl = &self.share.context;
-        unsafe { gl.DeleteFramebuffers(1, &frame_buffer); }
+        for name in frame_buffer.subpasses {
+            if name != n::DEFAULT_FRAMEBUFFER {
+                unsafe { gl.DeleteFramebuffers(1, &name); }
+            }
+        }
     }
 
     fn destroy_buffer(&self, buffer: n::Buffer) {
@@ -1374,6 +1813,29 @@ impl d::Device<B> for Device {
         }
     }
 
+    fn create_sparse_buffer(
+        &self, _size: u64, _usage: buffer::Usage, _sparse: memory::SparseFlags,
+    ) -> Result<n::Buffer, buffer::CreationError> {
+        unimplemented!()
+    }
+
+    fn get_sparse_buffer_requirements(&self, _buffer: &n::Buffer) -> memory::Requirements {
+        unimplemented!()
+    }
+
+    fn create_sparse_image(
+        &self, _kind: i::Kind, _mip_levels: i::Level, _format: Format,
+        _usage: i::Usage, _storage_flags: i::StorageFlags, _sparse: memory::SparseFlags,
+    ) -> Result<n::Image, i::CreationError> {
+        unimplemented!()
+    }
+
+    fn get_sparse_image_requirements(
+        &self, _image: &n::Image,
+    ) -> (memory::Requirements, Vec<i::SparseRequirements>) {
+        unimplemented!()
+    }
+
     fn destroy_image_view(&self, _image_view: n::ImageView) {
         // Nothing to do
     }
@@ -1400,6 +1862,10 @@ impl d::Device<B> for Device {
         }
     }
 
+    fn destroy_event(&self, event: n::Event) {
+        event.set_state(&self.share.context, n::EventState::Unsignaled);
+    }
+
     fn destroy_semaphore(&self, _: n::Semaphore) {
         // Nothing to do
     }
@@ -1430,3 +1896,60 @@ pub fn wait_fence(fence: &n::Fence, gl: &gl::Gl, timeout_ns: u64) -> GLenum {
     // could be cached to avoid calling this more than once (in core or in the backend ?).
     unsafe { gl.ClientWaitSync(fence.0.get(), gl::SYNC_FLUSH_COMMANDS_BIT, timeout_ns) }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{fnv_hash, parse_pipeline_cache, write_pipeline_cache, FNV_OFFSET_BASIS};
+    use hal::backend::FastHashMap;
+    use native as n;
+
+    #[test]
+    fn test_fnv_hash() {
+        assert_eq!(fnv_hash(FNV_OFFSET_BASIS, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv_hash(FNV_OFFSET_BASIS, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv_hash(FNV_OFFSET_BASIS, b"foobar"), 0x8594_4171_f739_67e8);
+        assert_eq!(fnv_hash(fnv_hash(FNV_OFFSET_BASIS, b"foo"), b"bar"), fnv_hash(FNV_OFFSET_BASIS, b"foobar"));
+    }
+
+    fn binaries() -> FastHashMap<u64, n::ProgramBinary> {
+        let mut binaries = FastHashMap::default();
+        binaries.insert(1, n::ProgramBinary { format: 0x8741, data: vec![1, 2, 3] });
+        binaries.insert(!0, n::ProgramBinary { format: 0x8742, data: vec![] });
+        binaries
+    }
+
+    #[test]
+    fn test_pipeline_cache_round_trip() {
+        let data = write_pipeline_cache(&binaries(), 0x1234_5678_9abc_def0);
+        let parsed = parse_pipeline_cache(&data, 0x1234_5678_9abc_def0).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[&1].format, 0x8741);
+        assert_eq!(parsed[&1].data, vec![1, 2, 3]);
+        assert_eq!(parsed[&!0].format, 0x8742);
+        assert!(parsed[&!0].data.is_empty());
+
+        let empty = write_pipeline_cache(&FastHashMap::default(), 5);
+        assert_eq!(empty.len(), 12);
+        assert!(parse_pipeline_cache(&empty, 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pipeline_cache_invalid() {
+        let mut binaries = binaries();
+        binaries.remove(&!0);
+        let data = write_pipeline_cache(&binaries, 5);
+        // bad magic
+        let mut magic = data.clone();
+        magic[0] = b'V';
+        assert!(parse_pipeline_cache(&magic, 5).is_none());
+        assert!(parse_pipeline_cache(&[], 5).is_none());
+        // written by another driver or version
+        assert!(parse_pipeline_cache(&data, 6).is_none());
+        // truncated anywhere
+        for length in 0 .. data.len() {
+            if length != 12 {
+                assert!(parse_pipeline_cache(&data[.. length], 5).is_none(), "length {}", length);
+            }
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use std::collections::HashSet;
 use std::{ffi, fmt, mem, str};
 use gl;
-use hal::{Features, Limits};
+use hal::{format, Features, Limits};
+
+use conv::{self, FormatFamily};
 
 /// A version number for a specific component of an OpenGL implementation
 #[derive(Copy, Clone, Eq, Ord, PartialEq, PartialOrd)]
@@ -177,6 +179,14 @@ pub struct PrivateCaps {
     pub map: bool,
     /// Indicates if we only have support via the EXT.
     pub sampler_anisotropy_ext: bool,
+    /// Timestamp queries and 64-bit query results
+    pub timer_query: bool,
+    /// Can write query results into buffers
+    pub query_buffer: bool,
+    /// Can retrieve and load program binaries
+    pub program_binary: bool,
+    /// Can discard the contents of framebuffer attachments
+    pub framebuffer_invalidate: bool,
 }
 
 /// OpenGL implementation information
@@ -365,6 +375,19 @@ pub fn query_all(gl: &gl::Gl) -> (Info, Features, LegacyFeatures, Limits, Privat
     if info.is_supported(&[Core(4, 3), Es(3, 1)]) { // TODO: extension
         legacy |= LegacyFeatures::INDIRECT_EXECUTION;
     }
+    if info.is_supported(&[
+        Core(4, 3),
+        Ext("GL_ARB_multi_draw_indirect"),
+    ]) {
+        features |= Features::MULTI_DRAW_INDIRECT;
+    }
+    if info.is_supported(&[
+        Core(4, 2),
+        Ext("GL_ARB_base_instance"),
+    ]) && legacy.contains(LegacyFeatures::INDIRECT_EXECUTION) {
+        // The `baseInstance` member of indirect commands is reserved without it.
+        features |= Features::DRAW_INDIRECT_FIRST_INSTANCE;
+    }
     if info.is_supported(&[
         Core(3, 1),
         Es(3, 0),
@@ -460,11 +483,146 @@ pub fn query_all(gl: &gl::Gl) -> (Info, Features, LegacyFeatures, Limits, Privat
         sampler_anisotropy_ext:             !info.is_supported(&[Core(4,6),
                                                                 Ext ("GL_ARB_texture_filter_anisotropic")]) &&
                                             info.is_supported(&[Ext ("GL_EXT_texture_filter_anisotropic")]),
+        timer_query:                        info.is_supported(&[Core(3,3),
+                                                                Ext ("GL_ARB_timer_query")]),
+        query_buffer:                       info.is_supported(&[Core(4,4),
+                                                                Ext ("GL_ARB_query_buffer_object")]),
+        program_binary:                     info.is_supported(&[Core(4,1),
+                                                                Es  (3,0),
+                                                                Ext ("GL_ARB_get_program_binary")]),
+        framebuffer_invalidate:             info.is_supported(&[Core(4,3),
+                                                                Es  (3,0),
+                                                                Ext ("GL_ARB_invalidate_subdata")]),
     };
 
     (info, features, legacy, limits, private)
 }
 
+fn is_format_supported(info: &Info, format: format::Format, family: FormatFamily) -> bool {
+    use self::Requirement::*;
+    use hal::format::Format::*;
+    // A few formats of the common families were introduced later on desktop GL.
+    match format {
+        R5g6b5Unorm | B5g6r5Unorm
+            if !info.is_supported(&[Core(4,1), Es(3,0), Ext("GL_ARB_ES2_compatibility")]) => return false,
+        A2r10g10b10Uint | A2b10g10r10Uint
+            if !info.is_supported(&[Core(3,3), Es(3,0), Ext("GL_ARB_texture_rgb10_a2ui")]) => return false,
+        _ => {}
+    }
+    match family {
+        FormatFamily::Color |
+        FormatFamily::DepthStencil => info.is_supported(&[Core(3,0), Es(3,0)]),
+        FormatFamily::Bgra => info.is_supported(&[Core(3,0)]),
+        FormatFamily::Snorm => info.is_supported(&[Core(3,1), Es(3,0)]),
+        FormatFamily::Norm16 => info.is_supported(&[Core(3,1), Ext("GL_EXT_texture_norm16")]),
+        FormatFamily::Stencil => info.is_supported(&[Core(4,4),
+                                                     Es(3,2),
+                                                     Ext("GL_ARB_texture_stencil8"),
+                                                     Ext("GL_OES_texture_stencil8")]),
+        FormatFamily::S3tc => info.is_supported(&[Ext("GL_EXT_texture_compression_s3tc")]),
+        FormatFamily::S3tcSrgb => info.is_supported(&[Ext("GL_EXT_texture_compression_s3tc")]) &&
+                                  info.is_supported(&[Ext("GL_EXT_texture_sRGB"),
+                                                      Ext("GL_EXT_texture_compression_s3tc_srgb")]),
+        FormatFamily::Rgtc => info.is_supported(&[Core(3,0),
+                                                  Ext("GL_ARB_texture_compression_rgtc"),
+                                                  Ext("GL_EXT_texture_compression_rgtc")]),
+        FormatFamily::Bptc => info.is_supported(&[Core(4,2),
+                                                  Ext("GL_ARB_texture_compression_bptc"),
+                                                  Ext("GL_EXT_texture_compression_bptc")]),
+        FormatFamily::Etc2 => info.is_supported(&[Core(4,3),
+                                                  Es(3,0),
+                                                  Ext("GL_ARB_ES3_compatibility")]),
+        FormatFamily::Astc => info.is_supported(&[Es(3,2),
+                                                  Ext("GL_KHR_texture_compression_astc_ldr")]),
+    }
+}
+
+fn is_color_renderable(info: &Info, format: format::Format, family: FormatFamily) -> bool {
+    use self::Requirement::*;
+    use hal::format::{ChannelType, SurfaceType};
+    let base = format.base_format();
+    match base.0 {
+        SurfaceType::R8_G8_B8 | SurfaceType::B8_G8_R8 |
+        SurfaceType::R16_G16_B16 | SurfaceType::R32_G32_B32 |
+        SurfaceType::E5_B9_G9_R9 => return false,
+        _ => {}
+    }
+    match family {
+        FormatFamily::Color | FormatFamily::Bgra => {
+            match base.1 {
+                // Float color buffers are optional on GLES.
+                ChannelType::Float | ChannelType::Ufloat => info.is_supported(&[Core(3,0),
+                                                                                Ext("GL_EXT_color_buffer_float")]),
+                _ => true,
+            }
+        }
+        FormatFamily::Norm16 => base.1 == ChannelType::Unorm,
+        _ => false,
+    }
+}
+
+/// Derive the capabilities of every format from the format table and the
+/// supported versions and extensions, indexed by `Format as usize`.
+pub fn query_format_properties(info: &Info) -> [format::Properties; format::NUM_FORMATS] {
+    use self::Requirement::*;
+    use hal::format::{BufferFeature, ChannelType, ImageFeature};
+    use std::mem;
+
+    let mut format_properties = [format::Properties::default(); format::NUM_FORMATS];
+    for (i, props) in format_properties.iter_mut().enumerate().skip(1) {
+        let format: format::Format = unsafe { mem::transmute(i as u32) };
+
+        if conv::format_to_gl_format(format).is_some() {
+            // Texel buffers aren't implemented yet.
+            props.buffer_features |= BufferFeature::VERTEX;
+        }
+
+        let (desc, family) = match conv::describe_format(format) {
+            Some(desc) => desc,
+            None => continue,
+        };
+        if !is_format_supported(info, format, family) {
+            continue;
+        }
+
+        let channel = format.base_format().1;
+        let is_integer = channel == ChannelType::Uint || channel == ChannelType::Int;
+        let is_float32 = match format {
+            format::Format::R32Float | format::Format::Rg32Float |
+            format::Format::Rgb32Float | format::Format::Rgba32Float => true,
+            _ => false,
+        };
+
+        let mut features = ImageFeature::SAMPLED | ImageFeature::BLIT_SRC;
+        let filterable = match family {
+            FormatFamily::Stencil => false,
+            FormatFamily::DepthStencil => !info.version.is_embedded,
+            _ => !is_integer && (!is_float32 || info.is_supported(&[Core(3,0),
+                                                                       Ext("GL_OES_texture_float_linear")])),
+        };
+        if filterable {
+            features |= ImageFeature::SAMPLED_LINEAR;
+        }
+        if family == FormatFamily::DepthStencil || family == FormatFamily::Stencil {
+            features |= ImageFeature::DEPTH_STENCIL_ATTACHMENT | ImageFeature::BLIT_DST;
+        } else if is_color_renderable(info, format, family) {
+            features |= ImageFeature::COLOR_ATTACHMENT | ImageFeature::BLIT_DST;
+            if !is_integer && (!is_float32 || info.is_supported(&[Core(3,0), Ext("GL_EXT_float_blend")])) {
+                features |= ImageFeature::COLOR_ATTACHMENT_BLEND;
+            }
+        }
+
+        props.optimal_tiling = features;
+        // Images are opaque in GL, linear tiling only restricts the formats
+        // to the ones with a texel layout.
+        if !desc.is_compressed() {
+            props.linear_tiling = features;
+        }
+    }
+
+    format_properties
+}
+
 #[cfg(test)]
 mod tests {
     use super::Version;
@@ -38,6 +38,8 @@ mod window;
 
 #[cfg(feature = "glutin")]
 pub use window::glutin::{config_context, Headless, Surface, Swapchain};
+#[cfg(feature = "egl")]
+pub use window::egl::{EglError, EglHeadless};
 
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Backend {}
@@ -70,14 +72,15 @@ impl hal::Backend for Backend {
     type ComputePipeline = native::ComputePipeline;
     type GraphicsPipeline = native::GraphicsPipeline;
     type PipelineLayout = native::PipelineLayout;
-    type PipelineCache = ();
+    type PipelineCache = native::PipelineCache;
     type DescriptorSetLayout = native::DescriptorSetLayout;
     type DescriptorPool = native::DescriptorPool;
     type DescriptorSet = native::DescriptorSet;
 
     type Fence = native::Fence;
     type Semaphore = native::Semaphore;
-    type QueryPool = ();
+    type Event = native::Event;
+    type QueryPool = native::QueryPool;
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -113,6 +116,7 @@ struct Share {
     legacy_features: info::LegacyFeatures,
     limits: hal::Limits,
     private_caps: info::PrivateCaps,
+    format_properties: [hal::format::Properties; hal::format::NUM_FORMATS],
     // Indicates if there is an active logical device.
     open: Cell<bool>,
 }
@@ -195,6 +199,7 @@ impl PhysicalDevice {
             debug!("- {}", *extension);
         }
         let name = info.platform_name.renderer.into();
+        let format_properties = info::query_format_properties(&info);
 
         // create the shared context
         let share = Share {
@@ -204,6 +209,7 @@ impl PhysicalDevice {
             legacy_features,
             limits,
             private_caps,
+            format_properties,
             open: Cell::new(false),
         };
         if let Err(err) = share.check() {
@@ -230,8 +236,12 @@ impl PhysicalDevice {
 
 impl hal::PhysicalDevice<Backend> for PhysicalDevice {
     fn open(
-        &self, families: &[(&QueueFamily, &[hal::QueuePriority])],
+        &self, families: &[(&QueueFamily, &[hal::QueuePriority])], requested_features: hal::Features,
     ) -> Result<hal::Gpu<Backend>, error::DeviceCreationError> {
+        if !self.features().contains(requested_features) {
+            return Err(error::DeviceCreationError::MissingFeature);
+        }
+
         // Can't have multiple logical devices at the same time
         // as they would share the same context.
         if self.0.open.get() {
@@ -282,15 +292,62 @@ impl hal::PhysicalDevice<Backend> for PhysicalDevice {
         })
     }
 
-    fn format_properties(&self, _: Option<hal::format::Format>) -> hal::format::Properties {
-        unimplemented!()
+    fn format_properties(&self, format: Option<hal::format::Format>) -> hal::format::Properties {
+        let idx = format.map(|format| format as usize).unwrap_or(0);
+        self.0.format_properties[idx]
     }
 
     fn image_format_properties(
-        &self, _format: hal::format::Format, _dimensions: u8, _tiling: image::Tiling,
-        _usage: image::Usage, _storage_flags: image::StorageFlags,
+        &self, format: hal::format::Format, dimensions: u8, tiling: image::Tiling,
+        usage: image::Usage, storage_flags: image::StorageFlags,
     ) -> Option<image::FormatProperties> {
-        None //TODO
+        use hal::format::ImageFeature;
+
+        let format_props = &self.0.format_properties[format as usize];
+        let props = match tiling {
+            image::Tiling::Optimal => format_props.optimal_tiling,
+            image::Tiling::Linear => format_props.linear_tiling,
+        };
+        let supported_usage = {
+            use hal::image::Usage as U;
+            let mut flags = U::empty();
+            if props.contains(ImageFeature::SAMPLED) {
+                // Copies into images are pixel transfers, available to
+                // every format which can be sampled.
+                flags |= U::SAMPLED | U::TRANSFER_DST;
+            }
+            if props.contains(ImageFeature::BLIT_SRC) {
+                flags |= U::TRANSFER_SRC;
+            }
+            if props.contains(ImageFeature::COLOR_ATTACHMENT) {
+                flags |= U::COLOR_ATTACHMENT | U::INPUT_ATTACHMENT;
+            }
+            if props.contains(ImageFeature::DEPTH_STENCIL_ATTACHMENT) {
+                flags |= U::DEPTH_STENCIL_ATTACHMENT | U::INPUT_ATTACHMENT;
+            }
+            flags
+        };
+        if props.is_empty() || !supported_usage.contains(usage) {
+            return None;
+        }
+
+        // Only single-sampled 2D images without layers can be created for now.
+        if dimensions != 2 || storage_flags.contains(image::StorageFlags::CUBE_VIEW) {
+            return None;
+        }
+        let max_size = self.0.limits.max_texture_size as image::Size;
+        let num_levels = 32 - max_size.leading_zeros();
+
+        Some(image::FormatProperties {
+            max_extent: image::Extent { width: max_size, height: max_size, depth: 1 },
+            max_levels: match tiling {
+                image::Tiling::Optimal => num_levels as _,
+                image::Tiling::Linear => 1,
+            },
+            max_layers: 1,
+            sample_count_mask: 0x1,
+            max_resource_size: !0,
+        })
     }
 
     fn memory_properties(&self) -> hal::MemoryProperties {
@@ -335,6 +392,11 @@ impl hal::PhysicalDevice<Backend> for PhysicalDevice {
     fn limits(&self) -> hal::Limits {
         self.0.limits
     }
+
+    fn timestamp_period(&self) -> f32 {
+        // Timestamps are expressed in nanoseconds.
+        1.0
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -12,14 +12,40 @@ use Backend;
 pub type RawBuffer   = gl::types::GLuint;
 pub type Shader      = gl::types::GLuint;
 pub type Program     = gl::types::GLuint;
-pub type FrameBuffer = gl::types::GLuint;
+pub type RawFrameBuffer = gl::types::GLuint;
 pub type Surface     = gl::types::GLuint;
 pub type Texture     = gl::types::GLuint;
 pub type Sampler     = gl::types::GLuint;
 
 pub type DescriptorSetLayout = Vec<pso::DescriptorSetLayoutBinding>;
 
-pub const DEFAULT_FRAMEBUFFER: FrameBuffer = 0;
+pub const DEFAULT_FRAMEBUFFER: RawFrameBuffer = 0;
+
+/// Framebuffer, made of one framebuffer object per subpass of the render
+/// pass it has been created for. Each only has the attachments rendered to
+/// by its subpass, so that input attachments can be sampled from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FrameBuffer {
+    pub(crate) subpasses: Vec<RawFrameBuffer>,
+}
+
+impl FrameBuffer {
+    /// The framebuffer provided by the window system, shared by all subpasses.
+    pub(crate) fn default_framebuffer() -> Self {
+        FrameBuffer {
+            subpasses: vec![DEFAULT_FRAMEBUFFER],
+        }
+    }
+
+    /// The framebuffer object to bind for a subpass.
+    pub(crate) fn subpass(&self, index: pass::SubpassId) -> RawFrameBuffer {
+        if self.subpasses.len() == 1 {
+            self.subpasses[0]
+        } else {
+            self.subpasses[index]
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Buffer {
@@ -31,6 +57,13 @@ pub struct Buffer {
 #[derive(Debug)]
 pub struct BufferView;
 
+#[derive(Debug)]
+pub struct QueryPool {
+    /// Target the queries are issued for, `TIMESTAMP` for timestamp queries.
+    pub(crate) target: gl::types::GLenum,
+    pub(crate) queries: Vec<gl::types::GLuint>,
+}
+
 #[derive(Debug)]
 pub struct Fence(pub(crate) Cell<gl::types::GLsync>);
 unsafe impl Send for Fence {}
@@ -42,6 +75,37 @@ impl Fence {
     }
 }
 
+/// State of an event, emulated with fence syncs.
+#[derive(Debug)]
+pub enum EventState {
+    Unsignaled,
+    /// Signaled on the host, or by a command when syncs aren't supported.
+    Host,
+    /// Signaled once the commands preceding the sync are completed.
+    Device(gl::types::GLsync),
+}
+
+/// Event, shared with the commands setting and waiting on it.
+#[derive(Clone, Debug)]
+pub struct Event(pub(crate) Arc<Mutex<EventState>>);
+unsafe impl Send for Event {}
+unsafe impl Sync for Event {}
+
+impl Event {
+    pub(crate) fn new() -> Self {
+        Event(Arc::new(Mutex::new(EventState::Unsignaled)))
+    }
+
+    /// Replace the state of the event, deleting its previous sync.
+    pub(crate) fn set_state(&self, gl: &gl::Gl, state: EventState) {
+        let mut current = self.0.lock().unwrap();
+        if let EventState::Device(sync) = *current {
+            unsafe { gl.DeleteSync(sync) };
+        }
+        *current = state;
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum BindingTypes {
     Images,
@@ -108,6 +172,17 @@ impl DescRemapData {
     }
 }
 
+/// Uniform of a program emulating a member of a push constant block.
+#[derive(Clone, Copy, Debug)]
+pub struct PushConstantUniform {
+    pub(crate) location: gl::types::GLint,
+    /// Offset of the member in the push constants, in bytes.
+    pub(crate) offset: u32,
+    pub(crate) ty: gl::types::GLenum,
+    /// Number of array elements.
+    pub(crate) count: gl::types::GLsizei,
+}
+
 #[derive(Clone, Debug)]
 pub struct GraphicsPipeline {
     pub(crate) program: Program,
@@ -116,16 +191,20 @@ pub struct GraphicsPipeline {
     pub(crate) blend_targets: Vec<pso::ColorBlendDesc>,
     pub(crate) attributes: Vec<AttributeDesc>,
     pub(crate) vertex_buffers: Vec<Option<pso::VertexBufferDesc>>,
+    pub(crate) push_constants: Vec<PushConstantUniform>,
 }
 
 #[derive(Clone, Debug)]
 pub struct ComputePipeline {
     pub(crate) program: Program,
+    pub(crate) push_constants: Vec<PushConstantUniform>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Image {
     pub(crate) kind: ImageKind,
+    // Required for pixel transfers
+    pub(crate) format: format::Format,
     // Required for clearing operations
     pub(crate) channel: format::ChannelType,
 }
@@ -240,12 +319,16 @@ pub struct RenderPass {
 #[derive(Clone, Debug)]
 pub struct SubpassDesc {
     pub(crate) color_attachments: Vec<usize>,
+    pub(crate) depth_stencil: Option<usize>,
+    pub(crate) input_attachments: Vec<usize>,
 }
 
 impl SubpassDesc {
     /// Check if an attachment is used by this sub-pass.
     pub(crate) fn is_using(&self, at_id: pass::AttachmentId) -> bool {
         self.color_attachments.iter()
+            .chain(self.depth_stencil.iter())
+            .chain(self.input_attachments.iter())
             .any(|id| *id == at_id)
     }
 }
@@ -255,6 +338,20 @@ pub struct PipelineLayout {
     pub(crate) desc_remap_data: Arc<RwLock<DescRemapData>>,
 }
 
+/// Binary of a linked program, as returned by `glGetProgramBinary`.
+#[derive(Clone, Debug)]
+pub struct ProgramBinary {
+    pub(crate) format: gl::types::GLenum,
+    pub(crate) data: Vec<u8>,
+}
+
+/// Program binaries keyed by the hash of the shader sources they have
+/// been linked from, which depend on both the shaders and the layout.
+#[derive(Debug, Default)]
+pub struct PipelineCache {
+    pub(crate) binaries: Mutex<FastHashMap<u64, ProgramBinary>>,
+}
+
 #[derive(Debug)]
 // No inter-queue synchronization required for GL.
 pub struct Semaphore;
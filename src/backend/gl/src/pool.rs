@@ -54,7 +54,7 @@ pub enum BufferMemory {
 
 
 pub struct RawCommandPool {
-    pub(crate) fbo: n::FrameBuffer,
+    pub(crate) fbo: n::RawFrameBuffer,
     pub(crate) limits: command::Limits,
     pub(crate) memory: Arc<Mutex<BufferMemory>>,
 }
@@ -80,7 +80,9 @@ impl pool::RawCommandPool<Backend> for RawCommandPool {
 
     fn allocate(
         &mut self, num: usize, _level: hal::command::RawLevel
-    ) -> Vec<RawCommandBuffer> { // TODO: Implement secondary buffers
+    ) -> Vec<RawCommandBuffer> {
+        // Primary and secondary command buffers share the same representation,
+        // secondary ones are spliced into the primary on submission.
         (0..num).map(|_|
                 RawCommandBuffer::new(
                     self.fbo,
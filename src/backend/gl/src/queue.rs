@@ -1,14 +1,17 @@
-use std::{mem, ptr, slice};
+use std::{mem, slice};
 use std::borrow::Borrow;
+use std::sync::{Arc, Mutex};
 use Starc;
 
 use hal;
 use hal::error;
+use hal::query::ResultFlags;
 
 use gl;
 use smallvec::SmallVec;
 
-use {command as com, native, state, window};
+use {command as com, conv, native, state, window};
+use pool::BufferMemory;
 use info::LegacyFeatures;
 use {Backend, Share};
 
@@ -174,6 +177,56 @@ impl CommandQueue {
         unsafe { gl.FramebufferTexture(point, attachment, 0, 0) };
     }
 
+    fn set_push_constant(&self, uniform: &native::PushConstantUniform, data: &[u8]) {
+        let gl = &self.share.context;
+        // Checked when creating the pipeline.
+        let (columns, rows) = conv::uniform_shape(uniform.ty).unwrap();
+        // Push constants use the std430 layout, where vectors and matrix
+        // columns with 3 components are aligned like 4 component ones.
+        // Uniforms take them tightly packed instead.
+        let stride = 4 * if rows == 3 { 4 } else { rows };
+        let vectors = columns * uniform.count as usize;
+
+        let start = uniform.offset as usize;
+        let end = start + (vectors - 1) * stride + 4 * rows;
+        if data.len() < end {
+            // Not pushed yet.
+            return;
+        }
+        let packed = (0 .. vectors)
+            .flat_map(|i| &data[start + i * stride .. start + i * stride + 4 * rows])
+            .cloned()
+            .collect::<Vec<u8>>();
+        let (location, count) = (uniform.location, uniform.count);
+        let ptr = packed.as_ptr();
+        unsafe {
+            match uniform.ty {
+                gl::FLOAT => gl.Uniform1fv(location, count, ptr as *const _),
+                gl::FLOAT_VEC2 => gl.Uniform2fv(location, count, ptr as *const _),
+                gl::FLOAT_VEC3 => gl.Uniform3fv(location, count, ptr as *const _),
+                gl::FLOAT_VEC4 => gl.Uniform4fv(location, count, ptr as *const _),
+                gl::INT => gl.Uniform1iv(location, count, ptr as *const _),
+                gl::INT_VEC2 => gl.Uniform2iv(location, count, ptr as *const _),
+                gl::INT_VEC3 => gl.Uniform3iv(location, count, ptr as *const _),
+                gl::INT_VEC4 => gl.Uniform4iv(location, count, ptr as *const _),
+                gl::UNSIGNED_INT => gl.Uniform1uiv(location, count, ptr as *const _),
+                gl::UNSIGNED_INT_VEC2 => gl.Uniform2uiv(location, count, ptr as *const _),
+                gl::UNSIGNED_INT_VEC3 => gl.Uniform3uiv(location, count, ptr as *const _),
+                gl::UNSIGNED_INT_VEC4 => gl.Uniform4uiv(location, count, ptr as *const _),
+                gl::FLOAT_MAT2 => gl.UniformMatrix2fv(location, count, gl::FALSE, ptr as *const _),
+                gl::FLOAT_MAT2x3 => gl.UniformMatrix2x3fv(location, count, gl::FALSE, ptr as *const _),
+                gl::FLOAT_MAT2x4 => gl.UniformMatrix2x4fv(location, count, gl::FALSE, ptr as *const _),
+                gl::FLOAT_MAT3x2 => gl.UniformMatrix3x2fv(location, count, gl::FALSE, ptr as *const _),
+                gl::FLOAT_MAT3 => gl.UniformMatrix3fv(location, count, gl::FALSE, ptr as *const _),
+                gl::FLOAT_MAT3x4 => gl.UniformMatrix3x4fv(location, count, gl::FALSE, ptr as *const _),
+                gl::FLOAT_MAT4x2 => gl.UniformMatrix4x2fv(location, count, gl::FALSE, ptr as *const _),
+                gl::FLOAT_MAT4x3 => gl.UniformMatrix4x3fv(location, count, gl::FALSE, ptr as *const _),
+                gl::FLOAT_MAT4 => gl.UniformMatrix4fv(location, count, gl::FALSE, ptr as *const _),
+                _ => unreachable!(),
+            }
+        }
+    }
+
     /// Return a reference to a stored data object.
     fn get<T>(data: &[u8], ptr: com::BufferSlice) -> &[T] {
         let u32_size = mem::size_of::<T>();
@@ -209,7 +262,10 @@ impl CommandQueue {
 
         // Reset indirect draw buffer
         if self.share.legacy_features.contains(LegacyFeatures::INDIRECT_EXECUTION) {
-            unsafe { gl.BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0) };
+            unsafe {
+                gl.BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+                gl.BindBuffer(gl::DISPATCH_INDIRECT_BUFFER, 0);
+            }
         }
 
         // Unbind index buffers
@@ -369,6 +425,64 @@ impl CommandQueue {
                     error!("Instanced indexed drawing is not supported");
                 }
             }
+            com::Command::DrawIndirect { primitive, buffer, offset, draw_count, stride } => {
+                let gl = &self.share.context;
+                if !self.share.legacy_features.contains(LegacyFeatures::INDIRECT_EXECUTION) {
+                    error!("Indirect drawing is not supported");
+                } else if self.share.features.contains(hal::Features::MULTI_DRAW_INDIRECT) {
+                    unsafe {
+                        gl.BindBuffer(gl::DRAW_INDIRECT_BUFFER, buffer);
+                        gl.MultiDrawArraysIndirect(
+                            primitive,
+                            offset as *const gl::types::GLvoid,
+                            draw_count as _,
+                            stride as _,
+                        );
+                    }
+                } else {
+                    // Emulate with a draw call per command.
+                    unsafe { gl.BindBuffer(gl::DRAW_INDIRECT_BUFFER, buffer) };
+                    for i in 0 .. draw_count as hal::buffer::Offset {
+                        let command_offset = offset + i * stride as hal::buffer::Offset;
+                        unsafe {
+                            gl.DrawArraysIndirect(
+                                primitive,
+                                command_offset as *const gl::types::GLvoid,
+                            );
+                        }
+                    }
+                }
+            }
+            com::Command::DrawIndexedIndirect { primitive, index_type, buffer, offset, draw_count, stride } => {
+                let gl = &self.share.context;
+                if !self.share.legacy_features.contains(LegacyFeatures::INDIRECT_EXECUTION) {
+                    error!("Indirect indexed drawing is not supported");
+                } else if self.share.features.contains(hal::Features::MULTI_DRAW_INDIRECT) {
+                    unsafe {
+                        gl.BindBuffer(gl::DRAW_INDIRECT_BUFFER, buffer);
+                        gl.MultiDrawElementsIndirect(
+                            primitive,
+                            index_type,
+                            offset as *const gl::types::GLvoid,
+                            draw_count as _,
+                            stride as _,
+                        );
+                    }
+                } else {
+                    // Emulate with a draw call per command.
+                    unsafe { gl.BindBuffer(gl::DRAW_INDIRECT_BUFFER, buffer) };
+                    for i in 0 .. draw_count as hal::buffer::Offset {
+                        let command_offset = offset + i * stride as hal::buffer::Offset;
+                        unsafe {
+                            gl.DrawElementsIndirect(
+                                primitive,
+                                index_type,
+                                command_offset as *const gl::types::GLvoid,
+                            );
+                        }
+                    }
+                }
+            }
             com::Command::Dispatch(count) => {
                 // Capability support is given by which queue types will be exposed.
                 // If there is no compute support, this pattern should never be reached
@@ -382,7 +496,7 @@ impl CommandQueue {
                 // because no queue with compute capability can be created.
                 let gl = &self.share.context;
                 unsafe {
-                    gl.BindBuffer(gl::DRAW_INDIRECT_BUFFER, buffer);
+                    gl.BindBuffer(gl::DISPATCH_INDIRECT_BUFFER, buffer);
                     // TODO: possible integer conversion issue
                     gl.DispatchComputeIndirect(offset as _);
                 }
@@ -423,6 +537,13 @@ impl CommandQueue {
                     unsafe { gl.ScissorArrayv(first_scissor, num_scissors as i32, scissors.as_ptr() as *const _) };
                 }
             }
+            com::Command::SetScissorTest(enable) => unsafe {
+                if enable {
+                    self.share.context.Enable(gl::SCISSOR_TEST);
+                } else {
+                    self.share.context.Disable(gl::SCISSOR_TEST);
+                }
+            }
             com::Command::SetBlendColor(color) => {
                 state::set_blend_color(&self.share.context, color);
             }
@@ -436,14 +557,13 @@ impl CommandQueue {
                 self.share.context.ClearBufferiv(gl::COLOR, draw_buffer, cv.as_ptr());
             }
             com::Command::ClearBufferDepthStencil(depth, stencil) => unsafe {
-                let (target, depth, stencil) = match (depth, stencil) {
-                    (Some(depth), Some(stencil)) => (gl::DEPTH_STENCIL, depth, stencil),
-                    (Some(depth), None) => (gl::DEPTH, depth, 0),
-                    (None, Some(stencil)) => (gl::STENCIL, 0.0, stencil),
+                let gl = &self.share.context;
+                match (depth, stencil) {
+                    (Some(depth), Some(stencil)) => gl.ClearBufferfi(gl::DEPTH_STENCIL, 0, depth, stencil as _),
+                    (Some(depth), None) => gl.ClearBufferfv(gl::DEPTH, 0, &depth),
+                    (None, Some(stencil)) => gl.ClearBufferiv(gl::STENCIL, 0, &(stencil as gl::types::GLint)),
                     _ => unreachable!(),
-                };
-
-                self.share.context.ClearBufferfi(target, 0, depth, stencil as _);
+                }
             }
             com::Command::DrawBuffers(draw_buffers) => unsafe {
                 let draw_buffers = Self::get::<gl::types::GLenum>(data_buf, draw_buffers);
@@ -460,6 +580,19 @@ impl CommandQueue {
                     error!("Tried to bind FBO {} without FBO support!", frame_buffer);
                 }
             }
+            com::Command::InvalidateFramebuffer(attachments) => {
+                // Only a hint for the driver.
+                if self.share.private_caps.framebuffer_invalidate {
+                    let attachments = Self::get::<gl::types::GLenum>(data_buf, attachments);
+                    unsafe {
+                        self.share.context.InvalidateFramebuffer(
+                            gl::DRAW_FRAMEBUFFER,
+                            attachments.len() as _,
+                            attachments.as_ptr(),
+                        );
+                    }
+                }
+            }
             com::Command::BindTargetView(point, attachment, view) => {
                 self.bind_target(point, attachment, &view)
             }
@@ -472,6 +605,13 @@ impl CommandQueue {
             com::Command::BindProgram(program) => unsafe {
                 self.share.context.UseProgram(program);
             }
+            com::Command::SetPushConstants { uniforms, data } => {
+                let uniforms = Self::get::<native::PushConstantUniform>(data_buf, uniforms);
+                let data = Self::get_raw(data_buf, data);
+                for uniform in uniforms {
+                    self.set_push_constant(uniform, data);
+                }
+            }
             com::Command::BindBlendSlot(slot, ref blend) => {
                 state::bind_blend_slot(&self.share.context, slot, blend);
             }
@@ -508,38 +648,61 @@ impl CommandQueue {
                 gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
                 gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
             }
-            com::Command::CopyBufferToTexture(buffer, texture, ref r) => unsafe {
-                // TODO: Fix format and active texture
+            com::Command::CopyBufferToTexture(buffer, texture, format, ref r) => unsafe {
+                // TODO: Fix active texture
                 assert_eq!(r.image_offset.z, 0);
                 let gl = &self.share.context;
+                let (desc, _) = conv::describe_format(format).unwrap();
                 gl.ActiveTexture(gl::TEXTURE0);
                 gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, buffer);
                 gl.BindTexture(gl::TEXTURE_2D, texture);
-                gl.TexSubImage2D(
-                    gl::TEXTURE_2D, r.image_layers.level as _,
-                    r.image_offset.x, r.image_offset.y,
-                    r.image_extent.width as _, r.image_extent.height as _,
-                    gl::RGBA, gl::UNSIGNED_BYTE, ptr::null(),
-                );
+                if desc.is_compressed() {
+                    let surface_desc = format.surface_desc();
+                    let (block_width, block_height) = surface_desc.dim;
+                    let blocks_x = (r.image_extent.width + block_width as u32 - 1) / block_width as u32;
+                    let blocks_y = (r.image_extent.height + block_height as u32 - 1) / block_height as u32;
+                    let size = blocks_x * blocks_y * surface_desc.bits as u32 / 8;
+                    gl.CompressedTexSubImage2D(
+                        gl::TEXTURE_2D, r.image_layers.level as _,
+                        r.image_offset.x, r.image_offset.y,
+                        r.image_extent.width as _, r.image_extent.height as _,
+                        desc.tex_internal, size as _, r.buffer_offset as *const _,
+                    );
+                } else {
+                    gl.TexSubImage2D(
+                        gl::TEXTURE_2D, r.image_layers.level as _,
+                        r.image_offset.x, r.image_offset.y,
+                        r.image_extent.width as _, r.image_extent.height as _,
+                        desc.tex_external, desc.data_type, r.buffer_offset as *const _,
+                    );
+                }
                 gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
             }
             com::Command::CopyBufferToSurface(..) => {
                 unimplemented!() //TODO: use FBO
             }
-            com::Command::CopyTextureToBuffer(texture, buffer, ref r) => unsafe {
-                // TODO: Fix format and active texture
+            com::Command::CopyTextureToBuffer(texture, format, buffer, ref r) => unsafe {
+                // TODO: Fix active texture
                 // TODO: handle partial copies gracefully
                 assert_eq!(r.image_offset, hal::image::Offset { x: 0, y: 0, z: 0 });
                 let gl = &self.share.context;
+                let (desc, _) = conv::describe_format(format).unwrap();
                 gl.ActiveTexture(gl::TEXTURE0);
                 gl.BindBuffer(gl::PIXEL_PACK_BUFFER, buffer);
                 gl.BindTexture(gl::TEXTURE_2D, texture);
-                gl.GetTexImage(
-                    gl::TEXTURE_2D, r.image_layers.level as _,
-                    //r.image_offset.x, r.image_offset.y,
-                    //r.image_extent.width as _, r.image_extent.height as _,
-                    gl::RGBA, gl::UNSIGNED_BYTE, ptr::null_mut(),
-                );
+                if desc.is_compressed() {
+                    gl.GetCompressedTexImage(
+                        gl::TEXTURE_2D, r.image_layers.level as _,
+                        r.buffer_offset as *mut _,
+                    );
+                } else {
+                    gl.GetTexImage(
+                        gl::TEXTURE_2D, r.image_layers.level as _,
+                        //r.image_offset.x, r.image_offset.y,
+                        //r.image_extent.width as _, r.image_extent.height as _,
+                        desc.tex_external, desc.data_type, r.buffer_offset as *mut _,
+                    );
+                }
                 gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
             }
             com::Command::CopySurfaceToBuffer(..) => {
@@ -564,6 +727,68 @@ impl CommandQueue {
                 let gl = &self.share.context;
                 gl.BindSampler(index, sampler);
             }
+            com::Command::BeginQuery(target, query) => unsafe {
+                self.share.context.BeginQuery(target, query);
+            }
+            com::Command::EndQuery(target) => unsafe {
+                self.share.context.EndQuery(target);
+            }
+            com::Command::WriteTimestamp(query) => unsafe {
+                self.share.context.QueryCounter(query, gl::TIMESTAMP);
+            }
+            com::Command::SetEvent(ref event) => {
+                let gl = &self.share.context;
+                let state = if self.share.private_caps.sync {
+                    native::EventState::Device(unsafe { gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) })
+                } else {
+                    // Commands are executed in order, there is nothing to wait for.
+                    native::EventState::Host
+                };
+                event.set_state(gl, state);
+            }
+            com::Command::ResetEvent(ref event) => {
+                event.set_state(&self.share.context, native::EventState::Unsignaled);
+            }
+            com::Command::WaitEvent(ref event) => {
+                match *event.0.lock().unwrap() {
+                    native::EventState::Device(sync) => unsafe {
+                        self.share.context.WaitSync(sync, 0, gl::TIMEOUT_IGNORED);
+                    },
+                    native::EventState::Host => {}
+                    // Commands are executed on submission, so the event
+                    // can't be set on the host while they are waiting.
+                    native::EventState::Unsignaled => {
+                        error!("Waiting on an event which has not been set before the submission");
+                    }
+                }
+            }
+            com::Command::CopyQueryResult { query, buffer, offset, flags } => {
+                if !self.share.private_caps.query_buffer {
+                    error!("Copying query results into buffers is not supported");
+                } else {
+                    let gl = &self.share.context;
+                    let size = if flags.contains(ResultFlags::BITS_64) { 8 } else { 4 };
+                    // With a query buffer bound, the pointer argument is an offset into the buffer.
+                    let get = |pname, offset: hal::buffer::Offset| unsafe {
+                        if flags.contains(ResultFlags::BITS_64) {
+                            gl.GetQueryObjectui64v(query, pname, offset as *mut _);
+                        } else {
+                            gl.GetQueryObjectuiv(query, pname, offset as *mut _);
+                        }
+                    };
+                    unsafe { gl.BindBuffer(gl::QUERY_BUFFER, buffer) };
+                    if flags.contains(ResultFlags::WAIT) {
+                        get(gl::QUERY_RESULT, offset);
+                    } else {
+                        // Leaves the buffer untouched if the result isn't available.
+                        get(gl::QUERY_RESULT_NO_WAIT, offset);
+                    }
+                    if flags.contains(ResultFlags::WITH_AVAILABILITY) {
+                        get(gl::QUERY_RESULT_AVAILABLE, offset + size);
+                    }
+                    unsafe { gl.BindBuffer(gl::QUERY_BUFFER, 0) };
+                }
+            }
             /*
             com::Command::BindConstantBuffer(pso::ConstantBufferParam(buffer, _, slot)) => unsafe {
                 self.share.context.BindBufferBase(gl::UNIFORM_BUFFER, slot as gl::types::GLuint, buffer);
@@ -666,12 +891,53 @@ impl CommandQueue {
                 }
             },
             */
+            com::Command::ExecuteCommands(_) => {
+                // Spliced into the command stream by `process_commands`.
+                unreachable!()
+            }
         }
         if let Err(err) = self.share.check() {
             panic!("Error {:?} executing command: {:?}", err, cmd)
         }
     }
     
+    /// Process the commands of a command buffer, with the memory of its pool already locked,
+    /// splicing in the commands of the secondary command buffers it executes.
+    fn process_commands(
+        &mut self,
+        owner: &Arc<Mutex<BufferMemory>>,
+        memory: &BufferMemory,
+        id: u64,
+        buf: com::BufferSlice,
+    ) {
+        let buffer = match *memory {
+            BufferMemory::Linear(ref buffer) => buffer,
+            BufferMemory::Individual { ref storage, .. } => {
+                storage.get(&id).unwrap()
+            }
+        };
+
+        assert!(buffer.commands.len() >= (buf.offset+buf.size) as usize);
+        let commands = &buffer.commands[buf.offset as usize..(buf.offset+buf.size) as usize];
+        for com in commands {
+            match *com {
+                com::Command::ExecuteCommands(ref secondary) => {
+                    if Arc::ptr_eq(&secondary.memory, owner) {
+                        // Allocated from the same pool, which is locked already.
+                        self.process_commands(owner, memory, secondary.id, secondary.buf);
+                    } else {
+                        let secondary_memory = secondary
+                            .memory
+                            .try_lock()
+                            .expect("Trying to submit a secondary command buffers, while memory is in-use.");
+                        self.process_commands(&secondary.memory, &*secondary_memory, secondary.id, secondary.buf);
+                    }
+                }
+                _ => self.process(com, &buffer.data),
+            }
+        }
+    }
+
     fn signal_fence(&mut self, fence: &native::Fence) {
         if self.share.private_caps.sync {
             let gl = &self.share.context;
@@ -693,7 +959,6 @@ impl hal::queue::RawCommandQueue<Backend> for CommandQueue {
         IC: IntoIterator,
         IC::Item: Borrow<com::RawCommandBuffer>,
     {
-        use pool::BufferMemory;
         {
             for buf in submit_info.cmd_buffers {
                 let cb = buf.borrow();
@@ -702,24 +967,17 @@ impl hal::queue::RawCommandQueue<Backend> for CommandQueue {
                     .try_lock()
                     .expect("Trying to submit a command buffers, while memory is in-use.");
 
-                let buffer = match *memory {
-                    BufferMemory::Linear(ref buffer) => buffer,
-                    BufferMemory::Individual { ref storage, .. } => {
-                        storage.get(&cb.id).unwrap()
-                    }
-                };
-
-                assert!(buffer.commands.len() >= (cb.buf.offset+cb.buf.size) as usize);
-                let commands = &buffer.commands[cb.buf.offset as usize..(cb.buf.offset+cb.buf.size) as usize];
                 self.reset_state();
-                for com in commands {
-                    self.process(com, &buffer.data);
-                }
+                self.process_commands(&cb.memory, &*memory, cb.id, cb.buf);
             }
         }
         fence.map(|fence| self.signal_fence(fence));
     }
 
+    unsafe fn bind_sparse(&mut self, _binding: hal::queue::RawSparseBinding<Backend>, _fence: Option<&native::Fence>) {
+        unimplemented!()
+    }
+
     #[cfg(feature = "glutin")]
     fn present<IS, S, IW>(&mut self, swapchains: IS, _wait_semaphores: IW) -> Result<(), ()>
     where
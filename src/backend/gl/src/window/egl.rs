@@ -0,0 +1,313 @@
+//! Headless initialization using EGL, without any window system.
+//!
+//! The display is acquired from the surfaceless platform
+//! (`EGL_MESA_platform_surfaceless`) when available, which allows running on
+//! Mesa software rasterizers like llvmpipe without X11 or a GPU. Otherwise the
+//! default display is used, with a 1x1 pbuffer if surfaceless contexts aren't
+//! supported.
+//!
+//! ```no_run
+//! extern crate gfx_backend_gl;
+//! extern crate gfx_hal;
+//!
+//! use gfx_hal::Instance;
+//! use gfx_backend_gl::EglHeadless;
+//!
+//! fn main() {
+//!     let headless = EglHeadless::new().expect("Failed to create EGL context");
+//!     let _adapters = headless.enumerate_adapters();
+//! }
+//! ```
+
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::{fmt, mem, ptr};
+
+use hal;
+
+use {Backend as B, PhysicalDevice};
+
+#[allow(non_camel_case_types)]
+mod ffi {
+    use std::os::raw::{c_char, c_void};
+
+    pub type EGLBoolean = u32;
+    pub type EGLenum = u32;
+    pub type EGLint = i32;
+    pub type EGLConfig = *mut c_void;
+    pub type EGLContext = *mut c_void;
+    pub type EGLDisplay = *mut c_void;
+    pub type EGLSurface = *mut c_void;
+    pub type EGLNativeDisplayType = *mut c_void;
+
+    pub type GetPlatformDisplayFn = unsafe extern "C" fn(
+        EGLenum, *mut c_void, *const EGLint,
+    ) -> EGLDisplay;
+
+    pub const EGL_FALSE: EGLBoolean = 0;
+    pub const EGL_NONE: EGLint = 0x3038;
+    pub const EGL_ALPHA_SIZE: EGLint = 0x3021;
+    pub const EGL_BLUE_SIZE: EGLint = 0x3022;
+    pub const EGL_GREEN_SIZE: EGLint = 0x3023;
+    pub const EGL_RED_SIZE: EGLint = 0x3024;
+    pub const EGL_SURFACE_TYPE: EGLint = 0x3033;
+    pub const EGL_RENDERABLE_TYPE: EGLint = 0x3040;
+    pub const EGL_EXTENSIONS: EGLint = 0x3055;
+    pub const EGL_HEIGHT: EGLint = 0x3056;
+    pub const EGL_WIDTH: EGLint = 0x3057;
+    pub const EGL_PBUFFER_BIT: EGLint = 0x0001;
+    pub const EGL_OPENGL_BIT: EGLint = 0x0008;
+    pub const EGL_OPENGL_API: EGLenum = 0x30A2;
+    pub const EGL_CONTEXT_MAJOR_VERSION: EGLint = 0x3098;
+    pub const EGL_CONTEXT_MINOR_VERSION: EGLint = 0x30FB;
+    pub const EGL_CONTEXT_OPENGL_PROFILE_MASK: EGLint = 0x30FD;
+    pub const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: EGLint = 0x0001;
+    pub const EGL_PLATFORM_SURFACELESS_MESA: EGLenum = 0x31DD;
+
+    #[link(name = "EGL")]
+    extern "C" {
+        pub fn eglGetError() -> EGLint;
+        pub fn eglGetDisplay(display_id: EGLNativeDisplayType) -> EGLDisplay;
+        pub fn eglInitialize(dpy: EGLDisplay, major: *mut EGLint, minor: *mut EGLint) -> EGLBoolean;
+        pub fn eglTerminate(dpy: EGLDisplay) -> EGLBoolean;
+        pub fn eglQueryString(dpy: EGLDisplay, name: EGLint) -> *const c_char;
+        pub fn eglChooseConfig(
+            dpy: EGLDisplay,
+            attrib_list: *const EGLint,
+            configs: *mut EGLConfig,
+            config_size: EGLint,
+            num_config: *mut EGLint,
+        ) -> EGLBoolean;
+        pub fn eglBindAPI(api: EGLenum) -> EGLBoolean;
+        pub fn eglCreateContext(
+            dpy: EGLDisplay,
+            config: EGLConfig,
+            share_context: EGLContext,
+            attrib_list: *const EGLint,
+        ) -> EGLContext;
+        pub fn eglDestroyContext(dpy: EGLDisplay, ctx: EGLContext) -> EGLBoolean;
+        pub fn eglCreatePbufferSurface(
+            dpy: EGLDisplay,
+            config: EGLConfig,
+            attrib_list: *const EGLint,
+        ) -> EGLSurface;
+        pub fn eglDestroySurface(dpy: EGLDisplay, surface: EGLSurface) -> EGLBoolean;
+        pub fn eglMakeCurrent(
+            dpy: EGLDisplay,
+            draw: EGLSurface,
+            read: EGLSurface,
+            ctx: EGLContext,
+        ) -> EGLBoolean;
+        pub fn eglGetProcAddress(procname: *const c_char) -> *const c_void;
+    }
+}
+
+/// Error occurring while creating an EGL context.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EglError {
+    /// No display is available.
+    NoDisplay,
+    /// No framebuffer configuration supports desktop OpenGL.
+    NoConfig,
+    /// An EGL call failed, with the error code returned by `eglGetError`.
+    Call(&'static str, i32),
+}
+
+impl fmt::Display for EglError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EglError::Call(call, code) => write!(f, "{} failed with error 0x{:04X}", call, code),
+            _ => f.write_str(self.message()),
+        }
+    }
+}
+
+impl Error for EglError {
+    fn description(&self) -> &str {
+        self.message()
+    }
+}
+
+impl EglError {
+    fn message(&self) -> &'static str {
+        match *self {
+            EglError::NoDisplay => "No EGL display is available",
+            EglError::NoConfig => "No EGL framebuffer configuration supports desktop OpenGL",
+            EglError::Call(..) => "An EGL call failed",
+        }
+    }
+}
+
+fn last_error(call: &'static str) -> EglError {
+    EglError::Call(call, unsafe { ffi::eglGetError() })
+}
+
+fn has_extension(extensions: *const c_char, name: &str) -> bool {
+    if extensions.is_null() {
+        return false;
+    }
+    let extensions = unsafe { CStr::from_ptr(extensions) };
+    extensions
+        .to_string_lossy()
+        .split(' ')
+        .any(|ext| ext == name)
+}
+
+/// Headless OpenGL context created with EGL.
+pub struct EglHeadless {
+    display: ffi::EGLDisplay,
+    context: ffi::EGLContext,
+    // Only required without `EGL_KHR_surfaceless_context`.
+    surface: ffi::EGLSurface,
+}
+
+unsafe impl Send for EglHeadless {}
+unsafe impl Sync for EglHeadless {}
+
+impl EglHeadless {
+    /// Create a desktop OpenGL context, preferring a 3.2 core profile.
+    pub fn new() -> Result<Self, EglError> {
+        let display = unsafe { Self::get_display() };
+        if display.is_null() {
+            return Err(EglError::NoDisplay);
+        }
+        let (mut major, mut minor) = (0, 0);
+        if unsafe { ffi::eglInitialize(display, &mut major, &mut minor) } == ffi::EGL_FALSE {
+            return Err(last_error("eglInitialize"));
+        }
+        info!("EGL {}.{}", major, minor);
+
+        match unsafe { Self::create_context(display) } {
+            Ok((context, surface)) => Ok(EglHeadless { display, context, surface }),
+            Err(err) => {
+                unsafe { ffi::eglTerminate(display) };
+                Err(err)
+            }
+        }
+    }
+
+    unsafe fn get_display() -> ffi::EGLDisplay {
+        // Client extensions are queried without a display.
+        let client_extensions = ffi::eglQueryString(ptr::null_mut(), ffi::EGL_EXTENSIONS);
+        if has_extension(client_extensions, "EGL_EXT_platform_base") &&
+            has_extension(client_extensions, "EGL_MESA_platform_surfaceless")
+        {
+            let name = b"eglGetPlatformDisplayEXT\0";
+            let get_platform_display = ffi::eglGetProcAddress(name.as_ptr() as *const _);
+            if !get_platform_display.is_null() {
+                let get_platform_display: ffi::GetPlatformDisplayFn = mem::transmute(get_platform_display);
+                let display = get_platform_display(
+                    ffi::EGL_PLATFORM_SURFACELESS_MESA,
+                    ptr::null_mut(),
+                    ptr::null(),
+                );
+                if !display.is_null() {
+                    info!("Using the EGL surfaceless platform");
+                    return display;
+                }
+            }
+        }
+        ffi::eglGetDisplay(ptr::null_mut())
+    }
+
+    unsafe fn create_context(
+        display: ffi::EGLDisplay,
+    ) -> Result<(ffi::EGLContext, ffi::EGLSurface), EglError> {
+        let extensions = ffi::eglQueryString(display, ffi::EGL_EXTENSIONS);
+        let surfaceless = has_extension(extensions, "EGL_KHR_surfaceless_context");
+
+        let config_attribs = [
+            ffi::EGL_SURFACE_TYPE, if surfaceless { 0 } else { ffi::EGL_PBUFFER_BIT },
+            ffi::EGL_RENDERABLE_TYPE, ffi::EGL_OPENGL_BIT,
+            ffi::EGL_RED_SIZE, 8,
+            ffi::EGL_GREEN_SIZE, 8,
+            ffi::EGL_BLUE_SIZE, 8,
+            ffi::EGL_ALPHA_SIZE, 8,
+            ffi::EGL_NONE,
+        ];
+        let mut config = ptr::null_mut();
+        let mut num_configs = 0;
+        if ffi::eglChooseConfig(display, config_attribs.as_ptr(), &mut config, 1, &mut num_configs) == ffi::EGL_FALSE {
+            return Err(last_error("eglChooseConfig"));
+        }
+        if num_configs == 0 {
+            return Err(EglError::NoConfig);
+        }
+
+        if ffi::eglBindAPI(ffi::EGL_OPENGL_API) == ffi::EGL_FALSE {
+            return Err(last_error("eglBindAPI"));
+        }
+        let core_attribs = [
+            ffi::EGL_CONTEXT_MAJOR_VERSION, 3,
+            ffi::EGL_CONTEXT_MINOR_VERSION, 2,
+            ffi::EGL_CONTEXT_OPENGL_PROFILE_MASK, ffi::EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            ffi::EGL_NONE,
+        ];
+        let mut context = ffi::eglCreateContext(display, config, ptr::null_mut(), core_attribs.as_ptr());
+        if context.is_null() {
+            info!("EGL core profile context unavailable, falling back to the default one");
+            let default_attribs = [ffi::EGL_NONE];
+            context = ffi::eglCreateContext(display, config, ptr::null_mut(), default_attribs.as_ptr());
+        }
+        if context.is_null() {
+            return Err(last_error("eglCreateContext"));
+        }
+
+        let surface = if surfaceless {
+            ptr::null_mut()
+        } else {
+            let surface_attribs = [
+                ffi::EGL_WIDTH, 1,
+                ffi::EGL_HEIGHT, 1,
+                ffi::EGL_NONE,
+            ];
+            let surface = ffi::eglCreatePbufferSurface(display, config, surface_attribs.as_ptr());
+            if surface.is_null() {
+                let err = last_error("eglCreatePbufferSurface");
+                ffi::eglDestroyContext(display, context);
+                return Err(err);
+            }
+            surface
+        };
+
+        Ok((context, surface))
+    }
+
+    /// Make the context current on the calling thread.
+    pub fn make_current(&self) -> Result<(), EglError> {
+        let result = unsafe {
+            ffi::eglMakeCurrent(self.display, self.surface, self.surface, self.context)
+        };
+        if result == ffi::EGL_FALSE {
+            Err(last_error("eglMakeCurrent"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for EglHeadless {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::eglMakeCurrent(self.display, ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+            if !self.surface.is_null() {
+                ffi::eglDestroySurface(self.display, self.surface);
+            }
+            ffi::eglDestroyContext(self.display, self.context);
+            ffi::eglTerminate(self.display);
+        }
+    }
+}
+
+impl hal::Instance for EglHeadless {
+    type Backend = B;
+    fn enumerate_adapters(&self) -> Vec<hal::Adapter<B>> {
+        self.make_current().unwrap();
+        let adapter = PhysicalDevice::new_adapter(|s| {
+            let name = CString::new(s).unwrap();
+            unsafe { ffi::eglGetProcAddress(name.as_ptr()) }
+        });
+        vec![adapter]
+    }
+}
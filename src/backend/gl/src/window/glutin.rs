@@ -45,7 +45,7 @@
 
 use hal::{self, format as f, image};
 
-use {Backend as B, Device, PhysicalDevice, QueueFamily, Starc};
+use {native, Backend as B, Device, PhysicalDevice, QueueFamily, Starc};
 
 use glutin::{self, GlContext};
 
@@ -157,7 +157,7 @@ impl Device {
         let swapchain = Swapchain {
             window: surface.window.clone(),
         };
-        let backbuffer = hal::Backbuffer::Framebuffer(0);
+        let backbuffer = hal::Backbuffer::Framebuffer(native::FrameBuffer::default_framebuffer());
         (swapchain, backbuffer)
     }
 }
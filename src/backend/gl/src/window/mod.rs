@@ -1,2 +1,5 @@
 #[cfg(feature = "glutin")]
 pub mod glutin;
+
+#[cfg(feature = "egl")]
+pub mod egl;
@@ -18,8 +18,8 @@ use hal::backend::FastHashMap;
 use hal::format::{Aspects, Format, FormatDesc};
 use hal::image::{Extent, Filter, Layout, Level, SubresourceRange};
 use hal::pass::{AttachmentLoadOp, AttachmentOps};
-use hal::query::{Query, QueryControl, QueryId};
-use hal::queue::{RawCommandQueue, RawSubmission};
+use hal::query::{Query, QueryControl, QueryId, ResultFlags};
+use hal::queue::{RawCommandQueue, RawSparseBinding, RawSubmission};
 use hal::range::RangeArg;
 
 use block::ConcreteBlock;
@@ -1705,6 +1705,10 @@ impl RawCommandQueue<Backend> for CommandQueue {
         }
     }
 
+    unsafe fn bind_sparse(&mut self, _binding: RawSparseBinding<Backend>, _fence: Option<&native::Fence>) {
+        unimplemented!()
+    }
+
     fn present<IS, S, IW>(&mut self, swapchains: IS, wait_semaphores: IW) -> Result<(), ()>
     where
         IS: IntoIterator<Item = (S, SwapImageIndex)>,
@@ -1940,6 +1944,28 @@ impl com::RawCommandBuffer<Backend> for CommandBuffer {
         // TODO: MTLRenderCommandEncoder.textureBarrier on macOS?
     }
 
+    fn set_event(&mut self, _event: &(), _stages: pso::PipelineStage) {
+        unimplemented!()
+    }
+
+    fn reset_event(&mut self, _event: &(), _stages: pso::PipelineStage) {
+        unimplemented!()
+    }
+
+    fn wait_events<'a, I, J>(
+        &mut self,
+        _events: I,
+        _stages: Range<pso::PipelineStage>,
+        _barriers: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<()>,
+        J: IntoIterator,
+        J::Item: Borrow<memory::Barrier<'a, Backend>>,
+    {
+        unimplemented!()
+    }
+
     fn fill_buffer<R>(
         &mut self,
         buffer: &native::Buffer,
@@ -3483,6 +3509,18 @@ impl com::RawCommandBuffer<Backend> for CommandBuffer {
         // nothing to do, timestamps are unsupported on Metal
     }
 
+    fn copy_query_pool_results(
+        &mut self,
+        _pool: &(),
+        _queries: Range<QueryId>,
+        _buffer: &native::Buffer,
+        _offset: buffer::Offset,
+        _stride: buffer::Offset,
+        _flags: ResultFlags,
+    ) {
+        unimplemented!()
+    }
+
     fn push_graphics_constants(
         &mut self,
         layout: &native::PipelineLayout,
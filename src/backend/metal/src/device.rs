@@ -401,8 +401,12 @@ impl PhysicalDevice {
 
 impl hal::PhysicalDevice<Backend> for PhysicalDevice {
     fn open(
-        &self, families: &[(&QueueFamily, &[hal::QueuePriority])],
+        &self, families: &[(&QueueFamily, &[hal::QueuePriority])], requested_features: hal::Features,
     ) -> Result<hal::Gpu<Backend>, error::DeviceCreationError> {
+        if !self.features().contains(requested_features) {
+            return Err(error::DeviceCreationError::MissingFeature);
+        }
+
         // TODO: Handle opening a physical device multiple times
         assert_eq!(families.len(), 1);
         assert_eq!(families[0].1.len(), 1);
@@ -543,6 +547,11 @@ impl hal::PhysicalDevice<Backend> for PhysicalDevice {
             non_coherent_atom_size: 4,
         }
     }
+
+    fn timestamp_period(&self) -> f32 {
+        // Note: timestamps are not supported, the period is irrelevant.
+        1.0
+    }
 }
 
 pub struct LanguageVersion {
@@ -976,12 +985,17 @@ impl hal::Device<Backend> for Device {
         }
     }
 
-    fn create_pipeline_cache(&self) -> n::PipelineCache {
+    fn create_pipeline_cache(&self, _data: Option<&[u8]>) -> n::PipelineCache {
         n::PipelineCache {
             modules: FastStorageMap::default(),
         }
     }
 
+    fn get_pipeline_cache_data(&self, _cache: &n::PipelineCache) -> Result<Vec<u8>, OutOfMemory> {
+        //TODO: serialize the compiled libraries
+        Ok(Vec::new())
+    }
+
     fn destroy_pipeline_cache(&self, _cache: n::PipelineCache) {
         //drop
     }
@@ -2243,6 +2257,29 @@ impl hal::Device<Backend> for Device {
         Ok(n::ImageView { raw: view, mtl_format })
     }
 
+    fn create_sparse_buffer(
+        &self, _size: u64, _usage: buffer::Usage, _sparse: memory::SparseFlags,
+    ) -> Result<n::Buffer, buffer::CreationError> {
+        unimplemented!()
+    }
+
+    fn get_sparse_buffer_requirements(&self, _buffer: &n::Buffer) -> memory::Requirements {
+        unimplemented!()
+    }
+
+    fn create_sparse_image(
+        &self, _kind: image::Kind, _mip_levels: image::Level, _format: format::Format,
+        _usage: image::Usage, _storage_flags: image::StorageFlags, _sparse: memory::SparseFlags,
+    ) -> Result<n::Image, image::CreationError> {
+        unimplemented!()
+    }
+
+    fn get_sparse_image_requirements(
+        &self, _image: &n::Image,
+    ) -> (memory::Requirements, Vec<image::SparseRequirements>) {
+        unimplemented!()
+    }
+
     fn destroy_image_view(&self, _view: n::ImageView) {
     }
 
@@ -2288,10 +2325,30 @@ impl hal::Device<Backend> for Device {
             },
         }
     }
+    fn create_event(&self) -> () {
+        unimplemented!()
+    }
+
+    fn get_event_status(&self, _event: &()) -> bool {
+        unimplemented!()
+    }
+
+    fn set_event(&self, _event: &()) {
+        unimplemented!()
+    }
+
+    fn reset_event(&self, _event: &()) {
+        unimplemented!()
+    }
+
+    fn destroy_event(&self, _event: ()) {
+        unimplemented!()
+    }
+
     fn destroy_fence(&self, _fence: n::Fence) {
     }
 
-    fn create_query_pool(&self, _ty: query::QueryType, _count: query::QueryId) -> () {
+    fn create_query_pool(&self, _ty: query::QueryType, _count: query::QueryId) -> Result<(), query::Error> {
         unimplemented!()
     }
 
@@ -2299,6 +2356,17 @@ impl hal::Device<Backend> for Device {
         unimplemented!()
     }
 
+    fn get_query_pool_results(
+        &self,
+        _pool: &(),
+        _queries: Range<query::QueryId>,
+        _data: &mut [u8],
+        _stride: buffer::Offset,
+        _flags: query::ResultFlags,
+    ) -> Result<bool, error::HostExecutionError> {
+        unimplemented!()
+    }
+
     fn create_swapchain(
         &self,
         surface: &mut Surface,
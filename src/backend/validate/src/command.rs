@@ -0,0 +1,829 @@
+use std::borrow::Borrow;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use hal::{self, buffer, command as com, image, memory, pass, pool, pso, query};
+use hal::{DrawCount, IndexCount, InstanceCount, VertexCount, VertexOffset, WorkGroupCount};
+use hal::range::RangeArg;
+
+use native as n;
+use track::{Context, Handle};
+use Backend;
+
+
+/// Where a command can be recorded, relative to render passes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scope {
+    Anywhere,
+    Inside,
+    Outside,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    recording: bool,
+    /// Resources referenced by the recorded commands.
+    resources: Vec<Handle>,
+    /// Render pass and index of the current subpass.
+    subpass: Option<(Arc<n::RenderPassInfo>, pass::SubpassId)>,
+    /// Subpass the bound graphics pipeline has been created for.
+    graphics_pipeline: Option<(Arc<n::RenderPassInfo>, pass::SubpassId)>,
+    compute_pipeline: bool,
+}
+
+/// Command buffer of the wrapped backend.
+///
+/// Clones share their recording state, the same way they share the native command buffer.
+#[derive(Clone, Debug)]
+pub struct CommandBuffer<B: hal::Backend> {
+    raw: B::CommandBuffer,
+    context: Arc<Context>,
+    pool: Handle,
+    level: com::RawLevel,
+    state: Arc<Mutex<State>>,
+}
+
+impl<B: hal::Backend> CommandBuffer<B> {
+    /// Check that the command `name` is recorded in a valid state.
+    fn validate(&self, name: &str, scope: Scope) {
+        let (recording, inside) = {
+            let state = self.state.lock().unwrap();
+            (state.recording, state.subpass.is_some())
+        };
+        if !recording {
+            report!(self.context, "`{}` is recorded into a command buffer which is not recording", name);
+        }
+        match scope {
+            Scope::Inside if !inside => {
+                report!(self.context, "`{}` is recorded outside of a render pass", name);
+            }
+            Scope::Outside if inside => {
+                report!(self.context, "`{}` is recorded inside of a render pass", name);
+            }
+            _ => {}
+        }
+    }
+
+    fn track<I: IntoIterator<Item = Handle>>(&self, handles: I) {
+        self.state.lock().unwrap().resources.extend(handles);
+    }
+
+    fn check_buffer_range(&self, buffer: &n::Buffer<B>, range: Range<buffer::Offset>, name: &str) {
+        if range.start > range.end || range.end > buffer.size {
+            report!(self.context, "`{}` accesses the range {:?} of {}, which is {} bytes long",
+                name, range, buffer.handle, buffer.size);
+        }
+    }
+
+    fn check_query(&self, pool: &n::QueryPool<B>, queries: Range<query::QueryId>, name: &str) {
+        if queries.end > pool.count {
+            report!(self.context, "`{}` uses the queries {:?} of {}, which has {} queries",
+                name, queries, pool.handle, pool.count);
+        }
+    }
+
+    /// Track the resources of memory barriers and unwrap them.
+    fn unwrap_barriers<'a, T>(&self, barriers: T) -> Vec<memory::Barrier<'a, B>>
+    where
+        T: IntoIterator,
+        T::Item: Borrow<memory::Barrier<'a, Backend<B>>>,
+    {
+        barriers
+            .into_iter()
+            .map(|barrier| match *barrier.borrow() {
+                memory::Barrier::AllBuffers(ref access) => memory::Barrier::AllBuffers(access.clone()),
+                memory::Barrier::AllImages(ref access) => memory::Barrier::AllImages(access.clone()),
+                memory::Barrier::Buffer { ref states, target, ref families } => {
+                    self.track(Some(target.handle.clone()));
+                    memory::Barrier::Buffer {
+                        states: states.clone(),
+                        target: &target.raw,
+                        families: families.clone(),
+                    }
+                }
+                memory::Barrier::Image { ref states, target, ref range, ref families } => {
+                    self.track(Some(target.handle.clone()));
+                    memory::Barrier::Image {
+                        states: states.clone(),
+                        target: &target.raw,
+                        range: range.clone(),
+                        families: families.clone(),
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Check that the bound graphics pipeline is compatible with the current subpass.
+    fn check_draw(&self, name: &str) {
+        self.validate(name, Scope::Inside);
+        let error = {
+            let state = self.state.lock().unwrap();
+            match (&state.subpass, &state.graphics_pipeline) {
+                (_, &None) => Some(format!("`{}` is recorded without a bound graphics pipeline", name)),
+                (&Some((ref pass, index)), &Some((ref pipeline_pass, pipeline_index))) => {
+                    if index != pipeline_index {
+                        Some(format!("`{}` is recorded in the subpass {} with a pipeline created for the subpass {}",
+                            name, index, pipeline_index))
+                    } else if pass != pipeline_pass {
+                        Some(format!("`{}` is recorded with a pipeline created for an incompatible render pass", name))
+                    } else {
+                        None
+                    }
+                }
+                (&None, _) => None,
+            }
+        };
+        if let Some(error) = error {
+            report!(self.context, "{}", error);
+        }
+    }
+
+    fn copy_image_checks(&self, src: Option<&n::Image<B>>, dst: Option<&n::Image<B>>, name: &str) {
+        if let Some(src) = src {
+            check_usage!(self.context, src.handle, src.usage, image::Usage::TRANSFER_SRC, format!("`{}`", name));
+            self.track(Some(src.handle.clone()));
+        }
+        if let Some(dst) = dst {
+            check_usage!(self.context, dst.handle, dst.usage, image::Usage::TRANSFER_DST, format!("`{}`", name));
+            self.track(Some(dst.handle.clone()));
+        }
+    }
+}
+
+impl<B: hal::Backend> com::RawCommandBuffer<Backend<B>> for CommandBuffer<B> {
+    fn begin(&mut self, flags: com::CommandBufferFlags, info: com::CommandBufferInheritanceInfo<Backend<B>>) {
+        let mut resources = Vec::new();
+        let subpass = match info.subpass {
+            Some(ref subpass) if self.level == com::RawLevel::Secondary &&
+                flags.contains(com::CommandBufferFlags::RENDER_PASS_CONTINUE) =>
+            {
+                resources.push(subpass.main_pass.handle.clone());
+                Some((subpass.main_pass.info.clone(), subpass.index))
+            }
+            _ => None,
+        };
+        resources.extend(info.framebuffer.map(|fb| fb.handle.clone()));
+
+        let was_recording = {
+            let mut state = self.state.lock().unwrap();
+            let was_recording = state.recording;
+            *state = State {
+                recording: true,
+                resources,
+                subpass,
+                graphics_pipeline: None,
+                compute_pipeline: false,
+            };
+            was_recording
+        };
+        if was_recording {
+            report!(self.context, "`begin` is called on a command buffer which is already recording");
+        }
+
+        self.raw.begin(flags, com::CommandBufferInheritanceInfo {
+            subpass: info.subpass.map(|subpass| pass::Subpass {
+                index: subpass.index,
+                main_pass: &subpass.main_pass.raw,
+            }),
+            framebuffer: info.framebuffer.map(|fb| &fb.raw),
+            occlusion_query_enable: info.occlusion_query_enable,
+            occlusion_query_flags: info.occlusion_query_flags,
+            pipeline_statistics: info.pipeline_statistics,
+        });
+    }
+
+    fn finish(&mut self) {
+        let scope = match self.level {
+            com::RawLevel::Primary => Scope::Outside,
+            com::RawLevel::Secondary => Scope::Anywhere,
+        };
+        self.validate("finish", scope);
+        self.state.lock().unwrap().recording = false;
+        self.raw.finish();
+    }
+
+    fn reset(&mut self, release_resources: bool) {
+        *self.state.lock().unwrap() = State::default();
+        self.raw.reset(release_resources);
+    }
+
+    fn pipeline_barrier<'a, T>(
+        &mut self,
+        stages: Range<pso::PipelineStage>,
+        dependencies: memory::Dependencies,
+        barriers: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<memory::Barrier<'a, Backend<B>>>,
+    {
+        self.validate("pipeline_barrier", Scope::Anywhere);
+        let barriers = self.unwrap_barriers(barriers);
+        self.raw.pipeline_barrier(stages, dependencies, barriers);
+    }
+
+    fn set_event(&mut self, event: &n::Event<B>, stages: pso::PipelineStage) {
+        self.validate("set_event", Scope::Outside);
+        self.track(Some(event.handle.clone()));
+        self.raw.set_event(&event.raw, stages);
+    }
+
+    fn reset_event(&mut self, event: &n::Event<B>, stages: pso::PipelineStage) {
+        self.validate("reset_event", Scope::Outside);
+        self.track(Some(event.handle.clone()));
+        self.raw.reset_event(&event.raw, stages);
+    }
+
+    fn wait_events<'a, I, J>(
+        &mut self,
+        events: I,
+        stages: Range<pso::PipelineStage>,
+        barriers: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<n::Event<B>>,
+        J: IntoIterator,
+        J::Item: Borrow<memory::Barrier<'a, Backend<B>>>,
+    {
+        self.validate("wait_events", Scope::Anywhere);
+        let events = events.into_iter().collect::<Vec<_>>();
+        if events.is_empty() {
+            report!(self.context, "`wait_events` called without any event");
+        }
+        self.track(events.iter().map(|event| event.borrow().handle.clone()));
+        let barriers = self.unwrap_barriers(barriers);
+        self.raw.wait_events(events.iter().map(|event| &event.borrow().raw), stages, barriers);
+    }
+
+    fn fill_buffer<R>(&mut self, buffer: &n::Buffer<B>, range: R, data: u32)
+    where
+        R: RangeArg<buffer::Offset>,
+    {
+        self.validate("fill_buffer", Scope::Outside);
+        check_usage!(self.context, buffer.handle, buffer.usage, buffer::Usage::TRANSFER_DST, "`fill_buffer`");
+        let bounds = range.start().cloned().unwrap_or(0) .. range.end().cloned().unwrap_or(buffer.size);
+        self.check_buffer_range(buffer, bounds, "fill_buffer");
+        self.track(Some(buffer.handle.clone()));
+        self.raw.fill_buffer(&buffer.raw, range, data);
+    }
+
+    fn update_buffer(&mut self, buffer: &n::Buffer<B>, offset: buffer::Offset, data: &[u8]) {
+        self.validate("update_buffer", Scope::Outside);
+        check_usage!(self.context, buffer.handle, buffer.usage, buffer::Usage::TRANSFER_DST, "`update_buffer`");
+        self.check_buffer_range(buffer, offset .. offset + data.len() as buffer::Offset, "update_buffer");
+        self.track(Some(buffer.handle.clone()));
+        self.raw.update_buffer(&buffer.raw, offset, data);
+    }
+
+    fn clear_image<T>(
+        &mut self,
+        image: &n::Image<B>,
+        layout: image::Layout,
+        color: com::ClearColorRaw,
+        depth_stencil: com::ClearDepthStencilRaw,
+        subresource_ranges: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<image::SubresourceRange>,
+    {
+        self.validate("clear_image", Scope::Outside);
+        self.copy_image_checks(None, Some(image), "clear_image");
+        self.raw.clear_image(&image.raw, layout, color, depth_stencil, subresource_ranges);
+    }
+
+    fn clear_attachments<T, U>(&mut self, clears: T, rects: U)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<com::AttachmentClear>,
+        U: IntoIterator,
+        U::Item: Borrow<pso::ClearRect>,
+    {
+        self.validate("clear_attachments", Scope::Inside);
+        self.raw.clear_attachments(clears, rects);
+    }
+
+    fn resolve_image<T>(
+        &mut self,
+        src: &n::Image<B>,
+        src_layout: image::Layout,
+        dst: &n::Image<B>,
+        dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ImageResolve>,
+    {
+        self.validate("resolve_image", Scope::Outside);
+        self.track(vec![src.handle.clone(), dst.handle.clone()]);
+        self.raw.resolve_image(&src.raw, src_layout, &dst.raw, dst_layout, regions);
+    }
+
+    fn blit_image<T>(
+        &mut self,
+        src: &n::Image<B>,
+        src_layout: image::Layout,
+        dst: &n::Image<B>,
+        dst_layout: image::Layout,
+        filter: image::Filter,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ImageBlit>,
+    {
+        self.validate("blit_image", Scope::Outside);
+        self.copy_image_checks(Some(src), Some(dst), "blit_image");
+        self.raw.blit_image(&src.raw, src_layout, &dst.raw, dst_layout, filter, regions);
+    }
+
+    fn bind_index_buffer(&mut self, ibv: buffer::IndexBufferView<Backend<B>>) {
+        self.validate("bind_index_buffer", Scope::Anywhere);
+        check_usage!(self.context, ibv.buffer.handle, ibv.buffer.usage, buffer::Usage::INDEX, "an index buffer");
+        self.track(Some(ibv.buffer.handle.clone()));
+        self.raw.bind_index_buffer(buffer::IndexBufferView {
+            buffer: &ibv.buffer.raw,
+            offset: ibv.offset,
+            index_type: ibv.index_type,
+        });
+    }
+
+    fn bind_vertex_buffers<I, T>(&mut self, first_binding: u32, buffers: I)
+    where
+        I: IntoIterator<Item = (T, buffer::Offset)>,
+        T: Borrow<n::Buffer<B>>,
+    {
+        self.validate("bind_vertex_buffers", Scope::Anywhere);
+        let buffers = buffers.into_iter().collect::<Vec<_>>();
+        for (buffer, _) in &buffers {
+            let buffer = buffer.borrow();
+            check_usage!(self.context, buffer.handle, buffer.usage, buffer::Usage::VERTEX, "a vertex buffer");
+            self.track(Some(buffer.handle.clone()));
+        }
+        self.raw.bind_vertex_buffers(
+            first_binding,
+            buffers.iter().map(|&(ref buffer, offset)| (&buffer.borrow().raw, offset)),
+        );
+    }
+
+    fn set_viewports<T>(&mut self, first_viewport: u32, viewports: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::Viewport>,
+    {
+        self.validate("set_viewports", Scope::Anywhere);
+        self.raw.set_viewports(first_viewport, viewports);
+    }
+
+    fn set_scissors<T>(&mut self, first_scissor: u32, rects: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<pso::Rect>,
+    {
+        self.validate("set_scissors", Scope::Anywhere);
+        self.raw.set_scissors(first_scissor, rects);
+    }
+
+    fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.validate("set_stencil_reference", Scope::Anywhere);
+        self.raw.set_stencil_reference(faces, value);
+    }
+
+    fn set_stencil_read_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.validate("set_stencil_read_mask", Scope::Anywhere);
+        self.raw.set_stencil_read_mask(faces, value);
+    }
+
+    fn set_stencil_write_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.validate("set_stencil_write_mask", Scope::Anywhere);
+        self.raw.set_stencil_write_mask(faces, value);
+    }
+
+    fn set_blend_constants(&mut self, color: pso::ColorValue) {
+        self.validate("set_blend_constants", Scope::Anywhere);
+        self.raw.set_blend_constants(color);
+    }
+
+    fn set_depth_bounds(&mut self, bounds: Range<f32>) {
+        self.validate("set_depth_bounds", Scope::Anywhere);
+        self.raw.set_depth_bounds(bounds);
+    }
+
+    fn set_line_width(&mut self, width: f32) {
+        self.validate("set_line_width", Scope::Anywhere);
+        self.raw.set_line_width(width);
+    }
+
+    fn set_depth_bias(&mut self, depth_bias: pso::DepthBias) {
+        self.validate("set_depth_bias", Scope::Anywhere);
+        self.raw.set_depth_bias(depth_bias);
+    }
+
+    fn begin_render_pass<T>(
+        &mut self,
+        render_pass: &n::RenderPass<B>,
+        framebuffer: &n::Framebuffer<B>,
+        render_area: pso::Rect,
+        clear_values: T,
+        first_subpass: com::SubpassContents,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ClearValueRaw>,
+    {
+        self.validate("begin_render_pass", Scope::Outside);
+        match framebuffer.pass {
+            Some(ref pass) if *pass != render_pass.info => {
+                report!(self.context, "{} is used with {}, which is incompatible with its render pass",
+                    framebuffer.handle, render_pass.handle);
+            }
+            _ => {}
+        }
+        {
+            let mut state = self.state.lock().unwrap();
+            state.subpass = Some((render_pass.info.clone(), 0));
+            state.resources.push(render_pass.handle.clone());
+            state.resources.push(framebuffer.handle.clone());
+        }
+        self.raw.begin_render_pass(&render_pass.raw, &framebuffer.raw, render_area, clear_values, first_subpass);
+    }
+
+    fn next_subpass(&mut self, contents: com::SubpassContents) {
+        self.validate("next_subpass", Scope::Inside);
+        let last = {
+            let mut state = self.state.lock().unwrap();
+            match state.subpass {
+                Some((ref pass, ref mut index)) if *index + 1 < pass.subpasses.len() => {
+                    *index += 1;
+                    false
+                }
+                Some(_) => true,
+                None => false,
+            }
+        };
+        if last {
+            report!(self.context, "`next_subpass` is recorded in the last subpass of the render pass");
+        }
+        self.raw.next_subpass(contents);
+    }
+
+    fn end_render_pass(&mut self) {
+        self.validate("end_render_pass", Scope::Inside);
+        let remaining = match self.state.lock().unwrap().subpass.take() {
+            Some((pass, index)) => pass.subpasses.len() - 1 - index,
+            None => 0,
+        };
+        if remaining != 0 {
+            report!(self.context, "`end_render_pass` is recorded with {} subpasses remaining", remaining);
+        }
+        self.raw.end_render_pass();
+    }
+
+    fn bind_graphics_pipeline(&mut self, pipeline: &n::GraphicsPipeline<B>) {
+        self.validate("bind_graphics_pipeline", Scope::Anywhere);
+        {
+            let mut state = self.state.lock().unwrap();
+            state.graphics_pipeline = Some((pipeline.pass.clone(), pipeline.subpass));
+            state.resources.push(pipeline.handle.clone());
+        }
+        self.raw.bind_graphics_pipeline(&pipeline.raw);
+    }
+
+    fn bind_graphics_descriptor_sets<I, J>(
+        &mut self,
+        layout: &n::PipelineLayout<B>,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<n::DescriptorSet<B>>,
+        J: IntoIterator,
+        J::Item: Borrow<com::DescriptorSetOffset>,
+    {
+        self.validate("bind_graphics_descriptor_sets", Scope::Anywhere);
+        let sets = sets.into_iter().collect::<Vec<_>>();
+        self.track(Some(layout.handle.clone()));
+        for set in &sets {
+            self.track(set.borrow().handles());
+        }
+        self.raw.bind_graphics_descriptor_sets(
+            &layout.raw,
+            first_set,
+            sets.iter().map(|set| &set.borrow().raw),
+            offsets,
+        );
+    }
+
+    fn bind_compute_pipeline(&mut self, pipeline: &n::ComputePipeline<B>) {
+        self.validate("bind_compute_pipeline", Scope::Outside);
+        {
+            let mut state = self.state.lock().unwrap();
+            state.compute_pipeline = true;
+            state.resources.push(pipeline.handle.clone());
+        }
+        self.raw.bind_compute_pipeline(&pipeline.raw);
+    }
+
+    fn bind_compute_descriptor_sets<I, J>(
+        &mut self,
+        layout: &n::PipelineLayout<B>,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<n::DescriptorSet<B>>,
+        J: IntoIterator,
+        J::Item: Borrow<com::DescriptorSetOffset>,
+    {
+        self.validate("bind_compute_descriptor_sets", Scope::Outside);
+        let sets = sets.into_iter().collect::<Vec<_>>();
+        self.track(Some(layout.handle.clone()));
+        for set in &sets {
+            self.track(set.borrow().handles());
+        }
+        self.raw.bind_compute_descriptor_sets(
+            &layout.raw,
+            first_set,
+            sets.iter().map(|set| &set.borrow().raw),
+            offsets,
+        );
+    }
+
+    fn dispatch(&mut self, count: WorkGroupCount) {
+        self.validate("dispatch", Scope::Outside);
+        if !self.state.lock().unwrap().compute_pipeline {
+            report!(self.context, "`dispatch` is recorded without a bound compute pipeline");
+        }
+        self.raw.dispatch(count);
+    }
+
+    fn dispatch_indirect(&mut self, buffer: &n::Buffer<B>, offset: buffer::Offset) {
+        self.validate("dispatch_indirect", Scope::Outside);
+        if !self.state.lock().unwrap().compute_pipeline {
+            report!(self.context, "`dispatch_indirect` is recorded without a bound compute pipeline");
+        }
+        check_usage!(self.context, buffer.handle, buffer.usage, buffer::Usage::INDIRECT, "`dispatch_indirect`");
+        self.check_buffer_range(buffer, offset .. offset + 12, "dispatch_indirect");
+        self.track(Some(buffer.handle.clone()));
+        self.raw.dispatch_indirect(&buffer.raw, offset);
+    }
+
+    fn copy_buffer<T>(&mut self, src: &n::Buffer<B>, dst: &n::Buffer<B>, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<com::BufferCopy>,
+    {
+        self.validate("copy_buffer", Scope::Outside);
+        check_usage!(self.context, src.handle, src.usage, buffer::Usage::TRANSFER_SRC, "`copy_buffer`");
+        check_usage!(self.context, dst.handle, dst.usage, buffer::Usage::TRANSFER_DST, "`copy_buffer`");
+        let regions = regions.into_iter().collect::<Vec<_>>();
+        for region in &regions {
+            let region = region.borrow();
+            self.check_buffer_range(src, region.src .. region.src + region.size, "copy_buffer");
+            self.check_buffer_range(dst, region.dst .. region.dst + region.size, "copy_buffer");
+        }
+        self.track(vec![src.handle.clone(), dst.handle.clone()]);
+        self.raw.copy_buffer(&src.raw, &dst.raw, regions);
+    }
+
+    fn copy_image<T>(
+        &mut self,
+        src: &n::Image<B>,
+        src_layout: image::Layout,
+        dst: &n::Image<B>,
+        dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::ImageCopy>,
+    {
+        self.validate("copy_image", Scope::Outside);
+        self.copy_image_checks(Some(src), Some(dst), "copy_image");
+        self.raw.copy_image(&src.raw, src_layout, &dst.raw, dst_layout, regions);
+    }
+
+    fn copy_buffer_to_image<T>(
+        &mut self,
+        src: &n::Buffer<B>,
+        dst: &n::Image<B>,
+        dst_layout: image::Layout,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::BufferImageCopy>,
+    {
+        self.validate("copy_buffer_to_image", Scope::Outside);
+        check_usage!(self.context, src.handle, src.usage, buffer::Usage::TRANSFER_SRC, "`copy_buffer_to_image`");
+        self.copy_image_checks(None, Some(dst), "copy_buffer_to_image");
+        self.track(Some(src.handle.clone()));
+        self.raw.copy_buffer_to_image(&src.raw, &dst.raw, dst_layout, regions);
+    }
+
+    fn copy_image_to_buffer<T>(
+        &mut self,
+        src: &n::Image<B>,
+        src_layout: image::Layout,
+        dst: &n::Buffer<B>,
+        regions: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<com::BufferImageCopy>,
+    {
+        self.validate("copy_image_to_buffer", Scope::Outside);
+        self.copy_image_checks(Some(src), None, "copy_image_to_buffer");
+        check_usage!(self.context, dst.handle, dst.usage, buffer::Usage::TRANSFER_DST, "`copy_image_to_buffer`");
+        self.track(Some(dst.handle.clone()));
+        self.raw.copy_image_to_buffer(&src.raw, src_layout, &dst.raw, regions);
+    }
+
+    fn draw(&mut self, vertices: Range<VertexCount>, instances: Range<InstanceCount>) {
+        self.check_draw("draw");
+        self.raw.draw(vertices, instances);
+    }
+
+    fn draw_indexed(
+        &mut self,
+        indices: Range<IndexCount>,
+        base_vertex: VertexOffset,
+        instances: Range<InstanceCount>,
+    ) {
+        self.check_draw("draw_indexed");
+        self.raw.draw_indexed(indices, base_vertex, instances);
+    }
+
+    fn draw_indirect(
+        &mut self,
+        buffer: &n::Buffer<B>,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: u32,
+    ) {
+        self.check_draw("draw_indirect");
+        check_usage!(self.context, buffer.handle, buffer.usage, buffer::Usage::INDIRECT, "`draw_indirect`");
+        self.track(Some(buffer.handle.clone()));
+        self.raw.draw_indirect(&buffer.raw, offset, draw_count, stride);
+    }
+
+    fn draw_indexed_indirect(
+        &mut self,
+        buffer: &n::Buffer<B>,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: u32,
+    ) {
+        self.check_draw("draw_indexed_indirect");
+        check_usage!(self.context, buffer.handle, buffer.usage, buffer::Usage::INDIRECT, "`draw_indexed_indirect`");
+        self.track(Some(buffer.handle.clone()));
+        self.raw.draw_indexed_indirect(&buffer.raw, offset, draw_count, stride);
+    }
+
+    fn begin_query(&mut self, query: query::Query<Backend<B>>, flags: query::QueryControl) {
+        self.validate("begin_query", Scope::Anywhere);
+        self.check_query(query.pool, query.id .. query.id + 1, "begin_query");
+        self.track(Some(query.pool.handle.clone()));
+        self.raw.begin_query(query::Query { pool: &query.pool.raw, id: query.id }, flags);
+    }
+
+    fn end_query(&mut self, query: query::Query<Backend<B>>) {
+        self.validate("end_query", Scope::Anywhere);
+        self.check_query(query.pool, query.id .. query.id + 1, "end_query");
+        self.raw.end_query(query::Query { pool: &query.pool.raw, id: query.id });
+    }
+
+    fn reset_query_pool(&mut self, pool: &n::QueryPool<B>, queries: Range<query::QueryId>) {
+        self.validate("reset_query_pool", Scope::Outside);
+        self.check_query(pool, queries.clone(), "reset_query_pool");
+        self.track(Some(pool.handle.clone()));
+        self.raw.reset_query_pool(&pool.raw, queries);
+    }
+
+    fn write_timestamp(&mut self, stage: pso::PipelineStage, query: query::Query<Backend<B>>) {
+        self.validate("write_timestamp", Scope::Anywhere);
+        self.check_query(query.pool, query.id .. query.id + 1, "write_timestamp");
+        self.track(Some(query.pool.handle.clone()));
+        self.raw.write_timestamp(stage, query::Query { pool: &query.pool.raw, id: query.id });
+    }
+
+    fn copy_query_pool_results(
+        &mut self,
+        pool: &n::QueryPool<B>,
+        queries: Range<query::QueryId>,
+        buffer: &n::Buffer<B>,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) {
+        self.validate("copy_query_pool_results", Scope::Outside);
+        self.check_query(pool, queries.clone(), "copy_query_pool_results");
+        check_usage!(self.context, buffer.handle, buffer.usage, buffer::Usage::TRANSFER_DST, "`copy_query_pool_results`");
+        self.track(vec![pool.handle.clone(), buffer.handle.clone()]);
+        self.raw.copy_query_pool_results(&pool.raw, queries, &buffer.raw, offset, stride, flags);
+    }
+
+    fn push_graphics_constants(
+        &mut self,
+        layout: &n::PipelineLayout<B>,
+        stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.validate("push_graphics_constants", Scope::Anywhere);
+        self.track(Some(layout.handle.clone()));
+        self.raw.push_graphics_constants(&layout.raw, stages, offset, constants);
+    }
+
+    fn push_compute_constants(
+        &mut self,
+        layout: &n::PipelineLayout<B>,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.validate("push_compute_constants", Scope::Anywhere);
+        self.track(Some(layout.handle.clone()));
+        self.raw.push_compute_constants(&layout.raw, offset, constants);
+    }
+
+    fn execute_commands<I>(&mut self, buffers: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<CommandBuffer<B>>,
+    {
+        self.validate("execute_commands", Scope::Anywhere);
+        let buffers = buffers.into_iter().collect::<Vec<_>>();
+        for buffer in &buffers {
+            let buffer = buffer.borrow();
+            if buffer.level != com::RawLevel::Secondary {
+                report!(self.context, "`execute_commands` is given a primary command buffer");
+            }
+            let resources = {
+                let state = buffer.state.lock().unwrap();
+                let mut resources = state.resources.clone();
+                resources.push(buffer.pool.clone());
+                resources
+            };
+            self.track(resources);
+        }
+        self.raw.execute_commands(buffers.iter().map(|buffer| &buffer.borrow().raw));
+    }
+}
+
+impl<B: hal::Backend> CommandBuffer<B> {
+    /// Resources to keep alive while a submission of the command buffer is pending.
+    pub(crate) fn submission_resources(&self) -> Result<Vec<Handle>, &'static str> {
+        let state = self.state.lock().unwrap();
+        if state.recording {
+            return Err("is still recording");
+        }
+        if self.level != com::RawLevel::Primary {
+            return Err("is not a primary command buffer");
+        }
+        let mut resources = state.resources.clone();
+        resources.push(self.pool.clone());
+        Ok(resources)
+    }
+
+    pub(crate) fn raw(&self) -> &B::CommandBuffer {
+        &self.raw
+    }
+}
+
+/// Command pool of the wrapped backend.
+#[derive(Debug)]
+pub struct CommandPool<B: hal::Backend> {
+    pub(crate) raw: B::CommandPool,
+    pub(crate) handle: Handle,
+    context: Arc<Context>,
+}
+
+impl<B: hal::Backend> CommandPool<B> {
+    pub(crate) fn new(raw: B::CommandPool, context: Arc<Context>) -> Self {
+        CommandPool {
+            raw,
+            handle: Handle::new("CommandPool"),
+            context,
+        }
+    }
+}
+
+impl<B: hal::Backend> pool::RawCommandPool<Backend<B>> for CommandPool<B> {
+    fn reset(&mut self) {
+        self.raw.reset();
+    }
+
+    fn allocate(&mut self, num: usize, level: com::RawLevel) -> Vec<CommandBuffer<B>> {
+        self.raw
+            .allocate(num, level)
+            .into_iter()
+            .map(|raw| CommandBuffer {
+                raw,
+                context: self.context.clone(),
+                pool: self.handle.clone(),
+                level,
+                state: Arc::new(Mutex::new(State::default())),
+            })
+            .collect()
+    }
+
+    unsafe fn free(&mut self, buffers: Vec<CommandBuffer<B>>) {
+        self.raw.free(buffers.into_iter().map(|buffer| buffer.raw).collect());
+    }
+}
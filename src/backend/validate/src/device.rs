@@ -0,0 +1,1034 @@
+use std::borrow::Borrow;
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use hal::{self, buffer, error, format, image, mapping, pass, pool, pso, query, window};
+use hal::Device as _;
+use hal::device::{BindError, FramebufferError, OutOfMemory, ShaderError, WaitFor};
+use hal::memory::{Properties, Requirements, SparseFlags};
+use hal::queue::QueueFamilyId;
+use hal::range::RangeArg;
+
+use command::CommandPool;
+use native as n;
+use track::{Context, Handle};
+use {Backend, Surface, Swapchain};
+
+
+/// Resolve the bounds of a range argument over a resource of `size` bytes.
+fn resolve_range<R: RangeArg<u64>>(range: &R, size: u64) -> Range<u64> {
+    range.start().cloned().unwrap_or(0) .. range.end().cloned().unwrap_or(size)
+}
+
+/// Find the layout binding of the descriptor at `index` in `binding`, moving
+/// on to the next bindings if the array of `binding` is exceeded.
+fn locate_descriptor<'a>(
+    bindings: &'a [pso::DescriptorSetLayoutBinding],
+    binding: &mut pso::DescriptorBinding,
+    index: &mut pso::DescriptorArrayIndex,
+) -> Option<&'a pso::DescriptorSetLayoutBinding> {
+    loop {
+        let layout_binding = bindings.iter().find(|b| b.binding == *binding)?;
+        if *index < layout_binding.count {
+            return Some(layout_binding);
+        }
+        *index -= layout_binding.count;
+        *binding += 1;
+    }
+}
+
+/// Device of the wrapped backend.
+#[derive(Debug)]
+pub struct Device<B: hal::Backend> {
+    raw: B::Device,
+    context: Arc<Context>,
+    memory_properties: hal::MemoryProperties,
+}
+
+impl<B: hal::Backend> Device<B> {
+    pub(crate) fn new(raw: B::Device, context: Arc<Context>, memory_properties: hal::MemoryProperties) -> Self {
+        Device {
+            raw,
+            context,
+            memory_properties,
+        }
+    }
+
+    fn check_binding(&self, memory: &n::Memory<B>, offset: u64, requirements: Requirements, resource: &str) {
+        if requirements.type_mask & (1 << memory.type_id.0) == 0 {
+            report!(self.context, "{} bound to {}, which has an incompatible memory type {}",
+                resource, memory.handle, memory.type_id.0);
+        }
+        if offset & (requirements.alignment - 1) != 0 {
+            report!(self.context, "{} bound at offset {}, which is not aligned to {}",
+                resource, offset, requirements.alignment);
+        }
+        if offset + requirements.size > memory.size {
+            report!(self.context, "{} bound to the range {:?} of {}, which is only {} bytes long",
+                resource, offset .. offset + requirements.size, memory.handle, memory.size);
+        }
+    }
+
+    fn check_mapped_range<R: RangeArg<u64>>(&self, memory: &n::Memory<B>, range: &R) {
+        if !memory.mapped.load(Ordering::Acquire) {
+            report!(self.context, "{} is accessed through a mapping while not mapped", memory.handle);
+        }
+        let range = resolve_range(range, memory.size);
+        if range.start > range.end || range.end > memory.size {
+            report!(self.context, "The range {:?} of {} is outside of the allocation of {} bytes",
+                range, memory.handle, memory.size);
+        }
+    }
+
+    /// Check a descriptor written to a binding of type `ty`, returning the handles it references.
+    fn check_descriptor(&self, descriptor: &pso::Descriptor<Backend<B>>, ty: pso::DescriptorType) -> Vec<Handle> {
+        use hal::pso::DescriptorType as Dt;
+
+        let (compatible, handles) = match *descriptor {
+            pso::Descriptor::Sampler(sampler) => {
+                (ty == Dt::Sampler, vec![sampler.handle.clone()])
+            }
+            pso::Descriptor::Image(view, _) => {
+                let required = match ty {
+                    Dt::SampledImage => Some(image::Usage::SAMPLED),
+                    Dt::StorageImage => Some(image::Usage::STORAGE),
+                    Dt::InputAttachment => Some(image::Usage::INPUT_ATTACHMENT),
+                    _ => None,
+                };
+                if let Some(required) = required {
+                    check_usage!(self.context, view.handle, view.usage, required, format!("a {:?} descriptor", ty));
+                }
+                (required.is_some(), vec![view.handle.clone()])
+            }
+            pso::Descriptor::CombinedImageSampler(view, _, sampler) => {
+                if ty == Dt::CombinedImageSampler {
+                    check_usage!(self.context, view.handle, view.usage, image::Usage::SAMPLED, "a combined image sampler descriptor");
+                }
+                (ty == Dt::CombinedImageSampler, vec![view.handle.clone(), sampler.handle.clone()])
+            }
+            pso::Descriptor::Buffer(buffer, ref range) => {
+                let required = match ty {
+                    Dt::UniformBuffer | Dt::UniformBufferDynamic => Some(buffer::Usage::UNIFORM),
+                    Dt::StorageBuffer | Dt::StorageBufferDynamic => Some(buffer::Usage::STORAGE),
+                    _ => None,
+                };
+                if let Some(required) = required {
+                    check_usage!(self.context, buffer.handle, buffer.usage, required, format!("a {:?} descriptor", ty));
+                }
+                let range = range.start.unwrap_or(0) .. range.end.unwrap_or(buffer.size);
+                if range.start > range.end || range.end > buffer.size {
+                    report!(self.context, "Descriptor range {:?} is outside of {}, which is {} bytes long",
+                        range, buffer.handle, buffer.size);
+                }
+                (required.is_some(), vec![buffer.handle.clone()])
+            }
+            pso::Descriptor::UniformTexelBuffer(view) => {
+                if ty == Dt::UniformTexelBuffer {
+                    check_usage!(self.context, view.handle, view.usage, buffer::Usage::UNIFORM_TEXEL, "a uniform texel buffer descriptor");
+                }
+                (ty == Dt::UniformTexelBuffer, vec![view.handle.clone()])
+            }
+            pso::Descriptor::StorageTexelBuffer(view) => {
+                if ty == Dt::StorageTexelBuffer {
+                    check_usage!(self.context, view.handle, view.usage, buffer::Usage::STORAGE_TEXEL, "a storage texel buffer descriptor");
+                }
+                (ty == Dt::StorageTexelBuffer, vec![view.handle.clone()])
+            }
+        };
+
+        if !compatible {
+            let name = match *descriptor {
+                pso::Descriptor::Sampler(..) => "Sampler",
+                pso::Descriptor::Image(..) => "Image",
+                pso::Descriptor::CombinedImageSampler(..) => "CombinedImageSampler",
+                pso::Descriptor::Buffer(..) => "Buffer",
+                pso::Descriptor::UniformTexelBuffer(..) => "UniformTexelBuffer",
+                pso::Descriptor::StorageTexelBuffer(..) => "StorageTexelBuffer",
+            };
+            report!(self.context, "{} descriptor written to a binding of type {:?}", name, ty);
+        }
+        handles
+    }
+
+    fn retire_fences(&self, fences: &[&n::Fence<B>], wait: WaitFor) {
+        for fence in fences {
+            let signaled = match wait {
+                WaitFor::All => true,
+                WaitFor::Any => self.raw.get_fence_status(&fence.raw),
+            };
+            if signaled {
+                self.context.retire_fence(&fence.handle);
+            }
+        }
+    }
+}
+
+impl<B: hal::Backend> hal::Device<Backend<B>> for Device<B> {
+    fn allocate_memory(&self, memory_type: hal::MemoryTypeId, size: u64) -> Result<n::Memory<B>, OutOfMemory> {
+        let properties = match self.memory_properties.memory_types.get(memory_type.0) {
+            Some(ty) => ty.properties,
+            None => {
+                report!(self.context, "Memory type {} doesn't exist", memory_type.0);
+                Properties::empty()
+            }
+        };
+        let raw = self.raw.allocate_memory(memory_type, size)?;
+        Ok(n::Memory {
+            raw,
+            handle: Handle::new("Memory"),
+            type_id: memory_type,
+            properties,
+            size,
+            mapped: AtomicBool::new(false),
+        })
+    }
+
+    fn free_memory(&self, memory: n::Memory<B>) {
+        self.context.destroy(&memory.handle);
+        self.raw.free_memory(memory.raw);
+    }
+
+    fn create_command_pool(
+        &self, family: QueueFamilyId, create_flags: pool::CommandPoolCreateFlags
+    ) -> CommandPool<B> {
+        let raw = self.raw.create_command_pool(family, create_flags);
+        CommandPool::new(raw, self.context.clone())
+    }
+
+    fn destroy_command_pool(&self, pool: CommandPool<B>) {
+        self.context.destroy(&pool.handle);
+        self.raw.destroy_command_pool(pool.raw);
+    }
+
+    fn create_render_pass<'a, IA, IS, ID>(
+        &self,
+        attachments: IA,
+        subpasses: IS,
+        dependencies: ID,
+    ) -> n::RenderPass<B>
+    where
+        IA: IntoIterator,
+        IA::Item: Borrow<pass::Attachment>,
+        IS: IntoIterator,
+        IS::Item: Borrow<pass::SubpassDesc<'a>>,
+        ID: IntoIterator,
+        ID::Item: Borrow<pass::SubpassDependency>,
+    {
+        let attachments = attachments.into_iter().collect::<Vec<_>>();
+        let subpasses = subpasses.into_iter().collect::<Vec<_>>();
+
+        let info = n::RenderPassInfo {
+            attachments: attachments
+                .iter()
+                .map(|at| (at.borrow().format, at.borrow().samples))
+                .collect(),
+            subpasses: subpasses
+                .iter()
+                .map(|sp| {
+                    let sp = sp.borrow();
+                    n::SubpassInfo {
+                        colors: sp.colors.iter().map(|&(id, _)| id).collect(),
+                        depth_stencil: sp.depth_stencil.map(|&(id, _)| id),
+                        inputs: sp.inputs.iter().map(|&(id, _)| id).collect(),
+                        resolves: sp.resolves.iter().map(|&(id, _)| id).collect(),
+                    }
+                })
+                .collect(),
+        };
+        for (i, sp) in info.subpasses.iter().enumerate() {
+            let ids = sp.colors.iter().chain(&sp.depth_stencil).chain(&sp.inputs).chain(&sp.resolves);
+            for &id in ids {
+                if id >= info.attachments.len() {
+                    report!(self.context, "Subpass {} references the attachment {}, but the render pass only has {}",
+                        i, id, info.attachments.len());
+                }
+            }
+            if !sp.resolves.is_empty() && sp.resolves.len() != sp.colors.len() {
+                report!(self.context, "Subpass {} has {} resolve attachments for {} color attachments",
+                    i, sp.resolves.len(), sp.colors.len());
+            }
+        }
+
+        let raw = self.raw.create_render_pass(attachments, subpasses, dependencies);
+        n::RenderPass {
+            raw,
+            handle: Handle::new("RenderPass"),
+            info: Arc::new(info),
+        }
+    }
+
+    fn destroy_render_pass(&self, rp: n::RenderPass<B>) {
+        self.context.destroy(&rp.handle);
+        self.raw.destroy_render_pass(rp.raw);
+    }
+
+    fn create_pipeline_layout<IS, IR>(
+        &self,
+        set_layouts: IS,
+        push_constant: IR,
+    ) -> n::PipelineLayout<B>
+    where
+        IS: IntoIterator,
+        IS::Item: Borrow<n::DescriptorSetLayout<B>>,
+        IR: IntoIterator,
+        IR::Item: Borrow<(pso::ShaderStageFlags, Range<u32>)>,
+    {
+        let set_layouts = set_layouts.into_iter().collect::<Vec<_>>();
+        let raw = self.raw.create_pipeline_layout(
+            set_layouts.iter().map(|layout| &layout.borrow().raw),
+            push_constant,
+        );
+        n::PipelineLayout {
+            raw,
+            handle: Handle::new("PipelineLayout"),
+        }
+    }
+
+    fn destroy_pipeline_layout(&self, layout: n::PipelineLayout<B>) {
+        self.context.destroy(&layout.handle);
+        self.raw.destroy_pipeline_layout(layout.raw);
+    }
+
+    fn create_pipeline_cache(&self, data: Option<&[u8]>) -> n::PipelineCache<B> {
+        n::PipelineCache {
+            raw: self.raw.create_pipeline_cache(data),
+        }
+    }
+
+    fn get_pipeline_cache_data(&self, cache: &n::PipelineCache<B>) -> Result<Vec<u8>, OutOfMemory> {
+        self.raw.get_pipeline_cache_data(&cache.raw)
+    }
+
+    fn merge_pipeline_caches<I>(&self, target: &n::PipelineCache<B>, sources: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::PipelineCache<B>>,
+    {
+        let sources = sources.into_iter().collect::<Vec<_>>();
+        self.raw.merge_pipeline_caches(&target.raw, sources.iter().map(|cache| &cache.borrow().raw));
+    }
+
+    fn destroy_pipeline_cache(&self, cache: n::PipelineCache<B>) {
+        self.raw.destroy_pipeline_cache(cache.raw);
+    }
+
+    fn create_graphics_pipelines<'a, I>(
+        &self, descs: I, cache: Option<&n::PipelineCache<B>>
+    ) -> Vec<Result<n::GraphicsPipeline<B>, pso::CreationError>>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::GraphicsPipelineDesc<'a, Backend<B>>>,
+    {
+        fn map_entry<'a, B: hal::Backend>(entry: &pso::EntryPoint<'a, Backend<B>>) -> pso::EntryPoint<'a, B> {
+            pso::EntryPoint {
+                entry: entry.entry,
+                module: &entry.module.raw,
+                specialization: entry.specialization,
+            }
+        }
+
+        let descs = descs.into_iter().collect::<Vec<_>>();
+        let raw_descs = descs
+            .iter()
+            .map(|desc| {
+                let desc = desc.borrow();
+                if desc.subpass.index >= desc.subpass.main_pass.info.subpasses.len() {
+                    report!(self.context, "Graphics pipeline created for the subpass {} of {}, which only has {}",
+                        desc.subpass.index, desc.subpass.main_pass.handle, desc.subpass.main_pass.info.subpasses.len());
+                }
+                pso::GraphicsPipelineDesc {
+                    shaders: pso::GraphicsShaderSet {
+                        vertex: map_entry(&desc.shaders.vertex),
+                        hull: desc.shaders.hull.as_ref().map(map_entry),
+                        domain: desc.shaders.domain.as_ref().map(map_entry),
+                        geometry: desc.shaders.geometry.as_ref().map(map_entry),
+                        fragment: desc.shaders.fragment.as_ref().map(map_entry),
+                    },
+                    rasterizer: desc.rasterizer.clone(),
+                    vertex_buffers: desc.vertex_buffers.clone(),
+                    attributes: desc.attributes.clone(),
+                    input_assembler: desc.input_assembler.clone(),
+                    blender: desc.blender.clone(),
+                    depth_stencil: desc.depth_stencil,
+                    multisampling: desc.multisampling.clone(),
+                    baked_states: desc.baked_states.clone(),
+                    layout: &desc.layout.raw,
+                    subpass: pass::Subpass {
+                        index: desc.subpass.index,
+                        main_pass: &desc.subpass.main_pass.raw,
+                    },
+                    flags: desc.flags,
+                    parent: match desc.parent {
+                        pso::BasePipeline::Pipeline(pipeline) => pso::BasePipeline::Pipeline(&pipeline.raw),
+                        pso::BasePipeline::Index(index) => pso::BasePipeline::Index(index),
+                        pso::BasePipeline::None => pso::BasePipeline::None,
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.raw
+            .create_graphics_pipelines(raw_descs, cache.map(|cache| &cache.raw))
+            .into_iter()
+            .zip(&descs)
+            .map(|(result, desc)| {
+                let subpass = &desc.borrow().subpass;
+                result.map(|raw| n::GraphicsPipeline {
+                    raw,
+                    handle: Handle::new("GraphicsPipeline"),
+                    pass: subpass.main_pass.info.clone(),
+                    subpass: subpass.index,
+                })
+            })
+            .collect()
+    }
+
+    fn destroy_graphics_pipeline(&self, pipeline: n::GraphicsPipeline<B>) {
+        self.context.destroy(&pipeline.handle);
+        self.raw.destroy_graphics_pipeline(pipeline.raw);
+    }
+
+    fn create_compute_pipelines<'a, I>(
+        &self, descs: I, cache: Option<&n::PipelineCache<B>>
+    ) -> Vec<Result<n::ComputePipeline<B>, pso::CreationError>>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::ComputePipelineDesc<'a, Backend<B>>>,
+    {
+        let descs = descs.into_iter().collect::<Vec<_>>();
+        let raw_descs = descs
+            .iter()
+            .map(|desc| {
+                let desc = desc.borrow();
+                pso::ComputePipelineDesc {
+                    shader: pso::EntryPoint {
+                        entry: desc.shader.entry,
+                        module: &desc.shader.module.raw,
+                        specialization: desc.shader.specialization,
+                    },
+                    layout: &desc.layout.raw,
+                    flags: desc.flags,
+                    parent: match desc.parent {
+                        pso::BasePipeline::Pipeline(pipeline) => pso::BasePipeline::Pipeline(&pipeline.raw),
+                        pso::BasePipeline::Index(index) => pso::BasePipeline::Index(index),
+                        pso::BasePipeline::None => pso::BasePipeline::None,
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.raw
+            .create_compute_pipelines(raw_descs, cache.map(|cache| &cache.raw))
+            .into_iter()
+            .map(|result| result.map(|raw| n::ComputePipeline {
+                raw,
+                handle: Handle::new("ComputePipeline"),
+            }))
+            .collect()
+    }
+
+    fn destroy_compute_pipeline(&self, pipeline: n::ComputePipeline<B>) {
+        self.context.destroy(&pipeline.handle);
+        self.raw.destroy_compute_pipeline(pipeline.raw);
+    }
+
+    fn create_framebuffer<I>(
+        &self,
+        pass: &n::RenderPass<B>,
+        attachments: I,
+        extent: image::Extent,
+    ) -> Result<n::Framebuffer<B>, FramebufferError>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::ImageView<B>>,
+    {
+        let attachments = attachments.into_iter().collect::<Vec<_>>();
+        if attachments.len() != pass.info.attachments.len() {
+            report!(self.context, "Framebuffer created with {} attachments for {}, which has {}",
+                attachments.len(), pass.handle, pass.info.attachments.len());
+        }
+        for (view, &(format, _)) in attachments.iter().zip(&pass.info.attachments) {
+            let view = view.borrow();
+            match format {
+                Some(format) if format != view.format => {
+                    report!(self.context, "{} has the format {:?}, but the render pass attachment expects {:?}",
+                        view.handle, view.format, format);
+                }
+                _ => {}
+            }
+            let required = if view.format.is_depth() || view.format.is_stencil() {
+                image::Usage::DEPTH_STENCIL_ATTACHMENT
+            } else {
+                image::Usage::COLOR_ATTACHMENT
+            };
+            check_usage!(self.context, view.handle, view.usage, required, "a framebuffer attachment");
+        }
+
+        let raw = self.raw.create_framebuffer(
+            &pass.raw,
+            attachments.iter().map(|view| &view.borrow().raw),
+            extent,
+        )?;
+        Ok(n::Framebuffer {
+            raw,
+            handle: Handle::with_deps(
+                "Framebuffer",
+                attachments.iter().map(|view| view.borrow().handle.clone()).collect(),
+            ),
+            pass: Some(pass.info.clone()),
+        })
+    }
+
+    fn destroy_framebuffer(&self, buf: n::Framebuffer<B>) {
+        self.context.destroy(&buf.handle);
+        self.raw.destroy_framebuffer(buf.raw);
+    }
+
+    fn create_shader_module(&self, spirv_data: &[u8]) -> Result<n::ShaderModule<B>, ShaderError> {
+        let raw = self.raw.create_shader_module(spirv_data)?;
+        Ok(n::ShaderModule { raw })
+    }
+
+    fn destroy_shader_module(&self, shader: n::ShaderModule<B>) {
+        self.raw.destroy_shader_module(shader.raw);
+    }
+
+    fn create_buffer(
+        &self, size: u64, usage: buffer::Usage,
+    ) -> Result<n::UnboundBuffer<B>, buffer::CreationError> {
+        let raw = self.raw.create_buffer(size, usage)?;
+        Ok(n::UnboundBuffer { raw, size, usage })
+    }
+
+    fn get_buffer_requirements(&self, buf: &n::UnboundBuffer<B>) -> Requirements {
+        self.raw.get_buffer_requirements(&buf.raw)
+    }
+
+    fn bind_buffer_memory(
+        &self, memory: &n::Memory<B>, offset: u64, buf: n::UnboundBuffer<B>
+    ) -> Result<n::Buffer<B>, BindError> {
+        let requirements = self.raw.get_buffer_requirements(&buf.raw);
+        self.check_binding(memory, offset, requirements, "Buffer");
+        let raw = self.raw.bind_buffer_memory(&memory.raw, offset, buf.raw)?;
+        Ok(n::Buffer {
+            raw,
+            handle: Handle::with_deps("Buffer", vec![memory.handle.clone()]),
+            size: buf.size,
+            usage: buf.usage,
+        })
+    }
+
+    fn destroy_buffer(&self, buffer: n::Buffer<B>) {
+        self.context.destroy(&buffer.handle);
+        self.raw.destroy_buffer(buffer.raw);
+    }
+
+    fn create_buffer_view<R: RangeArg<u64>>(
+        &self, buf: &n::Buffer<B>, fmt: Option<format::Format>, range: R
+    ) -> Result<n::BufferView<B>, buffer::ViewCreationError> {
+        if !buf.usage.intersects(buffer::Usage::UNIFORM_TEXEL | buffer::Usage::STORAGE_TEXEL) {
+            report!(self.context, "Buffer view created for {}, which lacks the UNIFORM_TEXEL and STORAGE_TEXEL usages",
+                buf.handle);
+        }
+        let bounds = resolve_range(&range, buf.size);
+        if bounds.start > bounds.end || bounds.end > buf.size {
+            report!(self.context, "Buffer view range {:?} is outside of {}, which is {} bytes long",
+                bounds, buf.handle, buf.size);
+        }
+        let raw = self.raw.create_buffer_view(&buf.raw, fmt, range)?;
+        Ok(n::BufferView {
+            raw,
+            handle: Handle::with_deps("BufferView", vec![buf.handle.clone()]),
+            usage: buf.usage,
+        })
+    }
+
+    fn destroy_buffer_view(&self, view: n::BufferView<B>) {
+        self.context.destroy(&view.handle);
+        self.raw.destroy_buffer_view(view.raw);
+    }
+
+    fn create_image(
+        &self, kind: image::Kind, mip_levels: image::Level, format: format::Format,
+        tiling: image::Tiling, usage: image::Usage, storage_flags: image::StorageFlags,
+    ) -> Result<n::UnboundImage<B>, image::CreationError> {
+        let raw = self.raw.create_image(kind, mip_levels, format, tiling, usage, storage_flags)?;
+        Ok(n::UnboundImage { raw, usage })
+    }
+
+    fn get_image_requirements(&self, image: &n::UnboundImage<B>) -> Requirements {
+        self.raw.get_image_requirements(&image.raw)
+    }
+
+    fn get_image_subresource_footprint(
+        &self, image: &n::Image<B>, subresource: image::Subresource
+    ) -> image::SubresourceFootprint {
+        self.raw.get_image_subresource_footprint(&image.raw, subresource)
+    }
+
+    fn bind_image_memory(
+        &self, memory: &n::Memory<B>, offset: u64, image: n::UnboundImage<B>
+    ) -> Result<n::Image<B>, BindError> {
+        let requirements = self.raw.get_image_requirements(&image.raw);
+        self.check_binding(memory, offset, requirements, "Image");
+        let raw = self.raw.bind_image_memory(&memory.raw, offset, image.raw)?;
+        Ok(n::Image {
+            raw,
+            handle: Handle::with_deps("Image", vec![memory.handle.clone()]),
+            usage: image.usage,
+        })
+    }
+
+    fn destroy_image(&self, image: n::Image<B>) {
+        self.context.destroy(&image.handle);
+        self.raw.destroy_image(image.raw);
+    }
+
+    fn create_sparse_buffer(
+        &self, size: u64, usage: buffer::Usage, sparse: SparseFlags,
+    ) -> Result<n::Buffer<B>, buffer::CreationError> {
+        let raw = self.raw.create_sparse_buffer(size, usage, sparse)?;
+        Ok(n::Buffer {
+            raw,
+            handle: Handle::new("Buffer"),
+            size,
+            usage,
+        })
+    }
+
+    fn get_sparse_buffer_requirements(&self, buffer: &n::Buffer<B>) -> Requirements {
+        self.raw.get_sparse_buffer_requirements(&buffer.raw)
+    }
+
+    fn create_sparse_image(
+        &self, kind: image::Kind, mip_levels: image::Level, format: format::Format,
+        usage: image::Usage, storage_flags: image::StorageFlags, sparse: SparseFlags,
+    ) -> Result<n::Image<B>, image::CreationError> {
+        let raw = self.raw.create_sparse_image(kind, mip_levels, format, usage, storage_flags, sparse)?;
+        Ok(n::Image {
+            raw,
+            handle: Handle::new("Image"),
+            usage,
+        })
+    }
+
+    fn get_sparse_image_requirements(
+        &self, image: &n::Image<B>,
+    ) -> (Requirements, Vec<image::SparseRequirements>) {
+        self.raw.get_sparse_image_requirements(&image.raw)
+    }
+
+    fn create_image_view(
+        &self,
+        image: &n::Image<B>,
+        view_kind: image::ViewKind,
+        format: format::Format,
+        swizzle: format::Swizzle,
+        range: image::SubresourceRange,
+    ) -> Result<n::ImageView<B>, image::ViewError> {
+        let raw = self.raw.create_image_view(&image.raw, view_kind, format, swizzle, range)?;
+        Ok(n::ImageView {
+            raw,
+            handle: Handle::with_deps("ImageView", vec![image.handle.clone()]),
+            format,
+            usage: image.usage,
+        })
+    }
+
+    fn destroy_image_view(&self, view: n::ImageView<B>) {
+        self.context.destroy(&view.handle);
+        self.raw.destroy_image_view(view.raw);
+    }
+
+    fn create_sampler(&self, info: image::SamplerInfo) -> n::Sampler<B> {
+        n::Sampler {
+            raw: self.raw.create_sampler(info),
+            handle: Handle::new("Sampler"),
+        }
+    }
+
+    fn destroy_sampler(&self, sampler: n::Sampler<B>) {
+        self.context.destroy(&sampler.handle);
+        self.raw.destroy_sampler(sampler.raw);
+    }
+
+    fn create_descriptor_pool<I>(&self, max_sets: usize, descriptor_ranges: I) -> n::DescriptorPool<B>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorRangeDesc>,
+    {
+        n::DescriptorPool {
+            raw: self.raw.create_descriptor_pool(max_sets, descriptor_ranges),
+            context: self.context.clone(),
+            sets: Vec::new(),
+        }
+    }
+
+    fn destroy_descriptor_pool(&self, pool: n::DescriptorPool<B>) {
+        for handle in &pool.sets {
+            self.context.destroy(handle);
+        }
+        self.raw.destroy_descriptor_pool(pool.raw);
+    }
+
+    fn create_descriptor_set_layout<I, J>(
+        &self, bindings: I, immutable_samplers: J
+    ) -> n::DescriptorSetLayout<B>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetLayoutBinding>,
+        J: IntoIterator,
+        J::Item: Borrow<n::Sampler<B>>,
+    {
+        let bindings = bindings
+            .into_iter()
+            .map(|binding| binding.borrow().clone())
+            .collect::<Vec<_>>();
+        let immutable_samplers = immutable_samplers.into_iter().collect::<Vec<_>>();
+        let raw = self.raw.create_descriptor_set_layout(
+            &bindings,
+            immutable_samplers.iter().map(|sampler| &sampler.borrow().raw),
+        );
+        n::DescriptorSetLayout {
+            raw,
+            bindings: Arc::new(bindings),
+        }
+    }
+
+    fn destroy_descriptor_set_layout(&self, layout: n::DescriptorSetLayout<B>) {
+        self.raw.destroy_descriptor_set_layout(layout.raw);
+    }
+
+    fn write_descriptor_sets<'a, I, J>(&self, write_iter: I)
+    where
+        I: IntoIterator<Item = pso::DescriptorSetWrite<'a, Backend<B>, J>>,
+        J: IntoIterator,
+        J::Item: Borrow<pso::Descriptor<'a, Backend<B>>>,
+    {
+        let mut raw_writes = Vec::new();
+        for write in write_iter {
+            let mut binding = write.binding;
+            let mut index = write.array_offset;
+            let mut raw_descriptors = Vec::new();
+            let mut resources = Vec::new();
+
+            for descriptor in write.descriptors {
+                let descriptor = descriptor.borrow();
+                let ty = match locate_descriptor(&write.set.bindings, &mut binding, &mut index) {
+                    Some(layout_binding) => Some(layout_binding.ty),
+                    None => {
+                        report!(self.context, "Descriptor written to the binding {} of {}, which doesn't exist",
+                            binding, write.set.handle);
+                        None
+                    }
+                };
+                let handles = match ty {
+                    Some(ty) => self.check_descriptor(descriptor, ty),
+                    None => Vec::new(),
+                };
+                resources.push(((binding, index), handles));
+                index += 1;
+
+                raw_descriptors.push(match *descriptor {
+                    pso::Descriptor::Sampler(sampler) => pso::Descriptor::Sampler(&sampler.raw),
+                    pso::Descriptor::Image(view, layout) => pso::Descriptor::Image(&view.raw, layout),
+                    pso::Descriptor::CombinedImageSampler(view, layout, sampler) => {
+                        pso::Descriptor::CombinedImageSampler(&view.raw, layout, &sampler.raw)
+                    }
+                    pso::Descriptor::Buffer(buffer, ref range) => pso::Descriptor::Buffer(&buffer.raw, range.clone()),
+                    pso::Descriptor::UniformTexelBuffer(view) => pso::Descriptor::UniformTexelBuffer(&view.raw),
+                    pso::Descriptor::StorageTexelBuffer(view) => pso::Descriptor::StorageTexelBuffer(&view.raw),
+                });
+            }
+
+            write.set.resources.lock().unwrap().extend(resources);
+            raw_writes.push(pso::DescriptorSetWrite {
+                set: &write.set.raw,
+                binding: write.binding,
+                array_offset: write.array_offset,
+                descriptors: raw_descriptors,
+            });
+        }
+
+        self.raw.write_descriptor_sets(raw_writes);
+    }
+
+    fn copy_descriptor_sets<'a, I>(&self, copy_iter: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetCopy<'a, Backend<B>>>,
+    {
+        let copies = copy_iter.into_iter().collect::<Vec<_>>();
+        for copy in &copies {
+            let copy = copy.borrow();
+            let (mut src_binding, mut src_index) = (copy.src_binding, copy.src_array_offset);
+            let (mut dst_binding, mut dst_index) = (copy.dst_binding, copy.dst_array_offset);
+            let mut resources = Vec::new();
+
+            for _ in 0 .. copy.count {
+                let src = locate_descriptor(&copy.src_set.bindings, &mut src_binding, &mut src_index);
+                let dst = locate_descriptor(&copy.dst_set.bindings, &mut dst_binding, &mut dst_index);
+                match (src, dst) {
+                    (Some(src), Some(dst)) if src.ty != dst.ty => {
+                        report!(self.context, "Descriptor of type {:?} copied to a binding of type {:?}", src.ty, dst.ty);
+                    }
+                    (Some(_), Some(_)) => {
+                        let handles = copy.src_set.resources
+                            .lock()
+                            .unwrap()
+                            .get(&(src_binding, src_index))
+                            .cloned()
+                            .unwrap_or_default();
+                        resources.push(((dst_binding, dst_index), handles));
+                    }
+                    _ => {
+                        report!(self.context, "Descriptor copy from {} to {} exceeds the bindings of the sets",
+                            copy.src_set.handle, copy.dst_set.handle);
+                        break;
+                    }
+                }
+                src_index += 1;
+                dst_index += 1;
+            }
+
+            copy.dst_set.resources.lock().unwrap().extend(resources);
+        }
+
+        self.raw.copy_descriptor_sets(copies.iter().map(|copy| {
+            let copy = copy.borrow();
+            pso::DescriptorSetCopy {
+                src_set: &copy.src_set.raw,
+                src_binding: copy.src_binding,
+                src_array_offset: copy.src_array_offset,
+                dst_set: &copy.dst_set.raw,
+                dst_binding: copy.dst_binding,
+                dst_array_offset: copy.dst_array_offset,
+                count: copy.count,
+            }
+        }));
+    }
+
+    fn map_memory<R>(&self, memory: &n::Memory<B>, range: R) -> Result<*mut u8, mapping::Error>
+    where
+        R: RangeArg<u64>,
+    {
+        if !memory.properties.contains(Properties::CPU_VISIBLE) {
+            report!(self.context, "{} is mapped, but its memory type is not CPU visible", memory.handle);
+        }
+        if memory.mapped.swap(true, Ordering::AcqRel) {
+            report!(self.context, "{} is mapped while already mapped", memory.handle);
+        }
+        self.check_mapped_range(memory, &range);
+        self.raw.map_memory(&memory.raw, range)
+    }
+
+    fn flush_mapped_memory_ranges<'a, I, R>(&self, ranges: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a n::Memory<B>, R)>,
+        R: RangeArg<u64>,
+    {
+        let ranges = ranges.into_iter().collect::<Vec<_>>();
+        for range in &ranges {
+            let (memory, ref range) = *range.borrow();
+            self.check_mapped_range(memory, range);
+        }
+        self.raw.flush_mapped_memory_ranges(ranges.iter().map(|range| {
+            let (memory, ref range) = *range.borrow();
+            (&memory.raw, (range.start().cloned(), range.end().cloned()))
+        }));
+    }
+
+    fn invalidate_mapped_memory_ranges<'a, I, R>(&self, ranges: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<(&'a n::Memory<B>, R)>,
+        R: RangeArg<u64>,
+    {
+        let ranges = ranges.into_iter().collect::<Vec<_>>();
+        for range in &ranges {
+            let (memory, ref range) = *range.borrow();
+            self.check_mapped_range(memory, range);
+        }
+        self.raw.invalidate_mapped_memory_ranges(ranges.iter().map(|range| {
+            let (memory, ref range) = *range.borrow();
+            (&memory.raw, (range.start().cloned(), range.end().cloned()))
+        }));
+    }
+
+    fn unmap_memory(&self, memory: &n::Memory<B>) {
+        if !memory.mapped.swap(false, Ordering::AcqRel) {
+            report!(self.context, "{} is unmapped while not mapped", memory.handle);
+        }
+        self.raw.unmap_memory(&memory.raw);
+    }
+
+    fn create_semaphore(&self) -> n::Semaphore<B> {
+        n::Semaphore {
+            raw: self.raw.create_semaphore(),
+            handle: Handle::new("Semaphore"),
+        }
+    }
+
+    fn destroy_semaphore(&self, semaphore: n::Semaphore<B>) {
+        self.context.destroy(&semaphore.handle);
+        self.raw.destroy_semaphore(semaphore.raw);
+    }
+
+    fn create_fence(&self, signaled: bool) -> n::Fence<B> {
+        n::Fence {
+            raw: self.raw.create_fence(signaled),
+            handle: Handle::new("Fence"),
+        }
+    }
+
+    fn reset_fences<I>(&self, fences: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::Fence<B>>,
+    {
+        let fences = fences.into_iter().collect::<Vec<_>>();
+        for fence in &fences {
+            let fence = fence.borrow();
+            if self.context.is_fence_pending(&fence.handle) {
+                report!(self.context, "{} is reset while a pending submission is going to signal it", fence.handle);
+            }
+        }
+        self.raw.reset_fences(fences.iter().map(|fence| &fence.borrow().raw));
+    }
+
+    fn wait_for_fences<I>(&self, fences: I, wait: WaitFor, timeout_ns: u64) -> bool
+    where
+        I: IntoIterator,
+        I::Item: Borrow<n::Fence<B>>,
+    {
+        let fences = fences.into_iter().collect::<Vec<_>>();
+        let fences = fences.iter().map(|fence| fence.borrow()).collect::<Vec<_>>();
+        let signaled = self.raw.wait_for_fences(fences.iter().map(|fence| &fence.raw), wait, timeout_ns);
+        if signaled {
+            self.retire_fences(&fences, wait);
+        }
+        signaled
+    }
+
+    fn get_fence_status(&self, fence: &n::Fence<B>) -> bool {
+        let signaled = self.raw.get_fence_status(&fence.raw);
+        if signaled {
+            self.context.retire_fence(&fence.handle);
+        }
+        signaled
+    }
+
+    fn destroy_fence(&self, fence: n::Fence<B>) {
+        self.context.destroy(&fence.handle);
+        self.raw.destroy_fence(fence.raw);
+    }
+
+    fn create_event(&self) -> n::Event<B> {
+        n::Event {
+            raw: self.raw.create_event(),
+            handle: Handle::new("Event"),
+        }
+    }
+
+    fn get_event_status(&self, event: &n::Event<B>) -> bool {
+        self.raw.get_event_status(&event.raw)
+    }
+
+    fn set_event(&self, event: &n::Event<B>) {
+        self.raw.set_event(&event.raw)
+    }
+
+    fn reset_event(&self, event: &n::Event<B>) {
+        self.raw.reset_event(&event.raw)
+    }
+
+    fn destroy_event(&self, event: n::Event<B>) {
+        self.context.destroy(&event.handle);
+        self.raw.destroy_event(event.raw);
+    }
+
+    fn create_query_pool(
+        &self, ty: query::QueryType, count: query::QueryId
+    ) -> Result<n::QueryPool<B>, query::Error> {
+        Ok(n::QueryPool {
+            raw: self.raw.create_query_pool(ty, count)?,
+            handle: Handle::new("QueryPool"),
+            count,
+        })
+    }
+
+    fn destroy_query_pool(&self, pool: n::QueryPool<B>) {
+        self.context.destroy(&pool.handle);
+        self.raw.destroy_query_pool(pool.raw);
+    }
+
+    fn get_query_pool_results(
+        &self,
+        pool: &n::QueryPool<B>,
+        queries: Range<query::QueryId>,
+        data: &mut [u8],
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) -> Result<bool, error::HostExecutionError> {
+        if queries.end > pool.count {
+            report!(self.context, "Queries {:?} are outside of {}, which has {} queries",
+                queries, pool.handle, pool.count);
+        }
+        if queries.start < queries.end {
+            let value_size = if flags.contains(query::ResultFlags::BITS_64) { 8 } else { 4 };
+            let values = if flags.contains(query::ResultFlags::WITH_AVAILABILITY) { 2 } else { 1 };
+            let size = (queries.end - queries.start - 1) as u64 * stride + values * value_size;
+            if size > data.len() as u64 {
+                report!(self.context, "Query results need {} bytes, but only {} are provided", size, data.len());
+            }
+        }
+        self.raw.get_query_pool_results(&pool.raw, queries, data, stride, flags)
+    }
+
+    fn create_swapchain(
+        &self,
+        surface: &mut Surface<B>,
+        config: window::SwapchainConfig,
+        old_swapchain: Option<Swapchain<B>>,
+    ) -> (Swapchain<B>, window::Backbuffer<Backend<B>>) {
+        let usage = config.image_usage;
+        let old_swapchain = old_swapchain.map(|swapchain| {
+            self.context.destroy(&swapchain.handle);
+            swapchain.raw
+        });
+        let (raw, backbuffer) = self.raw.create_swapchain(&mut surface.raw, config, old_swapchain);
+
+        let handle = Handle::new("Swapchain");
+        let backbuffer = match backbuffer {
+            window::Backbuffer::Images(images) => window::Backbuffer::Images(
+                images
+                    .into_iter()
+                    .map(|raw| n::Image {
+                        raw,
+                        handle: Handle::with_deps("Image", vec![handle.clone()]),
+                        usage,
+                    })
+                    .collect(),
+            ),
+            window::Backbuffer::Framebuffer(raw) => window::Backbuffer::Framebuffer(n::Framebuffer {
+                raw,
+                handle: Handle::with_deps("Framebuffer", vec![handle.clone()]),
+                pass: None,
+            }),
+        };
+        (Swapchain { raw, handle }, backbuffer)
+    }
+
+    fn destroy_swapchain(&self, swapchain: Swapchain<B>) {
+        self.context.destroy(&swapchain.handle);
+        self.raw.destroy_swapchain(swapchain.raw);
+    }
+
+    fn wait_idle(&self) -> Result<(), error::HostExecutionError> {
+        self.raw.wait_idle()?;
+        self.context.retire_all();
+        Ok(())
+    }
+}
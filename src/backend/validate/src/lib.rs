@@ -0,0 +1,340 @@
+//! Validation layer, wrapping another backend.
+//!
+//! `Backend<B>` implements the HAL on top of any backend `B`, checking the API
+//! usage before forwarding the calls to it. This catches errors which would
+//! otherwise make some backends panic deep inside their implementation, or
+//! silently misbehave, regardless of the native validation available:
+//!
+//! - resources used in ways not covered by the `Usage` flags they were created with,
+//! - descriptors written with a type not matching their descriptor set layout,
+//! - commands recorded inside or outside of a render pass when they can't be,
+//! - draws using a pipeline created for an incompatible subpass,
+//! - memory mapped or bound outside of its allocation,
+//! - resources destroyed while a pending submission still references them.
+//!
+//! A submission is considered pending until its completion has been observed,
+//! either by a fence it signals or by waiting for the queue or the device to idle.
+//!
+//! ```ignore
+//! let instance = validate::Instance::new(back::Instance::create("app", 1), validate::ErrorAction::Panic);
+//! let surface = instance.wrap_surface(instance.raw().create_surface(&window));
+//! let adapters = instance.enumerate_adapters();
+//! ```
+
+#[macro_use]
+extern crate log;
+extern crate gfx_hal as hal;
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use hal::{error, format, image};
+use hal::QueueFamily as _;
+use hal::backend::RawQueueGroup;
+use hal::queue::{QueueFamilyId, Queues};
+
+/// Report a validation error to a context.
+macro_rules! report {
+    ($context:expr, $($arg:tt)*) => {
+        $context.report(format_args!($($arg)*))
+    };
+}
+
+/// Check that the `usage` of a resource contains `required`.
+macro_rules! check_usage {
+    ($context:expr, $resource:expr, $usage:expr, $required:expr, $operation:expr) => {
+        if !$usage.contains($required) {
+            report!($context, "{} is used for {} but lacks the {:?} usage", $resource, $operation, $required);
+        }
+    };
+}
+
+mod command;
+mod device;
+mod native;
+mod queue;
+mod track;
+mod window;
+
+pub use command::{CommandBuffer, CommandPool};
+pub use device::Device;
+pub use native::*;
+pub use queue::CommandQueue;
+pub use track::ErrorAction;
+pub use window::{Surface, Swapchain};
+
+use track::Context;
+
+
+/// Validation backend, wrapping the backend `B`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Backend<B>(PhantomData<B>);
+impl<B: hal::Backend> hal::Backend for Backend<B> {
+    type PhysicalDevice = PhysicalDevice<B>;
+    type Device = Device<B>;
+
+    type Surface = Surface<B>;
+    type Swapchain = Swapchain<B>;
+
+    type QueueFamily = QueueFamily<B>;
+    type CommandQueue = CommandQueue<B>;
+    type CommandBuffer = CommandBuffer<B>;
+
+    type Memory = Memory<B>;
+    type CommandPool = CommandPool<B>;
+
+    type ShaderModule = ShaderModule<B>;
+    type RenderPass = RenderPass<B>;
+    type Framebuffer = Framebuffer<B>;
+
+    type UnboundBuffer = UnboundBuffer<B>;
+    type Buffer = Buffer<B>;
+    type BufferView = BufferView<B>;
+    type UnboundImage = UnboundImage<B>;
+    type Image = Image<B>;
+    type ImageView = ImageView<B>;
+    type Sampler = Sampler<B>;
+
+    type ComputePipeline = ComputePipeline<B>;
+    type GraphicsPipeline = GraphicsPipeline<B>;
+    type PipelineCache = PipelineCache<B>;
+    type PipelineLayout = PipelineLayout<B>;
+    type DescriptorSetLayout = DescriptorSetLayout<B>;
+    type DescriptorPool = DescriptorPool<B>;
+    type DescriptorSet = DescriptorSet<B>;
+
+    type Fence = Fence<B>;
+    type Semaphore = Semaphore<B>;
+    type Event = Event<B>;
+    type QueryPool = QueryPool<B>;
+}
+
+/// Instance of the validation layer, wrapping an instance of another backend.
+pub struct Instance<I> {
+    raw: I,
+    action: ErrorAction,
+}
+
+impl<I: hal::Instance> Instance<I> {
+    /// Wrap an instance, handling the validation errors according to `action`.
+    pub fn new(raw: I, action: ErrorAction) -> Self {
+        Instance { raw, action }
+    }
+
+    /// Access the wrapped instance, e.g. to create surfaces.
+    pub fn raw(&self) -> &I {
+        &self.raw
+    }
+
+    /// Wrap a surface created by the wrapped instance.
+    pub fn wrap_surface(&self, raw: <I::Backend as hal::Backend>::Surface) -> Surface<I::Backend> {
+        Surface { raw }
+    }
+}
+
+impl<I: hal::Instance> hal::Instance for Instance<I> {
+    type Backend = Backend<I::Backend>;
+    fn enumerate_adapters(&self) -> Vec<hal::Adapter<Self::Backend>> {
+        self.raw
+            .enumerate_adapters()
+            .into_iter()
+            .map(|adapter| hal::Adapter {
+                info: adapter.info,
+                physical_device: PhysicalDevice {
+                    raw: adapter.physical_device,
+                    action: self.action,
+                },
+                queue_families: adapter.queue_families
+                    .into_iter()
+                    .map(|family| QueueFamily { raw: Arc::new(family) })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// Physical device of the wrapped backend.
+#[derive(Debug)]
+pub struct PhysicalDevice<B: hal::Backend> {
+    raw: B::PhysicalDevice,
+    action: ErrorAction,
+}
+
+impl<B: hal::Backend> hal::PhysicalDevice<Backend<B>> for PhysicalDevice<B> {
+    fn open(
+        &self, families: &[(&QueueFamily<B>, &[hal::QueuePriority])], requested_features: hal::Features,
+    ) -> Result<hal::Gpu<Backend<B>>, error::DeviceCreationError> {
+        let raw_families = families
+            .iter()
+            .map(|&(family, priorities)| (&*family.raw, priorities))
+            .collect::<Vec<_>>();
+        let hal::Gpu { device, mut queues } = self.raw.open(&raw_families, requested_features)?;
+
+        let context = Arc::new(Context::new(self.action));
+        let queue_groups = families
+            .iter()
+            .map(|&(family, _)| {
+                let mut group = RawQueueGroup::new(family.clone());
+                for raw in queues.take_raw(family.raw.id()).unwrap_or_default() {
+                    group.add_queue(CommandQueue::new(raw, context.clone()));
+                }
+                group
+            })
+            .collect();
+
+        Ok(hal::Gpu {
+            device: Device::new(device, context, self.raw.memory_properties()),
+            queues: Queues::new(queue_groups),
+        })
+    }
+
+    fn format_properties(&self, format: Option<format::Format>) -> format::Properties {
+        self.raw.format_properties(format)
+    }
+
+    fn image_format_properties(
+        &self, format: format::Format, dimensions: u8, tiling: image::Tiling,
+        usage: image::Usage, storage_flags: image::StorageFlags,
+    ) -> Option<image::FormatProperties> {
+        self.raw.image_format_properties(format, dimensions, tiling, usage, storage_flags)
+    }
+
+    fn memory_properties(&self) -> hal::MemoryProperties {
+        self.raw.memory_properties()
+    }
+
+    fn features(&self) -> hal::Features {
+        self.raw.features()
+    }
+
+    fn limits(&self) -> hal::Limits {
+        self.raw.limits()
+    }
+
+    fn timestamp_period(&self) -> f32 {
+        self.raw.timestamp_period()
+    }
+}
+
+/// Queue family of the wrapped backend.
+#[derive(Debug)]
+pub struct QueueFamily<B: hal::Backend> {
+    raw: Arc<B::QueueFamily>,
+}
+
+impl<B: hal::Backend> Clone for QueueFamily<B> {
+    fn clone(&self) -> Self {
+        QueueFamily { raw: self.raw.clone() }
+    }
+}
+
+impl<B: hal::Backend> hal::QueueFamily for QueueFamily<B> {
+    fn queue_type(&self) -> hal::QueueType {
+        self.raw.queue_type()
+    }
+    fn max_queues(&self) -> usize {
+        self.raw.max_queues()
+    }
+    fn id(&self) -> QueueFamilyId {
+        self.raw.id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate gfx_backend_empty as empty;
+
+    use hal::{buffer, command, pso, Device, Instance as _, MemoryTypeId};
+    use hal::pool::CommandPoolCreateFlags;
+    use hal::queue::{QueueGroup, Submission};
+    use {Backend, ErrorAction, Instance};
+
+    type Back = Backend<empty::Backend>;
+
+    fn open() -> (<Back as hal::Backend>::Device, QueueGroup<Back, hal::General>) {
+        let instance = Instance::new(empty::Instance::create("validate", 1), ErrorAction::Panic);
+        let mut adapter = instance.enumerate_adapters().remove(0);
+        adapter.open_with(1, |_| true).unwrap()
+    }
+
+    fn create_buffer(
+        device: &<Back as hal::Backend>::Device, size: u64, usage: buffer::Usage,
+    ) -> (<Back as hal::Backend>::Buffer, <Back as hal::Backend>::Memory) {
+        let unbound = device.create_buffer(size, usage).unwrap();
+        let requirements = device.get_buffer_requirements(&unbound);
+        let memory = device.allocate_memory(MemoryTypeId(0), requirements.size).unwrap();
+        let buffer = device.bind_buffer_memory(&memory, 0, unbound).unwrap();
+        (buffer, memory)
+    }
+
+    fn copy(usage: buffer::Usage, wait: bool) {
+        let (device, mut queue_group) = open();
+        let (src, src_memory) = create_buffer(&device, 4, usage);
+        let (dst, dst_memory) = create_buffer(&device, 4, buffer::Usage::TRANSFER_DST);
+        let mut pool = device.create_command_pool_typed(&queue_group, CommandPoolCreateFlags::empty(), 1);
+        let submit = {
+            let mut cmd = pool.acquire_command_buffer(false);
+            cmd.copy_buffer(&src, &dst, &[command::BufferCopy { src: 0, dst: 0, size: 4 }]);
+            cmd.finish()
+        };
+        let fence = device.create_fence(false);
+        queue_group.queues[0].submit(Submission::new().submit(Some(submit)), Some(&fence));
+        if wait {
+            assert!(device.wait_for_fence(&fence, !0));
+        }
+
+        device.destroy_buffer(src);
+        device.destroy_buffer(dst);
+        device.free_memory(src_memory);
+        device.free_memory(dst_memory);
+        device.destroy_fence(fence);
+    }
+
+    #[test]
+    fn test_copy() {
+        copy(buffer::Usage::TRANSFER_SRC, true);
+    }
+
+    #[test]
+    #[should_panic(expected = "lacks the TRANSFER_SRC usage")]
+    fn test_copy_usage() {
+        copy(buffer::Usage::UNIFORM, true);
+    }
+
+    #[test]
+    #[should_panic(expected = "destroyed while in use by a pending submission")]
+    fn test_destroy_in_use() {
+        copy(buffer::Usage::TRANSFER_SRC, false);
+    }
+
+    #[test]
+    fn test_event() {
+        let (device, mut queue_group) = open();
+        let event = device.create_event();
+        let mut pool = device.create_command_pool_typed(&queue_group, CommandPoolCreateFlags::empty(), 1);
+        let submit = {
+            let mut cmd = pool.acquire_command_buffer(false);
+            cmd.set_event(&event, pso::PipelineStage::TRANSFER);
+            cmd.wait_events(Some(&event), pso::PipelineStage::TRANSFER .. pso::PipelineStage::TRANSFER, &[]);
+            cmd.finish()
+        };
+        let fence = device.create_fence(false);
+        assert!(!device.get_event_status(&event));
+        queue_group.queues[0].submit(Submission::new().submit(Some(submit)), Some(&fence));
+        assert!(device.wait_for_fence(&fence, !0));
+        assert!(device.get_event_status(&event));
+        device.reset_event(&event);
+        assert!(!device.get_event_status(&event));
+
+        device.destroy_event(event);
+        device.destroy_fence(fence);
+    }
+
+    #[test]
+    #[should_panic(expected = "is outside of the allocation")]
+    fn test_map_out_of_range() {
+        let (device, _) = open();
+        let (_, memory) = create_buffer(&device, 4, buffer::Usage::TRANSFER_SRC);
+        let _ = device.acquire_mapping_writer::<u8>(&memory, 0 .. 8);
+    }
+}
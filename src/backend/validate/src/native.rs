@@ -0,0 +1,224 @@
+//! Wrappers around the resources of the inner backend, carrying the
+//! information needed to validate their usage.
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicBool;
+
+use hal::{self, buffer, format, image, memory, pass, pso, query};
+use hal::backend::FastHashMap;
+
+use track::{Context, Handle};
+use Backend;
+
+
+#[derive(Debug)]
+pub struct ShaderModule<B: hal::Backend> {
+    pub(crate) raw: B::ShaderModule,
+}
+
+/// Information needed to check the compatibility of render passes.
+#[derive(Debug, PartialEq)]
+pub(crate) struct RenderPassInfo {
+    pub(crate) attachments: Vec<(Option<format::Format>, image::NumSamples)>,
+    pub(crate) subpasses: Vec<SubpassInfo>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct SubpassInfo {
+    pub(crate) colors: Vec<pass::AttachmentId>,
+    pub(crate) depth_stencil: Option<pass::AttachmentId>,
+    pub(crate) inputs: Vec<pass::AttachmentId>,
+    pub(crate) resolves: Vec<pass::AttachmentId>,
+}
+
+#[derive(Debug)]
+pub struct RenderPass<B: hal::Backend> {
+    pub(crate) raw: B::RenderPass,
+    pub(crate) handle: Handle,
+    pub(crate) info: Arc<RenderPassInfo>,
+}
+
+#[derive(Debug)]
+pub struct Framebuffer<B: hal::Backend> {
+    pub(crate) raw: B::Framebuffer,
+    pub(crate) handle: Handle,
+    /// Render pass the framebuffer has been created with, if not provided by a swapchain.
+    pub(crate) pass: Option<Arc<RenderPassInfo>>,
+}
+
+#[derive(Debug)]
+pub struct Memory<B: hal::Backend> {
+    pub(crate) raw: B::Memory,
+    pub(crate) handle: Handle,
+    pub(crate) type_id: hal::MemoryTypeId,
+    pub(crate) properties: memory::Properties,
+    pub(crate) size: u64,
+    pub(crate) mapped: AtomicBool,
+}
+
+#[derive(Debug)]
+pub struct UnboundBuffer<B: hal::Backend> {
+    pub(crate) raw: B::UnboundBuffer,
+    pub(crate) size: u64,
+    pub(crate) usage: buffer::Usage,
+}
+
+#[derive(Debug)]
+pub struct Buffer<B: hal::Backend> {
+    pub(crate) raw: B::Buffer,
+    pub(crate) handle: Handle,
+    pub(crate) size: u64,
+    pub(crate) usage: buffer::Usage,
+}
+
+#[derive(Debug)]
+pub struct BufferView<B: hal::Backend> {
+    pub(crate) raw: B::BufferView,
+    pub(crate) handle: Handle,
+    pub(crate) usage: buffer::Usage,
+}
+
+#[derive(Debug)]
+pub struct UnboundImage<B: hal::Backend> {
+    pub(crate) raw: B::UnboundImage,
+    pub(crate) usage: image::Usage,
+}
+
+#[derive(Debug)]
+pub struct Image<B: hal::Backend> {
+    pub(crate) raw: B::Image,
+    pub(crate) handle: Handle,
+    pub(crate) usage: image::Usage,
+}
+
+#[derive(Debug)]
+pub struct ImageView<B: hal::Backend> {
+    pub(crate) raw: B::ImageView,
+    pub(crate) handle: Handle,
+    pub(crate) format: format::Format,
+    /// Usage of the image the view has been created from.
+    pub(crate) usage: image::Usage,
+}
+
+#[derive(Debug)]
+pub struct Sampler<B: hal::Backend> {
+    pub(crate) raw: B::Sampler,
+    pub(crate) handle: Handle,
+}
+
+#[derive(Debug)]
+pub struct ComputePipeline<B: hal::Backend> {
+    pub(crate) raw: B::ComputePipeline,
+    pub(crate) handle: Handle,
+}
+
+#[derive(Debug)]
+pub struct GraphicsPipeline<B: hal::Backend> {
+    pub(crate) raw: B::GraphicsPipeline,
+    pub(crate) handle: Handle,
+    pub(crate) pass: Arc<RenderPassInfo>,
+    pub(crate) subpass: pass::SubpassId,
+}
+
+#[derive(Debug)]
+pub struct PipelineCache<B: hal::Backend> {
+    pub(crate) raw: B::PipelineCache,
+}
+
+#[derive(Debug)]
+pub struct PipelineLayout<B: hal::Backend> {
+    pub(crate) raw: B::PipelineLayout,
+    pub(crate) handle: Handle,
+}
+
+#[derive(Debug)]
+pub struct DescriptorSetLayout<B: hal::Backend> {
+    pub(crate) raw: B::DescriptorSetLayout,
+    pub(crate) bindings: Arc<Vec<pso::DescriptorSetLayoutBinding>>,
+}
+
+#[derive(Debug)]
+pub struct DescriptorSet<B: hal::Backend> {
+    pub(crate) raw: B::DescriptorSet,
+    pub(crate) handle: Handle,
+    pub(crate) bindings: Arc<Vec<pso::DescriptorSetLayoutBinding>>,
+    /// Resources written to each descriptor, keyed by binding and array index.
+    pub(crate) resources: Mutex<FastHashMap<(pso::DescriptorBinding, pso::DescriptorArrayIndex), Vec<Handle>>>,
+}
+
+impl<B: hal::Backend> DescriptorSet<B> {
+    /// Handles of the set itself and of all the resources it references.
+    pub(crate) fn handles(&self) -> Vec<Handle> {
+        let resources = self.resources.lock().unwrap();
+        let mut handles = vec![self.handle.clone()];
+        handles.extend(resources.values().flat_map(|list| list.iter().cloned()));
+        handles
+    }
+}
+
+#[derive(Debug)]
+pub struct DescriptorPool<B: hal::Backend> {
+    pub(crate) raw: B::DescriptorPool,
+    pub(crate) context: Arc<Context>,
+    /// Handles of the sets allocated from the pool and not freed yet.
+    pub(crate) sets: Vec<Handle>,
+}
+
+impl<B: hal::Backend> pso::DescriptorPool<Backend<B>> for DescriptorPool<B> {
+    fn allocate_set(&mut self, layout: &DescriptorSetLayout<B>) -> Result<DescriptorSet<B>, pso::AllocationError> {
+        let raw = self.raw.allocate_set(&layout.raw)?;
+        let handle = Handle::new("DescriptorSet");
+        self.sets.push(handle.clone());
+        Ok(DescriptorSet {
+            raw,
+            handle,
+            bindings: layout.bindings.clone(),
+            resources: Mutex::new(FastHashMap::default()),
+        })
+    }
+
+    fn free_sets<I>(&mut self, descriptor_sets: I)
+    where
+        I: IntoIterator<Item = DescriptorSet<B>>,
+    {
+        let mut raw_sets = Vec::new();
+        for set in descriptor_sets {
+            self.context.destroy(&set.handle);
+            self.sets.retain(|handle| !handle.is_same(&set.handle));
+            raw_sets.push(set.raw);
+        }
+        self.raw.free_sets(raw_sets);
+    }
+
+    fn reset(&mut self) {
+        for handle in self.sets.drain(..) {
+            self.context.destroy(&handle);
+        }
+        self.raw.reset();
+    }
+}
+
+#[derive(Debug)]
+pub struct Fence<B: hal::Backend> {
+    pub(crate) raw: B::Fence,
+    pub(crate) handle: Handle,
+}
+
+#[derive(Debug)]
+pub struct Semaphore<B: hal::Backend> {
+    pub(crate) raw: B::Semaphore,
+    pub(crate) handle: Handle,
+}
+
+#[derive(Debug)]
+pub struct Event<B: hal::Backend> {
+    pub(crate) raw: B::Event,
+    pub(crate) handle: Handle,
+}
+
+#[derive(Debug)]
+pub struct QueryPool<B: hal::Backend> {
+    pub(crate) raw: B::QueryPool,
+    pub(crate) handle: Handle,
+    pub(crate) count: query::QueryId,
+}
@@ -0,0 +1,198 @@
+use std::borrow::Borrow;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use hal::{self, error, memory, pso};
+use hal::queue::{RawCommandQueue, RawSparseBinding, RawSubmission};
+
+use command::CommandBuffer;
+use native as n;
+use track::{Context, Handle};
+use {Backend, Swapchain};
+
+
+static NEXT_QUEUE_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn unwrap_binds<'a, B: hal::Backend>(
+    binds: &[memory::SparseBind<'a, Backend<B>>],
+) -> Vec<memory::SparseBind<'a, B>> {
+    binds
+        .iter()
+        .map(|bind| memory::SparseBind {
+            resource_offset: bind.resource_offset,
+            size: bind.size,
+            memory: bind.memory.map(|(memory, offset)| (&memory.raw, offset)),
+        })
+        .collect()
+}
+
+fn unwrap_image_binds<'a, B: hal::Backend>(
+    binds: &[memory::SparseImageBind<'a, Backend<B>>],
+) -> Vec<memory::SparseImageBind<'a, B>> {
+    binds
+        .iter()
+        .map(|bind| memory::SparseImageBind {
+            subresource: bind.subresource,
+            offset: bind.offset,
+            extent: bind.extent,
+            memory: bind.memory.map(|(memory, offset)| (&memory.raw, offset)),
+        })
+        .collect()
+}
+
+/// Command queue of the wrapped backend.
+#[derive(Debug)]
+pub struct CommandQueue<B: hal::Backend> {
+    raw: B::CommandQueue,
+    context: Arc<Context>,
+    id: usize,
+}
+
+impl<B: hal::Backend> CommandQueue<B> {
+    pub(crate) fn new(raw: B::CommandQueue, context: Arc<Context>) -> Self {
+        CommandQueue {
+            raw,
+            context,
+            id: NEXT_QUEUE_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    fn check_sparse_memory(
+        &self, memory: Option<(&n::Memory<B>, u64)>, size: u64, resources: &mut Vec<Handle>,
+    ) {
+        if let Some((memory, offset)) = memory {
+            if offset + size > memory.size {
+                report!(self.context, "Sparse binding exceeds the size of {}", memory.handle);
+            }
+            resources.push(memory.handle.clone());
+        }
+    }
+}
+
+impl<B: hal::Backend> RawCommandQueue<Backend<B>> for CommandQueue<B> {
+    unsafe fn submit_raw<IC>(&mut self, submission: RawSubmission<Backend<B>, IC>, fence: Option<&n::Fence<B>>)
+    where
+        IC: IntoIterator,
+        IC::Item: Borrow<CommandBuffer<B>>,
+    {
+        let cmd_buffers = submission.cmd_buffers.into_iter().collect::<Vec<_>>();
+        let mut resources = Vec::new();
+        for cmd_buffer in &cmd_buffers {
+            match cmd_buffer.borrow().submission_resources() {
+                Ok(handles) => resources.extend(handles),
+                Err(error) => report!(self.context, "Submitted command buffer {}", error),
+            }
+        }
+        resources.extend(submission.wait_semaphores.iter().map(|&(semaphore, _)| semaphore.handle.clone()));
+        resources.extend(submission.signal_semaphores.iter().map(|semaphore| semaphore.handle.clone()));
+        self.context.submit(self.id, fence.map(|fence| &fence.handle), resources);
+
+        let wait_semaphores = submission.wait_semaphores
+            .iter()
+            .map(|&(semaphore, stage)| (&semaphore.raw, stage))
+            .collect::<Vec<(_, pso::PipelineStage)>>();
+        let signal_semaphores = submission.signal_semaphores
+            .iter()
+            .map(|semaphore| &semaphore.raw)
+            .collect::<Vec<_>>();
+        let raw_submission = RawSubmission {
+            cmd_buffers: cmd_buffers.iter().map(|cmd_buffer| cmd_buffer.borrow().raw()),
+            wait_semaphores: &wait_semaphores,
+            signal_semaphores: &signal_semaphores,
+        };
+        self.raw.submit_raw(raw_submission, fence.map(|fence| &fence.raw));
+    }
+
+    unsafe fn bind_sparse(&mut self, binding: RawSparseBinding<Backend<B>>, fence: Option<&n::Fence<B>>) {
+        let mut resources = Vec::new();
+        for &(buffer, binds) in binding.buffers {
+            resources.push(buffer.handle.clone());
+            for bind in binds {
+                if bind.resource_offset + bind.size > buffer.size {
+                    report!(self.context, "Sparse binding exceeds the size of {}", buffer.handle);
+                }
+                self.check_sparse_memory(bind.memory, bind.size, &mut resources);
+            }
+        }
+        for &(image, binds) in binding.image_opaques {
+            resources.push(image.handle.clone());
+            for bind in binds {
+                self.check_sparse_memory(bind.memory, bind.size, &mut resources);
+            }
+        }
+        for &(image, binds) in binding.images {
+            resources.push(image.handle.clone());
+            for bind in binds {
+                if let Some((memory, _)) = bind.memory {
+                    resources.push(memory.handle.clone());
+                }
+            }
+        }
+        resources.extend(binding.wait_semaphores.iter().map(|semaphore| semaphore.handle.clone()));
+        resources.extend(binding.signal_semaphores.iter().map(|semaphore| semaphore.handle.clone()));
+        self.context.submit(self.id, fence.map(|fence| &fence.handle), resources);
+
+        let buffer_binds = binding.buffers
+            .iter()
+            .map(|&(buffer, binds)| (&buffer.raw, unwrap_binds(binds)))
+            .collect::<Vec<_>>();
+        let image_opaque_binds = binding.image_opaques
+            .iter()
+            .map(|&(image, binds)| (&image.raw, unwrap_binds(binds)))
+            .collect::<Vec<_>>();
+        let image_binds = binding.images
+            .iter()
+            .map(|&(image, binds)| (&image.raw, unwrap_image_binds(binds)))
+            .collect::<Vec<_>>();
+
+        let buffers = buffer_binds
+            .iter()
+            .map(|&(buffer, ref binds)| (buffer, &binds[..]))
+            .collect::<Vec<_>>();
+        let image_opaques = image_opaque_binds
+            .iter()
+            .map(|&(image, ref binds)| (image, &binds[..]))
+            .collect::<Vec<_>>();
+        let images = image_binds
+            .iter()
+            .map(|&(image, ref binds)| (image, &binds[..]))
+            .collect::<Vec<_>>();
+        let wait_semaphores = binding.wait_semaphores
+            .iter()
+            .map(|semaphore| &semaphore.raw)
+            .collect::<Vec<_>>();
+        let signal_semaphores = binding.signal_semaphores
+            .iter()
+            .map(|semaphore| &semaphore.raw)
+            .collect::<Vec<_>>();
+        let raw_binding = RawSparseBinding {
+            wait_semaphores: &wait_semaphores,
+            signal_semaphores: &signal_semaphores,
+            buffers: &buffers,
+            image_opaques: &image_opaques,
+            images: &images,
+        };
+        self.raw.bind_sparse(raw_binding, fence.map(|fence| &fence.raw));
+    }
+
+    fn present<IS, S, IW>(&mut self, swapchains: IS, wait_semaphores: IW) -> Result<(), ()>
+    where
+        IS: IntoIterator<Item = (S, hal::SwapImageIndex)>,
+        S: Borrow<Swapchain<B>>,
+        IW: IntoIterator,
+        IW::Item: Borrow<n::Semaphore<B>>,
+    {
+        let swapchains = swapchains.into_iter().collect::<Vec<_>>();
+        let wait_semaphores = wait_semaphores.into_iter().collect::<Vec<_>>();
+        self.raw.present(
+            swapchains.iter().map(|&(ref swapchain, index)| (&swapchain.borrow().raw, index)),
+            wait_semaphores.iter().map(|semaphore| &semaphore.borrow().raw),
+        )
+    }
+
+    fn wait_idle(&self) -> Result<(), error::HostExecutionError> {
+        self.raw.wait_idle()?;
+        self.context.retire_queue(self.id);
+        Ok(())
+    }
+}
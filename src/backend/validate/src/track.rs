@@ -0,0 +1,191 @@
+//! Tracking of resource lifetimes across queue submissions.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Specifies what happens when invalid API usage is detected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorAction {
+    /// Log the error and panic, pointing at the offending call.
+    Panic,
+    /// Log the error and forward the call to the wrapped backend anyway.
+    Log,
+}
+
+struct Tracker {
+    kind: &'static str,
+    id: usize,
+    destroyed: AtomicBool,
+    /// Number of submissions referencing the resource that are not known to be complete.
+    pending: AtomicUsize,
+    /// Resources which have to outlive this one, e.g. the memory bound to a buffer.
+    deps: Vec<Handle>,
+}
+
+/// Shared liveness state of a resource.
+#[derive(Clone)]
+pub struct Handle(Arc<Tracker>);
+
+impl Handle {
+    pub fn new(kind: &'static str) -> Self {
+        Handle::with_deps(kind, Vec::new())
+    }
+
+    pub fn with_deps(kind: &'static str, deps: Vec<Handle>) -> Self {
+        Handle(Arc::new(Tracker {
+            kind,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            destroyed: AtomicBool::new(false),
+            pending: AtomicUsize::new(0),
+            deps,
+        }))
+    }
+
+    pub fn is_same(&self, other: &Handle) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    fn visit<F: FnMut(&Handle)>(&self, fun: &mut F) {
+        fun(self);
+        for dep in &self.0.deps {
+            dep.visit(fun);
+        }
+    }
+
+    fn find_destroyed(&self) -> Option<Handle> {
+        let mut found = None;
+        self.visit(&mut |handle| if found.is_none() && handle.0.destroyed.load(Ordering::Acquire) {
+            found = Some(handle.clone());
+        });
+        found
+    }
+
+    fn acquire(&self) {
+        self.visit(&mut |handle| { handle.0.pending.fetch_add(1, Ordering::AcqRel); });
+    }
+
+    fn release(&self) {
+        self.visit(&mut |handle| { handle.0.pending.fetch_sub(1, Ordering::AcqRel); });
+    }
+}
+
+impl fmt::Display for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} #{}", self.0.kind, self.0.id)
+    }
+}
+
+impl fmt::Debug for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle({})", self)
+    }
+}
+
+#[derive(Debug)]
+struct Submission {
+    queue: usize,
+    fence: Option<Handle>,
+    resources: Vec<Handle>,
+}
+
+impl Submission {
+    fn signals(&self, fence: &Handle) -> bool {
+        match self.fence {
+            Some(ref f) => f.is_same(fence),
+            None => false,
+        }
+    }
+}
+
+/// Validation state shared by a device and all the objects created from it.
+#[derive(Debug)]
+pub struct Context {
+    action: ErrorAction,
+    /// Submissions which have not been observed to complete yet.
+    submissions: Mutex<Vec<Submission>>,
+}
+
+impl Context {
+    pub fn new(action: ErrorAction) -> Self {
+        Context {
+            action,
+            submissions: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn report(&self, args: fmt::Arguments) {
+        error!("Validation error: {}", args);
+        if self.action == ErrorAction::Panic {
+            panic!("Validation error: {}", args);
+        }
+    }
+
+    /// Mark the resource as destroyed, checking that no pending submission uses it.
+    pub fn destroy(&self, handle: &Handle) {
+        if handle.0.pending.load(Ordering::Acquire) != 0 {
+            report!(self, "{} destroyed while in use by a pending submission", handle);
+        }
+        handle.0.destroyed.store(true, Ordering::Release);
+    }
+
+    /// Register a submission on the queue `queue`, checking that all the
+    /// resources it references are still alive.
+    pub fn submit(&self, queue: usize, fence: Option<&Handle>, resources: Vec<Handle>) {
+        for resource in resources.iter().chain(fence) {
+            if let Some(destroyed) = resource.find_destroyed() {
+                report!(self, "Submission references {}, which has been destroyed", destroyed);
+            }
+        }
+        for resource in resources.iter().chain(fence) {
+            resource.acquire();
+        }
+        self.submissions.lock().unwrap().push(Submission {
+            queue,
+            fence: fence.cloned(),
+            resources,
+        });
+    }
+
+    /// Check if a submission signaling `fence` is still pending.
+    pub fn is_fence_pending(&self, fence: &Handle) -> bool {
+        self.submissions
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|sub| sub.signals(fence))
+    }
+
+    /// Retire the submissions signaling `fence`, which has been observed signaled.
+    pub fn retire_fence(&self, fence: &Handle) {
+        self.retire(|sub| sub.signals(fence));
+    }
+
+    /// Retire the submissions of a queue which has been observed idle.
+    pub fn retire_queue(&self, queue: usize) {
+        self.retire(|sub| sub.queue == queue);
+    }
+
+    /// Retire all the submissions, after the device has been observed idle.
+    pub fn retire_all(&self) {
+        self.retire(|_| true);
+    }
+
+    fn retire<F: Fn(&Submission) -> bool>(&self, fun: F) {
+        let mut submissions = self.submissions.lock().unwrap();
+        let mut i = 0;
+        while i < submissions.len() {
+            if fun(&submissions[i]) {
+                let sub = submissions.swap_remove(i);
+                for resource in sub.resources.iter().chain(&sub.fence) {
+                    resource.release();
+                }
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use hal::{self, format, image};
+
+use track::Handle;
+use {Backend, PhysicalDevice, QueueFamily};
+
+
+/// Surface of the wrapped backend.
+pub struct Surface<B: hal::Backend> {
+    pub(crate) raw: B::Surface,
+}
+
+impl<B: hal::Backend> hal::Surface<Backend<B>> for Surface<B> {
+    fn kind(&self) -> image::Kind {
+        self.raw.kind()
+    }
+
+    fn supports_queue_family(&self, family: &QueueFamily<B>) -> bool {
+        self.raw.supports_queue_family(&family.raw)
+    }
+
+    fn compatibility(
+        &self, physical_device: &PhysicalDevice<B>
+    ) -> (hal::SurfaceCapabilities, Option<Vec<format::Format>>, Vec<hal::PresentMode>) {
+        self.raw.compatibility(&physical_device.raw)
+    }
+}
+
+/// Swapchain of the wrapped backend.
+pub struct Swapchain<B: hal::Backend> {
+    pub(crate) raw: B::Swapchain,
+    pub(crate) handle: Handle,
+}
+
+impl<B: hal::Backend> hal::Swapchain<Backend<B>> for Swapchain<B> {
+    fn acquire_image(&mut self, sync: hal::FrameSync<Backend<B>>) -> Result<hal::SwapImageIndex, ()> {
+        let sync = match sync {
+            hal::FrameSync::Semaphore(semaphore) => hal::FrameSync::Semaphore(&semaphore.raw),
+            hal::FrameSync::Fence(fence) => hal::FrameSync::Fence(&fence.raw),
+        };
+        self.raw.acquire_image(sync)
+    }
+}
@@ -6,7 +6,7 @@ use smallvec::SmallVec;
 use ash::vk;
 use ash::version::DeviceV1_0;
 
-use hal::{buffer, command as com, memory, pso, query};
+use hal::{buffer, command as com, memory, pso, query, queue};
 use hal::{DrawCount, IndexCount, InstanceCount, VertexCount, VertexOffset, WorkGroupCount};
 use hal::format::Aspects;
 use hal::image::{Filter, Layout, SubresourceRange};
@@ -27,6 +27,82 @@ fn map_subpass_contents(contents: com::SubpassContents) -> vk::SubpassContents {
     }
 }
 
+fn map_queue_families(families: &Option<Range<queue::QueueFamilyId>>) -> (u32, u32) {
+    match *families {
+        Some(ref families) => (families.start.0 as u32, families.end.0 as u32),
+        None => (vk::VK_QUEUE_FAMILY_IGNORED, vk::VK_QUEUE_FAMILY_IGNORED),
+    }
+}
+
+fn map_barriers<'a, T>(
+    barriers: T,
+) -> (
+    SmallVec<[vk::MemoryBarrier; 4]>,
+    SmallVec<[vk::BufferMemoryBarrier; 4]>,
+    SmallVec<[vk::ImageMemoryBarrier; 4]>,
+)
+where
+    T: IntoIterator,
+    T::Item: Borrow<memory::Barrier<'a, Backend>>,
+{
+    let mut global_bars: SmallVec<[vk::MemoryBarrier; 4]> = SmallVec::new();
+    let mut buffer_bars: SmallVec<[vk::BufferMemoryBarrier; 4]> = SmallVec::new();
+    let mut image_bars: SmallVec<[vk::ImageMemoryBarrier; 4]> = SmallVec::new();
+
+    for barrier in barriers {
+        match *barrier.borrow() {
+            memory::Barrier::AllBuffers(ref access) => {
+                global_bars.push(vk::MemoryBarrier {
+                    s_type: vk::StructureType::MemoryBarrier,
+                    p_next: ptr::null(),
+                    src_access_mask: conv::map_buffer_access(access.start),
+                    dst_access_mask: conv::map_buffer_access(access.end),
+                });
+            }
+            memory::Barrier::AllImages(ref access) => {
+                global_bars.push(vk::MemoryBarrier {
+                    s_type: vk::StructureType::MemoryBarrier,
+                    p_next: ptr::null(),
+                    src_access_mask: conv::map_image_access(access.start),
+                    dst_access_mask: conv::map_image_access(access.end),
+                });
+            }
+            memory::Barrier::Buffer { ref states, target, ref families } => {
+                let (src_family, dst_family) = map_queue_families(families);
+                buffer_bars.push(vk::BufferMemoryBarrier {
+                    s_type: vk::StructureType::BufferMemoryBarrier,
+                    p_next: ptr::null(),
+                    src_access_mask: conv::map_buffer_access(states.start),
+                    dst_access_mask: conv::map_buffer_access(states.end),
+                    src_queue_family_index: src_family,
+                    dst_queue_family_index: dst_family,
+                    buffer: target.raw,
+                    offset: 0,
+                    size: vk::VK_WHOLE_SIZE,
+                });
+            }
+            memory::Barrier::Image { ref states, target, ref range, ref families } => {
+                let subresource_range = conv::map_subresource_range(range);
+                let (src_family, dst_family) = map_queue_families(families);
+                image_bars.push(vk::ImageMemoryBarrier {
+                    s_type: vk::StructureType::ImageMemoryBarrier,
+                    p_next: ptr::null(),
+                    src_access_mask: conv::map_image_access(states.start.0),
+                    dst_access_mask: conv::map_image_access(states.end.0),
+                    old_layout: conv::map_image_layout(states.start.1),
+                    new_layout: conv::map_image_layout(states.end.1),
+                    src_queue_family_index: src_family,
+                    dst_queue_family_index: dst_family,
+                    image: target.raw,
+                    subresource_range,
+                });
+            }
+        }
+    }
+
+    (global_bars, buffer_bars, image_bars)
+}
+
 fn map_buffer_image_regions<T>(
     _image: &n::Image,
     regions: T,
@@ -189,58 +265,7 @@ impl com::RawCommandBuffer<Backend> for CommandBuffer {
         T: IntoIterator,
         T::Item: Borrow<memory::Barrier<'a, Backend>>,
     {
-        let mut global_bars: SmallVec<[vk::MemoryBarrier; 4]> = SmallVec::new();
-        let mut buffer_bars: SmallVec<[vk::BufferMemoryBarrier; 4]> = SmallVec::new();
-        let mut image_bars: SmallVec<[vk::ImageMemoryBarrier; 4]> = SmallVec::new();
-
-        for barrier in barriers {
-            match *barrier.borrow() {
-                memory::Barrier::AllBuffers(ref access) => {
-                    global_bars.push(vk::MemoryBarrier {
-                        s_type: vk::StructureType::MemoryBarrier,
-                        p_next: ptr::null(),
-                        src_access_mask: conv::map_buffer_access(access.start),
-                        dst_access_mask: conv::map_buffer_access(access.end),
-                    });
-                }
-                memory::Barrier::AllImages(ref access) => {
-                    global_bars.push(vk::MemoryBarrier {
-                        s_type: vk::StructureType::MemoryBarrier,
-                        p_next: ptr::null(),
-                        src_access_mask: conv::map_image_access(access.start),
-                        dst_access_mask: conv::map_image_access(access.end),
-                    });
-                }
-                memory::Barrier::Buffer { ref states, target} => {
-                    buffer_bars.push(vk::BufferMemoryBarrier {
-                        s_type: vk::StructureType::BufferMemoryBarrier,
-                        p_next: ptr::null(),
-                        src_access_mask: conv::map_buffer_access(states.start),
-                        dst_access_mask: conv::map_buffer_access(states.end),
-                        src_queue_family_index: vk::VK_QUEUE_FAMILY_IGNORED, // TODO
-                        dst_queue_family_index: vk::VK_QUEUE_FAMILY_IGNORED, // TODO
-                        buffer: target.raw,
-                        offset: 0,
-                        size: vk::VK_WHOLE_SIZE,
-                    });
-                }
-                memory::Barrier::Image { ref states, target, ref range } => {
-                    let subresource_range = conv::map_subresource_range(range);
-                    image_bars.push(vk::ImageMemoryBarrier {
-                        s_type: vk::StructureType::ImageMemoryBarrier,
-                        p_next: ptr::null(),
-                        src_access_mask: conv::map_image_access(states.start.0),
-                        dst_access_mask: conv::map_image_access(states.end.0),
-                        old_layout: conv::map_image_layout(states.start.1),
-                        new_layout: conv::map_image_layout(states.end.1),
-                        src_queue_family_index: vk::VK_QUEUE_FAMILY_IGNORED, // TODO
-                        dst_queue_family_index: vk::VK_QUEUE_FAMILY_IGNORED, // TODO
-                        image: target.raw,
-                        subresource_range,
-                    });
-                }
-            }
-        }
+        let (global_bars, buffer_bars, image_bars) = map_barriers(barriers);
 
         unsafe {
             self.device.0.cmd_pipeline_barrier(
@@ -255,6 +280,60 @@ impl com::RawCommandBuffer<Backend> for CommandBuffer {
         }
     }
 
+    fn set_event(&mut self, event: &n::Event, stages: pso::PipelineStage) {
+        unsafe {
+            self.device.0.fp_v1_0().cmd_set_event(
+                self.raw,
+                event.0,
+                conv::map_pipeline_stage(stages),
+            );
+        }
+    }
+
+    fn reset_event(&mut self, event: &n::Event, stages: pso::PipelineStage) {
+        unsafe {
+            self.device.0.fp_v1_0().cmd_reset_event(
+                self.raw,
+                event.0,
+                conv::map_pipeline_stage(stages),
+            );
+        }
+    }
+
+    fn wait_events<'a, I, J>(
+        &mut self,
+        events: I,
+        stages: Range<pso::PipelineStage>,
+        barriers: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<n::Event>,
+        J: IntoIterator,
+        J::Item: Borrow<memory::Barrier<'a, Backend>>,
+    {
+        let events = events
+            .into_iter()
+            .map(|event| event.borrow().0)
+            .collect::<SmallVec<[vk::Event; 4]>>();
+        let (global_bars, buffer_bars, image_bars) = map_barriers(barriers);
+
+        unsafe {
+            self.device.0.fp_v1_0().cmd_wait_events(
+                self.raw,
+                events.len() as u32,
+                events.as_ptr(),
+                conv::map_pipeline_stage(stages.start),
+                conv::map_pipeline_stage(stages.end),
+                global_bars.len() as u32,
+                global_bars.as_ptr(),
+                buffer_bars.len() as u32,
+                buffer_bars.as_ptr(),
+                image_bars.len() as u32,
+                image_bars.as_ptr(),
+            );
+        }
+    }
+
     fn fill_buffer<R>(
         &mut self,
         buffer: &n::Buffer,
@@ -909,6 +988,29 @@ impl com::RawCommandBuffer<Backend> for CommandBuffer {
         }
     }
 
+    fn copy_query_pool_results(
+        &mut self,
+        pool: &n::QueryPool,
+        queries: Range<query::QueryId>,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) {
+        unsafe {
+            self.device.0.fp_v1_0().cmd_copy_query_pool_results(
+                self.raw,
+                pool.0,
+                queries.start,
+                queries.end - queries.start,
+                buffer.raw,
+                offset,
+                stride,
+                conv::map_query_result_flags(flags),
+            )
+        }
+    }
+
     fn push_compute_constants(
         &mut self,
         layout: &n::PipelineLayout,
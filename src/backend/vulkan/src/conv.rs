@@ -2,8 +2,8 @@ use ash::vk;
 use byteorder::{NativeEndian, WriteBytesExt};
 use smallvec::SmallVec;
 
-use hal::{buffer, command, format, image, pass, pso, query};
-use hal::{IndexType, Primitive, PresentMode};
+use hal::{buffer, command, format, image, memory, pass, pso, query};
+use hal::{Features, IndexType, Primitive, PresentMode};
 use hal::range::RangeArg;
 
 use native as n;
@@ -403,6 +403,11 @@ pub fn map_query_control_flags(flags: query::QueryControl) -> vk::QueryControlFl
     unsafe { mem::transmute(flags) }
 }
 
+pub fn map_query_result_flags(flags: query::ResultFlags) -> vk::QueryResultFlags {
+    // Safe due to equivalence of HAL values and Vulkan values
+    unsafe { mem::transmute(flags) }
+}
+
 pub fn map_image_features(features: vk::FormatFeatureFlags) -> format::ImageFeature {
     // Safe due to equivalence of HAL values and Vulkan values
     unsafe { mem::transmute(features) }
@@ -521,7 +526,164 @@ pub fn map_image_flags(flags: image::StorageFlags) -> vk::ImageCreateFlags {
     unsafe { mem::transmute(flags) }
 }
 
+pub fn map_device_features(features: Features) -> vk::PhysicalDeviceFeatures {
+    let enabled = |feature| features.contains(feature) as vk::Bool32;
+    vk::PhysicalDeviceFeatures {
+        robust_buffer_access: enabled(Features::ROBUST_BUFFER_ACCESS),
+        full_draw_index_uint32: enabled(Features::FULL_DRAW_INDEX_U32),
+        image_cube_array: enabled(Features::IMAGE_CUBE_ARRAY),
+        independent_blend: enabled(Features::INDEPENDENT_BLENDING),
+        geometry_shader: enabled(Features::GEOMETRY_SHADER),
+        tessellation_shader: enabled(Features::TESSELLATION_SHADER),
+        sample_rate_shading: enabled(Features::SAMPLE_RATE_SHADING),
+        dual_src_blend: enabled(Features::DUAL_SRC_BLENDING),
+        logic_op: enabled(Features::LOGIC_OP),
+        multi_draw_indirect: enabled(Features::MULTI_DRAW_INDIRECT),
+        draw_indirect_first_instance: enabled(Features::DRAW_INDIRECT_FIRST_INSTANCE),
+        depth_clamp: enabled(Features::DEPTH_CLAMP),
+        depth_bias_clamp: enabled(Features::DEPTH_BIAS_CLAMP),
+        depth_bounds: enabled(Features::DEPTH_BOUNDS),
+        wide_lines: enabled(Features::LINE_WIDTH),
+        large_points: enabled(Features::POINT_SIZE),
+        alpha_to_one: enabled(Features::ALPHA_TO_ONE),
+        multi_viewport: enabled(Features::MULTI_VIEWPORTS),
+        sampler_anisotropy: enabled(Features::SAMPLER_ANISOTROPY),
+        texture_compression_etc2: enabled(Features::FORMAT_ETC2),
+        texture_compression_astc_ldr: enabled(Features::FORMAT_ASTC_LDR),
+        texture_compression_bc: enabled(Features::FORMAT_BC),
+        occlusion_query_precise: enabled(Features::PRECISE_OCCLUSION_QUERY),
+        pipeline_statistics_query: enabled(Features::PIPELINE_STATISTICS_QUERY),
+        vertex_pipeline_stores_and_atomics: enabled(Features::VERTEX_STORES_AND_ATOMICS),
+        fragment_stores_and_atomics: enabled(Features::FRAGMENT_STORES_AND_ATOMICS),
+        sparse_binding: enabled(Features::SPARSE_BINDING),
+        sparse_residency_buffer: enabled(Features::SPARSE_RESIDENCY_BUFFER),
+        sparse_residency_image2d: enabled(Features::SHADER_RESIDENCY_IMAGE_2D),
+        sparse_residency_image3d: enabled(Features::SHADER_RESIDENSY_IMAGE_3D),
+        sparse_residency2samples: enabled(Features::SPARSE_RESIDENCY_2_SAMPLES),
+        sparse_residency4samples: enabled(Features::SPARSE_RESIDENCY_4_SAMPLES),
+        sparse_residency8samples: enabled(Features::SPARSE_RESIDENCY_8_SAMPLES),
+        sparse_residency16samples: enabled(Features::SPARSE_RESIDENCY_16_SAMPLES),
+        sparse_residency_aliased: enabled(Features::SPARSE_RESIDENCY_ALIASED),
+        .. vk::PhysicalDeviceFeatures::default()
+    }
+}
+
+/// Features required to create a sparse buffer with the given flags.
+pub fn sparse_buffer_features(flags: memory::SparseFlags) -> Features {
+    let mut features = Features::SPARSE_BINDING;
+    if flags.contains(memory::SparseFlags::RESIDENCY) {
+        features |= Features::SPARSE_RESIDENCY_BUFFER;
+    }
+    if flags.contains(memory::SparseFlags::ALIASED) {
+        features |= Features::SPARSE_RESIDENCY_ALIASED;
+    }
+    features
+}
+
+/// Features required to create a sparse image with the given flags,
+/// or `None` if the kind of image can't be resident.
+pub fn sparse_image_features(kind: image::Kind, flags: memory::SparseFlags) -> Option<Features> {
+    let mut features = Features::SPARSE_BINDING;
+    if flags.contains(memory::SparseFlags::RESIDENCY) {
+        features |= match kind {
+            image::Kind::D1(..) => return None,
+            image::Kind::D2(..) => Features::SHADER_RESIDENCY_IMAGE_2D,
+            image::Kind::D3(..) => Features::SHADER_RESIDENSY_IMAGE_3D,
+        };
+        features |= match kind.num_samples() {
+            1 => Features::empty(),
+            2 => Features::SPARSE_RESIDENCY_2_SAMPLES,
+            4 => Features::SPARSE_RESIDENCY_4_SAMPLES,
+            8 => Features::SPARSE_RESIDENCY_8_SAMPLES,
+            16 => Features::SPARSE_RESIDENCY_16_SAMPLES,
+            _ => return None,
+        };
+    }
+    if flags.contains(memory::SparseFlags::ALIASED) {
+        features |= Features::SPARSE_RESIDENCY_ALIASED;
+    }
+    Some(features)
+}
+
+pub fn map_buffer_sparse_flags(flags: memory::SparseFlags) -> vk::BufferCreateFlags {
+    // the flag values have to match Vulkan
+    vk::BUFFER_CREATE_SPARSE_BINDING_BIT | vk::BufferCreateFlags::from_flags_truncate(flags.bits())
+}
+
+pub fn map_image_sparse_flags(flags: memory::SparseFlags) -> vk::ImageCreateFlags {
+    // the flag values have to match Vulkan
+    vk::IMAGE_CREATE_SPARSE_BINDING_BIT | vk::ImageCreateFlags::from_flags_truncate(flags.bits())
+}
+
+pub fn map_vk_sparse_image_requirements(
+    req: &vk::SparseImageMemoryRequirements,
+) -> image::SparseRequirements {
+    let properties = &req.format_properties;
+    image::SparseRequirements {
+        aspects: format::Aspects::from_bits_truncate(properties.aspect_mask.flags() as _),
+        granularity: image::Extent {
+            width: properties.image_granularity.width,
+            height: properties.image_granularity.height,
+            depth: properties.image_granularity.depth,
+        },
+        flags: image::SparseFormatFlags::from_bits_truncate(properties.flags.flags()),
+        mip_tail_first_level: req.image_mip_tail_first_lod as _,
+        mip_tail_size: req.image_mip_tail_size,
+        mip_tail_offset: req.image_mip_tail_offset,
+        mip_tail_stride: req.image_mip_tail_stride,
+    }
+}
+
 pub fn map_vk_present_mode(mode: vk::PresentModeKHR) -> PresentMode {
     // the enum variants have to match Vulkan
     unsafe { mem::transmute(mode) }
 }
+
+#[cfg(test)]
+mod tests {
+    use hal::{image, memory, Features};
+    use super::{map_device_features, sparse_buffer_features, sparse_image_features};
+
+    #[test]
+    fn test_device_features() {
+        let enabled = map_device_features(Features::empty());
+        assert_eq!(enabled.sparse_binding, 0);
+        assert_eq!(enabled.robust_buffer_access, 0);
+
+        let enabled = map_device_features(Features::SPARSE_BINDING | Features::SHADER_RESIDENCY_IMAGE_2D);
+        assert_eq!(enabled.sparse_binding, 1);
+        assert_eq!(enabled.sparse_residency_image2d, 1);
+        assert_eq!(enabled.sparse_residency_buffer, 0);
+        assert_eq!(enabled.sparse_residency_image3d, 0);
+        assert_eq!(enabled.sparse_residency_aliased, 0);
+        assert_eq!(enabled.geometry_shader, 0);
+    }
+
+    #[test]
+    fn test_sparse_buffer_features() {
+        assert_eq!(sparse_buffer_features(memory::SparseFlags::empty()), Features::SPARSE_BINDING);
+        assert_eq!(
+            sparse_buffer_features(memory::SparseFlags::RESIDENCY | memory::SparseFlags::ALIASED),
+            Features::SPARSE_BINDING | Features::SPARSE_RESIDENCY_BUFFER | Features::SPARSE_RESIDENCY_ALIASED
+        );
+    }
+
+    #[test]
+    fn test_sparse_image_features() {
+        let kind_1d = image::Kind::D1(64, 1);
+        let kind_2d = image::Kind::D2(64, 64, 1, 4);
+        let kind_3d = image::Kind::D3(64, 64, 64);
+        let residency = memory::SparseFlags::RESIDENCY;
+
+        assert_eq!(sparse_image_features(kind_1d, memory::SparseFlags::empty()), Some(Features::SPARSE_BINDING));
+        assert_eq!(sparse_image_features(kind_1d, residency), None);
+        assert_eq!(
+            sparse_image_features(kind_2d, residency),
+            Some(Features::SPARSE_BINDING | Features::SHADER_RESIDENCY_IMAGE_2D | Features::SPARSE_RESIDENCY_4_SAMPLES)
+        );
+        assert_eq!(
+            sparse_image_features(kind_3d, residency | memory::SparseFlags::ALIASED),
+            Some(Features::SPARSE_BINDING | Features::SHADER_RESIDENSY_IMAGE_3D | Features::SPARSE_RESIDENCY_ALIASED)
+        );
+    }
+}
@@ -6,7 +6,7 @@ use smallvec::SmallVec;
 use hal::{buffer, device as d, format, image, mapping, pass, pso, query, queue};
 use hal::{Backbuffer, Features, MemoryTypeId, SwapchainConfig};
 use hal::error::HostExecutionError;
-use hal::memory::Requirements;
+use hal::memory::{Requirements, SparseFlags};
 use hal::pool::CommandPoolCreateFlags;
 use hal::range::RangeArg;
 
@@ -27,6 +27,28 @@ pub struct UnboundBuffer(n::Buffer);
 #[derive(Debug)]
 pub struct UnboundImage(n::Image);
 
+/// Check the header of pipeline cache data against the identifiers of a
+/// device, since drivers may not validate it themselves.
+fn is_pipeline_cache_compatible(
+    data: &[u8], vendor_id: u32, device_id: u32, uuid: &[u8; 16],
+) -> bool {
+    const HEADER_SIZE: usize = 32;
+    const HEADER_VERSION_ONE: u32 = 1;
+    if data.len() < HEADER_SIZE {
+        return false;
+    }
+    // The header is always written least significant byte first.
+    let word = |i: usize| {
+        data[4 * i] as u32 | (data[4 * i + 1] as u32) << 8 |
+            (data[4 * i + 2] as u32) << 16 | (data[4 * i + 3] as u32) << 24
+    };
+    word(0) as usize >= HEADER_SIZE &&
+        word(1) == HEADER_VERSION_ONE &&
+        word(2) == vendor_id &&
+        word(3) == device_id &&
+        data[16 .. HEADER_SIZE] == uuid[..]
+}
+
 impl Device {
     #[cfg(feature = "glsl-to-spirv")]
     pub fn create_shader_module_from_glsl(
@@ -56,6 +78,72 @@ impl Device {
             Err(string) => Err(d::ShaderError::CompilationFailed(string)),
         }
     }
+
+    fn create_raw_buffer(
+        &self, size: u64, usage: buffer::Usage, flags: vk::BufferCreateFlags,
+    ) -> Result<n::Buffer, buffer::CreationError> {
+        let info = vk::BufferCreateInfo {
+            s_type: vk::StructureType::BufferCreateInfo,
+            p_next: ptr::null(),
+            flags,
+            size,
+            usage: conv::map_buffer_usage(usage),
+            sharing_mode: vk::SharingMode::Exclusive, // TODO:
+            queue_family_index_count: 0,
+            p_queue_family_indices: ptr::null(),
+        };
+
+        unsafe { self.raw.0.create_buffer(&info, None) }
+            .map(|raw| n::Buffer { raw })
+            .map_err(|err| match err {
+                vk::Result::ErrorOutOfHostMemory => buffer::CreationError::OutOfHostMemory,
+                vk::Result::ErrorOutOfDeviceMemory => buffer::CreationError::OutOfDeviceMemory,
+                _ => unreachable!(),
+            })
+    }
+
+    fn create_raw_image(
+        &self,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        flags: vk::ImageCreateFlags,
+    ) -> Result<n::Image, image::CreationError> {
+        let extent = conv::map_extent(kind.extent());
+        let array_layers = kind.num_layers();
+        let samples = kind.num_samples() as u32;
+        let image_type = match kind {
+            image::Kind::D1(..) => vk::ImageType::Type1d,
+            image::Kind::D2(..) => vk::ImageType::Type2d,
+            image::Kind::D3(..) => vk::ImageType::Type3d,
+        };
+
+        let info = vk::ImageCreateInfo {
+            s_type: vk::StructureType::ImageCreateInfo,
+            p_next: ptr::null(),
+            flags,
+            image_type,
+            format: conv::map_format(format),
+            extent: extent.clone(),
+            mip_levels: mip_levels as u32,
+            array_layers: array_layers as u32,
+            samples: vk::SampleCountFlags::from_flags_truncate(samples),
+            tiling: conv::map_tiling(tiling),
+            usage: conv::map_image_usage(usage),
+            sharing_mode: vk::SharingMode::Exclusive, // TODO:
+            queue_family_index_count: 0,
+            p_queue_family_indices: ptr::null(),
+            initial_layout: vk::ImageLayout::Undefined,
+        };
+
+        let raw = unsafe {
+            self.raw.0.create_image(&info, None)
+        }.map_err(|_| image::CreationError::OutOfMemory)?;
+
+        Ok(n::Image { raw, ty: image_type, flags, extent })
+    }
 }
 
 impl d::Device<B> for Device {
@@ -268,13 +356,23 @@ impl d::Device<B> for Device {
         n::PipelineLayout { raw }
     }
 
-    fn create_pipeline_cache(&self) -> n::PipelineCache {
+    fn create_pipeline_cache(&self, data: Option<&[u8]>) -> n::PipelineCache {
+        let data = match data {
+            Some(data) if is_pipeline_cache_compatible(
+                data, self.vendor_id, self.device_id, &self.pipeline_cache_uuid,
+            ) => data,
+            Some(_) => {
+                warn!("Ignoring pipeline cache data from another device or driver");
+                &[]
+            }
+            None => &[],
+        };
         let info = vk::PipelineCacheCreateInfo {
             s_type: vk::StructureType::PipelineCacheCreateInfo,
             p_next: ptr::null(),
             flags: vk::PipelineCacheCreateFlags::empty(),
-            initial_data_size: 0, //TODO
-            p_initial_data: ptr::null(),
+            initial_data_size: data.len(),
+            p_initial_data: data.as_ptr() as *const _,
         };
 
         let raw = unsafe {
@@ -288,6 +386,31 @@ impl d::Device<B> for Device {
         }
     }
 
+    fn get_pipeline_cache_data(&self, cache: &n::PipelineCache) -> Result<Vec<u8>, d::OutOfMemory> {
+        let fp = self.raw.0.fp_v1_0();
+        let device = self.raw.0.handle();
+        let mut size = 0;
+        let result = unsafe {
+            fp.get_pipeline_cache_data(device, cache.raw, &mut size, ptr::null_mut())
+        };
+        if result != vk::Result::Success {
+            return Err(d::OutOfMemory);
+        }
+
+        let mut data = vec![0u8; size];
+        let result = unsafe {
+            fp.get_pipeline_cache_data(device, cache.raw, &mut size, data.as_mut_ptr() as *mut _)
+        };
+        match result {
+            // The cache may have changed in between, the returned data is still valid.
+            vk::Result::Success | vk::Result::Incomplete => {
+                data.truncate(size);
+                Ok(data)
+            }
+            _ => Err(d::OutOfMemory),
+        }
+    }
+
     fn destroy_pipeline_cache(&self, cache: n::PipelineCache) {
         unsafe {
             self.raw.0.destroy_pipeline_cache(cache.raw, None)
@@ -940,23 +1063,8 @@ impl d::Device<B> for Device {
 
     ///
     fn create_buffer(&self, size: u64, usage: buffer::Usage) -> Result<UnboundBuffer, buffer::CreationError> {
-        let info = vk::BufferCreateInfo {
-            s_type: vk::StructureType::BufferCreateInfo,
-            p_next: ptr::null(),
-            flags: vk::BufferCreateFlags::empty(), // TODO:
-            size,
-            usage: conv::map_buffer_usage(usage),
-            sharing_mode: vk::SharingMode::Exclusive, // TODO:
-            queue_family_index_count: 0,
-            p_queue_family_indices: ptr::null(),
-        };
-
-        let buffer = unsafe {
-            self.raw.0.create_buffer(&info, None)
-                .expect("Error on buffer creation") // TODO: error handling
-        };
-
-        Ok(UnboundBuffer(n::Buffer { raw: buffer }))
+        self.create_raw_buffer(size, usage, vk::BufferCreateFlags::empty())
+            .map(UnboundBuffer)
     }
 
     fn get_buffer_requirements(&self, buffer: &UnboundBuffer) -> Requirements {
@@ -1012,38 +1120,8 @@ impl d::Device<B> for Device {
         storage_flags: image::StorageFlags,
     ) -> Result<UnboundImage, image::CreationError> {
         let flags = conv::map_image_flags(storage_flags);
-        let extent = conv::map_extent(kind.extent());
-        let array_layers = kind.num_layers();
-        let samples = kind.num_samples() as u32;
-        let image_type = match kind {
-            image::Kind::D1(..) => vk::ImageType::Type1d,
-            image::Kind::D2(..) => vk::ImageType::Type2d,
-            image::Kind::D3(..) => vk::ImageType::Type3d,
-        };
-
-        let info = vk::ImageCreateInfo {
-            s_type: vk::StructureType::ImageCreateInfo,
-            p_next: ptr::null(),
-            flags,
-            image_type,
-            format: conv::map_format(format),
-            extent: extent.clone(),
-            mip_levels: mip_levels as u32,
-            array_layers: array_layers as u32,
-            samples: vk::SampleCountFlags::from_flags_truncate(samples),
-            tiling: conv::map_tiling(tiling),
-            usage: conv::map_image_usage(usage),
-            sharing_mode: vk::SharingMode::Exclusive, // TODO:
-            queue_family_index_count: 0,
-            p_queue_family_indices: ptr::null(),
-            initial_layout: vk::ImageLayout::Undefined,
-        };
-
-        let raw = unsafe {
-            self.raw.0.create_image(&info, None)
-        }.expect("Error on image creation"); // TODO: error handling
-
-        Ok(UnboundImage(n::Image{ raw, ty: image_type, flags, extent }))
+        self.create_raw_image(kind, mip_levels, format, tiling, usage, flags)
+            .map(UnboundImage)
     }
 
     fn get_image_requirements(&self, image: &UnboundImage) -> Requirements {
@@ -1470,11 +1548,56 @@ impl d::Device<B> for Device {
         }
     }
 
+    fn create_event(&self) -> n::Event {
+        let info = vk::EventCreateInfo {
+            s_type: vk::StructureType::EventCreateInfo,
+            p_next: ptr::null(),
+            flags: vk::EventCreateFlags::empty(),
+        };
+
+        let mut event = vk::Event::null();
+        let result = unsafe {
+            self.raw.0
+                .fp_v1_0()
+                .create_event(self.raw.0.handle(), &info, ptr::null(), &mut event)
+        };
+        assert_eq!(vk::Result::Success, result); // TODO: error handling
+
+        n::Event(event)
+    }
+
+    fn get_event_status(&self, event: &n::Event) -> bool {
+        let result = unsafe {
+            self.raw.0.fp_v1_0().get_event_status(self.raw.0.handle(), event.0)
+        };
+        match result {
+            vk::Result::EventSet => true,
+            vk::Result::EventReset => false,
+            _ => panic!("Unexpected get_event_status result {:?}", result),
+        }
+    }
+
+    fn set_event(&self, event: &n::Event) {
+        let result = unsafe {
+            self.raw.0.fp_v1_0().set_event(self.raw.0.handle(), event.0)
+        };
+        assert_eq!(vk::Result::Success, result);
+    }
+
+    fn reset_event(&self, event: &n::Event) {
+        let result = unsafe {
+            self.raw.0.fp_v1_0().reset_event(self.raw.0.handle(), event.0)
+        };
+        assert_eq!(vk::Result::Success, result);
+    }
+
     fn free_memory(&self, memory: n::Memory) {
         unsafe { self.raw.0.free_memory(memory.raw, None); }
     }
 
-    fn create_query_pool(&self, ty: query::QueryType, query_count: query::QueryId) -> n::QueryPool {
+    fn create_query_pool(
+        &self, ty: query::QueryType, query_count: query::QueryId
+    ) -> Result<n::QueryPool, query::Error> {
         let (query_type, pipeline_statistics) = match ty {
             query::QueryType::Occlusion =>
                 (vk::QueryType::Occlusion, vk::QueryPipelineStatisticFlags::empty()),
@@ -1493,12 +1616,13 @@ impl d::Device<B> for Device {
             pipeline_statistics
         };
 
-        let pool = unsafe {
-            self.raw.0.create_query_pool(&info, None)
-                        .expect("Error on query pool creation") // TODO: error handling
-        };
-
-        n::QueryPool(pool)
+        unsafe { self.raw.0.create_query_pool(&info, None) }
+            .map(n::QueryPool)
+            .map_err(|err| match err {
+                vk::Result::ErrorOutOfHostMemory => query::Error::OutOfHostMemory,
+                vk::Result::ErrorOutOfDeviceMemory => query::Error::OutOfDeviceMemory,
+                _ => unreachable!(),
+            })
     }
 
     fn create_swapchain(
@@ -1580,6 +1704,35 @@ impl d::Device<B> for Device {
         unsafe { self.raw.0.destroy_query_pool(pool.0, None); }
     }
 
+    fn get_query_pool_results(
+        &self,
+        pool: &n::QueryPool,
+        queries: Range<query::QueryId>,
+        data: &mut [u8],
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) -> Result<bool, HostExecutionError> {
+        let status = unsafe {
+            self.raw.0
+                .fp_v1_0()
+                .get_query_pool_results(
+                    self.raw.0.handle(),
+                    pool.0,
+                    queries.start,
+                    queries.end - queries.start,
+                    data.len(),
+                    data.as_mut_ptr() as *mut _,
+                    stride,
+                    conv::map_query_result_flags(flags),
+                )
+        };
+        match status {
+            vk::Result::Success => Ok(true),
+            vk::Result::NotReady => Ok(false),
+            error => Err(result::Error::from(error).into()),
+        }
+    }
+
     fn destroy_shader_module(&self, module: n::ShaderModule) {
         unsafe { self.raw.0.destroy_shader_module(module.raw, None); }
     }
@@ -1616,6 +1769,78 @@ impl d::Device<B> for Device {
         unsafe { self.raw.0.destroy_image(image.raw, None); }
     }
 
+    fn create_sparse_buffer(
+        &self, size: u64, usage: buffer::Usage, sparse: SparseFlags,
+    ) -> Result<n::Buffer, buffer::CreationError> {
+        let required = conv::sparse_buffer_features(sparse);
+        if !self.raw.1.contains(required) {
+            return Err(buffer::CreationError::MissingFeature {
+                features: required - self.raw.1,
+            });
+        }
+
+        let flags = conv::map_buffer_sparse_flags(sparse);
+        self.create_raw_buffer(size, usage, flags)
+    }
+
+    fn get_sparse_buffer_requirements(&self, buffer: &n::Buffer) -> Requirements {
+        let req = self.raw.0.get_buffer_memory_requirements(buffer.raw);
+
+        Requirements {
+            size: req.size,
+            alignment: req.alignment,
+            type_mask: req.memory_type_bits as _,
+        }
+    }
+
+    fn create_sparse_image(
+        &self,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        usage: image::Usage,
+        storage_flags: image::StorageFlags,
+        sparse: SparseFlags,
+    ) -> Result<n::Image, image::CreationError> {
+        let required = conv::sparse_image_features(kind, sparse)
+            .ok_or(image::CreationError::Kind)?;
+        if !self.raw.1.contains(required) {
+            return Err(image::CreationError::MissingFeature(required - self.raw.1));
+        }
+
+        let flags = conv::map_image_flags(storage_flags) | conv::map_image_sparse_flags(sparse);
+        self.create_raw_image(kind, mip_levels, format, image::Tiling::Optimal, usage, flags)
+    }
+
+    fn get_sparse_image_requirements(
+        &self, image: &n::Image,
+    ) -> (Requirements, Vec<image::SparseRequirements>) {
+        let req = self.raw.0.get_image_memory_requirements(image.raw);
+        let requirements = Requirements {
+            size: req.size,
+            alignment: req.alignment,
+            type_mask: req.memory_type_bits as _,
+        };
+
+        let fp = self.raw.0.fp_v1_0();
+        let device = self.raw.0.handle();
+        let mut count = 0;
+        let mut sparse = unsafe {
+            fp.get_image_sparse_memory_requirements(device, image.raw, &mut count, ptr::null_mut());
+            Vec::with_capacity(count as usize)
+        };
+        unsafe {
+            fp.get_image_sparse_memory_requirements(device, image.raw, &mut count, sparse.as_mut_ptr());
+            sparse.set_len(count as usize);
+        }
+
+        let sparse = sparse
+            .iter()
+            .map(conv::map_vk_sparse_image_requirements)
+            .collect();
+        (requirements, sparse)
+    }
+
     fn destroy_image_view(&self, view: n::ImageView) {
         unsafe { self.raw.0.destroy_image_view(view.view, None); }
     }
@@ -1636,6 +1861,14 @@ impl d::Device<B> for Device {
         unsafe { self.raw.0.destroy_fence(fence.0, None); }
     }
 
+    fn destroy_event(&self, event: n::Event) {
+        unsafe {
+            self.raw.0
+                .fp_v1_0()
+                .destroy_event(self.raw.0.handle(), event.0, ptr::null())
+        };
+    }
+
     fn destroy_semaphore(&self, semaphore: n::Semaphore) {
         unsafe { self.raw.0.destroy_semaphore(semaphore.0, None); }
     }
@@ -1654,3 +1887,29 @@ fn test_send_sync() {
     fn foo<T: Send+Sync>() {}
     foo::<Device>()
 }
+
+#[test]
+fn test_pipeline_cache_compatibility() {
+    let uuid = [7; 16];
+    let mut header = vec![32, 0, 0, 0, 1, 0, 0, 0, 0x02, 0x10, 0, 0, 0x34, 0x12, 0, 0];
+    header.extend_from_slice(&uuid);
+    let mut data = header.clone();
+    data.extend_from_slice(&[0xAB; 64]);
+
+    assert!(is_pipeline_cache_compatible(&header, 0x1002, 0x1234, &uuid));
+    assert!(is_pipeline_cache_compatible(&data, 0x1002, 0x1234, &uuid));
+    // truncated header
+    assert!(!is_pipeline_cache_compatible(&data[.. 31], 0x1002, 0x1234, &uuid));
+    assert!(!is_pipeline_cache_compatible(&[], 0x1002, 0x1234, &uuid));
+    // another device or driver
+    assert!(!is_pipeline_cache_compatible(&data, 0x10DE, 0x1234, &uuid));
+    assert!(!is_pipeline_cache_compatible(&data, 0x1002, 0x4321, &uuid));
+    assert!(!is_pipeline_cache_compatible(&data, 0x1002, 0x1234, &[8; 16]));
+    // wrong header version or size
+    let mut version = data.clone();
+    version[4] = 2;
+    assert!(!is_pipeline_cache_compatible(&version, 0x1002, 0x1234, &uuid));
+    let mut size = data.clone();
+    size[0] = 16;
+    assert!(!is_pipeline_cache_compatible(&size, 0x1002, 0x1234, &uuid));
+}
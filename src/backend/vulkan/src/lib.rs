@@ -328,6 +328,9 @@ impl hal::queue::QueueFamily for QueueFamily {
     fn max_queues(&self) -> usize {
         self.properties.queue_count as _
     }
+    fn supports_sparse_binding(&self) -> bool {
+        self.properties.queue_flags.subset(vk::QUEUE_SPARSE_BINDING_BIT)
+    }
     fn id(&self) -> queue::QueueFamilyId {
         queue::QueueFamilyId(self.index as _)
     }
@@ -342,8 +345,12 @@ pub struct PhysicalDevice {
 
 impl hal::PhysicalDevice<Backend> for PhysicalDevice {
     fn open(
-        &self, families: &[(&QueueFamily, &[hal::QueuePriority])]
+        &self, families: &[(&QueueFamily, &[hal::QueuePriority])], requested_features: Features,
     ) -> Result<hal::Gpu<Backend>, DeviceCreationError> {
+        if !self.features().contains(requested_features) {
+            return Err(DeviceCreationError::MissingFeature);
+        }
+
         let family_infos = families
             .iter()
             .map(|&(family, priorities)| vk::DeviceQueueCreateInfo {
@@ -356,9 +363,6 @@ impl hal::PhysicalDevice<Backend> for PhysicalDevice {
             })
             .collect::<Vec<_>>();
 
-        // enabled features mask
-        let features = Features::empty();
-
         // Create device
         let device_raw = {
             let cstrings = DEVICE_EXTENSIONS
@@ -371,8 +375,7 @@ impl hal::PhysicalDevice<Backend> for PhysicalDevice {
                 .map(|s| s.as_ptr())
                 .collect::<Vec<_>>();
 
-            // TODO: derive from `features`
-            let enabled_features = unsafe { mem::zeroed() };
+            let enabled_features = conv::map_device_features(requested_features);
             let info = vk::DeviceCreateInfo {
                 s_type: vk::StructureType::DeviceCreateInfo,
                 p_next: ptr::null(),
@@ -411,7 +414,10 @@ impl hal::PhysicalDevice<Backend> for PhysicalDevice {
         }).unwrap();
 
         let device = Device {
-            raw: Arc::new(RawDevice(device_raw, features)),
+            raw: Arc::new(RawDevice(device_raw, requested_features)),
+            vendor_id: self.properties.vendor_id,
+            device_id: self.properties.device_id,
+            pipeline_cache_uuid: self.properties.pipeline_cache_uuid,
         };
 
         let device_arc = device.raw.clone();
@@ -620,6 +626,33 @@ impl hal::PhysicalDevice<Backend> for PhysicalDevice {
         if features.fragment_stores_and_atomics != 0 {
             bits |= Features::FRAGMENT_STORES_AND_ATOMICS;
         }
+        if features.sparse_binding != 0 {
+            bits |= Features::SPARSE_BINDING;
+        }
+        if features.sparse_residency_buffer != 0 {
+            bits |= Features::SPARSE_RESIDENCY_BUFFER;
+        }
+        if features.sparse_residency_image2d != 0 {
+            bits |= Features::SHADER_RESIDENCY_IMAGE_2D;
+        }
+        if features.sparse_residency_image3d != 0 {
+            bits |= Features::SHADER_RESIDENSY_IMAGE_3D;
+        }
+        if features.sparse_residency2samples != 0 {
+            bits |= Features::SPARSE_RESIDENCY_2_SAMPLES;
+        }
+        if features.sparse_residency4samples != 0 {
+            bits |= Features::SPARSE_RESIDENCY_4_SAMPLES;
+        }
+        if features.sparse_residency8samples != 0 {
+            bits |= Features::SPARSE_RESIDENCY_8_SAMPLES;
+        }
+        if features.sparse_residency16samples != 0 {
+            bits |= Features::SPARSE_RESIDENCY_16_SAMPLES;
+        }
+        if features.sparse_residency_aliased != 0 {
+            bits |= Features::SPARSE_RESIDENCY_ALIASED;
+        }
         //TODO: cover more features
 
         bits
@@ -653,6 +686,10 @@ impl hal::PhysicalDevice<Backend> for PhysicalDevice {
             non_coherent_atom_size: limits.non_coherent_atom_size as _,
         }
     }
+
+    fn timestamp_period(&self) -> f32 {
+        self.properties.limits.timestamp_period
+    }
 }
 
 #[doc(hidden)]
@@ -724,6 +761,113 @@ impl hal::queue::RawCommandQueue<Backend> for CommandQueue {
         assert_eq!(Ok(()), result);
     }
 
+    unsafe fn bind_sparse(
+        &mut self,
+        binding: hal::queue::RawSparseBinding<Backend>,
+        fence: Option<&native::Fence>,
+    ) {
+        fn map_bind(bind: &memory::SparseBind<Backend>) -> vk::SparseMemoryBind {
+            let (memory, memory_offset) = bind.memory
+                .map_or((vk::DeviceMemory::null(), 0), |(memory, offset)| (memory.raw, offset));
+            vk::SparseMemoryBind {
+                resource_offset: bind.resource_offset,
+                size: bind.size,
+                memory,
+                memory_offset,
+                flags: vk::SparseMemoryBindFlags::empty(),
+            }
+        }
+
+        let waits = binding.wait_semaphores
+            .iter()
+            .map(|semaphore| semaphore.0)
+            .collect::<Vec<_>>();
+        let signals = binding.signal_semaphores
+            .iter()
+            .map(|semaphore| semaphore.0)
+            .collect::<Vec<_>>();
+
+        let buffer_binds = binding.buffers
+            .iter()
+            .map(|&(_, binds)| binds.iter().map(map_bind).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let buffer_infos = binding.buffers
+            .iter()
+            .zip(&buffer_binds)
+            .map(|(&(buffer, _), binds)| vk::SparseBufferMemoryBindInfo {
+                buffer: buffer.raw,
+                bind_count: binds.len() as u32,
+                p_binds: binds.as_ptr(),
+            })
+            .collect::<Vec<_>>();
+
+        let image_opaque_binds = binding.image_opaques
+            .iter()
+            .map(|&(_, binds)| binds.iter().map(map_bind).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let image_opaque_infos = binding.image_opaques
+            .iter()
+            .zip(&image_opaque_binds)
+            .map(|(&(image, _), binds)| vk::SparseImageOpaqueMemoryBindInfo {
+                image: image.raw,
+                bind_count: binds.len() as u32,
+                p_binds: binds.as_ptr(),
+            })
+            .collect::<Vec<_>>();
+
+        let image_binds = binding.images
+            .iter()
+            .map(|&(_, binds)| {
+                binds
+                    .iter()
+                    .map(|bind| {
+                        let (memory, memory_offset) = bind.memory
+                            .map_or((vk::DeviceMemory::null(), 0), |(memory, offset)| (memory.raw, offset));
+                        vk::SparseImageMemoryBind {
+                            subresource: conv::map_subresource(&bind.subresource),
+                            offset: conv::map_offset(bind.offset),
+                            extent: conv::map_extent(bind.extent),
+                            memory,
+                            memory_offset,
+                            flags: vk::SparseMemoryBindFlags::empty(),
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let image_infos = binding.images
+            .iter()
+            .zip(&image_binds)
+            .map(|(&(image, _), binds)| vk::SparseImageMemoryBindInfo {
+                image: image.raw,
+                bind_count: binds.len() as u32,
+                p_binds: binds.as_ptr(),
+            })
+            .collect::<Vec<_>>();
+
+        let info = vk::BindSparseInfo {
+            s_type: vk::StructureType::BindSparseInfo,
+            p_next: ptr::null(),
+            wait_semaphore_count: waits.len() as u32,
+            p_wait_semaphores: waits.as_ptr(),
+            buffer_bind_count: buffer_infos.len() as u32,
+            p_buffer_binds: buffer_infos.as_ptr(),
+            image_opaque_bind_count: image_opaque_infos.len() as u32,
+            p_image_opaque_binds: image_opaque_infos.as_ptr(),
+            image_bind_count: image_infos.len() as u32,
+            p_image_binds: image_infos.as_ptr(),
+            signal_semaphore_count: signals.len() as u32,
+            p_signal_semaphores: signals.as_ptr(),
+        };
+
+        let fence_raw = fence
+            .map(|fence| fence.0)
+            .unwrap_or(vk::Fence::null());
+
+        let result = self.device.0.fp_v1_0().queue_bind_sparse(*self.raw, 1, &info, fence_raw);
+        assert_eq!(vk::Result::Success, result);
+    }
+
     fn present<IS, S, IW>(&mut self, swapchains: IS, wait_semaphores: IW) -> Result<(), ()>
     where
         IS: IntoIterator<Item = (S, SwapImageIndex)>,
@@ -777,6 +921,10 @@ impl hal::queue::RawCommandQueue<Backend> for CommandQueue {
 
 pub struct Device {
     raw: Arc<RawDevice>,
+    /// Identifiers written in the header of the pipeline cache data.
+    vendor_id: u32,
+    device_id: u32,
+    pipeline_cache_uuid: [u8; 16],
 }
 
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
@@ -817,5 +965,6 @@ impl hal::Backend for Backend {
 
     type Fence = native::Fence;
     type Semaphore = native::Semaphore;
+    type Event = native::Event;
     type QueryPool = native::QueryPool;
 }
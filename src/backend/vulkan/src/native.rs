@@ -12,6 +12,9 @@ pub struct Semaphore(pub vk::Semaphore);
 #[derive(Debug, Hash, PartialEq, Eq)]
 pub struct Fence(pub vk::Fence);
 
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub struct Event(pub vk::Event);
+
 #[derive(Debug, Hash)]
 pub struct GraphicsPipeline(pub vk::Pipeline);
 
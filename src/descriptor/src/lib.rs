@@ -0,0 +1,471 @@
+#![deny(missing_docs)]
+
+//! Descriptor set allocation on top of gfx-hal.
+//!
+//! Descriptor pools have a fixed capacity, chosen at creation, and allocating
+//! from a full or fragmented pool fails. `DescriptorAllocator` takes care of
+//! creating pools on demand, sized for the layouts of the sets and growing with
+//! their usage, and of recycling the sets freed by the application.
+//!
+//! Sets come in two flavours:
+//!
+//! - persistent sets, allocated with `allocate` and returned with `free`. Freed
+//!   sets are kept aside and handed out again for the same layout.
+//! - transient sets, allocated with `allocate_transient`, which live for the
+//!   duration of a frame. They are released all at once, along with their pools,
+//!   when the frame is released.
+//!
+//! ```ignore
+//! let mut allocator = DescriptorAllocator::new();
+//! let layout = allocator.create_layout(&device, &bindings, &[]);
+//! let set = allocator.allocate(&device, &layout)?;
+//! device.write_descriptor_sets(vec![pso::DescriptorSetWrite { set: set.raw(), .. }]);
+//! ```
+
+extern crate gfx_hal as hal;
+#[macro_use]
+extern crate log;
+
+use std::borrow::Borrow;
+use std::collections::VecDeque;
+
+use hal::{pso, Backend, Device};
+use hal::backend::FastHashMap;
+use hal::pso::{AllocationError, DescriptorPool as _};
+
+
+/// Number of sets of the first pool of a layout.
+const MIN_POOL_SETS: usize = 16;
+/// Number of sets pools of a layout stop growing at.
+const MAX_POOL_SETS: usize = 1024;
+/// Number of descriptor types, used to index `DescriptorCounts`.
+const DESCRIPTOR_TYPES: usize = 11;
+
+/// Number of descriptors of each type needed by a set.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DescriptorCounts([usize; DESCRIPTOR_TYPES]);
+
+impl DescriptorCounts {
+    /// Count the descriptors of a set with the given bindings.
+    pub fn from_bindings<I>(bindings: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetLayoutBinding>,
+    {
+        let mut counts = DescriptorCounts::default();
+        for binding in bindings {
+            let binding = binding.borrow();
+            counts.0[binding.ty as usize] += binding.count;
+        }
+        counts
+    }
+
+    /// Number of descriptors of the given type.
+    pub fn get(&self, ty: pso::DescriptorType) -> usize {
+        self.0[ty as usize]
+    }
+
+    /// Ranges of a pool able to hold `sets` sets with these counts.
+    fn ranges(&self, sets: usize) -> Vec<pso::DescriptorRangeDesc> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count != 0)
+            .map(|(ty, &count)| pso::DescriptorRangeDesc {
+                ty: descriptor_type(ty),
+                count: count * sets,
+            })
+            .collect()
+    }
+}
+
+fn descriptor_type(index: usize) -> pso::DescriptorType {
+    use hal::pso::DescriptorType::*;
+    [
+        Sampler, CombinedImageSampler, SampledImage, StorageImage,
+        UniformTexelBuffer, StorageTexelBuffer, UniformBuffer, StorageBuffer,
+        UniformBufferDynamic, StorageBufferDynamic, InputAttachment,
+    ][index]
+}
+
+/// Descriptor set layout created by a `DescriptorAllocator`.
+#[derive(Debug)]
+pub struct Layout<B: Backend> {
+    raw: B::DescriptorSetLayout,
+    id: usize,
+    counts: DescriptorCounts,
+}
+
+impl<B: Backend> Layout<B> {
+    /// The layout of the backend, to create pipeline layouts with.
+    pub fn raw(&self) -> &B::DescriptorSetLayout {
+        &self.raw
+    }
+
+    /// Number of descriptors of each type in the sets of the layout.
+    pub fn counts(&self) -> DescriptorCounts {
+        self.counts
+    }
+}
+
+/// Descriptor set allocated by a `DescriptorAllocator`.
+#[derive(Debug)]
+pub struct DescriptorSet<B: Backend> {
+    raw: B::DescriptorSet,
+    layout: usize,
+}
+
+impl<B: Backend> DescriptorSet<B> {
+    /// The set of the backend, to write descriptors to and bind.
+    pub fn raw(&self) -> &B::DescriptorSet {
+        &self.raw
+    }
+}
+
+#[derive(Debug)]
+struct Pool<B: Backend> {
+    raw: B::DescriptorPool,
+    capacity: usize,
+    allocated: usize,
+}
+
+impl<B: Backend> Pool<B> {
+    fn new(device: &B::Device, counts: &DescriptorCounts, capacity: usize) -> Self {
+        debug!("Creating a descriptor pool of {} sets", capacity);
+        Pool {
+            raw: device.create_descriptor_pool(capacity, counts.ranges(capacity)),
+            capacity,
+            allocated: 0,
+        }
+    }
+
+    /// Allocate a set, unless the pool is full.
+    fn allocate(&mut self, layout: &B::DescriptorSetLayout) -> Result<Option<B::DescriptorSet>, AllocationError> {
+        if self.allocated == self.capacity {
+            return Ok(None);
+        }
+        match self.raw.allocate_set(layout) {
+            Ok(set) => {
+                self.allocated += 1;
+                Ok(Some(set))
+            }
+            Err(AllocationError::OutOfPoolMemory) | Err(AllocationError::FragmentedPool) => {
+                self.allocated = self.capacity;
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Pools holding the transient sets of a frame.
+#[derive(Debug)]
+struct Frame<B: Backend> {
+    index: u64,
+    pools: Vec<(usize, Pool<B>)>,
+}
+
+/// Allocation state of the sets of a layout.
+#[derive(Debug)]
+struct Bucket<B: Backend> {
+    counts: DescriptorCounts,
+    /// Pools of the persistent sets, the last one being allocated from.
+    pools: Vec<Pool<B>>,
+    /// Persistent sets freed by the application, ready to be reused.
+    free: Vec<B::DescriptorSet>,
+    /// Pools of the transient sets of the current frame, the last one being allocated from.
+    transient: Vec<Pool<B>>,
+    /// Pools of released frames, ready to be reused for transient sets.
+    idle: Vec<Pool<B>>,
+}
+
+impl<B: Backend> Bucket<B> {
+    /// Size of the next pool, doubling the capacity of the existing ones.
+    fn next_capacity(pools: &[Pool<B>]) -> usize {
+        let total = pools.iter().map(|pool| pool.capacity).sum::<usize>();
+        total.max(MIN_POOL_SETS).min(MAX_POOL_SETS)
+    }
+
+    fn allocate_from(
+        device: &B::Device,
+        counts: &DescriptorCounts,
+        pools: &mut Vec<Pool<B>>,
+        idle: Option<&mut Vec<Pool<B>>>,
+        layout: &B::DescriptorSetLayout,
+    ) -> Result<B::DescriptorSet, AllocationError> {
+        if let Some(pool) = pools.last_mut() {
+            if let Some(set) = pool.allocate(layout)? {
+                return Ok(set);
+            }
+        }
+        let mut pool = match idle.and_then(Vec::pop) {
+            Some(pool) => pool,
+            None => Pool::new(device, counts, Self::next_capacity(pools)),
+        };
+        let set = pool.allocate(layout)?;
+        pools.push(pool);
+        // A fresh pool has room for at least one set of the layout.
+        set.ok_or(AllocationError::OutOfPoolMemory)
+    }
+}
+
+/// Allocator of descriptor sets, creating descriptor pools as needed.
+///
+/// Sets are allocated for layouts created by the allocator itself, so that every
+/// layout has pools sized for its sets. The pools of a layout grow geometrically
+/// with the number of sets allocated for it.
+#[derive(Debug)]
+pub struct DescriptorAllocator<B: Backend> {
+    buckets: FastHashMap<usize, Bucket<B>>,
+    next_layout: usize,
+    /// Frames waiting for the device to be done with their transient sets.
+    frames: VecDeque<Frame<B>>,
+}
+
+impl<B: Backend> DescriptorAllocator<B> {
+    /// Create an empty allocator.
+    pub fn new() -> Self {
+        DescriptorAllocator {
+            buckets: FastHashMap::default(),
+            next_layout: 0,
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Create a descriptor set layout to allocate sets for.
+    pub fn create_layout<I, J>(
+        &mut self, device: &B::Device, bindings: I, immutable_samplers: J
+    ) -> Layout<B>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<pso::DescriptorSetLayoutBinding>,
+        J: IntoIterator,
+        J::Item: Borrow<B::Sampler>,
+    {
+        let bindings = bindings
+            .into_iter()
+            .map(|binding| binding.borrow().clone())
+            .collect::<Vec<_>>();
+        let counts = DescriptorCounts::from_bindings(&bindings);
+        let raw = device.create_descriptor_set_layout(bindings, immutable_samplers);
+        let id = self.next_layout;
+        self.next_layout += 1;
+        self.buckets.insert(id, Bucket {
+            counts,
+            pools: Vec::new(),
+            free: Vec::new(),
+            transient: Vec::new(),
+            idle: Vec::new(),
+        });
+        Layout { raw, id, counts }
+    }
+
+    /// Destroy a layout, along with the pools of its sets.
+    ///
+    /// All the sets of the layout have to be freed and their frames released beforehand.
+    pub fn destroy_layout(&mut self, device: &B::Device, layout: Layout<B>) {
+        let bucket = self.buckets.remove(&layout.id).expect("Unknown layout");
+        let allocated = bucket.pools.iter().map(|pool| pool.allocated).sum::<usize>();
+        if allocated != bucket.free.len() {
+            error!("Destroying a layout with {} sets still alive", allocated - bucket.free.len());
+        }
+        if !bucket.transient.is_empty() {
+            error!("Destroying a layout with transient sets still alive");
+        }
+        for pool in bucket.pools.into_iter().chain(bucket.transient).chain(bucket.idle) {
+            device.destroy_descriptor_pool(pool.raw);
+        }
+        // The pools of pending frames are destroyed once released.
+        device.destroy_descriptor_set_layout(layout.raw);
+    }
+
+    /// Allocate a persistent set, to be returned with `free`.
+    pub fn allocate(&mut self, device: &B::Device, layout: &Layout<B>) -> Result<DescriptorSet<B>, AllocationError> {
+        let bucket = self.buckets.get_mut(&layout.id).expect("Unknown layout");
+        let raw = match bucket.free.pop() {
+            Some(set) => set,
+            None => Bucket::allocate_from(device, &bucket.counts, &mut bucket.pools, None, &layout.raw)?,
+        };
+        Ok(DescriptorSet { raw, layout: layout.id })
+    }
+
+    /// Return a persistent set to the allocator, for it to be reused by later allocations.
+    ///
+    /// The set must not be in use by the device anymore. Its descriptors are left as they are.
+    pub fn free(&mut self, set: DescriptorSet<B>) {
+        let bucket = self.buckets.get_mut(&set.layout).expect("Unknown layout");
+        bucket.free.push(set.raw);
+    }
+
+    /// Allocate a set living until the current frame is released.
+    pub fn allocate_transient(
+        &mut self, device: &B::Device, layout: &Layout<B>
+    ) -> Result<DescriptorSet<B>, AllocationError> {
+        let bucket = self.buckets.get_mut(&layout.id).expect("Unknown layout");
+        let raw = Bucket::allocate_from(
+            device, &bucket.counts, &mut bucket.transient, Some(&mut bucket.idle), &layout.raw,
+        )?;
+        Ok(DescriptorSet { raw, layout: layout.id })
+    }
+
+    /// Group the transient sets allocated since the previous frame into the frame `index`.
+    ///
+    /// Frame indices are expected to increase.
+    pub fn end_frame(&mut self, index: u64) {
+        let mut pools = Vec::new();
+        for (&id, bucket) in &mut self.buckets {
+            pools.extend(bucket.transient.drain(..).map(|pool| (id, pool)));
+        }
+        self.frames.push_back(Frame { index, pools });
+    }
+
+    /// Release the transient sets of all the frames up to `index`, which the device is done with.
+    ///
+    /// The sets become invalid, and their pools are reset to be reused by the next frames.
+    pub fn release(&mut self, device: &B::Device, index: u64) {
+        while self.frames.front().map_or(false, |frame| frame.index <= index) {
+            let frame = self.frames.pop_front().unwrap();
+            for (id, mut pool) in frame.pools {
+                match self.buckets.get_mut(&id) {
+                    Some(bucket) => {
+                        pool.raw.reset();
+                        pool.allocated = 0;
+                        bucket.idle.push(pool);
+                    }
+                    // The layout has been destroyed in the meantime.
+                    None => device.destroy_descriptor_pool(pool.raw),
+                }
+            }
+        }
+    }
+
+    /// Number of descriptor pools created by the allocator.
+    pub fn pool_count(&self) -> usize {
+        let frames = self.frames.iter().map(|frame| frame.pools.len()).sum::<usize>();
+        self.buckets
+            .values()
+            .map(|bucket| bucket.pools.len() + bucket.transient.len() + bucket.idle.len())
+            .sum::<usize>() + frames
+    }
+
+    /// Destroy all the pools of the allocator.
+    ///
+    /// All the sets have to be freed and their frames released beforehand.
+    pub fn dispose(self, device: &B::Device) {
+        let frames = self.frames.into_iter().flat_map(|frame| frame.pools.into_iter().map(|(_, pool)| pool));
+        let buckets = self.buckets
+            .into_iter()
+            .flat_map(|(_, bucket)| bucket.pools.into_iter().chain(bucket.transient).chain(bucket.idle));
+        for pool in frames.chain(buckets) {
+            device.destroy_descriptor_pool(pool.raw);
+        }
+    }
+}
+
+impl<B: Backend> Default for DescriptorAllocator<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate gfx_backend_empty as empty;
+
+    use hal::{pso, Instance, PhysicalDevice};
+    use super::*;
+
+    fn open() -> empty::Device {
+        let adapter = empty::Instance::create("descriptor", 1).enumerate_adapters().remove(0);
+        let hal::Gpu { device, .. } = adapter.physical_device
+            .open(&[(&adapter.queue_families[0], &[1.0])], hal::Features::empty())
+            .unwrap();
+        device
+    }
+
+    fn bindings() -> Vec<pso::DescriptorSetLayoutBinding> {
+        vec![
+            pso::DescriptorSetLayoutBinding {
+                binding: 0,
+                ty: pso::DescriptorType::UniformBuffer,
+                count: 1,
+                stage_flags: pso::ShaderStageFlags::VERTEX,
+                immutable_samplers: false,
+            },
+            pso::DescriptorSetLayoutBinding {
+                binding: 1,
+                ty: pso::DescriptorType::SampledImage,
+                count: 4,
+                stage_flags: pso::ShaderStageFlags::FRAGMENT,
+                immutable_samplers: false,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_counts() {
+        let counts = DescriptorCounts::from_bindings(&bindings());
+        assert_eq!(counts.get(pso::DescriptorType::UniformBuffer), 1);
+        assert_eq!(counts.get(pso::DescriptorType::SampledImage), 4);
+        assert_eq!(counts.get(pso::DescriptorType::Sampler), 0);
+        let ranges = counts.ranges(16);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!((ranges[0].ty, ranges[0].count), (pso::DescriptorType::SampledImage, 64));
+        assert_eq!((ranges[1].ty, ranges[1].count), (pso::DescriptorType::UniformBuffer, 16));
+    }
+
+    #[test]
+    fn test_growth() {
+        let device = open();
+        let mut allocator = DescriptorAllocator::<empty::Backend>::new();
+        let layout = allocator.create_layout(&device, bindings(), &[]);
+
+        let mut sets = (0 .. MIN_POOL_SETS + 1)
+            .map(|_| allocator.allocate(&device, &layout).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(allocator.pool_count(), 2);
+        // The third pool is as large as the first two.
+        sets.extend((0 .. MIN_POOL_SETS).map(|_| allocator.allocate(&device, &layout).unwrap()));
+        assert_eq!(allocator.pool_count(), 3);
+        sets.extend((0 .. 2 * MIN_POOL_SETS - 1).map(|_| allocator.allocate(&device, &layout).unwrap()));
+        assert_eq!(allocator.pool_count(), 3);
+
+        // Freed sets are reused before allocating from a new pool.
+        allocator.free(sets.pop().unwrap());
+        sets.push(allocator.allocate(&device, &layout).unwrap());
+        assert_eq!(allocator.pool_count(), 3);
+
+        for set in sets {
+            allocator.free(set);
+        }
+        allocator.destroy_layout(&device, layout);
+        assert_eq!(allocator.pool_count(), 0);
+        allocator.dispose(&device);
+    }
+
+    #[test]
+    fn test_transient() {
+        let device = open();
+        let mut allocator = DescriptorAllocator::<empty::Backend>::new();
+        let layout = allocator.create_layout(&device, bindings(), &[]);
+
+        for _ in 0 .. 3 {
+            allocator.allocate_transient(&device, &layout).unwrap();
+        }
+        allocator.end_frame(0);
+        assert_eq!(allocator.pool_count(), 1);
+        // The pool of the previous frame is still in use.
+        allocator.allocate_transient(&device, &layout).unwrap();
+        allocator.end_frame(1);
+        assert_eq!(allocator.pool_count(), 2);
+
+        allocator.release(&device, 0);
+        allocator.allocate_transient(&device, &layout).unwrap();
+        allocator.end_frame(2);
+        assert_eq!(allocator.pool_count(), 2);
+
+        allocator.release(&device, 2);
+        allocator.destroy_layout(&device, layout);
+        allocator.dispose(&device);
+    }
+}
@@ -0,0 +1,78 @@
+//! Placement of transient resources in shared memory.
+
+use std::ops::Range;
+
+use gfx_memory::align_up;
+
+
+/// Memory needed by a transient resource during a range of steps.
+#[derive(Clone, Debug)]
+pub(crate) struct Request {
+    pub size: u64,
+    pub alignment: u64,
+    pub lifetime: Range<usize>,
+}
+
+fn overlaps<T: PartialOrd>(a: &Range<T>, b: &Range<T>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+/// Find an offset for each request so that the resources alive at the same time
+/// don't overlap, while the others can alias the same memory.
+///
+/// Returns the offsets and the total size of the memory. The largest resources
+/// are placed first, each at the lowest offset available.
+pub(crate) fn pack(requests: &[Request]) -> (Vec<u64>, u64) {
+    let mut order = (0 .. requests.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| !requests[i].size);
+
+    let mut offsets = vec![0; requests.len()];
+    let mut placed: Vec<usize> = Vec::new();
+    let mut total = 0;
+    for i in order {
+        let request = &requests[i];
+        let conflicts = placed
+            .iter()
+            .filter(|&&j| overlaps(&requests[j].lifetime, &request.lifetime))
+            .map(|&j| offsets[j] .. offsets[j] + requests[j].size)
+            .collect::<Vec<_>>();
+        let mut candidates = conflicts
+            .iter()
+            .map(|range| align_up(range.end, request.alignment))
+            .chain(Some(0))
+            .collect::<Vec<_>>();
+        candidates.sort();
+        let offset = candidates
+            .into_iter()
+            .find(|&offset| {
+                let range = offset .. offset + request.size;
+                conflicts.iter().all(|other| !overlaps(other, &range))
+            })
+            .unwrap();
+
+        offsets[i] = offset;
+        total = total.max(offset + request.size);
+        placed.push(i);
+    }
+
+    (offsets, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack() {
+        let requests = [
+            Request { size: 100, alignment: 1, lifetime: 0 .. 2 },
+            Request { size: 100, alignment: 1, lifetime: 2 .. 3 },
+            Request { size: 50, alignment: 64, lifetime: 1 .. 3 },
+            Request { size: 20, alignment: 4, lifetime: 3 .. 4 },
+        ];
+        let (offsets, total) = pack(&requests);
+        // The second resource aliases the first one, the last one any of them.
+        assert_eq!(offsets, vec![0, 0, 128, 0]);
+        assert_eq!(total, 178);
+    }
+}
@@ -0,0 +1,585 @@
+//! Building and recording of frame graphs.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use hal::{self, buffer, format, image, memory, pso, Backend, Device};
+use hal::command::{ClearColorRaw, ClearValueRaw, RawCommandBuffer, SubpassContents};
+use hal::pass::{Subpass, SubpassDesc};
+use hal::pso::PipelineStage;
+use track::{self, BufferState, ImageState};
+
+use alias::{pack, Request};
+use pass::Use;
+use plan::{self, PassInfo, Plan, ResourceId, State};
+use resource::{BufferResource, ImageResource, Slot};
+use {GraphError, ImageInfo, Pass, Resources};
+
+
+/// Declaration of the resources and passes of a frame.
+///
+/// Passes are recorded in the order they are added.
+pub struct GraphBuilder<'a, B: Backend> {
+    names: HashMap<String, ResourceId>,
+    images: Vec<(ImageInfo, Option<(&'a track::Image<B>, &'a B::ImageView)>)>,
+    buffers: Vec<(u64, buffer::Usage, Option<&'a track::Buffer<B>>)>,
+    passes: Vec<Pass<'a, B>>,
+}
+
+impl<'a, B: Backend> GraphBuilder<'a, B> {
+    /// Create an empty graph.
+    pub fn new() -> Self {
+        GraphBuilder {
+            names: HashMap::new(),
+            images: Vec::new(),
+            buffers: Vec::new(),
+            passes: Vec::new(),
+        }
+    }
+
+    fn add_name(&mut self, name: &str, id: ResourceId) {
+        let previous = self.names.insert(name.to_string(), id);
+        assert!(previous.is_none(), "Resource {} is declared twice", name);
+    }
+
+    /// Declare a transient image, created by the graph and living only for the passes
+    /// using it. Its contents start undefined at the first pass of every recording.
+    pub fn create_image(&mut self, name: &str, info: ImageInfo) -> &mut Self {
+        let id = ResourceId::Image(self.images.len());
+        self.add_name(name, id);
+        self.images.push((info, None));
+        self
+    }
+
+    /// Declare an image owned by the application, for example a swapchain image,
+    /// with a view of the whole image.
+    ///
+    /// Its contents are preserved, and the passes using it leave it in the state
+    /// of its last use.
+    pub fn import_image(
+        &mut self, name: &str, info: ImageInfo, image: &'a track::Image<B>, view: &'a B::ImageView
+    ) -> &mut Self {
+        let id = ResourceId::Image(self.images.len());
+        self.add_name(name, id);
+        self.images.push((info, Some((image, view))));
+        self
+    }
+
+    /// Declare a transient buffer.
+    pub fn create_buffer(&mut self, name: &str, size: u64, usage: buffer::Usage) -> &mut Self {
+        let id = ResourceId::Buffer(self.buffers.len());
+        self.add_name(name, id);
+        self.buffers.push((size, usage, None));
+        self
+    }
+
+    /// Declare a buffer owned by the application.
+    pub fn import_buffer(&mut self, name: &str, buffer: &'a track::Buffer<B>) -> &mut Self {
+        let id = ResourceId::Buffer(self.buffers.len());
+        self.add_name(name, id);
+        self.buffers.push((0, buffer::Usage::empty(), Some(buffer)));
+        self
+    }
+
+    /// Add a pass after the previous ones.
+    pub fn add_pass(&mut self, pass: Pass<'a, B>) -> &mut Self {
+        self.passes.push(pass);
+        self
+    }
+
+    /// Derive the steps of the graph and create its render passes, framebuffers
+    /// and transient resources.
+    ///
+    /// Transient resources whose lifetimes don't overlap alias the same memory.
+    pub fn build(
+        self, device: &B::Device, memory_properties: &hal::MemoryProperties
+    ) -> Result<Graph<'a, B>, GraphError> {
+        let GraphBuilder { names, images, buffers, passes } = self;
+
+        let infos = passes
+            .iter()
+            .map(|pass| {
+                let uses = pass.uses
+                    .iter()
+                    .map(|&(ref name, u)| {
+                        let id = *names
+                            .get(name)
+                            .ok_or_else(|| GraphError::UnknownResource(name.clone()))?;
+                        match (id, u) {
+                            (ResourceId::Buffer(_), Use::Buffer(_)) => Ok((id, u)),
+                            (ResourceId::Buffer(_), _) |
+                            (ResourceId::Image(_), Use::Buffer(_)) => Err(GraphError::WrongResourceKind(name.clone())),
+                            (ResourceId::Image(_), _) => Ok((id, u)),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(PassInfo {
+                    name: pass.name.clone(),
+                    kind: pass.kind,
+                    uses,
+                })
+            })
+            .collect::<Result<Vec<_>, GraphError>>()?;
+        let image_infos = images.iter().map(|&(ref info, _)| info.clone()).collect::<Vec<_>>();
+        let imported = images
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(_, import))| import.is_some())
+            .map(|(i, _)| ResourceId::Image(i))
+            .chain(buffers
+                .iter()
+                .enumerate()
+                .filter(|&(_, &(_, _, import))| import.is_some())
+                .map(|(i, _)| ResourceId::Buffer(i))
+            )
+            .collect::<HashSet<_>>();
+        let plan = plan::plan(&infos, &image_infos, &imported)?;
+
+        let resources = Resources {
+            names,
+            images: images
+                .into_iter()
+                .map(|(info, import)| ImageResource {
+                    info,
+                    image: import.map(|(image, _)| Slot::Imported(image)),
+                    view: import.map(|(_, view)| Slot::Imported(view)),
+                })
+                .collect(),
+            buffers: buffers
+                .iter()
+                .map(|&(_, _, import)| BufferResource {
+                    buffer: import.map(Slot::Imported),
+                })
+                .collect(),
+        };
+        let mut graph = Graph {
+            resources,
+            passes,
+            steps: Vec::new(),
+            memories: Vec::new(),
+        };
+        let transient_buffers = buffers.iter().map(|&(size, usage, _)| (size, usage)).collect::<Vec<_>>();
+        match graph.create(device, memory_properties, plan, &transient_buffers) {
+            Ok(()) => Ok(graph),
+            Err(e) => {
+                graph.dispose(device);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl<'a, B: Backend> Default for GraphBuilder<'a, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Render pass of a step.
+struct RenderPass<B: Backend> {
+    raw: B::RenderPass,
+    framebuffer: B::Framebuffer,
+    area: pso::Rect,
+    clears: Vec<ClearValueRaw>,
+    /// States of the attachments at the end of the render pass.
+    after: Vec<State>,
+}
+
+/// Consecutive passes recorded after a single barrier.
+struct Step<B: Backend> {
+    passes: Range<usize>,
+    /// Transient resources whose contents start over, with the memory
+    /// dependencies on the resources aliasing them.
+    discards: Vec<State>,
+    before: Vec<State>,
+    render_pass: Option<RenderPass<B>>,
+}
+
+/// Frame graph ready to be recorded, every frame.
+pub struct Graph<'a, B: Backend> {
+    resources: Resources<'a, B>,
+    passes: Vec<Pass<'a, B>>,
+    steps: Vec<Step<B>>,
+    memories: Vec<B::Memory>,
+}
+
+impl<'a, B: Backend> Graph<'a, B> {
+    fn create(
+        &mut self,
+        device: &B::Device,
+        memory_properties: &hal::MemoryProperties,
+        plan: Plan,
+        buffers: &[(u64, buffer::Usage)],
+    ) -> Result<(), GraphError> {
+        let Plan { steps, lifetimes } = plan;
+
+        // Create the transient resources, grouped by memory type. Images and buffers
+        // never share a memory, so that their granularity doesn't matter.
+        let mut unbound_images = HashMap::new();
+        let mut unbound_buffers = HashMap::new();
+        let mut groups = HashMap::<(hal::MemoryTypeId, bool), Vec<(ResourceId, Request)>>::new();
+        let mut ids = lifetimes.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            let lifetime = &lifetimes[&id];
+            let requirements = match id {
+                ResourceId::Image(index) => {
+                    let resource = &self.resources.images[index];
+                    if resource.image.is_some() {
+                        continue;
+                    }
+                    let info = &resource.info;
+                    let unbound = device
+                        .create_image(
+                            info.kind, info.levels, info.format,
+                            image::Tiling::Optimal, info.usage, image::StorageFlags::empty(),
+                        )
+                        .map_err(|_| GraphError::ResourceCreation(self.resources.name(id).to_string()))?;
+                    let requirements = device.get_image_requirements(&unbound);
+                    unbound_images.insert(index, unbound);
+                    requirements
+                }
+                ResourceId::Buffer(index) => {
+                    if self.resources.buffers[index].buffer.is_some() {
+                        continue;
+                    }
+                    let (size, usage) = buffers[index];
+                    let unbound = device
+                        .create_buffer(size, usage)
+                        .map_err(|_| GraphError::ResourceCreation(self.resources.name(id).to_string()))?;
+                    let requirements = device.get_buffer_requirements(&unbound);
+                    unbound_buffers.insert(index, unbound);
+                    requirements
+                }
+            };
+            let memory_type = find_memory_type(memory_properties, requirements.type_mask)
+                .ok_or_else(|| GraphError::ResourceCreation(self.resources.name(id).to_string()))?;
+            let is_image = match id {
+                ResourceId::Image(_) => true,
+                ResourceId::Buffer(_) => false,
+            };
+            groups.entry((memory_type, is_image)).or_insert_with(Vec::new).push((id, Request {
+                size: requirements.size,
+                alignment: requirements.alignment,
+                lifetime: lifetime.steps.clone(),
+            }));
+        }
+
+        let mut discards = vec![Vec::new(); steps.len()];
+        for (&(memory_type, _), resources) in &groups {
+            let requests = resources.iter().map(|&(_, ref r)| r.clone()).collect::<Vec<_>>();
+            let (offsets, size) = pack(&requests);
+            let memory = device
+                .allocate_memory(memory_type, size)
+                .map_err(|_| GraphError::OutOfMemory)?;
+            self.memories.push(memory);
+            let memory = self.memories.last().unwrap();
+
+            for (i, &(id, ref request)) in resources.iter().enumerate() {
+                let error = GraphError::ResourceCreation(self.resources.name(id).to_string());
+                match id {
+                    ResourceId::Image(index) => {
+                        let unbound = unbound_images.remove(&index).unwrap();
+                        let raw = device.bind_image_memory(memory, offsets[i], unbound).map_err(|_| error.clone())?;
+                        let info = self.resources.images[index].info.clone();
+                        let aspects = info.format.surface_desc().aspects;
+                        let range = image::SubresourceRange {
+                            aspects,
+                            levels: 0 .. info.levels,
+                            layers: 0 .. info.kind.num_layers(),
+                        };
+                        let view = device.create_image_view(
+                            &raw, view_kind(info.kind), info.format, format::Swizzle::NO, range,
+                        );
+                        let image = track::Image::new(raw, aspects, info.levels, info.kind.num_layers());
+                        self.resources.images[index].image = Some(Slot::Owned(image));
+                        self.resources.images[index].view = Some(Slot::Owned(view.map_err(|_| error.clone())?));
+                    }
+                    ResourceId::Buffer(index) => {
+                        let unbound = unbound_buffers.remove(&index).unwrap();
+                        let raw = device.bind_buffer_memory(memory, offsets[i], unbound).map_err(|_| error.clone())?;
+                        self.resources.buffers[index].buffer = Some(Slot::Owned(track::Buffer::new(raw)));
+                    }
+                }
+
+                // The first use waits for the last uses of all the resources sharing
+                // the memory, including the previous recording of this one.
+                let range = offsets[i] .. offsets[i] + request.size;
+                let stages = resources
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| {
+                        offsets[j] < range.end && range.start < offsets[j] + requests[j].size
+                    })
+                    .fold(PipelineStage::empty(), |stages, (_, &(other, _))| stages | lifetimes[&other].last_stages);
+                discards[request.lifetime.start].push(match id {
+                    ResourceId::Image(index) => State::Image(index, ImageState {
+                        access: image::Access::MEMORY_WRITE,
+                        layout: image::Layout::Undefined,
+                        stages,
+                    }),
+                    ResourceId::Buffer(index) => State::Buffer(index, BufferState {
+                        access: buffer::Access::MEMORY_WRITE,
+                        stages,
+                    }),
+                });
+            }
+        }
+
+        for (step, discards) in steps.into_iter().zip(discards) {
+            let render_pass = match step.render_pass {
+                Some(rp) => Some(self.create_render_pass(device, rp, &step.passes)?),
+                None => None,
+            };
+            self.steps.push(Step {
+                passes: step.passes,
+                discards,
+                before: step.before,
+                render_pass,
+            });
+        }
+        Ok(())
+    }
+
+    fn create_render_pass(
+        &self, device: &B::Device, rp: plan::RenderPass, passes: &Range<usize>
+    ) -> Result<RenderPass<B>, GraphError> {
+        let subpasses = rp.subpasses
+            .iter()
+            .map(|sp| SubpassDesc {
+                colors: &sp.colors,
+                depth_stencil: sp.depth_stencil.as_ref(),
+                inputs: &sp.inputs,
+                resolves: &[],
+                preserves: &sp.preserves,
+            })
+            .collect::<Vec<_>>();
+        let raw = device.create_render_pass(
+            rp.attachments.iter().map(|a| &a.desc),
+            &subpasses,
+            &rp.dependencies,
+        );
+        let views = rp.attachments
+            .iter()
+            .map(|a| self.resources.images[a.image].view.as_ref().unwrap().get());
+        let framebuffer = match device.create_framebuffer(&raw, views, rp.extent) {
+            Ok(framebuffer) => framebuffer,
+            Err(_) => {
+                device.destroy_render_pass(raw);
+                return Err(GraphError::InvalidAttachments(self.passes[passes.start].name.clone()));
+            }
+        };
+
+        let undefined = ClearValueRaw {
+            color: ClearColorRaw { uint32: [0; 4] },
+        };
+        Ok(RenderPass {
+            raw,
+            framebuffer,
+            area: pso::Rect {
+                x: 0,
+                y: 0,
+                w: rp.extent.width as i16,
+                h: rp.extent.height as i16,
+            },
+            clears: rp.attachments.iter().map(|a| a.clear.map_or(undefined, Into::into)).collect(),
+            after: rp.attachments.iter().map(|a| State::Image(a.image, a.last)).collect(),
+        })
+    }
+
+    /// Resources of the graph.
+    pub fn resources(&self) -> &Resources<'a, B> {
+        &self.resources
+    }
+
+    /// Subpass a graphics pass is recorded in, to create its pipelines.
+    pub fn subpass(&self, pass: &str) -> Option<Subpass<'_, B>> {
+        let index = self.passes.iter().position(|p| p.name == pass)?;
+        let step = self.steps.iter().find(|s| s.passes.start <= index && index < s.passes.end)?;
+        step.render_pass.as_ref().map(|rp| Subpass {
+            index: index - step.passes.start,
+            main_pass: &rp.raw,
+        })
+    }
+
+    /// Record all the passes, with the barriers between them.
+    ///
+    /// Graphics passes merged into a render pass are recorded as consecutive
+    /// subpasses with inline contents.
+    pub fn record<'c>(&'c mut self, cmd: &mut track::CommandBuffer<'c, B>) {
+        let Graph { ref resources, ref mut passes, ref steps, .. } = *self;
+        for step in steps {
+            for state in &step.discards {
+                assume(cmd, resources, state);
+            }
+            for state in &step.before {
+                match *state {
+                    State::Image(index, s) => {
+                        let info = &resources.images[index].info;
+                        cmd.use_image(
+                            resources.tracked_image(index),
+                            0 .. info.levels,
+                            0 .. info.kind.num_layers(),
+                            s.access, s.layout, s.stages,
+                        );
+                    }
+                    State::Buffer(index, s) => {
+                        cmd.use_buffer(resources.tracked_buffer(index), s.access, s.stages);
+                    }
+                }
+            }
+
+            let passes = &mut passes[step.passes.clone()];
+            match step.render_pass {
+                Some(ref rp) => {
+                    cmd.raw().begin_render_pass(
+                        &rp.raw, &rp.framebuffer, rp.area, &rp.clears, SubpassContents::Inline,
+                    );
+                    for (i, pass) in passes.iter_mut().enumerate() {
+                        if i != 0 {
+                            cmd.raw().next_subpass(SubpassContents::Inline);
+                        }
+                        (pass.record)(cmd.raw(), resources);
+                    }
+                    cmd.raw().end_render_pass();
+                    for state in &rp.after {
+                        assume(cmd, resources, state);
+                    }
+                }
+                None => {
+                    for pass in passes {
+                        (pass.record)(cmd.raw(), resources);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Destroy the render passes, framebuffers and transient resources.
+    pub fn dispose(self, device: &B::Device) {
+        for step in self.steps {
+            if let Some(rp) = step.render_pass {
+                device.destroy_framebuffer(rp.framebuffer);
+                device.destroy_render_pass(rp.raw);
+            }
+        }
+        for resource in self.resources.images {
+            if let Some(Slot::Owned(view)) = resource.view {
+                device.destroy_image_view(view);
+            }
+            if let Some(Slot::Owned(image)) = resource.image {
+                device.destroy_image(image.into_raw());
+            }
+        }
+        for resource in self.resources.buffers {
+            if let Some(Slot::Owned(buffer)) = resource.buffer {
+                device.destroy_buffer(buffer.into_raw());
+            }
+        }
+        for memory in self.memories {
+            device.free_memory(memory);
+        }
+    }
+}
+
+fn assume<'c, B: Backend>(cmd: &mut track::CommandBuffer<'c, B>, resources: &'c Resources<B>, state: &State) {
+    match *state {
+        State::Image(index, s) => {
+            let info = &resources.images[index].info;
+            cmd.assume_image(resources.tracked_image(index), 0 .. info.levels, 0 .. info.kind.num_layers(), s);
+        }
+        State::Buffer(index, s) => cmd.assume_buffer(resources.tracked_buffer(index), s),
+    }
+}
+
+/// First memory type allowed by the mask, preferring device local ones.
+fn find_memory_type(memory_properties: &hal::MemoryProperties, type_mask: u64) -> Option<hal::MemoryTypeId> {
+    let types = &memory_properties.memory_types;
+    gfx_memory::find_memory_type(types, type_mask, memory::Properties::DEVICE_LOCAL)
+        .or_else(|| gfx_memory::find_memory_type(types, type_mask, memory::Properties::empty()))
+}
+
+fn view_kind(kind: image::Kind) -> image::ViewKind {
+    match kind {
+        image::Kind::D1(_, 1) => image::ViewKind::D1,
+        image::Kind::D1(..) => image::ViewKind::D1Array,
+        image::Kind::D2(_, _, 1, _) => image::ViewKind::D2,
+        image::Kind::D2(..) => image::ViewKind::D2Array,
+        image::Kind::D3(..) => image::ViewKind::D3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate gfx_backend_empty as empty;
+
+    use std::ptr;
+
+    use hal::{self, buffer, command, Device, Instance, PhysicalDevice, Submission};
+    use hal::pool::CommandPoolCreateFlags;
+    use super::*;
+    use PassKind;
+
+    #[test]
+    fn test_transient_buffers() {
+        let mut adapter = empty::Instance::create("graph", 1).enumerate_adapters().remove(0);
+        let memory_properties = adapter.physical_device.memory_properties();
+        let (device, mut queue_group) = adapter.open_with::<_, hal::General>(1, |_| true).unwrap();
+
+        let memory = device.allocate_memory(hal::MemoryTypeId(0), 8).unwrap();
+        let unbound = device.create_buffer(8, buffer::Usage::TRANSFER_DST).unwrap();
+        let output = track::Buffer::new(device.bind_buffer_memory(&memory, 0, unbound).unwrap());
+
+        // Two temporary buffers filled with a value and copied to the output one.
+        let mut builder = GraphBuilder::new();
+        builder
+            .create_buffer("a", 4, buffer::Usage::TRANSFER_SRC | buffer::Usage::TRANSFER_DST)
+            .create_buffer("b", 4, buffer::Usage::TRANSFER_SRC | buffer::Usage::TRANSFER_DST)
+            .import_buffer("output", &output);
+        for &(name, value, offset) in &[("a", 1, 0), ("b", 2, 4)] {
+            builder
+                .add_pass(
+                    Pass::<empty::Backend>::new(&format!("fill_{}", name), PassKind::Transfer, move |cmd, res| {
+                        cmd.fill_buffer(res.buffer(name).unwrap(), .., value);
+                    })
+                    .buffer(name, buffer::Access::TRANSFER_WRITE, PipelineStage::TRANSFER)
+                )
+                .add_pass(
+                    Pass::<empty::Backend>::new(&format!("copy_{}", name), PassKind::Transfer, move |cmd, res| {
+                        let region = command::BufferCopy { src: 0, dst: offset, size: 4 };
+                        cmd.copy_buffer(res.buffer(name).unwrap(), res.buffer("output").unwrap(), &[region]);
+                    })
+                    .buffer(name, buffer::Access::TRANSFER_READ, PipelineStage::TRANSFER)
+                    .buffer("output", buffer::Access::TRANSFER_WRITE, PipelineStage::TRANSFER)
+                );
+        }
+        let mut graph = builder.build(&device, &memory_properties).unwrap();
+        // Both buffers alias the same memory.
+        assert_eq!(graph.memories.len(), 1);
+        assert_eq!(graph.steps[2].discards, vec![State::Buffer(1, BufferState {
+            access: buffer::Access::MEMORY_WRITE,
+            stages: PipelineStage::TRANSFER,
+        })]);
+
+        let mut pool = device.create_command_pool_typed(&queue_group, CommandPoolCreateFlags::empty(), 1);
+        let submit = {
+            let mut cmd_buffer = pool.acquire_command_buffer(false);
+            {
+                let mut cmd = track::CommandBuffer::new(cmd_buffer.as_raw_mut());
+                graph.record(&mut cmd);
+                cmd.commit();
+            }
+            cmd_buffer.finish()
+        };
+        queue_group.queues[0].submit(Submission::new().submit(Some(submit)), None);
+
+        let ptr = device.map_memory(&memory, 0 .. 8).unwrap();
+        let mut data = [0u32; 2];
+        unsafe {
+            ptr::copy_nonoverlapping(ptr as *const u32, data.as_mut_ptr(), 2);
+        }
+        assert_eq!(data, [1, 2]);
+        device.unmap_memory(&memory);
+        graph.dispose(&device);
+    }
+}
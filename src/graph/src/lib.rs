@@ -0,0 +1,86 @@
+#![deny(missing_docs)]
+
+//! Frame graphs on top of gfx-hal.
+//!
+//! A frame is described as a sequence of named passes, reading and writing
+//! named images and buffers. From this description the graph derives:
+//!
+//! - the render passes, merging consecutive graphics passes into subpasses
+//!   when nothing but their attachments needs synchronization, with the
+//!   load and store operations, layouts, preserved attachments and subpass
+//!   dependencies,
+//! - the framebuffers,
+//! - the transient resources, aliasing the same memory when their lifetimes
+//!   don't overlap,
+//! - the barriers between the steps, inserted with `gfx-track`.
+//!
+//! ```ignore
+//! let mut builder = GraphBuilder::new();
+//! builder
+//!     .create_image("albedo", ImageInfo { kind, levels: 1, format, usage })
+//!     .import_image("target", target_info, &backbuffer, &backbuffer_view)
+//!     .add_pass(Pass::new("gbuffer", PassKind::Graphics, |cmd, _| { .. })
+//!         .color("albedo", Some(ClearColor::Float([0.0; 4]))))
+//!     .add_pass(Pass::new("lighting", PassKind::Graphics, |cmd, _| { .. })
+//!         .input("albedo")
+//!         .color("target", None));
+//! let mut graph = builder.build(&device, &memory_properties)?;
+//! let pipeline = create_pipeline(graph.subpass("lighting").unwrap());
+//!
+//! let mut cmd = gfx_track::CommandBuffer::new(raw_cmd);
+//! graph.record(&mut cmd);
+//! cmd.commit();
+//! ```
+
+#[macro_use]
+extern crate failure;
+extern crate gfx_hal as hal;
+extern crate gfx_memory;
+extern crate gfx_track as track;
+
+use hal::{format, image};
+
+mod alias;
+mod graph;
+mod pass;
+mod plan;
+mod resource;
+
+pub use graph::{Graph, GraphBuilder};
+pub use pass::{Pass, PassKind};
+pub use resource::Resources;
+
+
+/// Error building a graph.
+#[derive(Fail, Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    /// A pass uses a resource that isn't declared.
+    #[fail(display = "Resource {} is not declared.", _0)]
+    UnknownResource(String),
+    /// A pass uses a buffer as an image or the other way around.
+    #[fail(display = "Resource {} is used as another kind of resource.", _0)]
+    WrongResourceKind(String),
+    /// A graphics pass has no attachment or attachments of different extents,
+    /// or another pass has attachments.
+    #[fail(display = "Attachments of pass {} don't fit in a render pass.", _0)]
+    InvalidAttachments(String),
+    /// Creating or binding a transient resource failed.
+    #[fail(display = "Failed to create the transient resource {}.", _0)]
+    ResourceCreation(String),
+    /// Device memory allocation failed.
+    #[fail(display = "Device memory allocation failed.")]
+    OutOfMemory,
+}
+
+/// Description of an image of a graph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageInfo {
+    /// Kind and size of the image.
+    pub kind: image::Kind,
+    /// Number of mip levels.
+    pub levels: image::Level,
+    /// Format of the texels.
+    pub format: format::Format,
+    /// Usage of the image, only used to create transient images.
+    pub usage: image::Usage,
+}
@@ -0,0 +1,130 @@
+//! Passes of a frame and the way they use resources.
+
+use hal::{buffer, image, Backend};
+use hal::command::{ClearColor, ClearDepthStencil};
+use hal::pso::PipelineStage;
+use track::{BufferState, ImageState};
+
+use Resources;
+
+
+/// Kind of work recorded by a pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PassKind {
+    /// Draws, recorded in a subpass of a render pass.
+    Graphics,
+    /// Dispatches, recorded outside of render passes.
+    Compute,
+    /// Copies and clears, recorded outside of render passes.
+    Transfer,
+}
+
+/// Way a pass uses a resource.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Use {
+    Color(Option<ClearColor>),
+    DepthStencil(Option<ClearDepthStencil>),
+    Input,
+    Image(ImageState),
+    Buffer(BufferState),
+}
+
+impl Use {
+    /// State of the image if it's used as an attachment.
+    pub(crate) fn attachment_state(&self) -> Option<ImageState> {
+        match *self {
+            Use::Color(_) => Some(ImageState {
+                access: image::Access::COLOR_ATTACHMENT_READ | image::Access::COLOR_ATTACHMENT_WRITE,
+                layout: image::Layout::ColorAttachmentOptimal,
+                stages: PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            }),
+            Use::DepthStencil(_) => Some(ImageState {
+                access: image::Access::DEPTH_STENCIL_ATTACHMENT_READ | image::Access::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                layout: image::Layout::DepthStencilAttachmentOptimal,
+                stages: PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS,
+            }),
+            Use::Input => Some(ImageState {
+                access: image::Access::INPUT_ATTACHMENT_READ,
+                layout: image::Layout::ShaderReadOnlyOptimal,
+                stages: PipelineStage::FRAGMENT_SHADER,
+            }),
+            Use::Image(_) | Use::Buffer(_) => None,
+        }
+    }
+
+    pub(crate) fn is_attachment(&self) -> bool {
+        self.attachment_state().is_some()
+    }
+
+    pub(crate) fn stages(&self) -> PipelineStage {
+        match *self {
+            Use::Image(state) => state.stages,
+            Use::Buffer(state) => state.stages,
+            _ => self.attachment_state().unwrap().stages,
+        }
+    }
+}
+
+/// Pass of a frame, declaring the resources it uses by name and recording
+/// its commands with a callback.
+///
+/// The callback gets the raw command buffer and the resources of the graph.
+/// For graphics passes, it is called inside the subpass of the pass.
+pub struct Pass<'a, B: Backend> {
+    pub(crate) name: String,
+    pub(crate) kind: PassKind,
+    pub(crate) uses: Vec<(String, Use)>,
+    pub(crate) record: Box<dyn FnMut(&mut B::CommandBuffer, &Resources<'a, B>) + 'a>,
+}
+
+impl<'a, B: Backend> Pass<'a, B> {
+    /// Create a pass without any resource.
+    pub fn new<F>(name: &str, kind: PassKind, record: F) -> Self
+    where
+        F: FnMut(&mut B::CommandBuffer, &Resources<'a, B>) + 'a,
+    {
+        Pass {
+            name: name.to_string(),
+            kind,
+            uses: Vec::new(),
+            record: Box::new(record),
+        }
+    }
+
+    /// Name of the pass.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Render to an image as a color attachment, optionally clearing it first.
+    pub fn color(mut self, name: &str, clear: Option<ClearColor>) -> Self {
+        self.uses.push((name.to_string(), Use::Color(clear)));
+        self
+    }
+
+    /// Use an image as the depth-stencil attachment, optionally clearing it first.
+    pub fn depth_stencil(mut self, name: &str, clear: Option<ClearDepthStencil>) -> Self {
+        self.uses.push((name.to_string(), Use::DepthStencil(clear)));
+        self
+    }
+
+    /// Read an image rendered by a previous pass as an input attachment.
+    pub fn input(mut self, name: &str) -> Self {
+        self.uses.push((name.to_string(), Use::Input));
+        self
+    }
+
+    /// Access a whole image in any other way, such as sampling or storage.
+    pub fn image(
+        mut self, name: &str, access: image::Access, layout: image::Layout, stages: PipelineStage
+    ) -> Self {
+        self.uses.push((name.to_string(), Use::Image(ImageState { access, layout, stages })));
+        self
+    }
+
+    /// Access a buffer.
+    pub fn buffer(mut self, name: &str, access: buffer::Access, stages: PipelineStage) -> Self {
+        self.uses.push((name.to_string(), Use::Buffer(BufferState { access, stages })));
+        self
+    }
+}
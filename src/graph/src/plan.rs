@@ -0,0 +1,480 @@
+//! Grouping of the passes into steps and derivation of the render passes
+//! and resource states, independently of any device.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use hal::{format, image};
+use hal::command::ClearValue;
+use hal::pass::{
+    AttachmentLoadOp, AttachmentOps, AttachmentRef, AttachmentStoreOp, SubpassDependency, SubpassRef,
+};
+use hal::pso::PipelineStage;
+use track::{BufferState, ImageState};
+
+use pass::Use;
+use {GraphError, ImageInfo, PassKind};
+
+
+/// Resource of a graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum ResourceId {
+    Image(usize),
+    Buffer(usize),
+}
+
+/// State of a whole resource.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum State {
+    Image(usize, ImageState),
+    Buffer(usize, BufferState),
+}
+
+/// Pass with its resources resolved.
+#[derive(Debug)]
+pub(crate) struct PassInfo {
+    pub name: String,
+    pub kind: PassKind,
+    pub uses: Vec<(ResourceId, Use)>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Attachment {
+    pub image: usize,
+    pub desc: ::hal::pass::Attachment,
+    pub clear: Option<ClearValue>,
+    pub first: ImageState,
+    pub last: ImageState,
+    /// Subpasses from the first to the last use.
+    pub subpasses: Range<usize>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Subpass {
+    pub colors: Vec<AttachmentRef>,
+    pub depth_stencil: Option<AttachmentRef>,
+    pub inputs: Vec<AttachmentRef>,
+    pub preserves: Vec<usize>,
+}
+
+impl Subpass {
+    fn uses(&self, attachment: usize) -> bool {
+        self.colors.iter().chain(&self.depth_stencil).chain(&self.inputs).any(|&(id, _)| id == attachment)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct RenderPass {
+    pub attachments: Vec<Attachment>,
+    pub subpasses: Vec<Subpass>,
+    pub dependencies: Vec<SubpassDependency>,
+    pub extent: image::Extent,
+}
+
+/// Consecutive passes recorded without barriers between them.
+#[derive(Debug)]
+pub(crate) struct Step {
+    pub passes: Range<usize>,
+    /// States the resources need to be in before the step.
+    pub before: Vec<State>,
+    /// Render pass of graphics steps, one subpass per pass.
+    pub render_pass: Option<RenderPass>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Lifetime {
+    pub steps: Range<usize>,
+    /// Stages of the uses in the last step.
+    pub last_stages: PipelineStage,
+}
+
+#[derive(Debug)]
+pub(crate) struct Plan {
+    pub steps: Vec<Step>,
+    pub lifetimes: HashMap<ResourceId, Lifetime>,
+}
+
+/// Group the passes into steps, merging consecutive graphics passes into
+/// the subpasses of a single render pass when possible.
+pub(crate) fn plan(
+    passes: &[PassInfo], images: &[ImageInfo], imported: &HashSet<ResourceId>
+) -> Result<Plan, GraphError> {
+    let mut pass_uses = HashMap::<ResourceId, Range<usize>>::new();
+    for (i, pass) in passes.iter().enumerate() {
+        let extents = pass.uses
+            .iter()
+            .filter(|&&(_, ref u)| u.is_attachment())
+            .map(|&(id, _)| match id {
+                ResourceId::Image(index) => images[index].kind.extent(),
+                ResourceId::Buffer(_) => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        let valid = match pass.kind {
+            PassKind::Graphics => !extents.is_empty() && extents.iter().all(|e| *e == extents[0]),
+            PassKind::Compute | PassKind::Transfer => extents.is_empty(),
+        };
+        if !valid {
+            return Err(GraphError::InvalidAttachments(pass.name.clone()));
+        }
+        for &(id, _) in &pass.uses {
+            pass_uses.entry(id).or_insert(i .. i + 1).end = i + 1;
+        }
+    }
+
+    let mut groups: Vec<Range<usize>> = Vec::new();
+    for (i, pass) in passes.iter().enumerate() {
+        let merge = match groups.last() {
+            Some(group) => {
+                pass.kind == PassKind::Graphics &&
+                passes[group.start].kind == PassKind::Graphics &&
+                can_merge(&passes[group.clone()], pass, images)
+            }
+            None => false,
+        };
+        if merge {
+            groups.last_mut().unwrap().end = i + 1;
+        } else {
+            groups.push(i .. i + 1);
+        }
+    }
+
+    let mut lifetimes = HashMap::new();
+    let mut steps = Vec::with_capacity(groups.len());
+    for (s, group) in groups.into_iter().enumerate() {
+        for pass in &passes[group.clone()] {
+            for &(id, ref u) in &pass.uses {
+                let lifetime = lifetimes.entry(id).or_insert(Lifetime {
+                    steps: s .. s + 1,
+                    last_stages: PipelineStage::empty(),
+                });
+                if lifetime.steps.end != s + 1 {
+                    lifetime.steps.end = s + 1;
+                    lifetime.last_stages = PipelineStage::empty();
+                }
+                lifetime.last_stages |= u.stages();
+            }
+        }
+        steps.push(plan_step(passes, group, images, imported, &pass_uses));
+    }
+
+    Ok(Plan { steps, lifetimes })
+}
+
+/// Check if a graphics pass can be recorded as the next subpass of a group.
+///
+/// Apart from the attachments, which are synchronized by subpass dependencies,
+/// the pass can't use any resource of the group as it would need a barrier.
+fn can_merge(group: &[PassInfo], pass: &PassInfo, images: &[ImageInfo]) -> bool {
+    let extent = |p: &PassInfo| {
+        p.uses
+            .iter()
+            .filter_map(|&(id, ref u)| match id {
+                ResourceId::Image(index) if u.is_attachment() => Some(images[index].kind.extent()),
+                _ => None,
+            })
+            .next()
+    };
+    extent(&group[0]) == extent(pass) && pass.uses.iter().all(|&(id, ref u)| {
+        group.iter().all(|other| {
+            other.uses.iter().all(|&(other_id, ref other_use)| {
+                other_id != id || (u.is_attachment() && other_use.is_attachment())
+            })
+        })
+    })
+}
+
+fn plan_step(
+    passes: &[PassInfo],
+    group: Range<usize>,
+    images: &[ImageInfo],
+    imported: &HashSet<ResourceId>,
+    pass_uses: &HashMap<ResourceId, Range<usize>>,
+) -> Step {
+    let mut before = Vec::new();
+    if passes[group.start].kind != PassKind::Graphics {
+        for pass in &passes[group.clone()] {
+            before.extend(pass.uses.iter().map(|&(id, u)| state(id, u)));
+        }
+        return Step {
+            passes: group,
+            before,
+            render_pass: None,
+        };
+    }
+
+    let mut attachments: Vec<Attachment> = Vec::new();
+    let mut subpasses = Vec::new();
+    let mut dependencies = Vec::new();
+    for (sp, pass) in passes[group.clone()].iter().enumerate() {
+        let mut subpass = Subpass::default();
+        for &(id, u) in &pass.uses {
+            let state = match u.attachment_state() {
+                Some(state) => state,
+                None => {
+                    before.push(self::state(id, u));
+                    continue;
+                }
+            };
+            let image = match id {
+                ResourceId::Image(index) => index,
+                ResourceId::Buffer(_) => unreachable!(),
+            };
+            let index = match attachments.iter().position(|a| a.image == image) {
+                Some(index) => {
+                    let a = &attachments[index];
+                    let previous = a.subpasses.end - 1;
+                    if previous != sp && (writes(&a.last) || writes(&state)) {
+                        add_dependency(&mut dependencies, previous, sp, a.last, state);
+                    }
+                    index
+                }
+                None => {
+                    attachments.push(new_attachment(
+                        image, u, state, &images[image], imported.contains(&id), &pass_uses[&id], &group, sp,
+                    ));
+                    attachments.len() - 1
+                }
+            };
+
+            let a = &mut attachments[index];
+            a.last = state;
+            a.desc.layouts.end = state.layout;
+            a.subpasses.end = sp + 1;
+            let reference = (index, state.layout);
+            match u {
+                Use::Color(_) => subpass.colors.push(reference),
+                Use::DepthStencil(_) => subpass.depth_stencil = Some(reference),
+                Use::Input => subpass.inputs.push(reference),
+                Use::Image(_) | Use::Buffer(_) => unreachable!(),
+            }
+        }
+        subpasses.push(subpass);
+    }
+
+    // Keep the contents of the attachments in the subpasses between their uses.
+    for (index, a) in attachments.iter().enumerate() {
+        for sp in a.subpasses.clone() {
+            if !subpasses[sp].uses(index) {
+                subpasses[sp].preserves.push(index);
+            }
+        }
+    }
+    before.extend(attachments.iter().map(|a| State::Image(a.image, a.first)));
+
+    let extent = images[attachments[0].image].kind.extent();
+    Step {
+        passes: group,
+        before,
+        render_pass: Some(RenderPass {
+            attachments,
+            subpasses,
+            dependencies,
+            extent,
+        }),
+    }
+}
+
+fn new_attachment(
+    image: usize,
+    u: Use,
+    state: ImageState,
+    info: &ImageInfo,
+    imported: bool,
+    pass_uses: &Range<usize>,
+    group: &Range<usize>,
+    subpass: usize,
+) -> Attachment {
+    let (load, clear) = match u {
+        Use::Color(Some(color)) => (AttachmentLoadOp::Clear, Some(ClearValue::Color(color))),
+        Use::DepthStencil(Some(ds)) => (AttachmentLoadOp::Clear, Some(ClearValue::DepthStencil(ds))),
+        // Transient contents written by a previous step.
+        _ if imported || pass_uses.start < group.start => (AttachmentLoadOp::Load, None),
+        _ => (AttachmentLoadOp::DontCare, None),
+    };
+    let store = if imported || pass_uses.end > group.end {
+        AttachmentStoreOp::Store
+    } else {
+        AttachmentStoreOp::DontCare
+    };
+    let ops = AttachmentOps::new(load, store);
+    let stencil = info.format.surface_desc().aspects.contains(format::Aspects::STENCIL);
+
+    Attachment {
+        image,
+        desc: ::hal::pass::Attachment {
+            format: Some(info.format),
+            samples: info.kind.num_samples(),
+            ops,
+            stencil_ops: if stencil { ops } else { AttachmentOps::DONT_CARE },
+            layouts: state.layout .. state.layout,
+        },
+        clear,
+        first: state,
+        last: state,
+        subpasses: subpass .. subpass + 1,
+    }
+}
+
+fn state(id: ResourceId, u: Use) -> State {
+    match (id, u) {
+        (ResourceId::Image(index), Use::Image(state)) => State::Image(index, state),
+        (ResourceId::Buffer(index), Use::Buffer(state)) => State::Buffer(index, state),
+        _ => unreachable!(),
+    }
+}
+
+fn writes(state: &ImageState) -> bool {
+    state.access.intersects(
+        image::Access::COLOR_ATTACHMENT_WRITE | image::Access::DEPTH_STENCIL_ATTACHMENT_WRITE
+    )
+}
+
+fn add_dependency(
+    dependencies: &mut Vec<SubpassDependency>, src: usize, dst: usize, before: ImageState, after: ImageState
+) {
+    let passes = SubpassRef::Pass(src) .. SubpassRef::Pass(dst);
+    match dependencies.iter_mut().find(|d| d.passes == passes) {
+        Some(d) => {
+            d.stages.start |= before.stages;
+            d.stages.end |= after.stages;
+            d.accesses.start |= before.access;
+            d.accesses.end |= after.access;
+        }
+        None => dependencies.push(SubpassDependency {
+            passes,
+            stages: before.stages .. after.stages,
+            accesses: before.access .. after.access,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hal::command::{ClearColor, ClearDepthStencil};
+    use hal::pso::PipelineStage;
+    use hal::{format, image};
+    use super::*;
+
+    fn info(size: image::Size, format: format::Format) -> ImageInfo {
+        ImageInfo {
+            kind: image::Kind::D2(size, size, 1, 1),
+            levels: 1,
+            format,
+            usage: image::Usage::COLOR_ATTACHMENT,
+        }
+    }
+
+    fn pass(name: &str, kind: PassKind, uses: Vec<(ResourceId, Use)>) -> PassInfo {
+        PassInfo {
+            name: name.to_string(),
+            kind,
+            uses,
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let (shadow, albedo, depth, target) = (
+            ResourceId::Image(0), ResourceId::Image(1), ResourceId::Image(2), ResourceId::Image(3),
+        );
+        let images = [
+            info(512, format::Format::D32Float),
+            info(64, format::Format::Rgba8Unorm),
+            info(64, format::Format::D32Float),
+            info(64, format::Format::Rgba8Srgb),
+        ];
+        let clear = Some(ClearColor::Float([0.0; 4]));
+        let passes = [
+            pass("shadow", PassKind::Graphics, vec![(shadow, Use::DepthStencil(Some(ClearDepthStencil(1.0, 0))))]),
+            pass("gbuffer", PassKind::Graphics, vec![
+                (shadow, Use::Image(ImageState {
+                    access: image::Access::SHADER_READ,
+                    layout: image::Layout::ShaderReadOnlyOptimal,
+                    stages: PipelineStage::FRAGMENT_SHADER,
+                })),
+                (albedo, Use::Color(clear)),
+                (depth, Use::DepthStencil(Some(ClearDepthStencil(1.0, 0)))),
+            ]),
+            pass("lighting", PassKind::Graphics, vec![(albedo, Use::Input), (target, Use::Color(None))]),
+            pass("post", PassKind::Compute, vec![(target, Use::Image(ImageState {
+                access: image::Access::SHADER_READ | image::Access::SHADER_WRITE,
+                layout: image::Layout::General,
+                stages: PipelineStage::COMPUTE_SHADER,
+            }))]),
+        ];
+        let imported = Some(target).into_iter().collect();
+        let plan = plan(&passes, &images, &imported).unwrap();
+
+        // The shadow pass has another extent, and it is sampled by the gbuffer pass.
+        let groups = plan.steps.iter().map(|s| s.passes.clone()).collect::<Vec<_>>();
+        assert_eq!(groups, vec![0 .. 1, 1 .. 3, 3 .. 4]);
+        let rp = plan.steps[1].render_pass.as_ref().unwrap();
+        let ops = rp.attachments.iter().map(|a| (a.image, a.desc.ops)).collect::<Vec<_>>();
+        assert_eq!(ops, vec![
+            (1, AttachmentOps::new(AttachmentLoadOp::Clear, AttachmentStoreOp::DontCare)),
+            (2, AttachmentOps::new(AttachmentLoadOp::Clear, AttachmentStoreOp::DontCare)),
+            (3, AttachmentOps::new(AttachmentLoadOp::Load, AttachmentStoreOp::Store)),
+        ]);
+        assert_eq!(
+            rp.attachments[0].desc.layouts,
+            image::Layout::ColorAttachmentOptimal .. image::Layout::ShaderReadOnlyOptimal
+        );
+        assert_eq!(rp.subpasses[1].inputs, vec![(0, image::Layout::ShaderReadOnlyOptimal)]);
+        assert_eq!(rp.dependencies.len(), 1);
+        assert_eq!(rp.dependencies[0].passes, SubpassRef::Pass(0) .. SubpassRef::Pass(1));
+        assert_eq!(
+            rp.dependencies[0].stages,
+            PipelineStage::COLOR_ATTACHMENT_OUTPUT .. PipelineStage::FRAGMENT_SHADER
+        );
+        // The shadow map is transitioned with the attachments before the render pass.
+        assert!(plan.steps[1].before.contains(&State::Image(0, ImageState {
+            access: image::Access::SHADER_READ,
+            layout: image::Layout::ShaderReadOnlyOptimal,
+            stages: PipelineStage::FRAGMENT_SHADER,
+        })));
+
+        assert_eq!(plan.lifetimes[&shadow], Lifetime {
+            steps: 0 .. 2,
+            last_stages: PipelineStage::FRAGMENT_SHADER,
+        });
+        assert_eq!(plan.lifetimes[&target].steps, 1 .. 3);
+    }
+
+    #[test]
+    fn test_preserve() {
+        let (a, b) = (ResourceId::Image(0), ResourceId::Image(1));
+        let images = [info(64, format::Format::Rgba8Unorm), info(64, format::Format::Rgba8Unorm)];
+        let passes = [
+            pass("first", PassKind::Graphics, vec![(a, Use::Color(None))]),
+            pass("second", PassKind::Graphics, vec![(b, Use::Color(None))]),
+            pass("third", PassKind::Graphics, vec![(a, Use::Input), (b, Use::Input)]),
+        ];
+        let plan = plan(&passes, &images, &HashSet::new()).unwrap();
+
+        assert_eq!(plan.steps.len(), 1);
+        let rp = plan.steps[0].render_pass.as_ref().unwrap();
+        assert_eq!(rp.subpasses[1].preserves, vec![0]);
+        let passes = rp.dependencies.iter().map(|d| d.passes.clone()).collect::<Vec<_>>();
+        assert_eq!(passes, vec![
+            SubpassRef::Pass(0) .. SubpassRef::Pass(2),
+            SubpassRef::Pass(1) .. SubpassRef::Pass(2),
+        ]);
+        // Nothing uses the attachments after the render pass.
+        assert!(rp.attachments.iter().all(|a| a.desc.ops.store == AttachmentStoreOp::DontCare));
+    }
+
+    #[test]
+    fn test_invalid_attachments() {
+        let images = [info(64, format::Format::Rgba8Unorm), info(32, format::Format::D32Float)];
+        let passes = [pass("mismatch", PassKind::Graphics, vec![
+            (ResourceId::Image(0), Use::Color(None)),
+            (ResourceId::Image(1), Use::DepthStencil(None)),
+        ])];
+        assert_eq!(
+            plan(&passes, &images, &HashSet::new()).unwrap_err(),
+            GraphError::InvalidAttachments("mismatch".to_string())
+        );
+        let passes = [pass("compute", PassKind::Compute, vec![(ResourceId::Image(0), Use::Color(None))])];
+        assert!(plan(&passes, &images, &HashSet::new()).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+//! Resources of a graph, either created by it or imported.
+
+use std::collections::HashMap;
+
+use hal::Backend;
+use track;
+
+use plan::ResourceId;
+use ImageInfo;
+
+
+pub(crate) enum Slot<'a, T: 'a> {
+    Owned(T),
+    Imported(&'a T),
+}
+
+impl<'a, T> Slot<'a, T> {
+    pub(crate) fn get(&self) -> &T {
+        match *self {
+            Slot::Owned(ref value) => value,
+            Slot::Imported(value) => value,
+        }
+    }
+}
+
+pub(crate) struct ImageResource<'a, B: Backend> {
+    pub info: ImageInfo,
+    /// `None` for transient images that no pass uses.
+    pub image: Option<Slot<'a, track::Image<B>>>,
+    pub view: Option<Slot<'a, B::ImageView>>,
+}
+
+pub(crate) struct BufferResource<'a, B: Backend> {
+    /// `None` for transient buffers that no pass uses.
+    pub buffer: Option<Slot<'a, track::Buffer<B>>>,
+}
+
+/// Resources of a graph, looked up by name by the passes when recording.
+///
+/// Transient resources that no pass uses are never created.
+pub struct Resources<'a, B: Backend> {
+    pub(crate) names: HashMap<String, ResourceId>,
+    pub(crate) images: Vec<ImageResource<'a, B>>,
+    pub(crate) buffers: Vec<BufferResource<'a, B>>,
+}
+
+impl<'a, B: Backend> Resources<'a, B> {
+    /// Image with the given name.
+    pub fn image(&self, name: &str) -> Option<&B::Image> {
+        match self.names.get(name) {
+            Some(&ResourceId::Image(index)) => self.images[index].image.as_ref().map(|i| i.get().raw()),
+            _ => None,
+        }
+    }
+
+    /// View of the whole image with the given name.
+    pub fn image_view(&self, name: &str) -> Option<&B::ImageView> {
+        match self.names.get(name) {
+            Some(&ResourceId::Image(index)) => self.images[index].view.as_ref().map(Slot::get),
+            _ => None,
+        }
+    }
+
+    /// Buffer with the given name.
+    pub fn buffer(&self, name: &str) -> Option<&B::Buffer> {
+        match self.names.get(name) {
+            Some(&ResourceId::Buffer(index)) => self.buffers[index].buffer.as_ref().map(|b| b.get().raw()),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn tracked_image(&self, index: usize) -> &track::Image<B> {
+        self.images[index].image.as_ref().unwrap().get()
+    }
+
+    pub(crate) fn tracked_buffer(&self, index: usize) -> &track::Buffer<B> {
+        self.buffers[index].buffer.as_ref().unwrap().get()
+    }
+
+    pub(crate) fn name(&self, id: ResourceId) -> &str {
+        self.names
+            .iter()
+            .find(|&(_, &other)| other == id)
+            .map(|(name, _)| name.as_str())
+            .unwrap()
+    }
+}
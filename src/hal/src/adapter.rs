@@ -51,11 +51,13 @@ pub struct MemoryProperties {
 
 /// Represents a physical device (such as a GPU) capable of supporting the given backend.
 pub trait PhysicalDevice<B: Backend>: Any + Send + Sync {
-    /// Create a new logical device.
+    /// Create a new logical device with the requested features enabled.
     ///
     /// # Errors
     ///
     /// - Returns `TooManyObjects` if the implementation can't create a new logical device.
+    /// - Returns `MissingFeature` if the physical device doesn't support all the
+    ///   requested features.
     ///
     /// # Examples
     ///
@@ -63,15 +65,15 @@ pub trait PhysicalDevice<B: Backend>: Any + Send + Sync {
     /// # extern crate gfx_backend_empty as empty;
     /// # extern crate gfx_hal;
     /// # fn main() {
-    /// use gfx_hal::PhysicalDevice;
+    /// use gfx_hal::{Features, PhysicalDevice};
     ///
     /// # let physical_device: empty::PhysicalDevice = return;
     /// # let family: empty::QueueFamily = return;
-    /// let gpu = physical_device.open(&[(&family, &[1.0; 1])]);
+    /// let gpu = physical_device.open(&[(&family, &[1.0; 1])], Features::empty());
     /// # }
     /// ```
     fn open(
-        &self, families: &[(&B::QueueFamily, &[QueuePriority])]
+        &self, families: &[(&B::QueueFamily, &[QueuePriority])], requested_features: Features,
     ) -> Result<Gpu<B>, DeviceCreationError>;
 
     /// Fetch details for a particular format.
@@ -94,6 +96,10 @@ pub trait PhysicalDevice<B: Backend>: Any + Send + Sync {
 
     /// Returns the resource limits of this `Device`.
     fn limits(&self) -> Limits;
+
+    /// Returns the number of nanoseconds it takes for a timestamp query value
+    /// to be incremented by one.
+    fn timestamp_period(&self) -> f32;
 }
 
 /// Metadata about a backend adapter.
@@ -127,7 +133,7 @@ pub struct Adapter<B: Backend> {
 impl<B: Backend> Adapter<B> {
     /// Open the physical device with `count` queues from some active queue family. The family is
     /// the first that both provides the capability `C`, supports at least `count' queues, and for
-    /// which `selector` returns true.
+    /// which `selector` returns true. No features are enabled on the device.
     ///
     /// # Examples
     ///
@@ -170,7 +176,7 @@ impl<B: Backend> Adapter<B> {
             _ => return Err(DeviceCreationError::InitializationFailed),
         };
 
-        let Gpu { device, mut queues } = self.physical_device.open(&families)?;
+        let Gpu { device, mut queues } = self.physical_device.open(&families, Features::empty())?;
         Ok((device, queues.take(id).unwrap()))
     }
 }
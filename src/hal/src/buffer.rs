@@ -6,7 +6,7 @@
 //! They can be used as shader resources, vertex buffers, index buffers or for
 //! specifying the action commands for indirect execution.
 
-use {format, IndexType, Backend};
+use {format, Backend, Features, IndexType};
 
 
 /// An offset inside a buffer, in bytes.
@@ -34,6 +34,12 @@ pub enum CreationError {
         /// Unsupported usage passed on buffer creation.
         usage: Usage,
     },
+    /// The buffer requires features which are not enabled on the device.
+    #[fail(display = "Device features not enabled ({:?}).", features)]
+    MissingFeature {
+        /// Features required by the buffer.
+        features: Features,
+    },
 }
 
 /// Error creating a buffer view.
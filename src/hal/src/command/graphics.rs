@@ -4,7 +4,8 @@ use std::ops::Range;
 
 use Backend;
 use {buffer, image, pso};
-use query::{Query, QueryControl, QueryId};
+use memory::Barrier;
+use query::{Query, QueryControl, QueryId, ResultFlags};
 use queue::capability::{Graphics, GraphicsOrCompute, Supports};
 use super::{
     CommandBuffer, RawCommandBuffer,
@@ -356,4 +357,42 @@ impl<'a, B: Backend, C: Supports<GraphicsOrCompute>, S: Shot, L: Level> CommandB
     pub fn write_timestamp(&mut self, stage: pso::PipelineStage, query: Query<B>) {
         self.raw.write_timestamp(stage, query)
     }
+
+    /// Identical to the `RawCommandBuffer` method of the same name.
+    pub fn copy_query_pool_results(
+        &mut self,
+        pool: &B::QueryPool,
+        queries: Range<QueryId>,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: ResultFlags,
+    ) {
+        self.raw.copy_query_pool_results(pool, queries, buffer, offset, stride, flags)
+    }
+
+    /// Identical to the `RawCommandBuffer` method of the same name.
+    pub fn set_event(&mut self, event: &B::Event, stages: pso::PipelineStage) {
+        self.raw.set_event(event, stages)
+    }
+
+    /// Identical to the `RawCommandBuffer` method of the same name.
+    pub fn reset_event(&mut self, event: &B::Event, stages: pso::PipelineStage) {
+        self.raw.reset_event(event, stages)
+    }
+
+    /// Identical to the `RawCommandBuffer` method of the same name.
+    pub fn wait_events<'i, I, J>(
+        &mut self,
+        events: I,
+        stages: Range<pso::PipelineStage>,
+        barriers: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<B::Event>,
+        J: IntoIterator,
+        J::Item: Borrow<Barrier<'i, B>>,
+    {
+        self.raw.wait_events(events, stages, barriers)
+    }
 }
@@ -7,7 +7,7 @@ use {buffer, pass, pso};
 use {Backend, DrawCount, IndexCount, InstanceCount, VertexCount, VertexOffset, WorkGroupCount};
 use image::{Filter, Layout, SubresourceRange};
 use memory::{Barrier, Dependencies};
-use query::{PipelineStatistic, Query, QueryControl, QueryId};
+use query::{PipelineStatistic, Query, QueryControl, QueryId, ResultFlags};
 use range::RangeArg;
 use super::{
     AttachmentClear, BufferCopy, BufferImageCopy,
@@ -31,6 +31,7 @@ pub union ClearColorRaw {
 /// and so is used when a known layout is needed.
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ClearDepthStencilRaw {
     /// Depth value
     pub depth: f32,
@@ -64,6 +65,7 @@ pub type DescriptorSetOffset = u32;
 bitflags! {
     /// Option flags for various command buffer settings.
     #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
     pub struct CommandBufferFlags: u32 {
         // TODO: Remove once 'const fn' is stabilized: https://github.com/rust-lang/rust/issues/24111
         /// No flags.
@@ -89,6 +91,7 @@ bitflags! {
 /// and `command::Secondary` do at compile-time.
 #[allow(missing_docs)]
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Level {
     Primary,
     Secondary,
@@ -142,6 +145,31 @@ pub trait RawCommandBuffer<B: Backend>: Clone + Any + Send + Sync {
         T: IntoIterator,
         T::Item: Borrow<Barrier<'a, B>>;
 
+    /// Signals an event once the given pipeline stages of all the previous
+    /// commands are completed.
+    fn set_event(&mut self, event: &B::Event, stages: pso::PipelineStage);
+
+    /// Unsignals an event once the given pipeline stages of all the previous
+    /// commands are completed.
+    fn reset_event(&mut self, event: &B::Event, stages: pso::PipelineStage);
+
+    /// Waits for all the given events to be signaled, then inserts a synchronization
+    /// dependency between the `stages.start` stages of the commands preceding the
+    /// signal operations and the `stages.end` stages of the following commands.
+    ///
+    /// Splitting a `pipeline_barrier` into `set_event` and `wait_events` allows
+    /// independent commands recorded in between to overlap with the dependency.
+    fn wait_events<'a, I, J>(
+        &mut self,
+        events: I,
+        stages: Range<pso::PipelineStage>,
+        barriers: J,
+    ) where
+        I: IntoIterator,
+        I::Item: Borrow<B::Event>,
+        J: IntoIterator,
+        J::Item: Borrow<Barrier<'a, B>>;
+
     /// Fill a buffer with the given `u32` value.
     fn fill_buffer<R>(
         &mut self,
@@ -501,6 +529,20 @@ pub trait RawCommandBuffer<B: Backend>: Clone + Any + Send + Sync {
     /// Requests a timestamp to be written.
     fn write_timestamp(&mut self, pso::PipelineStage, Query<B>);
 
+    /// Copy query results of the given range of the query pool into a buffer.
+    ///
+    /// The layout of the results is the same as for `Device::get_query_pool_results`,
+    /// starting at `offset` bytes into the buffer.
+    fn copy_query_pool_results(
+        &mut self,
+        pool: &B::QueryPool,
+        queries: Range<QueryId>,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        stride: buffer::Offset,
+        flags: ResultFlags,
+    );
+
     /// Modify constant data in a graphics pipeline.
     /// Push constants are intended to modify data in a pipeline more
     /// quickly than a updating the values inside a descriptor set.
@@ -21,7 +21,7 @@ use {buffer, format, image, mapping, pass, pso, query};
 use {Backend, MemoryTypeId};
 
 use error::HostExecutionError;
-use memory::Requirements;
+use memory::{Requirements, SparseFlags};
 use pool::{CommandPool, CommandPoolCreateFlags};
 use queue::{QueueFamilyId, QueueGroup};
 use range::RangeArg;
@@ -218,9 +218,16 @@ pub trait Device<B: Backend>: Any + Send + Sync {
     /// Destroy a pipeline layout object
     fn destroy_pipeline_layout(&self, layout: B::PipelineLayout);
 
-    /// Create a pipeline cache object.
-    //TODO: allow loading from disk
-    fn create_pipeline_cache(&self) -> B::PipelineCache;
+    /// Create a pipeline cache object, optionally initialized with the data
+    /// previously retrieved with `get_pipeline_cache_data`.
+    ///
+    /// Data produced by another driver or another device is ignored, leaving
+    /// the cache empty.
+    fn create_pipeline_cache(&self, data: Option<&[u8]>) -> B::PipelineCache;
+
+    /// Retrieve the data of a pipeline cache, to be stored and used to
+    /// initialize a cache on a later run.
+    fn get_pipeline_cache_data(&self, cache: &B::PipelineCache) -> Result<Vec<u8>, OutOfMemory>;
 
     /// Merge a number of source pipeline caches into the target one.
     fn merge_pipeline_caches<I>(&self, target: &B::PipelineCache, sources: I)
@@ -377,6 +384,34 @@ pub trait Device<B: Backend>: Any + Send + Sync {
     /// which references the images, has finished execution.
     fn destroy_image(&self, image: B::Image);
 
+    /// Create a sparse buffer, whose memory is bound with `RawCommandQueue::bind_sparse`.
+    ///
+    /// Requires `Features::SPARSE_BINDING` to be enabled on the device, along with the
+    /// features required by `sparse`, or `MissingFeature` is returned.
+    fn create_sparse_buffer(
+        &self, size: u64, usage: buffer::Usage, sparse: SparseFlags,
+    ) -> Result<B::Buffer, buffer::CreationError>;
+
+    /// Get memory requirements for a sparse buffer, the alignment being the size of its pages.
+    fn get_sparse_buffer_requirements(&self, buffer: &B::Buffer) -> Requirements;
+
+    /// Create a sparse image with optimal tiling, whose memory is bound with
+    /// `RawCommandQueue::bind_sparse`.
+    ///
+    /// Requires `Features::SPARSE_BINDING` to be enabled on the device, along with the
+    /// features required by `sparse`, or `MissingFeature` is returned.
+    fn create_sparse_image(
+        &self, kind: image::Kind, mip_levels: image::Level, format: format::Format,
+        usage: image::Usage, storage_flags: image::StorageFlags, sparse: SparseFlags,
+    ) -> Result<B::Image, image::CreationError>;
+
+    /// Get memory requirements for a sparse image: the requirements of its
+    /// opaque range, the alignment being the size of its pages, and the
+    /// sparse requirements of each of its aspects.
+    fn get_sparse_image_requirements(
+        &self, image: &B::Image,
+    ) -> (Requirements, Vec<image::SparseRequirements>);
+
     /// Create an image view from an existing image
     fn create_image_view(
         &self,
@@ -612,15 +647,56 @@ pub trait Device<B: Backend>: Any + Send + Sync {
     /// Destroy a fence object
     fn destroy_fence(&self, fence: B::Fence);
 
+    /// Create a new event object, initially unsignaled.
+    ///
+    /// Events are a synchronization primitive that **can** be used to insert a fine-grained
+    /// dependency between commands of a queue, or from the host to a queue. Events **can** be
+    /// signaled and unsignaled by commands with `RawCommandBuffer::set_event` and
+    /// `RawCommandBuffer::reset_event`, or on the host with *set_event* and *reset_event*.
+    /// Commands **can** wait on them with `RawCommandBuffer::wait_events` and the current state
+    /// **can** be queried on the host with *get_event_status*.
+    fn create_event(&self) -> B::Event;
+
+    /// true for signaled, false for unsignaled
+    fn get_event_status(&self, event: &B::Event) -> bool;
+
+    /// Signal an event from the host.
+    fn set_event(&self, event: &B::Event);
+
+    /// Unsignal an event from the host.
+    fn reset_event(&self, event: &B::Event);
+
+    /// Destroy an event object
+    fn destroy_event(&self, event: B::Event);
+
     /// Create a new query pool object
     ///
     /// Queries are managed using query pool objects. Each query pool is a collection of a specific
     /// number of queries of a particular type.
-    fn create_query_pool(&self, ty: query::QueryType, count: query::QueryId) -> B::QueryPool;
+    fn create_query_pool(
+        &self, ty: query::QueryType, count: query::QueryId
+    ) -> Result<B::QueryPool, query::Error>;
 
     /// Destroy a query pool object
     fn destroy_query_pool(&self, pool: B::QueryPool);
 
+    /// Get query pool results into the specified CPU memory.
+    ///
+    /// The result of each query is written at `stride` bytes from the previous one,
+    /// with the layout specified by `flags`. Returns `Ok(false)` if the results of
+    /// some queries are not available yet and the `WAIT` flag is not set.
+    ///
+    /// With the `WAIT` flag, waiting on a query that will never become available,
+    /// for example because it was never issued, may return `DeviceLost`.
+    fn get_query_pool_results(
+        &self,
+        pool: &B::QueryPool,
+        queries: Range<query::QueryId>,
+        data: &mut [u8],
+        stride: buffer::Offset,
+        flags: query::ResultFlags,
+    ) -> Result<bool, HostExecutionError>;
+
     /// Create a new swapchain from a surface and a queue family, optionally providing the old
     /// swapchain to aid in resource reuse and rendering continuity.
     ///
@@ -8,6 +8,7 @@ use std::ops::Range;
 
 use format;
 use buffer::Offset as RawOffset;
+use Features;
 use pso::Comparison;
 
 
@@ -99,6 +100,10 @@ pub enum CreationError {
     Data(usize),
     /// The mentioned usage mode is not supported
     Usage(Usage),
+    /// The image requires features which are not enabled on the device.
+    MissingFeature(Features),
+    /// Memory allocation failed.
+    OutOfMemory,
 }
 
 impl fmt::Display for CreationError {
@@ -109,6 +114,7 @@ impl fmt::Display for CreationError {
             CreationError::Size(size) => write!(f, "{}: {}", self.description(), size),
             CreationError::Data(data) => write!(f, "{}: {}", self.description(), data),
             CreationError::Usage(usage) => write!(f, "{}: {:?}", self.description(), usage),
+            CreationError::MissingFeature(features) => write!(f, "{}: {:?}", self.description(), features),
             _ => write!(f, "{}", self.description()),
         }
     }
@@ -123,6 +129,8 @@ impl Error for CreationError {
             CreationError::Size(_) => "Unsupported size in one of the dimensions",
             CreationError::Data(_) => "The given data has a different size than the target image slice",
             CreationError::Usage(_) => "The expected image usage mode is not supported by a graphic API",
+            CreationError::MissingFeature(_) => "The image requires device features which are not enabled",
+            CreationError::OutOfMemory => "Out of memory",
         }
     }
 }
@@ -713,6 +721,43 @@ pub struct FormatProperties {
     pub max_resource_size: usize,
 }
 
+bitflags!(
+    /// Properties of the sparse memory layout of an image.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct SparseFormatFlags: u32 {
+        /// The image uses a single mip tail for all its array layers.
+        const SINGLE_MIPTAIL = 0x1;
+        /// The first mip level of the tail is the first one whose size isn't
+        /// a multiple of the granularity.
+        const ALIGNED_MIP_SIZE = 0x2;
+        /// The granularity doesn't follow the standard block shapes.
+        const NONSTANDARD_BLOCK_SIZE = 0x4;
+    }
+);
+
+/// Sparse memory requirements of an aspect of an image.
+///
+/// The mip levels smaller than the granularity are packed in a mip tail, which
+/// is bound as an opaque range of the image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SparseRequirements {
+    /// Aspects of the image these requirements apply to.
+    pub aspects: format::Aspects,
+    /// Size of a page, in texels.
+    pub granularity: Extent,
+    /// Properties of the layout.
+    pub flags: SparseFormatFlags,
+    /// First mip level in the tail.
+    pub mip_tail_first_level: Level,
+    /// Size of the mip tail of each array layer, in bytes.
+    pub mip_tail_size: u64,
+    /// Offset of the mip tail in the opaque range of the image.
+    pub mip_tail_offset: u64,
+    /// Distance between the mip tails of consecutive array layers.
+    pub mip_tail_stride: u64,
+}
+
 /// Footprint of a subresource in memory.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
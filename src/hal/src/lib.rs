@@ -51,6 +51,7 @@ pub mod mapping;
 pub mod memory;
 pub mod pass;
 pub mod pool;
+pub mod profile;
 pub mod pso;
 pub mod query;
 pub mod queue;
@@ -379,6 +380,7 @@ pub trait Backend: 'static + Sized + Eq + Clone + Hash + fmt::Debug + Any + Send
 
     type Fence:               fmt::Debug + Any + Send + Sync;
     type Semaphore:           fmt::Debug + Any + Send + Sync;
+    type Event:               fmt::Debug + Any + Send + Sync;
     type QueryPool:           fmt::Debug + Any + Send + Sync;
 }
 
@@ -2,7 +2,7 @@
 
 use std::mem;
 use std::ops::Range;
-use {buffer, image};
+use {buffer, image, queue};
 use Backend;
 
 /// A trait for plain-old-data types.
@@ -74,6 +74,55 @@ bitflags!(
     }
 );
 
+bitflags!(
+    /// Flags of sparse buffers and images.
+    ///
+    /// Sparse resources are created without memory, which is bound to their
+    /// pages with `RawCommandQueue::bind_sparse` instead.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct SparseFlags: u32 {
+        /// The resource can be used with some of its pages left unbound.
+        ///
+        /// Requires `Features::SPARSE_RESIDENCY_BUFFER` for buffers and one of the
+        /// `Features::SPARSE_RESIDENCY_*` features for images.
+        const RESIDENCY = 0x2;
+        /// The memory bound to the resource can also be bound to other
+        /// resources, or other pages of the same resource.
+        ///
+        /// Requires `Features::SPARSE_RESIDENCY_ALIASED`.
+        const ALIASED = 0x4;
+    }
+);
+
+/// Memory bound to a range of a sparse buffer, or to the opaque range of a
+/// sparse image.
+#[derive(Debug)]
+pub struct SparseBind<'a, B: Backend + 'a> {
+    /// Offset of the range in the resource, in bytes.
+    ///
+    /// Must be a multiple of the alignment of the memory requirements.
+    pub resource_offset: u64,
+    /// Size of the range, a multiple of the alignment unless it ends the resource.
+    pub size: u64,
+    /// Memory and offset in it bound to the range, or `None` to unbind it.
+    pub memory: Option<(&'a B::Memory, u64)>,
+}
+
+/// Memory bound to a region of a mip level of a sparse image, created with
+/// `SparseFlags::RESIDENCY`.
+#[derive(Debug)]
+pub struct SparseImageBind<'a, B: Backend + 'a> {
+    /// Mip level and array layer of the region.
+    pub subresource: image::Subresource,
+    /// Offset of the region in texels, a multiple of the granularity.
+    pub offset: image::Offset,
+    /// Extent of the region in texels, a multiple of the granularity unless
+    /// it ends the mip level.
+    pub extent: image::Extent,
+    /// Memory and offset in it bound to the region, or `None` to unbind it.
+    pub memory: Option<(&'a B::Memory, u64)>,
+}
+
 // DOC TODO: Could be better, but I don't know how to do this without 
 // trying to explain the whole synchronization model.
 /// A [memory barrier](https://www.khronos.org/registry/vulkan/specs/1.0/html/vkspec.html#synchronization-memory-barriers)
@@ -91,6 +140,9 @@ pub enum Barrier<'a, B: Backend> {
         states: Range<buffer::State>,
         /// The buffer the barrier controls.
         target: &'a B::Buffer,
+        /// Source and destination queue families, when transferring the
+        /// ownership of the buffer between them.
+        families: Option<Range<queue::QueueFamilyId>>,
     },
     /// A memory barrier that defines access to (a subset of) an image.
     Image {
@@ -100,6 +152,9 @@ pub enum Barrier<'a, B: Backend> {
         target: &'a B::Image,
         /// A `SubresourceRange` that defines which section of an image the barrier applies to.
         range: image::SubresourceRange,
+        /// Source and destination queue families, when transferring the
+        /// ownership of the image between them.
+        families: Option<Range<queue::QueueFamilyId>>,
     },
 }
 
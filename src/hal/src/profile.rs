@@ -0,0 +1,263 @@
+//! GPU profiling.
+//!
+//! A `Profiler` records named, nestable scopes into command buffers using timestamp
+//! queries. Each frame in flight has its own query pool, which is read back once the
+//! fence of the frame has been signaled, producing a tree of scope durations.
+//!
+//! ```ignore
+//! let period = physical_device.timestamp_period();
+//! let mut profiler = Profiler::new(&device, period, frames_in_flight, 64).unwrap();
+//! loop {
+//!     device.wait_for_fence(&fences[frame], !0);
+//!     let mut cmd = pool.acquire_command_buffer(false);
+//!     if let Some(profile) = profiler.begin_frame(&device, &fences[frame], &mut cmd) {
+//!         println!("{:#?}", profile);
+//!     }
+//!     profiler.begin_scope(&mut cmd, "shadows");
+//!     // ...
+//!     profiler.end_scope(&mut cmd);
+//!     // ...
+//! }
+//! ```
+
+use std::{mem, slice};
+use std::time::Duration;
+
+use {Backend, Device};
+use command::{CommandBuffer, Level, Shot};
+use pso::PipelineStage;
+use query::{Error, Query, QueryId, QueryType, ResultFlags};
+use queue::capability::{GraphicsOrCompute, Supports};
+
+
+/// Timing of a profiling scope, along with the scopes nested inside.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scope {
+    /// Name given when opening the scope.
+    pub name: String,
+    /// Time elapsed on the GPU between the start and the end of the scope.
+    pub duration: Duration,
+    /// Scopes opened while this one was active, in recording order.
+    pub children: Vec<Scope>,
+}
+
+/// Timings of all the scopes recorded during a frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameProfile {
+    /// Top-level scopes, in recording order.
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Debug)]
+struct RawScope {
+    name: String,
+    parent: Option<usize>,
+}
+
+#[derive(Debug)]
+struct Frame<B: Backend> {
+    pool: B::QueryPool,
+    /// Scopes in opening order. Scope `i` writes the timestamps `2 * i` and `2 * i + 1`.
+    scopes: Vec<RawScope>,
+    /// Currently open scopes, innermost last.
+    open: Vec<usize>,
+    /// Indicates that the scopes have been recorded but not read back yet.
+    pending: bool,
+}
+
+/// Records profiling scopes using timestamp queries, for a number of frames in flight.
+#[derive(Debug)]
+pub struct Profiler<B: Backend> {
+    frames: Vec<Frame<B>>,
+    current: usize,
+    max_scopes: u32,
+    timestamp_period: f32,
+}
+
+impl<B: Backend> Profiler<B> {
+    /// Create a profiler with one query pool per frame in flight, each holding
+    /// enough queries for `max_scopes` scopes.
+    ///
+    /// `timestamp_period` is the one reported by the physical device.
+    pub fn new(
+        device: &B::Device, timestamp_period: f32, frames_in_flight: usize, max_scopes: u32
+    ) -> Result<Self, Error> {
+        assert!(frames_in_flight > 0);
+        let frames = (0 .. frames_in_flight)
+            .map(|_| Ok(Frame {
+                pool: device.create_query_pool(QueryType::Timestamp, 2 * max_scopes)?,
+                scopes: Vec::new(),
+                open: Vec::new(),
+                pending: false,
+            }))
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Profiler {
+            frames,
+            // The first call to `begin_frame` starts at the first frame.
+            current: frames_in_flight - 1,
+            max_scopes,
+            timestamp_period,
+        })
+    }
+
+    /// Start recording the next frame in flight into `cmd`.
+    ///
+    /// `fence` is the fence signaled by the previous submission of this frame in flight.
+    /// It must be signaled, and the profile recorded by that submission is returned,
+    /// or `None` if nothing was recorded for it yet.
+    ///
+    /// Must be called outside of a render pass, before any scope is opened.
+    pub fn begin_frame<C, S, L>(
+        &mut self,
+        device: &B::Device,
+        fence: &B::Fence,
+        cmd: &mut CommandBuffer<B, C, S, L>,
+    ) -> Option<FrameProfile>
+    where
+        C: Supports<GraphicsOrCompute>,
+        S: Shot,
+        L: Level,
+    {
+        assert!(self.frames[self.current].open.is_empty(), "Profiling scopes left open at the end of the frame");
+        self.current = (self.current + 1) % self.frames.len();
+
+        let profile = if self.frames[self.current].pending {
+            assert!(device.get_fence_status(fence), "The fence of the frame is not signaled");
+            Some(self.read_back(device))
+        } else {
+            None
+        };
+
+        let frame = &mut self.frames[self.current];
+        frame.scopes.clear();
+        frame.pending = false;
+        cmd.reset_query_pool(&frame.pool, 0 .. 2 * self.max_scopes);
+        profile
+    }
+
+    /// Open a new scope, nested in the currently open one if any.
+    pub fn begin_scope<C, S, L, N>(&mut self, cmd: &mut CommandBuffer<B, C, S, L>, name: N)
+    where
+        C: Supports<GraphicsOrCompute>,
+        S: Shot,
+        L: Level,
+        N: Into<String>,
+    {
+        let frame = &mut self.frames[self.current];
+        let index = frame.scopes.len();
+        assert!(index < self.max_scopes as usize, "Too many profiling scopes in a frame, the limit is {}", self.max_scopes);
+
+        frame.scopes.push(RawScope {
+            name: name.into(),
+            parent: frame.open.last().cloned(),
+        });
+        frame.open.push(index);
+        frame.pending = true;
+        cmd.write_timestamp(PipelineStage::TOP_OF_PIPE, Query {
+            pool: &frame.pool,
+            id: 2 * index as QueryId,
+        });
+    }
+
+    /// Close the innermost open scope.
+    pub fn end_scope<C, S, L>(&mut self, cmd: &mut CommandBuffer<B, C, S, L>)
+    where
+        C: Supports<GraphicsOrCompute>,
+        S: Shot,
+        L: Level,
+    {
+        let frame = &mut self.frames[self.current];
+        let index = frame.open.pop().expect("No profiling scope to end");
+        cmd.write_timestamp(PipelineStage::BOTTOM_OF_PIPE, Query {
+            pool: &frame.pool,
+            id: 2 * index as QueryId + 1,
+        });
+    }
+
+    /// Destroy the query pools of the profiler.
+    pub fn destroy(self, device: &B::Device) {
+        for frame in self.frames {
+            device.destroy_query_pool(frame.pool);
+        }
+    }
+
+    fn read_back(&self, device: &B::Device) -> FrameProfile {
+        let frame = &self.frames[self.current];
+        let count = 2 * frame.scopes.len();
+        let mut timestamps = vec![0u64; count];
+        let data = unsafe {
+            slice::from_raw_parts_mut(timestamps.as_mut_ptr() as *mut u8, count * mem::size_of::<u64>())
+        };
+        device
+            .get_query_pool_results(
+                &frame.pool,
+                0 .. count as QueryId,
+                data,
+                mem::size_of::<u64>() as _,
+                ResultFlags::BITS_64 | ResultFlags::WAIT,
+            )
+            .expect("Unable to read back the profiling timestamps");
+
+        FrameProfile {
+            scopes: build_tree(&frame.scopes, &timestamps, self.timestamp_period),
+        }
+    }
+}
+
+/// Assemble the scopes into a tree, computing their durations from the timestamps.
+fn build_tree(scopes: &[RawScope], timestamps: &[u64], timestamp_period: f32) -> Vec<Scope> {
+    let mut children = vec![Vec::new(); scopes.len()];
+    let mut roots = Vec::new();
+    for (index, scope) in scopes.iter().enumerate() {
+        match scope.parent {
+            Some(parent) => children[parent].push(index),
+            None => roots.push(index),
+        }
+    }
+
+    fn build(index: usize, scopes: &[RawScope], children: &[Vec<usize>], timestamps: &[u64], period: f32) -> Scope {
+        let ticks = timestamps[2 * index + 1].wrapping_sub(timestamps[2 * index]);
+        let nanos = (ticks as f64 * period as f64) as u64;
+        Scope {
+            name: scopes[index].name.clone(),
+            duration: Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32),
+            children: children[index]
+                .iter()
+                .map(|&child| build(child, scopes, children, timestamps, period))
+                .collect(),
+        }
+    }
+
+    roots
+        .into_iter()
+        .map(|index| build(index, scopes, &children, timestamps, timestamp_period))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_tree, RawScope, Scope};
+    use std::time::Duration;
+
+    #[test]
+    fn test_build_tree() {
+        let raw = |name: &str, parent| RawScope { name: name.to_string(), parent };
+        let scopes = [raw("frame", None), raw("shadows", Some(0)), raw("cascade", Some(1)), raw("lighting", Some(0)), raw("ui", None)];
+        let timestamps = [0, 100, 10, 40, 15, 20, 50, 90, 100, 101];
+        let scope = |name: &str, nanos, children| Scope {
+            name: name.to_string(),
+            duration: Duration::new(0, nanos),
+            children,
+        };
+        assert_eq!(build_tree(&scopes, &timestamps, 2.0), vec![
+            scope("frame", 200, vec![
+                scope("shadows", 60, vec![
+                    scope("cascade", 10, vec![]),
+                ]),
+                scope("lighting", 80, vec![]),
+            ]),
+            scope("ui", 2, vec![]),
+        ]);
+    }
+}
@@ -11,6 +11,8 @@ mod descriptor;
 mod graphics;
 mod input_assembler;
 mod output_merger;
+pub mod reflect;
+pub mod spirv;
 
 pub use self::compute::*;
 pub use self::descriptor::*;
@@ -208,6 +210,7 @@ pub enum BasePipeline<'a, P: 'a> {
 /// More importantly, they are fast to execute, since the driver 
 /// can optimize out the branch on that other PSO creation.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Specialization {
     /// Constant identifier in shader source.
     pub id: u32,
@@ -218,6 +221,7 @@ pub struct Specialization {
 /// Scalar specialization constant with value for overriding.
 #[allow(missing_docs)]
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Constant {
     Bool(bool),
     U32(u32),
@@ -0,0 +1,948 @@
+//! Reflection of the interface of SPIR-V shader modules.
+//!
+//! Descriptor set layouts, vertex attributes and push constant ranges are
+//! usually written by hand next to the shaders and are easy to get out of sync
+//! with them. `ShaderInterface` reads them back from the SPIR-V code, and
+//! `check_graphics_pipeline` compares a pipeline description against the
+//! interfaces of its shaders.
+//!
+//! The reflection only looks at the declarations of the module, read by the
+//! parser of the `spirv` module, so that the HAL doesn't depend on a native
+//! shader compiler. The resources are reflected per module, not per entry
+//! point: every binding and push constant range is visible to the stages of
+//! all the entry points of the module.
+//!
+//! ```ignore
+//! let vs = ShaderInterface::parse(include_bytes!("shader.vert.spv"))?;
+//! let fs = ShaderInterface::parse(include_bytes!("shader.frag.spv"))?;
+//!
+//! let sets = merge_descriptor_sets(&[&vs, &fs])?;
+//! let (attributes, stride) = vs.vertex_attributes("main", 0).unwrap();
+//! ..
+//! for mismatch in check_graphics_pipeline(&desc, &vs, Some(&fs)) {
+//!     println!("{}", mismatch);
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use format::{ChannelType, Format};
+use Backend;
+use super::{
+    AttributeDesc, BufferIndex, DescriptorBinding, DescriptorSetIndex,
+    DescriptorSetLayoutBinding, DescriptorType, Element, ElemStride,
+    EntryPoint, GraphicsPipelineDesc, Location, ShaderStageFlags, Stage,
+};
+use super::spirv::{self, builtin, op, storage, Decorations, Module, Type};
+
+/// Error reading the interface of a SPIR-V module.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReflectError {
+    /// The code isn't a valid SPIR-V module.
+    Invalid(String),
+    /// The module uses a construct that can't be expressed with the HAL,
+    /// like runtime sized arrays of descriptors.
+    Unsupported(String),
+    /// Two modules declare different types of descriptors at the same binding.
+    ConflictingBinding(DescriptorSetIndex, DescriptorBinding),
+}
+
+impl fmt::Display for ReflectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReflectError::Invalid(ref msg) |
+            ReflectError::Unsupported(ref msg) => write!(f, "{}: {}", self.description(), msg),
+            ReflectError::ConflictingBinding(set, binding) => {
+                write!(f, "{}: set {}, binding {}", self.description(), set, binding)
+            }
+        }
+    }
+}
+
+impl Error for ReflectError {
+    fn description(&self) -> &str {
+        match *self {
+            ReflectError::Invalid(_) => "Invalid SPIR-V module",
+            ReflectError::Unsupported(_) => "Unsupported SPIR-V construct",
+            ReflectError::ConflictingBinding(..) => "Conflicting descriptor types",
+        }
+    }
+}
+
+/// Numeric class of the components of a shader input or output.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ScalarKind {
+    /// Boolean.
+    Bool,
+    /// Signed integer.
+    Sint,
+    /// Unsigned integer.
+    Uint,
+    /// Floating point, or normalized and scaled integers on the API side.
+    Float,
+}
+
+impl ScalarKind {
+    /// Numeric class of the values read from a vertex attribute of the given format.
+    pub fn of_format(format: Format) -> Self {
+        match format.base_format().1 {
+            ChannelType::Uint => ScalarKind::Uint,
+            ChannelType::Int => ScalarKind::Sint,
+            _ => ScalarKind::Float,
+        }
+    }
+}
+
+/// An input or output of an entry point, at a single location.
+///
+/// Matrices and arrays are split into one variable per location they occupy.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InterfaceVariable {
+    /// Location of the variable.
+    pub location: Location,
+    /// Class of the components.
+    pub kind: ScalarKind,
+    /// Width of the components, in bits.
+    pub width: u32,
+    /// Number of components, between 1 and 4.
+    pub components: u32,
+}
+
+impl InterfaceVariable {
+    /// Format of a vertex attribute holding this variable, if any.
+    pub fn format(&self) -> Option<Format> {
+        use format::Format::*;
+        const FORMATS: [[[Format; 4]; 3]; 3] = [
+            [
+                [R16Uint, Rg16Uint, Rgb16Uint, Rgba16Uint],
+                [R32Uint, Rg32Uint, Rgb32Uint, Rgba32Uint],
+                [R64Uint, Rg64Uint, Rgb64Uint, Rgba64Uint],
+            ],
+            [
+                [R16Int, Rg16Int, Rgb16Int, Rgba16Int],
+                [R32Int, Rg32Int, Rgb32Int, Rgba32Int],
+                [R64Int, Rg64Int, Rgb64Int, Rgba64Int],
+            ],
+            [
+                [R16Float, Rg16Float, Rgb16Float, Rgba16Float],
+                [R32Float, Rg32Float, Rgb32Float, Rgba32Float],
+                [R64Float, Rg64Float, Rgb64Float, Rgba64Float],
+            ],
+        ];
+
+        let kind = match self.kind {
+            ScalarKind::Uint => 0,
+            ScalarKind::Sint => 1,
+            ScalarKind::Float => 2,
+            ScalarKind::Bool => return None,
+        };
+        let width = match self.width {
+            16 => 0,
+            32 => 1,
+            64 => 2,
+            _ => return None,
+        };
+        match self.components {
+            1 ..= 4 => Some(FORMATS[kind][width][self.components as usize - 1]),
+            _ => None,
+        }
+    }
+
+    /// Size of the variable in a vertex buffer, in bytes.
+    pub fn size(&self) -> ElemStride {
+        self.components * self.width / 8
+    }
+}
+
+/// Entry point of a shader module.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryPointInfo {
+    /// Name of the entry point.
+    pub name: String,
+    /// Shader stage of the entry point.
+    pub stage: Stage,
+    /// Inputs declared with a location, sorted by location.
+    pub inputs: Vec<InterfaceVariable>,
+    /// Outputs declared with a location, sorted by location.
+    pub outputs: Vec<InterfaceVariable>,
+    /// Size of the local workgroup of compute entry points.
+    pub workgroup_size: Option<[u32; 3]>,
+}
+
+/// Interface of a SPIR-V shader module.
+#[derive(Clone, Debug)]
+pub struct ShaderInterface {
+    /// Entry points of the module.
+    pub entry_points: Vec<EntryPointInfo>,
+    /// Bindings of each descriptor set used by the module, sorted by binding.
+    ///
+    /// Dynamic uniform and storage buffers can't be told apart from the code
+    /// and are reported as regular buffers.
+    pub descriptor_sets: BTreeMap<DescriptorSetIndex, Vec<DescriptorSetLayoutBinding>>,
+    /// Range of the push constant block, in 32-bit words.
+    pub push_constants: Option<Range<u32>>,
+    /// Identifiers of the specialization constants, sorted.
+    pub specialization_constants: Vec<u32>,
+}
+
+/// Global variable, along with the type it points to.
+struct Variable {
+    id: u32,
+    pointee: u32,
+    storage: u32,
+}
+
+/// Entry point of a shader stage.
+struct RawEntryPoint<'a> {
+    raw: &'a spirv::EntryPoint,
+    stage: Stage,
+}
+
+fn stage_of_model(model: u32) -> Option<Stage> {
+    match model {
+        0 => Some(Stage::Vertex),
+        1 => Some(Stage::Hull),
+        2 => Some(Stage::Domain),
+        3 => Some(Stage::Geometry),
+        4 => Some(Stage::Fragment),
+        5 => Some(Stage::Compute),
+        _ => None,
+    }
+}
+
+/// Reader of the interface of a parsed module.
+struct Reflector<'a> {
+    module: &'a Module,
+    /// Global variables, sorted by id.
+    variables: Vec<Variable>,
+}
+
+impl<'a> Reflector<'a> {
+    fn new(module: &'a Module) -> Result<Self, ReflectError> {
+        let mut variables = Vec::new();
+        for (&id, var) in &module.variables {
+            let pointee = match module.types.get(&var.ty) {
+                Some(&Type::Pointer { pointee }) => pointee,
+                _ => return Err(ReflectError::Invalid(format!("variable {} isn't a pointer", id))),
+            };
+            variables.push(Variable {
+                id,
+                pointee,
+                storage: var.storage,
+            });
+        }
+        variables.sort_by_key(|var| var.id);
+        Ok(Reflector { module, variables })
+    }
+
+    fn ty(&self, id: u32) -> Result<&'a Type, ReflectError> {
+        self.module.types
+            .get(&id)
+            .ok_or_else(|| ReflectError::Invalid(format!("unknown type {}", id)))
+    }
+
+    /// Value of a 32-bit integer constant, or the default value of a specialization constant.
+    fn constant(&self, id: u32) -> Result<u32, ReflectError> {
+        self.module.constants
+            .iter()
+            .find(|inst| inst.operands.get(1) == Some(&id))
+            .and_then(|inst| match inst.op {
+                op::CONSTANT | op::SPEC_CONSTANT => inst.operands.get(2).cloned(),
+                _ => None,
+            })
+            .ok_or_else(|| ReflectError::Unsupported(format!("constant {} isn't a 32-bit integer", id)))
+    }
+
+    /// Split an input or output into the variables at each location.
+    fn interface_variables(
+        &self,
+        ty: u32,
+        location: &mut Location,
+        variables: &mut Vec<InterfaceVariable>,
+    ) -> Result<(), ReflectError> {
+        let (kind, width) = match *self.ty(ty)? {
+            Type::Bool => (ScalarKind::Bool, 32),
+            Type::Int { width, signed: true } => (ScalarKind::Sint, width),
+            Type::Int { width, signed: false } => (ScalarKind::Uint, width),
+            Type::Float { width } => (ScalarKind::Float, width),
+            Type::Vector { component, count } => {
+                let mut scalar = Vec::new();
+                self.interface_variables(component, &mut 0, &mut scalar)?;
+                let scalar = scalar.pop().unwrap();
+                variables.push(InterfaceVariable {
+                    location: *location,
+                    components: count,
+                    ..scalar
+                });
+                // 64-bit vectors of 3 or 4 components take two locations.
+                *location += if scalar.width == 64 && count > 2 { 2 } else { 1 };
+                return Ok(());
+            }
+            Type::Matrix { column, count } => {
+                for _ in 0 .. count {
+                    self.interface_variables(column, location, variables)?;
+                }
+                return Ok(());
+            }
+            Type::Array { element, length } => {
+                for _ in 0 .. self.constant(length)? {
+                    self.interface_variables(element, location, variables)?;
+                }
+                return Ok(());
+            }
+            Type::Struct { ref members } => {
+                for (i, &member) in members.iter().enumerate() {
+                    let decorations = self.module.member_decorations(ty, i as u32);
+                    // Built-in members, like the ones of `gl_PerVertex`, have no location.
+                    if decorations.builtin.is_some() {
+                        continue;
+                    }
+                    if let Some(member_location) = decorations.location {
+                        *location = member_location;
+                    }
+                    self.interface_variables(member, location, variables)?;
+                }
+                return Ok(());
+            }
+            _ => return Err(ReflectError::Unsupported(format!("interface variable of type {}", ty))),
+        };
+        variables.push(InterfaceVariable {
+            location: *location,
+            kind,
+            width,
+            components: 1,
+        });
+        *location += 1;
+        Ok(())
+    }
+
+    /// Inputs or outputs of an entry point with a location, ignoring built-ins.
+    fn interface(&self, entry: &RawEntryPoint, storage: u32) -> Result<Vec<InterfaceVariable>, ReflectError> {
+        // Inputs of the tessellation and geometry stages, and outputs of the
+        // hull stage, are arrays over the vertices of the primitive.
+        let per_vertex = matches!(
+            (entry.stage, storage),
+            (Stage::Hull, _) | (Stage::Domain, storage::INPUT) | (Stage::Geometry, storage::INPUT)
+        );
+
+        let mut variables = Vec::new();
+        for var in &self.variables {
+            if var.storage != storage || !entry.raw.interface.contains(&var.id) {
+                continue;
+            }
+            let decorations = self.module.decorations(var.id);
+            if decorations.builtin.is_some() {
+                continue;
+            }
+            let ty = match *self.ty(var.pointee)? {
+                Type::Array { element, .. } if per_vertex => element,
+                _ => var.pointee,
+            };
+            let mut location = match (decorations.location, self.ty(ty)?) {
+                (Some(location), _) => location,
+                // The members of a block without a location have their own.
+                (None, &Type::Struct { .. }) => 0,
+                (None, _) => continue,
+            };
+            self.interface_variables(ty, &mut location, &mut variables)?;
+        }
+        variables.sort_by_key(|var| var.location);
+        Ok(variables)
+    }
+
+    fn workgroup_size(&self, entry: &RawEntryPoint) -> Result<Option<[u32; 3]>, ReflectError> {
+        if entry.stage != Stage::Compute {
+            return Ok(None);
+        }
+        // The built-in takes precedence over the execution mode.
+        for inst in &self.module.constants {
+            match inst.op {
+                op::CONSTANT_COMPOSITE | op::SPEC_CONSTANT_COMPOSITE => {}
+                _ => continue,
+            }
+            let constituents = &inst.operands[2 ..];
+            if self.module.decorations(inst.operands[1]).builtin == Some(builtin::WORKGROUP_SIZE) &&
+                constituents.len() == 3
+            {
+                return Ok(Some([
+                    self.constant(constituents[0])?,
+                    self.constant(constituents[1])?,
+                    self.constant(constituents[2])?,
+                ]));
+            }
+        }
+        let function = entry.raw.function;
+        if let Some(&size) = self.module.local_sizes.get(&function) {
+            return Ok(Some(size));
+        }
+        match self.module.local_size_ids.get(&function) {
+            Some(ids) => Ok(Some([
+                self.constant(ids[0])?,
+                self.constant(ids[1])?,
+                self.constant(ids[2])?,
+            ])),
+            None => Ok(None),
+        }
+    }
+
+    fn descriptor_type(&self, variable: &Variable, ty: u32) -> Result<DescriptorType, ReflectError> {
+        let invalid = || ReflectError::Unsupported(format!("type of the descriptor variable {}", variable.id));
+        match (variable.storage, self.ty(ty)?) {
+            (storage::UNIFORM_CONSTANT, &Type::Sampler) => Ok(DescriptorType::Sampler),
+            (storage::UNIFORM_CONSTANT, &Type::SampledImage { image }) => match *self.ty(image)? {
+                Type::Image { dim: spirv::DIM_BUFFER, .. } => Ok(DescriptorType::UniformTexelBuffer),
+                _ => Ok(DescriptorType::CombinedImageSampler),
+            },
+            (storage::UNIFORM_CONSTANT, &Type::Image { dim, sampled }) => match (dim, sampled) {
+                (spirv::DIM_SUBPASS_DATA, _) => Ok(DescriptorType::InputAttachment),
+                (spirv::DIM_BUFFER, 2) => Ok(DescriptorType::StorageTexelBuffer),
+                (spirv::DIM_BUFFER, _) => Ok(DescriptorType::UniformTexelBuffer),
+                (_, 2) => Ok(DescriptorType::StorageImage),
+                _ => Ok(DescriptorType::SampledImage),
+            },
+            (storage::UNIFORM, &Type::Struct { .. }) => {
+                if self.module.decorations(ty).buffer_block {
+                    Ok(DescriptorType::StorageBuffer)
+                } else {
+                    Ok(DescriptorType::UniformBuffer)
+                }
+            }
+            (storage::STORAGE_BUFFER, &Type::Struct { .. }) => Ok(DescriptorType::StorageBuffer),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn descriptor_sets(
+        &self,
+        stages: ShaderStageFlags,
+    ) -> Result<BTreeMap<DescriptorSetIndex, Vec<DescriptorSetLayoutBinding>>, ReflectError> {
+        let mut sets = BTreeMap::new();
+        for var in &self.variables {
+            match var.storage {
+                storage::UNIFORM_CONSTANT | storage::UNIFORM | storage::STORAGE_BUFFER => {}
+                _ => continue,
+            }
+            let decorations = self.module.decorations(var.id);
+            let (set, binding) = match (decorations.descriptor_set, decorations.binding) {
+                (Some(set), Some(binding)) => (set as DescriptorSetIndex, binding),
+                _ => continue,
+            };
+
+            let mut ty = var.pointee;
+            let mut count = 1;
+            loop {
+                match *self.ty(ty)? {
+                    Type::Array { element, length } => {
+                        count *= self.constant(length)? as usize;
+                        ty = element;
+                    }
+                    Type::RuntimeArray { .. } => {
+                        return Err(ReflectError::Unsupported(format!("runtime array of descriptors {}", var.id)));
+                    }
+                    _ => break,
+                }
+            }
+
+            let bindings: &mut Vec<DescriptorSetLayoutBinding> = sets.entry(set).or_insert_with(Vec::new);
+            // Aliased variables share the first declaration.
+            if bindings.iter().any(|b| b.binding == binding) {
+                continue;
+            }
+            bindings.push(DescriptorSetLayoutBinding {
+                binding,
+                ty: self.descriptor_type(var, ty)?,
+                count,
+                stage_flags: stages,
+                immutable_samplers: false,
+            });
+        }
+        for bindings in sets.values_mut() {
+            bindings.sort_by_key(|b| b.binding);
+        }
+        Ok(sets)
+    }
+
+    /// Size of a type in a push constant block, in bytes.
+    fn size_of(&self, ty: u32, member: &Decorations) -> Result<u32, ReflectError> {
+        Ok(match *self.ty(ty)? {
+            Type::Bool => 4,
+            Type::Int { width, .. } | Type::Float { width } => width / 8,
+            Type::Vector { component, count } => count * self.size_of(component, member)?,
+            Type::Matrix { column, count } => {
+                let rows = match *self.ty(column)? {
+                    Type::Vector { count, .. } => count,
+                    _ => 1,
+                };
+                let vectors = if member.row_major { rows } else { count };
+                match member.matrix_stride {
+                    Some(stride) => vectors * stride,
+                    None => count * self.size_of(column, &Decorations::default())?,
+                }
+            }
+            Type::Array { element, length } => {
+                let stride = match self.module.decorations(ty).array_stride {
+                    Some(stride) => stride,
+                    None => self.size_of(element, member)?,
+                };
+                self.constant(length)? * stride
+            }
+            Type::RuntimeArray { .. } => 0,
+            Type::Struct { ref members } => {
+                let mut size = 0;
+                for (i, &member_ty) in members.iter().enumerate() {
+                    let decorations = self.module.member_decorations(ty, i as u32);
+                    let end = decorations.offset.unwrap_or(size) + self.size_of(member_ty, &decorations)?;
+                    size = size.max(end);
+                }
+                size
+            }
+            _ => return Err(ReflectError::Unsupported(format!("size of the opaque type {}", ty))),
+        })
+    }
+
+    fn push_constants(&self) -> Result<Option<Range<u32>>, ReflectError> {
+        let var = match self.variables.iter().find(|var| var.storage == storage::PUSH_CONSTANT) {
+            Some(var) => var,
+            None => return Ok(None),
+        };
+        let members = match *self.ty(var.pointee)? {
+            Type::Struct { ref members } => members.len() as u32,
+            _ => return Err(ReflectError::Invalid("push constants aren't a block".to_owned())),
+        };
+        let start = (0 .. members)
+            .filter_map(|i| self.module.member_decorations(var.pointee, i).offset)
+            .min()
+            .unwrap_or(0);
+        let end = self.size_of(var.pointee, &Decorations::default())?;
+        Ok(Some(start / 4 .. end.div_ceil(4)))
+    }
+}
+
+impl ShaderInterface {
+    /// Read the interface of a SPIR-V module.
+    pub fn parse(code: &[u8]) -> Result<Self, ReflectError> {
+        let module = spirv::parse(code).map_err(ReflectError::Invalid)?;
+        let reflector = Reflector::new(&module)?;
+
+        let mut entry_points = Vec::new();
+        let mut stages = ShaderStageFlags::empty();
+        for raw in &module.entry_points {
+            // Kernels aren't supported by the HAL.
+            let entry = match stage_of_model(raw.execution_model) {
+                Some(stage) => RawEntryPoint { raw, stage },
+                None => continue,
+            };
+            stages |= entry.stage.into();
+            entry_points.push(EntryPointInfo {
+                name: raw.name.clone(),
+                stage: entry.stage,
+                inputs: reflector.interface(&entry, storage::INPUT)?,
+                outputs: reflector.interface(&entry, storage::OUTPUT)?,
+                workgroup_size: reflector.workgroup_size(&entry)?,
+            });
+        }
+
+        let mut specialization_constants = module.decorations
+            .values()
+            .filter_map(|decorations| decorations.spec_id)
+            .collect::<Vec<_>>();
+        specialization_constants.sort();
+        specialization_constants.dedup();
+
+        Ok(ShaderInterface {
+            entry_points,
+            descriptor_sets: reflector.descriptor_sets(stages)?,
+            push_constants: reflector.push_constants()?,
+            specialization_constants,
+        })
+    }
+
+    /// Entry point with the given name and stage.
+    pub fn entry_point(&self, name: &str, stage: Stage) -> Option<&EntryPointInfo> {
+        self.entry_points
+            .iter()
+            .find(|entry| entry.name == name && entry.stage == stage)
+    }
+
+    /// Stages of all the entry points of the module.
+    pub fn stages(&self) -> ShaderStageFlags {
+        self.entry_points
+            .iter()
+            .fold(ShaderStageFlags::empty(), |stages, entry| stages | entry.stage.into())
+    }
+
+    /// Push constant range as expected by `Device::create_pipeline_layout`.
+    pub fn push_constant_range(&self) -> Option<(ShaderStageFlags, Range<u32>)> {
+        self.push_constants
+            .clone()
+            .map(|range| (self.stages(), range))
+    }
+
+    /// Attributes reading all the inputs of a vertex entry point from a single
+    /// vertex buffer, packed in location order, along with the stride of the buffer.
+    ///
+    /// Returns `None` if the entry point doesn't exist or an input can't be
+    /// read from a vertex buffer.
+    pub fn vertex_attributes(&self, entry: &str, binding: BufferIndex) -> Option<(Vec<AttributeDesc>, ElemStride)> {
+        let entry = self.entry_point(entry, Stage::Vertex)?;
+        let mut attributes = Vec::new();
+        let mut offset = 0;
+        for input in &entry.inputs {
+            attributes.push(AttributeDesc {
+                location: input.location,
+                binding,
+                element: Element {
+                    format: input.format()?,
+                    offset,
+                },
+            });
+            offset += input.size();
+        }
+        Some((attributes, offset))
+    }
+}
+
+/// Merge the descriptor sets of the modules of a pipeline, combining the
+/// stages of the bindings declared by several of them.
+pub fn merge_descriptor_sets(
+    interfaces: &[&ShaderInterface],
+) -> Result<BTreeMap<DescriptorSetIndex, Vec<DescriptorSetLayoutBinding>>, ReflectError> {
+    let mut sets: BTreeMap<DescriptorSetIndex, Vec<DescriptorSetLayoutBinding>> = BTreeMap::new();
+    for interface in interfaces {
+        for (&set, bindings) in &interface.descriptor_sets {
+            let merged = sets.entry(set).or_default();
+            for binding in bindings {
+                match merged.iter_mut().find(|b| b.binding == binding.binding) {
+                    Some(b) => {
+                        if b.ty != binding.ty || b.count != binding.count {
+                            return Err(ReflectError::ConflictingBinding(set, binding.binding));
+                        }
+                        b.stage_flags |= binding.stage_flags;
+                    }
+                    None => merged.push(binding.clone()),
+                }
+            }
+            merged.sort_by_key(|b| b.binding);
+        }
+    }
+    Ok(sets)
+}
+
+/// Difference between a graphics pipeline description and its shaders.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mismatch {
+    /// The entry point of a stage isn't in the module.
+    MissingEntryPoint(Stage, String),
+    /// A specialization constant of a stage isn't declared by the module.
+    UnknownSpecialization(Stage, u32),
+    /// A vertex shader input has no attribute.
+    MissingAttribute(Location),
+    /// The format of an attribute doesn't match the type of the vertex shader input.
+    AttributeFormat(Location, Format),
+    /// An attribute reads from a vertex buffer that isn't described.
+    MissingVertexBuffer(Location, BufferIndex),
+    /// A fragment shader output has no color target to blend into.
+    MissingColorTarget(Location),
+    /// A fragment shader input isn't written by the vertex shader with the same type.
+    StageInterface(Location),
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Mismatch::MissingEntryPoint(stage, ref name) => {
+                write!(f, "{:?} entry point {} is missing from the module", stage, name)
+            }
+            Mismatch::UnknownSpecialization(stage, id) => {
+                write!(f, "{:?} shader has no specialization constant {}", stage, id)
+            }
+            Mismatch::MissingAttribute(location) => {
+                write!(f, "Vertex input at location {} has no attribute", location)
+            }
+            Mismatch::AttributeFormat(location, format) => {
+                write!(f, "Attribute format {:?} doesn't match the vertex input at location {}", format, location)
+            }
+            Mismatch::MissingVertexBuffer(location, binding) => {
+                write!(f, "Attribute at location {} reads from the missing vertex buffer {}", location, binding)
+            }
+            Mismatch::MissingColorTarget(location) => {
+                write!(f, "Fragment output at location {} has no color target", location)
+            }
+            Mismatch::StageInterface(location) => {
+                write!(f, "Fragment input at location {} doesn't match a vertex output", location)
+            }
+        }
+    }
+}
+
+fn check_specialization<B: Backend>(
+    stage: Stage,
+    entry: &EntryPoint<B>,
+    interface: &ShaderInterface,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    for spec in entry.specialization {
+        if !interface.specialization_constants.contains(&spec.id) {
+            mismatches.push(Mismatch::UnknownSpecialization(stage, spec.id));
+        }
+    }
+}
+
+/// Compare a graphics pipeline description with the interfaces of its vertex
+/// and fragment shader modules.
+///
+/// The pipeline layout and render pass are opaque and aren't checked. The
+/// inputs of the fragment shader are only matched against the outputs of the
+/// vertex shader if there are no tessellation or geometry shaders.
+pub fn check_graphics_pipeline<B: Backend>(
+    desc: &GraphicsPipelineDesc<B>,
+    vertex: &ShaderInterface,
+    fragment: Option<&ShaderInterface>,
+) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+
+    let vs = &desc.shaders.vertex;
+    check_specialization(Stage::Vertex, vs, vertex, &mut mismatches);
+    let vertex_entry = vertex.entry_point(vs.entry, Stage::Vertex);
+    match vertex_entry {
+        Some(entry) => for input in &entry.inputs {
+            let attribute = match desc.attributes.iter().find(|a| a.location == input.location) {
+                Some(attribute) => attribute,
+                None => {
+                    mismatches.push(Mismatch::MissingAttribute(input.location));
+                    continue;
+                }
+            };
+            if ScalarKind::of_format(attribute.element.format) != input.kind {
+                mismatches.push(Mismatch::AttributeFormat(input.location, attribute.element.format));
+            }
+            if !desc.vertex_buffers.iter().any(|vb| vb.binding == attribute.binding) {
+                mismatches.push(Mismatch::MissingVertexBuffer(input.location, attribute.binding));
+            }
+        },
+        None => mismatches.push(Mismatch::MissingEntryPoint(Stage::Vertex, vs.entry.to_owned())),
+    }
+
+    let (fs, fragment) = match (desc.shaders.fragment.as_ref(), fragment) {
+        (Some(fs), Some(fragment)) => (fs, fragment),
+        _ => return mismatches,
+    };
+    check_specialization(Stage::Fragment, fs, fragment, &mut mismatches);
+    let entry = match fragment.entry_point(fs.entry, Stage::Fragment) {
+        Some(entry) => entry,
+        None => {
+            mismatches.push(Mismatch::MissingEntryPoint(Stage::Fragment, fs.entry.to_owned()));
+            return mismatches;
+        }
+    };
+    for output in &entry.outputs {
+        if output.location as usize >= desc.blender.targets.len() {
+            mismatches.push(Mismatch::MissingColorTarget(output.location));
+        }
+    }
+    let direct = desc.shaders.hull.is_none() &&
+        desc.shaders.domain.is_none() &&
+        desc.shaders.geometry.is_none();
+    if let (true, Some(vertex_entry)) = (direct, vertex_entry) {
+        for input in &entry.inputs {
+            let matched = vertex_entry.outputs.iter().any(|output| {
+                output.location == input.location &&
+                    output.kind == input.kind &&
+                    output.width == input.width &&
+                    output.components >= input.components
+            });
+            if !matched {
+                mismatches.push(Mismatch::StageInterface(input.location));
+            }
+        }
+    }
+
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use format::Format;
+    use pso::spirv::assembler::{string, Assembler};
+    use pso::spirv::decoration;
+
+    fn assemble(instructions: &[(u16, Vec<u32>)]) -> Vec<u8> {
+        let mut asm = Assembler::new();
+        for &(op, ref operands) in instructions {
+            asm.inst(op, operands);
+        }
+        asm.bytes()
+    }
+
+    fn entry_point(model: u32, interface: &[u32]) -> (u16, Vec<u32>) {
+        let mut operands = vec![model, 1];
+        operands.extend(string("main"));
+        operands.extend(interface);
+        (op::ENTRY_POINT, operands)
+    }
+
+    fn function(label: u32) -> Vec<(u16, Vec<u32>)> {
+        vec![(op::FUNCTION, vec![2, 1, 0, 3]), (248, vec![label]), (253, vec![]), (56, vec![])]
+    }
+
+    #[test]
+    fn test_interface() {
+        // Vertex inputs at locations 0 to 2, an output at location 0, a
+        // uniform buffer at set 0, binding 1, a push constant block holding a
+        // mat4 and a vec4, and a specialization constant with id 3.
+        let mut vertex = vec![
+            entry_point(0, &[14, 15, 16, 17]),
+            (op::DECORATE, vec![14, decoration::LOCATION, 0]),
+            (op::DECORATE, vec![15, decoration::LOCATION, 1]),
+            (op::DECORATE, vec![16, decoration::LOCATION, 2]),
+            (op::DECORATE, vec![17, decoration::LOCATION, 0]),
+            (op::DECORATE, vec![18, decoration::BLOCK]),
+            (op::MEMBER_DECORATE, vec![18, 0, decoration::OFFSET, 0]),
+            (op::MEMBER_DECORATE, vec![18, 0, decoration::MATRIX_STRIDE, 16]),
+            (op::DECORATE, vec![20, decoration::DESCRIPTOR_SET, 0]),
+            (op::DECORATE, vec![20, decoration::BINDING, 1]),
+            (op::DECORATE, vec![21, decoration::BLOCK]),
+            (op::MEMBER_DECORATE, vec![21, 0, decoration::OFFSET, 0]),
+            (op::MEMBER_DECORATE, vec![21, 0, decoration::MATRIX_STRIDE, 16]),
+            (op::MEMBER_DECORATE, vec![21, 1, decoration::OFFSET, 64]),
+            (op::DECORATE, vec![24, decoration::SPEC_ID, 3]),
+            (19, vec![2]),
+            (33, vec![3, 2]),
+            (op::TYPE_FLOAT, vec![4, 32]),
+            (op::TYPE_VECTOR, vec![5, 4, 2]),
+            (op::TYPE_VECTOR, vec![6, 4, 3]),
+            (op::TYPE_VECTOR, vec![7, 4, 4]),
+            (op::TYPE_INT, vec![8, 32, 0]),
+            (op::TYPE_MATRIX, vec![9, 7, 4]),
+            (op::TYPE_POINTER, vec![10, storage::INPUT, 6]),
+            (op::TYPE_POINTER, vec![11, storage::INPUT, 5]),
+            (op::TYPE_POINTER, vec![12, storage::INPUT, 8]),
+            (op::TYPE_POINTER, vec![13, storage::OUTPUT, 5]),
+            (op::VARIABLE, vec![10, 14, storage::INPUT]),
+            (op::VARIABLE, vec![11, 15, storage::INPUT]),
+            (op::VARIABLE, vec![12, 16, storage::INPUT]),
+            (op::VARIABLE, vec![13, 17, storage::OUTPUT]),
+            (op::TYPE_STRUCT, vec![18, 9]),
+            (op::TYPE_POINTER, vec![19, storage::UNIFORM, 18]),
+            (op::VARIABLE, vec![19, 20, storage::UNIFORM]),
+            (op::TYPE_STRUCT, vec![21, 9, 7]),
+            (op::TYPE_POINTER, vec![22, storage::PUSH_CONSTANT, 21]),
+            (op::VARIABLE, vec![22, 23, storage::PUSH_CONSTANT]),
+            (op::SPEC_CONSTANT, vec![8, 24, 1]),
+        ];
+        vertex.extend(function(25));
+        let vertex = ShaderInterface::parse(&assemble(&vertex)).unwrap();
+
+        let entry = vertex.entry_point("main", Stage::Vertex).unwrap();
+        let var = |location, kind, components| InterfaceVariable { location, kind, width: 32, components };
+        assert_eq!(entry.inputs, vec![
+            var(0, ScalarKind::Float, 3),
+            var(1, ScalarKind::Float, 2),
+            var(2, ScalarKind::Uint, 1),
+        ]);
+        assert_eq!(entry.outputs, vec![var(0, ScalarKind::Float, 2)]);
+        let (attributes, stride) = vertex.vertex_attributes("main", 0).unwrap();
+        let formats = attributes
+            .iter()
+            .map(|a| (a.location, a.element.format, a.element.offset))
+            .collect::<Vec<_>>();
+        assert_eq!(formats, vec![(0, Format::Rgb32Float, 0), (1, Format::Rg32Float, 12), (2, Format::R32Uint, 20)]);
+        assert_eq!(stride, 24);
+        assert_eq!(vertex.push_constant_range(), Some((ShaderStageFlags::VERTEX, 0 .. 20)));
+        assert_eq!(vertex.specialization_constants, vec![3]);
+
+        // The same uniform buffer, and an array of two combined image samplers
+        // at set 1, binding 0.
+        let mut fragment = vec![
+            entry_point(4, &[]),
+            (op::DECORATE, vec![13, decoration::DESCRIPTOR_SET, 1]),
+            (op::DECORATE, vec![13, decoration::BINDING, 0]),
+            (op::DECORATE, vec![18, decoration::BLOCK]),
+            (op::DECORATE, vec![20, decoration::DESCRIPTOR_SET, 0]),
+            (op::DECORATE, vec![20, decoration::BINDING, 1]),
+            (19, vec![2]),
+            (33, vec![3, 2]),
+            (op::TYPE_FLOAT, vec![4, 32]),
+            (op::TYPE_MATRIX, vec![6, 4, 4]),
+            (op::TYPE_IMAGE, vec![7, 4, 1, 0, 0, 0, 1, 0]),
+            (op::TYPE_SAMPLED_IMAGE, vec![8, 7]),
+            (op::TYPE_INT, vec![9, 32, 0]),
+            (op::CONSTANT, vec![9, 10, 2]),
+            (op::TYPE_ARRAY, vec![11, 8, 10]),
+            (op::TYPE_POINTER, vec![12, storage::UNIFORM_CONSTANT, 11]),
+            (op::VARIABLE, vec![12, 13, storage::UNIFORM_CONSTANT]),
+            (op::TYPE_STRUCT, vec![18, 6]),
+            (op::TYPE_POINTER, vec![19, storage::UNIFORM, 18]),
+            (op::VARIABLE, vec![19, 20, storage::UNIFORM]),
+        ];
+        fragment.extend(function(21));
+        let fragment = ShaderInterface::parse(&assemble(&fragment)).unwrap();
+
+        let binding = |binding, ty, count, stage_flags| DescriptorSetLayoutBinding {
+            binding,
+            ty,
+            count,
+            stage_flags,
+            immutable_samplers: false,
+        };
+        let sets = merge_descriptor_sets(&[&vertex, &fragment]).unwrap();
+        assert_eq!(format!("{:?}", sets), format!("{:?}", {
+            let mut sets = BTreeMap::new();
+            sets.insert(0, vec![binding(1, DescriptorType::UniformBuffer, 1, ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT)]);
+            sets.insert(1, vec![binding(0, DescriptorType::CombinedImageSampler, 2, ShaderStageFlags::FRAGMENT)]);
+            sets
+        }));
+
+        let mut compute = vec![
+            entry_point(5, &[]),
+            (op::EXECUTION_MODE, vec![1, spirv::EXECUTION_MODE_LOCAL_SIZE, 8, 4, 1]),
+            (19, vec![2]),
+            (33, vec![3, 2]),
+        ];
+        compute.extend(function(4));
+        let compute = ShaderInterface::parse(&assemble(&compute)).unwrap();
+        assert_eq!(compute.entry_point("main", Stage::Compute).unwrap().workgroup_size, Some([8, 4, 1]));
+    }
+
+    #[test]
+    fn test_blocks() {
+        // A vertex shader writing `gl_PerVertex` and a block with members at
+        // locations 1 and 3, and a geometry shader reading `gl_in[3]`.
+        let mut module = vec![
+            entry_point(0, &[9, 12]),
+            entry_point(3, &[17]),
+            (op::MEMBER_DECORATE, vec![7, 0, decoration::BUILT_IN, 0]),
+            (op::MEMBER_DECORATE, vec![7, 1, decoration::BUILT_IN, 1]),
+            (op::DECORATE, vec![7, decoration::BLOCK]),
+            (op::MEMBER_DECORATE, vec![10, 0, decoration::LOCATION, 1]),
+            (op::MEMBER_DECORATE, vec![10, 1, decoration::LOCATION, 3]),
+            (op::DECORATE, vec![10, decoration::BLOCK]),
+            (19, vec![2]),
+            (33, vec![3, 2]),
+            (op::TYPE_FLOAT, vec![4, 32]),
+            (op::TYPE_VECTOR, vec![5, 4, 4]),
+            (op::TYPE_VECTOR, vec![6, 4, 2]),
+            (op::TYPE_STRUCT, vec![7, 5, 4]),
+            (op::TYPE_POINTER, vec![8, storage::OUTPUT, 7]),
+            (op::VARIABLE, vec![8, 9, storage::OUTPUT]),
+            (op::TYPE_STRUCT, vec![10, 6, 4]),
+            (op::TYPE_POINTER, vec![11, storage::OUTPUT, 10]),
+            (op::VARIABLE, vec![11, 12, storage::OUTPUT]),
+            (op::TYPE_INT, vec![13, 32, 0]),
+            (op::CONSTANT, vec![13, 14, 3]),
+            (op::TYPE_ARRAY, vec![15, 7, 14]),
+            (op::TYPE_POINTER, vec![16, storage::INPUT, 15]),
+            (op::VARIABLE, vec![16, 17, storage::INPUT]),
+        ];
+        module.extend(function(18));
+        let interface = ShaderInterface::parse(&assemble(&module)).unwrap();
+
+        let var = |location, components| InterfaceVariable { location, kind: ScalarKind::Float, width: 32, components };
+        assert_eq!(interface.entry_point("main", Stage::Vertex).unwrap().outputs, vec![var(1, 2), var(3, 1)]);
+        assert_eq!(interface.entry_point("main", Stage::Geometry).unwrap().inputs, vec![]);
+    }
+}
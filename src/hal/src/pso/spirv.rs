@@ -0,0 +1,851 @@
+//! Parser of SPIR-V shader modules.
+//!
+//! The module is kept close to its binary form: types, decorations and
+//! constants are indexed by their result id, while function bodies are split
+//! into basic blocks of raw instructions. It is shared by the reflection of
+//! shader interfaces and the backends that need to look into the code.
+
+use std::collections::HashMap;
+
+const MAGIC_NUMBER: u32 = 0x0723_0203;
+
+/// Opcodes of the instructions understood by the parser.
+#[allow(missing_docs)]
+pub mod op {
+    pub const NOP: u16 = 0;
+    pub const UNDEF: u16 = 1;
+    pub const SOURCE_CONTINUED: u16 = 2;
+    pub const SOURCE: u16 = 3;
+    pub const SOURCE_EXTENSION: u16 = 4;
+    pub const NAME: u16 = 5;
+    pub const MEMBER_NAME: u16 = 6;
+    pub const STRING: u16 = 7;
+    pub const LINE: u16 = 8;
+    pub const EXTENSION: u16 = 10;
+    pub const EXT_INST_IMPORT: u16 = 11;
+    pub const EXT_INST: u16 = 12;
+    pub const MEMORY_MODEL: u16 = 14;
+    pub const ENTRY_POINT: u16 = 15;
+    pub const EXECUTION_MODE: u16 = 16;
+    pub const CAPABILITY: u16 = 17;
+    pub const TYPE_VOID: u16 = 19;
+    pub const TYPE_BOOL: u16 = 20;
+    pub const TYPE_INT: u16 = 21;
+    pub const TYPE_FLOAT: u16 = 22;
+    pub const TYPE_VECTOR: u16 = 23;
+    pub const TYPE_MATRIX: u16 = 24;
+    pub const TYPE_IMAGE: u16 = 25;
+    pub const TYPE_SAMPLER: u16 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u16 = 27;
+    pub const TYPE_ARRAY: u16 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u16 = 29;
+    pub const TYPE_STRUCT: u16 = 30;
+    pub const TYPE_POINTER: u16 = 32;
+    pub const TYPE_FUNCTION: u16 = 33;
+    pub const TYPE_FORWARD_POINTER: u16 = 39;
+    pub const CONSTANT_TRUE: u16 = 41;
+    pub const CONSTANT_FALSE: u16 = 42;
+    pub const CONSTANT: u16 = 43;
+    pub const CONSTANT_COMPOSITE: u16 = 44;
+    pub const CONSTANT_NULL: u16 = 46;
+    pub const SPEC_CONSTANT_TRUE: u16 = 48;
+    pub const SPEC_CONSTANT_FALSE: u16 = 49;
+    pub const SPEC_CONSTANT: u16 = 50;
+    pub const SPEC_CONSTANT_COMPOSITE: u16 = 51;
+    pub const SPEC_CONSTANT_OP: u16 = 52;
+    pub const FUNCTION: u16 = 54;
+    pub const FUNCTION_PARAMETER: u16 = 55;
+    pub const FUNCTION_END: u16 = 56;
+    pub const FUNCTION_CALL: u16 = 57;
+    pub const VARIABLE: u16 = 59;
+    pub const LOAD: u16 = 61;
+    pub const STORE: u16 = 62;
+    pub const COPY_MEMORY: u16 = 63;
+    pub const ACCESS_CHAIN: u16 = 65;
+    pub const IN_BOUNDS_ACCESS_CHAIN: u16 = 66;
+    pub const ARRAY_LENGTH: u16 = 68;
+    pub const DECORATE: u16 = 71;
+    pub const MEMBER_DECORATE: u16 = 72;
+    pub const DECORATION_GROUP: u16 = 73;
+    pub const GROUP_DECORATE: u16 = 74;
+    pub const VECTOR_EXTRACT_DYNAMIC: u16 = 77;
+    pub const VECTOR_INSERT_DYNAMIC: u16 = 78;
+    pub const VECTOR_SHUFFLE: u16 = 79;
+    pub const COMPOSITE_CONSTRUCT: u16 = 80;
+    pub const COMPOSITE_EXTRACT: u16 = 81;
+    pub const COMPOSITE_INSERT: u16 = 82;
+    pub const COPY_OBJECT: u16 = 83;
+    pub const TRANSPOSE: u16 = 84;
+    pub const CONVERT_F_TO_U: u16 = 109;
+    pub const CONVERT_F_TO_S: u16 = 110;
+    pub const CONVERT_S_TO_F: u16 = 111;
+    pub const CONVERT_U_TO_F: u16 = 112;
+    pub const U_CONVERT: u16 = 113;
+    pub const S_CONVERT: u16 = 114;
+    pub const F_CONVERT: u16 = 115;
+    pub const BITCAST: u16 = 124;
+    pub const S_NEGATE: u16 = 126;
+    pub const F_NEGATE: u16 = 127;
+    pub const I_ADD: u16 = 128;
+    pub const F_ADD: u16 = 129;
+    pub const I_SUB: u16 = 130;
+    pub const F_SUB: u16 = 131;
+    pub const I_MUL: u16 = 132;
+    pub const F_MUL: u16 = 133;
+    pub const U_DIV: u16 = 134;
+    pub const S_DIV: u16 = 135;
+    pub const F_DIV: u16 = 136;
+    pub const U_MOD: u16 = 137;
+    pub const S_REM: u16 = 138;
+    pub const S_MOD: u16 = 139;
+    pub const F_REM: u16 = 140;
+    pub const F_MOD: u16 = 141;
+    pub const VECTOR_TIMES_SCALAR: u16 = 142;
+    pub const MATRIX_TIMES_SCALAR: u16 = 143;
+    pub const VECTOR_TIMES_MATRIX: u16 = 144;
+    pub const MATRIX_TIMES_VECTOR: u16 = 145;
+    pub const MATRIX_TIMES_MATRIX: u16 = 146;
+    pub const OUTER_PRODUCT: u16 = 147;
+    pub const DOT: u16 = 148;
+    pub const I_ADD_CARRY: u16 = 149;
+    pub const I_SUB_BORROW: u16 = 150;
+    pub const U_MUL_EXTENDED: u16 = 151;
+    pub const S_MUL_EXTENDED: u16 = 152;
+    pub const ANY: u16 = 154;
+    pub const ALL: u16 = 155;
+    pub const IS_NAN: u16 = 156;
+    pub const IS_INF: u16 = 157;
+    pub const LOGICAL_EQUAL: u16 = 164;
+    pub const LOGICAL_NOT_EQUAL: u16 = 165;
+    pub const LOGICAL_OR: u16 = 166;
+    pub const LOGICAL_AND: u16 = 167;
+    pub const LOGICAL_NOT: u16 = 168;
+    pub const SELECT: u16 = 169;
+    pub const I_EQUAL: u16 = 170;
+    pub const I_NOT_EQUAL: u16 = 171;
+    pub const U_GREATER_THAN: u16 = 172;
+    pub const S_GREATER_THAN: u16 = 173;
+    pub const U_GREATER_THAN_EQUAL: u16 = 174;
+    pub const S_GREATER_THAN_EQUAL: u16 = 175;
+    pub const U_LESS_THAN: u16 = 176;
+    pub const S_LESS_THAN: u16 = 177;
+    pub const U_LESS_THAN_EQUAL: u16 = 178;
+    pub const S_LESS_THAN_EQUAL: u16 = 179;
+    pub const F_ORD_EQUAL: u16 = 180;
+    pub const F_UNORD_EQUAL: u16 = 181;
+    pub const F_ORD_NOT_EQUAL: u16 = 182;
+    pub const F_UNORD_NOT_EQUAL: u16 = 183;
+    pub const F_ORD_LESS_THAN: u16 = 184;
+    pub const F_UNORD_LESS_THAN: u16 = 185;
+    pub const F_ORD_GREATER_THAN: u16 = 186;
+    pub const F_UNORD_GREATER_THAN: u16 = 187;
+    pub const F_ORD_LESS_THAN_EQUAL: u16 = 188;
+    pub const F_UNORD_LESS_THAN_EQUAL: u16 = 189;
+    pub const F_ORD_GREATER_THAN_EQUAL: u16 = 190;
+    pub const F_UNORD_GREATER_THAN_EQUAL: u16 = 191;
+    pub const SHIFT_RIGHT_LOGICAL: u16 = 194;
+    pub const SHIFT_RIGHT_ARITHMETIC: u16 = 195;
+    pub const SHIFT_LEFT_LOGICAL: u16 = 196;
+    pub const BITWISE_OR: u16 = 197;
+    pub const BITWISE_XOR: u16 = 198;
+    pub const BITWISE_AND: u16 = 199;
+    pub const NOT: u16 = 200;
+    pub const BIT_FIELD_INSERT: u16 = 201;
+    pub const BIT_FIELD_S_EXTRACT: u16 = 202;
+    pub const BIT_FIELD_U_EXTRACT: u16 = 203;
+    pub const BIT_REVERSE: u16 = 204;
+    pub const BIT_COUNT: u16 = 205;
+    pub const CONTROL_BARRIER: u16 = 224;
+    pub const MEMORY_BARRIER: u16 = 225;
+    pub const ATOMIC_LOAD: u16 = 227;
+    pub const ATOMIC_STORE: u16 = 228;
+    pub const ATOMIC_EXCHANGE: u16 = 229;
+    pub const ATOMIC_COMPARE_EXCHANGE: u16 = 230;
+    pub const ATOMIC_I_INCREMENT: u16 = 232;
+    pub const ATOMIC_I_DECREMENT: u16 = 233;
+    pub const ATOMIC_I_ADD: u16 = 234;
+    pub const ATOMIC_I_SUB: u16 = 235;
+    pub const ATOMIC_S_MIN: u16 = 236;
+    pub const ATOMIC_U_MIN: u16 = 237;
+    pub const ATOMIC_S_MAX: u16 = 238;
+    pub const ATOMIC_U_MAX: u16 = 239;
+    pub const ATOMIC_AND: u16 = 240;
+    pub const ATOMIC_OR: u16 = 241;
+    pub const ATOMIC_XOR: u16 = 242;
+    pub const PHI: u16 = 245;
+    pub const LOOP_MERGE: u16 = 246;
+    pub const SELECTION_MERGE: u16 = 247;
+    pub const LABEL: u16 = 248;
+    pub const BRANCH: u16 = 249;
+    pub const BRANCH_CONDITIONAL: u16 = 250;
+    pub const SWITCH: u16 = 251;
+    pub const RETURN: u16 = 253;
+    pub const RETURN_VALUE: u16 = 254;
+    pub const UNREACHABLE: u16 = 255;
+    pub const NO_LINE: u16 = 317;
+    pub const MODULE_PROCESSED: u16 = 330;
+    pub const EXECUTION_MODE_ID: u16 = 331;
+    pub const DECORATE_ID: u16 = 332;
+    pub const DECORATE_STRING: u16 = 5632;
+    pub const MEMBER_DECORATE_STRING: u16 = 5633;
+}
+
+/// Instructions of the `GLSL.std.450` extended instruction set.
+#[allow(missing_docs)]
+pub mod glsl {
+    pub const ROUND: u32 = 1;
+    pub const ROUND_EVEN: u32 = 2;
+    pub const TRUNC: u32 = 3;
+    pub const F_ABS: u32 = 4;
+    pub const S_ABS: u32 = 5;
+    pub const F_SIGN: u32 = 6;
+    pub const S_SIGN: u32 = 7;
+    pub const FLOOR: u32 = 8;
+    pub const CEIL: u32 = 9;
+    pub const FRACT: u32 = 10;
+    pub const RADIANS: u32 = 11;
+    pub const DEGREES: u32 = 12;
+    pub const SIN: u32 = 13;
+    pub const COS: u32 = 14;
+    pub const TAN: u32 = 15;
+    pub const ASIN: u32 = 16;
+    pub const ACOS: u32 = 17;
+    pub const ATAN: u32 = 18;
+    pub const SINH: u32 = 19;
+    pub const COSH: u32 = 20;
+    pub const TANH: u32 = 21;
+    pub const ASINH: u32 = 22;
+    pub const ACOSH: u32 = 23;
+    pub const ATANH: u32 = 24;
+    pub const ATAN2: u32 = 25;
+    pub const POW: u32 = 26;
+    pub const EXP: u32 = 27;
+    pub const LOG: u32 = 28;
+    pub const EXP2: u32 = 29;
+    pub const LOG2: u32 = 30;
+    pub const SQRT: u32 = 31;
+    pub const INVERSE_SQRT: u32 = 32;
+    pub const F_MIN: u32 = 37;
+    pub const U_MIN: u32 = 38;
+    pub const S_MIN: u32 = 39;
+    pub const F_MAX: u32 = 40;
+    pub const U_MAX: u32 = 41;
+    pub const S_MAX: u32 = 42;
+    pub const F_CLAMP: u32 = 43;
+    pub const U_CLAMP: u32 = 44;
+    pub const S_CLAMP: u32 = 45;
+    pub const F_MIX: u32 = 46;
+    pub const STEP: u32 = 48;
+    pub const SMOOTH_STEP: u32 = 49;
+    pub const FMA: u32 = 50;
+    pub const LENGTH: u32 = 66;
+    pub const DISTANCE: u32 = 67;
+    pub const CROSS: u32 = 68;
+    pub const NORMALIZE: u32 = 69;
+    pub const FIND_I_LSB: u32 = 73;
+    pub const FIND_S_MSB: u32 = 74;
+    pub const FIND_U_MSB: u32 = 75;
+    pub const N_MIN: u32 = 79;
+    pub const N_MAX: u32 = 80;
+    pub const N_CLAMP: u32 = 81;
+}
+
+/// Decorations understood by the parser, the others being ignored.
+#[allow(missing_docs)]
+pub mod decoration {
+    pub const SPEC_ID: u32 = 1;
+    pub const BLOCK: u32 = 2;
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const ROW_MAJOR: u32 = 4;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const MATRIX_STRIDE: u32 = 7;
+    pub const BUILT_IN: u32 = 11;
+    pub const LOCATION: u32 = 30;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+}
+
+/// Storage classes of pointers and variables.
+#[allow(missing_docs)]
+pub mod storage {
+    pub const UNIFORM_CONSTANT: u32 = 0;
+    pub const INPUT: u32 = 1;
+    pub const UNIFORM: u32 = 2;
+    pub const OUTPUT: u32 = 3;
+    pub const WORKGROUP: u32 = 4;
+    pub const PRIVATE: u32 = 6;
+    pub const PUSH_CONSTANT: u32 = 9;
+    pub const STORAGE_BUFFER: u32 = 12;
+}
+
+/// Built-in variables available to compute shaders.
+#[allow(missing_docs)]
+pub mod builtin {
+    pub const NUM_WORKGROUPS: u32 = 24;
+    pub const WORKGROUP_SIZE: u32 = 25;
+    pub const WORKGROUP_ID: u32 = 26;
+    pub const LOCAL_INVOCATION_ID: u32 = 27;
+    pub const GLOBAL_INVOCATION_ID: u32 = 28;
+    pub const LOCAL_INVOCATION_INDEX: u32 = 29;
+}
+
+/// Execution model of compute entry points.
+pub const EXECUTION_MODEL_GL_COMPUTE: u32 = 5;
+/// Execution mode declaring the local workgroup size with literals.
+pub const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+/// Execution mode declaring the local workgroup size with constant ids.
+pub const EXECUTION_MODE_LOCAL_SIZE_ID: u32 = 38;
+/// Dimensionality of texel buffer images.
+pub const DIM_BUFFER: u32 = 5;
+/// Dimensionality of input attachment images.
+pub const DIM_SUBPASS_DATA: u32 = 6;
+
+/// Name of the only supported extended instruction set.
+pub const GLSL_STD_450: &str = "GLSL.std.450";
+
+/// Instruction of a function body, or a constant declaration.
+#[derive(Clone, Debug)]
+pub struct Instruction {
+    /// Opcode of the instruction.
+    pub op: u16,
+    /// Operands following the opcode, including the result type and id.
+    pub operands: Vec<u32>,
+}
+
+/// Type declaration. Type ids in the variants refer to other declarations.
+#[derive(Clone, Debug)]
+#[allow(missing_docs)]
+pub enum Type {
+    Void,
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    /// Image with the given dimensionality, `sampled` being 2 for storage images.
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage { image: u32 },
+    /// Array with the length given by a constant id.
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+    Function,
+}
+
+/// Decorations of an id or a structure member.
+#[derive(Clone, Debug, Default)]
+#[allow(missing_docs)]
+pub struct Decorations {
+    pub spec_id: Option<u32>,
+    pub block: bool,
+    pub buffer_block: bool,
+    pub row_major: bool,
+    pub array_stride: Option<u32>,
+    pub matrix_stride: Option<u32>,
+    pub builtin: Option<u32>,
+    pub location: Option<u32>,
+    pub binding: Option<u32>,
+    pub descriptor_set: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+impl Decorations {
+    fn apply(&mut self, decoration: u32, arguments: &[u32]) -> Result<(), String> {
+        let value = || arguments
+            .first()
+            .cloned()
+            .ok_or_else(|| format!("Missing argument of decoration {}", decoration));
+        match decoration {
+            decoration::SPEC_ID => self.spec_id = Some(value()?),
+            decoration::BLOCK => self.block = true,
+            decoration::BUFFER_BLOCK => self.buffer_block = true,
+            decoration::ROW_MAJOR => self.row_major = true,
+            decoration::ARRAY_STRIDE => self.array_stride = Some(value()?),
+            decoration::MATRIX_STRIDE => self.matrix_stride = Some(value()?),
+            decoration::BUILT_IN => self.builtin = Some(value()?),
+            decoration::LOCATION => self.location = Some(value()?),
+            decoration::BINDING => self.binding = Some(value()?),
+            decoration::DESCRIPTOR_SET => self.descriptor_set = Some(value()?),
+            decoration::OFFSET => self.offset = Some(value()?),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Global variable.
+#[derive(Clone, Debug)]
+pub struct Variable {
+    /// Pointer type of the variable.
+    pub ty: u32,
+    /// Storage class of the variable.
+    pub storage: u32,
+    /// Constant id of the initial value.
+    pub initializer: Option<u32>,
+}
+
+/// Basic block of a function.
+#[derive(Clone, Debug)]
+pub struct Block {
+    /// Label id starting the block.
+    pub label: u32,
+    /// Instructions of the block, the last one being a branch or a return.
+    pub instructions: Vec<Instruction>,
+}
+
+/// Function body.
+#[derive(Clone, Debug, Default)]
+pub struct Function {
+    /// Ids of the parameters.
+    pub parameters: Vec<u32>,
+    /// Basic blocks, the first one being the entry of the function.
+    pub blocks: Vec<Block>,
+    /// Index of the block starting with each label.
+    pub labels: HashMap<u32, usize>,
+}
+
+/// Entry point declaration.
+#[derive(Clone, Debug)]
+pub struct EntryPoint {
+    /// Execution model, telling the shader stage.
+    pub execution_model: u32,
+    /// Id of the entry point function.
+    pub function: u32,
+    /// Name of the entry point.
+    pub name: String,
+    /// Ids of the input and output variables used by the entry point.
+    pub interface: Vec<u32>,
+}
+
+/// Parsed SPIR-V module.
+#[derive(Debug, Default)]
+pub struct Module {
+    /// Type declarations.
+    pub types: HashMap<u32, Type>,
+    /// Decorations of ids.
+    pub decorations: HashMap<u32, Decorations>,
+    /// Decorations of the structure members, indexed by structure type and member index.
+    pub member_decorations: HashMap<(u32, u32), Decorations>,
+    /// Constant and specialization constant instructions, in declaration order.
+    pub constants: Vec<Instruction>,
+    /// Global variables.
+    pub variables: HashMap<u32, Variable>,
+    /// Function bodies.
+    pub functions: HashMap<u32, Function>,
+    /// Entry points, in declaration order.
+    pub entry_points: Vec<EntryPoint>,
+    /// Local workgroup size declared with literals for each entry point function.
+    pub local_sizes: HashMap<u32, [u32; 3]>,
+    /// Local workgroup size declared with constant ids for each entry point function.
+    pub local_size_ids: HashMap<u32, [u32; 3]>,
+    /// Names of the imported extended instruction sets.
+    pub ext_inst_imports: HashMap<u32, String>,
+    /// Result type of every instruction producing a typed value.
+    pub result_types: HashMap<u32, u32>,
+}
+
+impl Module {
+    /// Decorations of an id, or the default ones if it isn't decorated.
+    pub fn decorations(&self, id: u32) -> Decorations {
+        self.decorations.get(&id).cloned().unwrap_or_default()
+    }
+
+    /// Decorations of a structure member, or the default ones if it isn't decorated.
+    pub fn member_decorations(&self, id: u32, member: u32) -> Decorations {
+        self.member_decorations.get(&(id, member)).cloned().unwrap_or_default()
+    }
+}
+
+/// Check if the instruction starts with a result type and a result id.
+fn has_result_type(op: u16) -> bool {
+    !matches!(
+        op,
+        op::NOP | op::LINE | op::NO_LINE | op::STORE | op::COPY_MEMORY |
+        op::CONTROL_BARRIER | op::MEMORY_BARRIER | op::ATOMIC_STORE |
+        op::LOOP_MERGE | op::SELECTION_MERGE | op::BRANCH | op::BRANCH_CONDITIONAL |
+        op::SWITCH | op::RETURN | op::RETURN_VALUE | op::UNREACHABLE
+    )
+}
+
+/// Decode a nul-terminated literal string, returning it with the number of words it took.
+fn literal_string(words: &[u32]) -> (String, usize) {
+    let bytes = words
+        .iter()
+        .flat_map(|&word| (0 .. 4).map(move |i| (word >> (8 * i)) as u8))
+        .take_while(|&byte| byte != 0)
+        .collect::<Vec<_>>();
+    let length = (bytes.len() / 4 + 1).min(words.len());
+    (String::from_utf8_lossy(&bytes).into_owned(), length)
+}
+
+/// Parse the binary form of a module.
+pub fn parse(bytes: &[u8]) -> Result<Module, String> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err("SPIR-V code size is not a multiple of 4".to_owned());
+    }
+    let mut words = bytes
+        .chunks(4)
+        .map(|c| c[0] as u32 | (c[1] as u32) << 8 | (c[2] as u32) << 16 | (c[3] as u32) << 24)
+        .collect::<Vec<_>>();
+    if words.len() < 5 {
+        return Err("SPIR-V header is incomplete".to_owned());
+    }
+    if words[0] == MAGIC_NUMBER.swap_bytes() {
+        for word in &mut words {
+            *word = word.swap_bytes();
+        }
+    } else if words[0] != MAGIC_NUMBER {
+        return Err(format!("Invalid SPIR-V magic number {:#x}", words[0]));
+    }
+
+    let mut module = Module::default();
+    let mut function: Option<(u32, Function)> = None;
+    let mut position = 5;
+    while position < words.len() {
+        let count = (words[position] >> 16) as usize;
+        let op = (words[position] & 0xFFFF) as u16;
+        if count == 0 || position + count > words.len() {
+            return Err(format!("Invalid instruction size at word {}", position));
+        }
+        let operands = &words[position + 1 .. position + count];
+        position += count;
+
+        let arg = |i: usize| operands
+            .get(i)
+            .cloned()
+            .ok_or_else(|| format!("Missing operand {} of instruction {}", i, op));
+        let rest = |i: usize| &operands[i.min(operands.len()) ..];
+
+        if let Some((id, ref mut fun)) = function {
+            match op {
+                op::FUNCTION_PARAMETER => {
+                    module.result_types.insert(arg(1)?, arg(0)?);
+                    fun.parameters.push(arg(1)?);
+                }
+                op::LABEL => {
+                    fun.labels.insert(arg(0)?, fun.blocks.len());
+                    fun.blocks.push(Block {
+                        label: arg(0)?,
+                        instructions: Vec::new(),
+                    });
+                }
+                op::FUNCTION_END => {}
+                _ => {
+                    if has_result_type(op) && operands.len() >= 2 {
+                        module.result_types.insert(operands[1], operands[0]);
+                    }
+                    match fun.blocks.last_mut() {
+                        Some(block) => block.instructions.push(Instruction {
+                            op,
+                            operands: operands.to_vec(),
+                        }),
+                        None => return Err(format!("Instruction {} outside of a block in function {}", op, id)),
+                    }
+                }
+            }
+            if op == op::FUNCTION_END {
+                let (id, fun) = function.take().unwrap();
+                module.functions.insert(id, fun);
+            }
+            continue
+        }
+
+        match op {
+            op::NOP | op::SOURCE_CONTINUED | op::SOURCE | op::SOURCE_EXTENSION | op::NAME |
+            op::MEMBER_NAME | op::STRING | op::LINE | op::NO_LINE | op::EXTENSION |
+            op::MEMORY_MODEL | op::CAPABILITY | op::MODULE_PROCESSED | op::DECORATION_GROUP |
+            op::DECORATE_ID | op::DECORATE_STRING | op::MEMBER_DECORATE_STRING |
+            op::TYPE_FORWARD_POINTER => {}
+            op::EXT_INST_IMPORT => {
+                module.ext_inst_imports.insert(arg(0)?, literal_string(rest(1)).0);
+            }
+            op::ENTRY_POINT => {
+                let (name, length) = literal_string(rest(2));
+                module.entry_points.push(EntryPoint {
+                    execution_model: arg(0)?,
+                    function: arg(1)?,
+                    name,
+                    interface: rest(2 + length).to_vec(),
+                });
+            }
+            op::EXECUTION_MODE => {
+                if arg(1)? == EXECUTION_MODE_LOCAL_SIZE {
+                    module.local_sizes.insert(arg(0)?, [arg(2)?, arg(3)?, arg(4)?]);
+                }
+            }
+            op::EXECUTION_MODE_ID => {
+                if arg(1)? == EXECUTION_MODE_LOCAL_SIZE_ID {
+                    module.local_size_ids.insert(arg(0)?, [arg(2)?, arg(3)?, arg(4)?]);
+                }
+            }
+            op::DECORATE => {
+                module.decorations
+                    .entry(arg(0)?)
+                    .or_insert_with(Decorations::default)
+                    .apply(arg(1)?, rest(2))?;
+            }
+            op::MEMBER_DECORATE => {
+                module.member_decorations
+                    .entry((arg(0)?, arg(1)?))
+                    .or_insert_with(Decorations::default)
+                    .apply(arg(2)?, rest(3))?;
+            }
+            op::GROUP_DECORATE => {
+                let group = module.decorations(arg(0)?);
+                for &target in rest(1) {
+                    module.decorations.insert(target, group.clone());
+                }
+            }
+            op::TYPE_VOID => { module.types.insert(arg(0)?, Type::Void); }
+            op::TYPE_BOOL => { module.types.insert(arg(0)?, Type::Bool); }
+            op::TYPE_INT => {
+                module.types.insert(arg(0)?, Type::Int { width: arg(1)?, signed: arg(2)? != 0 });
+            }
+            op::TYPE_FLOAT => {
+                module.types.insert(arg(0)?, Type::Float { width: arg(1)? });
+            }
+            op::TYPE_VECTOR => {
+                module.types.insert(arg(0)?, Type::Vector { component: arg(1)?, count: arg(2)? });
+            }
+            op::TYPE_MATRIX => {
+                module.types.insert(arg(0)?, Type::Matrix { column: arg(1)?, count: arg(2)? });
+            }
+            op::TYPE_IMAGE => {
+                module.types.insert(arg(0)?, Type::Image { dim: arg(2)?, sampled: arg(6)? });
+            }
+            op::TYPE_SAMPLER => { module.types.insert(arg(0)?, Type::Sampler); }
+            op::TYPE_SAMPLED_IMAGE => {
+                module.types.insert(arg(0)?, Type::SampledImage { image: arg(1)? });
+            }
+            op::TYPE_ARRAY => {
+                module.types.insert(arg(0)?, Type::Array { element: arg(1)?, length: arg(2)? });
+            }
+            op::TYPE_RUNTIME_ARRAY => {
+                module.types.insert(arg(0)?, Type::RuntimeArray { element: arg(1)? });
+            }
+            op::TYPE_STRUCT => {
+                module.types.insert(arg(0)?, Type::Struct { members: rest(1).to_vec() });
+            }
+            op::TYPE_POINTER => {
+                module.types.insert(arg(0)?, Type::Pointer { pointee: arg(2)? });
+            }
+            op::TYPE_FUNCTION => { module.types.insert(arg(0)?, Type::Function); }
+            op::UNDEF | op::CONSTANT_TRUE | op::CONSTANT_FALSE | op::CONSTANT |
+            op::CONSTANT_COMPOSITE | op::CONSTANT_NULL | op::SPEC_CONSTANT_TRUE |
+            op::SPEC_CONSTANT_FALSE | op::SPEC_CONSTANT | op::SPEC_CONSTANT_COMPOSITE |
+            op::SPEC_CONSTANT_OP => {
+                module.result_types.insert(arg(1)?, arg(0)?);
+                module.constants.push(Instruction {
+                    op,
+                    operands: operands.to_vec(),
+                });
+            }
+            op::VARIABLE => {
+                module.result_types.insert(arg(1)?, arg(0)?);
+                module.variables.insert(arg(1)?, Variable {
+                    ty: arg(0)?,
+                    storage: arg(2)?,
+                    initializer: operands.get(3).cloned(),
+                });
+            }
+            op::FUNCTION => {
+                module.result_types.insert(arg(1)?, arg(0)?);
+                function = Some((arg(1)?, Function::default()));
+            }
+            _ => return Err(format!("Unsupported instruction {}", op)),
+        }
+    }
+
+    if function.is_some() {
+        return Err("Missing end of function".to_owned());
+    }
+    Ok(module)
+}
+
+/// Assembler of hand-written modules, used by the tests of the HAL and the backends.
+#[doc(hidden)]
+pub mod assembler {
+    use super::MAGIC_NUMBER;
+
+    /// Encode a literal string as nul-terminated words.
+    pub fn string(value: &str) -> Vec<u32> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        bytes
+            .chunks(4)
+            .map(|c| c.iter().enumerate().fold(0, |word, (i, &b)| word | (b as u32) << (8 * i)))
+            .collect()
+    }
+
+    /// Module being assembled, starting with a valid header.
+    #[derive(Debug)]
+    pub struct Assembler {
+        words: Vec<u32>,
+    }
+
+    impl Default for Assembler {
+        fn default() -> Self {
+            Assembler::new()
+        }
+    }
+
+    impl Assembler {
+        /// Start an empty module.
+        pub fn new() -> Self {
+            Assembler {
+                words: vec![MAGIC_NUMBER, 0x0001_0000, 0, 0x1000, 0],
+            }
+        }
+
+        /// Append an instruction.
+        pub fn inst(&mut self, op: u16, operands: &[u32]) -> &mut Self {
+            self.words.push((operands.len() as u32 + 1) << 16 | op as u32);
+            self.words.extend_from_slice(operands);
+            self
+        }
+
+        /// Words of the module.
+        pub fn words(&self) -> &[u32] {
+            &self.words
+        }
+
+        /// Bytes of the module, in little endian.
+        pub fn bytes(&self) -> Vec<u8> {
+            self.words
+                .iter()
+                .flat_map(|&word| (0 .. 4).map(move |i| (word >> (8 * i)) as u8))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::assembler::{string, Assembler};
+
+    /// Entry point writing into a storage buffer laid out as
+    /// `struct { uint count; uint data[]; }`, with the array at offset 16
+    /// and a stride of 8 bytes.
+    fn storage_module() -> Assembler {
+        let mut asm = Assembler::new();
+        asm
+            .inst(op::CAPABILITY, &[1])
+            .inst(op::EXT_INST_IMPORT, &[&[2][..], &string(GLSL_STD_450)[..]].concat())
+            .inst(op::MEMORY_MODEL, &[0, 1])
+            .inst(op::ENTRY_POINT, &[&[EXECUTION_MODEL_GL_COMPUTE, 30][..], &string("main")[..]].concat())
+            .inst(op::EXECUTION_MODE, &[30, EXECUTION_MODE_LOCAL_SIZE, 4, 2, 1])
+            .inst(op::NAME, &[&[30][..], &string("main")[..]].concat())
+            .inst(op::DECORATE, &[11, decoration::ARRAY_STRIDE, 8])
+            .inst(op::MEMBER_DECORATE, &[12, 0, decoration::OFFSET, 0])
+            .inst(op::MEMBER_DECORATE, &[12, 1, decoration::OFFSET, 16])
+            .inst(op::DECORATE, &[14, decoration::DESCRIPTOR_SET, 1])
+            .inst(op::DECORATE, &[14, decoration::BINDING, 2])
+            .inst(op::TYPE_VOID, &[1])
+            .inst(op::TYPE_FUNCTION, &[2, 1])
+            .inst(op::TYPE_INT, &[3, 32, 0])
+            .inst(op::TYPE_RUNTIME_ARRAY, &[11, 3])
+            .inst(op::TYPE_STRUCT, &[12, 3, 11])
+            .inst(op::TYPE_POINTER, &[13, storage::STORAGE_BUFFER, 12])
+            .inst(op::CONSTANT, &[3, 16, 7])
+            .inst(op::VARIABLE, &[13, 14, storage::STORAGE_BUFFER])
+            .inst(op::FUNCTION, &[1, 30, 0, 2])
+            .inst(op::LABEL, &[31])
+            .inst(op::BRANCH, &[32])
+            .inst(op::LABEL, &[32])
+            .inst(op::RETURN, &[])
+            .inst(op::FUNCTION_END, &[]);
+        asm
+    }
+
+    #[test]
+    fn parse_module() {
+        let module = parse(&storage_module().bytes()).unwrap();
+
+        assert_eq!(module.entry_points.len(), 1);
+        assert_eq!(module.entry_points[0].name, "main");
+        assert_eq!(module.entry_points[0].function, 30);
+        assert_eq!(module.local_sizes[&30], [4, 2, 1]);
+        assert_eq!(module.ext_inst_imports[&2], GLSL_STD_450);
+
+        let decorations = module.decorations(14);
+        assert_eq!((decorations.descriptor_set, decorations.binding), (Some(1), Some(2)));
+        assert_eq!(module.decorations(11).array_stride, Some(8));
+        assert_eq!(module.member_decorations(12, 0).offset, Some(0));
+        assert_eq!(module.member_decorations(12, 1).offset, Some(16));
+        assert_eq!(module.member_decorations(12, 2).offset, None);
+        match module.types[&12] {
+            Type::Struct { ref members } => assert_eq!(*members, vec![3, 11]),
+            ref other => panic!("Unexpected type {:?}", other),
+        }
+        match module.types[&13] {
+            Type::Pointer { pointee } => assert_eq!(pointee, 12),
+            ref other => panic!("Unexpected type {:?}", other),
+        }
+
+        assert_eq!(module.variables[&14].storage, storage::STORAGE_BUFFER);
+        assert_eq!(module.result_types[&14], 13);
+        assert_eq!(module.result_types[&16], 3);
+        assert_eq!(module.constants.len(), 1);
+
+        let function = &module.functions[&30];
+        assert_eq!(function.blocks.len(), 2);
+        assert_eq!(function.labels[&32], 1);
+        assert_eq!(function.blocks[0].instructions[0].op, op::BRANCH);
+    }
+
+    #[test]
+    fn parse_swapped_endianness() {
+        let bytes = storage_module()
+            .words()
+            .iter()
+            .flat_map(|&word| (0 .. 4).rev().map(move |i| (word >> (8 * i)) as u8))
+            .collect::<Vec<_>>();
+        let module = parse(&bytes).unwrap();
+        assert_eq!(module.entry_points[0].name, "main");
+        assert_eq!(module.member_decorations(12, 1).offset, Some(16));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let mut bytes = storage_module().bytes();
+        assert!(parse(&bytes[.. 6]).is_err());
+        assert!(parse(&bytes[.. 16]).is_err());
+
+        // last instruction claiming to be longer than the module
+        let len = bytes.len();
+        bytes[len - 2] = 2;
+        assert!(parse(&bytes).is_err());
+
+        bytes[0] = 0;
+        assert!(parse(&bytes).is_err());
+
+        let mut asm = Assembler::new();
+        asm.inst(op::TRANSPOSE, &[1, 2, 3]);
+        assert!(parse(&asm.bytes()).is_err());
+
+        let mut asm = Assembler::new();
+        asm.inst(op::FUNCTION, &[1, 30, 0, 2]).inst(op::LABEL, &[31]);
+        assert!(parse(&asm.bytes()).is_err());
+    }
+
+    #[test]
+    fn parse_entry_point_interface() {
+        let mut asm = Assembler::new();
+        asm
+            .inst(op::ENTRY_POINT, &[&[0, 30][..], &string("vert")[..], &[14, 15]].concat())
+            .inst(op::ENTRY_POINT, &[&[EXECUTION_MODEL_GL_COMPUTE, 31][..], &string("comp")[..]].concat())
+            .inst(op::EXECUTION_MODE_ID, &[31, EXECUTION_MODE_LOCAL_SIZE_ID, 20, 21, 22]);
+        let module = parse(&asm.bytes()).unwrap();
+
+        assert_eq!(module.entry_points[0].name, "vert");
+        assert_eq!(module.entry_points[0].interface, vec![14, 15]);
+        assert_eq!(module.entry_points[1].name, "comp");
+        assert!(module.entry_points[1].interface.is_empty());
+        assert_eq!(module.local_size_ids[&31], [20, 21, 22]);
+        assert!(module.local_sizes.is_empty());
+    }
+}
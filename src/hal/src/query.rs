@@ -9,6 +9,22 @@ use Backend;
 /// A query identifier.
 pub type QueryId = u32;
 
+/// Error creating a query pool.
+#[derive(Fail, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Memory allocation on the host side failed.
+    /// This could be caused by a lack of memory.
+    #[fail(display = "Host memory allocation failed.")]
+    OutOfHostMemory,
+    /// Memory allocation on the device side failed.
+    /// This could be caused by a lack of memory.
+    #[fail(display = "Device memory allocation failed.")]
+    OutOfDeviceMemory,
+    /// The query type is not supported by the device.
+    #[fail(display = "Query type unsupported ({:?}).", _0)]
+    Unsupported(QueryType),
+}
+
 /// A `Query` object has a particular identifier and saves its results to a given `QueryPool`.
 /// It is passed as a parameter to the command buffer's query methods.
 #[derive(Debug)]
@@ -30,8 +46,27 @@ bitflags!(
     }
 );
 
+bitflags!(
+    /// Query result flags.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct ResultFlags: u32 {
+        /// Results will be written as an array of 64-bit unsigned integer values.
+        /// If this bit is not set, the results will be written as an array of
+        /// 32-bit unsigned integer values.
+        const BITS_64 = 0x1;
+        /// Wait for each query's status to become available before retrieving its results.
+        const WAIT = 0x2;
+        /// Availability status accompanies the results, as an extra value
+        /// following each query result.
+        const WITH_AVAILABILITY = 0x4;
+        /// Returning partial results is acceptable.
+        const PARTIAL = 0x8;
+    }
+);
+
 /// Type of queries in a query pool.
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum QueryType {
     /// Occlusion query. Count the number of drawn samples between
     /// the start and end of the query command.
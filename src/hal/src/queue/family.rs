@@ -24,6 +24,10 @@ pub trait QueueFamily: Debug + Any + Send + Sync {
     fn supports_compute(&self) -> bool {
         Compute::supported_by(self.queue_type())
     }
+    /// Returns true if the queue supports binding memory to sparse resources.
+    fn supports_sparse_binding(&self) -> bool {
+        false
+    }
     /// Returns the queue family ID.
     fn id(&self) -> QueueFamilyId;
 }
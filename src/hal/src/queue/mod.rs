@@ -25,7 +25,7 @@ pub use self::capability::{
 pub use self::family::{
     QueueFamily, QueueFamilyId, QueueGroup, Queues,
 };
-pub use self::submission::{RawSubmission, Submission};
+pub use self::submission::{RawSparseBinding, RawSubmission, Submission};
 
 
 /// The type of the queue, an enum encompassing `queue::Capability`
@@ -57,6 +57,12 @@ pub trait RawCommandQueue<B: Backend>: Any + Send + Sync {
         IC: IntoIterator,
         IC::Item: Borrow<B::CommandBuffer>;
 
+    /// Bind memory to sparse buffers and images.
+    /// `fence` will be signalled after binding and _must_ be unsignalled.
+    ///
+    /// Unsafe because it's not checked that the queue family supports sparse binding.
+    unsafe fn bind_sparse(&mut self, binding: RawSparseBinding<B>, fence: Option<&B::Fence>);
+
     /// Presents the result of the queue to the given swapchains, after waiting on all the
     /// semaphores given in `wait_semaphores`. A given swapchain must not appear in this
     /// list more than once.
@@ -116,6 +122,17 @@ impl<B: Backend, C> CommandQueue<B, C> {
         }
     }
 
+    /// Bind memory to sparse buffers and images.
+    /// `fence` will be signalled after binding and _must_ be unsignalled.
+    ///
+    /// # Safety
+    ///
+    /// The queue family must support sparse binding, as reported by
+    /// `QueueFamily::supports_sparse_binding`.
+    pub unsafe fn bind_sparse(&mut self, binding: RawSparseBinding<B>, fence: Option<&B::Fence>) {
+        self.0.bind_sparse(binding, fence)
+    }
+
     /// Presents the result of the queue to the given swapchains, after waiting on all the
     /// semaphores given in `wait_semaphores`. A given swapchain must not appear in this
     /// list more than once.
@@ -1,7 +1,7 @@
 //! A `Submission` is simply a collection of data bundled up and ready
 //! to be submitted to a command queue.
 
-use {pso, Backend};
+use {memory, pso, Backend};
 use command::{Submittable, Primary};
 use super::capability::{Transfer, Supports, Upper};
 use std::borrow::{Borrow, Cow};
@@ -23,6 +23,20 @@ where
     pub signal_semaphores: &'a [&'a B::Semaphore],
 }
 
+/// Sparse binding operations for a command queue, executed in order.
+pub struct RawSparseBinding<'a, B: Backend + 'a> {
+    /// Semaphores to wait being signalled before binding.
+    pub wait_semaphores: &'a [&'a B::Semaphore],
+    /// Semaphores which get signalled after binding.
+    pub signal_semaphores: &'a [&'a B::Semaphore],
+    /// Ranges of sparse buffers to bind.
+    pub buffers: &'a [(&'a B::Buffer, &'a [memory::SparseBind<'a, B>])],
+    /// Opaque ranges of sparse images to bind, including their mip tails.
+    pub image_opaques: &'a [(&'a B::Image, &'a [memory::SparseBind<'a, B>])],
+    /// Regions of sparse images to bind.
+    pub images: &'a [(&'a B::Image, &'a [memory::SparseImageBind<'a, B>])],
+}
+
 /// Submission information for a command queue, generic over a particular
 /// backend and a particular queue type.
 pub struct Submission<'a, B: Backend, C> {
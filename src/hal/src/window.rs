@@ -174,6 +174,7 @@ pub enum FrameSync<'a, B: Backend> {
 /// Specifies the mode regulating how a swapchain presents frames.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PresentMode {
     /// Don't ever wait for v-sync.
     Immediate = 0,
@@ -202,6 +203,7 @@ pub enum PresentMode {
 /// # }
 /// ```
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SwapchainConfig {
     /// Presentation mode.
     pub present_mode: PresentMode,
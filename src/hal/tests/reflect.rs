@@ -0,0 +1,192 @@
+extern crate gfx_backend_empty as empty;
+extern crate gfx_hal as hal;
+
+use hal::{format, pass, pso, Device, Primitive};
+use hal::pso::reflect::{check_graphics_pipeline, Mismatch, ShaderInterface};
+use hal::pso::spirv::assembler::{string, Assembler};
+use hal::pso::spirv::{decoration, op, storage};
+
+type ShaderModule = <empty::Backend as hal::Backend>::ShaderModule;
+
+fn function(asm: &mut Assembler) {
+    asm
+        .inst(op::FUNCTION, &[2, 1, 0, 3])
+        .inst(op::LABEL, &[30])
+        .inst(op::RETURN, &[])
+        .inst(op::FUNCTION_END, &[]);
+}
+
+/// Vertex entry point `main` reading a `vec3` at location 0 and a `uint` at
+/// location 1, writing a `vec2` at location 0, with a specialization constant
+/// of id 3.
+fn vertex_module() -> Vec<u8> {
+    let mut asm = Assembler::new();
+    asm
+        .inst(op::ENTRY_POINT, &[&[0, 1][..], &string("main")[..], &[11, 12, 13]].concat())
+        .inst(op::DECORATE, &[11, decoration::LOCATION, 0])
+        .inst(op::DECORATE, &[12, decoration::LOCATION, 1])
+        .inst(op::DECORATE, &[13, decoration::LOCATION, 0])
+        .inst(op::DECORATE, &[14, decoration::SPEC_ID, 3])
+        .inst(op::TYPE_VOID, &[2])
+        .inst(op::TYPE_FUNCTION, &[3, 2])
+        .inst(op::TYPE_FLOAT, &[4, 32])
+        .inst(op::TYPE_VECTOR, &[5, 4, 2])
+        .inst(op::TYPE_VECTOR, &[6, 4, 3])
+        .inst(op::TYPE_INT, &[7, 32, 0])
+        .inst(op::TYPE_POINTER, &[8, storage::INPUT, 6])
+        .inst(op::TYPE_POINTER, &[9, storage::INPUT, 7])
+        .inst(op::TYPE_POINTER, &[10, storage::OUTPUT, 5])
+        .inst(op::VARIABLE, &[8, 11, storage::INPUT])
+        .inst(op::VARIABLE, &[9, 12, storage::INPUT])
+        .inst(op::VARIABLE, &[10, 13, storage::OUTPUT])
+        .inst(op::SPEC_CONSTANT, &[7, 14, 1]);
+    function(&mut asm);
+    asm.bytes()
+}
+
+/// Fragment entry points writing a `vec4` at location 0, `main` reading a
+/// `vec2` at location 0 and `other` reading a `float` at location 1.
+fn fragment_module() -> Vec<u8> {
+    let mut asm = Assembler::new();
+    asm
+        .inst(op::ENTRY_POINT, &[&[4, 1][..], &string("main")[..], &[10, 11]].concat())
+        .inst(op::ENTRY_POINT, &[&[4, 1][..], &string("other")[..], &[12, 11]].concat())
+        .inst(op::DECORATE, &[10, decoration::LOCATION, 0])
+        .inst(op::DECORATE, &[11, decoration::LOCATION, 0])
+        .inst(op::DECORATE, &[12, decoration::LOCATION, 1])
+        .inst(op::TYPE_VOID, &[2])
+        .inst(op::TYPE_FUNCTION, &[3, 2])
+        .inst(op::TYPE_FLOAT, &[4, 32])
+        .inst(op::TYPE_VECTOR, &[5, 4, 2])
+        .inst(op::TYPE_VECTOR, &[6, 4, 4])
+        .inst(op::TYPE_POINTER, &[7, storage::INPUT, 5])
+        .inst(op::TYPE_POINTER, &[8, storage::OUTPUT, 6])
+        .inst(op::TYPE_POINTER, &[9, storage::INPUT, 4])
+        .inst(op::VARIABLE, &[7, 10, storage::INPUT])
+        .inst(op::VARIABLE, &[8, 11, storage::OUTPUT])
+        .inst(op::VARIABLE, &[9, 12, storage::INPUT]);
+    function(&mut asm);
+    asm.bytes()
+}
+
+struct Shaders {
+    vertex: (ShaderModule, ShaderInterface),
+    fragment: (ShaderModule, ShaderInterface),
+}
+
+impl Shaders {
+    fn new() -> Self {
+        let load = |code: Vec<u8>| (
+            empty::Device.create_shader_module(&code).unwrap(),
+            ShaderInterface::parse(&code).unwrap(),
+        );
+        Shaders {
+            vertex: load(vertex_module()),
+            fragment: load(fragment_module()),
+        }
+    }
+
+    fn check<F>(
+        &self,
+        vertex: (&str, &[pso::Specialization]),
+        fragment: (&str, &[pso::Specialization]),
+        configure: F,
+    ) -> Vec<Mismatch>
+    where
+        F: FnOnce(&mut pso::GraphicsPipelineDesc<empty::Backend>),
+    {
+        let shaders = pso::GraphicsShaderSet {
+            vertex: pso::EntryPoint {
+                entry: vertex.0,
+                module: &self.vertex.0,
+                specialization: vertex.1,
+            },
+            hull: None,
+            domain: None,
+            geometry: None,
+            fragment: Some(pso::EntryPoint {
+                entry: fragment.0,
+                module: &self.fragment.0,
+                specialization: fragment.1,
+            }),
+        };
+        let subpass = pass::Subpass { index: 0, main_pass: &() };
+        let mut desc = pso::GraphicsPipelineDesc::new(
+            shaders,
+            Primitive::TriangleList,
+            pso::Rasterizer::FILL,
+            &(),
+            subpass,
+        );
+        desc.vertex_buffers.push(pso::VertexBufferDesc { binding: 0, stride: 16, rate: 0 });
+        desc.attributes.push(attribute(0, 0, format::Format::Rgb32Float));
+        desc.attributes.push(attribute(1, 0, format::Format::R32Uint));
+        desc.blender.targets.push(pso::ColorBlendDesc::EMPTY);
+        configure(&mut desc);
+        check_graphics_pipeline(&desc, &self.vertex.1, Some(&self.fragment.1))
+    }
+}
+
+fn attribute(location: pso::Location, binding: pso::BufferIndex, format: format::Format) -> pso::AttributeDesc {
+    pso::AttributeDesc {
+        location,
+        binding,
+        element: pso::Element { format, offset: 0 },
+    }
+}
+
+fn specialization(id: u32) -> pso::Specialization {
+    pso::Specialization { id, value: pso::Constant::U32(0) }
+}
+
+#[test]
+fn matching_pipeline() {
+    let shaders = Shaders::new();
+    let mismatches = shaders.check(("main", &[specialization(3)]), ("main", &[]), |_| {});
+    assert_eq!(mismatches, vec![]);
+}
+
+#[test]
+fn missing_entry_points() {
+    let shaders = Shaders::new();
+    let mismatches = shaders.check(("vert", &[]), ("frag", &[]), |_| {});
+    assert_eq!(mismatches, vec![
+        Mismatch::MissingEntryPoint(pso::Stage::Vertex, "vert".to_owned()),
+        Mismatch::MissingEntryPoint(pso::Stage::Fragment, "frag".to_owned()),
+    ]);
+}
+
+#[test]
+fn unknown_specialization() {
+    let shaders = Shaders::new();
+    let mismatches = shaders.check(("main", &[specialization(3), specialization(4)]), ("main", &[specialization(3)]), |_| {});
+    assert_eq!(mismatches, vec![
+        Mismatch::UnknownSpecialization(pso::Stage::Vertex, 4),
+        Mismatch::UnknownSpecialization(pso::Stage::Fragment, 3),
+    ]);
+}
+
+#[test]
+fn attribute_mismatches() {
+    let shaders = Shaders::new();
+    let mismatches = shaders.check(("main", &[]), ("main", &[]), |desc| {
+        desc.attributes = vec![attribute(0, 1, format::Format::Rgba8Uint)];
+    });
+    assert_eq!(mismatches, vec![
+        Mismatch::AttributeFormat(0, format::Format::Rgba8Uint),
+        Mismatch::MissingVertexBuffer(0, 1),
+        Mismatch::MissingAttribute(1),
+    ]);
+}
+
+#[test]
+fn fragment_mismatches() {
+    let shaders = Shaders::new();
+    let mismatches = shaders.check(("main", &[]), ("other", &[]), |desc| {
+        desc.blender.targets.clear();
+    });
+    assert_eq!(mismatches, vec![
+        Mismatch::MissingColorTarget(0),
+        Mismatch::StageInterface(1),
+    ]);
+}
@@ -0,0 +1,482 @@
+//! Sub-allocation of blocks from large chunks of device memory.
+
+use std::iter;
+use std::ops::Range;
+use std::sync::Arc;
+
+use hal::{self, mapping, memory, Backend, Device, MemoryTypeId};
+
+use range_alloc::RangeAllocator;
+use {align_up, find_memory_type, is_non_coherent, AllocationError, Stats};
+
+
+/// Configuration of a `GeneralAllocator`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Size of the chunks of device memory blocks are sub-allocated from.
+    ///
+    /// It is limited to the size of the heap of each memory type.
+    pub chunk_size: u64,
+    /// Blocks larger than this size get a device memory object of their own.
+    pub dedicated_threshold: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            chunk_size: 64 << 20,
+            dedicated_threshold: 16 << 20,
+        }
+    }
+}
+
+/// Block of device memory, allocated by a `GeneralAllocator`.
+///
+/// It has to be returned to the allocator it comes from with `free`.
+#[derive(Debug)]
+pub struct Block<B: Backend> {
+    memory: Arc<B::Memory>,
+    memory_type: MemoryTypeId,
+    chunk: usize,
+    /// Range reserved in the chunk, aligned for flushing.
+    allocation: Range<u64>,
+    size: u64,
+}
+
+impl<B: Backend> Block<B> {
+    /// Device memory the block is part of.
+    pub fn memory(&self) -> &B::Memory {
+        &self.memory
+    }
+
+    /// Range of the block in its device memory.
+    pub fn range(&self) -> Range<u64> {
+        self.allocation.start .. self.allocation.start + self.size
+    }
+
+    /// Memory type of the device memory.
+    pub fn memory_type(&self) -> MemoryTypeId {
+        self.memory_type
+    }
+}
+
+#[derive(Debug)]
+struct Chunk<B: Backend> {
+    memory: Arc<B::Memory>,
+    size: u64,
+    ranges: RangeAllocator<u64>,
+    /// Chunks holding a single large block are released along with it.
+    dedicated: bool,
+    blocks: usize,
+    used: u64,
+    /// Pointer to the whole chunk, mapped on the first access from the host.
+    mapping: Option<*mut u8>,
+}
+
+// The mapping is only accessed through the allocator, which is borrowed mutably.
+unsafe impl<B: Backend> Send for Chunk<B> {}
+unsafe impl<B: Backend> Sync for Chunk<B> {}
+
+impl<B: Backend> Chunk<B> {
+    fn stats(&self) -> Stats {
+        Stats {
+            chunks: 1,
+            blocks: self.blocks,
+            allocated: self.size,
+            used: self.used,
+            largest_free: self.ranges.largest_free_range().unwrap_or(0),
+        }
+    }
+
+    fn release(self, device: &B::Device) {
+        if self.mapping.is_some() {
+            device.unmap_memory(&self.memory);
+        }
+        match Arc::try_unwrap(self.memory) {
+            Ok(memory) => device.free_memory(memory),
+            Err(_) => error!("Memory chunk released while still referenced"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MemoryType<B: Backend> {
+    properties: memory::Properties,
+    chunk_size: u64,
+    /// Chunks indexed by the blocks, with the slots of released chunks left empty.
+    chunks: Vec<Option<Chunk<B>>>,
+}
+
+impl<B: Backend> MemoryType<B> {
+    fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        for chunk in self.chunks.iter().filter_map(Option::as_ref) {
+            stats.add(&chunk.stats());
+        }
+        stats
+    }
+
+    fn insert(&mut self, chunk: Chunk<B>) -> usize {
+        match self.chunks.iter().position(Option::is_none) {
+            Some(index) => {
+                self.chunks[index] = Some(chunk);
+                index
+            }
+            None => {
+                self.chunks.push(Some(chunk));
+                self.chunks.len() - 1
+            }
+        }
+    }
+}
+
+/// Allocator of memory blocks of any size and lifetime.
+///
+/// Blocks are sub-allocated from large chunks of device memory, one set of chunks
+/// per memory type, with a best-fit strategy. Large blocks get device memory of
+/// their own. Blocks in non-coherent host visible memory are aligned to the
+/// non-coherent atom size, so that flushing one never touches its neighbours.
+///
+/// The chunks are mapped as a whole on the first `map` of one of their blocks,
+/// and stay mapped until they are released.
+#[derive(Debug)]
+pub struct GeneralAllocator<B: Backend> {
+    config: Config,
+    memory_types: Vec<MemoryType<B>>,
+    raw_memory_types: Vec<hal::MemoryType>,
+    non_coherent_atom_size: u64,
+}
+
+impl<B: Backend> GeneralAllocator<B> {
+    /// Create an allocator for a device with the given properties.
+    pub fn new(memory_properties: &hal::MemoryProperties, limits: &hal::Limits, config: Config) -> Self {
+        let memory_types = memory_properties.memory_types
+            .iter()
+            .map(|ty| MemoryType {
+                properties: ty.properties,
+                chunk_size: config.chunk_size.min(memory_properties.memory_heaps[ty.heap_index]),
+                chunks: Vec::new(),
+            })
+            .collect();
+        GeneralAllocator {
+            config,
+            memory_types,
+            raw_memory_types: memory_properties.memory_types.clone(),
+            non_coherent_atom_size: limits.non_coherent_atom_size as u64,
+        }
+    }
+
+    /// Allocate a block satisfying the `requirements` of a resource, in a memory
+    /// type with at least the given `properties`.
+    pub fn allocate(
+        &mut self,
+        device: &B::Device,
+        requirements: memory::Requirements,
+        properties: memory::Properties,
+    ) -> Result<Block<B>, AllocationError> {
+        let memory_type = find_memory_type(&self.raw_memory_types, requirements.type_mask, properties)
+            .ok_or(AllocationError::NoCompatibleMemoryType)?;
+        self.allocate_from(device, memory_type, requirements.size, requirements.alignment)
+    }
+
+    /// Allocate a block of `size` bytes aligned to `alignment` in the given memory type.
+    pub fn allocate_from(
+        &mut self,
+        device: &B::Device,
+        memory_type: MemoryTypeId,
+        size: u64,
+        alignment: u64,
+    ) -> Result<Block<B>, AllocationError> {
+        let ty = &mut self.memory_types[memory_type.0];
+        let (alignment, reserved) = if is_non_coherent(ty.properties) {
+            let atom = self.non_coherent_atom_size;
+            (align_up(alignment.max(1), atom), align_up(size, atom))
+        } else {
+            (alignment.max(1), size)
+        };
+        // Empty blocks still need a distinct range.
+        let reserved = reserved.max(1);
+
+        if reserved > self.config.dedicated_threshold || reserved > ty.chunk_size {
+            let memory = device
+                .allocate_memory(memory_type, reserved)
+                .map_err(|_| AllocationError::OutOfMemory)?;
+            return Ok(Self::insert_chunk(ty, memory_type, memory, reserved, true, size));
+        }
+
+        // Over-allocate by the alignment, then give back the unaligned ends.
+        let padded = reserved + alignment - 1;
+        for (index, slot) in ty.chunks.iter_mut().enumerate() {
+            let chunk = match *slot {
+                Some(ref mut chunk) if !chunk.dedicated => chunk,
+                _ => continue,
+            };
+            if let Ok(range) = chunk.ranges.allocate_range(padded) {
+                let start = align_up(range.start, alignment);
+                if start > range.start {
+                    chunk.ranges.free_range(range.start .. start);
+                }
+                if start + reserved < range.end {
+                    chunk.ranges.free_range(start + reserved .. range.end);
+                }
+                chunk.blocks += 1;
+                chunk.used += reserved;
+                return Ok(Block {
+                    memory: chunk.memory.clone(),
+                    memory_type,
+                    chunk: index,
+                    allocation: start .. start + reserved,
+                    size,
+                });
+            }
+        }
+
+        match device.allocate_memory(memory_type, ty.chunk_size) {
+            Ok(memory) => {
+                let chunk_size = ty.chunk_size;
+                let block = Self::insert_chunk(ty, memory_type, memory, chunk_size, false, size);
+                let chunk = ty.chunks[block.chunk].as_mut().unwrap();
+                // The block is at the start of the chunk, so it is aligned.
+                chunk.used = reserved;
+                chunk.ranges.allocate_range(reserved).unwrap();
+                Ok(Block {
+                    allocation: 0 .. reserved,
+                    .. block
+                })
+            }
+            Err(_) => {
+                // There may still be room for the block alone.
+                debug!("Failed to allocate a chunk of {} bytes, trying a dedicated allocation", ty.chunk_size);
+                let memory = device
+                    .allocate_memory(memory_type, reserved)
+                    .map_err(|_| AllocationError::OutOfMemory)?;
+                Ok(Self::insert_chunk(ty, memory_type, memory, reserved, true, size))
+            }
+        }
+    }
+
+    fn insert_chunk(
+        ty: &mut MemoryType<B>,
+        memory_type: MemoryTypeId,
+        memory: B::Memory,
+        chunk_size: u64,
+        dedicated: bool,
+        size: u64,
+    ) -> Block<B> {
+        let memory = Arc::new(memory);
+        let chunk = ty.insert(Chunk {
+            memory: memory.clone(),
+            size: chunk_size,
+            ranges: RangeAllocator::new(0 .. chunk_size),
+            dedicated,
+            blocks: 1,
+            used: if dedicated { chunk_size } else { 0 },
+            mapping: None,
+        });
+        if dedicated {
+            ty.chunks[chunk].as_mut().unwrap().ranges.allocate_range(chunk_size).unwrap();
+        }
+        Block {
+            memory,
+            memory_type,
+            chunk,
+            allocation: 0 .. chunk_size,
+            size,
+        }
+    }
+
+    /// Return a block to the allocator.
+    ///
+    /// Chunks left without any block are released, except for the last one of
+    /// each memory type, which is kept around for future allocations.
+    pub fn free(&mut self, device: &B::Device, block: Block<B>) {
+        let Block { memory, memory_type, chunk: index, allocation, .. } = block;
+        drop(memory);
+        let ty = &mut self.memory_types[memory_type.0];
+        let release = {
+            let chunk = ty.chunks[index].as_mut().expect("Block freed twice");
+            chunk.ranges.free_range(allocation.clone());
+            chunk.blocks -= 1;
+            chunk.used -= allocation.end - allocation.start;
+            chunk.blocks == 0 && (chunk.dedicated || ty.chunks
+                .iter()
+                .enumerate()
+                .any(|(i, other)| i != index && other.as_ref().map_or(false, |c| !c.dedicated)))
+        };
+        if release {
+            ty.chunks[index].take().unwrap().release(device);
+        }
+    }
+
+    /// Map a block for access from the host, returning a pointer to its start.
+    ///
+    /// The memory type of the block has to be host visible.
+    pub fn map(&mut self, device: &B::Device, block: &Block<B>) -> Result<*mut u8, mapping::Error> {
+        let chunk = self.memory_types[block.memory_type.0].chunks[block.chunk]
+            .as_mut()
+            .expect("Block already freed");
+        let base = match chunk.mapping {
+            Some(ptr) => ptr,
+            None => {
+                let ptr = device.map_memory(&chunk.memory, 0 .. chunk.size)?;
+                chunk.mapping = Some(ptr);
+                ptr
+            }
+        };
+        Ok(unsafe { base.offset(block.allocation.start as isize) })
+    }
+
+    /// Make the writes of the host to a mapped block visible to the device.
+    ///
+    /// This is only needed for memory types that aren't coherent.
+    pub fn flush(&self, device: &B::Device, block: &Block<B>) {
+        if is_non_coherent(self.memory_types[block.memory_type.0].properties) {
+            device.flush_mapped_memory_ranges(iter::once((block.memory(), block.allocation.clone())));
+        }
+    }
+
+    /// Make the writes of the device to a mapped block visible to the host.
+    ///
+    /// This is only needed for memory types that aren't coherent.
+    pub fn invalidate(&self, device: &B::Device, block: &Block<B>) {
+        if is_non_coherent(self.memory_types[block.memory_type.0].properties) {
+            device.invalidate_mapped_memory_ranges(iter::once((block.memory(), block.allocation.clone())));
+        }
+    }
+
+    /// Statistics of the whole allocator.
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        for ty in &self.memory_types {
+            stats.add(&ty.stats());
+        }
+        stats
+    }
+
+    /// Statistics of a single memory type.
+    pub fn memory_type_stats(&self, memory_type: MemoryTypeId) -> Stats {
+        self.memory_types[memory_type.0].stats()
+    }
+
+    /// Release all the device memory of the allocator.
+    ///
+    /// All the blocks have to be freed beforehand.
+    pub fn dispose(self, device: &B::Device) {
+        for ty in self.memory_types {
+            for chunk in ty.chunks.into_iter().filter_map(|chunk| chunk) {
+                if chunk.blocks != 0 {
+                    error!("Disposing of an allocator with {} blocks still alive", chunk.blocks);
+                }
+                chunk.release(device);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate gfx_backend_empty as empty;
+
+    use std::ptr;
+
+    use hal::{self, memory, Instance, MemoryTypeId, PhysicalDevice};
+    use super::*;
+
+    fn open() -> (empty::Device, hal::MemoryProperties, hal::Limits) {
+        let adapter = empty::Instance::create("memory", 1).enumerate_adapters().remove(0);
+        let hal::Gpu { device, .. } = adapter.physical_device
+            .open(&[(&adapter.queue_families[0], &[1.0])], hal::Features::empty())
+            .unwrap();
+        let properties = adapter.physical_device.memory_properties();
+        let limits = adapter.physical_device.limits();
+        (device, properties, limits)
+    }
+
+    fn requirements(size: u64, alignment: u64) -> memory::Requirements {
+        memory::Requirements {
+            size,
+            alignment,
+            type_mask: !0,
+        }
+    }
+
+    fn config() -> Config {
+        Config {
+            chunk_size: 1024,
+            dedicated_threshold: 512,
+        }
+    }
+
+    #[test]
+    fn test_sub_allocation() {
+        let (device, properties, limits) = open();
+        let mut allocator = GeneralAllocator::<empty::Backend>::new(&properties, &limits, config());
+
+        let a = allocator.allocate(&device, requirements(100, 1), memory::Properties::CPU_VISIBLE).unwrap();
+        let b = allocator.allocate(&device, requirements(100, 64), memory::Properties::CPU_VISIBLE).unwrap();
+        assert_eq!(a.range(), 0 .. 100);
+        assert_eq!(b.range(), 128 .. 228);
+        assert_eq!(a.memory_type(), MemoryTypeId(0));
+
+        let stats = allocator.stats();
+        assert_eq!((stats.chunks, stats.blocks, stats.allocated, stats.used), (1, 2, 1024, 200));
+        // The padding between the blocks is still free.
+        assert_eq!(stats.largest_free, 1024 - 228);
+
+        allocator.free(&device, a);
+        allocator.free(&device, b);
+        let stats = allocator.stats();
+        assert_eq!((stats.chunks, stats.blocks, stats.used, stats.largest_free), (1, 0, 0, 1024));
+        allocator.dispose(&device);
+    }
+
+    #[test]
+    fn test_chunks() {
+        let (device, properties, limits) = open();
+        let mut allocator = GeneralAllocator::<empty::Backend>::new(&properties, &limits, config());
+
+        let blocks = (0 .. 5)
+            .map(|_| allocator.allocate(&device, requirements(400, 1), memory::Properties::empty()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(allocator.stats().chunks, 3);
+        let large = allocator.allocate(&device, requirements(600, 1), memory::Properties::empty()).unwrap();
+        assert_eq!(large.range(), 0 .. 600);
+        assert_eq!(allocator.stats().chunks, 4);
+
+        allocator.free(&device, large);
+        for block in blocks {
+            allocator.free(&device, block);
+        }
+        // The last chunk is kept for future allocations.
+        assert_eq!(allocator.stats().chunks, 1);
+        allocator.dispose(&device);
+    }
+
+    #[test]
+    fn test_map() {
+        let (device, properties, limits) = open();
+        let mut allocator = GeneralAllocator::<empty::Backend>::new(&properties, &limits, config());
+
+        let a = allocator.allocate(&device, requirements(4, 4), memory::Properties::CPU_VISIBLE).unwrap();
+        let b = allocator.allocate(&device, requirements(4, 4), memory::Properties::CPU_VISIBLE).unwrap();
+        unsafe {
+            ptr::copy_nonoverlapping([1u8, 2, 3, 4].as_ptr(), allocator.map(&device, &a).unwrap(), 4);
+            ptr::copy_nonoverlapping([5u8, 6, 7, 8].as_ptr(), allocator.map(&device, &b).unwrap(), 4);
+            allocator.flush(&device, &a);
+            let ptr = allocator.map(&device, &a).unwrap();
+            assert_eq!(*ptr.offset(3), 4);
+            assert_eq!(*ptr.offset(4), 5);
+        }
+        allocator.free(&device, a);
+        allocator.free(&device, b);
+        allocator.dispose(&device);
+    }
+
+    #[test]
+    fn test_no_compatible_memory_type() {
+        let (device, properties, limits) = open();
+        let mut allocator = GeneralAllocator::<empty::Backend>::new(&properties, &limits, config());
+        let result = allocator.allocate(&device, requirements(4, 4), memory::Properties::LAZILY_ALLOCATED);
+        assert_eq!(result.err(), Some(AllocationError::NoCompatibleMemoryType));
+    }
+}
@@ -0,0 +1,162 @@
+#![deny(missing_docs)]
+
+//! Device memory allocators on top of gfx-hal.
+//!
+//! Device memory objects are expensive to create and their number is limited,
+//! so resources are expected to share them. This crate provides two allocators
+//! doing so:
+//!
+//! - `GeneralAllocator`, sub-allocating blocks of any lifetime from large chunks
+//!   of memory of every memory type.
+//! - `LinearAllocator`, handing out blocks of a single memory in a ring,
+//!   released all at once per frame. It suits data uploaded every frame.
+//!
+//! ```ignore
+//! let mut allocator = GeneralAllocator::new(
+//!     &adapter.physical_device.memory_properties(),
+//!     &adapter.physical_device.limits(),
+//!     Config::default(),
+//! );
+//! let unbound = device.create_buffer(size, buffer::Usage::VERTEX)?;
+//! let requirements = device.get_buffer_requirements(&unbound);
+//! let block = allocator.allocate(&device, requirements, memory::Properties::CPU_VISIBLE)?;
+//! let buffer = device.bind_buffer_memory(block.memory(), block.range().start, unbound)?;
+//! ```
+
+#[macro_use]
+extern crate failure;
+extern crate gfx_hal as hal;
+#[macro_use]
+extern crate log;
+
+use hal::{memory, MemoryTypeId};
+
+mod general;
+mod linear;
+#[allow(dead_code)]
+#[path = "../../backend/auxil/range_alloc.rs"]
+mod range_alloc;
+
+pub use general::{Block, Config, GeneralAllocator};
+pub use linear::{LinearAllocator, LinearBlock};
+
+
+/// Error allocating a block of memory.
+#[derive(Fail, Debug, Clone, PartialEq, Eq)]
+pub enum AllocationError {
+    /// None of the memory types allowed by the requirements has the requested properties.
+    #[fail(display = "No memory type is compatible with the requirements.")]
+    NoCompatibleMemoryType,
+    /// Memory allocation on the device side failed.
+    #[fail(display = "Device memory allocation failed.")]
+    OutOfMemory,
+}
+
+/// Memory usage statistics of an allocator.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// Number of device memory objects allocated.
+    pub chunks: usize,
+    /// Number of live blocks.
+    pub blocks: usize,
+    /// Total size of the device memory objects, in bytes.
+    pub allocated: u64,
+    /// Size of the live blocks, including their alignment padding, in bytes.
+    pub used: u64,
+    /// Size of the largest free range of any device memory object, in bytes.
+    pub largest_free: u64,
+}
+
+impl Stats {
+    /// Size of the device memory not used by any block, in bytes.
+    pub fn free(&self) -> u64 {
+        self.allocated - self.used
+    }
+
+    /// Fragmentation of the free memory, from 0 when it is contiguous
+    /// to almost 1 when it is scattered in many small ranges.
+    pub fn fragmentation(&self) -> f32 {
+        match self.free() {
+            0 => 0.0,
+            free => 1.0 - self.largest_free as f32 / free as f32,
+        }
+    }
+
+    fn add(&mut self, other: &Stats) {
+        self.chunks += other.chunks;
+        self.blocks += other.blocks;
+        self.allocated += other.allocated;
+        self.used += other.used;
+        self.largest_free = self.largest_free.max(other.largest_free);
+    }
+}
+
+/// Find the first memory type allowed by `type_mask` that has all the `properties`.
+///
+/// The memory types are reported in order of preference, so the first compatible
+/// one is the most efficient.
+pub fn find_memory_type(
+    memory_types: &[hal::MemoryType], type_mask: u64, properties: memory::Properties
+) -> Option<MemoryTypeId> {
+    memory_types
+        .iter()
+        .enumerate()
+        .position(|(id, ty)| type_mask & (1 << id) != 0 && ty.properties.contains(properties))
+        .map(MemoryTypeId)
+}
+
+/// Round `value` up to a multiple of `alignment`, where an alignment of 0 or 1
+/// leaves it unchanged.
+pub fn align_up(value: u64, alignment: u64) -> u64 {
+    match alignment {
+        0 | 1 => value,
+        _ => value.div_ceil(alignment) * alignment,
+    }
+}
+
+/// Whether the ranges of a memory type have to be flushed and invalidated
+/// in multiples of the non-coherent atom size.
+fn is_non_coherent(properties: memory::Properties) -> bool {
+    properties.contains(memory::Properties::CPU_VISIBLE) && !properties.contains(memory::Properties::COHERENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_memory_type() {
+        let memory_types = [
+            hal::MemoryType {
+                properties: memory::Properties::DEVICE_LOCAL,
+                heap_index: 0,
+            },
+            hal::MemoryType {
+                properties: memory::Properties::CPU_VISIBLE | memory::Properties::COHERENT,
+                heap_index: 1,
+            },
+            hal::MemoryType {
+                properties: memory::Properties::CPU_VISIBLE | memory::Properties::CPU_CACHED,
+                heap_index: 1,
+            },
+        ];
+        assert_eq!(find_memory_type(&memory_types, !0, memory::Properties::empty()), Some(MemoryTypeId(0)));
+        assert_eq!(find_memory_type(&memory_types, !0, memory::Properties::CPU_VISIBLE), Some(MemoryTypeId(1)));
+        assert_eq!(find_memory_type(&memory_types, 0b100, memory::Properties::CPU_VISIBLE), Some(MemoryTypeId(2)));
+        assert_eq!(find_memory_type(&memory_types, 0b001, memory::Properties::CPU_VISIBLE), None);
+    }
+
+    #[test]
+    fn test_fragmentation() {
+        let stats = Stats {
+            chunks: 1,
+            blocks: 2,
+            allocated: 100,
+            used: 60,
+            largest_free: 10,
+        };
+        assert_eq!(stats.free(), 40);
+        assert_eq!(stats.fragmentation(), 0.75);
+        assert_eq!(Stats::default().fragmentation(), 0.0);
+    }
+}
@@ -0,0 +1,318 @@
+//! Ring allocation of short-lived blocks from a single device memory.
+
+use std::collections::VecDeque;
+use std::iter;
+use std::ops::Range;
+
+use hal::{self, mapping, memory, Backend, Device, MemoryTypeId};
+
+use {align_up, find_memory_type, is_non_coherent, AllocationError, Stats};
+
+
+/// Block of memory allocated by a `LinearAllocator`.
+///
+/// It stays valid until the frame it was allocated in is released.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinearBlock {
+    /// Range of the block in the memory of the allocator.
+    pub range: Range<u64>,
+    /// Pointer to the start of the block, if the memory is host visible.
+    pub ptr: Option<*mut u8>,
+}
+
+/// End of the allocations of a frame.
+#[derive(Debug)]
+struct Frame {
+    index: u64,
+    head: u64,
+    consumed: u64,
+    blocks: usize,
+}
+
+/// Allocator of short-lived blocks, typically data uploaded every frame.
+///
+/// Blocks are carved one after the other from a single device memory, wrapping
+/// around at its end. They aren't freed individually: the allocations are grouped
+/// in frames with `end_frame`, and the frames are released once the device is done
+/// with them, or all at once with `reset`.
+///
+/// Host visible memory is mapped for the whole lifetime of the allocator.
+#[derive(Debug)]
+pub struct LinearAllocator<B: Backend> {
+    memory: B::Memory,
+    memory_type: MemoryTypeId,
+    properties: memory::Properties,
+    size: u64,
+    mapping: Option<*mut u8>,
+    non_coherent_atom_size: u64,
+    /// Offset of the next allocation.
+    head: u64,
+    /// Offset of the oldest allocation still in use.
+    tail: u64,
+    /// Bytes consumed since the creation, including padding.
+    consumed: u64,
+    /// Bytes released since the creation.
+    released: u64,
+    /// Blocks allocated since the creation.
+    allocated_blocks: usize,
+    /// Blocks released since the creation.
+    released_blocks: usize,
+    frames: VecDeque<Frame>,
+}
+
+// The mapping is only accessed through the allocator, which is borrowed mutably.
+unsafe impl<B: Backend> Send for LinearAllocator<B> {}
+unsafe impl<B: Backend> Sync for LinearAllocator<B> {}
+
+impl<B: Backend> LinearAllocator<B> {
+    /// Create an allocator of `size` bytes, in a memory type allowed by `type_mask`
+    /// with at least the given `properties`.
+    pub fn new(
+        device: &B::Device,
+        memory_properties: &hal::MemoryProperties,
+        limits: &hal::Limits,
+        type_mask: u64,
+        properties: memory::Properties,
+        size: u64,
+    ) -> Result<Self, AllocationError> {
+        let memory_type = find_memory_type(&memory_properties.memory_types, type_mask, properties)
+            .ok_or(AllocationError::NoCompatibleMemoryType)?;
+        let properties = memory_properties.memory_types[memory_type.0].properties;
+        let memory = device
+            .allocate_memory(memory_type, size)
+            .map_err(|_| AllocationError::OutOfMemory)?;
+        let mapping = if properties.contains(memory::Properties::CPU_VISIBLE) {
+            match device.map_memory(&memory, 0 .. size) {
+                Ok(ptr) => Some(ptr),
+                Err(e) => {
+                    device.free_memory(memory);
+                    error!("Failed to map a linear allocator: {}", e);
+                    return Err(AllocationError::OutOfMemory);
+                }
+            }
+        } else {
+            None
+        };
+
+        Ok(LinearAllocator {
+            memory,
+            memory_type,
+            properties,
+            size,
+            mapping,
+            non_coherent_atom_size: limits.non_coherent_atom_size as u64,
+            head: 0,
+            tail: 0,
+            consumed: 0,
+            released: 0,
+            allocated_blocks: 0,
+            released_blocks: 0,
+            frames: VecDeque::new(),
+        })
+    }
+
+    /// Device memory the blocks are allocated from.
+    pub fn memory(&self) -> &B::Memory {
+        &self.memory
+    }
+
+    /// Memory type of the device memory.
+    pub fn memory_type(&self) -> MemoryTypeId {
+        self.memory_type
+    }
+
+    /// Allocate a block of `size` bytes aligned to `alignment`.
+    ///
+    /// Returns `None` if the memory still used by the previous frames leaves no room for it.
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<LinearBlock> {
+        let (alignment, reserved) = if is_non_coherent(self.properties) {
+            let atom = self.non_coherent_atom_size;
+            (align_up(alignment.max(1), atom), align_up(size, atom))
+        } else {
+            (alignment.max(1), size)
+        };
+
+        if self.consumed == self.released {
+            // Nothing is in use, so start over from the beginning.
+            self.head = 0;
+            self.tail = 0;
+        }
+        let wrapped = self.head < self.tail || (self.head == self.tail && self.consumed != self.released);
+        let start = if wrapped {
+            let start = align_up(self.head, alignment);
+            if start + reserved > self.tail {
+                return None;
+            }
+            start
+        } else {
+            let start = align_up(self.head, alignment);
+            if start + reserved <= self.size {
+                start
+            } else if reserved <= self.tail {
+                // Skip the end of the memory and wrap around.
+                0
+            } else {
+                return None;
+            }
+        };
+
+        let end = start + reserved;
+        self.consumed += if start >= self.head {
+            end - self.head
+        } else {
+            self.size - self.head + end
+        };
+        self.head = end;
+        self.allocated_blocks += 1;
+        Some(LinearBlock {
+            range: start .. start + size,
+            ptr: self.mapping.map(|ptr| unsafe { ptr.offset(start as isize) }),
+        })
+    }
+
+    /// Group the allocations made since the previous frame into the frame `index`.
+    ///
+    /// Frame indices are expected to increase.
+    pub fn end_frame(&mut self, index: u64) {
+        self.frames.push_back(Frame {
+            index,
+            head: self.head,
+            consumed: self.consumed,
+            blocks: self.allocated_blocks,
+        });
+    }
+
+    /// Release the blocks of all the frames up to `index`, which the device is done with.
+    pub fn release(&mut self, index: u64) {
+        while self.frames.front().map_or(false, |frame| frame.index <= index) {
+            let frame = self.frames.pop_front().unwrap();
+            self.tail = frame.head;
+            self.released = frame.consumed;
+            self.released_blocks = frame.blocks;
+        }
+    }
+
+    /// Release all the blocks, including the ones of the current frame.
+    pub fn reset(&mut self) {
+        self.frames.clear();
+        self.head = 0;
+        self.tail = 0;
+        self.released = self.consumed;
+        self.released_blocks = self.allocated_blocks;
+    }
+
+    /// Make the writes of the host to a block visible to the device.
+    ///
+    /// This is only needed for memory types that aren't coherent.
+    pub fn flush(&self, device: &B::Device, block: &LinearBlock) {
+        if is_non_coherent(self.properties) {
+            let end = align_up(block.range.end, self.non_coherent_atom_size).min(self.size);
+            device.flush_mapped_memory_ranges(iter::once((&self.memory, block.range.start .. end)));
+        }
+    }
+
+    /// Statistics of the allocator, counting the padding skipped when wrapping
+    /// around as used.
+    pub fn stats(&self) -> Stats {
+        let used = self.consumed - self.released;
+        let largest_free = if used == 0 {
+            self.size
+        } else if self.head > self.tail {
+            (self.size - self.head).max(self.tail)
+        } else {
+            self.tail - self.head
+        };
+        Stats {
+            chunks: 1,
+            blocks: self.allocated_blocks - self.released_blocks,
+            allocated: self.size,
+            used,
+            largest_free,
+        }
+    }
+
+    /// Release the device memory of the allocator.
+    pub fn dispose(self, device: &B::Device) {
+        if self.mapping.is_some() {
+            device.unmap_memory(&self.memory);
+        }
+        device.free_memory(self.memory);
+    }
+
+    /// Pointer to the start of the memory, if it is host visible.
+    pub fn mapping(&self) -> Result<*mut u8, mapping::Error> {
+        self.mapping.ok_or(mapping::Error::InvalidAccess)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate gfx_backend_empty as empty;
+
+    use hal::{self, memory, Instance, PhysicalDevice};
+    use super::*;
+
+    fn create(size: u64) -> (empty::Device, LinearAllocator<empty::Backend>) {
+        let adapter = empty::Instance::create("memory", 1).enumerate_adapters().remove(0);
+        let hal::Gpu { device, .. } = adapter.physical_device
+            .open(&[(&adapter.queue_families[0], &[1.0])], hal::Features::empty())
+            .unwrap();
+        let allocator = LinearAllocator::new(
+            &device,
+            &adapter.physical_device.memory_properties(),
+            &adapter.physical_device.limits(),
+            !0,
+            memory::Properties::CPU_VISIBLE,
+            size,
+        ).unwrap();
+        (device, allocator)
+    }
+
+    #[test]
+    fn test_ring() {
+        let (device, mut allocator) = create(100);
+
+        assert_eq!(allocator.allocate(30, 1).unwrap().range, 0 .. 30);
+        assert_eq!(allocator.allocate(30, 8).unwrap().range, 32 .. 62);
+        allocator.end_frame(0);
+        assert_eq!(allocator.allocate(30, 1).unwrap().range, 62 .. 92);
+        // The memory of the first frame is still in use.
+        assert_eq!(allocator.allocate(30, 1), None);
+        allocator.end_frame(1);
+
+        allocator.release(0);
+        assert_eq!(allocator.stats().used, 30);
+        assert_eq!(allocator.stats().blocks, 1);
+        // Wraps around, skipping the end of the memory.
+        let block = allocator.allocate(50, 1).unwrap();
+        assert_eq!(block.range, 0 .. 50);
+        assert_eq!(allocator.stats().used, 30 + 8 + 50);
+        assert_eq!(allocator.allocate(20, 1), None);
+        assert_eq!(allocator.allocate(12, 1).unwrap().range, 50 .. 62);
+        assert_eq!(allocator.stats().largest_free, 0);
+        allocator.end_frame(2);
+
+        allocator.release(2);
+        assert_eq!(allocator.stats().used, 0);
+        assert_eq!(allocator.allocate(100, 1).unwrap().range, 0 .. 100);
+        allocator.reset();
+        assert_eq!(allocator.stats().used, 0);
+        allocator.dispose(&device);
+    }
+
+    #[test]
+    fn test_mapping() {
+        let (device, mut allocator) = create(16);
+        let a = allocator.allocate(4, 4).unwrap();
+        let b = allocator.allocate(4, 4).unwrap();
+        unsafe {
+            *a.ptr.unwrap() = 1;
+            *b.ptr.unwrap() = 2;
+            let base = allocator.mapping().unwrap();
+            assert_eq!(*base, 1);
+            assert_eq!(*base.offset(4), 2);
+        }
+        allocator.flush(&device, &b);
+        allocator.dispose(&device);
+    }
+}
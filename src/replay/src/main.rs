@@ -0,0 +1,142 @@
+//! Replay of the API calls captured by `gfx-backend-capture`.
+//!
+//! Usage: `gfx-replay <capture> [--adapter <index>]`
+
+#![cfg_attr(
+    not(any(feature = "vulkan", feature = "dx12", feature = "metal", feature = "gl", feature = "empty")),
+    allow(dead_code, unused_imports)
+)]
+
+extern crate gfx_backend_capture as capture;
+extern crate gfx_hal as hal;
+#[macro_use]
+extern crate log;
+
+#[cfg(feature = "env_logger")]
+extern crate env_logger;
+#[cfg(feature = "empty")]
+extern crate gfx_backend_empty;
+#[cfg(feature = "vulkan")]
+extern crate gfx_backend_vulkan;
+#[cfg(feature = "dx12")]
+extern crate gfx_backend_dx12;
+#[cfg(feature = "metal")]
+extern crate gfx_backend_metal;
+#[cfg(feature = "gl")]
+extern crate gfx_backend_gl;
+
+mod replay;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use replay::Replay;
+
+
+fn run<I: hal::Instance>(instance: I, path: &Path, adapter: usize) -> usize {
+    let mut adapters = instance.enumerate_adapters();
+    if adapter >= adapters.len() {
+        println!("\tAdapter {} is not available, only {} found", adapter, adapters.len());
+        return 1
+    }
+    let adapter = adapters.swap_remove(adapter);
+    println!("\tAdapter: {:?}", adapter.info);
+
+    let file = File::open(path).expect("failed to open the capture");
+    let reader = capture::format::Reader::new(BufReader::new(file))
+        .expect("failed to parse the capture header");
+
+    let mut replay = Replay::new(adapter);
+    let mut num_calls = 0;
+    for call in reader {
+        match call {
+            Ok(call) => {
+                debug!("{:?}", call);
+                replay.execute(call);
+                num_calls += 1;
+            }
+            Err(e) => {
+                println!("\tFailed to read call {}: {}", num_calls, e);
+                return 1
+            }
+        }
+    }
+    println!("\tReplayed {} calls", num_calls);
+    0
+}
+
+fn main() {
+    use std::{env, process};
+
+    #[cfg(feature = "env_logger")]
+    env_logger::init();
+    let mut num_failures = 0;
+
+    let mut args = env::args().skip(1);
+    let path = match args.next() {
+        Some(path) => path,
+        None => {
+            println!("Call with the path of the capture to replay");
+            return
+        }
+    };
+    let mut adapter = 0;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--adapter" => {
+                adapter = args
+                    .next()
+                    .and_then(|index| index.parse().ok())
+                    .expect("--adapter expects the index of an adapter");
+            }
+            other => {
+                println!("Unknown argument '{}'", other);
+                process::exit(1);
+            }
+        }
+    }
+    let path = Path::new(&path);
+
+    #[cfg(feature = "empty")]
+    {
+        println!("Replaying on CPU:");
+        let instance = gfx_backend_empty::Instance::create("gfx-replay", 1);
+        num_failures += run(instance, path, adapter);
+    }
+    #[cfg(feature = "vulkan")]
+    {
+        println!("Replaying on Vulkan:");
+        let instance = gfx_backend_vulkan::Instance::create("gfx-replay", 1);
+        num_failures += run(instance, path, adapter);
+    }
+    #[cfg(feature = "dx12")]
+    {
+        println!("Replaying on DX12:");
+        let instance = gfx_backend_dx12::Instance::create("gfx-replay", 1);
+        num_failures += run(instance, path, adapter);
+    }
+    #[cfg(feature = "metal")]
+    {
+        println!("Replaying on Metal:");
+        let instance = gfx_backend_metal::Instance::create("gfx-replay", 1);
+        num_failures += run(instance, path, adapter);
+    }
+    #[cfg(feature = "gl")]
+    {
+        use gfx_backend_gl::glutin;
+        println!("Replaying on GL:");
+        let events_loop = glutin::EventsLoop::new();
+        let window = glutin::GlWindow::new(
+            glutin::WindowBuilder::new().with_visibility(false),
+            glutin::ContextBuilder::new()
+                .with_gl_profile(glutin::GlProfile::Core),
+            &events_loop,
+            ).unwrap();
+        let instance = gfx_backend_gl::Surface::from_window(window);
+        num_failures += run(instance, path, adapter);
+    }
+    let _ = (path, adapter);
+    num_failures += 0; // mark as mutated
+    process::exit(num_failures as _);
+}
@@ -0,0 +1,1232 @@
+//! Execution of captured calls on a backend.
+
+use std::iter;
+use std::ops::Range;
+use std::ptr;
+
+use hal::{self, buffer, command as com, format, image, memory, pass, pso, window};
+use hal::{Device, PhysicalDevice, QueueFamily};
+use hal::backend::FastHashMap;
+use hal::command::RawCommandBuffer;
+use hal::device::WaitFor;
+use hal::pool::RawCommandPool;
+use hal::pso::DescriptorPool;
+use hal::queue::{QueueFamilyId, RawCommandQueue, RawSparseBinding, RawSubmission};
+
+use capture::format::{
+    BasePipeline, Backbuffer, Barrier, Call, Command, Descriptor, EntryPoint, Id, SparseBind,
+};
+
+
+fn get<'a, T>(map: &'a FastHashMap<Id, T>, id: Id, kind: &str) -> &'a T {
+    match map.get(&id) {
+        Some(object) => object,
+        None => panic!("{} {} is not alive", kind, id),
+    }
+}
+
+fn get_mut<'a, T>(map: &'a mut FastHashMap<Id, T>, id: Id, kind: &str) -> &'a mut T {
+    match map.get_mut(&id) {
+        Some(object) => object,
+        None => panic!("{} {} is not alive", kind, id),
+    }
+}
+
+fn take<T>(map: &mut FastHashMap<Id, T>, id: Id, kind: &str) -> T {
+    match map.remove(&id) {
+        Some(object) => object,
+        None => panic!("{} {} is not alive", kind, id),
+    }
+}
+
+fn entry_point<'a, B: hal::Backend>(
+    entry: &'a EntryPoint, modules: &'a FastHashMap<Id, B::ShaderModule>
+) -> pso::EntryPoint<'a, B> {
+    pso::EntryPoint {
+        entry: &entry.entry,
+        module: get(modules, entry.module, "ShaderModule"),
+        specialization: &entry.specialization,
+    }
+}
+
+fn base_pipeline<'a, P>(parent: &BasePipeline, pipelines: &'a FastHashMap<Id, P>) -> pso::BasePipeline<'a, P> {
+    match *parent {
+        BasePipeline::Pipeline(id) => pso::BasePipeline::Pipeline(get(pipelines, id, "Pipeline")),
+        BasePipeline::Index(index) => pso::BasePipeline::Index(index),
+        BasePipeline::None => pso::BasePipeline::None,
+    }
+}
+
+/// Captured memory allocation.
+///
+/// The memory types accepted by a resource differ between devices, so the
+/// allocation is delayed until the memory is first bound or mapped, at which
+/// point the type can be chosen among the ones accepted by that resource.
+/// Resources bound later to the same memory are expected to accept it too.
+struct Memory<B: hal::Backend> {
+    raw: Option<(B::Memory, hal::MemoryTypeId)>,
+    properties: memory::Properties,
+    size: u64,
+    /// Pointer to the mapped range, and the range itself.
+    mapping: Option<(*mut u8, Range<Option<u64>>)>,
+}
+
+impl<B: hal::Backend> Memory<B> {
+    fn raw(&self) -> &B::Memory {
+        &self.raw.as_ref().expect("Memory is used before being allocated").0
+    }
+}
+
+/// Offscreen images standing in for the ones of a captured swapchain.
+struct Swapchain<B: hal::Backend> {
+    config: window::SwapchainConfig,
+    images: Vec<(Id, B::Memory)>,
+    /// Framebuffer provided by the swapchain instead of images, created on first use.
+    framebuffer: Option<(Id, Option<(B::Memory, B::Image, B::ImageView)>)>,
+}
+
+/// State of a replay, mapping the objects of the capture to the ones of the backend.
+pub struct Replay<B: hal::Backend> {
+    adapter: hal::Adapter<B>,
+    memory_types: Vec<hal::MemoryType>,
+    device: Option<B::Device>,
+    families: FastHashMap<usize, QueueFamilyId>,
+    queues: FastHashMap<Id, B::CommandQueue>,
+    memories: FastHashMap<Id, Memory<B>>,
+    command_pools: FastHashMap<Id, B::CommandPool>,
+    command_buffers: FastHashMap<Id, B::CommandBuffer>,
+    render_passes: FastHashMap<Id, B::RenderPass>,
+    pipeline_layouts: FastHashMap<Id, B::PipelineLayout>,
+    pipeline_caches: FastHashMap<Id, B::PipelineCache>,
+    graphics_pipelines: FastHashMap<Id, B::GraphicsPipeline>,
+    compute_pipelines: FastHashMap<Id, B::ComputePipeline>,
+    framebuffers: FastHashMap<Id, B::Framebuffer>,
+    shader_modules: FastHashMap<Id, B::ShaderModule>,
+    unbound_buffers: FastHashMap<Id, B::UnboundBuffer>,
+    buffers: FastHashMap<Id, B::Buffer>,
+    buffer_views: FastHashMap<Id, B::BufferView>,
+    unbound_images: FastHashMap<Id, B::UnboundImage>,
+    images: FastHashMap<Id, B::Image>,
+    image_views: FastHashMap<Id, B::ImageView>,
+    samplers: FastHashMap<Id, B::Sampler>,
+    descriptor_pools: FastHashMap<Id, B::DescriptorPool>,
+    descriptor_set_layouts: FastHashMap<Id, B::DescriptorSetLayout>,
+    descriptor_sets: FastHashMap<Id, B::DescriptorSet>,
+    semaphores: FastHashMap<Id, B::Semaphore>,
+    fences: FastHashMap<Id, B::Fence>,
+    events: FastHashMap<Id, B::Event>,
+    query_pools: FastHashMap<Id, B::QueryPool>,
+    swapchains: FastHashMap<Id, Swapchain<B>>,
+}
+
+impl<B: hal::Backend> Replay<B> {
+    pub fn new(adapter: hal::Adapter<B>) -> Self {
+        Replay {
+            memory_types: adapter.physical_device.memory_properties().memory_types,
+            adapter,
+            device: None,
+            families: FastHashMap::default(),
+            queues: FastHashMap::default(),
+            memories: FastHashMap::default(),
+            command_pools: FastHashMap::default(),
+            command_buffers: FastHashMap::default(),
+            render_passes: FastHashMap::default(),
+            pipeline_layouts: FastHashMap::default(),
+            pipeline_caches: FastHashMap::default(),
+            graphics_pipelines: FastHashMap::default(),
+            compute_pipelines: FastHashMap::default(),
+            framebuffers: FastHashMap::default(),
+            shader_modules: FastHashMap::default(),
+            unbound_buffers: FastHashMap::default(),
+            buffers: FastHashMap::default(),
+            buffer_views: FastHashMap::default(),
+            unbound_images: FastHashMap::default(),
+            images: FastHashMap::default(),
+            image_views: FastHashMap::default(),
+            samplers: FastHashMap::default(),
+            descriptor_pools: FastHashMap::default(),
+            descriptor_set_layouts: FastHashMap::default(),
+            descriptor_sets: FastHashMap::default(),
+            semaphores: FastHashMap::default(),
+            fences: FastHashMap::default(),
+            events: FastHashMap::default(),
+            query_pools: FastHashMap::default(),
+            swapchains: FastHashMap::default(),
+        }
+    }
+
+    fn device(&self) -> &B::Device {
+        self.device.as_ref().expect("The capture doesn't start by opening a device")
+    }
+
+    /// Find a memory type with at least the `properties` captured on another device.
+    fn memory_type(&self, properties: memory::Properties, type_mask: u64) -> hal::MemoryTypeId {
+        let compatible = |&(id, ty): &(usize, &hal::MemoryType)| {
+            type_mask & (1 << id) != 0 && ty.properties.contains(properties)
+        };
+        match self.memory_types.iter().enumerate().find(compatible) {
+            Some((id, _)) => hal::MemoryTypeId(id),
+            None => {
+                warn!("No memory type has the properties {:?}, falling back to the first one", properties);
+                hal::MemoryTypeId(0)
+            }
+        }
+    }
+
+    /// Allocate the captured memory on first use, from a type in `type_mask`.
+    fn allocate_memory(&mut self, id: Id, type_mask: u64) {
+        let (properties, size) = match *get(&self.memories, id, "Memory") {
+            Memory { raw: Some((_, memory_type)), .. } => {
+                if type_mask & (1 << memory_type.0) == 0 {
+                    warn!("Memory {} has the type {:?}, not accepted by a resource bound to it", id, memory_type);
+                }
+                return
+            }
+            Memory { properties, size, .. } => (properties, size),
+        };
+        let memory_type = self.memory_type(properties, type_mask);
+        let raw = self.device()
+            .allocate_memory(memory_type, size)
+            .expect("Failed to allocate memory");
+        get_mut(&mut self.memories, id, "Memory").raw = Some((raw, memory_type));
+    }
+
+    /// Submit no work, only to wait for and signal synchronization primitives.
+    fn submit_sync(&mut self, wait: &[Id], signal: &[Id], fence: Option<Id>) {
+        let semaphores = &self.semaphores;
+        let wait_semaphores = wait
+            .iter()
+            .map(|&id| (get(semaphores, id, "Semaphore"), pso::PipelineStage::BOTTOM_OF_PIPE))
+            .collect::<Vec<_>>();
+        let signal_semaphores = signal
+            .iter()
+            .map(|&id| get(semaphores, id, "Semaphore"))
+            .collect::<Vec<_>>();
+        let fences = &self.fences;
+        let fence = fence.map(|id| get(fences, id, "Fence"));
+        let queue = self.queues.values_mut().next().expect("No queue to submit to");
+        let submission = RawSubmission {
+            cmd_buffers: iter::empty::<&B::CommandBuffer>(),
+            wait_semaphores: &wait_semaphores,
+            signal_semaphores: &signal_semaphores,
+        };
+        unsafe {
+            queue.submit_raw(submission, fence);
+        }
+    }
+
+    /// Create an offscreen image for a swapchain, bound to its own memory.
+    fn create_backbuffer_image(&self, config: &window::SwapchainConfig) -> (B::Memory, B::Image) {
+        let device = self.device();
+        let kind = image::Kind::D2(config.extent.width, config.extent.height, config.image_layers, 1);
+        let unbound = device
+            .create_image(
+                kind, 1, config.format, image::Tiling::Optimal,
+                config.image_usage, image::StorageFlags::empty(),
+            )
+            .expect("Failed to create a backbuffer image");
+        let requirements = device.get_image_requirements(&unbound);
+        let memory_type = self.memory_type(memory::Properties::DEVICE_LOCAL, requirements.type_mask);
+        let memory = device
+            .allocate_memory(memory_type, requirements.size)
+            .expect("Failed to allocate a backbuffer image");
+        let image = device
+            .bind_image_memory(&memory, 0, unbound)
+            .expect("Failed to bind a backbuffer image");
+        (memory, image)
+    }
+
+    /// Create the framebuffer standing in for the one provided by a swapchain.
+    fn prepare_backbuffer_framebuffer(&mut self, framebuffer: Id, render_pass: Id) {
+        if self.framebuffers.contains_key(&framebuffer) {
+            return;
+        }
+        let config = match self.swapchains
+            .values()
+            .find(|swapchain| match swapchain.framebuffer {
+                Some((id, _)) => id == framebuffer,
+                None => false,
+            }) {
+            Some(swapchain) => swapchain.config.clone(),
+            None => return,
+        };
+
+        let (memory, image) = self.create_backbuffer_image(&config);
+        let range = image::SubresourceRange {
+            aspects: format::Aspects::COLOR,
+            levels: 0 .. 1,
+            layers: 0 .. config.image_layers,
+        };
+        let device = self.device();
+        let view = device
+            .create_image_view(&image, image::ViewKind::D2, config.format, format::Swizzle::NO, range)
+            .expect("Failed to create a backbuffer view");
+        let raw = device
+            .create_framebuffer(
+                get(&self.render_passes, render_pass, "RenderPass"),
+                iter::once(&view),
+                image::Extent {
+                    width: config.extent.width,
+                    height: config.extent.height,
+                    depth: 1,
+                },
+            )
+            .expect("Failed to create a backbuffer framebuffer");
+
+        self.framebuffers.insert(framebuffer, raw);
+        for swapchain in self.swapchains.values_mut() {
+            if let Some((id, ref mut resources)) = swapchain.framebuffer {
+                if id == framebuffer {
+                    *resources = Some((memory, image, view));
+                    break;
+                }
+            }
+        }
+    }
+
+    fn destroy_swapchain(&mut self, id: Id) {
+        let swapchain = take(&mut self.swapchains, id, "Swapchain");
+        let device = self.device.as_ref().unwrap();
+        for (image, memory) in swapchain.images {
+            device.destroy_image(take(&mut self.images, image, "Image"));
+            device.free_memory(memory);
+        }
+        if let Some((framebuffer, resources)) = swapchain.framebuffer {
+            if let Some((memory, image, view)) = resources {
+                device.destroy_framebuffer(take(&mut self.framebuffers, framebuffer, "Framebuffer"));
+                device.destroy_image_view(view);
+                device.destroy_image(image);
+                device.free_memory(memory);
+            }
+        }
+    }
+
+    fn open_device(&mut self, families: Vec<::capture::format::QueueFamily>, features: hal::Features) {
+        // Queue families differ between devices, so the captured ones are matched by type,
+        // falling back to a general family.
+        let mut requests: Vec<(usize, Vec<hal::QueuePriority>)> = Vec::new();
+        let mut targets = Vec::new();
+        for family in &families {
+            let index = self.adapter.queue_families
+                .iter()
+                .position(|f| f.queue_type() == family.queue_type)
+                .or_else(|| self.adapter.queue_families
+                    .iter()
+                    .position(|f| f.queue_type() == hal::QueueType::General))
+                .expect("No queue family is compatible with the captured ones");
+            match requests.iter_mut().find(|&&mut (i, _)| i == index) {
+                Some(&mut (_, ref mut priorities)) => priorities.extend(&family.priorities),
+                None => requests.push((index, family.priorities.clone())),
+            }
+            targets.push(index);
+            self.families.insert(family.id, self.adapter.queue_families[index].id());
+        }
+
+        let raw_families = requests
+            .iter()
+            .map(|&(index, ref priorities)| (&self.adapter.queue_families[index], priorities.as_slice()))
+            .collect::<Vec<_>>();
+        let hal::Gpu { device, mut queues } = self.adapter.physical_device
+            .open(&raw_families, features)
+            .expect("Failed to open the device");
+
+        let mut raw_queues = FastHashMap::default();
+        for &(index, _) in &requests {
+            let id = self.adapter.queue_families[index].id();
+            raw_queues.insert(index, queues.take_raw(id).unwrap_or_default());
+        }
+        for (family, index) in families.iter().zip(targets) {
+            let available = raw_queues.get_mut(&index).unwrap();
+            for &id in &family.queues {
+                self.queues.insert(id, available.remove(0));
+            }
+        }
+        self.device = Some(device);
+    }
+
+    /// Execute a captured call.
+    pub fn execute(&mut self, call: Call) {
+        match call {
+            Call::OpenDevice { adapter, families, features } => {
+                info!("Replaying a capture made on {:?} with {:?}", adapter, self.adapter.info);
+                self.open_device(families, features);
+            }
+
+            Call::AllocateMemory { memory, properties, size } => {
+                self.memories.insert(memory, Memory { raw: None, properties, size, mapping: None });
+            }
+            Call::FreeMemory(id) => {
+                let memory = take(&mut self.memories, id, "Memory");
+                if let Some((raw, _)) = memory.raw {
+                    self.device().free_memory(raw);
+                }
+            }
+            Call::MapMemory { memory, range } => {
+                self.allocate_memory(memory, !0);
+                let device = self.device.as_ref().unwrap();
+                let memory = get_mut(&mut self.memories, memory, "Memory");
+                let ptr = device
+                    .map_memory(memory.raw(), (range.start, range.end))
+                    .expect("Failed to map memory");
+                memory.mapping = Some((ptr, range));
+            }
+            Call::WriteMemory { memory, offset, data } => {
+                let device = self.device.as_ref().unwrap();
+                let memory = get(&self.memories, memory, "Memory");
+                match memory.mapping {
+                    Some((ptr, ref range)) => unsafe {
+                        let start = range.start.unwrap_or(0);
+                        ptr::copy_nonoverlapping(data.as_ptr(), ptr.offset((offset - start) as isize), data.len());
+                        device.flush_mapped_memory_ranges(iter::once((memory.raw(), (range.start, range.end))));
+                    },
+                    None => warn!("Contents written to an unmapped memory"),
+                }
+            }
+            Call::UnmapMemory(id) => {
+                let device = self.device.as_ref().unwrap();
+                let memory = get_mut(&mut self.memories, id, "Memory");
+                memory.mapping = None;
+                device.unmap_memory(memory.raw());
+            }
+
+            Call::CreateCommandPool { pool, family, flags } => {
+                let family = self.families[&family];
+                let raw = self.device().create_command_pool(family, flags);
+                self.command_pools.insert(pool, raw);
+            }
+            Call::ResetCommandPool(id) => {
+                get_mut(&mut self.command_pools, id, "CommandPool").reset();
+            }
+            Call::AllocateCommandBuffers { pool, buffers, level } => {
+                let pool = get_mut(&mut self.command_pools, pool, "CommandPool");
+                let raw = pool.allocate(buffers.len(), level);
+                self.command_buffers.extend(buffers.into_iter().zip(raw));
+            }
+            Call::FreeCommandBuffers { pool, buffers } => {
+                let raw = buffers
+                    .into_iter()
+                    .map(|id| take(&mut self.command_buffers, id, "CommandBuffer"))
+                    .collect();
+                unsafe {
+                    get_mut(&mut self.command_pools, pool, "CommandPool").free(raw);
+                }
+            }
+            Call::DestroyCommandPool(id) => {
+                let pool = take(&mut self.command_pools, id, "CommandPool");
+                self.device().destroy_command_pool(pool);
+            }
+            Call::Command(id, command) => self.execute_command(id, command),
+
+            Call::CreateRenderPass { render_pass, attachments, subpasses, dependencies } => {
+                let subpasses = subpasses
+                    .iter()
+                    .map(|sp| pass::SubpassDesc {
+                        colors: &sp.colors,
+                        depth_stencil: sp.depth_stencil.as_ref(),
+                        inputs: &sp.inputs,
+                        resolves: &sp.resolves,
+                        preserves: &sp.preserves,
+                    })
+                    .collect::<Vec<_>>();
+                let raw = self.device().create_render_pass(&attachments, &subpasses, &dependencies);
+                self.render_passes.insert(render_pass, raw);
+            }
+            Call::DestroyRenderPass(id) => {
+                let render_pass = take(&mut self.render_passes, id, "RenderPass");
+                self.device().destroy_render_pass(render_pass);
+            }
+            Call::CreatePipelineLayout { layout, set_layouts, push_constants } => {
+                let raw = self.device().create_pipeline_layout(
+                    set_layouts.iter().map(|&id| get(&self.descriptor_set_layouts, id, "DescriptorSetLayout")),
+                    &push_constants,
+                );
+                self.pipeline_layouts.insert(layout, raw);
+            }
+            Call::DestroyPipelineLayout(id) => {
+                let layout = take(&mut self.pipeline_layouts, id, "PipelineLayout");
+                self.device().destroy_pipeline_layout(layout);
+            }
+            Call::CreatePipelineCache(id) => {
+                let raw = self.device().create_pipeline_cache(None);
+                self.pipeline_caches.insert(id, raw);
+            }
+            Call::MergePipelineCaches { target, sources } => {
+                self.device().merge_pipeline_caches(
+                    get(&self.pipeline_caches, target, "PipelineCache"),
+                    sources.iter().map(|&id| get(&self.pipeline_caches, id, "PipelineCache")),
+                );
+            }
+            Call::DestroyPipelineCache(id) => {
+                let cache = take(&mut self.pipeline_caches, id, "PipelineCache");
+                self.device().destroy_pipeline_cache(cache);
+            }
+            Call::CreateGraphicsPipelines { pipelines, descs, cache } => {
+                let raw = {
+                    let modules = &self.shader_modules;
+                    let raw_descs = descs.iter().map(|desc| pso::GraphicsPipelineDesc {
+                        shaders: pso::GraphicsShaderSet {
+                            vertex: entry_point(&desc.vertex, modules),
+                            hull: desc.hull.as_ref().map(|entry| entry_point(entry, modules)),
+                            domain: desc.domain.as_ref().map(|entry| entry_point(entry, modules)),
+                            geometry: desc.geometry.as_ref().map(|entry| entry_point(entry, modules)),
+                            fragment: desc.fragment.as_ref().map(|entry| entry_point(entry, modules)),
+                        },
+                        rasterizer: desc.rasterizer.clone(),
+                        vertex_buffers: desc.vertex_buffers.clone(),
+                        attributes: desc.attributes.clone(),
+                        input_assembler: desc.input_assembler.clone(),
+                        blender: desc.blender.clone(),
+                        depth_stencil: desc.depth_stencil,
+                        multisampling: desc.multisampling.clone(),
+                        baked_states: desc.baked_states.clone(),
+                        layout: get(&self.pipeline_layouts, desc.layout, "PipelineLayout"),
+                        subpass: pass::Subpass {
+                            index: desc.subpass,
+                            main_pass: get(&self.render_passes, desc.render_pass, "RenderPass"),
+                        },
+                        flags: desc.flags,
+                        parent: base_pipeline(&desc.parent, &self.graphics_pipelines),
+                    });
+                    let cache = cache.map(|id| get(&self.pipeline_caches, id, "PipelineCache"));
+                    self.device().create_graphics_pipelines(raw_descs, cache)
+                };
+                for (id, result) in pipelines.into_iter().zip(raw) {
+                    match (id, result) {
+                        (Some(id), Ok(pipeline)) => {
+                            self.graphics_pipelines.insert(id, pipeline);
+                        }
+                        (Some(id), Err(e)) => error!("Failed to create the graphics pipeline {}: {:?}", id, e),
+                        (None, Ok(pipeline)) => self.device().destroy_graphics_pipeline(pipeline),
+                        (None, Err(_)) => {}
+                    }
+                }
+            }
+            Call::DestroyGraphicsPipeline(id) => {
+                let pipeline = take(&mut self.graphics_pipelines, id, "GraphicsPipeline");
+                self.device().destroy_graphics_pipeline(pipeline);
+            }
+            Call::CreateComputePipelines { pipelines, descs, cache } => {
+                let raw = {
+                    let raw_descs = descs.iter().map(|desc| pso::ComputePipelineDesc {
+                        shader: entry_point(&desc.shader, &self.shader_modules),
+                        layout: get(&self.pipeline_layouts, desc.layout, "PipelineLayout"),
+                        flags: desc.flags,
+                        parent: base_pipeline(&desc.parent, &self.compute_pipelines),
+                    });
+                    let cache = cache.map(|id| get(&self.pipeline_caches, id, "PipelineCache"));
+                    self.device().create_compute_pipelines(raw_descs, cache)
+                };
+                for (id, result) in pipelines.into_iter().zip(raw) {
+                    match (id, result) {
+                        (Some(id), Ok(pipeline)) => {
+                            self.compute_pipelines.insert(id, pipeline);
+                        }
+                        (Some(id), Err(e)) => error!("Failed to create the compute pipeline {}: {:?}", id, e),
+                        (None, Ok(pipeline)) => self.device().destroy_compute_pipeline(pipeline),
+                        (None, Err(_)) => {}
+                    }
+                }
+            }
+            Call::DestroyComputePipeline(id) => {
+                let pipeline = take(&mut self.compute_pipelines, id, "ComputePipeline");
+                self.device().destroy_compute_pipeline(pipeline);
+            }
+            Call::CreateFramebuffer { framebuffer, render_pass, attachments, extent } => {
+                let raw = self.device()
+                    .create_framebuffer(
+                        get(&self.render_passes, render_pass, "RenderPass"),
+                        attachments.iter().map(|&id| get(&self.image_views, id, "ImageView")),
+                        extent,
+                    )
+                    .expect("Failed to create a framebuffer");
+                self.framebuffers.insert(framebuffer, raw);
+            }
+            Call::DestroyFramebuffer(id) => {
+                let framebuffer = take(&mut self.framebuffers, id, "Framebuffer");
+                self.device().destroy_framebuffer(framebuffer);
+            }
+            Call::CreateShaderModule { module, spirv } => {
+                let raw = self.device()
+                    .create_shader_module(&spirv)
+                    .expect("Failed to create a shader module");
+                self.shader_modules.insert(module, raw);
+            }
+            Call::DestroyShaderModule(id) => {
+                let module = take(&mut self.shader_modules, id, "ShaderModule");
+                self.device().destroy_shader_module(module);
+            }
+
+            Call::CreateBuffer { buffer, size, usage } => {
+                let raw = self.device()
+                    .create_buffer(size, usage)
+                    .expect("Failed to create a buffer");
+                self.unbound_buffers.insert(buffer, raw);
+            }
+            Call::BindBufferMemory { buffer, memory, offset } => {
+                let unbound = take(&mut self.unbound_buffers, buffer, "UnboundBuffer");
+                let type_mask = self.device().get_buffer_requirements(&unbound).type_mask;
+                self.allocate_memory(memory, type_mask);
+                let raw = self.device()
+                    .bind_buffer_memory(get(&self.memories, memory, "Memory").raw(), offset, unbound)
+                    .expect("Failed to bind a buffer");
+                self.buffers.insert(buffer, raw);
+            }
+            Call::CreateSparseBuffer { buffer, size, usage, sparse } => {
+                let raw = self.device()
+                    .create_sparse_buffer(size, usage, sparse)
+                    .expect("Failed to create a sparse buffer");
+                self.buffers.insert(buffer, raw);
+            }
+            Call::DestroyBuffer(id) => {
+                let buffer = take(&mut self.buffers, id, "Buffer");
+                self.device().destroy_buffer(buffer);
+            }
+            Call::CreateBufferView { view, buffer, format, range } => {
+                let raw = self.device()
+                    .create_buffer_view(get(&self.buffers, buffer, "Buffer"), format, (range.start, range.end))
+                    .expect("Failed to create a buffer view");
+                self.buffer_views.insert(view, raw);
+            }
+            Call::DestroyBufferView(id) => {
+                let view = take(&mut self.buffer_views, id, "BufferView");
+                self.device().destroy_buffer_view(view);
+            }
+            Call::CreateImage { image, kind, mip_levels, format, tiling, usage, storage_flags } => {
+                let raw = self.device()
+                    .create_image(kind, mip_levels, format, tiling, usage, storage_flags)
+                    .expect("Failed to create an image");
+                self.unbound_images.insert(image, raw);
+            }
+            Call::BindImageMemory { image, memory, offset } => {
+                let unbound = take(&mut self.unbound_images, image, "UnboundImage");
+                let type_mask = self.device().get_image_requirements(&unbound).type_mask;
+                self.allocate_memory(memory, type_mask);
+                let raw = self.device()
+                    .bind_image_memory(get(&self.memories, memory, "Memory").raw(), offset, unbound)
+                    .expect("Failed to bind an image");
+                self.images.insert(image, raw);
+            }
+            Call::CreateSparseImage { image, kind, mip_levels, format, usage, storage_flags, sparse } => {
+                let raw = self.device()
+                    .create_sparse_image(kind, mip_levels, format, usage, storage_flags, sparse)
+                    .expect("Failed to create a sparse image");
+                self.images.insert(image, raw);
+            }
+            Call::DestroyImage(id) => {
+                let image = take(&mut self.images, id, "Image");
+                self.device().destroy_image(image);
+            }
+            Call::CreateImageView { view, image, kind, format, swizzle, range } => {
+                let raw = self.device()
+                    .create_image_view(get(&self.images, image, "Image"), kind, format, swizzle, range)
+                    .expect("Failed to create an image view");
+                self.image_views.insert(view, raw);
+            }
+            Call::DestroyImageView(id) => {
+                let view = take(&mut self.image_views, id, "ImageView");
+                self.device().destroy_image_view(view);
+            }
+            Call::CreateSampler { sampler, info } => {
+                let raw = self.device().create_sampler(info);
+                self.samplers.insert(sampler, raw);
+            }
+            Call::DestroySampler(id) => {
+                let sampler = take(&mut self.samplers, id, "Sampler");
+                self.device().destroy_sampler(sampler);
+            }
+
+            Call::CreateDescriptorPool { pool, max_sets, ranges } => {
+                let raw = self.device().create_descriptor_pool(max_sets, &ranges);
+                self.descriptor_pools.insert(pool, raw);
+            }
+            Call::AllocateDescriptorSet { pool, set, layout } => {
+                let raw = get_mut(&mut self.descriptor_pools, pool, "DescriptorPool")
+                    .allocate_set(get(&self.descriptor_set_layouts, layout, "DescriptorSetLayout"))
+                    .expect("Failed to allocate a descriptor set");
+                self.descriptor_sets.insert(set, raw);
+            }
+            Call::FreeDescriptorSets { pool, sets } => {
+                let raw = sets
+                    .into_iter()
+                    .map(|id| take(&mut self.descriptor_sets, id, "DescriptorSet"))
+                    .collect::<Vec<_>>();
+                get_mut(&mut self.descriptor_pools, pool, "DescriptorPool").free_sets(raw);
+            }
+            Call::ResetDescriptorPool(id) => {
+                get_mut(&mut self.descriptor_pools, id, "DescriptorPool").reset();
+            }
+            Call::DestroyDescriptorPool(id) => {
+                let pool = take(&mut self.descriptor_pools, id, "DescriptorPool");
+                self.device().destroy_descriptor_pool(pool);
+            }
+            Call::CreateDescriptorSetLayout { layout, bindings, immutable_samplers } => {
+                let raw = self.device().create_descriptor_set_layout(
+                    &bindings,
+                    immutable_samplers.iter().map(|&id| get(&self.samplers, id, "Sampler")),
+                );
+                self.descriptor_set_layouts.insert(layout, raw);
+            }
+            Call::DestroyDescriptorSetLayout(id) => {
+                let layout = take(&mut self.descriptor_set_layouts, id, "DescriptorSetLayout");
+                self.device().destroy_descriptor_set_layout(layout);
+            }
+            Call::WriteDescriptorSets(writes) => {
+                let writes = writes.iter().map(|write| pso::DescriptorSetWrite {
+                    set: get(&self.descriptor_sets, write.set, "DescriptorSet"),
+                    binding: write.binding,
+                    array_offset: write.array_offset,
+                    descriptors: write.descriptors
+                        .iter()
+                        .map(|descriptor| match *descriptor {
+                            Descriptor::Sampler(id) => pso::Descriptor::Sampler(get(&self.samplers, id, "Sampler")),
+                            Descriptor::Image(id, layout) => {
+                                pso::Descriptor::Image(get(&self.image_views, id, "ImageView"), layout)
+                            }
+                            Descriptor::CombinedImageSampler(view, layout, sampler) => {
+                                pso::Descriptor::CombinedImageSampler(
+                                    get(&self.image_views, view, "ImageView"),
+                                    layout,
+                                    get(&self.samplers, sampler, "Sampler"),
+                                )
+                            }
+                            Descriptor::Buffer(id, ref range) => {
+                                pso::Descriptor::Buffer(get(&self.buffers, id, "Buffer"), range.clone())
+                            }
+                            Descriptor::UniformTexelBuffer(id) => {
+                                pso::Descriptor::UniformTexelBuffer(get(&self.buffer_views, id, "BufferView"))
+                            }
+                            Descriptor::StorageTexelBuffer(id) => {
+                                pso::Descriptor::StorageTexelBuffer(get(&self.buffer_views, id, "BufferView"))
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                });
+                self.device().write_descriptor_sets(writes);
+            }
+            Call::CopyDescriptorSets(copies) => {
+                let copies = copies
+                    .iter()
+                    .map(|copy| pso::DescriptorSetCopy {
+                        src_set: get(&self.descriptor_sets, copy.src_set, "DescriptorSet"),
+                        src_binding: copy.src_binding,
+                        src_array_offset: copy.src_array_offset,
+                        dst_set: get(&self.descriptor_sets, copy.dst_set, "DescriptorSet"),
+                        dst_binding: copy.dst_binding,
+                        dst_array_offset: copy.dst_array_offset,
+                        count: copy.count,
+                    })
+                    .collect::<Vec<_>>();
+                self.device().copy_descriptor_sets(copies);
+            }
+
+            Call::CreateSemaphore(id) => {
+                let raw = self.device().create_semaphore();
+                self.semaphores.insert(id, raw);
+            }
+            Call::DestroySemaphore(id) => {
+                let semaphore = take(&mut self.semaphores, id, "Semaphore");
+                self.device().destroy_semaphore(semaphore);
+            }
+            Call::CreateFence { fence, signaled } => {
+                let raw = self.device().create_fence(signaled);
+                self.fences.insert(fence, raw);
+            }
+            Call::ResetFences(ids) => {
+                self.device().reset_fences(ids.iter().map(|&id| get(&self.fences, id, "Fence")));
+            }
+            Call::WaitForFences(ids) => {
+                let fences = ids.iter().map(|&id| get(&self.fences, id, "Fence"));
+                if !self.device().wait_for_fences(fences, WaitFor::All, !0) {
+                    error!("Failed to wait for the fences {:?}", ids);
+                }
+            }
+            Call::DestroyFence(id) => {
+                let fence = take(&mut self.fences, id, "Fence");
+                self.device().destroy_fence(fence);
+            }
+            Call::CreateEvent(id) => {
+                let raw = self.device().create_event();
+                self.events.insert(id, raw);
+            }
+            Call::SetEvent(id) => {
+                self.device().set_event(get(&self.events, id, "Event"));
+            }
+            Call::ResetEvent(id) => {
+                self.device().reset_event(get(&self.events, id, "Event"));
+            }
+            Call::DestroyEvent(id) => {
+                let event = take(&mut self.events, id, "Event");
+                self.device().destroy_event(event);
+            }
+            Call::CreateQueryPool { pool, ty, count } => {
+                let raw = self.device().create_query_pool(ty, count)
+                    .expect("Failed to create a query pool");
+                self.query_pools.insert(pool, raw);
+            }
+            Call::DestroyQueryPool(id) => {
+                let pool = take(&mut self.query_pools, id, "QueryPool");
+                self.device().destroy_query_pool(pool);
+            }
+
+            Call::CreateSwapchain { swapchain, config, old_swapchain, backbuffer } => {
+                if let Some(old) = old_swapchain {
+                    self.destroy_swapchain(old);
+                }
+                let mut images = Vec::new();
+                let framebuffer = match backbuffer {
+                    Backbuffer::Images(ids) => {
+                        for id in ids {
+                            let (memory, image) = self.create_backbuffer_image(&config);
+                            self.images.insert(id, image);
+                            images.push((id, memory));
+                        }
+                        None
+                    }
+                    Backbuffer::Framebuffer(id) => Some((id, None)),
+                };
+                self.swapchains.insert(swapchain, Swapchain { config, images, framebuffer });
+            }
+            Call::DestroySwapchain(id) => self.destroy_swapchain(id),
+            Call::AcquireImage { semaphore, fence, .. } => {
+                // Backbuffer images are always available, the primitives only need to be signaled.
+                let signal = semaphore.into_iter().collect::<Vec<_>>();
+                self.submit_sync(&[], &signal, fence);
+            }
+            Call::Present { wait_semaphores, .. } => {
+                self.submit_sync(&wait_semaphores, &[], None);
+            }
+
+            Call::Submit { queue, command_buffers, wait_semaphores, signal_semaphores, fence } => {
+                let semaphores = &self.semaphores;
+                let command_buffers_map = &self.command_buffers;
+                let wait_semaphores = wait_semaphores
+                    .iter()
+                    .map(|&(id, stage)| (get(semaphores, id, "Semaphore"), stage))
+                    .collect::<Vec<_>>();
+                let signal_semaphores = signal_semaphores
+                    .iter()
+                    .map(|&id| get(semaphores, id, "Semaphore"))
+                    .collect::<Vec<_>>();
+                let submission = RawSubmission {
+                    cmd_buffers: command_buffers
+                        .iter()
+                        .map(|&id| get(command_buffers_map, id, "CommandBuffer")),
+                    wait_semaphores: &wait_semaphores,
+                    signal_semaphores: &signal_semaphores,
+                };
+                let fences = &self.fences;
+                let fence = fence.map(|id| get(fences, id, "Fence"));
+                unsafe {
+                    get_mut(&mut self.queues, queue, "CommandQueue").submit_raw(submission, fence);
+                }
+            }
+            Call::BindSparse { queue, wait_semaphores, signal_semaphores, buffers, image_opaques, images, fence } => {
+                // the requirements of sparse resources aren't known
+                let bound_memories = buffers
+                    .iter()
+                    .chain(&image_opaques)
+                    .flat_map(|&(_, ref binds)| binds.iter().filter_map(|bind| bind.memory))
+                    .chain(images.iter().flat_map(|&(_, ref binds)| binds.iter().filter_map(|bind| bind.memory)))
+                    .map(|(id, _)| id)
+                    .collect::<Vec<_>>();
+                for id in bound_memories {
+                    self.allocate_memory(id, !0);
+                }
+                let semaphores = &self.semaphores;
+                let memories = &self.memories;
+                let buffers_map = &self.buffers;
+                let images_map = &self.images;
+                let wait_semaphores = wait_semaphores
+                    .iter()
+                    .map(|&id| get(semaphores, id, "Semaphore"))
+                    .collect::<Vec<_>>();
+                let signal_semaphores = signal_semaphores
+                    .iter()
+                    .map(|&id| get(semaphores, id, "Semaphore"))
+                    .collect::<Vec<_>>();
+                let sparse_memory = |memory: Option<(Id, u64)>| {
+                    memory.map(|(id, offset)| (get(memories, id, "Memory").raw(), offset))
+                };
+                let sparse_binds = |binds: &[SparseBind]| {
+                    binds
+                        .iter()
+                        .map(|bind| memory::SparseBind {
+                            resource_offset: bind.resource_offset,
+                            size: bind.size,
+                            memory: sparse_memory(bind.memory),
+                        })
+                        .collect::<Vec<_>>()
+                };
+                let buffer_binds = buffers
+                    .iter()
+                    .map(|&(id, ref binds)| (get(buffers_map, id, "Buffer"), sparse_binds(binds)))
+                    .collect::<Vec<_>>();
+                let image_opaque_binds = image_opaques
+                    .iter()
+                    .map(|&(id, ref binds)| (get(images_map, id, "Image"), sparse_binds(binds)))
+                    .collect::<Vec<_>>();
+                let image_binds = images
+                    .iter()
+                    .map(|&(id, ref binds)| {
+                        let binds = binds
+                            .iter()
+                            .map(|bind| memory::SparseImageBind {
+                                subresource: bind.subresource,
+                                offset: bind.offset,
+                                extent: bind.extent,
+                                memory: sparse_memory(bind.memory),
+                            })
+                            .collect::<Vec<_>>();
+                        (get(images_map, id, "Image"), binds)
+                    })
+                    .collect::<Vec<_>>();
+                let buffers = buffer_binds
+                    .iter()
+                    .map(|&(buffer, ref binds)| (buffer, &binds[..]))
+                    .collect::<Vec<_>>();
+                let image_opaques = image_opaque_binds
+                    .iter()
+                    .map(|&(image, ref binds)| (image, &binds[..]))
+                    .collect::<Vec<_>>();
+                let images = image_binds
+                    .iter()
+                    .map(|&(image, ref binds)| (image, &binds[..]))
+                    .collect::<Vec<_>>();
+                let binding = RawSparseBinding {
+                    wait_semaphores: &wait_semaphores,
+                    signal_semaphores: &signal_semaphores,
+                    buffers: &buffers,
+                    image_opaques: &image_opaques,
+                    images: &images,
+                };
+                let fences = &self.fences;
+                let fence = fence.map(|id| get(fences, id, "Fence"));
+                unsafe {
+                    get_mut(&mut self.queues, queue, "CommandQueue").bind_sparse(binding, fence);
+                }
+            }
+            Call::QueueWaitIdle(id) => {
+                get(&self.queues, id, "CommandQueue")
+                    .wait_idle()
+                    .expect("Failed to wait for a queue");
+            }
+            Call::WaitIdle => {
+                self.device().wait_idle().expect("Failed to wait for the device");
+            }
+        }
+    }
+
+    fn barrier(&self, barrier: &Barrier) -> memory::Barrier<B> {
+        match *barrier {
+            Barrier::AllBuffers(ref access) => memory::Barrier::AllBuffers(access.clone()),
+            Barrier::AllImages(ref access) => memory::Barrier::AllImages(access.clone()),
+            Barrier::Buffer { ref states, target, ref families } => memory::Barrier::Buffer {
+                states: states.clone(),
+                target: get(&self.buffers, target, "Buffer"),
+                families: families.as_ref().map(|f| self.families[&f.start] .. self.families[&f.end]),
+            },
+            Barrier::Image { ref states, target, ref range, ref families } => memory::Barrier::Image {
+                states: states.clone(),
+                target: get(&self.images, target, "Image"),
+                range: range.clone(),
+                families: families.as_ref().map(|f| self.families[&f.start] .. self.families[&f.end]),
+            },
+        }
+    }
+
+    fn execute_command(&mut self, id: Id, command: Command) {
+        if let Command::BeginRenderPass { framebuffer, render_pass, .. } = command {
+            self.prepare_backbuffer_framebuffer(framebuffer, render_pass);
+        }
+
+        // The command buffer is taken out of the map while recording,
+        // so that the other objects can be looked up.
+        let mut cmd = take(&mut self.command_buffers, id, "CommandBuffer");
+        match command {
+            Command::Begin {
+                flags, subpass, framebuffer,
+                occlusion_query_enable, occlusion_query_flags, pipeline_statistics,
+            } => {
+                cmd.begin(flags, com::CommandBufferInheritanceInfo {
+                    subpass: subpass.map(|(render_pass, index)| pass::Subpass {
+                        index,
+                        main_pass: get(&self.render_passes, render_pass, "RenderPass"),
+                    }),
+                    framebuffer: framebuffer.map(|id| get(&self.framebuffers, id, "Framebuffer")),
+                    occlusion_query_enable,
+                    occlusion_query_flags,
+                    pipeline_statistics,
+                });
+            }
+            Command::Finish => cmd.finish(),
+            Command::Reset { release_resources } => cmd.reset(release_resources),
+            Command::PipelineBarrier { stages, dependencies, barriers } => {
+                let barriers = barriers.iter().map(|barrier| self.barrier(barrier));
+                cmd.pipeline_barrier(stages, dependencies, barriers);
+            }
+            Command::SetEvent { event, stages } => {
+                cmd.set_event(get(&self.events, event, "Event"), stages);
+            }
+            Command::ResetEvent { event, stages } => {
+                cmd.reset_event(get(&self.events, event, "Event"), stages);
+            }
+            Command::WaitEvents { events, stages, barriers } => {
+                let events = events.iter().map(|&id| get(&self.events, id, "Event"));
+                let barriers = barriers.iter().map(|barrier| self.barrier(barrier));
+                cmd.wait_events(events, stages, barriers);
+            }
+            Command::FillBuffer { buffer, range, data } => {
+                cmd.fill_buffer(get(&self.buffers, buffer, "Buffer"), (range.start, range.end), data);
+            }
+            Command::UpdateBuffer { buffer, offset, data } => {
+                cmd.update_buffer(get(&self.buffers, buffer, "Buffer"), offset, &data);
+            }
+            Command::ClearImage { image, layout, color, depth_stencil, ranges } => {
+                let color = com::ClearColorRaw { uint32: color };
+                cmd.clear_image(get(&self.images, image, "Image"), layout, color, depth_stencil, ranges);
+            }
+            Command::ClearAttachments { clears, rects } => cmd.clear_attachments(clears, rects),
+            Command::ResolveImage { src, src_layout, dst, dst_layout, regions } => {
+                cmd.resolve_image(
+                    get(&self.images, src, "Image"), src_layout,
+                    get(&self.images, dst, "Image"), dst_layout,
+                    regions,
+                );
+            }
+            Command::BlitImage { src, src_layout, dst, dst_layout, filter, regions } => {
+                cmd.blit_image(
+                    get(&self.images, src, "Image"), src_layout,
+                    get(&self.images, dst, "Image"), dst_layout,
+                    filter, regions,
+                );
+            }
+            Command::BindIndexBuffer { buffer, offset, index_type } => {
+                cmd.bind_index_buffer(buffer::IndexBufferView {
+                    buffer: get(&self.buffers, buffer, "Buffer"),
+                    offset,
+                    index_type,
+                });
+            }
+            Command::BindVertexBuffers { first_binding, buffers } => {
+                cmd.bind_vertex_buffers(
+                    first_binding,
+                    buffers.iter().map(|&(id, offset)| (get(&self.buffers, id, "Buffer"), offset)),
+                );
+            }
+            Command::SetViewports { first, viewports } => cmd.set_viewports(first, viewports),
+            Command::SetScissors { first, rects } => cmd.set_scissors(first, rects),
+            Command::SetStencilReference(faces, value) => cmd.set_stencil_reference(faces, value),
+            Command::SetStencilReadMask(faces, value) => cmd.set_stencil_read_mask(faces, value),
+            Command::SetStencilWriteMask(faces, value) => cmd.set_stencil_write_mask(faces, value),
+            Command::SetBlendConstants(color) => cmd.set_blend_constants(color),
+            Command::SetDepthBounds(bounds) => cmd.set_depth_bounds(bounds),
+            Command::SetLineWidth(width) => cmd.set_line_width(width),
+            Command::SetDepthBias(depth_bias) => cmd.set_depth_bias(depth_bias),
+            Command::BeginRenderPass { render_pass, framebuffer, render_area, clear_values, first_subpass } => {
+                cmd.begin_render_pass(
+                    get(&self.render_passes, render_pass, "RenderPass"),
+                    get(&self.framebuffers, framebuffer, "Framebuffer"),
+                    render_area,
+                    clear_values
+                        .into_iter()
+                        .map(|value| com::ClearValueRaw { color: com::ClearColorRaw { uint32: value } }),
+                    first_subpass,
+                );
+            }
+            Command::NextSubpass(contents) => cmd.next_subpass(contents),
+            Command::EndRenderPass => cmd.end_render_pass(),
+            Command::BindGraphicsPipeline(id) => {
+                cmd.bind_graphics_pipeline(get(&self.graphics_pipelines, id, "GraphicsPipeline"));
+            }
+            Command::BindGraphicsDescriptorSets { layout, first_set, sets, offsets } => {
+                cmd.bind_graphics_descriptor_sets(
+                    get(&self.pipeline_layouts, layout, "PipelineLayout"),
+                    first_set,
+                    sets.iter().map(|&id| get(&self.descriptor_sets, id, "DescriptorSet")),
+                    offsets,
+                );
+            }
+            Command::BindComputePipeline(id) => {
+                cmd.bind_compute_pipeline(get(&self.compute_pipelines, id, "ComputePipeline"));
+            }
+            Command::BindComputeDescriptorSets { layout, first_set, sets, offsets } => {
+                cmd.bind_compute_descriptor_sets(
+                    get(&self.pipeline_layouts, layout, "PipelineLayout"),
+                    first_set,
+                    sets.iter().map(|&id| get(&self.descriptor_sets, id, "DescriptorSet")),
+                    offsets,
+                );
+            }
+            Command::Dispatch(count) => cmd.dispatch(count),
+            Command::DispatchIndirect { buffer, offset } => {
+                cmd.dispatch_indirect(get(&self.buffers, buffer, "Buffer"), offset);
+            }
+            Command::CopyBuffer { src, dst, regions } => {
+                cmd.copy_buffer(get(&self.buffers, src, "Buffer"), get(&self.buffers, dst, "Buffer"), regions);
+            }
+            Command::CopyImage { src, src_layout, dst, dst_layout, regions } => {
+                cmd.copy_image(
+                    get(&self.images, src, "Image"), src_layout,
+                    get(&self.images, dst, "Image"), dst_layout,
+                    regions,
+                );
+            }
+            Command::CopyBufferToImage { src, dst, dst_layout, regions } => {
+                cmd.copy_buffer_to_image(
+                    get(&self.buffers, src, "Buffer"),
+                    get(&self.images, dst, "Image"), dst_layout,
+                    regions,
+                );
+            }
+            Command::CopyImageToBuffer { src, src_layout, dst, regions } => {
+                cmd.copy_image_to_buffer(
+                    get(&self.images, src, "Image"), src_layout,
+                    get(&self.buffers, dst, "Buffer"),
+                    regions,
+                );
+            }
+            Command::Draw { vertices, instances } => cmd.draw(vertices, instances),
+            Command::DrawIndexed { indices, base_vertex, instances } => {
+                cmd.draw_indexed(indices, base_vertex, instances);
+            }
+            Command::DrawIndirect { buffer, offset, draw_count, stride } => {
+                cmd.draw_indirect(get(&self.buffers, buffer, "Buffer"), offset, draw_count, stride);
+            }
+            Command::DrawIndexedIndirect { buffer, offset, draw_count, stride } => {
+                cmd.draw_indexed_indirect(get(&self.buffers, buffer, "Buffer"), offset, draw_count, stride);
+            }
+            Command::BeginQuery(query, flags) => {
+                cmd.begin_query(
+                    hal::query::Query { pool: get(&self.query_pools, query.pool, "QueryPool"), id: query.id },
+                    flags,
+                );
+            }
+            Command::EndQuery(query) => {
+                cmd.end_query(hal::query::Query { pool: get(&self.query_pools, query.pool, "QueryPool"), id: query.id });
+            }
+            Command::ResetQueryPool { pool, queries } => {
+                cmd.reset_query_pool(get(&self.query_pools, pool, "QueryPool"), queries);
+            }
+            Command::WriteTimestamp(stage, query) => {
+                cmd.write_timestamp(
+                    stage,
+                    hal::query::Query { pool: get(&self.query_pools, query.pool, "QueryPool"), id: query.id },
+                );
+            }
+            Command::CopyQueryPoolResults { pool, queries, buffer, offset, stride, flags } => {
+                cmd.copy_query_pool_results(
+                    get(&self.query_pools, pool, "QueryPool"),
+                    queries,
+                    get(&self.buffers, buffer, "Buffer"),
+                    offset,
+                    stride,
+                    flags,
+                );
+            }
+            Command::PushGraphicsConstants { layout, stages, offset, constants } => {
+                cmd.push_graphics_constants(
+                    get(&self.pipeline_layouts, layout, "PipelineLayout"), stages, offset, &constants,
+                );
+            }
+            Command::PushComputeConstants { layout, offset, constants } => {
+                cmd.push_compute_constants(get(&self.pipeline_layouts, layout, "PipelineLayout"), offset, &constants);
+            }
+            Command::ExecuteCommands(ids) => {
+                cmd.execute_commands(ids.iter().map(|&id| get(&self.command_buffers, id, "CommandBuffer")));
+            }
+        }
+        self.command_buffers.insert(id, cmd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate gfx_backend_empty as empty;
+
+    use std::io::{self, Cursor, Write};
+    use std::sync::{Arc, Mutex};
+
+    use hal::{buffer, command, Device, Instance as _, MemoryTypeId};
+    use hal::pool::CommandPoolCreateFlags;
+    use hal::queue::Submission;
+    use capture::format::Reader;
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(data)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn read<B: hal::Backend>(device: &B::Device, memory: &B::Memory, size: u64) -> Vec<u8> {
+        let ptr = device.map_memory(memory, 0 .. size).unwrap();
+        let data = unsafe { ::std::slice::from_raw_parts(ptr, size as usize) }.to_vec();
+        device.unmap_memory(memory);
+        data
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let output = Shared::default();
+        let captured = {
+            let instance = capture::Instance::new(empty::Instance::create("replay", 1), output.clone()).unwrap();
+            let mut adapter = instance.enumerate_adapters().remove(0);
+            let (device, mut queue_group) = adapter.open_with::<_, hal::General>(1, |_| true).unwrap();
+
+            let memory = device.allocate_memory(MemoryTypeId(0), 16).unwrap();
+            let src = device.create_buffer(8, buffer::Usage::TRANSFER_SRC).unwrap();
+            let src = device.bind_buffer_memory(&memory, 0, src).unwrap();
+            let dst = device.create_buffer(8, buffer::Usage::TRANSFER_DST).unwrap();
+            let dst = device.bind_buffer_memory(&memory, 8, dst).unwrap();
+            let ptr = device.map_memory(&memory, 0 .. 8).unwrap();
+            unsafe {
+                ptr::copy_nonoverlapping([1u8, 2, 3, 4, 5, 6, 7, 8].as_ptr(), ptr, 8);
+            }
+
+            let mut pool = device.create_command_pool_typed(&queue_group, CommandPoolCreateFlags::empty(), 1);
+            let submit = {
+                let mut cmd = pool.acquire_command_buffer(false);
+                cmd.copy_buffer(&src, &dst, &[command::BufferCopy { src: 2, dst: 0, size: 4 }]);
+                cmd.fill_buffer(&dst, 4 .. 8, 0x0A0B_0C0D);
+                cmd.finish()
+            };
+            let fence = device.create_fence(false);
+            queue_group.queues[0].submit(Submission::new().submit(Some(submit)), Some(&fence));
+            assert!(device.wait_for_fence(&fence, !0));
+            device.unmap_memory(&memory);
+            read::<capture::Backend<empty::Backend>>(&device, &memory, 16)
+        };
+        assert_eq!(captured, [1, 2, 3, 4, 5, 6, 7, 8, 3, 4, 5, 6, 0x0D, 0x0C, 0x0B, 0x0A]);
+
+        let data = output.0.lock().unwrap().clone();
+        let calls = Reader::new(Cursor::new(data))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let memory = calls
+            .iter()
+            .filter_map(|call| match *call {
+                Call::AllocateMemory { memory, .. } => Some(memory),
+                _ => None,
+            })
+            .next()
+            .unwrap();
+
+        let adapter = empty::Instance::create("replay", 1).enumerate_adapters().remove(0);
+        let mut replay = Replay::new(adapter);
+        for call in calls {
+            replay.execute(call);
+        }
+        for fence in replay.fences.values() {
+            assert!(replay.device().wait_for_fence(fence, !0));
+        }
+        let replayed = read::<empty::Backend>(replay.device(), get(&replay.memories, memory, "Memory").raw(), 16);
+        assert_eq!(replayed, captured);
+    }
+}
@@ -0,0 +1,575 @@
+#![deny(missing_docs)]
+
+//! Resource state tracking and automatic barriers on top of gfx-hal.
+//!
+//! Resources wrapped in a tracked `Buffer` or `Image` remember the last known state
+//! of their contents: the access types and stages since the last barrier and, for
+//! image subresources, the layout. Commands recorded through a tracked
+//! `CommandBuffer` update these states and insert the minimal `pipeline_barrier`
+//! calls before copies, draws and dispatches.
+//!
+//! Buffers are tracked as a whole, images per mip level and array layer.
+//!
+//! The states are read by a command buffer at the first use of a resource and
+//! written back by `CommandBuffer::commit`, so the command buffers touching the
+//! same resources are expected to be committed in the order they are submitted.
+//! Across submission boundaries, for example after acquiring a swapchain image or
+//! a queue family ownership transfer, the state is declared with `Buffer::assume`
+//! and `Image::assume`.
+//!
+//! ```ignore
+//! let staging = Buffer::new(staging_raw);
+//! let texture = Image::new(texture_raw, format::Aspects::COLOR, 1, 1);
+//!
+//! let mut cmd = CommandBuffer::new(&mut raw_cmd);
+//! cmd.copy_buffer_to_image(&staging, &texture, regions);
+//! cmd.use_image(
+//!     &texture, 0 .. 1, 0 .. 1,
+//!     image::Access::SHADER_READ, image::Layout::ShaderReadOnlyOptimal,
+//!     pso::PipelineStage::FRAGMENT_SHADER,
+//! );
+//! cmd.raw().begin_render_pass(..);
+//! cmd.commit();
+//! ```
+
+extern crate gfx_hal as hal;
+
+use std::borrow::Borrow;
+use std::ops::Range;
+use std::ptr;
+use std::sync::Mutex;
+
+use hal::{buffer, format, image, memory, Backend, WorkGroupCount};
+use hal::command::{
+    BufferCopy, BufferImageCopy, ClearColorRaw, ClearDepthStencilRaw, ImageBlit, ImageCopy,
+    RawCommandBuffer,
+};
+use hal::pso::PipelineStage;
+use hal::range::RangeArg;
+
+mod state;
+
+pub use state::{BufferState, ImageState};
+
+
+/// Buffer with a tracked state.
+#[derive(Debug)]
+pub struct Buffer<B: Backend> {
+    raw: B::Buffer,
+    state: Mutex<BufferState>,
+}
+
+impl<B: Backend> Buffer<B> {
+    /// Wrap a buffer that hasn't been accessed by the device yet.
+    pub fn new(raw: B::Buffer) -> Self {
+        Buffer {
+            raw,
+            state: Mutex::new(BufferState {
+                access: buffer::Access::empty(),
+                stages: PipelineStage::empty(),
+            }),
+        }
+    }
+
+    /// Raw buffer.
+    pub fn raw(&self) -> &B::Buffer {
+        &self.raw
+    }
+
+    /// Unwrap the raw buffer.
+    pub fn into_raw(self) -> B::Buffer {
+        self.raw
+    }
+
+    /// Last known state of the buffer.
+    pub fn state(&self) -> BufferState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Declare the state of the buffer, as left by operations that aren't tracked.
+    pub fn assume(&self, state: BufferState) {
+        *self.state.lock().unwrap() = state;
+    }
+}
+
+/// Image with a tracked state for each subresource.
+#[derive(Debug)]
+pub struct Image<B: Backend> {
+    raw: B::Image,
+    aspects: format::Aspects,
+    levels: image::Level,
+    layers: image::Layer,
+    states: Mutex<Vec<ImageState>>,
+}
+
+impl<B: Backend> Image<B> {
+    /// Wrap an image that hasn't been accessed by the device yet,
+    /// with its contents in the `Undefined` layout.
+    pub fn new(raw: B::Image, aspects: format::Aspects, levels: image::Level, layers: image::Layer) -> Self {
+        let state = ImageState {
+            access: image::Access::empty(),
+            layout: image::Layout::Undefined,
+            stages: PipelineStage::empty(),
+        };
+        Image {
+            raw,
+            aspects,
+            levels,
+            layers,
+            states: Mutex::new(vec![state; levels as usize * layers as usize]),
+        }
+    }
+
+    /// Raw image.
+    pub fn raw(&self) -> &B::Image {
+        &self.raw
+    }
+
+    /// Unwrap the raw image.
+    pub fn into_raw(self) -> B::Image {
+        self.raw
+    }
+
+    /// Last known state of a subresource.
+    pub fn state(&self, level: image::Level, layer: image::Layer) -> ImageState {
+        self.states.lock().unwrap()[self.index(level, layer)]
+    }
+
+    /// Declare the state of a range of subresources, as left by operations
+    /// that aren't tracked.
+    pub fn assume(&self, levels: Range<image::Level>, layers: Range<image::Layer>, state: ImageState) {
+        let mut states = self.states.lock().unwrap();
+        for level in levels {
+            for layer in layers.clone() {
+                states[self.index(level, layer)] = state;
+            }
+        }
+    }
+
+    fn index(&self, level: image::Level, layer: image::Layer) -> usize {
+        assert!(level < self.levels && layer < self.layers);
+        level as usize * self.layers as usize + layer as usize
+    }
+}
+
+/// Barriers waiting to be recorded.
+struct Pending<'a, B: Backend> {
+    stages: Range<PipelineStage>,
+    barriers: Vec<memory::Barrier<'a, B>>,
+}
+
+impl<'a, B: Backend> Pending<'a, B> {
+    fn add_image(
+        &mut self,
+        image: &'a Image<B>,
+        level: image::Level,
+        layers: Range<image::Layer>,
+        old: ImageState,
+        new: ImageState,
+    ) {
+        self.stages.start |= old.stages;
+        self.stages.end |= new.stages;
+        self.barriers.push(memory::Barrier::Image {
+            states: (old.access, old.layout) .. (new.access, new.layout),
+            target: image.raw(),
+            range: image::SubresourceRange {
+                aspects: image.aspects,
+                levels: level .. level + 1,
+                layers,
+            },
+            families: None,
+        });
+    }
+}
+
+/// Command buffer recording with automatic barriers.
+///
+/// Only the commands recorded through the tracked methods are taken into account.
+/// Draws happen inside render passes where barriers can't be inserted, so the
+/// resources they use have to be declared with `use_buffer` and `use_image` before
+/// beginning the render pass.
+///
+/// The new states of the resources are kept local to the command buffer until it
+/// is committed. Dropping it without committing discards them.
+pub struct CommandBuffer<'a, B: Backend> {
+    raw: &'a mut B::CommandBuffer,
+    buffers: Vec<(&'a Buffer<B>, BufferState)>,
+    images: Vec<(&'a Image<B>, Vec<ImageState>)>,
+    pending: Pending<'a, B>,
+}
+
+impl<'a, B: Backend> CommandBuffer<'a, B> {
+    /// Track the commands recorded into a raw command buffer, which is expected
+    /// to be in the recording state.
+    pub fn new(raw: &'a mut B::CommandBuffer) -> Self {
+        CommandBuffer {
+            raw,
+            buffers: Vec::new(),
+            images: Vec::new(),
+            pending: Pending {
+                stages: PipelineStage::empty() .. PipelineStage::empty(),
+                barriers: Vec::new(),
+            },
+        }
+    }
+
+    /// Declare an access to a buffer by the following commands, queueing a barrier
+    /// if needed.
+    pub fn use_buffer(&mut self, buffer: &'a Buffer<B>, access: buffer::Access, stages: PipelineStage) {
+        let index = self.buffer_index(buffer);
+        if let Some(old) = self.buffers[index].1.transition(access, stages) {
+            self.pending.stages.start |= old.stages;
+            self.pending.stages.end |= stages;
+            self.pending.barriers.push(memory::Barrier::Buffer {
+                states: old.access .. access,
+                target: buffer.raw(),
+                families: None,
+            });
+        }
+    }
+
+    /// Declare an access to a range of image subresources by the following commands,
+    /// queueing barriers if needed.
+    ///
+    /// Consecutive layers of a level sharing the same previous state are transitioned
+    /// by a single barrier.
+    pub fn use_image(
+        &mut self,
+        image: &'a Image<B>,
+        levels: Range<image::Level>,
+        layers: Range<image::Layer>,
+        access: image::Access,
+        layout: image::Layout,
+        stages: PipelineStage,
+    ) {
+        let index = self.image_index(image);
+        let new = ImageState { access, layout, stages };
+        let states = &mut self.images[index].1;
+        let pending = &mut self.pending;
+
+        for level in levels {
+            // Layers and previous state of the barrier being grouped.
+            let mut run: Option<(Range<image::Layer>, ImageState)> = None;
+            for layer in layers.clone() {
+                let old = states[image.index(level, layer)].transition(access, layout, stages);
+                run = match (run, old) {
+                    (Some((range, state)), Some(old)) if state == old => Some((range.start .. layer + 1, state)),
+                    (run, old) => {
+                        if let Some((range, state)) = run {
+                            pending.add_image(image, level, range, state, new);
+                        }
+                        old.map(|old| (layer .. layer + 1, old))
+                    }
+                };
+            }
+            if let Some((range, state)) = run {
+                pending.add_image(image, level, range, state, new);
+            }
+        }
+    }
+
+    /// Declare the state of a buffer as left by commands recorded without tracking,
+    /// without queueing any barrier.
+    pub fn assume_buffer(&mut self, buffer: &'a Buffer<B>, state: BufferState) {
+        let index = self.buffer_index(buffer);
+        self.buffers[index].1 = state;
+    }
+
+    /// Declare the state of a range of image subresources as left by commands
+    /// recorded without tracking, such as the layout transitions of a render pass,
+    /// without queueing any barrier.
+    pub fn assume_image(
+        &mut self,
+        image: &'a Image<B>,
+        levels: Range<image::Level>,
+        layers: Range<image::Layer>,
+        state: ImageState,
+    ) {
+        let index = self.image_index(image);
+        let states = &mut self.images[index].1;
+        for level in levels {
+            for layer in layers.clone() {
+                states[image.index(level, layer)] = state;
+            }
+        }
+    }
+
+    /// Record the queued barriers.
+    pub fn flush_barriers(&mut self) {
+        if self.pending.barriers.is_empty() {
+            return;
+        }
+        let src = if self.pending.stages.start.is_empty() {
+            PipelineStage::TOP_OF_PIPE
+        } else {
+            self.pending.stages.start
+        };
+        let dst = if self.pending.stages.end.is_empty() {
+            PipelineStage::BOTTOM_OF_PIPE
+        } else {
+            self.pending.stages.end
+        };
+        self.raw.pipeline_barrier(src .. dst, memory::Dependencies::empty(), self.pending.barriers.drain(..));
+        self.pending.stages = PipelineStage::empty() .. PipelineStage::empty();
+    }
+
+    /// Record the queued barriers and get the raw command buffer, to record commands
+    /// without tracking.
+    pub fn raw(&mut self) -> &mut B::CommandBuffer {
+        self.flush_barriers();
+        self.raw
+    }
+
+    /// Copy regions between buffers.
+    pub fn copy_buffer<T>(&mut self, src: &'a Buffer<B>, dst: &'a Buffer<B>, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<BufferCopy>,
+    {
+        self.use_buffer(src, buffer::Access::TRANSFER_READ, PipelineStage::TRANSFER);
+        self.use_buffer(dst, buffer::Access::TRANSFER_WRITE, PipelineStage::TRANSFER);
+        self.flush_barriers();
+        self.raw.copy_buffer(src.raw(), dst.raw(), regions);
+    }
+
+    /// Copy regions between images.
+    pub fn copy_image<T>(&mut self, src: &'a Image<B>, dst: &'a Image<B>, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<ImageCopy>,
+    {
+        let regions = regions.into_iter().collect::<Vec<_>>();
+        for region in &regions {
+            let r = region.borrow();
+            self.use_image_layers(src, &r.src_subresource, image::Access::TRANSFER_READ, image::Layout::TransferSrcOptimal);
+            self.use_image_layers(dst, &r.dst_subresource, image::Access::TRANSFER_WRITE, image::Layout::TransferDstOptimal);
+        }
+        self.flush_barriers();
+        self.raw.copy_image(
+            src.raw(), image::Layout::TransferSrcOptimal,
+            dst.raw(), image::Layout::TransferDstOptimal,
+            regions,
+        );
+    }
+
+    /// Copy regions of a buffer into an image.
+    pub fn copy_buffer_to_image<T>(&mut self, src: &'a Buffer<B>, dst: &'a Image<B>, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<BufferImageCopy>,
+    {
+        let regions = regions.into_iter().collect::<Vec<_>>();
+        self.use_buffer(src, buffer::Access::TRANSFER_READ, PipelineStage::TRANSFER);
+        for region in &regions {
+            let r = region.borrow();
+            self.use_image_layers(dst, &r.image_layers, image::Access::TRANSFER_WRITE, image::Layout::TransferDstOptimal);
+        }
+        self.flush_barriers();
+        self.raw.copy_buffer_to_image(src.raw(), dst.raw(), image::Layout::TransferDstOptimal, regions);
+    }
+
+    /// Copy regions of an image into a buffer.
+    pub fn copy_image_to_buffer<T>(&mut self, src: &'a Image<B>, dst: &'a Buffer<B>, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<BufferImageCopy>,
+    {
+        let regions = regions.into_iter().collect::<Vec<_>>();
+        for region in &regions {
+            let r = region.borrow();
+            self.use_image_layers(src, &r.image_layers, image::Access::TRANSFER_READ, image::Layout::TransferSrcOptimal);
+        }
+        self.use_buffer(dst, buffer::Access::TRANSFER_WRITE, PipelineStage::TRANSFER);
+        self.flush_barriers();
+        self.raw.copy_image_to_buffer(src.raw(), image::Layout::TransferSrcOptimal, dst.raw(), regions);
+    }
+
+    /// Copy regions between images, with scaling and filtering.
+    pub fn blit_image<T>(&mut self, src: &'a Image<B>, dst: &'a Image<B>, filter: image::Filter, regions: T)
+    where
+        T: IntoIterator,
+        T::Item: Borrow<ImageBlit>,
+    {
+        let regions = regions.into_iter().collect::<Vec<_>>();
+        for region in &regions {
+            let r = region.borrow();
+            self.use_image_layers(src, &r.src_subresource, image::Access::TRANSFER_READ, image::Layout::TransferSrcOptimal);
+            self.use_image_layers(dst, &r.dst_subresource, image::Access::TRANSFER_WRITE, image::Layout::TransferDstOptimal);
+        }
+        self.flush_barriers();
+        self.raw.blit_image(
+            src.raw(), image::Layout::TransferSrcOptimal,
+            dst.raw(), image::Layout::TransferDstOptimal,
+            filter, regions,
+        );
+    }
+
+    /// Fill a range of a buffer with the given `u32` value.
+    pub fn fill_buffer<R>(&mut self, buffer: &'a Buffer<B>, range: R, data: u32)
+    where
+        R: RangeArg<buffer::Offset>,
+    {
+        self.use_buffer(buffer, buffer::Access::TRANSFER_WRITE, PipelineStage::TRANSFER);
+        self.flush_barriers();
+        self.raw.fill_buffer(buffer.raw(), range, data);
+    }
+
+    /// Copy data from the given slice into a buffer.
+    pub fn update_buffer(&mut self, buffer: &'a Buffer<B>, offset: buffer::Offset, data: &[u8]) {
+        self.use_buffer(buffer, buffer::Access::TRANSFER_WRITE, PipelineStage::TRANSFER);
+        self.flush_barriers();
+        self.raw.update_buffer(buffer.raw(), offset, data);
+    }
+
+    /// Clear ranges of an image to the given color/depth/stencil.
+    pub fn clear_image<T>(
+        &mut self,
+        image: &'a Image<B>,
+        color: ClearColorRaw,
+        depth_stencil: ClearDepthStencilRaw,
+        subresource_ranges: T,
+    ) where
+        T: IntoIterator,
+        T::Item: Borrow<image::SubresourceRange>,
+    {
+        let ranges = subresource_ranges.into_iter().collect::<Vec<_>>();
+        for range in &ranges {
+            let r = range.borrow();
+            self.use_image(
+                image, r.levels.clone(), r.layers.clone(),
+                image::Access::TRANSFER_WRITE, image::Layout::TransferDstOptimal, PipelineStage::TRANSFER,
+            );
+        }
+        self.flush_barriers();
+        self.raw.clear_image(image.raw(), image::Layout::TransferDstOptimal, color, depth_stencil, ranges);
+    }
+
+    /// Dispatch compute work, after recording the barriers for the resources
+    /// declared with `use_buffer` and `use_image`.
+    pub fn dispatch(&mut self, count: WorkGroupCount) {
+        self.flush_barriers();
+        self.raw.dispatch(count);
+    }
+
+    /// Dispatch compute work with the parameters read from a buffer.
+    pub fn dispatch_indirect(&mut self, buffer: &'a Buffer<B>, offset: buffer::Offset) {
+        self.use_buffer(buffer, buffer::Access::INDIRECT_COMMAND_READ, PipelineStage::DRAW_INDIRECT);
+        self.flush_barriers();
+        self.raw.dispatch_indirect(buffer.raw(), offset);
+    }
+
+    /// Record the queued barriers and store the final states of the resources,
+    /// for the command buffers committed next.
+    ///
+    /// The raw command buffer is left in the recording state.
+    pub fn commit(mut self) {
+        self.flush_barriers();
+        for (buffer, state) in self.buffers.drain(..) {
+            *buffer.state.lock().unwrap() = state;
+        }
+        for (image, states) in self.images.drain(..) {
+            *image.states.lock().unwrap() = states;
+        }
+    }
+
+    fn buffer_index(&mut self, buffer: &'a Buffer<B>) -> usize {
+        match self.buffers.iter().position(|&(b, _)| ptr::eq(b, buffer)) {
+            Some(index) => index,
+            None => {
+                self.buffers.push((buffer, buffer.state()));
+                self.buffers.len() - 1
+            }
+        }
+    }
+
+    fn image_index(&mut self, image: &'a Image<B>) -> usize {
+        match self.images.iter().position(|&(i, _)| ptr::eq(i, image)) {
+            Some(index) => index,
+            None => {
+                let states = image.states.lock().unwrap().clone();
+                self.images.push((image, states));
+                self.images.len() - 1
+            }
+        }
+    }
+
+    fn use_image_layers(
+        &mut self,
+        image: &'a Image<B>,
+        layers: &image::SubresourceLayers,
+        access: image::Access,
+        layout: image::Layout,
+    ) {
+        self.use_image(
+            image, layers.level .. layers.level + 1, layers.layers.clone(),
+            access, layout, PipelineStage::TRANSFER,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate gfx_backend_empty as empty;
+
+    use hal::{self, Device, Instance, PhysicalDevice, QueueFamily};
+    use hal::command::RawCommandBuffer;
+    use hal::pool::RawCommandPool;
+    use super::*;
+
+    #[test]
+    fn test_layer_grouping() {
+        let adapter = empty::Instance::create("track", 1).enumerate_adapters().remove(0);
+        let hal::Gpu { device, .. } = adapter.physical_device
+            .open(&[(&adapter.queue_families[0], &[1.0])], hal::Features::empty())
+            .unwrap();
+        let mut pool = device.create_command_pool(
+            adapter.queue_families[0].id(), hal::pool::CommandPoolCreateFlags::empty(),
+        );
+        let mut raw = pool.allocate(1, hal::command::RawLevel::Primary).remove(0);
+        let unbound = device.create_image(
+            image::Kind::D2(4, 4, 4, 1), 2, format::Format::Rgba8Unorm,
+            image::Tiling::Optimal, image::Usage::TRANSFER_DST, image::StorageFlags::empty(),
+        ).unwrap();
+        let size = device.get_image_requirements(&unbound).size;
+        let memory = device.allocate_memory(hal::MemoryTypeId(0), size).unwrap();
+        let image = Image::<empty::Backend>::new(
+            device.bind_image_memory(&memory, 0, unbound).unwrap(), format::Aspects::COLOR, 2, 4,
+        );
+        image.assume(0 .. 1, 1 .. 2, ImageState {
+            access: image::Access::SHADER_READ,
+            layout: image::Layout::ShaderReadOnlyOptimal,
+            stages: PipelineStage::FRAGMENT_SHADER,
+        });
+
+        let mut cmd = CommandBuffer::new(&mut raw);
+        cmd.use_image(
+            &image, 0 .. 2, 0 .. 4,
+            image::Access::TRANSFER_WRITE, image::Layout::TransferDstOptimal, PipelineStage::TRANSFER,
+        );
+        // The first level is split by the layer in another state, the second one isn't.
+        let ranges = cmd.pending.barriers
+            .iter()
+            .map(|barrier| match *barrier {
+                memory::Barrier::Image { ref range, .. } => (range.levels.start, range.layers.clone()),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec![(0, 0 .. 1), (0, 1 .. 2), (0, 2 .. 4), (1, 0 .. 4)]);
+        assert_eq!(
+            cmd.pending.stages,
+            PipelineStage::FRAGMENT_SHADER .. PipelineStage::TRANSFER
+        );
+        cmd.flush_barriers();
+        assert!(cmd.pending.barriers.is_empty());
+
+        // Writing again has to wait for the previous write, but the layers are grouped.
+        cmd.use_image(
+            &image, 1 .. 2, 0 .. 4,
+            image::Access::TRANSFER_WRITE, image::Layout::TransferDstOptimal, PipelineStage::TRANSFER,
+        );
+        assert_eq!(cmd.pending.barriers.len(), 1);
+        cmd.commit();
+        assert_eq!(image.state(1, 3).layout, image::Layout::TransferDstOptimal);
+        raw.finish();
+    }
+}
@@ -0,0 +1,145 @@
+//! States of the resources and the transitions between them.
+
+use hal::{buffer, image};
+use hal::pso::PipelineStage;
+
+
+/// Buffer accesses modifying its contents.
+fn buffer_writes() -> buffer::Access {
+    buffer::Access::SHADER_WRITE | buffer::Access::TRANSFER_WRITE |
+    buffer::Access::HOST_WRITE | buffer::Access::MEMORY_WRITE
+}
+
+/// Image accesses modifying its contents.
+fn image_writes() -> image::Access {
+    image::Access::SHADER_WRITE | image::Access::COLOR_ATTACHMENT_WRITE |
+    image::Access::DEPTH_STENCIL_ATTACHMENT_WRITE | image::Access::TRANSFER_WRITE |
+    image::Access::HOST_WRITE | image::Access::MEMORY_WRITE
+}
+
+/// Known state of a buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferState {
+    /// Accesses since the last barrier.
+    pub access: buffer::Access,
+    /// Pipeline stages of these accesses.
+    pub stages: PipelineStage,
+}
+
+impl BufferState {
+    /// Update the state for a new access, returning the previous state
+    /// if a barrier has to be inserted before the access.
+    ///
+    /// Reads following reads are accumulated in the state, so that a later write
+    /// waits for all of them. They only need a barrier when they come from stages
+    /// or access types that previous barriers didn't cover.
+    pub(crate) fn transition(&mut self, access: buffer::Access, stages: PipelineStage) -> Option<BufferState> {
+        let old = *self;
+        let read_only = !(old.access | access).intersects(buffer_writes());
+        if read_only {
+            self.access |= access;
+            self.stages |= stages;
+        } else {
+            *self = BufferState { access, stages };
+        }
+
+        let covered = old.access.contains(access) && old.stages.contains(stages);
+        if old.access.is_empty() || (read_only && covered) {
+            None
+        } else {
+            Some(old)
+        }
+    }
+}
+
+/// Known state of an image subresource.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageState {
+    /// Accesses since the last barrier.
+    pub access: image::Access,
+    /// Current layout.
+    pub layout: image::Layout,
+    /// Pipeline stages of the accesses.
+    pub stages: PipelineStage,
+}
+
+impl ImageState {
+    /// Update the state for a new access, returning the previous state
+    /// if a barrier has to be inserted before the access.
+    ///
+    /// Layout transitions always need a barrier, otherwise the rules are the same
+    /// as for buffers.
+    pub(crate) fn transition(
+        &mut self, access: image::Access, layout: image::Layout, stages: PipelineStage
+    ) -> Option<ImageState> {
+        let old = *self;
+        let same_layout = old.layout == layout;
+        let read_only = !(old.access | access).intersects(image_writes());
+        if same_layout && read_only {
+            self.access |= access;
+            self.stages |= stages;
+        } else {
+            *self = ImageState { access, layout, stages };
+        }
+
+        let covered = old.access.contains(access) && old.stages.contains(stages);
+        if same_layout && (old.access.is_empty() || (read_only && covered)) {
+            None
+        } else {
+            Some(old)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hal::{buffer, image};
+    use hal::pso::PipelineStage;
+    use super::*;
+
+    #[test]
+    fn test_buffer_reads() {
+        let mut state = BufferState {
+            access: buffer::Access::TRANSFER_WRITE,
+            stages: PipelineStage::TRANSFER,
+        };
+        let old = state.transition(buffer::Access::SHADER_READ, PipelineStage::VERTEX_SHADER);
+        assert_eq!(old.map(|s| s.access), Some(buffer::Access::TRANSFER_WRITE));
+        assert_eq!(state.transition(buffer::Access::SHADER_READ, PipelineStage::VERTEX_SHADER), None);
+        // Reading from another stage, which the first barrier didn't cover.
+        let old = state.transition(buffer::Access::VERTEX_BUFFER_READ, PipelineStage::VERTEX_INPUT);
+        assert_eq!(old.map(|s| s.access), Some(buffer::Access::SHADER_READ));
+        assert_eq!(state.access, buffer::Access::SHADER_READ | buffer::Access::VERTEX_BUFFER_READ);
+        // Writing has to wait for both reads.
+        let old = state.transition(buffer::Access::TRANSFER_WRITE, PipelineStage::TRANSFER).unwrap();
+        assert_eq!(old.stages, PipelineStage::VERTEX_SHADER | PipelineStage::VERTEX_INPUT);
+        // Writing after a write.
+        assert!(state.transition(buffer::Access::TRANSFER_WRITE, PipelineStage::TRANSFER).is_some());
+    }
+
+    #[test]
+    fn test_image_layouts() {
+        let mut state = ImageState {
+            access: image::Access::empty(),
+            layout: image::Layout::Undefined,
+            stages: PipelineStage::TOP_OF_PIPE,
+        };
+        // Layout transitions need a barrier, even without any prior access.
+        let old = state.transition(
+            image::Access::TRANSFER_WRITE, image::Layout::TransferDstOptimal, PipelineStage::TRANSFER,
+        );
+        assert_eq!(old.map(|s| s.layout), Some(image::Layout::Undefined));
+        let old = state.transition(
+            image::Access::SHADER_READ, image::Layout::ShaderReadOnlyOptimal, PipelineStage::FRAGMENT_SHADER,
+        );
+        assert_eq!(old.map(|s| s.access), Some(image::Access::TRANSFER_WRITE));
+        assert!(state.transition(
+            image::Access::SHADER_READ, image::Layout::ShaderReadOnlyOptimal, PipelineStage::COMPUTE_SHADER,
+        ).is_some());
+        assert_eq!(state.stages, PipelineStage::FRAGMENT_SHADER | PipelineStage::COMPUTE_SHADER);
+        // Both stages are covered now.
+        assert_eq!(state.transition(
+            image::Access::SHADER_READ, image::Layout::ShaderReadOnlyOptimal, PipelineStage::FRAGMENT_SHADER,
+        ), None);
+    }
+}
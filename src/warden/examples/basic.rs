@@ -42,7 +42,7 @@ fn main() {
 
     let adapter = instance.enumerate_adapters().swap_remove(0);
     let mut scene = warden::gpu::Scene::<gl::Backend, _>
-        ::new(adapter, &raw_scene, base_path.join("data"))
+        ::new(adapter, &raw_scene, base_path.join("data"), hal::Features::empty())
         .unwrap();
     scene.run(Some("empty"));
     let guard = scene.fetch_image("image.color");
@@ -1,5 +1,5 @@
 #![cfg_attr(
-    not(any(feature = "vulkan", feature = "dx12", feature = "metal", feature = "gl")),
+    not(any(feature = "vulkan", feature = "dx12", feature = "metal", feature = "gl", feature = "empty")),
     allow(dead_code)
 )]
 
@@ -11,41 +11,77 @@ extern crate serde;
 
 #[cfg(feature = "env_logger")]
 extern crate env_logger;
+#[cfg(feature = "empty")]
+extern crate gfx_backend_empty;
 #[cfg(feature = "vulkan")]
 extern crate gfx_backend_vulkan;
 #[cfg(feature = "dx12")]
 extern crate gfx_backend_dx12;
 #[cfg(feature = "metal")]
 extern crate gfx_backend_metal;
-#[cfg(any(feature = "gl", feature = "gl-headless"))]
+#[cfg(any(feature = "gl", feature = "gl-headless", feature = "gl-egl"))]
 extern crate gfx_backend_gl;
 
-use std::collections::HashMap;
-use std::fs::File;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 use ron::de;
+use warden::pam;
+use warden::report::{self, Outcome, TestResult};
 
 
+/// Allowed difference between an image and its reference.
+#[derive(Debug, Default, Deserialize)]
+struct Tolerance {
+    /// Maximum absolute difference of any channel for pixels to be considered equal.
+    #[serde(default)]
+    channel: u8,
+    /// Maximum number of pixels which aren't considered equal.
+    #[serde(default)]
+    pixels: usize,
+}
+
+/// Rectangle of an image, in pixels.
+#[derive(Debug, Deserialize)]
+struct Region {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
 #[derive(Debug, Deserialize)]
 enum Expectation {
     Buffer(String, Vec<u8>),
     ImageRow(String, usize, Vec<u8>),
+    /// Compare the image, or a region of it, against a PAM reference file
+    /// with the size of the compared area.
+    Image {
+        image: String,
+        reference: String,
+        #[serde(default)]
+        region: Option<Region>,
+        #[serde(default)]
+        tolerance: Tolerance,
+    },
 }
 
 #[derive(Debug, Deserialize)]
 struct Test {
     features: hal::Features,
     jobs: Vec<String>,
-    expect: Expectation,
+    expect: Vec<Expectation>,
 }
 
-type Suite = HashMap<String, HashMap<String, Test>>;
+type Suite = BTreeMap<String, BTreeMap<String, Test>>;
 
 struct TestGroup {
     name: String,
     scene: warden::raw::Scene,
-    tests: HashMap<String, Test>,
+    tests: BTreeMap<String, Test>,
 }
 
 #[derive(Debug)]
@@ -59,22 +95,154 @@ struct TestResults {
 struct Disabilities {
 }
 
+const USAGE: &str = "\
+Usage: reftest <suite> [options]
+
+Options:
+    --filter <pattern>  only run the tests whose `scene/test` name matches the
+                        pattern, where `*` matches any sequence of characters
+    --backend <name>    only run the given backend among the enabled ones
+    --adapter <index>   index of the adapter to use, 0 by default
+    --junit <path>      write the results to a JUnit XML file
+    --tap <path>        write the results to a TAP file";
+
+/// Command line options of the harness.
+#[derive(Debug, Default)]
+struct Options {
+    suite: String,
+    filter: Option<String>,
+    backend: Option<String>,
+    adapter: usize,
+    junit: Option<PathBuf>,
+    tap: Option<PathBuf>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut suite = None;
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", name));
+            match arg.as_str() {
+                "--filter" => options.filter = Some(value("--filter")?),
+                "--backend" => options.backend = Some(value("--backend")?),
+                "--adapter" => {
+                    let index = value("--adapter")?;
+                    options.adapter = index
+                        .parse()
+                        .map_err(|_| format!("invalid adapter index {:?}", index))?;
+                }
+                "--junit" => options.junit = Some(value("--junit")?.into()),
+                "--tap" => options.tap = Some(value("--tap")?.into()),
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ if suite.is_none() => suite = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+        options.suite = suite.ok_or_else(|| "missing the reftest suite name".to_string())?;
+        Ok(options)
+    }
+
+    fn is_backend_selected(&self, backend: &str) -> bool {
+        self.backend.as_ref().map_or(true, |name| name == backend)
+    }
+
+    fn is_test_selected(&self, group: &str, test: &str) -> bool {
+        match self.filter {
+            Some(ref pattern) => glob_match(pattern.as_bytes(), format!("{}/{}", group, test).as_bytes()),
+            None => true,
+        }
+    }
+}
+
+/// Match a text against a pattern, where `*` matches any sequence of
+/// characters and `?` any single character.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((&b'*', rest)) => (0 .. text.len() + 1).any(|i| glob_match(rest, &text[i ..])),
+        Some((&b'?', rest)) => !text.is_empty() && glob_match(rest, &text[1 ..]),
+        Some((&c, rest)) => text.first() == Some(&c) && glob_match(rest, &text[1 ..]),
+    }
+}
+
 
 struct Harness {
     base_path: PathBuf,
+    output_path: PathBuf,
+    options: Options,
     suite: Vec<TestGroup>,
 }
 
+/// Get the size of an image, with depth slices stacked vertically, and its format.
+fn find_image(scene: &warden::raw::Scene, name: &str) -> Option<(usize, usize, hal::format::Format)> {
+    match scene.resources.get(name) {
+        Some(&warden::raw::Resource::Image { kind, format, .. }) => {
+            let extent = kind.extent();
+            Some((extent.width as _, (extent.height * extent.depth) as _, format))
+        }
+        _ => None,
+    }
+}
+
+/// Number of channels of formats with 8 bits per channel, the only ones
+/// which can be stored in reference images and compared channel by channel.
+fn channel_count(format: hal::format::Format) -> Option<usize> {
+    use hal::format::SurfaceType as St;
+    match format.base_format().0 {
+        St::R8 => Some(1),
+        St::R8_G8 => Some(2),
+        St::R8_G8_B8 | St::B8_G8_R8 => Some(3),
+        St::R8_G8_B8_A8 | St::B8_G8_R8_A8 | St::A8_B8_G8_R8 => Some(4),
+        _ => None,
+    }
+}
+
+fn check_row(actual: &[u8], expected: &[u8]) -> Result<(), String> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("{:?}", actual))
+    }
+}
+
+/// Compare two images of the same size, returning the number of differing
+/// pixels and an image of the absolute differences.
+fn compare_images(actual: &pam::Image, expected: &pam::Image, tolerance: &Tolerance) -> (usize, pam::Image) {
+    let mut diff = pam::Image::new(actual.width, actual.height, actual.depth);
+    let mut num_different = 0;
+    for y in 0 .. actual.height {
+        for x in 0 .. actual.width {
+            let mut is_different = false;
+            let out = diff.pixel_mut(x, y);
+            for (d, (&a, &e)) in out.iter_mut().zip(actual.pixel(x, y).iter().zip(expected.pixel(x, y))) {
+                *d = if a > e { a - e } else { e - a };
+                is_different |= *d > tolerance.channel;
+            }
+            if actual.depth == 4 {
+                // Keep the differences visible regardless of the alpha.
+                out[3] = 0xFF;
+            }
+            if is_different {
+                num_different += 1;
+            }
+        }
+    }
+    (num_different, diff)
+}
+
 impl Harness {
-    fn new(suite_name: &str) -> Self {
+    fn new(options: Options) -> Self {
         let base_path = PathBuf::from(concat!(
             env!("CARGO_MANIFEST_DIR"),
             "/../../reftests",
         ));
-        println!("Parsing test suite '{}'...", suite_name);
+        println!("Parsing test suite '{}'...", options.suite);
 
         let suite_path = base_path
-            .join(suite_name)
+            .join(&options.suite)
             .with_extension("ron");
         let suite = File::open(suite_path)
             .map_err(de::Error::from)
@@ -99,26 +267,110 @@ impl Harness {
             .collect();
 
         Harness {
+            output_path: base_path.join("../target/reftests"),
             base_path,
+            options,
             suite,
         }
     }
 
+    /// Check an image against its reference, writing the actual, expected
+    /// and difference images into the output directory on failure.
+    fn check_image<B: hal::Backend>(
+        &self,
+        scene: &mut warden::gpu::Scene<B, hal::General>,
+        raw_scene: &warden::raw::Scene,
+        output_dir: &Path,
+        test_name: &str,
+        image: &str,
+        reference: &str,
+        region: Option<&Region>,
+        tolerance: &Tolerance,
+    ) -> Result<(), String> {
+        let (width, height, format) = match find_image(raw_scene, image) {
+            Some(image) => image,
+            None => return Err(format!("unknown image {:?}", image)),
+        };
+        let texel_size = match channel_count(format) {
+            Some(count) => count,
+            None => return Err(format!("format {:?} doesn't have 8-bit channels and can't be compared", format)),
+        };
+        let (x, y, width, height) = match region {
+            Some(r) if r.x + r.width > width || r.y + r.height > height => {
+                return Err(format!("region {:?} is out of the image bounds", r))
+            }
+            Some(r) => (r.x, r.y, r.width, r.height),
+            None => (0, 0, width, height),
+        };
+
+        let expected = pam::Image::load(&self.base_path.join("data").join(reference))
+            .map_err(|e| e.to_string())?;
+        if (expected.width, expected.height, expected.depth) != (width, height, texel_size) {
+            return Err(format!(
+                "reference is {}x{} with {} channels, expected {}x{} with {}",
+                expected.width, expected.height, expected.depth, width, height, texel_size,
+            ));
+        }
+
+        let mut actual = pam::Image::new(width, height, texel_size);
+        {
+            let guard = scene.fetch_image(image);
+            for row in 0 .. height {
+                let src = &guard.row(y + row)[x * texel_size .. (x + width) * texel_size];
+                let offset = row * width * texel_size;
+                actual.data[offset .. offset + src.len()].copy_from_slice(src);
+            }
+        }
+
+        let (num_different, diff) = compare_images(&actual, &expected, tolerance);
+        if num_different <= tolerance.pixels {
+            return Ok(());
+        }
+
+        let _ = fs::create_dir_all(output_dir);
+        for &(suffix, data) in &[("actual", &actual), ("expected", &expected), ("diff", &diff)] {
+            let path = output_dir.join(format!("{}.{}.pam", test_name, suffix));
+            if let Err(e) = data.save(&path) {
+                println!("\t\tfailed to write {:?}: {}", path, e);
+            }
+        }
+        Err(format!(
+            "{} pixels differ (tolerance {}), see {:?}",
+            num_different, tolerance.pixels, output_dir.join(test_name),
+        ))
+    }
+
     fn run<I: hal::Instance>(
         &self,
+        backend: &str,
         instance: I,
         _disabilities: Disabilities,
-    ) -> usize {
+    ) -> Vec<TestResult> {
         use hal::{PhysicalDevice};
 
-        let mut results = TestResults {
-            pass: 0,
-            skip: 0,
-            fail: 0,
-        };
+        let mut results = Vec::new();
         for tg in &self.suite {
+            let tests = tg.tests
+                .iter()
+                .filter(|&(name, _)| self.options.is_test_selected(&tg.name, name))
+                .collect::<Vec<_>>();
+            if tests.is_empty() {
+                continue
+            }
+            let mut record = |name: &str, outcome: Outcome, start: Instant| results.push(TestResult {
+                backend: backend.to_string(),
+                group: tg.name.clone(),
+                name: name.to_string(),
+                outcome,
+                duration: start.elapsed(),
+            });
+
             let mut adapters = instance.enumerate_adapters();
-            let adapter = adapters.remove(0);
+            if self.options.adapter >= adapters.len() {
+                println!("\tAdapter {} is not available, found {}", self.options.adapter, adapters.len());
+                break
+            }
+            let adapter = adapters.remove(self.options.adapter);
             let features = adapter.physical_device.features();
             let limits = adapter.physical_device.limits();
             //println!("\t{:?}", adapter.info);
@@ -133,28 +385,52 @@ impl Harness {
                         _ => true,
                     });
                 if !all_spirv {
-                    println!("\t\tskipped {} tests (GLSL shaders)", tg.tests.len());
-                    results.skip += tg.tests.len();
+                    println!("\t\tskipped {} tests (GLSL shaders)", tests.len());
+                    for &(test_name, _) in &tests {
+                        record(test_name, Outcome::Skip("GLSL shaders".to_string()), Instant::now());
+                    }
                     continue
                 }
             }
 
-            let mut scene = warden::gpu::Scene::<I::Backend, _>::new(
+            // enable the features of the tests that can run on this adapter
+            let requested_features = tests
+                .iter()
+                .fold(hal::Features::empty(), |acc, &(_, test)| acc | test.features) & features;
+            let mut scene = match warden::gpu::Scene::<I::Backend, _>::new(
                 adapter,
                 &tg.scene,
                 self.base_path.join("data"),
-            ).unwrap();
+                requested_features,
+            ) {
+                Ok(scene) => scene,
+                Err(e) => {
+                    let message = format!("scene: {}", e);
+                    println!("		FAIL {}", message);
+                    for &(test_name, _) in &tests {
+                        record(test_name, Outcome::Fail(message.clone()), Instant::now());
+                    }
+                    continue
+                }
+            };
 
-            for (test_name, test) in &tg.tests {
+            for &(test_name, test) in &tests {
+                let start = Instant::now();
                 print!("\t\tTest '{}' ...", test_name);
                 if !features.contains(test.features) {
-                    println!("\tskipped (features missing: {:?})", test.features - features);
-                    results.skip += 1;
+                    let reason = format!("features missing: {:?}", test.features - features);
+                    println!("\tskipped ({})", reason);
+                    record(test_name, Outcome::Skip(reason), start);
+                    continue
                 }
                 let mut max_compute_groups = [0; 3];
                 for job_name in &test.jobs {
-                    if let warden::raw::Job::Compute { dispatch, .. } = tg.scene.jobs[job_name] {
-                        for (max, count) in max_compute_groups.iter_mut().zip(dispatch.iter()) {
+                    let job = match tg.scene.jobs[job_name] {
+                        warden::raw::Job::Submit { ref job, .. } => &**job,
+                        ref job => job,
+                    };
+                    if let warden::raw::Job::Compute { dispatch: warden::raw::Dispatch::Direct(ref count), .. } = *job {
+                        for (max, count) in max_compute_groups.iter_mut().zip(count.iter()) {
                             *max = (*max).max(*count);
                         }
                     }
@@ -163,33 +439,66 @@ impl Harness {
                     max_compute_groups[1] > limits.max_compute_group_size[1] ||
                     max_compute_groups[2] > limits.max_compute_group_size[2]
                 {
-                    println!("\tskipped (compute {:?})", max_compute_groups);
-                    results.skip += 1;
+                    let reason = format!("compute {:?}", max_compute_groups);
+                    println!("\tskipped ({})", reason);
+                    record(test_name, Outcome::Skip(reason), start);
                     continue
                 }
 
-                scene.run(test.jobs.iter().map(|x| x.as_str()));
-
                 print!("\tran: ");
-                let (guard, row, data) = match test.expect {
-                    Expectation::Buffer(ref buffer, ref data) =>
-                        (scene.fetch_buffer(buffer), 0, data),
-                    Expectation::ImageRow(ref image, row, ref data) =>
-                        (scene.fetch_image(image), row, data),
-                };
-
-                if data.as_slice() == guard.row(row) {
+                if let Err(e) = scene.run(test.jobs.iter().map(|x| x.as_str())) {
+                    let message = format!("run: {}", e);
+                    println!("FAIL {}", message);
+                    record(test_name, Outcome::Fail(message), start);
+                    continue
+                }
+                let mut failures = Vec::new();
+                for (i, expect) in test.expect.iter().enumerate() {
+                    let result = match *expect {
+                        Expectation::Buffer(ref buffer, ref data) => {
+                            let guard = scene.fetch_buffer(buffer);
+                            check_row(guard.row(0), data)
+                        }
+                        Expectation::ImageRow(ref image, row, ref data) => {
+                            let guard = scene.fetch_image(image);
+                            check_row(guard.row(row), data)
+                        }
+                        Expectation::Image { ref image, ref reference, ref region, ref tolerance } => {
+                            let output_dir = self.output_path.join(backend).join(&tg.name);
+                            let output_name = if test.expect.len() > 1 {
+                                format!("{}.{}", test_name, i)
+                            } else {
+                                test_name.clone()
+                            };
+                            self.check_image(
+                                &mut scene, &tg.scene, &output_dir, &output_name,
+                                image, reference, region.as_ref(), tolerance,
+                            )
+                        }
+                    };
+                    if let Err(message) = result {
+                        failures.push(format!("expectation {}: {}", i, message));
+                    }
+                }
+
+                if failures.is_empty() {
                     println!("PASS");
-                    results.pass += 1;
+                    record(test_name, Outcome::Pass, start);
                 } else {
-                    println!("FAIL {:?}", guard.row(row));
-                    results.fail += 1;
+                    let message = failures.join("; ");
+                    println!("FAIL {}", message);
+                    record(test_name, Outcome::Fail(message), start);
                 }
             }
         }
 
-        println!("\t{:?}", results);
-        results.fail
+        let count = |f: fn(&Outcome) -> bool| results.iter().filter(|r| f(&r.outcome)).count();
+        println!("\t{:?}", TestResults {
+            pass: count(|o| *o == Outcome::Pass),
+            skip: count(|o| match *o { Outcome::Skip(_) => true, _ => false }),
+            fail: count(|o| match *o { Outcome::Fail(_) => true, _ => false }),
+        });
+        results
     }
 }
 
@@ -198,39 +507,46 @@ fn main() {
 
     #[cfg(feature = "env_logger")]
     env_logger::init();
-    let mut num_failures = 0;
+    #[allow(unused_mut)]
+    let mut results: Vec<TestResult> = Vec::new();
 
-    let suite_name = match env::args().nth(1) {
-        Some(name) => name,
-        None => {
-            println!("Call with the argument of the reftest suite name");
-            return
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            println!("{}\n\n{}", message, USAGE);
+            process::exit(2);
         }
     };
 
-    let harness = Harness::new(&suite_name);
+    let harness = Harness::new(options);
+    #[cfg(feature = "empty")]
+    if harness.options.is_backend_selected("empty") {
+        println!("Warding CPU:");
+        let instance = gfx_backend_empty::Instance::create("warden", 1);
+        results.extend(harness.run("empty", instance, Disabilities::default()));
+    }
     #[cfg(feature = "vulkan")]
-    {
+    if harness.options.is_backend_selected("vulkan") {
         println!("Warding Vulkan:");
         let instance = gfx_backend_vulkan::Instance::create("warden", 1);
-        num_failures += harness.run(instance, Disabilities::default());
+        results.extend(harness.run("vulkan", instance, Disabilities::default()));
     }
     #[cfg(feature = "dx12")]
-    {
+    if harness.options.is_backend_selected("dx12") {
         println!("Warding DX12:");
         let instance = gfx_backend_dx12::Instance::create("warden", 1);
-        num_failures += harness.run(instance, Disabilities::default());
+        results.extend(harness.run("dx12", instance, Disabilities::default()));
     }
     #[cfg(feature = "metal")]
-    {
+    if harness.options.is_backend_selected("metal") {
         println!("Warding Metal:");
         let instance = gfx_backend_metal::Instance::create("warden", 1);
-        num_failures += harness.run(instance, Disabilities {
+        results.extend(harness.run("metal", instance, Disabilities {
             .. Disabilities::default()
-        });
+        }));
     }
     #[cfg(feature = "gl")]
-    {
+    if harness.options.is_backend_selected("gl") {
         use gfx_backend_gl::glutin;
         println!("Warding GL:");
         let events_loop = glutin::EventsLoop::new();
@@ -241,18 +557,97 @@ fn main() {
             &events_loop,
             ).unwrap();
         let instance = gfx_backend_gl::Surface::from_window(window);
-        num_failures += harness.run(instance, Disabilities::default());
+        results.extend(harness.run("gl", instance, Disabilities::default()));
     }
     #[cfg(feature = "gl-headless")]
-    {
+    if harness.options.is_backend_selected("gl-headless") {
         println!("Warding GL headless:");
         let context = gfx_backend_gl::glutin::HeadlessRendererBuilder::new(1, 1)
             .build()
             .unwrap();
         let instance = gfx_backend_gl::Headless(context);
-        num_failures += harness.run(instance, Disabilities::default());
+        results.extend(harness.run("gl-headless", instance, Disabilities::default()));
+    }
+    #[cfg(feature = "gl-egl")]
+    if harness.options.is_backend_selected("gl-egl") {
+        println!("Warding GL EGL:");
+        let instance = gfx_backend_gl::EglHeadless::new().unwrap();
+        results.extend(harness.run("gl-egl", instance, Disabilities::default()));
     }
-    let _ = harness;
-    num_failures += 0; // mark as mutated
+    if let Some(ref path) = harness.options.junit {
+        let file = File::create(path).expect("failed to create the JUnit report");
+        report::write_junit(BufWriter::new(file), &results).expect("failed to write the JUnit report");
+    }
+    if let Some(ref path) = harness.options.tap {
+        let file = File::create(path).expect("failed to create the TAP report");
+        report::write_tap(BufWriter::new(file), &results).expect("failed to write the TAP report");
+    }
+
+    let num_failures = results
+        .iter()
+        .filter(|r| match r.outcome { Outcome::Fail(_) => true, _ => false })
+        .count();
     process::exit(num_failures as _);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_match, Options};
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn glob() {
+        let matches = |pattern: &str, text: &str| glob_match(pattern.as_bytes(), text.as_bytes());
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+        assert!(matches("*", ""));
+        assert!(matches("*", "basic/clear"));
+        assert!(matches("basic/*", "basic/clear"));
+        assert!(!matches("basic/*", "compute/fill"));
+        assert!(matches("*/clear", "basic/clear"));
+        assert!(matches("*clear*", "basic/clear-depth"));
+        assert!(matches("b*c*r", "basic/clear"));
+        assert!(!matches("b*c*r", "basic/clean"));
+        assert!(matches("**", "a"));
+        assert!(matches("basic/clea?", "basic/clear"));
+        assert!(!matches("basic/clear?", "basic/clear"));
+        assert!(!matches("?", ""));
+        assert!(!matches("basic", "basic/clear"));
+    }
+
+    #[test]
+    fn options() {
+        let options = parse(&["ci"]).unwrap();
+        assert_eq!(options.suite, "ci");
+        assert_eq!(options.filter, None);
+        assert_eq!(options.backend, None);
+        assert_eq!(options.adapter, 0);
+        assert!(options.is_backend_selected("gl"));
+        assert!(options.is_test_selected("basic", "clear"));
+
+        let options = parse(&[
+            "--filter", "basic/*", "local", "--backend", "vulkan", "--adapter", "2",
+            "--junit", "out.xml", "--tap", "out.tap",
+        ]).unwrap();
+        assert_eq!(options.suite, "local");
+        assert_eq!(options.adapter, 2);
+        assert_eq!(options.junit.as_ref().unwrap().to_str(), Some("out.xml"));
+        assert_eq!(options.tap.as_ref().unwrap().to_str(), Some("out.tap"));
+        assert!(options.is_backend_selected("vulkan"));
+        assert!(!options.is_backend_selected("gl"));
+        assert!(options.is_test_selected("basic", "clear"));
+        assert!(!options.is_test_selected("compute", "fill"));
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert_eq!(parse(&[]).unwrap_err(), "missing the reftest suite name");
+        assert_eq!(parse(&["ci", "--filter"]).unwrap_err(), "missing value for --filter");
+        assert_eq!(parse(&["ci", "--adapter", "first"]).unwrap_err(), "invalid adapter index \"first\"");
+        assert_eq!(parse(&["ci", "--verbose"]).unwrap_err(), "unknown option --verbose");
+        assert_eq!(parse(&["ci", "local"]).unwrap_err(), "unexpected argument local");
+    }
+}
@@ -1,4 +1,4 @@
-use failure::Error;
+use failure::{err_msg, Error};
 #[cfg(feature = "glsl-to-spirv")]
 use glsl_to_spirv;
 
@@ -9,7 +9,7 @@ use std::path::PathBuf;
 use std::{slice};
 
 use hal::{self, buffer as b, command as c, format as f, image as i, memory, pso};
-use hal::{Device, DescriptorPool, PhysicalDevice};
+use hal::{Capability, Device, DescriptorPool, PhysicalDevice, QueueFamily};
 
 use raw;
 
@@ -20,6 +20,9 @@ const COLOR_RANGE: i::SubresourceRange = i::SubresourceRange {
     layers: 0 .. 1,
 };
 
+/// Time given to the jobs of a run to signal their fences, in nanoseconds.
+const FENCE_TIMEOUT: u64 = 10_000_000_000;
+
 pub struct FetchGuard<'a, B: hal::Backend> {
     device: &'a mut B::Device,
     buffer: Option<B::Buffer>,
@@ -27,15 +30,22 @@ pub struct FetchGuard<'a, B: hal::Backend> {
     mapping: *const u8,
     row_pitch: usize,
     width: usize,
+    height: usize,
 }
 
 impl<'a, B: hal::Backend> FetchGuard<'a, B> {
     pub fn row(&self, i: usize) -> &[u8] {
+        assert!(i < self.height);
         let offset = (i * self.row_pitch) as isize;
         unsafe {
             slice::from_raw_parts(self.mapping.offset(offset), self.width)
         }
     }
+
+    /// Number of rows, including all the depth slices of 3D images.
+    pub fn height(&self) -> usize {
+        self.height
+    }
 }
 
 impl<'a, B: hal::Backend> Drop for FetchGuard<'a, B> {
@@ -60,12 +70,14 @@ impl<B: hal::Backend> Buffer<B> {
         memory::Barrier::Buffer {
             states: self.stable_state .. access,
             target: &self.handle,
+            families: None,
         }
     }
     fn barrier_from(&self, access: b::Access) -> memory::Barrier<B> {
         memory::Barrier::Buffer {
             states: access .. self.stable_state,
             target: &self.handle,
+            families: None,
         }
     }
 }
@@ -85,6 +97,7 @@ impl<B: hal::Backend> Image<B> {
             states: self.stable_state .. (access, layout),
             target: &self.handle,
             range: self.range.clone(),
+            families: None,
         }
     }
     fn barrier_from(&self, access: i::Access, layout: i::Layout) -> memory::Barrier<B> {
@@ -92,6 +105,7 @@ impl<B: hal::Backend> Image<B> {
             states: (access, layout) .. self.stable_state,
             target: &self.handle,
             range: self.range.clone(),
+            families: None,
         }
     }
 }
@@ -106,6 +120,8 @@ pub struct Resources<B: hal::Backend> {
     pub buffers: HashMap<String, Buffer<B>>,
     pub images: HashMap<String, Image<B>>,
     pub image_views: HashMap<String, B::ImageView>,
+    pub buffer_views: HashMap<String, B::BufferView>,
+    pub samplers: HashMap<String, B::Sampler>,
     pub render_passes: HashMap<String, RenderPass<B>>,
     pub framebuffers: HashMap<String, (B::Framebuffer, i::Extent)>,
     pub shaders: HashMap<String, B::ShaderModule>,
@@ -115,10 +131,22 @@ pub struct Resources<B: hal::Backend> {
     pub pipeline_layouts: HashMap<String, B::PipelineLayout>,
     pub graphics_pipelines: HashMap<String, B::GraphicsPipeline>,
     pub compute_pipelines: HashMap<String, (String, B::ComputePipeline)>,
+    pub semaphores: HashMap<String, B::Semaphore>,
+    pub fences: HashMap<String, B::Fence>,
 }
 
 pub struct Job<B: hal::Backend, C> {
     submission: c::Submit<B, C, c::MultiShot, c::Primary>,
+    family: hal::queue::QueueFamilyId,
+    wait: Vec<(String, pso::PipelineStage)>,
+    signal: Vec<String>,
+    fence: Option<String>,
+}
+
+/// Queue of a family other than the general one, used by the `Submit` jobs.
+struct ExtraQueue<B: hal::Backend, C> {
+    queue: hal::CommandQueue<B, C>,
+    command_pool: Option<hal::CommandPool<B, C>>,
 }
 
 pub struct Scene<B: hal::Backend, C> {
@@ -128,11 +156,31 @@ pub struct Scene<B: hal::Backend, C> {
     device: B::Device,
     queue_group: hal::QueueGroup<B, C>,
     command_pool: Option<hal::CommandPool<B, C>>,
+    extra_queues: HashMap<hal::queue::QueueFamilyId, ExtraQueue<B, C>>,
     upload_buffers: HashMap<String, (B::Buffer, B::Memory)>,
     download_type: hal::MemoryTypeId,
     limits: hal::Limits,
 }
 
+/// Pick the family to run the jobs of a given queue type on: a family of this
+/// exact type if the adapter has one, or the general family otherwise.
+fn select_queue_family<F: QueueFamily>(families: &[F], ty: hal::QueueType) -> Option<&F> {
+    families
+        .iter()
+        .find(|family| family.queue_type() == ty)
+        .or_else(|| families.iter().find(|family| family.queue_type() == hal::QueueType::General))
+}
+
+fn is_supported_by(job: &raw::Job, ty: hal::QueueType) -> bool {
+    match *job {
+        raw::Job::Transfer(_) |
+        raw::Job::OwnershipTransfer { .. } => true,
+        raw::Job::Graphics { .. } => hal::Graphics::supported_by(ty),
+        raw::Job::Compute { .. } => hal::Compute::supported_by(ty),
+        raw::Job::Submit { .. } => false,
+    }
+}
+
 fn align(x: u64, y: u64) -> u64 {
     if x > 0 && y > 0 {
         ((x - 1) | (y - 1)) + 1
@@ -143,7 +191,7 @@ fn align(x: u64, y: u64) -> u64 {
 
 impl<B: hal::Backend> Scene<B, hal::General> {
     pub fn new(
-        mut adapter: hal::Adapter<B>, raw: &raw::Scene, data_path: PathBuf
+        adapter: hal::Adapter<B>, raw: &raw::Scene, data_path: PathBuf, features: hal::Features,
     ) -> Result<Self, Error> {
         info!("creating Scene from {:?}", data_path);
         let memory_types = adapter
@@ -154,8 +202,78 @@ impl<B: hal::Backend> Scene<B, hal::General> {
             .physical_device
             .limits();
 
+        // collect the queue types used by the jobs, the general one coming first
+        let mut queue_types = vec![hal::QueueType::General];
+        for job in raw.jobs.values() {
+            let (job, queue) = match *job {
+                raw::Job::Submit { queue, ref job, .. } => (&**job, queue),
+                _ => (job, hal::QueueType::General),
+            };
+            let mut types = vec![queue];
+            if let raw::Job::OwnershipTransfer { ref families, .. } = *job {
+                types.push(families.start);
+                types.push(families.end);
+            }
+            for ty in types {
+                if !queue_types.contains(&ty) {
+                    queue_types.push(ty);
+                }
+            }
+        }
+        let queue_families = queue_types
+            .iter()
+            .map(|&ty| {
+                select_queue_family(&adapter.queue_families, ty)
+                    .map(|family| (ty, family.id()))
+                    .ok_or_else(|| err_msg(format!("No queue family for {:?} jobs", ty)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let family_of = |ty: hal::QueueType| {
+            queue_families
+                .iter()
+                .find(|&&(t, _)| t == ty)
+                .unwrap()
+                .1
+        };
+        let mut family_ids = Vec::new();
+        for &(_, id) in &queue_families {
+            if !family_ids.contains(&id) {
+                family_ids.push(id);
+            }
+        }
+
         // initialize graphics
-        let (device, queue_group) = adapter.open_with(1, |_| true)?;
+        let (device, queue_group, mut extra_queues) = {
+            let priorities = [1.0];
+            let requests = family_ids
+                .iter()
+                .map(|&id| {
+                    let family = adapter.queue_families
+                        .iter()
+                        .find(|family| family.id() == id)
+                        .unwrap();
+                    (family, &priorities[..])
+                })
+                .collect::<Vec<_>>();
+            let hal::Gpu { device, mut queues } = adapter.physical_device.open(&requests, features)?;
+            let queue_group = queues.take::<hal::General>(family_ids[0]).unwrap();
+            let extra_queues = family_ids[1 ..]
+                .iter()
+                .map(|&id| {
+                    let raw_queue = queues.take_raw(id).unwrap().remove(0);
+                    let raw_pool = device.create_command_pool(id, hal::pool::CommandPoolCreateFlags::empty());
+                    // The jobs are recorded and submitted through the general wrappers,
+                    // and `is_supported_by` makes sure they only use the capabilities
+                    // of the family they run on.
+                    let queue = ExtraQueue {
+                        queue: unsafe { hal::CommandQueue::new(raw_queue) },
+                        command_pool: Some(unsafe { hal::CommandPool::new(raw_pool) }),
+                    };
+                    (id, queue)
+                })
+                .collect::<HashMap<_, _>>();
+            (device, queue_group, extra_queues)
+        };
 
         let upload_type: hal::MemoryTypeId = memory_types
             .iter()
@@ -187,6 +305,8 @@ impl<B: hal::Backend> Scene<B, hal::General> {
             buffers: HashMap::new(),
             images: HashMap::new(),
             image_views: HashMap::new(),
+            buffer_views: HashMap::new(),
+            samplers: HashMap::new(),
             render_passes: HashMap::new(),
             framebuffers: HashMap::new(),
             shaders: HashMap::new(),
@@ -196,14 +316,32 @@ impl<B: hal::Backend> Scene<B, hal::General> {
             pipeline_layouts: HashMap::new(),
             graphics_pipelines: HashMap::new(),
             compute_pipelines: HashMap::new(),
+            semaphores: HashMap::new(),
+            fences: HashMap::new(),
         };
         let mut upload_buffers = HashMap::new();
         let init_submit = {
             let mut init_cmd = command_pool.acquire_command_buffer(false);
 
-            // Pass[1]: images, buffers, passes, descriptor set layouts/pools
+            // Pass[0]: samplers, needed by the descriptor set layouts
+            for (name, resource) in &raw.resources {
+                if let raw::Resource::Sampler(ref info) = *resource {
+                    let sampler = device.create_sampler(info.clone());
+                    resources.samplers.insert(name.clone(), sampler);
+                }
+            }
+
+            // Pass[1]: images, buffers, passes, descriptor set layouts/pools, synchronization
             for (name, resource) in &raw.resources {
                 match *resource {
+                    raw::Resource::Semaphore => {
+                        let semaphore = device.create_semaphore();
+                        resources.semaphores.insert(name.clone(), semaphore);
+                    }
+                    raw::Resource::Fence => {
+                        let fence = device.create_fence(false);
+                        resources.fences.insert(name.clone(), fence);
+                    }
                     raw::Resource::Buffer { size, usage, ref data } => {
                         // allocate memory
                         let unbound = device.create_buffer(size as _, usage)
@@ -230,6 +368,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                                 let buffer_barrier = memory::Barrier::Buffer {
                                     states: b::Access::empty() .. access,
                                     target: &buffer,
+                                    families: None,
                                 };
                                 init_cmd.pipeline_barrier(
                                     pso::PipelineStage::TOP_OF_PIPE .. pso::PipelineStage::BOTTOM_OF_PIPE,
@@ -265,6 +404,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                             let pre_barrier = memory::Barrier::Buffer {
                                 states: b::Access::empty() .. b::Access::TRANSFER_WRITE,
                                 target: &buffer,
+                                families: None,
                             };
                             init_cmd.pipeline_barrier(
                                 pso::PipelineStage::TOP_OF_PIPE .. pso::PipelineStage::TRANSFER,
@@ -284,6 +424,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                             let post_barrier = memory::Barrier::Buffer {
                                 states: b::Access::TRANSFER_WRITE .. final_state,
                                 target: &buffer,
+                                families: None,
                             };
                             init_cmd.pipeline_barrier(
                                 pso::PipelineStage::TRANSFER .. pso::PipelineStage::BOTTOM_OF_PIPE,
@@ -337,6 +478,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                                         aspects,
                                         .. COLOR_RANGE.clone()
                                     },
+                                    families: None,
                                 };
                                 init_cmd.pipeline_barrier(
                                     pso::PipelineStage::TOP_OF_PIPE .. pso::PipelineStage::BOTTOM_OF_PIPE,
@@ -391,6 +533,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                                         (i::Access::TRANSFER_WRITE, i::Layout::TransferDstOptimal),
                                 target: &image,
                                 range: COLOR_RANGE.clone(), //TODO
+                                families: None,
                             };
                             init_cmd.pipeline_barrier(
                                 pso::PipelineStage::TOP_OF_PIPE .. pso::PipelineStage::TRANSFER,
@@ -421,6 +564,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                                 states: (i::Access::TRANSFER_WRITE, i::Layout::TransferDstOptimal) .. final_state,
                                 target: &image,
                                 range: COLOR_RANGE.clone(), //TODO
+                                families: None,
                             };
                             init_cmd.pipeline_barrier(
                                 pso::PipelineStage::TRANSFER .. pso::PipelineStage::BOTTOM_OF_PIPE,
@@ -539,9 +683,14 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                         resources.shaders.insert(name.clone(), module);
                     }
                     raw::Resource::DescriptorSetLayout { ref bindings, ref immutable_samplers } => {
-                        assert!(immutable_samplers.is_empty()); //TODO! requires changing the order,
-                        // since samples are expect to be all read by this point
-                        let layout = device.create_descriptor_set_layout(bindings, &[]);
+                        let samplers = immutable_samplers
+                            .iter()
+                            .map(|name| {
+                                resources.samplers
+                                    .get(name)
+                                    .expect(&format!("Missing immutable sampler: {}", name))
+                            });
+                        let layout = device.create_descriptor_set_layout(bindings, samplers);
                         let binding_indices = bindings.iter().map(|dsb| dsb.binding).collect();
                         resources.desc_set_layouts.insert(name.clone(), (binding_indices, layout));
                     }
@@ -553,7 +702,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                 }
             }
 
-            // Pass[2]: image & buffer views, descriptor sets, pipeline layouts
+            // Pass[2]: image & buffer views, pipeline layouts
             for (name, resource) in &raw.resources {
                 match *resource {
                     raw::Resource::ImageView { ref image, kind, format, swizzle, ref range } => {
@@ -562,6 +711,31 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                             .unwrap();
                         resources.image_views.insert(name.clone(), view);
                     }
+                    raw::Resource::BufferView { ref buffer, format, ref range } => {
+                        let buffer = &resources.buffers
+                            .get(buffer)
+                            .expect(&format!("Missing buffer: {}", buffer))
+                            .handle;
+                        let view = device.create_buffer_view(buffer, format, range.clone())
+                            .unwrap();
+                        resources.buffer_views.insert(name.clone(), view);
+                    }
+                    raw::Resource::PipelineLayout { ref set_layouts, ref push_constant_ranges } => {
+                        let layout = {
+                            let layouts = set_layouts
+                                .iter()
+                                .map(|sl| &resources.desc_set_layouts[sl].1);
+                            device.create_pipeline_layout(layouts, push_constant_ranges)
+                        };
+                        resources.pipeline_layouts.insert(name.clone(), layout);
+                    }
+                    _ => {}
+                }
+            }
+
+            // Pass[3]: descriptor sets, framebuffers and pipelines
+            for (name, resource) in &raw.resources {
+                match *resource {
                     raw::Resource::DescriptorSet { ref pool, ref layout, ref data } => {
                         // create a descriptor set
                         let (ref binding_indices, ref set_layout) = resources.desc_set_layouts[layout];
@@ -573,6 +747,27 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                         resources.desc_sets.insert(name.clone(), desc_set);
                         // fill it up
                         let set = &resources.desc_sets[name];
+                        let buffer = |s: &String| {
+                            &resources.buffers
+                                .get(s)
+                                .expect(&format!("Missing buffer: {}", s))
+                                .handle
+                        };
+                        let buffer_view = |s: &String| {
+                            resources.buffer_views
+                                .get(s)
+                                .expect(&format!("Missing buffer view: {}", s))
+                        };
+                        let image_view = |s: &String| {
+                            resources.image_views
+                                .get(s)
+                                .expect(&format!("Missing image view: {}", s))
+                        };
+                        let sampler = |s: &String| {
+                            resources.samplers
+                                .get(s)
+                                .expect(&format!("Missing sampler: {}", s))
+                        };
                         let writes = binding_indices
                             .iter()
                             .zip(data)
@@ -584,36 +779,53 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                                     raw::DescriptorRange::Buffers(ref names) => {
                                         names
                                             .iter()
-                                            .map(|s| {
-                                                let buf = resources.buffers
-                                                    .get(s)
-                                                    .expect(&format!("Missing buffer: {}", s));
-                                                hal::pso::Descriptor::Buffer(&buf.handle, None .. None)
+                                            .map(|s| hal::pso::Descriptor::Buffer(buffer(s), None .. None))
+                                            .collect::<Vec<_>>()
+                                    }
+                                    raw::DescriptorRange::BufferRanges(ref ranges) => {
+                                        ranges
+                                            .iter()
+                                            .map(|&(ref s, ref r)| {
+                                                hal::pso::Descriptor::Buffer(buffer(s), Some(r.start) .. Some(r.end))
                                             })
+                                            .collect()
                                     }
-                                    raw::DescriptorRange::Images(_) => {
-                                        unimplemented!()
+                                    raw::DescriptorRange::UniformTexelBuffers(ref names) => {
+                                        names
+                                            .iter()
+                                            .map(|s| hal::pso::Descriptor::UniformTexelBuffer(buffer_view(s)))
+                                            .collect()
+                                    }
+                                    raw::DescriptorRange::StorageTexelBuffers(ref names) => {
+                                        names
+                                            .iter()
+                                            .map(|s| hal::pso::Descriptor::StorageTexelBuffer(buffer_view(s)))
+                                            .collect()
+                                    }
+                                    raw::DescriptorRange::Images(ref views) => {
+                                        views
+                                            .iter()
+                                            .map(|&(ref s, layout)| hal::pso::Descriptor::Image(image_view(s), layout))
+                                            .collect()
+                                    }
+                                    raw::DescriptorRange::Samplers(ref names) => {
+                                        names
+                                            .iter()
+                                            .map(|s| hal::pso::Descriptor::Sampler(sampler(s)))
+                                            .collect()
+                                    }
+                                    raw::DescriptorRange::CombinedImageSamplers(ref pairs) => {
+                                        pairs
+                                            .iter()
+                                            .map(|&(ref view, layout, ref s)| {
+                                                hal::pso::Descriptor::CombinedImageSampler(image_view(view), layout, sampler(s))
+                                            })
+                                            .collect()
                                     }
                                 },
                             });
                         device.write_descriptor_sets(writes);
                     }
-                    raw::Resource::PipelineLayout { ref set_layouts, ref push_constant_ranges } => {
-                        let layout = {
-                            let layouts = set_layouts
-                                .iter()
-                                .map(|sl| &resources.desc_set_layouts[sl].1);
-                            device.create_pipeline_layout(layouts, push_constant_ranges)
-                        };
-                        resources.pipeline_layouts.insert(name.clone(), layout);
-                    }
-                    _ => {}
-                }
-            }
-
-            // Pass[3]: framebuffers and pipelines
-            for (name, resource) in &raw.resources {
-                match *resource {
                     raw::Resource::Framebuffer { ref pass, ref views, extent } => {
                         let rp = &resources.render_passes[pass];
                         let framebuffer = {
@@ -715,7 +927,30 @@ impl<B: hal::Backend> Scene<B, hal::General> {
         let mut jobs = HashMap::new();
         for (name, job) in &raw.jobs {
             use raw::TransferCommand as Tc;
-            let mut command_buf = command_pool.acquire_command_buffer(false);
+            let (job, queue_type, wait, signal, fence) = match *job {
+                raw::Job::Submit { queue, ref wait, ref signal, ref fence, ref job } => {
+                    (&**job, queue, wait.clone(), signal.clone(), fence.clone())
+                }
+                _ => (job, hal::QueueType::General, Vec::new(), Vec::new(), None),
+            };
+            if let raw::Job::Submit { .. } = *job {
+                return Err(err_msg(format!("Job {} submits another submission", name)));
+            }
+            if !is_supported_by(job, queue_type) {
+                return Err(err_msg(format!("Job {} can't be submitted to a {:?} queue", name, queue_type)));
+            }
+            let family = family_of(queue_type);
+            let pool = if family == queue_group.family() {
+                &mut command_pool
+            } else {
+                extra_queues
+                    .get_mut(&family)
+                    .unwrap()
+                    .command_pool
+                    .as_mut()
+                    .unwrap()
+            };
+            let mut command_buf = pool.acquire_command_buffer(false);
             match *job {
                 raw::Job::Transfer(ref command) => match *command {
                     Tc::CopyBuffer { ref src, ref dst, ref regions } => {
@@ -954,7 +1189,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                                         .expect(&format!("Missing graphics pipeline: {}", name));
                                     encoder.bind_graphics_pipeline(pso);
                                 }
-                                Dc::BindDescriptorSets { ref layout, first, ref sets } => {
+                                Dc::BindDescriptorSets { ref layout, first, ref sets, ref dynamic_offsets } => {
                                     encoder.bind_graphics_descriptor_sets(
                                         resources.pipeline_layouts
                                             .get(layout)
@@ -965,7 +1200,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                                                 .get(name)
                                                 .expect(&format!("Missing descriptor set: {}", name))
                                         }),
-                                        &[],
+                                        dynamic_offsets,
                                     );
                                 }
                                 Dc::Draw { ref vertices, ref instances } => {
@@ -974,37 +1209,117 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                                 Dc::DrawIndexed { ref indices, base_vertex, ref instances } => {
                                     encoder.draw_indexed(indices.clone(), base_vertex, instances.clone());
                                 }
+                                Dc::DrawIndirect { ref buffer, offset, draw_count, stride } => {
+                                    let buf = &resources.buffers
+                                        .get(buffer)
+                                        .expect(&format!("Missing indirect buffer: {}", buffer))
+                                        .handle;
+                                    encoder.draw_indirect(buf, offset, draw_count, stride);
+                                }
+                                Dc::DrawIndexedIndirect { ref buffer, offset, draw_count, stride } => {
+                                    let buf = &resources.buffers
+                                        .get(buffer)
+                                        .expect(&format!("Missing indirect buffer: {}", buffer))
+                                        .handle;
+                                    encoder.draw_indexed_indirect(buf, offset, draw_count, stride);
+                                }
+                                Dc::PushConstants { ref layout, stages, offset, ref data } => {
+                                    encoder.push_graphics_constants(
+                                        resources.pipeline_layouts
+                                            .get(layout)
+                                            .expect(&format!("Missing pipeline layout: {}", layout)),
+                                        stages,
+                                        offset,
+                                        data,
+                                    );
+                                }
                                 Dc::SetViewports(ref viewports) => {
                                     encoder.set_viewports(0, viewports);
                                 }
                                 Dc::SetScissors(ref scissors) => {
                                     encoder.set_scissors(0, scissors);
                                 }
+                                Dc::SetStencilReference { faces, value } => {
+                                    encoder.set_stencil_reference(faces, value);
+                                }
+                                Dc::SetBlendConstants(color) => {
+                                    encoder.set_blend_constants(color);
+                                }
+                                Dc::SetDepthBias(depth_bias) => {
+                                    encoder.set_depth_bias(depth_bias);
+                                }
                             }
                         }
                     }
                 }
-                raw::Job::Compute { ref pipeline, ref descriptor_sets, dispatch } => {
-                    let (ref layout, ref pso) = resources.compute_pipelines[pipeline];
+                raw::Job::Compute { ref pipeline, ref descriptor_sets, ref dynamic_offsets, ref push_constants, ref dispatch } => {
+                    let (ref layout_name, ref pso) = resources.compute_pipelines[pipeline];
+                    let layout = resources.pipeline_layouts
+                        .get(layout_name)
+                        .expect(&format!("Missing pipeline layout: {}", layout_name));
                     command_buf.bind_compute_pipeline(pso);
                     command_buf.bind_compute_descriptor_sets(
-                        resources.pipeline_layouts
-                            .get(layout)
-                            .expect(&format!("Missing pipeline layout: {}", layout)),
+                        layout,
                         0,
                         descriptor_sets.iter().map(|name| {
                             resources.desc_sets
                                 .get(name)
                                 .expect(&format!("Missing descriptor set: {}", name))
                         }),
-                        &[],
+                        dynamic_offsets,
                     );
-                    command_buf.dispatch(dispatch);
+                    for &(offset, ref data) in push_constants {
+                        command_buf.push_compute_constants(layout, offset, data);
+                    }
+                    match *dispatch {
+                        raw::Dispatch::Direct(count) => {
+                            command_buf.dispatch(count);
+                        }
+                        raw::Dispatch::Indirect { ref buffer, offset } => {
+                            let buf = &resources.buffers
+                                .get(buffer)
+                                .expect(&format!("Missing indirect buffer: {}", buffer))
+                                .handle;
+                            command_buf.dispatch_indirect(buf, offset);
+                        }
+                    }
                 }
+                raw::Job::OwnershipTransfer { ref resource, ref families } => {
+                    let families = Some(family_of(families.start) .. family_of(families.end));
+                    let barrier = match resources.buffers.get(resource) {
+                        Some(buffer) => memory::Barrier::Buffer {
+                            states: buffer.stable_state .. buffer.stable_state,
+                            target: &buffer.handle,
+                            families,
+                        },
+                        None => {
+                            let image = resources.images
+                                .get(resource)
+                                .expect(&format!("Missing buffer or image: {}", resource));
+                            memory::Barrier::Image {
+                                states: image.stable_state .. image.stable_state,
+                                target: &image.handle,
+                                range: image.range.clone(),
+                                families,
+                            }
+                        }
+                    };
+                    command_buf.pipeline_barrier(
+                        pso::PipelineStage::TOP_OF_PIPE .. pso::PipelineStage::BOTTOM_OF_PIPE,
+                        memory::Dependencies::empty(),
+                        &[barrier],
+                    );
+                }
+                // nested submissions are rejected above
+                raw::Job::Submit { .. } => unreachable!(),
             }
 
             jobs.insert(name.clone(), Job {
                 submission: command_buf.finish(),
+                family,
+                wait,
+                signal,
+                fence,
             });
         }
 
@@ -1016,6 +1331,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
             device,
             queue_group,
             command_pool: Some(command_pool),
+            extra_queues,
             upload_buffers,
             download_type,
             limits,
@@ -1024,24 +1340,63 @@ impl<B: hal::Backend> Scene<B, hal::General> {
 }
 
 impl<B: hal::Backend> Scene<B, hal::General> {
-    pub fn run<'a, I>(&mut self, job_names: I)
+    pub fn run<'a, I>(&mut self, job_names: I) -> Result<(), Error>
     where
         I: IntoIterator<Item = &'a str>
     {
-        let jobs = &self.jobs;
-        let submits = job_names
-            .into_iter()
-            .map(|name| {
-                &jobs
+        // the initial data has to be uploaded before the jobs start on any queue
+        let init_fence = self.device.create_fence(false);
+        let submission = hal::queue::Submission::new()
+            .submit(Some(&self.init_submit));
+        self.queue_group.queues[0].submit(submission, Some(&init_fence));
+        self.device.wait_for_fence(&init_fence, !0);
+        self.device.destroy_fence(init_fence);
+
+        let mut fences = Vec::new();
+        for name in job_names {
+            let job = self.jobs
+                .get(name)
+                .expect(&format!("Missing job: {}", name));
+            let semaphores = &self.resources.semaphores;
+            let semaphore = |name: &String| {
+                semaphores
+                    .get(name)
+                    .expect(&format!("Missing semaphore: {}", name))
+            };
+            let all_fences = &self.resources.fences;
+            let fence = job.fence.as_ref().map(|name| {
+                fences.push(name);
+                all_fences
                     .get(name)
-                    .expect(&format!("Missing job: {}", name))
-                    .submission
+                    .expect(&format!("Missing fence: {}", name))
             });
+            let submission = hal::queue::Submission::new()
+                .wait_on(job.wait.iter().map(|&(ref name, stage)| (semaphore(name), stage)))
+                .signal(job.signal.iter().map(&semaphore))
+                .submit(Some(&job.submission));
+            let queue = if job.family == self.queue_group.family() {
+                &mut self.queue_group.queues[0]
+            } else {
+                &mut self.extra_queues
+                    .get_mut(&job.family)
+                    .unwrap()
+                    .queue
+            };
+            queue.submit(submission, fence);
+        }
 
-        let submission = hal::queue::Submission::new()
-            .submit(Some(&self.init_submit))
-            .submit(submits);
-        self.queue_group.queues[0].submit(submission, None);
+        let mut result = Ok(());
+        for &name in &fences {
+            if !self.device.wait_for_fence(&self.resources.fences[name], FENCE_TIMEOUT) && result.is_ok() {
+                result = Err(err_msg(format!("Fence {} has not been signaled", name)));
+            }
+        }
+        // the results are fetched on the general queue
+        for extra in self.extra_queues.values() {
+            extra.queue.wait_idle()?;
+        }
+        self.device.reset_fences(fences.iter().map(|&name| &self.resources.fences[name]));
+        result
     }
 
     pub fn fetch_buffer(&mut self, name: &str) -> FetchGuard<B> {
@@ -1071,6 +1426,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
             let pre_barrier = memory::Barrier::Buffer {
                 states: buffer.stable_state .. b::Access::TRANSFER_READ,
                 target: &buffer.handle,
+                families: None,
             };
             cmd_buffer.pipeline_barrier(
                 pso::PipelineStage::TOP_OF_PIPE .. pso::PipelineStage::TRANSFER,
@@ -1092,6 +1448,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
             let post_barrier = memory::Barrier::Buffer {
                 states: b::Access::TRANSFER_READ .. buffer.stable_state,
                 target: &buffer.handle,
+                families: None,
             };
             cmd_buffer.pipeline_barrier(
                 pso::PipelineStage::TRANSFER .. pso::PipelineStage::BOTTOM_OF_PIPE,
@@ -1121,6 +1478,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
             mapping,
             row_pitch: down_size as _,
             width: buffer.size,
+            height: 1,
         }
     }
 
@@ -1166,6 +1524,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                 states: image.stable_state .. (i::Access::TRANSFER_READ, i::Layout::TransferSrcOptimal),
                 target: &image.handle,
                 range: COLOR_RANGE.clone(), //TODO
+                families: None,
             };
             cmd_buffer.pipeline_barrier(
                 pso::PipelineStage::TOP_OF_PIPE .. pso::PipelineStage::TRANSFER,
@@ -1200,6 +1559,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
                 states: (i::Access::TRANSFER_READ, i::Layout::TransferSrcOptimal) .. image.stable_state,
                 target: &image.handle,
                 range: COLOR_RANGE.clone(), //TODO
+                families: None,
             };
             cmd_buffer.pipeline_barrier(
                 pso::PipelineStage::TRANSFER .. pso::PipelineStage::BOTTOM_OF_PIPE,
@@ -1229,6 +1589,7 @@ impl<B: hal::Backend> Scene<B, hal::General> {
             mapping,
             row_pitch: row_pitch as _,
             width: width_bytes as _,
+            height: (height * depth as u64 / block_height as u64) as _,
         }
     }
 }
@@ -1242,5 +1603,8 @@ impl<B: hal::Backend, C> Drop for Scene<B, C> {
         //TODO: free those properly
         let _ = &self.queue_group;
         self.device.destroy_command_pool(self.command_pool.take().unwrap().into_raw());
+        for (_, mut extra) in self.extra_queues.drain() {
+            self.device.destroy_command_pool(extra.command_pool.take().unwrap().into_raw());
+        }
     }
 }
@@ -11,4 +11,6 @@ extern crate failure;
 extern crate glsl_to_spirv;
 
 pub mod gpu;
+pub mod pam;
 pub mod raw;
+pub mod report;
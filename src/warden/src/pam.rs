@@ -0,0 +1,186 @@
+//! Minimal support for the Netpbm PAM format, used for reference images.
+//!
+//! PAM stores uncompressed 8-bit tuples with an arbitrary number of channels,
+//! which maps directly to the texel layout of the fetched images and can be
+//! opened by most image viewers and editors.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use failure::{err_msg, Error};
+
+
+/// An image with `depth` 8-bit channels per pixel, stored row by row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    /// Create a black image.
+    pub fn new(width: usize, height: usize, depth: usize) -> Self {
+        Image {
+            width,
+            height,
+            depth,
+            data: vec![0; width * height * depth],
+        }
+    }
+
+    /// Get the channels of the pixel at the given position.
+    pub fn pixel(&self, x: usize, y: usize) -> &[u8] {
+        let offset = (y * self.width + x) * self.depth;
+        &self.data[offset .. offset + self.depth]
+    }
+
+    /// Get the mutable channels of the pixel at the given position.
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> &mut [u8] {
+        let offset = (y * self.width + x) * self.depth;
+        &mut self.data[offset .. offset + self.depth]
+    }
+
+    /// Load an image from a PAM file.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        let mut image = Image::new(0, 0, 0);
+        let mut pos = 0;
+        let mut magic = true;
+        loop {
+            let end = match bytes[pos ..].iter().position(|&b| b == b'\n') {
+                Some(len) => pos + len,
+                None => return Err(err_msg(format!("{:?}: unterminated PAM header", path))),
+            };
+            let line = String::from_utf8_lossy(&bytes[pos .. end]).into_owned();
+            pos = end + 1;
+
+            let mut words = line.split_whitespace();
+            let (key, value) = match words.next() {
+                Some(key) if key.starts_with('#') => continue,
+                Some(key) => (key, words.next()),
+                None => continue,
+            };
+            if magic {
+                if key != "P7" {
+                    return Err(err_msg(format!("{:?}: not a PAM image", path)));
+                }
+                magic = false;
+                continue;
+            }
+            let number = || -> Result<usize, Error> {
+                value
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| err_msg(format!("{:?}: invalid PAM header line {:?}", path, line)))
+            };
+            match key {
+                "WIDTH" => image.width = number()?,
+                "HEIGHT" => image.height = number()?,
+                "DEPTH" => image.depth = number()?,
+                "MAXVAL" => if number()? != 255 {
+                    return Err(err_msg(format!("{:?}: only 8-bit channels are supported", path)));
+                },
+                "TUPLTYPE" => {}
+                "ENDHDR" => break,
+                _ => return Err(err_msg(format!("{:?}: unknown PAM header {:?}", path, key))),
+            }
+        }
+
+        let size = image.width * image.height * image.depth;
+        if bytes.len() - pos != size {
+            return Err(err_msg(format!(
+                "{:?}: expected {} bytes of data, found {}", path, size, bytes.len() - pos
+            )));
+        }
+        image.data = bytes[pos ..].to_vec();
+        Ok(image)
+    }
+
+    /// Save the image to a PAM file.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let tuple_type = match self.depth {
+            1 => "GRAYSCALE",
+            2 => "GRAYSCALE_ALPHA",
+            3 => "RGB",
+            _ => "RGB_ALPHA",
+        };
+        let mut file = File::create(path)?;
+        write!(
+            file,
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH {}\nMAXVAL 255\nTUPLTYPE {}\nENDHDR\n",
+            self.width, self.height, self.depth, tuple_type,
+        )?;
+        file.write_all(&self.data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use std::path::PathBuf;
+    use std::process;
+
+    use super::Image;
+
+    fn temp_path(name: &str) -> PathBuf {
+        ::std::env::temp_dir().join(format!("warden-{}-{}.pam", process::id(), name))
+    }
+
+    fn load_bytes(name: &str, bytes: &[u8]) -> Result<Image, String> {
+        let path = temp_path(name);
+        File::create(&path).unwrap().write_all(bytes).unwrap();
+        let result = Image::load(&path).map_err(|e| e.to_string());
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn save_and_load() {
+        for &(depth, tuple_type) in &[(1, "GRAYSCALE"), (2, "GRAYSCALE_ALPHA"), (3, "RGB"), (4, "RGB_ALPHA")] {
+            let mut image = Image::new(3, 2, depth);
+            for (i, byte) in image.data.iter_mut().enumerate() {
+                *byte = i as u8 * 10;
+            }
+            let path = temp_path(tuple_type);
+            image.save(&path).unwrap();
+
+            let mut bytes = Vec::new();
+            File::open(&path).unwrap().read_to_end(&mut bytes).unwrap();
+            let header = format!("TUPLTYPE {}\nENDHDR\n", tuple_type);
+            assert!(bytes.windows(header.len()).any(|w| w == header.as_bytes()));
+
+            assert_eq!(Image::load(&path).unwrap(), image);
+            fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn load_header() {
+        let bytes = b"P7\n# reference\nWIDTH 2\nHEIGHT 1\n\nDEPTH 2\nMAXVAL 255\nTUPLTYPE ANYTHING\nENDHDR\n\x01\x02\x03\x04";
+        let image = load_bytes("header", bytes).unwrap();
+        assert_eq!((image.width, image.height, image.depth), (2, 1, 2));
+        assert_eq!(image.pixel(1, 0), &[3, 4]);
+    }
+
+    #[test]
+    fn load_invalid() {
+        let cases: &[(&str, &[u8], &str)] = &[
+            ("magic", b"P6\n1 1\n255\n\0\0\0", "not a PAM image"),
+            ("maxval", b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 1\nMAXVAL 65535\nENDHDR\n\0\0", "only 8-bit channels"),
+            ("number", b"P7\nWIDTH one\nENDHDR\n", "invalid PAM header line"),
+            ("unknown", b"P7\nCOLORS 3\nENDHDR\n", "unknown PAM header"),
+            ("unterminated", b"P7\nWIDTH 1", "unterminated PAM header"),
+            ("size", b"P7\nWIDTH 2\nHEIGHT 1\nDEPTH 1\nMAXVAL 255\nENDHDR\n\0", "expected 2 bytes of data, found 1"),
+        ];
+        for &(name, bytes, message) in cases {
+            let error = load_bytes(name, bytes).unwrap_err();
+            assert!(error.contains(message), "{}: {}", name, error);
+        }
+    }
+}
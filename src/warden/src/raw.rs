@@ -61,6 +61,11 @@ pub enum Resource {
         #[serde(default)]
         data: String,
     },
+    BufferView {
+        buffer: String,
+        format: Option<hal::format::Format>,
+        range: Range<hal::buffer::Offset>,
+    },
     ImageView {
         image: String,
         kind: hal::image::ViewKind,
@@ -74,6 +79,7 @@ pub enum Resource {
         subpasses: HashMap<String, Subpass>,
         dependencies: Vec<SubpassDependency>,
     },
+    Sampler(hal::image::SamplerInfo),
     Shader(String),
     DescriptorSetLayout {
         bindings: Vec<hal::pso::DescriptorSetLayoutBinding>,
@@ -116,6 +122,8 @@ pub enum Resource {
         views: HashMap<String, String>,
         extent: hal::image::Extent,
     },
+    Semaphore,
+    Fence,
 }
 
 #[derive(Debug, Deserialize)]
@@ -163,7 +171,12 @@ pub enum TransferCommand {
 #[derive(Clone, Debug, Deserialize)]
 pub enum DescriptorRange {
     Buffers(Vec<String>),
-    Images(Vec<String>),
+    BufferRanges(Vec<(String, Range<hal::buffer::Offset>)>),
+    UniformTexelBuffers(Vec<String>),
+    StorageTexelBuffers(Vec<String>),
+    Images(Vec<(String, hal::image::Layout)>),
+    Samplers(Vec<String>),
+    CombinedImageSamplers(Vec<(String, hal::image::Layout, String)>),
 }
 
 fn default_instance_range() -> Range<hal::InstanceCount> {
@@ -183,6 +196,8 @@ pub enum DrawCommand {
         layout: String,
         first: usize,
         sets: Vec<String>,
+        #[serde(default)]
+        dynamic_offsets: Vec<hal::command::DescriptorSetOffset>,
     },
     Draw {
         vertices: Range<hal::VertexCount>,
@@ -194,8 +209,32 @@ pub enum DrawCommand {
         base_vertex: hal::VertexOffset,
         instances: Range<hal::InstanceCount>,
     },
+    DrawIndirect {
+        buffer: String,
+        offset: hal::buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
+    },
+    DrawIndexedIndirect {
+        buffer: String,
+        offset: hal::buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: u32,
+    },
+    PushConstants {
+        layout: String,
+        stages: hal::pso::ShaderStageFlags,
+        offset: u32,
+        data: Vec<u32>,
+    },
     SetViewports(Vec<hal::pso::Viewport>),
     SetScissors(Vec<hal::pso::Rect>),
+    SetStencilReference {
+        faces: hal::pso::Face,
+        value: hal::pso::StencilValue,
+    },
+    SetBlendConstants(hal::pso::ColorValue),
+    SetDepthBias(hal::pso::DepthBias),
 }
 
 #[derive(Debug, Deserialize)]
@@ -203,6 +242,15 @@ pub struct DrawPass {
     pub commands: Vec<DrawCommand>,
 }
 
+#[derive(Debug, Deserialize)]
+pub enum Dispatch {
+    Direct(hal::WorkGroupCount),
+    Indirect {
+        buffer: String,
+        offset: hal::buffer::Offset,
+    },
+}
+
 #[derive(Debug, Deserialize)]
 pub enum Job {
     Transfer(TransferCommand),
@@ -214,7 +262,25 @@ pub enum Job {
     Compute {
         pipeline: String,
         descriptor_sets: Vec<String>,
-        dispatch: hal::WorkGroupCount,
+        #[serde(default)]
+        dynamic_offsets: Vec<hal::command::DescriptorSetOffset>,
+        #[serde(default)]
+        push_constants: Vec<(u32, Vec<u32>)>,
+        dispatch: Dispatch,
+    },
+    OwnershipTransfer {
+        resource: String,
+        families: Range<hal::QueueType>,
+    },
+    Submit {
+        queue: hal::QueueType,
+        #[serde(default)]
+        wait: Vec<(String, hal::pso::PipelineStage)>,
+        #[serde(default)]
+        signal: Vec<String>,
+        #[serde(default)]
+        fence: Option<String>,
+        job: Box<Job>,
     },
 }
 
@@ -0,0 +1,188 @@
+//! Machine readable reports of the test results, for CI dashboards.
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+
+/// Outcome of a single test.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Pass,
+    Skip(String),
+    Fail(String),
+}
+
+/// Result of a test run on a given backend.
+#[derive(Clone, Debug)]
+pub struct TestResult {
+    pub backend: String,
+    pub group: String,
+    pub name: String,
+    pub outcome: Outcome,
+    pub duration: Duration,
+}
+
+impl TestResult {
+    /// Full name of the test, unique across backends.
+    pub fn full_name(&self) -> String {
+        format!("{}/{}/{}", self.backend, self.group, self.name)
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+/// Write the results in the JUnit XML format, with one test suite per
+/// backend and scene.
+pub fn write_junit<W: Write>(mut out: W, results: &[TestResult]) -> io::Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, "<testsuites>")?;
+
+    let mut start = 0;
+    while start < results.len() {
+        let (backend, group) = (&results[start].backend, &results[start].group);
+        let count = results[start ..]
+            .iter()
+            .take_while(|r| &r.backend == backend && &r.group == group)
+            .count();
+        let suite = &results[start .. start + count];
+        start += count;
+
+        let failures = suite.iter().filter(|r| match r.outcome { Outcome::Fail(_) => true, _ => false }).count();
+        let skipped = suite.iter().filter(|r| match r.outcome { Outcome::Skip(_) => true, _ => false }).count();
+        let time = suite.iter().map(|r| seconds(r.duration)).sum::<f64>();
+        let suite_name = escape_xml(&format!("{}.{}", backend, group));
+        writeln!(
+            out,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" skipped="{}" time="{:.3}">"#,
+            suite_name, suite.len(), failures, skipped, time,
+        )?;
+        for result in suite {
+            write!(
+                out,
+                r#"    <testcase classname="{}" name="{}" time="{:.3}""#,
+                suite_name, escape_xml(&result.name), seconds(result.duration),
+            )?;
+            match result.outcome {
+                Outcome::Pass => writeln!(out, "/>")?,
+                Outcome::Skip(ref reason) => {
+                    writeln!(out, ">")?;
+                    writeln!(out, r#"      <skipped message="{}"/>"#, escape_xml(reason))?;
+                    writeln!(out, "    </testcase>")?;
+                }
+                Outcome::Fail(ref message) => {
+                    writeln!(out, ">")?;
+                    writeln!(out, r#"      <failure message="{}"/>"#, escape_xml(message))?;
+                    writeln!(out, "    </testcase>")?;
+                }
+            }
+        }
+        writeln!(out, "  </testsuite>")?;
+    }
+
+    writeln!(out, "</testsuites>")
+}
+
+/// Write the results in the TAP version 13 format.
+pub fn write_tap<W: Write>(mut out: W, results: &[TestResult]) -> io::Result<()> {
+    writeln!(out, "TAP version 13")?;
+    writeln!(out, "1..{}", results.len())?;
+    for (i, result) in results.iter().enumerate() {
+        let name = result.full_name();
+        match result.outcome {
+            Outcome::Pass => writeln!(out, "ok {} - {}", i + 1, name)?,
+            Outcome::Skip(ref reason) => writeln!(out, "ok {} - {} # SKIP {}", i + 1, name, reason)?,
+            Outcome::Fail(ref message) => {
+                writeln!(out, "not ok {} - {}", i + 1, name)?;
+                writeln!(out, "  ---")?;
+                writeln!(out, "  message: {:?}", message)?;
+                writeln!(out, "  ...")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{write_junit, write_tap, Outcome, TestResult};
+
+    fn result(backend: &str, group: &str, name: &str, outcome: Outcome) -> TestResult {
+        TestResult {
+            backend: backend.to_string(),
+            group: group.to_string(),
+            name: name.to_string(),
+            outcome,
+            duration: Duration::from_millis(250),
+        }
+    }
+
+    fn results() -> Vec<TestResult> {
+        vec![
+            result("gl", "basic", "pass", Outcome::Pass),
+            result("gl", "basic", "skip", Outcome::Skip("features missing: <none>".to_string())),
+            result("vulkan", "basic", "a&b", Outcome::Fail(r#"row 0: "1" != '2'"#.to_string())),
+        ]
+    }
+
+    #[test]
+    fn junit() {
+        let mut out = Vec::new();
+        write_junit(&mut out, &results()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="gl.basic" tests="2" failures="0" skipped="1" time="0.500">
+    <testcase classname="gl.basic" name="pass" time="0.250"/>
+    <testcase classname="gl.basic" name="skip" time="0.250">
+      <skipped message="features missing: &lt;none&gt;"/>
+    </testcase>
+  </testsuite>
+  <testsuite name="vulkan.basic" tests="1" failures="1" skipped="0" time="0.250">
+    <testcase classname="vulkan.basic" name="a&amp;b" time="0.250">
+      <failure message="row 0: &quot;1&quot; != &apos;2&apos;"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#);
+    }
+
+    #[test]
+    fn junit_empty() {
+        let mut out = Vec::new();
+        write_junit(&mut out, &[]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n</testsuites>\n");
+    }
+
+    #[test]
+    fn tap() {
+        let mut out = Vec::new();
+        write_tap(&mut out, &results()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), r#"TAP version 13
+1..3
+ok 1 - gl/basic/pass
+ok 2 - gl/basic/skip # SKIP features missing: <none>
+not ok 3 - vulkan/basic/a&b
+  ---
+  message: "row 0: \"1\" != '2'"
+  ...
+"#);
+    }
+}